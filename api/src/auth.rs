@@ -14,10 +14,11 @@
 // limitations under the License.
 
 use crate::router::{Handler, HandlerObj, ResponseFuture};
+use crate::util::to_base64;
 use crate::web::response;
 use futures::future::ok;
 use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::{Body, Method, Request, Response, StatusCode};
 use ring::constant_time::verify_slices_are_equal;
 
 lazy_static! {
@@ -32,6 +33,7 @@ pub struct BasicAuthMiddleware {
 	api_basic_auth: String,
 	basic_realm: &'static HeaderValue,
 	ignore_uri: Option<String>,
+	ignore_prefixes: Vec<String>,
 }
 
 impl BasicAuthMiddleware {
@@ -44,8 +46,16 @@ impl BasicAuthMiddleware {
 			api_basic_auth,
 			basic_realm,
 			ignore_uri,
+			ignore_prefixes: vec![],
 		}
 	}
+
+	/// Skip the auth for all paths that start with the prefix. Used for the routes
+	/// that are doing their own auth, like the wallet proxy.
+	pub fn with_ignore_prefix(mut self, prefix: String) -> BasicAuthMiddleware {
+		self.ignore_prefixes.push(prefix);
+		self
+	}
 }
 
 impl Handler for BasicAuthMiddleware {
@@ -66,6 +76,13 @@ impl Handler for BasicAuthMiddleware {
 				return next_handler.call(req, handlers);
 			}
 		}
+		if self
+			.ignore_prefixes
+			.iter()
+			.any(|prefix| req.uri().path().starts_with(prefix.as_str()))
+		{
+			return next_handler.call(req, handlers);
+		}
 		if req.headers().contains_key(AUTHORIZATION)
 			&& verify_slices_are_equal(
 				req.headers()[AUTHORIZATION].as_bytes(),
@@ -134,6 +151,239 @@ impl Handler for BasicAuthURIMiddleware {
 	}
 }
 
+/// Operations that the API key is allowed to call. Every scope includes the lower ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+	/// Read endpoints (GET requests)
+	ReadOnly,
+	/// Read endpoints and the transactions push
+	PoolPush,
+	/// Everything: owner API, peers ban/unban, compaction, chain validation, txhashset reads,
+	/// node shutdown, stratum and mining
+	Owner,
+}
+
+impl ApiScope {
+	/// Scope that is required for the request
+	pub fn required(method: &Method, path: &str) -> ApiScope {
+		if OWNER_PREFIXES.iter().any(|p| path.starts_with(p)) {
+			ApiScope::Owner
+		} else if path == "/v1/pool/push_tx" {
+			ApiScope::PoolPush
+		} else if *method == Method::GET || *method == Method::HEAD {
+			ApiScope::ReadOnly
+		} else {
+			ApiScope::Owner
+		}
+	}
+}
+
+/// Owner endpoints that require the owner scope for any method. The chain validation and
+/// the txhashset reads are GET requests, but they are expensive enough to be owner only.
+const OWNER_PREFIXES: &[&str] = &[
+	"/v2/owner",
+	"/v2/stratum",
+	"/v2/mining",
+	"/v1/chain/validate",
+	"/v1/txhashset",
+];
+
+/// API key configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiTokenConfig {
+	/// Key name for the logs
+	pub name: String,
+	/// File with the key, the first line is used. Same format as `api_secret_path`
+	pub token_path: String,
+	/// Operations allowed with this key
+	pub scope: ApiScope,
+}
+
+/// API keys with their scopes
+#[derive(Clone, Default)]
+pub struct ApiTokenStore {
+	tokens: Vec<(String, String, ApiScope)>,
+}
+
+impl ApiTokenStore {
+	pub fn new() -> ApiTokenStore {
+		ApiTokenStore { tokens: vec![] }
+	}
+
+	/// Add the key. The key is accepted as the basic auth password or as the bearer token.
+	pub fn add(&mut self, name: String, token: String, scope: ApiScope) {
+		self.tokens.push((name, token, scope));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tokens.is_empty()
+	}
+
+	/// Accepted Authorization header values: key name, header, scope
+	pub fn auth_headers(&self, basic_auth_user: &str) -> Vec<(String, String, ApiScope)> {
+		let mut auth_headers = vec![];
+		for (name, token, scope) in &self.tokens {
+			let basic = format!(
+				"Basic {}",
+				to_base64(&format!("{}:{}", basic_auth_user, token))
+			);
+			auth_headers.push((name.clone(), basic, *scope));
+			auth_headers.push((name.clone(), format!("Bearer {}", token), *scope));
+		}
+		auth_headers
+	}
+}
+
+// Scoped API keys Authentication Middleware
+pub struct TokenAuthMiddleware {
+	/// Accepted Authorization header values: key name, header, scope
+	auth_headers: Vec<(String, String, ApiScope)>,
+	basic_realm: &'static HeaderValue,
+	ignore_uri: Option<String>,
+	ignore_prefixes: Vec<String>,
+}
+
+impl TokenAuthMiddleware {
+	pub fn new(
+		tokens: &ApiTokenStore,
+		basic_auth_user: &str,
+		basic_realm: &'static HeaderValue,
+		ignore_uri: Option<String>,
+	) -> TokenAuthMiddleware {
+		TokenAuthMiddleware {
+			auth_headers: tokens.auth_headers(basic_auth_user),
+			basic_realm,
+			ignore_uri,
+			ignore_prefixes: vec![],
+		}
+	}
+
+	/// Skip the auth for all paths that start with the prefix. Used for the routes
+	/// that are doing their own auth, like the wallet proxy.
+	pub fn with_ignore_prefix(mut self, prefix: String) -> TokenAuthMiddleware {
+		self.ignore_prefixes.push(prefix);
+		self
+	}
+
+	/// Scope of the key from the Authorization header
+	fn scope(&self, auth: &HeaderValue) -> Option<(&str, ApiScope)> {
+		// every key is checked, so the timing doesn't leak which one matched
+		let mut res = None;
+		for (name, header, scope) in &self.auth_headers {
+			if verify_slices_are_equal(auth.as_bytes(), header.as_bytes()).is_ok() {
+				res = Some((name.as_str(), *scope));
+			}
+		}
+		res
+	}
+}
+
+impl Handler for TokenAuthMiddleware {
+	fn call(
+		&self,
+		req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		if req.method().as_str() == "OPTIONS" {
+			return next_handler.call(req, handlers);
+		}
+		if let Some(u) = self.ignore_uri.as_ref() {
+			if req.uri().path() == u {
+				return next_handler.call(req, handlers);
+			}
+		}
+		if self
+			.ignore_prefixes
+			.iter()
+			.any(|prefix| req.uri().path().starts_with(prefix.as_str()))
+		{
+			return next_handler.call(req, handlers);
+		}
+		match req.headers().get(AUTHORIZATION).and_then(|a| self.scope(a)) {
+			Some((name, scope)) => {
+				let required = ApiScope::required(req.method(), req.uri().path());
+				if scope >= required {
+					next_handler.call(req, handlers)
+				} else {
+					debug!(
+						"API key {} with scope {:?} is not allowed to call {} {}",
+						name,
+						scope,
+						req.method(),
+						req.uri().path()
+					);
+					response(StatusCode::FORBIDDEN, "API key scope is not sufficient")
+				}
+			}
+			// Unauthorized 401
+			None => unauthorized_response(&self.basic_realm),
+		}
+	}
+}
+
+/// Request extension, the connection has a client certificate verified with the
+/// client CA from the TLS config
+#[derive(Debug, Clone, Copy)]
+pub struct TlsClientCert;
+
+// Client certificate Middleware. Requests to the owner scoped routes, the same ones that
+// require the owner API key, are rejected if the TLS connection doesn't have a verified
+// client certificate.
+pub struct ClientCertMiddleware {
+	ignore_uri: Option<String>,
+	ignore_prefixes: Vec<String>,
+}
+
+impl ClientCertMiddleware {
+	pub fn new(ignore_uri: Option<String>) -> ClientCertMiddleware {
+		ClientCertMiddleware {
+			ignore_uri,
+			ignore_prefixes: vec![],
+		}
+	}
+
+	/// Skip the check for all paths that start with the prefix, like the routes that are
+	/// skipped by `TokenAuthMiddleware`.
+	pub fn with_ignore_prefix(mut self, prefix: String) -> ClientCertMiddleware {
+		self.ignore_prefixes.push(prefix);
+		self
+	}
+
+	fn is_required(&self, method: &Method, path: &str) -> bool {
+		self.ignore_uri.as_deref() != Some(path)
+			&& !self
+				.ignore_prefixes
+				.iter()
+				.any(|prefix| path.starts_with(prefix.as_str()))
+			&& ApiScope::required(method, path) == ApiScope::Owner
+	}
+}
+
+impl Handler for ClientCertMiddleware {
+	fn call(
+		&self,
+		req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		if req.method().as_str() == "OPTIONS"
+			|| req.extensions().get::<TlsClientCert>().is_some()
+			|| !self.is_required(req.method(), req.uri().path())
+		{
+			return next_handler.call(req, handlers);
+		}
+		response(StatusCode::FORBIDDEN, "client certificate is required")
+	}
+}
+
 fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
 	let response = Response::builder()
 		.status(StatusCode::UNAUTHORIZED)
@@ -142,3 +392,99 @@ fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
 		.unwrap();
 	Box::pin(ok(response))
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn api_scopes() {
+		let get = Method::GET;
+		let post = Method::POST;
+		assert_eq!(ApiScope::required(&get, "/v1/chain"), ApiScope::ReadOnly);
+		assert_eq!(ApiScope::required(&get, "/v1/pool"), ApiScope::ReadOnly);
+		assert_eq!(
+			ApiScope::required(&post, "/v1/pool/push_tx"),
+			ApiScope::PoolPush
+		);
+		assert_eq!(
+			ApiScope::required(&post, "/v1/peers/10.0.0.1:3414/ban"),
+			ApiScope::Owner
+		);
+		assert_eq!(
+			ApiScope::required(&post, "/v1/chain/compact"),
+			ApiScope::Owner
+		);
+		assert_eq!(ApiScope::required(&get, "/v2/owner"), ApiScope::Owner);
+		assert_eq!(ApiScope::required(&post, "/v2/mining"), ApiScope::Owner);
+		assert!(ApiScope::Owner > ApiScope::PoolPush);
+		assert!(ApiScope::PoolPush > ApiScope::ReadOnly);
+
+		let mut tokens = ApiTokenStore::new();
+		tokens.add("explorer".into(), "read".into(), ApiScope::ReadOnly);
+		tokens.add("admin".into(), "owner".into(), ApiScope::Owner);
+		let auth = TokenAuthMiddleware::new(&tokens, "mwc", &MWC_BASIC_REALM, None);
+		let basic = HeaderValue::from_str(&format!("Basic {}", to_base64("mwc:read"))).unwrap();
+		assert_eq!(auth.scope(&basic), Some(("explorer", ApiScope::ReadOnly)));
+		let bearer = HeaderValue::from_static("Bearer owner");
+		assert_eq!(auth.scope(&bearer), Some(("admin", ApiScope::Owner)));
+		let wrong = HeaderValue::from_static("Bearer other");
+		assert_eq!(auth.scope(&wrong), None);
+	}
+
+	#[test]
+	fn chain_validate_requires_owner() {
+		assert_eq!(
+			ApiScope::required(&Method::GET, "/v1/chain/validate"),
+			ApiScope::Owner
+		);
+		assert_eq!(
+			ApiScope::required(&Method::HEAD, "/v1/chain/validate"),
+			ApiScope::Owner
+		);
+		// other chain reads stay read only
+		assert_eq!(
+			ApiScope::required(&Method::GET, "/v1/chain/outputs/byheight?start_height=1"),
+			ApiScope::ReadOnly
+		);
+	}
+
+	#[test]
+	fn txhashset_requires_owner() {
+		for path in &[
+			"/v1/txhashset/roots",
+			"/v1/txhashset/lastoutputs",
+			"/v1/txhashset/lastrangeproofs",
+			"/v1/txhashset/lastkernels",
+			"/v1/txhashset/outputs",
+			"/v1/txhashset/merkleproof",
+		] {
+			assert_eq!(ApiScope::required(&Method::GET, path), ApiScope::Owner);
+		}
+	}
+
+	#[test]
+	fn client_cert_owner_routes() {
+		let middleware = ClientCertMiddleware::new(Some("/v2/foreign".into()))
+			.with_ignore_prefix("/v2/health".into());
+		for (method, path) in &[
+			(Method::POST, "/v2/owner"),
+			(Method::POST, "/v2/stratum"),
+			(Method::GET, "/v2/mining"),
+			(Method::GET, "/v1/chain/validate"),
+			(Method::GET, "/v1/txhashset/roots"),
+			(Method::POST, "/v1/peers/10.0.0.1:3414/ban"),
+			(Method::POST, "/v1/chain/compact"),
+		] {
+			assert!(middleware.is_required(method, path), "{} {}", method, path);
+		}
+		for (method, path) in &[
+			(Method::GET, "/v1/chain"),
+			(Method::POST, "/v1/pool/push_tx"),
+			(Method::POST, "/v2/foreign"),
+			(Method::GET, "/v2/health"),
+		] {
+			assert!(!middleware.is_required(method, path), "{} {}", method, path);
+		}
+	}
+}
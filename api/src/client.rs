@@ -16,16 +16,158 @@
 //! High level JSON/HTTP client API
 
 use crate::core::global;
+use crate::proxy::{Proxy, ProxyConnector};
 use crate::rest::Error;
-use crate::util::to_base64;
+use crate::util::{to_base64, Mutex, RwLock};
+use futures::Future;
 use http::uri::Uri;
 use hyper::body;
 use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
-use hyper::{Body, Client, Request};
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::HttpsConnector;
 use hyper_timeout::TimeoutConnector;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::runtime::Builder;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// Shared HTTP client settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HttpClientConfig {
+	/// Idle connections that are kept open per host. Default: 8
+	pub pool_max_idle_per_host: usize,
+	/// Idle connections are closed after that, in seconds. Default: 90
+	pub pool_idle_timeout_secs: u64,
+	/// Worker threads of the runtime that runs the blocking calls. Default: 2
+	pub runtime_threads: usize,
+	/// Proxy of the outgoing calls, like `socks5://127.0.0.1:9050` for Tor or
+	/// `http://proxy:3128`. The .onion hosts are reachable through the socks5 proxy only.
+	#[serde(default)]
+	pub proxy: Option<String>,
+}
+
+impl Default for HttpClientConfig {
+	fn default() -> HttpClientConfig {
+		HttpClientConfig {
+			pool_max_idle_per_host: 8,
+			pool_idle_timeout_secs: 90,
+			runtime_threads: 2,
+			proxy: None,
+		}
+	}
+}
+
+type HttpsClient = Client<TimeoutConnector<HttpsConnector<ProxyConnector>>, Body>;
+
+/// Connections are spawned on the shared runtime, so the pooled connections outlive
+/// the runtime of the caller
+#[derive(Clone)]
+struct RuntimeExecutor(Handle);
+
+impl<F> hyper::rt::Executor<F> for RuntimeExecutor
+where
+	F: Future + Send + 'static,
+	F::Output: Send + 'static,
+{
+	fn execute(&self, fut: F) {
+		self.0.spawn(fut);
+	}
+}
+
+/// Runtime for the blocking calls and the pooled clients. The timeouts are the connector
+/// settings, so there is a client per timeouts.
+struct SharedClient {
+	runtime: Runtime,
+	config: HttpClientConfig,
+	proxy: Option<Proxy>,
+	clients: Mutex<HashMap<(Duration, Duration, Duration), HttpsClient>>,
+}
+
+impl SharedClient {
+	fn new(config: HttpClientConfig) -> Result<SharedClient, Error> {
+		let proxy = match &config.proxy {
+			Some(proxy) => Some(Proxy::parse(proxy).map_err(Error::Argument)?),
+			None => None,
+		};
+		let runtime = Builder::new_multi_thread()
+			.worker_threads(config.runtime_threads.max(1))
+			.thread_name("http-client")
+			.enable_all()
+			.build()
+			.map_err(|e| Error::Internal(format!("can't create Tokio runtime, {}", e)))?;
+		Ok(SharedClient {
+			runtime,
+			config,
+			proxy,
+			clients: Mutex::new(HashMap::new()),
+		})
+	}
+
+	fn client(&self, timeout: &TimeOut) -> HttpsClient {
+		let key = (timeout.connect, timeout.read, timeout.write);
+		let mut clients = self.clients.lock();
+		if let Some(client) = clients.get(&key) {
+			return client.clone();
+		}
+		let https = hyper_rustls::HttpsConnectorBuilder::new()
+			.with_native_roots()
+			.https_or_http()
+			.enable_http1()
+			.wrap_connector(ProxyConnector::new(self.proxy.clone()));
+		let mut connector = TimeoutConnector::new(https);
+		connector.set_connect_timeout(Some(timeout.connect));
+		connector.set_read_timeout(Some(timeout.read));
+		connector.set_write_timeout(Some(timeout.write));
+		let client = Client::builder()
+			.executor(RuntimeExecutor(self.runtime.handle().clone()))
+			.pool_max_idle_per_host(self.config.pool_max_idle_per_host)
+			.pool_idle_timeout(Duration::from_secs(self.config.pool_idle_timeout_secs))
+			.build::<_, Body>(connector);
+		clients.insert(key, client.clone());
+		client
+	}
+}
+
+lazy_static! {
+	/// Created by `init_http_client` or with the default settings by the first request
+	static ref SHARED_CLIENT: RwLock<Option<Arc<SharedClient>>> = RwLock::new(None);
+}
+
+/// Create the shared client with the settings. Must be called before the first request,
+/// the settings are ignored after that. The error is returned if the proxy is invalid or
+/// the runtime can't be created, so the node fails at the start.
+pub fn init_http_client(config: HttpClientConfig) -> Result<(), Error> {
+	let mut shared = SHARED_CLIENT.write();
+	if shared.is_some() {
+		warn!(
+			"HTTP client is already in use, the pool settings {:?} are ignored",
+			config
+		);
+		return Ok(());
+	}
+	*shared = Some(Arc::new(SharedClient::new(config)?));
+	Ok(())
+}
+
+/// The shared client, created with the default settings if it is not initialized. The
+/// failed creation is not stored, it is retried by the next request.
+fn shared_client() -> Result<Arc<SharedClient>, Error> {
+	if let Some(client) = SHARED_CLIENT.read().as_ref() {
+		return Ok(client.clone());
+	}
+	let mut shared = SHARED_CLIENT.write();
+	if let Some(client) = shared.as_ref() {
+		return Ok(client.clone());
+	}
+	let client = Arc::new(SharedClient::new(HttpClientConfig::default()).map_err(|e| {
+		error!("Unable to create the HTTP client, {}", e);
+		e
+	})?);
+	*shared = Some(client.clone());
+	Ok(client)
+}
 
 // Client Request Timeout
 pub struct TimeOut {
@@ -54,30 +196,104 @@ impl Default for TimeOut {
 	}
 }
 
+/// Backoff delay is not growing above that
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Failures that are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOn {
+	/// Connect errors and timeouts, the request was not sent
+	Connect,
+	/// Connect errors and the server errors (5xx responses). The server errors are
+	/// retried for the idempotent (GET) requests only.
+	ConnectAndServerError,
+}
+
+// Request failure, for the retry decision
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Failure {
+	Connect,
+	ServerError,
+	Other,
+}
+
+/// Retry policy of the request
+#[derive(Debug, Clone)]
+pub struct RequestPolicy {
+	/// Retries after the first attempt. Default: 0, not retried
+	pub retries: u32,
+	/// Delay before the first retry, doubled for every next one. The delay is
+	/// randomized down to the half of it, so the clients don't retry all together.
+	pub backoff: Duration,
+	pub retry_on: RetryOn,
+}
+
+impl Default for RequestPolicy {
+	fn default() -> RequestPolicy {
+		RequestPolicy {
+			retries: 0,
+			backoff: Duration::from_millis(500),
+			retry_on: RetryOn::ConnectAndServerError,
+		}
+	}
+}
+
+impl RequestPolicy {
+	/// Retry the connect and the server errors
+	pub fn retry(retries: u32, backoff: Duration) -> RequestPolicy {
+		RequestPolicy {
+			retries,
+			backoff,
+			retry_on: RetryOn::ConnectAndServerError,
+		}
+	}
+
+	fn is_retried(&self, failure: Failure, idempotent: bool) -> bool {
+		match failure {
+			Failure::Connect => true,
+			Failure::ServerError => idempotent && self.retry_on == RetryOn::ConnectAndServerError,
+			Failure::Other => false,
+		}
+	}
+
+	fn delay(&self, attempt: u32) -> Duration {
+		let delay = self
+			.backoff
+			.checked_mul(1 << attempt.min(16))
+			.unwrap_or(MAX_BACKOFF)
+			.min(MAX_BACKOFF);
+		let half_ms = delay.as_millis() as u64 / 2;
+		Duration::from_millis(half_ms + rand::thread_rng().gen_range(0, half_ms + 1))
+	}
+}
+
 /// Helper function to easily issue a HTTP GET request against a given URL that
 /// returns a JSON object. Handles request building, JSON deserialization and
 /// response code checking.
-/// This function spawns a new Tokio runtime, which means it is pretty inefficient for multiple
-/// requests. In those situations you are probably better off creating a runtime once and spawning
-/// `get_async` tasks on it
-pub fn get<T>(url: &str, api_secret: Option<String>) -> Result<T, Error>
+/// The request is run on the shared runtime, the connections are pooled between the calls.
+pub fn get<T>(url: &str, api_secret: Option<String>, policy: RequestPolicy) -> Result<T, Error>
 where
 	for<'de> T: Deserialize<'de>,
 {
 	handle_request(
 		build_request(url, "GET", api_secret, None)?,
 		TimeOut::default(),
+		policy,
 	)
 }
 
 /// Helper function to easily issue an async HTTP GET request against a given
 /// URL that returns a future. Handles request building, JSON deserialization
 /// and response code checking.
-pub async fn get_async<T>(url: &str, api_secret: Option<String>) -> Result<T, Error>
+pub async fn get_async<T>(
+	url: &str,
+	api_secret: Option<String>,
+	policy: RequestPolicy,
+) -> Result<T, Error>
 where
 	for<'de> T: Deserialize<'de> + Send + 'static,
 {
-	handle_request_async(build_request(url, "GET", api_secret, None)?).await
+	handle_request_async(build_request(url, "GET", api_secret, None)?, policy).await
 }
 
 /// Helper function to easily issue a HTTP GET request
@@ -100,13 +316,14 @@ pub fn post<IN, OUT>(
 	api_secret: Option<String>,
 	input: &IN,
 	timeout: TimeOut,
+	policy: RequestPolicy,
 ) -> Result<OUT, Error>
 where
 	IN: Serialize,
 	for<'de> OUT: Deserialize<'de>,
 {
 	let req = create_post_request(url, api_secret, input)?;
-	handle_request(req, timeout)
+	handle_request(req, timeout, policy)
 }
 
 /// Helper function to easily issue an async HTTP POST request with the
@@ -117,13 +334,14 @@ pub async fn post_async<IN, OUT>(
 	url: &str,
 	input: &IN,
 	api_secret: Option<String>,
+	policy: RequestPolicy,
 ) -> Result<OUT, Error>
 where
 	IN: Serialize,
 	OUT: Send + 'static,
 	for<'de> OUT: Deserialize<'de>,
 {
-	handle_request_async(create_post_request(url, api_secret, input)?).await
+	handle_request_async(create_post_request(url, api_secret, input)?, policy).await
 }
 
 /// Helper function to easily issue a HTTP POST request with the provided JSON
@@ -251,69 +469,164 @@ where
 	build_request_ex(url, "POST", api_secret, basic_auth_key, Some(json))
 }
 
-fn handle_request<T>(req: Request<Body>, timeout: TimeOut) -> Result<T, Error>
+fn handle_request<T>(
+	req: Request<Body>,
+	timeout: TimeOut,
+	policy: RequestPolicy,
+) -> Result<T, Error>
 where
 	for<'de> T: Deserialize<'de>,
 {
-	let data = send_request(req, timeout)?;
+	let data = shared_client()?
+		.runtime
+		.block_on(send_request_with_policy(req, timeout, policy))?;
 	serde_json::from_str(&data)
 		.map_err(|e| Error::ResponseError(format!("Cannot parse response: {}, {}", data, e)))
 }
 
-async fn handle_request_async<T>(req: Request<Body>) -> Result<T, Error>
+async fn handle_request_async<T>(req: Request<Body>, policy: RequestPolicy) -> Result<T, Error>
 where
 	for<'de> T: Deserialize<'de> + Send + 'static,
 {
-	let data = send_request_async(req, TimeOut::default()).await?;
+	let data = send_request_with_policy(req, TimeOut::default(), policy).await?;
 	let ser = serde_json::from_str(&data)
 		.map_err(|e| Error::ResponseError(format!("Cannot parse response: {}, {}", data, e)))?;
 	Ok(ser)
 }
 
-async fn send_request_async(req: Request<Body>, timeout: TimeOut) -> Result<String, Error> {
-	let https = hyper_rustls::HttpsConnectorBuilder::new()
-		.with_native_roots()
-		.https_or_http()
-		.enable_http1()
-		.build();
-
-	let (connect, read, write) = (
-		Some(timeout.connect),
-		Some(timeout.read),
-		Some(timeout.write),
-	);
-	let mut connector = TimeoutConnector::new(https);
-	connector.set_connect_timeout(connect);
-	connector.set_read_timeout(read);
-	connector.set_write_timeout(write);
-	let client = Client::builder().build::<_, Body>(connector);
-
-	let resp = client
-		.request(req)
+// Request is resent with the same body, so the body is read once
+async fn send_request_with_policy(
+	req: Request<Body>,
+	timeout: TimeOut,
+	policy: RequestPolicy,
+) -> Result<String, Error> {
+	if policy.retries == 0 {
+		return send_request_async(req, timeout).await;
+	}
+	let idempotent = *req.method() == Method::GET || *req.method() == Method::HEAD;
+	let (parts, body) = req.into_parts();
+	let body = body::to_bytes(body)
 		.await
-		.map_err(|e| Error::RequestError(format!("Cannot make request: {}", e)))?;
+		.map_err(|e| Error::RequestError(format!("Cannot read request body: {}", e)))?;
+	let mut attempt = 0;
+	loop {
+		let mut req = Request::new(Body::from(body.clone()));
+		*req.method_mut() = parts.method.clone();
+		*req.uri_mut() = parts.uri.clone();
+		*req.version_mut() = parts.version;
+		*req.headers_mut() = parts.headers.clone();
+		match send_once(req, &timeout).await {
+			Ok(data) => return Ok(data),
+			Err((failure, e)) => {
+				if attempt >= policy.retries || !policy.is_retried(failure, idempotent) {
+					return Err(e);
+				}
+				let delay = policy.delay(attempt);
+				debug!(
+					"Request {} {} failed, retry in {:?}, {}",
+					parts.method, parts.uri, delay, e
+				);
+				tokio::time::sleep(delay).await;
+				attempt += 1;
+			}
+		}
+	}
+}
+
+async fn send_request_async(req: Request<Body>, timeout: TimeOut) -> Result<String, Error> {
+	send_once(req, &timeout).await.map_err(|(_, e)| e)
+}
+
+async fn send_once(req: Request<Body>, timeout: &TimeOut) -> Result<String, (Failure, Error)> {
+	let client = shared_client()
+		.map_err(|e| (Failure::Other, e))?
+		.client(timeout);
+
+	let resp = client.request(req).await.map_err(|e| {
+		let failure = if e.is_connect() {
+			Failure::Connect
+		} else {
+			Failure::Other
+		};
+		(
+			failure,
+			Error::RequestError(format!("Cannot make request: {}", e)),
+		)
+	})?;
 
 	let status = resp.status().clone();
 
 	// Read body first because we want to return it in case of error.
-	let raw = body::to_bytes(resp.into_body())
-		.await
-		.map_err(|e| Error::RequestError(format!("Cannot read response body: {}", e)))?;
+	let raw = body::to_bytes(resp.into_body()).await.map_err(|e| {
+		(
+			Failure::Other,
+			Error::RequestError(format!("Cannot read response body: {}", e)),
+		)
+	})?;
 	let response_body = String::from_utf8_lossy(&raw).to_string();
 
 	if !status.is_success() {
-		return Err(Error::RequestError(format!(
-			"Wrong response code: {} with data {}",
-			status, response_body
-		)));
+		let failure = if status.is_server_error() {
+			Failure::ServerError
+		} else {
+			Failure::Other
+		};
+		return Err((
+			failure,
+			Error::RequestError(format!(
+				"Wrong response code: {} with data {}",
+				status, response_body
+			)),
+		));
 	}
 	Ok(response_body)
 }
 
 pub fn send_request(req: Request<Body>, timeout: TimeOut) -> Result<String, Error> {
-	let rt = Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(|e| Error::RequestError(format!("can't create Tokio runtime, {}", e)))?;
-	rt.block_on(send_request_async(req, timeout))
+	shared_client()?
+		.runtime
+		.block_on(send_request_async(req, timeout))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn request_policy() {
+		let policy = RequestPolicy::retry(3, Duration::from_millis(100));
+		assert!(policy.is_retried(Failure::Connect, false));
+		assert!(policy.is_retried(Failure::ServerError, true));
+		assert!(!policy.is_retried(Failure::ServerError, false));
+		assert!(!policy.is_retried(Failure::Other, true));
+		let policy = RequestPolicy {
+			retry_on: RetryOn::Connect,
+			..policy
+		};
+		assert!(!policy.is_retried(Failure::ServerError, true));
+
+		for attempt in 0..4 {
+			let max = Duration::from_millis(100 << attempt);
+			let delay = policy.delay(attempt);
+			assert!(delay >= max / 2 && delay <= max);
+		}
+		assert!(policy.delay(40) <= MAX_BACKOFF);
+	}
+
+	#[test]
+	fn shared_client_init() {
+		// Invalid settings are reported, the client is not created
+		let config = HttpClientConfig {
+			proxy: Some("not a proxy".to_string()),
+			..HttpClientConfig::default()
+		};
+		assert!(matches!(init_http_client(config), Err(Error::Argument(_))));
+		assert!(SHARED_CLIENT.read().is_none());
+
+		// The first request creates the client with the default settings
+		let client = shared_client().unwrap();
+		assert_eq!(client.config, HttpClientConfig::default());
+		assert!(Arc::ptr_eq(&client, &shared_client().unwrap()));
+		init_http_client(HttpClientConfig::default()).unwrap();
+	}
 }
@@ -0,0 +1,176 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cross-origin requests policy of the API server, so the browser based explorers and
+//! web wallets can call the API directly. The policy is applied by the router: the
+//! preflight requests are answered before the auth middleware, the other responses
+//! get the allow origin header if the origin is allowed.
+
+use hyper::header::{
+	HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+	ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+/// Any origin is allowed
+const ANY_ORIGIN: &str = "*";
+
+/// CORS policy configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CorsConfig {
+	/// Origins that are allowed to call the API, like `https://explorer.example.com`.
+	/// `*` allows any origin. Default: `*`
+	pub allowed_origins: Vec<String>,
+	/// Allowed methods. Default: GET, POST, OPTIONS
+	pub allowed_methods: Vec<String>,
+	/// Allowed request headers. Default: Content-Type, Authorization
+	pub allowed_headers: Vec<String>,
+	/// How long the browser can cache the preflight result, in seconds. Default: 3600
+	pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+	fn default() -> CorsConfig {
+		CorsConfig {
+			allowed_origins: vec![ANY_ORIGIN.to_string()],
+			allowed_methods: vec!["GET".to_string(), "POST".to_string(), "OPTIONS".to_string()],
+			allowed_headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
+			max_age_secs: 3600,
+		}
+	}
+}
+
+impl CorsConfig {
+	fn any_origin(&self) -> bool {
+		self.allowed_origins.iter().any(|o| o == ANY_ORIGIN)
+	}
+
+	fn is_origin_allowed(&self, origin: &HeaderValue) -> bool {
+		self.any_origin()
+			|| self
+				.allowed_origins
+				.iter()
+				.any(|o| o.as_bytes() == origin.as_bytes())
+	}
+
+	fn allow_origin(&self, origin: &HeaderValue) -> HeaderValue {
+		if self.any_origin() {
+			HeaderValue::from_static(ANY_ORIGIN)
+		} else {
+			origin.clone()
+		}
+	}
+
+	/// Is it the preflight request that is answered by the policy
+	pub fn is_preflight(req: &Request<Body>) -> bool {
+		*req.method() == Method::OPTIONS
+			&& req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+	}
+
+	/// Answer for the preflight request. Forbidden if the origin or the method is not allowed.
+	pub fn preflight_response(&self, req: &Request<Body>, origin: &HeaderValue) -> Response<Body> {
+		let method_allowed = req
+			.headers()
+			.get(ACCESS_CONTROL_REQUEST_METHOD)
+			.and_then(|m| m.to_str().ok())
+			.map(|m| {
+				self.allowed_methods
+					.iter()
+					.any(|a| a.eq_ignore_ascii_case(m))
+			})
+			.unwrap_or(false);
+		let mut resp = Response::new(Body::empty());
+		if !self.is_origin_allowed(origin) || !method_allowed {
+			*resp.status_mut() = StatusCode::FORBIDDEN;
+			return resp;
+		}
+		*resp.status_mut() = StatusCode::NO_CONTENT;
+		let headers = resp.headers_mut();
+		headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin(origin));
+		if let Ok(methods) = HeaderValue::from_str(&self.allowed_methods.join(", ")) {
+			headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+		}
+		if let Ok(allowed_headers) = HeaderValue::from_str(&self.allowed_headers.join(", ")) {
+			headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+		}
+		headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(self.max_age_secs));
+		headers.append(VARY, HeaderValue::from_static("Origin"));
+		resp
+	}
+
+	/// Set the allow origin header of the response. The header that is set by the handler
+	/// is replaced, so the origins that are not allowed are blocked by the browser.
+	pub fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+		if self.is_origin_allowed(origin) {
+			headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin(origin));
+			headers.append(VARY, HeaderValue::from_static("Origin"));
+		} else {
+			headers.remove(ACCESS_CONTROL_ALLOW_ORIGIN);
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn preflight(origin: &str, method: &str) -> Request<Body> {
+		Request::builder()
+			.method(Method::OPTIONS)
+			.uri("/v2/foreign")
+			.header("Origin", origin)
+			.header(ACCESS_CONTROL_REQUEST_METHOD, method)
+			.body(Body::empty())
+			.unwrap()
+	}
+
+	#[test]
+	fn cors_policy() {
+		let config = CorsConfig {
+			allowed_origins: vec!["https://explorer.mwc.mw".to_string()],
+			..CorsConfig::default()
+		};
+		let origin = HeaderValue::from_static("https://explorer.mwc.mw");
+		let other = HeaderValue::from_static("https://other.com");
+
+		let req = preflight("https://explorer.mwc.mw", "POST");
+		assert!(CorsConfig::is_preflight(&req));
+		let resp = config.preflight_response(&req, &origin);
+		assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+		assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+		assert_eq!(
+			resp.headers()[ACCESS_CONTROL_ALLOW_METHODS],
+			"GET, POST, OPTIONS"
+		);
+
+		let req = preflight("https://explorer.mwc.mw", "DELETE");
+		let resp = config.preflight_response(&req, &origin);
+		assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+		let req = preflight("https://other.com", "POST");
+		let resp = config.preflight_response(&req, &other);
+		assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+		// Handler allows any origin, the policy is stricter
+		let mut headers = HeaderMap::new();
+		headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+		config.apply(&other, &mut headers);
+		assert!(headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+		config.apply(&origin, &mut headers);
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+
+		let mut headers = HeaderMap::new();
+		CorsConfig::default().apply(&other, &mut headers);
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+	}
+}
@@ -0,0 +1,86 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runtime control of the Dandelion relay. The settings are applied without the node
+//! restart: the timers are used by the monitor from its next run, the stem probability
+//! is used from the next epoch.
+
+use crate::pool::DandelionConfig;
+use crate::rest::Error;
+use crate::types::DandelionStats;
+
+/// Dandelion settings and stats, implemented by the node pool to network adapter
+pub trait DandelionControl: Send + Sync {
+	/// Current Dandelion settings
+	fn get_dandelion_config(&self) -> DandelionConfig;
+
+	/// Replace the Dandelion settings, the config is validated by the caller
+	fn set_dandelion_config(&self, config: DandelionConfig);
+
+	/// Epochs and stem relay peers rotation stats
+	fn get_dandelion_stats(&self) -> DandelionStats;
+}
+
+/// Check that the Dandelion settings make sense
+pub fn validate_dandelion_config(config: &DandelionConfig) -> Result<(), Error> {
+	if config.stem_probability > 100 {
+		return Err(Error::Argument(format!(
+			"stem_probability {} is out of range, expected 0-100",
+			config.stem_probability
+		)));
+	}
+	if config.epoch_secs == 0 || config.embargo_secs == 0 || config.aggregation_secs == 0 {
+		return Err(Error::Argument(
+			"epoch_secs, embargo_secs and aggregation_secs must be positive".to_string(),
+		));
+	}
+	if config.aggregation_secs > config.embargo_secs {
+		return Err(Error::Argument(format!(
+			"aggregation_secs {} is larger than embargo_secs {}",
+			config.aggregation_secs, config.embargo_secs
+		)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn dandelion_config_validation() {
+		let config = DandelionConfig::default();
+		assert!(validate_dandelion_config(&config).is_ok());
+		assert!(validate_dandelion_config(&DandelionConfig {
+			stem_probability: 100,
+			..config.clone()
+		})
+		.is_ok());
+		assert!(validate_dandelion_config(&DandelionConfig {
+			stem_probability: 101,
+			..config.clone()
+		})
+		.is_err());
+		assert!(validate_dandelion_config(&DandelionConfig {
+			epoch_secs: 0,
+			..config.clone()
+		})
+		.is_err());
+		assert!(validate_dandelion_config(&DandelionConfig {
+			aggregation_secs: config.embargo_secs + 1,
+			..config.clone()
+		})
+		.is_err());
+	}
+}
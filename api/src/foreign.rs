@@ -21,13 +21,13 @@ use crate::core::core::hash::Hashed;
 use crate::core::core::transaction::Transaction;
 use crate::handlers::blocks_api::{BlockHandler, HeaderHandler};
 use crate::handlers::chain_api::{ChainHandler, KernelHandler, OutputHandler};
-use crate::handlers::pool_api::PoolHandler;
+use crate::handlers::pool_api::{FeeEstimateHandler, PoolHandler};
 use crate::handlers::transactions_api::TxHashSetHandler;
 use crate::handlers::version_api::VersionHandler;
 use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockPrintable, FeeEstimate, LocatedTxKernel, OutputListing,
+	OutputPrintable, PaymentProofSignatures, PaymentProofVerification, Tip, Version,
 };
 use crate::util::RwLock;
 use crate::{rest::*, BlockListing};
@@ -241,6 +241,36 @@ where
 		kernel_handler.get_kernel_v2(excess, min_height, max_height)
 	}
 
+	/// Verifies the payment proof: the signatures of the recipient (and of the sender if
+	/// there is one) over the amount, the kernel excess and the sender address, and the
+	/// kernel of the payment in the chain. The amount is hidden in the chain, it is proven
+	/// by the recipient signature only.
+	///
+	/// # Arguments
+	/// * `excess` - kernel excess of the payment.
+	/// * `amount` - amount that the recipient signed.
+	/// * `fee` - optional expected fee of the kernel.
+	/// * `signatures` - a [`PaymentProofSignatures`](types/struct.PaymentProofSignatures.html).
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`PaymentProofVerification`](types/struct.PaymentProofVerification.html) with the confirmations of the kernel
+	/// * or [`Error`](struct.Error.html) if the proof is invalid or the kernel is not in the chain.
+	///
+
+	pub fn verify_payment_proof(
+		&self,
+		excess: String,
+		amount: u64,
+		fee: Option<u64>,
+		signatures: PaymentProofSignatures,
+	) -> Result<PaymentProofVerification, Error> {
+		let kernel_handler = KernelHandler {
+			chain: self.chain.clone(),
+		};
+		kernel_handler.verify_payment_proof(excess, amount, fee, signatures)
+	}
+
 	/// Retrieves details about specifics outputs. Supports retrieval of multiple outputs in a single request.
 	/// Support retrieval by both commitment string and block height.
 	///
@@ -357,6 +387,26 @@ where
 		pool_handler.get_stempool_size()
 	}
 
+	/// Recommends the fee base for the new transaction, from the transactions that are waiting
+	/// in the pool and the fullness of the recent blocks.
+	///
+	/// # Arguments
+	/// * `blocks_target` - the transaction is expected to be mined in this number of blocks.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`FeeEstimate`](types/struct.FeeEstimate.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error> {
+		let fee_estimate_handler = FeeEstimateHandler {
+			chain: self.chain.clone(),
+			tx_pool: self.tx_pool.clone(),
+		};
+		fee_estimate_handler.get_fee_estimate(blocks_target)
+	}
+
 	/// Returns the unconfirmed transactions in the transaction pool.
 	/// Will not return transactions in the stempool.
 	///
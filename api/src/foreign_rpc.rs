@@ -23,8 +23,8 @@ use crate::pool::PoolEntry;
 use crate::pool::{BlockChain, PoolAdapter};
 use crate::rest::Error;
 use crate::types::{
-	BlockHeaderPrintable, BlockListing, BlockPrintable, LocatedTxKernel, OutputListing,
-	OutputPrintable, Tip, Version,
+	BlockHeaderPrintable, BlockListing, BlockPrintable, FeeEstimate, LocatedTxKernel,
+	OutputListing, OutputPrintable, PaymentProofSignatures, PaymentProofVerification, Tip, Version,
 };
 use crate::{util, Libp2pMessages, Libp2pPeers};
 
@@ -725,6 +725,52 @@ pub trait ForeignRpc: Sync + Send {
 		max_height: Option<u64>,
 	) -> Result<LocatedTxKernel, Error>;
 
+	/**
+	Networked version of [Foreign::verify_payment_proof](struct.Foreign.html#method.verify_payment_proof).
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "verify_payment_proof",
+		"params": [
+			"09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b",
+			1000000000,
+			8000000,
+			{
+				"sender_address": "8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c",
+				"sender_sig": null,
+				"recipient_address": "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+				"recipient_sig": "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+			}
+		],
+		"id": 1
+	}
+
+	Respond:
+	{
+	  "id": 1,
+	  "jsonrpc": "2.0",
+	  "result": {
+		"Ok": {
+		  "excess": "09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b",
+		  "amount": 1000000000,
+		  "fee": 8000000,
+		  "height": 374557,
+		  "mmr_index": 2211662,
+		  "confirmations": 12,
+		  "sender_signed": false
+		}
+	  }
+	}
+	*/
+	fn verify_payment_proof(
+		&self,
+		excess: String,
+		amount: u64,
+		fee: Option<u64>,
+		signatures: PaymentProofSignatures,
+	) -> Result<PaymentProofVerification, Error>;
+
 	/**
 	Networked version of [Foreign::get_outputs](struct.Foreign.html#method.get_outputs).
 
@@ -970,6 +1016,36 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn get_stempool_size(&self) -> Result<usize, Error>;
 
+	/**
+	Networked version of [Foreign::get_fee_estimate](struct.Foreign.html#method.get_fee_estimate).
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "get_fee_estimate",
+		"params": [3],
+		"id": 1
+	}
+
+	Respond:
+	{
+	  "id": 1,
+	  "jsonrpc": "2.0",
+	  "result": {
+		"Ok": {
+		  "blocks_target": 3,
+		  "fee_base": 1000,
+		  "min_fee_base": 1000,
+		  "pool_txs": 2,
+		  "pool_weight": 92,
+		  "block_weight": 40000,
+		  "recent_utilization": 0.0046
+		}
+	  }
+	}
+	*/
+	fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error>;
+
 	/**
 	Networked version of [Foreign::get_unconfirmed_transactions](struct.Foreign.html#method.get_unconfirmed_transactions).
 
@@ -1229,6 +1305,16 @@ where
 		Foreign::get_kernel(self, excess, min_height, max_height)
 	}
 
+	fn verify_payment_proof(
+		&self,
+		excess: String,
+		amount: u64,
+		fee: Option<u64>,
+		signatures: PaymentProofSignatures,
+	) -> Result<PaymentProofVerification, Error> {
+		Foreign::verify_payment_proof(self, excess, amount, fee, signatures)
+	}
+
 	fn get_outputs(
 		&self,
 		commits: Option<Vec<String>>,
@@ -1273,6 +1359,10 @@ where
 		Foreign::get_stempool_size(self)
 	}
 
+	fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error> {
+		Foreign::get_fee_estimate(self, blocks_target)
+	}
+
 	fn get_unconfirmed_transactions(&self) -> Result<Vec<PoolEntry>, Error> {
 		Foreign::get_unconfirmed_transactions(self)
 	}
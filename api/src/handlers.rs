@@ -15,53 +15,83 @@
 
 pub mod blocks_api;
 pub mod chain_api;
+pub mod health_api;
+pub mod openapi;
 pub mod peers_api;
 pub mod pool_api;
+pub mod read_only;
+pub mod rpc_batch;
 pub mod server_api;
+pub mod sse_api;
 pub mod transactions_api;
 pub mod utils;
 pub mod version_api;
+pub mod wallet_proxy;
+pub mod ws_api;
 
 use self::blocks_api::BlockHandler;
 use self::blocks_api::HeaderHandler;
 use self::chain_api::ChainCompactHandler;
+use self::chain_api::ChainFeesHandler;
 use self::chain_api::ChainHandler;
+use self::chain_api::ChainIndexHandler;
+use self::chain_api::ChainKernelsHandler;
+use self::chain_api::ChainOutputProofHandler;
+use self::chain_api::ChainOutputsHandler;
+use self::chain_api::ChainStatsHandler;
 use self::chain_api::ChainValidationHandler;
 use self::chain_api::KernelHandler;
 use self::chain_api::OutputHandler;
+use self::health_api::{HealthConfig, HealthHandler, ReadyHandler};
+use self::openapi::{schema_generator, ApiDoc, ApiOperation, OpenApiHandler};
 use self::peers_api::PeerHandler;
 use self::peers_api::PeersAllHandler;
 use self::peers_api::PeersConnectedHandler;
+use self::peers_api::PeersTrafficHandler;
+use self::pool_api::FeeEstimateHandler;
+use self::pool_api::PoolCheckHandler;
 use self::pool_api::PoolInfoHandler;
 use self::pool_api::PoolPushHandler;
+use self::read_only::{check_allowed_methods, ReadOnlyApiConfig};
+use self::rpc_batch::handle_rpc_request;
 use self::server_api::IndexHandler;
 use self::server_api::StatusHandler;
+use self::sse_api::{ChainTipStreamHandler, TipEventBus};
 use self::transactions_api::TxHashSetHandler;
 use self::version_api::VersionHandler;
+use self::wallet_proxy::{WalletProxyConfig, WalletProxyHandler, WALLET_PROXY_PREFIX};
+use self::ws_api::{WsEventBus, WsHandler};
 use crate::auth::{
-	BasicAuthMiddleware, BasicAuthURIMiddleware, MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
+	ApiTokenStore, BasicAuthURIMiddleware, ClientCertMiddleware, TokenAuthMiddleware,
+	MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
 };
 use crate::chain;
 use crate::chain::{Chain, SyncState};
 use crate::core::global;
 use crate::core::stratum;
+use crate::cors::CorsConfig;
+use crate::dandelion::DandelionControl;
 use crate::foreign::Foreign;
 use crate::foreign_rpc::ForeignRpc;
+use crate::mining::{BlockTemplateProvider, Mining, MiningStatsProvider};
+use crate::mining_rpc::MiningRpc;
+use crate::node_control::NodeControl;
 use crate::owner::Owner;
 use crate::owner_rpc::OwnerRpc;
 use crate::p2p;
 use crate::pool;
 use crate::pool::{BlockChain, PoolAdapter};
-use crate::rest::{ApiServer, Error, TLSConfig};
+use crate::rate_limit::{ApiRateLimiter, RateLimitMiddleware};
+use crate::rest::{ApiServer, Error, TLSConfig, ThreadSupervisor};
 use crate::router::ResponseFuture;
-use crate::router::{Router, RouterError};
+use crate::router::{HandlerObj, Router, RouterError};
 use crate::stratum::Stratum;
 use crate::stratum_rpc::StratumRpc;
 use crate::util::to_base64;
-use crate::util::RwLock;
 use crate::util::StopState;
+use crate::util::{Mutex, RwLock};
 use crate::web::*;
-use easy_jsonrpc_mw::{Handler, MaybeReply};
+use easy_jsonrpc_mw::Handler;
 use futures::channel::oneshot;
 use hyper::{Body, Request, Response, StatusCode};
 use serde::Serialize;
@@ -77,26 +107,55 @@ pub fn node_apis<B, P>(
 	tx_pool: Arc<RwLock<pool::TransactionPool<B, P>>>,
 	peers: Arc<p2p::Peers>,
 	sync_state: Arc<chain::SyncState>,
-	api_secret: Option<String>,
+	api_tokens: ApiTokenStore,
 	foreign_api_secret: Option<String>,
 	tls_config: Option<TLSConfig>,
+	wallet_proxy_config: Option<WalletProxyConfig>,
+	read_only_config: Option<ReadOnlyApiConfig>,
+	cors_config: Option<CorsConfig>,
+	rate_limiter: Option<Arc<Mutex<ApiRateLimiter>>>,
 	allow_to_stop: bool,
 	stratum_ip_pool: Arc<stratum::connections::StratumIpPool>,
-	api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+	ws_events: Arc<WsEventBus>,
+	tip_events: Arc<TipEventBus>,
+	block_templates: Option<Arc<dyn BlockTemplateProvider>>,
+	mining_stats: Option<Arc<dyn MiningStatsProvider>>,
+	dandelion: Option<Arc<dyn DandelionControl>>,
+	node_control: Option<Arc<dyn NodeControl>>,
+	health_config: HealthConfig,
+	supervisor: Option<Arc<dyn ThreadSupervisor>>,
+	api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 	stop_state: Arc<StopState>,
 ) -> Result<(), Error>
 where
 	B: BlockChain + 'static,
 	P: PoolAdapter + 'static,
 {
+	let read_only_config = read_only_config.filter(|c| c.enabled);
+
 	// Adding legacy owner v1 API
-	let mut router = build_router(
-		chain.clone(),
-		tx_pool.clone(),
-		peers.clone(),
-		sync_state.clone(),
-		allow_to_stop,
-	)
+	let mut router = match read_only_config.as_ref() {
+		Some(read_only_config) => {
+			warn!(
+				"Node API is running with the read-only profile, routes: {:?}, foreign API methods: {:?}",
+				read_only_config.allowed_routes, read_only_config.allowed_foreign_methods
+			);
+			build_read_only_router(
+				chain.clone(),
+				tx_pool.clone(),
+				peers.clone(),
+				sync_state.clone(),
+				read_only_config,
+			)
+		}
+		None => build_router(
+			chain.clone(),
+			tx_pool.clone(),
+			peers.clone(),
+			sync_state.clone(),
+			allow_to_stop,
+		),
+	}
 	.expect("unable to build API router");
 
 	let basic_auth_key = if global::is_mainnet() {
@@ -107,29 +166,85 @@ where
 		"mwc"
 	};
 
-	// Add basic auth to v1 API and owner v2 API
-	if let Some(api_secret) = api_secret {
-		let api_basic_auth = format!(
-			"Basic {}",
-			to_base64(&format!("{}:{}", basic_auth_key, api_secret))
+	// Read-only profile doesn't serve the owner APIs, the public read endpoints are open
+	if read_only_config.is_none() {
+		// Add scoped keys auth to v1 API and owner v2 API
+		if !api_tokens.is_empty() {
+			let basic_auth_middleware = TokenAuthMiddleware::new(
+				&api_tokens,
+				basic_auth_key,
+				&MWC_BASIC_REALM,
+				Some("/v2/foreign".into()),
+			)
+			// wallet proxy has its own auth
+			.with_ignore_prefix(format!("{}/", WALLET_PROXY_PREFIX))
+			// events subscriptions are public like the foreign API
+			.with_ignore_prefix("/v2/ws".into())
+			// orchestration probes don't have the keys
+			.with_ignore_prefix("/v2/health".into())
+			.with_ignore_prefix("/v2/ready".into());
+			router.add_middleware(Arc::new(basic_auth_middleware));
+		}
+
+		// Owner scoped routes are served only to the clients with the certificate from the
+		// client CA, the public routes are the same as without the API keys
+		if let Some(client_ca) = tls_config.as_ref().and_then(|c| c.client_ca.as_ref()) {
+			info!(
+				"Owner scoped API routes require the client certificate signed by {}",
+				client_ca
+			);
+			let client_cert_middleware = ClientCertMiddleware::new(Some("/v2/foreign".into()))
+				.with_ignore_prefix(format!("{}/", WALLET_PROXY_PREFIX))
+				.with_ignore_prefix("/v2/ws".into())
+				.with_ignore_prefix("/v2/health".into())
+				.with_ignore_prefix("/v2/ready".into());
+			router.add_middleware(Arc::new(client_cert_middleware));
+		}
+
+		let api_handler = OwnerAPIHandlerV2::new(
+			Arc::downgrade(&chain),
+			Arc::downgrade(&peers),
+			Arc::downgrade(&sync_state),
+			dandelion,
+			node_control,
 		);
-		let basic_auth_middleware = Arc::new(BasicAuthMiddleware::new(
-			api_basic_auth,
-			&MWC_BASIC_REALM,
-			Some("/v2/foreign".into()),
-		));
-		router.add_middleware(basic_auth_middleware);
-	}
+		router.add_route("/v2/owner", Arc::new(api_handler))?;
 
-	let api_handler = OwnerAPIHandlerV2::new(
-		Arc::downgrade(&chain),
-		Arc::downgrade(&peers),
-		Arc::downgrade(&sync_state),
-	);
-	router.add_route("/v2/owner", Arc::new(api_handler))?;
+		let stratum_handler_v2 = StratumAPIHandlerV2::new(stratum_ip_pool);
+		router.add_route("/v2/stratum", Arc::new(stratum_handler_v2))?;
 
-	let stratum_handler_v2 = StratumAPIHandlerV2::new(stratum_ip_pool);
-	router.add_route("/v2/stratum", Arc::new(stratum_handler_v2))?;
+		let mining_handler_v2 =
+			MiningAPIHandlerV2::new(block_templates, mining_stats, Arc::downgrade(&sync_state));
+		router.add_route("/v2/mining", Arc::new(mining_handler_v2))?;
+	}
+
+	// Rate limit goes before the foreign API auth, so its secret can't be brute forced
+	if let Some(rate_limiter) = rate_limiter {
+		{
+			let limiter = rate_limiter.lock();
+			let rate_limit_config = limiter.config();
+			info!(
+				"API requests are rate limited for {:?}, {} per minute per IP, {} per minute per key",
+				rate_limit_config.routes,
+				rate_limit_config.requests_per_min,
+				rate_limit_config.token_requests_per_min
+			);
+		}
+		let mut known_tokens: Vec<String> = api_tokens
+			.auth_headers(basic_auth_key)
+			.into_iter()
+			.map(|(_, header, _)| header)
+			.collect();
+		if let Some(api_secret) = foreign_api_secret.as_ref() {
+			known_tokens.push(format!(
+				"Basic {}",
+				to_base64(&format!("{}:{}", basic_auth_key, api_secret))
+			));
+		}
+		let rate_limit_middleware =
+			RateLimitMiddleware::with_limiter(rate_limiter).with_known_tokens(&known_tokens);
+		router.add_middleware(Arc::new(rate_limit_middleware));
+	}
 
 	// Add basic auth to v2 foreign API only
 	if let Some(api_secret) = foreign_api_secret {
@@ -138,11 +253,17 @@ where
 			to_base64(&format!("{}:{}", basic_auth_key, api_secret))
 		);
 		let basic_auth_middleware = Arc::new(BasicAuthURIMiddleware::new(
-			api_basic_auth,
+			api_basic_auth.clone(),
 			&MWC_FOREIGN_BASIC_REALM,
 			"/v2/foreign".into(),
 		));
 		router.add_middleware(basic_auth_middleware);
+		let ws_basic_auth_middleware = Arc::new(BasicAuthURIMiddleware::new(
+			api_basic_auth,
+			&MWC_FOREIGN_BASIC_REALM,
+			"/v2/ws".into(),
+		));
+		router.add_middleware(ws_basic_auth_middleware);
 	}
 
 	let api_handler = ForeignAPIHandlerV2::new(
@@ -151,9 +272,57 @@ where
 		Arc::downgrade(&tx_pool),
 		Arc::downgrade(&sync_state),
 	);
-	router.add_route("/v2/foreign", Arc::new(api_handler))?;
+	match read_only_config.as_ref() {
+		Some(read_only_config) => {
+			if !read_only_config.allowed_foreign_methods.is_empty() {
+				let api_handler = api_handler
+					.with_allowed_methods(read_only_config.allowed_foreign_methods.clone());
+				router.add_route("/v2/foreign", Arc::new(api_handler))?;
+			}
+		}
+		None => {
+			router.add_route("/v2/foreign", Arc::new(api_handler))?;
+		}
+	}
+
+	add_stream_and_probe_routes(
+		&mut router,
+		read_only_config.as_ref(),
+		chain.clone(),
+		peers.clone(),
+		sync_state.clone(),
+		ws_events,
+		tip_events,
+		health_config,
+	)?;
+
+	if let Some(wallet_proxy_config) = wallet_proxy_config.filter(|c| c.enabled) {
+		if read_only_config.is_some() {
+			warn!("Wallet listener proxy is disabled by the read-only API profile");
+		} else {
+			let wallet_proxy = WalletProxyHandler::new(&wallet_proxy_config).map_err(|e| {
+				Error::Internal(format!("Unable to start wallet listener proxy, {}", e))
+			})?;
+			warn!(
+				"Wallet listener proxy is forwarding {}/** to {}",
+				WALLET_PROXY_PREFIX, wallet_proxy_config.wallet_listener_url
+			);
+			router.add_route("/wallet/**", Arc::new(wallet_proxy))?;
+		}
+	}
+
+	if let Some(cors_config) = cors_config {
+		info!(
+			"API cross-origin requests are allowed for {:?}",
+			cors_config.allowed_origins
+		);
+		router.set_cors(cors_config);
+	}
 
 	let mut apis = ApiServer::new();
+	if let Some(supervisor) = supervisor {
+		apis.set_supervisor(supervisor);
+	}
 	warn!("Starting HTTP Node APIs server at {}.", addr);
 	let socket_addr: SocketAddr = addr.parse().expect("unable to parse socket address");
 	let api_thread = apis.start(socket_addr, router, tls_config, api_chan);
@@ -186,20 +355,87 @@ where
 	}
 }
 
+/// Events subscriptions, the tip stream and the health probes. With the read-only
+/// profile only the routes of `allowed_routes` are served.
+fn add_stream_and_probe_routes(
+	router: &mut Router,
+	read_only_config: Option<&ReadOnlyApiConfig>,
+	chain: Arc<chain::Chain>,
+	peers: Arc<p2p::Peers>,
+	sync_state: Arc<chain::SyncState>,
+	ws_events: Arc<WsEventBus>,
+	tip_events: Arc<TipEventBus>,
+	health_config: HealthConfig,
+) -> Result<(), RouterError> {
+	let is_allowed = |route: &str| {
+		read_only_config
+			.map(|c| c.is_route_allowed(route))
+			.unwrap_or(true)
+	};
+
+	let ws_route = "/v2/ws";
+	if is_allowed(ws_route) {
+		let ws_handler = WsHandler {
+			events: ws_events,
+			sync_state: Arc::downgrade(&sync_state),
+		};
+		router.add_route(ws_route, Arc::new(ws_handler))?;
+	}
+
+	let health_route = "/v2/health";
+	if is_allowed(health_route) {
+		let health_handler = HealthHandler {
+			chain: Arc::downgrade(&chain),
+		};
+		router.add_route(health_route, Arc::new(health_handler))?;
+	}
+	let ready_route = "/v2/ready";
+	if is_allowed(ready_route) {
+		let ready_handler = ReadyHandler {
+			chain: Arc::downgrade(&chain),
+			peers: Arc::downgrade(&peers),
+			sync_state: Arc::downgrade(&sync_state),
+			config: health_config,
+		};
+		router.add_route(ready_route, Arc::new(ready_handler))?;
+	}
+
+	// Tip stream is a part of v1 API, same auth and read-only routes filter
+	let tip_stream_route = "/v1/chain/tip/stream";
+	if is_allowed(tip_stream_route) {
+		let tip_stream_handler = ChainTipStreamHandler {
+			chain: Arc::downgrade(&chain),
+			events: tip_events,
+		};
+		router.add_route(tip_stream_route, Arc::new(tip_stream_handler))?;
+	}
+	Ok(())
+}
+
 /// V2 API Handler/Wrapper for owner functions
 pub struct OwnerAPIHandlerV2 {
 	pub chain: Weak<Chain>,
 	pub peers: Weak<p2p::Peers>,
 	pub sync_state: Weak<SyncState>,
+	pub dandelion: Option<Arc<dyn DandelionControl>>,
+	pub node_control: Option<Arc<dyn NodeControl>>,
 }
 
 impl OwnerAPIHandlerV2 {
 	/// Create a new owner API handler for GET methods
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		peers: Weak<p2p::Peers>,
+		sync_state: Weak<SyncState>,
+		dandelion: Option<Arc<dyn DandelionControl>>,
+		node_control: Option<Arc<dyn NodeControl>>,
+	) -> Self {
 		OwnerAPIHandlerV2 {
 			chain,
 			peers,
 			sync_state,
+			dandelion,
+			node_control,
 		}
 	}
 }
@@ -210,20 +446,15 @@ impl crate::router::Handler for OwnerAPIHandlerV2 {
 			self.chain.clone(),
 			self.peers.clone(),
 			self.sync_state.clone(),
+			self.dandelion.clone(),
+			self.node_control.clone(),
 		);
 
 		Box::pin(async move {
 			match parse_body(req).await {
 				Ok(val) => {
 					let owner_api = &api as &dyn OwnerRpc;
-					let res = match owner_api.handle_request(val) {
-						MaybeReply::Reply(r) => r,
-						MaybeReply::DontReply => {
-							// Since it's http, we need to return something. We return [] because jsonrpc
-							// clients will parse it as an empty batch response.
-							serde_json::json!([])
-						}
-					};
+					let res = handle_rpc_request(val, |call| owner_api.handle_request(call));
 					Ok(json_response_pretty(&res))
 				}
 				Err(e) => {
@@ -249,6 +480,8 @@ where
 	pub chain: Weak<Chain>,
 	pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
 	pub sync_state: Weak<SyncState>,
+	/// Only these methods are served if set (read-only API profile)
+	pub allowed_methods: Option<Vec<String>>,
 }
 
 impl<B, P> ForeignAPIHandlerV2<B, P>
@@ -268,8 +501,15 @@ where
 			chain,
 			tx_pool,
 			sync_state,
+			allowed_methods: None,
 		}
 	}
+
+	/// Serve only the `methods`, other requests get 'Method not found' error
+	pub fn with_allowed_methods(mut self, methods: Vec<String>) -> Self {
+		self.allowed_methods = Some(methods);
+		self
+	}
 }
 
 impl<B, P> crate::router::Handler for ForeignAPIHandlerV2<B, P>
@@ -284,19 +524,18 @@ where
 			self.tx_pool.clone(),
 			self.sync_state.clone(),
 		);
+		let allowed_methods = self.allowed_methods.clone();
 
 		Box::pin(async move {
 			match parse_body(req).await {
 				Ok(val) => {
-					let foreign_api = &api as &dyn ForeignRpc;
-					let res = match foreign_api.handle_request(val) {
-						MaybeReply::Reply(r) => r,
-						MaybeReply::DontReply => {
-							// Since it's http, we need to return something. We return [] because jsonrpc
-							// clients will parse it as an empty batch response.
-							serde_json::json!([])
+					if let Some(allowed_methods) = allowed_methods {
+						if let Some(res) = check_allowed_methods(&val, &allowed_methods) {
+							return Ok(json_response_pretty(&res));
 						}
-					};
+					}
+					let foreign_api = &api as &dyn ForeignRpc;
+					let res = handle_rpc_request(val, |call| foreign_api.handle_request(call));
 					Ok(json_response_pretty(&res))
 				}
 				Err(e) => {
@@ -332,14 +571,7 @@ impl crate::router::Handler for StratumAPIHandlerV2 {
 			match parse_body(req).await {
 				Ok(val) => {
 					let stratum_api = &api as &dyn StratumRpc;
-					let res = match stratum_api.handle_request(val) {
-						MaybeReply::Reply(r) => r,
-						MaybeReply::DontReply => {
-							// Since it's http, we need to return something. We return [] because jsonrpc
-							// clients will parse it as an empty batch response.
-							serde_json::json!([])
-						}
-					};
+					let res = handle_rpc_request(val, |call| stratum_api.handle_request(call));
 					Ok(json_response_pretty(&res))
 				}
 				Err(e) => {
@@ -355,6 +587,66 @@ impl crate::router::Handler for StratumAPIHandlerV2 {
 	}
 }
 
+/// V2 API Handler/Wrapper for mining
+pub struct MiningAPIHandlerV2 {
+	block_templates: Option<Arc<dyn BlockTemplateProvider>>,
+	mining_stats: Option<Arc<dyn MiningStatsProvider>>,
+	sync_state: Weak<SyncState>,
+}
+
+impl MiningAPIHandlerV2 {
+	/// Create a new mining API handler
+	pub fn new(
+		block_templates: Option<Arc<dyn BlockTemplateProvider>>,
+		mining_stats: Option<Arc<dyn MiningStatsProvider>>,
+		sync_state: Weak<SyncState>,
+	) -> Self {
+		MiningAPIHandlerV2 {
+			block_templates,
+			mining_stats,
+			sync_state,
+		}
+	}
+}
+
+impl crate::router::Handler for MiningAPIHandlerV2 {
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		let api = Mining::new(
+			self.block_templates.clone(),
+			self.mining_stats.clone(),
+			self.sync_state.clone(),
+		);
+
+		Box::pin(async move {
+			match parse_body(req).await {
+				Ok(val) => {
+					// The block template long poll waits, it can't block the API runtime
+					let res = tokio::task::spawn_blocking(move || {
+						let mining_api = &api as &dyn MiningRpc;
+						handle_rpc_request(val, |call| mining_api.handle_request(call))
+					})
+					.await;
+					match res {
+						Ok(res) => Ok(json_response_pretty(&res)),
+						Err(e) => Ok(create_error_response(Error::Internal(format!(
+							"Mining API call failed, {}",
+							e
+						)))),
+					}
+				}
+				Err(e) => {
+					error!("Request Error: {:?}", e);
+					Ok(create_error_response(e))
+				}
+			}
+		})
+	}
+
+	fn options(&self, _req: Request<Body>) -> ResponseFuture {
+		Box::pin(async { Ok(create_ok_response("{}")) })
+	}
+}
+
 // pretty-printed version of above
 fn json_response_pretty<T>(s: &T) -> Response<Body>
 where
@@ -431,6 +723,39 @@ pub fn build_router<B, P>(
 where
 	B: BlockChain + 'static,
 	P: PoolAdapter + 'static,
+{
+	build_router_filtered(chain, tx_pool, peers, sync_state, allow_to_stop, |_| true)
+}
+
+/// Router with only the allowed routes of the read-only API profile
+pub fn build_read_only_router<B, P>(
+	chain: Arc<chain::Chain>,
+	tx_pool: Arc<RwLock<pool::TransactionPool<B, P>>>,
+	peers: Arc<p2p::Peers>,
+	sync_state: Arc<chain::SyncState>,
+	config: &ReadOnlyApiConfig,
+) -> Result<Router, RouterError>
+where
+	B: BlockChain + 'static,
+	P: PoolAdapter + 'static,
+{
+	build_router_filtered(chain, tx_pool, peers, sync_state, false, |route| {
+		config.is_route_allowed(route)
+	})
+}
+
+fn build_router_filtered<B, P, F>(
+	chain: Arc<chain::Chain>,
+	tx_pool: Arc<RwLock<pool::TransactionPool<B, P>>>,
+	peers: Arc<p2p::Peers>,
+	sync_state: Arc<chain::SyncState>,
+	allow_to_stop: bool,
+	is_allowed: F,
+) -> Result<Router, RouterError>
+where
+	B: BlockChain + 'static,
+	P: PoolAdapter + 'static,
+	F: Fn(&str) -> bool,
 {
 	let route_list = vec![
 		"get blocks".to_string(),
@@ -438,6 +763,7 @@ where
 		"get chain".to_string(),
 		"post chain/compact".to_string(),
 		"get chain/validate".to_string(),
+		"get chain/tip/stream".to_string(),
 		"get chain/kernels/xxx?min_height=yyy&max_height=zzz".to_string(),
 		"get chain/outputs/byids?id=xxx,yyy,zzz".to_string(),
 		"get chain/outputs/byheight?start_height=101&end_height=200".to_string(),
@@ -449,6 +775,7 @@ where
 		"get txhashset/outputs?start_index=1&max=100".to_string(),
 		"get txhashset/merkleproof?n=1".to_string(),
 		"get pool".to_string(),
+		"get pool?offset=0&limit=100&sort=fee_rate&min_fee=0".to_string(),
 		"post pool/push_tx".to_string(),
 		"post peers/a.b.c.d:p/ban".to_string(),
 		"post peers/a.b.c.d:p/unban".to_string(),
@@ -480,6 +807,22 @@ where
 	let chain_validation_handler = ChainValidationHandler {
 		chain: Arc::downgrade(&chain),
 	};
+	let chain_fees_handler = ChainFeesHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let chain_outputs_handler = ChainOutputsHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let chain_output_proof_handler = ChainOutputProofHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let chain_index_handler = ChainIndexHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let chain_kernels_handler = ChainKernelsHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let chain_stats_handler = ChainStatsHandler::new(Arc::downgrade(&chain));
 	let status_handler = StatusHandler {
 		chain: Arc::downgrade(&chain),
 		peers: Arc::downgrade(&peers),
@@ -495,6 +838,13 @@ where
 	let pool_push_handler = PoolPushHandler {
 		tx_pool: Arc::downgrade(&tx_pool),
 	};
+	let pool_check_handler = PoolCheckHandler {
+		tx_pool: Arc::downgrade(&tx_pool),
+	};
+	let fee_estimate_handler = FeeEstimateHandler {
+		chain: Arc::downgrade(&chain),
+		tx_pool: Arc::downgrade(&tx_pool),
+	};
 	let peers_all_handler = PeersAllHandler {
 		peers: Arc::downgrade(&peers),
 	};
@@ -504,27 +854,443 @@ where
 	let peer_handler = PeerHandler {
 		peers: Arc::downgrade(&peers),
 	};
+	let peers_traffic_handler = PeersTrafficHandler {
+		peers: Arc::downgrade(&peers),
+	};
 	let version_handler = VersionHandler {
 		chain: Arc::downgrade(&chain),
 	};
 
+	// The OpenAPI document is generated from the route table, only the served routes are documented
+	let mut gen = schema_generator();
+	let mut operations: Vec<ApiOperation> = vec![];
 	let mut router = Router::new();
+	{
+		let mut add_route = |route: &'static str,
+		                     handler: HandlerObj,
+		                     ops: Vec<ApiOperation>|
+		 -> Result<(), RouterError> {
+			if is_allowed(route) {
+				router.add_route(route, handler)?;
+				for op in ops {
+					debug_assert_eq!(op.route, route, "operation is documented for another route");
+					operations.push(op);
+				}
+			}
+			Ok(())
+		};
+		add_route("/v1/", Arc::new(index_handler), vec![])?;
+		add_route(
+			"/v1/blocks/*",
+			Arc::new(block_handler),
+			BlockHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/headers/*",
+			Arc::new(header_handler),
+			HeaderHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/chain",
+			Arc::new(chain_tip_handler),
+			ChainHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/chain/outputs/*",
+			Arc::new(output_handler),
+			OutputHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/chain/kernels/*",
+			Arc::new(kernel_handler),
+			KernelHandler::api_operations(&mut gen),
+		)?;
+		add_route("/v1/chain/compact", Arc::new(chain_compact_handler), vec![])?;
+		add_route(
+			"/v1/chain/validate",
+			Arc::new(chain_validation_handler),
+			vec![],
+		)?;
+		add_route("/v1/txhashset/*", Arc::new(txhashset_handler), vec![])?;
+		add_route(
+			"/v1/status",
+			Arc::new(status_handler),
+			StatusHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/pool",
+			Arc::new(pool_info_handler),
+			PoolInfoHandler::<B, P>::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v1/pool/push_tx",
+			Arc::new(pool_push_handler),
+			PoolPushHandler::<B, P>::api_operations(&mut gen),
+		)?;
+		add_route("/v1/peers/all", Arc::new(peers_all_handler), vec![])?;
+		add_route(
+			"/v1/peers/connected",
+			Arc::new(peers_connected_handler),
+			vec![],
+		)?;
+		add_route("/v1/peers/**", Arc::new(peer_handler), vec![])?;
+		add_route(
+			"/v1/version",
+			Arc::new(version_handler),
+			VersionHandler::api_operations(&mut gen),
+		)?;
+		add_route("/v2/p2p/traffic", Arc::new(peers_traffic_handler), vec![])?;
+		add_route(
+			"/v2/chain/fees",
+			Arc::new(chain_fees_handler),
+			ChainFeesHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/chain/outputs",
+			Arc::new(chain_outputs_handler),
+			ChainOutputsHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/chain/output_proof",
+			Arc::new(chain_output_proof_handler),
+			ChainOutputProofHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/chain/index/*",
+			Arc::new(chain_index_handler),
+			ChainIndexHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/chain/kernels",
+			Arc::new(chain_kernels_handler),
+			ChainKernelsHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/chain/stats",
+			Arc::new(chain_stats_handler),
+			ChainStatsHandler::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/pool/fee_estimate",
+			Arc::new(fee_estimate_handler),
+			FeeEstimateHandler::<B, P>::api_operations(&mut gen),
+		)?;
+		add_route(
+			"/v2/pool/check",
+			Arc::new(pool_check_handler),
+			PoolCheckHandler::<B, P>::api_operations(&mut gen),
+		)?;
+	}
 
-	router.add_route("/v1/", Arc::new(index_handler))?;
-	router.add_route("/v1/blocks/*", Arc::new(block_handler))?;
-	router.add_route("/v1/headers/*", Arc::new(header_handler))?;
-	router.add_route("/v1/chain", Arc::new(chain_tip_handler))?;
-	router.add_route("/v1/chain/outputs/*", Arc::new(output_handler))?;
-	router.add_route("/v1/chain/kernels/*", Arc::new(kernel_handler))?;
-	router.add_route("/v1/chain/compact", Arc::new(chain_compact_handler))?;
-	router.add_route("/v1/chain/validate", Arc::new(chain_validation_handler))?;
-	router.add_route("/v1/txhashset/*", Arc::new(txhashset_handler))?;
-	router.add_route("/v1/status", Arc::new(status_handler))?;
-	router.add_route("/v1/pool", Arc::new(pool_info_handler))?;
-	router.add_route("/v1/pool/push_tx", Arc::new(pool_push_handler))?;
-	router.add_route("/v1/peers/all", Arc::new(peers_all_handler))?;
-	router.add_route("/v1/peers/connected", Arc::new(peers_connected_handler))?;
-	router.add_route("/v1/peers/**", Arc::new(peer_handler))?;
-	router.add_route("/v1/version", Arc::new(version_handler))?;
+	let openapi_handler = OpenApiHandler {
+		doc: openapi::openapi_document(&operations, gen),
+	};
+	router.add_route("/v2/openapi.json", Arc::new(openapi_handler))?;
 	Ok(router)
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::core::core::hash::Hash;
+	use crate::core::core::{BlockHeader, BlockSums, Inputs, OutputIdentifier, Transaction};
+	use crate::core::pow;
+	use crate::handlers::chain_api::ChainUtxoDumpHandler;
+	use crate::pool::types::NoopPoolAdapter;
+	use crate::pool::{PoolConfig, PoolError, TransactionPool};
+	use serde_json::Value;
+
+	// Pool chain for the router, the tests don't process transactions
+	struct NoPoolChain;
+
+	impl BlockChain for NoPoolChain {
+		fn chain_head(&self) -> Result<BlockHeader, PoolError> {
+			Ok(BlockHeader::default())
+		}
+		fn get_block_header(&self, _hash: &Hash) -> Result<BlockHeader, PoolError> {
+			unimplemented!()
+		}
+		fn get_block_sums(&self, _hash: &Hash) -> Result<BlockSums, PoolError> {
+			unimplemented!()
+		}
+		fn validate_tx(&self, _tx: &Transaction) -> Result<(), PoolError> {
+			unimplemented!()
+		}
+		fn validate_inputs(&self, _inputs: &Inputs) -> Result<Vec<OutputIdentifier>, PoolError> {
+			unimplemented!()
+		}
+		fn verify_coinbase_maturity(&self, _inputs: &Inputs) -> Result<(), PoolError> {
+			unimplemented!()
+		}
+		fn verify_tx_lock_height(&self, _tx: &Transaction) -> Result<(), PoolError> {
+			unimplemented!()
+		}
+		fn replay_attack_check(&self, _tx: &Transaction) -> Result<(), PoolError> {
+			unimplemented!()
+		}
+	}
+
+	type TestPool = TransactionPool<NoPoolChain, NoopPoolAdapter>;
+
+	struct TestNode {
+		_dir: tempfile::TempDir,
+		chain: Arc<Chain>,
+		tx_pool: Arc<RwLock<TestPool>>,
+		peers: Arc<p2p::Peers>,
+		sync_state: Arc<SyncState>,
+	}
+
+	fn test_node() -> TestNode {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let dir = tempfile::tempdir().unwrap();
+		let chain = Chain::init(
+			dir.path().join("chain").to_str().unwrap().to_string(),
+			Arc::new(chain::types::NoopAdapter {}),
+			pow::mine_genesis_block().unwrap(),
+			pow::verify_size,
+			false,
+		)
+		.unwrap();
+		let tx_pool = TransactionPool::new(
+			PoolConfig::default(),
+			Arc::new(NoPoolChain),
+			Arc::new(NoopPoolAdapter {}),
+		);
+		let peers = p2p::Peers::new(
+			p2p::store::PeerStore::new(dir.path().join("peers").to_str().unwrap()).unwrap(),
+			Arc::new(p2p::DummyAdapter {}),
+			p2p::P2PConfig::default(),
+			Arc::new(StopState::new()),
+		);
+		TestNode {
+			_dir: dir,
+			chain: Arc::new(chain),
+			tx_pool: Arc::new(RwLock::new(tx_pool)),
+			peers: Arc::new(peers),
+			sync_state: Arc::new(SyncState::new()),
+		}
+	}
+
+	fn get(router: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+		let req = Request::get(uri).body(Body::empty()).unwrap();
+		let rt = tokio::runtime::Runtime::new().unwrap();
+		rt.block_on(async {
+			let resp = router.handle(req).await.unwrap();
+			let status = resp.status();
+			let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+			(status, body.to_vec())
+		})
+	}
+
+	fn get_json(router: &Router, uri: &str) -> Value {
+		let (status, body) = get(router, uri);
+		assert_eq!(status, StatusCode::OK);
+		serde_json::from_slice(&body).unwrap()
+	}
+
+	// OpenAPI paths with a sample value of the path parameters
+	fn served_paths(doc: &Value) -> Vec<String> {
+		doc["paths"]
+			.as_object()
+			.unwrap()
+			.keys()
+			.map(|path| {
+				path.split('/')
+					.map(|p| if p.starts_with('{') { "x" } else { p })
+					.collect::<Vec<_>>()
+					.join("/")
+			})
+			.collect()
+	}
+
+	#[test]
+	fn openapi_matches_routes() {
+		let node = test_node();
+		let router = build_router(
+			node.chain.clone(),
+			node.tx_pool.clone(),
+			node.peers.clone(),
+			node.sync_state.clone(),
+			false,
+		)
+		.unwrap();
+		let doc = get_json(&router, "http://127.0.0.1/v2/openapi.json");
+
+		// Every documented path is served
+		let paths = served_paths(&doc);
+		for path in &paths {
+			assert!(router.get(path).is_ok(), "{} is not served", path);
+		}
+
+		// Documented routes of the table
+		let mut documented: Vec<&str> = doc["paths"]
+			.as_object()
+			.unwrap()
+			.keys()
+			.map(|p| p.as_str())
+			.collect();
+		documented.sort();
+		assert_eq!(
+			documented,
+			vec![
+				"/v1/blocks/{id}",
+				"/v1/chain",
+				"/v1/chain/kernels/{excess}",
+				"/v1/chain/outputs/byheight",
+				"/v1/chain/outputs/byids",
+				"/v1/headers/{id}",
+				"/v1/pool",
+				"/v1/pool/push_tx",
+				"/v1/status",
+				"/v1/version",
+				"/v2/chain/fees",
+				"/v2/chain/index/kernel",
+				"/v2/chain/index/output",
+				"/v2/chain/kernels",
+				"/v2/chain/output_proof",
+				"/v2/chain/outputs",
+				"/v2/chain/stats",
+				"/v2/pool/check",
+				"/v2/pool/fee_estimate",
+			]
+		);
+
+		// The routes that are not served are not documented
+		let config = ReadOnlyApiConfig {
+			enabled: true,
+			..ReadOnlyApiConfig::default()
+		};
+		let router = build_read_only_router(
+			node.chain.clone(),
+			node.tx_pool.clone(),
+			node.peers.clone(),
+			node.sync_state.clone(),
+			&config,
+		)
+		.unwrap();
+		let doc = get_json(&router, "http://127.0.0.1/v2/openapi.json");
+		for path in served_paths(&doc) {
+			assert!(router.get(&path).is_ok(), "{} is not served", path);
+		}
+		assert!(doc["paths"]["/v1/chain"].is_object());
+		assert!(doc["paths"]["/v1/status"].is_null());
+		assert!(router.get("/v1/status").is_err());
+	}
+
+	#[test]
+	fn read_only_stream_and_probe_routes() {
+		let node = test_node();
+		let add_routes = |router: &mut Router, config: Option<&ReadOnlyApiConfig>| {
+			add_stream_and_probe_routes(
+				router,
+				config,
+				node.chain.clone(),
+				node.peers.clone(),
+				node.sync_state.clone(),
+				Arc::new(WsEventBus::new()),
+				Arc::new(TipEventBus::new()),
+				HealthConfig::default(),
+			)
+			.unwrap();
+		};
+		let routes = ["/v2/ws", "/v2/health", "/v2/ready", "/v1/chain/tip/stream"];
+
+		// Every route is served without the read-only profile
+		let mut router = build_router(
+			node.chain.clone(),
+			node.tx_pool.clone(),
+			node.peers.clone(),
+			node.sync_state.clone(),
+			false,
+		)
+		.unwrap();
+		add_routes(&mut router, None);
+		for route in &routes {
+			assert!(router.get(route).is_ok(), "{} is not served", route);
+		}
+
+		// Only the listed routes are served with the read-only profile
+		let config = ReadOnlyApiConfig {
+			enabled: true,
+			allowed_routes: vec!["/v1/chain".to_string(), "/v2/health".to_string()],
+			..ReadOnlyApiConfig::default()
+		};
+		let mut router = build_read_only_router(
+			node.chain.clone(),
+			node.tx_pool.clone(),
+			node.peers.clone(),
+			node.sync_state.clone(),
+			&config,
+		)
+		.unwrap();
+		add_routes(&mut router, Some(&config));
+		let (status, _) = get(&router, "http://127.0.0.1/v2/health");
+		assert_eq!(status, StatusCode::OK);
+		for route in &["/v2/ws", "/v2/ready", "/v1/chain/tip/stream", "/v1/status"] {
+			let (status, _) = get(&router, &format!("http://127.0.0.1{}", route));
+			assert_eq!(status, StatusCode::NOT_FOUND, "{} is served", route);
+		}
+	}
+
+	#[test]
+	fn pool_txs_page() {
+		let node = test_node();
+		let router = build_router(
+			node.chain.clone(),
+			node.tx_pool.clone(),
+			node.peers.clone(),
+			node.sync_state.clone(),
+			false,
+		)
+		.unwrap();
+
+		// Pool info without the transactions by default
+		let info = get_json(&router, "http://127.0.0.1/v1/pool");
+		assert_eq!(info["pool_size"], 0);
+		assert!(info.get("txs").is_none());
+
+		// The page is a part of the pool info
+		let info = get_json(
+			&router,
+			"http://127.0.0.1/v1/pool?offset=0&limit=10&sort=age&min_fee=1",
+		);
+		assert_eq!(info["pool_size"], 0);
+		assert_eq!(info["txs"]["total"], 0);
+		assert_eq!(info["txs"]["offset"], 0);
+		assert_eq!(info["txs"]["txs"].as_array().unwrap().len(), 0);
+
+		let (status, _) = get(&router, "http://127.0.0.1/v1/pool?sort=size");
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		// There is no separate route for the transactions
+		assert!(router.get("/v1/pool/txs").is_err());
+	}
+
+	#[test]
+	fn utxo_dump_in_data_dir() {
+		let node = test_node();
+		let handler = ChainUtxoDumpHandler {
+			chain: Arc::downgrade(&node.chain),
+		};
+
+		let info = handler.export_utxo_set("head.dump").unwrap();
+		let path = node._dir.path().join("utxo_dumps").join("head.dump");
+		assert_eq!(info.path, path.to_str().unwrap());
+		assert!(path.exists());
+
+		// Only the file name is accepted
+		for name in &[
+			"",
+			".",
+			"..",
+			"../head.dump",
+			"/tmp/head.dump",
+			"dir\\head.dump",
+		] {
+			assert!(matches!(
+				handler.export_utxo_set(name),
+				Err(Error::Argument(_))
+			));
+		}
+	}
+}
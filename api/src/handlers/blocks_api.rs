@@ -12,6 +12,7 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use super::openapi::{ApiDoc, ApiOperation};
 use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
 use crate::core::core::hash::Hash;
@@ -23,6 +24,7 @@ use crate::util;
 use crate::web::*;
 use hyper::{Body, Request, StatusCode};
 use regex::Regex;
+use schemars::gen::SchemaGenerator;
 use std::sync::Weak;
 
 pub const BLOCK_TRANSFER_LIMIT: u64 = 1000;
@@ -140,6 +142,18 @@ impl Handler for HeaderHandler {
 	}
 }
 
+impl ApiDoc for HeaderHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<BlockHeaderPrintable>(
+			gen,
+			"/v1/headers/*",
+			"/v1/headers/{id}",
+			"Block header",
+		)
+		.path_param("id", "Block hash, height or output commitment")]
+	}
+}
+
 /// Gets block details given either a hash or an unspent commit
 /// GET /v1/blocks/<hash>
 /// GET /v1/blocks/<height>
@@ -370,3 +384,14 @@ impl Handler for BlockHandler {
 		result_to_response(self.get_block(&h, include_proof, include_merkle_proof))
 	}
 }
+
+impl ApiDoc for BlockHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![
+			ApiOperation::get::<BlockPrintable>(gen, "/v1/blocks/*", "/v1/blocks/{id}", "Block")
+				.path_param("id", "Block hash, height or output commitment")
+				.query_param("include_proof", "Include the range proofs")
+				.query_param("no_merkle_proof", "Skip the outputs merkle proofs"),
+		]
+	}
+}
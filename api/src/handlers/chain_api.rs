@@ -13,16 +13,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::openapi::{ApiDoc, ApiOperation};
 use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::{pmmr, BlockHeader, KernelFeatures};
+use crate::core::{consensus, global};
+use crate::payment_proof;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
-use crate::util;
 use crate::util::secp::pedersen::Commitment;
+use crate::util::{self, Mutex, ToHex};
 use crate::web::*;
 use hyper::{Body, Request, StatusCode};
+use schemars::gen::SchemaGenerator;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::sync::Weak;
 
 /// Chain handler. Get the head details.
@@ -34,7 +45,7 @@ pub struct ChainHandler {
 impl ChainHandler {
 	pub fn get_tip(&self) -> Result<Tip, Error> {
 		let head = w(&self.chain)?
-			.head()
+			.node_head()
 			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
 		Ok(Tip::from_tip(head))
 	}
@@ -46,6 +57,337 @@ impl Handler for ChainHandler {
 	}
 }
 
+impl ApiDoc for ChainHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<Tip>(
+			gen,
+			"/v1/chain",
+			"/v1/chain",
+			"Chain head",
+		)]
+	}
+}
+
+/// Default number of the blocks in the fees summary
+const CHAIN_FEES_LAST_BLOCKS: u64 = 60;
+/// Fees summary is limited to a day of blocks
+const CHAIN_FEES_MAX_BLOCKS: u64 = 1440;
+
+/// Fees and fullness of the recent blocks, for the wallets fee estimation.
+/// GET /v2/chain/fees?last_blocks=60
+pub struct ChainFeesHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainFeesHandler {
+	pub fn get_fees(&self, last_blocks: u64) -> Result<ChainFees, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let last_blocks = cmp::min(cmp::max(last_blocks, 1), CHAIN_FEES_MAX_BLOCKS);
+		let max_block_weight = global::max_block_weight();
+
+		let mut blocks: Vec<BlockFees> = Vec::new();
+		let mut hash = head.last_block_h;
+		while (blocks.len() as u64) < last_blocks {
+			// Compacted node doesn't have the blocks below the horizon
+			let block = match chain.get_block(&hash) {
+				Ok(block) => block,
+				Err(_) => break,
+			};
+			blocks.push(BlockFees::from_block(&block, max_block_weight));
+			if block.header.height == 0 {
+				break;
+			}
+			hash = block.header.prev_hash;
+		}
+		Ok(ChainFees::from_blocks(blocks))
+	}
+}
+
+impl Handler for ChainFeesHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let last_blocks = parse_param_no_err!(params, "last_blocks", CHAIN_FEES_LAST_BLOCKS);
+		result_to_response(self.get_fees(last_blocks))
+	}
+}
+
+impl ApiDoc for ChainFeesHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<ChainFees>(
+			gen,
+			"/v2/chain/fees",
+			"/v2/chain/fees",
+			"Fees and fullness of the recent blocks",
+		)
+		.query_param("last_blocks", "Number of the blocks, default 60, max 1440")]
+	}
+}
+
+/// Default number of the blocks in the chain stats
+const CHAIN_STATS_WINDOW: u64 = 1440;
+/// Chain stats are limited to a week of blocks
+const CHAIN_STATS_MAX_WINDOW: u64 = 10080;
+/// Windows of the current head that are cached
+const CHAIN_STATS_CACHE_SIZE: usize = 16;
+/// Parts of the window in the difficulty trend
+const CHAIN_STATS_DIFFICULTY_SEGMENTS: usize = 10;
+/// Upper bounds of the block intervals distribution, in seconds
+const CHAIN_STATS_INTERVAL_BUCKETS: [i64; 6] = [15, 30, 60, 120, 300, 600];
+
+/// Aggregate statistics of the last blocks: block intervals, difficulty, fees, kernels
+/// and emission. The stats are cached until the head is changed. The samples of the
+/// blocks are kept up to the max window, so a new head reads the new blocks only.
+/// GET /v2/chain/stats?window=1440
+pub struct ChainStatsHandler {
+	pub chain: Weak<chain::Chain>,
+	/// Head hash and the stats of this head by the window
+	pub cache: Mutex<(Hash, HashMap<u64, ChainStats>)>,
+	/// Samples of the consecutive blocks up to the last processed head, oldest first
+	samples: Mutex<VecDeque<BlockSample>>,
+}
+
+/// Data of the block that goes into the chain stats
+#[derive(Clone)]
+struct BlockSample {
+	hash: Hash,
+	prev_hash: Hash,
+	height: u64,
+	interval: i64,
+	difficulty: u64,
+	emission: u64,
+	/// Fees and the transaction kernels, None if the node doesn't have the block
+	body: Option<(u64, u64)>,
+}
+
+impl BlockSample {
+	fn new(chain: &chain::Chain, header: &BlockHeader, prev: &BlockHeader) -> BlockSample {
+		BlockSample {
+			hash: header.hash(),
+			prev_hash: header.prev_hash,
+			height: header.height,
+			interval: header.timestamp.timestamp() - prev.timestamp.timestamp(),
+			difficulty: (header.total_difficulty() - prev.total_difficulty()).to_num(),
+			emission: consensus::calc_mwc_block_reward(header.height),
+			// Compacted node doesn't have the blocks below the horizon
+			body: chain.get_block(&header.hash()).ok().map(|block| {
+				(
+					block.total_fees(),
+					block.kernels().iter().filter(|k| !k.is_coinbase()).count() as u64,
+				)
+			}),
+		}
+	}
+}
+
+impl ChainStatsHandler {
+	pub fn new(chain: Weak<chain::Chain>) -> ChainStatsHandler {
+		ChainStatsHandler {
+			chain,
+			cache: Mutex::new((Hash::default(), HashMap::new())),
+			samples: Mutex::new(VecDeque::new()),
+		}
+	}
+
+	pub fn get_stats(&self, window: u64) -> Result<ChainStats, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let window = cmp::min(cmp::max(window, 1), CHAIN_STATS_MAX_WINDOW);
+		{
+			let cache = self.cache.lock();
+			if cache.0 == head.last_block_h {
+				if let Some(stats) = cache.1.get(&window) {
+					return Ok(stats.clone());
+				}
+			}
+		}
+
+		let stats = self.build_stats(&chain, &head.last_block_h, window)?;
+
+		let mut cache = self.cache.lock();
+		if cache.0 != head.last_block_h || cache.1.len() >= CHAIN_STATS_CACHE_SIZE {
+			*cache = (head.last_block_h, HashMap::new());
+		}
+		cache.1.insert(window, stats.clone());
+		Ok(stats)
+	}
+
+	/// Samples of the last `window` blocks up to the head, oldest first. The stored samples
+	/// are moved to the head, only the blocks that are not sampled yet are read.
+	fn window_samples(
+		&self,
+		chain: &chain::Chain,
+		head_header: &BlockHeader,
+		window: u64,
+	) -> Result<Vec<BlockSample>, Error> {
+		let read_err = |e: chain::Error| Error::Internal(format!("can't read header: {}", e));
+		let mut samples = self.samples.lock();
+
+		// New blocks down to the sampled one, newest first. The samples above the fork
+		// point are dropped on reorg.
+		let mut new_samples = vec![];
+		let mut header = head_header.clone();
+		let mut connected = false;
+		while header.height > 0 && (new_samples.len() as u64) < window {
+			let sampled = samples
+				.front()
+				.filter(|s| header.height >= s.height)
+				.map(|s| (header.height - s.height) as usize)
+				.filter(|idx| samples.get(*idx).map(|s| s.hash) == Some(header.hash()));
+			if let Some(idx) = sampled {
+				samples.truncate(idx + 1);
+				connected = true;
+				break;
+			}
+			let prev = chain.get_previous_header(&header).map_err(read_err)?;
+			new_samples.push(BlockSample::new(chain, &header, &prev));
+			header = prev;
+		}
+		if !connected {
+			samples.clear();
+		}
+		samples.extend(new_samples.into_iter().rev());
+
+		// Older blocks if the window is longer than the sampled one
+		while (samples.len() as u64) < window {
+			let prev_hash = match samples.front() {
+				Some(s) if s.height > 1 => s.prev_hash,
+				_ => break,
+			};
+			let header = chain.get_block_header(&prev_hash).map_err(read_err)?;
+			let prev = chain.get_previous_header(&header).map_err(read_err)?;
+			samples.push_front(BlockSample::new(chain, &header, &prev));
+		}
+		while samples.len() as u64 > CHAIN_STATS_MAX_WINDOW {
+			samples.pop_front();
+		}
+
+		let skip = samples.len().saturating_sub(window as usize);
+		Ok(samples.iter().skip(skip).cloned().collect())
+	}
+
+	fn build_stats(
+		&self,
+		chain: &chain::Chain,
+		head_hash: &Hash,
+		window: u64,
+	) -> Result<ChainStats, Error> {
+		let head_header = chain
+			.get_block_header(head_hash)
+			.map_err(|e| Error::Internal(format!("can't read header: {}", e)))?;
+		let samples = self.window_samples(chain, &head_header, window)?;
+
+		let intervals: Vec<i64> = samples.iter().map(|s| s.interval).collect();
+		// Oldest first
+		let difficulties: Vec<u64> = samples.iter().map(|s| s.difficulty).collect();
+		let emission: u64 = samples.iter().map(|s| s.emission).sum();
+		let mut blocks_with_body = 0;
+		let mut total_fees = 0;
+		let mut tx_kernels = 0;
+		for (fees, kernels) in samples.iter().filter_map(|s| s.body) {
+			blocks_with_body += 1;
+			total_fees += fees;
+			tx_kernels += kernels;
+		}
+		let blocks = intervals.len() as u64;
+
+		let mut distribution: Vec<IntervalBucket> = CHAIN_STATS_INTERVAL_BUCKETS
+			.iter()
+			.map(|b| IntervalBucket {
+				up_to_secs: Some(*b),
+				blocks: 0,
+			})
+			.chain(std::iter::once(IntervalBucket {
+				up_to_secs: None,
+				blocks: 0,
+			}))
+			.collect();
+		for interval in &intervals {
+			let idx = CHAIN_STATS_INTERVAL_BUCKETS
+				.iter()
+				.position(|b| interval <= b)
+				.unwrap_or(CHAIN_STATS_INTERVAL_BUCKETS.len());
+			distribution[idx].blocks += 1;
+		}
+		let mut sorted = intervals.clone();
+		sorted.sort_unstable();
+		let block_intervals = BlockIntervals {
+			average: if blocks > 0 {
+				intervals.iter().sum::<i64>() as f64 / blocks as f64
+			} else {
+				0.0
+			},
+			median: sorted.get(sorted.len() / 2).cloned().unwrap_or(0),
+			min: sorted.first().cloned().unwrap_or(0),
+			max: sorted.last().cloned().unwrap_or(0),
+			distribution,
+		};
+
+		let average = |d: &[u64]| {
+			if d.is_empty() {
+				0
+			} else {
+				(d.iter().map(|d| *d as u128).sum::<u128>() / d.len() as u128) as u64
+			}
+		};
+		let segment_len = cmp::max(
+			(difficulties.len() + CHAIN_STATS_DIFFICULTY_SEGMENTS - 1)
+				/ CHAIN_STATS_DIFFICULTY_SEGMENTS,
+			1,
+		);
+		let difficulty = DifficultyTrend {
+			first: difficulties.first().cloned().unwrap_or(0),
+			last: difficulties.last().cloned().unwrap_or(0),
+			average: average(&difficulties),
+			segments: difficulties.chunks(segment_len).map(average).collect(),
+		};
+
+		Ok(ChainStats {
+			window: blocks,
+			from_height: head_header.height + 1 - cmp::max(blocks, 1),
+			to_height: head_header.height,
+			to_hash: head_hash.to_hex(),
+			block_intervals,
+			difficulty,
+			blocks_with_body,
+			total_fees,
+			average_fees: if blocks_with_body > 0 {
+				total_fees / blocks_with_body
+			} else {
+				0
+			},
+			tx_kernels,
+			total_kernels: pmmr::n_leaves(head_header.kernel_mmr_size),
+			emission,
+			total_emission: consensus::calc_mwc_block_overage(head_header.height, true),
+		})
+	}
+}
+
+impl Handler for ChainStatsHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let window = parse_param_no_err!(params, "window", CHAIN_STATS_WINDOW);
+		result_to_response(self.get_stats(window))
+	}
+}
+
+impl ApiDoc for ChainStatsHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<ChainStats>(
+			gen,
+			"/v2/chain/stats",
+			"/v2/chain/stats",
+			"Aggregate statistics of the last blocks",
+		)
+		.query_param("window", "Number of the blocks, default 1440, max 10080")]
+	}
+}
+
 /// Chain validation handler.
 /// GET /v1/chain/validate
 pub struct ChainValidationHandler {
@@ -98,6 +440,83 @@ impl ChainResetHandler {
 	}
 }
 
+/// Chain UTXO set export. Writes the canonical UTXO set dump (see chain::utxo_dump)
+/// into the file of the node UTXO dumps directory (see Chain::get_utxo_dump_dir).
+pub struct ChainUtxoDumpHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainUtxoDumpHandler {
+	/// `file_name` is the name of the dump file, the paths are rejected
+	pub fn export_utxo_set(&self, file_name: &str) -> Result<UtxoDumpInfo, Error> {
+		// Windows separator is rejected at any host, the name must be a single component
+		if file_name.contains(|c| c == '/' || c == '\\')
+			|| Path::new(file_name).file_name() != Some(OsStr::new(file_name))
+		{
+			return Err(Error::Argument(format!(
+				"Invalid UTXO dump file name {}, the path is not accepted",
+				file_name
+			)));
+		}
+		let chain = w(&self.chain)?;
+		let dump_dir = chain.get_utxo_dump_dir();
+		fs::create_dir_all(&dump_dir).map_err(|e| {
+			Error::Internal(format!(
+				"Unable to create directory {}, {}",
+				dump_dir.display(),
+				e
+			))
+		})?;
+		let path = dump_dir.join(file_name);
+		let path = path
+			.to_str()
+			.ok_or_else(|| Error::Argument(format!("Invalid UTXO dump file name {}", file_name)))?;
+
+		let dump = chain
+			.export_utxo_set()
+			.map_err(|e| Error::Internal(format!("UTXO set export error, {}", e)))?;
+		let file = File::create(path)
+			.map_err(|e| Error::Internal(format!("Unable to create file {}, {}", path, e)))?;
+		let mut writer = BufWriter::new(file);
+		dump.write(&mut writer)
+			.map_err(|e| Error::Internal(format!("Unable to write UTXO dump, {}", e)))?;
+		writer
+			.flush()
+			.map_err(|e| Error::Internal(format!("Unable to write UTXO dump, {}", e)))?;
+		Ok(UtxoDumpInfo::from_dump(path, &dump))
+	}
+}
+
+/// Chain data snapshot. Writes the consistent copy of the chain data (see chain::snapshot)
+/// into the directory at the node host.
+pub struct ChainSnapshotHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainSnapshotHandler {
+	pub fn create_snapshot(&self, path: &str) -> Result<SnapshotInfo, Error> {
+		let snapshot = w(&self.chain)?
+			.create_snapshot(Path::new(path))
+			.map_err(|e| Error::Internal(format!("Chain snapshot error, {}", e)))?;
+		Ok(SnapshotInfo::from_snapshot(&snapshot))
+	}
+}
+
+/// UTXO archive. Writes the portable UTXO set (see chain::utxo_archive) into the file
+/// at the node host.
+pub struct ChainUtxoArchiveHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainUtxoArchiveHandler {
+	pub fn write_utxo_archive(&self, path: &str) -> Result<UtxoArchiveInfo, Error> {
+		let archive = w(&self.chain)?
+			.write_utxo_archive(Path::new(path))
+			.map_err(|e| Error::Internal(format!("UTXO archive error, {}", e)))?;
+		Ok(UtxoArchiveInfo::from_archive(&archive))
+	}
+}
+
 /// Chain compaction handler. Trigger a compaction of the chain state to regain
 /// storage space.
 /// POST /v1/chain/compact
@@ -335,8 +754,12 @@ impl OutputHandler {
 		Ok(outputs)
 	}
 
-	// returns outputs for a specified range of blocks
-	fn outputs_block_batch(&self, req: &Request<Body>) -> Result<Vec<BlockOutputs>, Error> {
+	// returns outputs for a specified range of blocks, the blocks are read while
+	// the response is streamed
+	fn outputs_block_batch(
+		&self,
+		req: &Request<Body>,
+	) -> Result<impl Iterator<Item = Result<BlockOutputs, Error>> + Send + 'static, Error> {
 		let mut commitments: Vec<Commitment> = vec![];
 
 		let query = must_get_query!(req);
@@ -360,16 +783,15 @@ impl OutputHandler {
 			start_height, end_height, commitments, include_rp,
 		);
 
-		let mut return_vec = vec![];
-		for i in (start_height..=end_height).rev() {
-			if let Ok(res) = self.outputs_at_height(i, commitments.clone(), include_rp) {
-				if !res.outputs.is_empty() {
-					return_vec.push(res);
-				}
+		let handler = OutputHandler {
+			chain: self.chain.clone(),
+		};
+		Ok((start_height..=end_height).rev().filter_map(move |i| {
+			match handler.outputs_at_height(i, commitments.clone(), include_rp) {
+				Ok(res) if !res.outputs.is_empty() => Some(Ok(res)),
+				_ => None,
 			}
-		}
-
-		Ok(return_vec)
+		}))
 	}
 
 	// returns outputs for a specified range of blocks
@@ -409,15 +831,384 @@ impl Handler for OutputHandler {
 	fn get(&self, req: Request<Body>) -> ResponseFuture {
 		match right_path_element!(req) {
 			"byids" => result_to_response(self.outputs_by_ids(&req)),
-			"byheight" => result_to_response(self.outputs_block_batch(&req)),
+			"byheight" => match self.outputs_block_batch(&req) {
+				Ok(outputs) => json_stream_response(outputs),
+				Err(e) => result_to_response::<()>(Err(e)),
+			},
 			_ => response(StatusCode::BAD_REQUEST, ""),
 		}
 	}
 }
 
+impl ApiDoc for OutputHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![
+			ApiOperation::get::<Vec<Output>>(
+				gen,
+				"/v1/chain/outputs/*",
+				"/v1/chain/outputs/byids",
+				"Unspent outputs by commitments",
+			)
+			.query_param("id", "Output commitments, comma separated or repeated"),
+			ApiOperation::get::<Vec<BlockOutputs>>(
+				gen,
+				"/v1/chain/outputs/*",
+				"/v1/chain/outputs/byheight",
+				"Outputs of the blocks height range",
+			)
+			.query_param("start_height", "First block height")
+			.query_param("end_height", "Last block height")
+			.query_param("id", "Only these output commitments")
+			.query_param("include_rp", "Include the range proofs"),
+		]
+	}
+}
+
+/// Kernel features page is limited by the number of the blocks
+const CHAIN_KERNELS_MAX_BLOCKS: u64 = 1000;
+
+/// Decoded kernel features of the blocks height range: plain, coinbase, height locked
+/// and NRD with their lock heights. Paginated by the blocks, use `next_height` from the
+/// response as the next `start_height`.
+/// GET /v2/chain/kernels?start_height=101&end_height=200
+pub struct ChainKernelsHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainKernelsHandler {
+	pub fn get_kernel_features(
+		&self,
+		start_height: u64,
+		end_height: u64,
+	) -> Result<KernelFeaturesByHeight, Error> {
+		if start_height > end_height {
+			return Err(Error::RequestError(format!(
+				"start_height {} is greater than end_height {}",
+				start_height, end_height
+			)));
+		}
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let end_height = cmp::min(end_height, head.height);
+
+		let mut blocks: Vec<BlockKernelFeatures> = Vec::new();
+		let mut last_retrieved_height = start_height.saturating_sub(1);
+		let mut height = start_height;
+		while height <= end_height && (blocks.len() as u64) < CHAIN_KERNELS_MAX_BLOCKS {
+			let header = chain
+				.get_header_by_height(height)
+				.map_err(|e| Error::NotFound(format!("Header at height {}, {}", height, e)))?;
+			let hash = header.hash();
+			// Compacted node doesn't have the blocks below the horizon
+			let block = chain.get_block(&hash).map_err(|e| {
+				Error::NotFound(format!(
+					"Block at height {} for hash {}, {}",
+					height, hash, e
+				))
+			})?;
+			blocks.push(BlockKernelFeatures {
+				height,
+				hash: hash.to_hex(),
+				kernels: block
+					.kernels()
+					.iter()
+					.map(|k| KernelFeaturesPrintable::from_kernel(k, height))
+					.collect(),
+			});
+			last_retrieved_height = height;
+			height += 1;
+		}
+
+		Ok(KernelFeaturesByHeight {
+			last_retrieved_height,
+			next_height: if height <= end_height {
+				Some(height)
+			} else {
+				None
+			},
+			blocks,
+		})
+	}
+}
+
+impl Handler for ChainKernelsHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let start_height = parse_param_no_err!(params, "start_height", 0);
+		let end_height = parse_param_no_err!(params, "end_height", start_height);
+		result_to_response(self.get_kernel_features(start_height, end_height))
+	}
+}
+
+impl ApiDoc for ChainKernelsHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<KernelFeaturesByHeight>(
+			gen,
+			"/v2/chain/kernels",
+			"/v2/chain/kernels",
+			"Decoded kernel features and lock heights of the blocks height range",
+		)
+		.query_param("start_height", "First block height")
+		.query_param("end_height", "Last block height, default start_height")]
+	}
+}
+
+/// Default number of the outputs in the outputs by height page
+const CHAIN_OUTPUTS_PAGE: u64 = 1000;
+/// Page is limited, the single block can be larger, it is never split
+const CHAIN_OUTPUTS_MAX_PAGE: u64 = 10_000;
+
+/// Outputs created in the blocks height range with their block positions, paginated by
+/// the whole blocks. Use `next_height` from the response as the next `start_height`.
+/// GET /v2/chain/outputs?start_height=101&end_height=200&include_proof=true&max=1000
+pub struct ChainOutputsHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainOutputsHandler {
+	pub fn get_outputs_by_height(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_proof: bool,
+		max: u64,
+	) -> Result<OutputsByHeight, Error> {
+		if start_height > end_height {
+			return Err(Error::RequestError(format!(
+				"start_height {} is greater than end_height {}",
+				start_height, end_height
+			)));
+		}
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let end_height = cmp::min(end_height, head.height);
+		let max = cmp::min(cmp::max(max, 1), CHAIN_OUTPUTS_MAX_PAGE);
+
+		let mut outputs: Vec<BlockOutputPosition> = Vec::new();
+		let mut last_retrieved_height = start_height.saturating_sub(1);
+		let mut height = start_height;
+		while height <= end_height && (outputs.len() as u64) < max {
+			let header = chain
+				.get_header_by_height(height)
+				.map_err(|e| Error::NotFound(format!("Header at height {}, {}", height, e)))?;
+			let hash = header.hash();
+			// Compacted node doesn't have the blocks below the horizon
+			let block = chain.get_block(&hash).map_err(|e| {
+				Error::NotFound(format!(
+					"Block at height {} for hash {}, {}",
+					height, hash, e
+				))
+			})?;
+			for (output_index, output) in block.outputs().iter().enumerate() {
+				let output = OutputPrintable::from_output(
+					output,
+					&chain,
+					Some(&header),
+					include_proof,
+					false,
+				)
+				.map_err(|e| {
+					Error::Internal(format!("chain read outputs from block error, {}", e))
+				})?;
+				outputs.push(BlockOutputPosition {
+					block_height: height,
+					block_hash: hash.to_hex(),
+					output_index: output_index as u64,
+					output,
+				});
+			}
+			last_retrieved_height = height;
+			height += 1;
+		}
+
+		Ok(OutputsByHeight {
+			last_retrieved_height,
+			next_height: if height <= end_height {
+				Some(height)
+			} else {
+				None
+			},
+			outputs,
+		})
+	}
+}
+
+impl Handler for ChainOutputsHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let start_height = parse_param_no_err!(params, "start_height", 0);
+		let end_height = parse_param_no_err!(params, "end_height", start_height);
+		let include_proof = parse_param_no_err!(params, "include_proof", false);
+		let max = parse_param_no_err!(params, "max", CHAIN_OUTPUTS_PAGE);
+		result_to_response(self.get_outputs_by_height(start_height, end_height, include_proof, max))
+	}
+}
+
+impl ApiDoc for ChainOutputsHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<OutputsByHeight>(
+			gen,
+			"/v2/chain/outputs",
+			"/v2/chain/outputs",
+			"Outputs of the blocks height range with their block positions",
+		)
+		.query_param("start_height", "First block height")
+		.query_param("end_height", "Last block height, default start_height")
+		.query_param("include_proof", "Include the range proofs")
+		.query_param("max", "Page size, default 1000, max 10000")]
+	}
+}
+
+/// Merkle proof of the unspent output for the output root of the chain head. The proof can be
+/// verified without trusting the node with the returned header, if this header is on the chain.
+/// GET /v2/chain/output_proof?commit=XXX
+pub struct ChainOutputProofHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainOutputProofHandler {
+	pub fn get_output_proof(&self, commit_s: &str) -> Result<OutputMerkleProof, Error> {
+		let commit = parse_commit(commit_s)?;
+		let chain = w(&self.chain)?;
+		let (pos, proof, header) = chain
+			.get_output_merkle_proof(commit)
+			.map_err(|e| {
+				Error::Internal(format!(
+					"Unable to build the merkle proof for {}, {}",
+					commit_s, e
+				))
+			})?
+			.ok_or_else(|| Error::NotFound(format!("unspent output {}", commit_s)))?;
+		Ok(OutputMerkleProof {
+			commit: commit_s.to_string(),
+			height: pos.height,
+			mmr_index: pos.pos,
+			mmr_size: proof.mmr_size,
+			path: proof.path.iter().map(|h| h.to_hex()).collect(),
+			proof: proof.to_hex(),
+			header: BlockHeaderPrintable::from_header(&header),
+		})
+	}
+}
+
+impl Handler for ChainOutputProofHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		match params.get("commit") {
+			Some(commit) => result_to_response(self.get_output_proof(commit)),
+			None => result_to_response::<OutputMerkleProof>(Err(Error::RequestError(
+				"missing parameter 'commit'".to_string(),
+			))),
+		}
+	}
+}
+
+impl ApiDoc for ChainOutputProofHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<OutputMerkleProof>(
+			gen,
+			"/v2/chain/output_proof",
+			"/v2/chain/output_proof",
+			"Merkle proof of the unspent output",
+		)
+		.query_param("commit", "Output commitment, hex")]
+	}
+}
+
+/// Kernel excess and output commitment index lookups. The index is maintained only if
+/// `enable_index` is set in the node config, it covers the blocks accepted after that.
+/// GET /v2/chain/index/kernel?excess=XXX
+/// GET /v2/chain/index/output?commit=XXX
+/// Main chain blocks with the kernel or output are returned, the output can be spent already.
+pub struct ChainIndexHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainIndexHandler {
+	pub fn get_kernel_blocks(&self, excess_s: &str) -> Result<IndexLookup, Error> {
+		let excess = parse_commit(excess_s)?;
+		let headers = w(&self.chain)?
+			.get_kernel_index(&excess)
+			.map_err(|e| Error::Internal(format!("Kernel index lookup failed, {}", e)))?;
+		Ok(IndexLookup::new(excess_s, &headers))
+	}
+
+	pub fn get_output_blocks(&self, commit_s: &str) -> Result<IndexLookup, Error> {
+		let commit = parse_commit(commit_s)?;
+		let headers = w(&self.chain)?
+			.get_output_index(&commit)
+			.map_err(|e| Error::Internal(format!("Output index lookup failed, {}", e)))?;
+		Ok(IndexLookup::new(commit_s, &headers))
+	}
+}
+
+impl Handler for ChainIndexHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let (param, res) = match right_path_element!(req) {
+			"kernel" => (
+				"excess",
+				params.get("excess").map(|e| self.get_kernel_blocks(e)),
+			),
+			"output" => (
+				"commit",
+				params.get("commit").map(|c| self.get_output_blocks(c)),
+			),
+			_ => return response(StatusCode::BAD_REQUEST, ""),
+		};
+		match res {
+			Some(res) => result_to_response(res),
+			None => result_to_response::<IndexLookup>(Err(Error::RequestError(format!(
+				"missing parameter '{}'",
+				param
+			)))),
+		}
+	}
+}
+
+impl ApiDoc for ChainIndexHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![
+			ApiOperation::get::<IndexLookup>(
+				gen,
+				"/v2/chain/index/*",
+				"/v2/chain/index/kernel",
+				"Blocks with the kernel",
+			)
+			.query_param("excess", "Kernel excess, hex"),
+			ApiOperation::get::<IndexLookup>(
+				gen,
+				"/v2/chain/index/*",
+				"/v2/chain/index/output",
+				"Blocks with the output",
+			)
+			.query_param("commit", "Output commitment, hex"),
+		]
+	}
+}
+
+fn parse_commit(commit_s: &str) -> Result<Commitment, Error> {
+	let commit = util::from_hex(commit_s)
+		.map_err(|e| Error::RequestError(format!("invalid commit hex {}, {}", commit_s, e)))?;
+	if commit.len() != 33 {
+		return Err(Error::RequestError(format!(
+			"invalid commit {}, get length {}, expected 33",
+			commit_s,
+			commit.len()
+		)));
+	}
+	Ok(Commitment::from_vec(commit))
+}
+
 /// Kernel handler, search for a kernel by excess commitment
 /// GET /v1/chain/kernels/XXX?min_height=YYY&max_height=ZZZ
-/// The `min_height` and `max_height` parameters are optional
+/// GET /v1/chain/kernels/XXX?merkle_proof=true&proof_height=NNN
+/// The `min_height` and `max_height` parameters are optional. With `merkle_proof` the kernel
+/// inclusion proof is returned for the header at `proof_height` (default is the kernel block).
 pub struct KernelHandler {
 	pub chain: Weak<chain::Chain>,
 }
@@ -488,8 +1279,57 @@ impl KernelHandler {
 				tx_kernel,
 				height,
 				mmr_index,
+				merkle_proof: None,
 			});
-		Ok(kernel)
+
+		// Optional inclusion proof, by default for the block of the kernel
+		let params = QueryParams::from(req.uri().query());
+		let merkle_proof = parse_param_no_err!(params, "merkle_proof", false);
+		match kernel {
+			Some(mut kernel) if merkle_proof => {
+				let proof_height = match params.get("proof_height") {
+					Some(hs) => hs.parse().map_err(|e| {
+						Error::RequestError(format!(
+							"invalid parameter 'proof_height' value {}, {}",
+							hs, e
+						))
+					})?,
+					None => kernel.height,
+				};
+				kernel.merkle_proof = Some(self.get_merkle_proof(
+					&chain,
+					kernel.mmr_index,
+					kernel.height,
+					proof_height,
+				)?);
+				Ok(Some(kernel))
+			}
+			kernel => Ok(kernel),
+		}
+	}
+
+	fn get_merkle_proof(
+		&self,
+		chain: &chain::Chain,
+		mmr_index: u64,
+		kernel_height: u64,
+		proof_height: u64,
+	) -> Result<KernelMerkleProof, Error> {
+		if proof_height < kernel_height {
+			return Err(Error::RequestError(format!(
+				"proof_height {} is below the kernel height {}",
+				proof_height, kernel_height
+			)));
+		}
+		let header = chain
+			.get_header_by_height(proof_height)
+			.map_err(|e| Error::NotFound(format!("header at height {}, {}", proof_height, e)))?;
+		let (proof, peaks) = chain
+			.get_kernel_merkle_proof(mmr_index, &header)
+			.map_err(|e| {
+				Error::Internal(format!("Unable to build the kernel merkle proof, {}", e))
+			})?;
+		Ok(KernelMerkleProof::new(&header, &proof, &peaks))
 	}
 
 	pub fn get_kernel_v2(
@@ -522,9 +1362,50 @@ impl KernelHandler {
 				tx_kernel,
 				height,
 				mmr_index,
+				merkle_proof: None,
 			});
 		kernel.ok_or_else(|| Error::NotFound(format!("kernel value for excess {}", excess_s)))
 	}
+
+	pub fn verify_payment_proof(
+		&self,
+		excess_s: String,
+		amount: u64,
+		fee: Option<u64>,
+		signatures: PaymentProofSignatures,
+	) -> Result<PaymentProofVerification, Error> {
+		let kernel = self.get_kernel_v2(excess_s.clone(), None, None)?;
+		let sender_signed =
+			payment_proof::verify_signatures(amount, &kernel.tx_kernel.excess, &signatures)?;
+
+		let kernel_fee = match kernel.tx_kernel.features {
+			KernelFeatures::Coinbase => 0,
+			KernelFeatures::Plain { fee }
+			| KernelFeatures::HeightLocked { fee, .. }
+			| KernelFeatures::NoRecentDuplicate { fee, .. } => fee.fee(kernel.height),
+		};
+		if let Some(fee) = fee {
+			if fee != kernel_fee {
+				return Err(Error::Argument(format!(
+					"kernel {} fee is {}, expected {}",
+					excess_s, kernel_fee, fee
+				)));
+			}
+		}
+
+		let head = w(&self.chain)?
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		Ok(PaymentProofVerification {
+			excess: excess_s,
+			amount,
+			fee: kernel_fee,
+			height: kernel.height,
+			mmr_index: kernel.mmr_index,
+			confirmations: (head.height + 1).saturating_sub(kernel.height),
+			sender_signed,
+		})
+	}
 }
 
 impl Handler for KernelHandler {
@@ -532,3 +1413,19 @@ impl Handler for KernelHandler {
 		result_to_response(self.get_kernel(req))
 	}
 }
+
+impl ApiDoc for KernelHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<LocatedTxKernel>(
+			gen,
+			"/v1/chain/kernels/*",
+			"/v1/chain/kernels/{excess}",
+			"Kernel by excess commitment",
+		)
+		.path_param("excess", "Kernel excess, hex")
+		.query_param("min_height", "Search from this height")
+		.query_param("max_height", "Search up to this height")
+		.query_param("merkle_proof", "Include the kernel inclusion proof")
+		.query_param("proof_height", "Proof for the header at this height")]
+	}
+}
@@ -0,0 +1,212 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Health and readiness probes for the orchestration (Kubernetes, systemd watchdogs).
+//! Both endpoints answer 200 when the check passes and 503 otherwise, the body has
+//! the details. They don't require the auth, so the probes can call them.
+
+use super::utils::w;
+use crate::chain::{Chain, SyncState};
+use crate::p2p;
+use crate::rest::Error;
+use crate::router::{Handler, ResponseFuture};
+use crate::types::{NodeHealth, NodeReadiness};
+use crate::web::*;
+use hyper::{Body, Request, StatusCode};
+use serde::Serialize;
+use std::sync::Weak;
+
+/// Thresholds of the readiness check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HealthConfig {
+	/// Node is ready when its head is within that many blocks of the height reached by
+	/// the majority of the connected peers. Default: 5
+	pub max_blocks_behind: u64,
+	/// Node is ready with at least that many connected peers. Default: 1
+	pub min_peers: usize,
+}
+
+impl Default for HealthConfig {
+	fn default() -> HealthConfig {
+		HealthConfig {
+			max_blocks_behind: 5,
+			min_peers: 1,
+		}
+	}
+}
+
+// 200 if the check passed, 503 otherwise
+fn probe_response<T: Serialize>(ok: bool, body: &T) -> ResponseFuture {
+	let status = if ok {
+		StatusCode::OK
+	} else {
+		StatusCode::SERVICE_UNAVAILABLE
+	};
+	match serde_json::to_string_pretty(body) {
+		Ok(json) => response(status, json),
+		Err(e) => response(
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("Unable to build respond json, {}", e),
+		),
+	}
+}
+
+/// Process is alive and the chain db is writable
+/// GET /v2/health
+pub struct HealthHandler {
+	pub chain: Weak<Chain>,
+}
+
+impl HealthHandler {
+	pub fn get_health(&self) -> NodeHealth {
+		let res = w(&self.chain).and_then(|chain| {
+			chain
+				.check_store_writable()
+				.map_err(|e| Error::Internal(format!("Chain db is not writable, {}", e)))
+		});
+		match res {
+			Ok(_) => NodeHealth {
+				healthy: true,
+				db_writable: true,
+				error: None,
+			},
+			Err(e) => NodeHealth {
+				healthy: false,
+				db_writable: false,
+				error: Some(e.to_string()),
+			},
+		}
+	}
+}
+
+impl Handler for HealthHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		let health = self.get_health();
+		probe_response(health.healthy, &health)
+	}
+}
+
+/// Node is synced close to the network tip and has enough peers
+/// GET /v2/ready
+pub struct ReadyHandler {
+	pub chain: Weak<Chain>,
+	pub peers: Weak<p2p::Peers>,
+	pub sync_state: Weak<SyncState>,
+	pub config: HealthConfig,
+}
+
+impl ReadyHandler {
+	pub fn get_readiness(&self) -> Result<NodeReadiness, Error> {
+		let height = w(&self.chain)?
+			.head()
+			.map_err(|e| Error::Internal(format!("Unable to get chain tip, {}", e)))?
+			.height;
+		let peers = w(&self.peers)?;
+		let heights: Vec<u64> = peers.iter().connected().map(|p| p.info.height()).collect();
+		let syncing = w(&self.sync_state)?.is_syncing();
+		Ok(readiness(&self.config, height, &heights, syncing))
+	}
+}
+
+/// Readiness by our head height, the connected peers heights and the sync state
+pub fn readiness(
+	config: &HealthConfig,
+	height: u64,
+	peer_heights: &[u64],
+	syncing: bool,
+) -> NodeReadiness {
+	// A peer can advertise any height, the network height is the one that more than
+	// half of the connected peers have reached
+	let mut heights = peer_heights.to_vec();
+	heights.sort_unstable_by(|a, b| b.cmp(a));
+	let network_height = heights
+		.get(heights.len() / 2)
+		.cloned()
+		.unwrap_or(0)
+		.max(height);
+	let blocks_behind = network_height - height;
+	let mut reasons = vec![];
+	if syncing {
+		reasons.push("node is syncing".to_string());
+	}
+	if blocks_behind > config.max_blocks_behind {
+		reasons.push(format!(
+			"{} blocks behind the network, the limit is {}",
+			blocks_behind, config.max_blocks_behind
+		));
+	}
+	if peer_heights.len() < config.min_peers {
+		reasons.push(format!(
+			"{} connected peers, at least {} are required",
+			peer_heights.len(),
+			config.min_peers
+		));
+	}
+	NodeReadiness {
+		ready: reasons.is_empty(),
+		syncing,
+		height,
+		network_height,
+		blocks_behind,
+		connected_peers: peer_heights.len(),
+		reasons,
+	}
+}
+
+impl Handler for ReadyHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		match self.get_readiness() {
+			Ok(readiness) => probe_response(readiness.ready, &readiness),
+			Err(e) => response(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn node_readiness() {
+		let config = HealthConfig::default();
+
+		let r = readiness(&config, 100, &[103], false);
+		assert!(r.ready);
+		assert_eq!(r.network_height, 103);
+		assert_eq!(r.blocks_behind, 3);
+
+		let r = readiness(&config, 100, &[106, 100, 107], false);
+		assert!(!r.ready);
+		assert_eq!(r.network_height, 106);
+		assert_eq!(r.reasons.len(), 1);
+
+		// a single peer that advertises a far height is outvoted
+		let r = readiness(&config, 100, &[100, 1_000_000], false);
+		assert!(r.ready);
+		assert_eq!(r.network_height, 100);
+		let r = readiness(&config, 100, &[101, 1_000_000, 102], false);
+		assert!(r.ready);
+		assert_eq!(r.network_height, 102);
+
+		// peers behind us don't make us behind
+		let r = readiness(&config, 100, &[90], false);
+		assert!(r.ready);
+		assert_eq!(r.blocks_behind, 0);
+
+		let r = readiness(&config, 100, &[], true);
+		assert!(!r.ready);
+		assert_eq!(r.reasons.len(), 2);
+	}
+}
@@ -0,0 +1,236 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenAPI 3.0 document of the REST API. Every handler describes its operations with
+//! `ApiDoc` next to its implementation, the schemas are derived from the response types
+//! with `JsonSchema`. The document is served at `/v2/openapi.json`.
+
+use crate::rest::*;
+use crate::router::{Handler, ResponseFuture};
+use crate::web::*;
+use hyper::{Body, Request};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+/// OpenAPI version of the document
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Request parameter
+pub struct ApiParam {
+	name: &'static str,
+	/// `path` or `query`
+	location: &'static str,
+	description: &'static str,
+}
+
+/// REST API operation
+pub struct ApiOperation {
+	/// Router route, like `/v1/blocks/*`
+	pub route: &'static str,
+	method: &'static str,
+	/// OpenAPI path, like `/v1/blocks/{id}`
+	path: &'static str,
+	summary: &'static str,
+	params: Vec<ApiParam>,
+	request: Option<Value>,
+	response: Option<Value>,
+}
+
+impl ApiOperation {
+	/// GET operation with the json response
+	pub fn get<T: JsonSchema>(
+		gen: &mut SchemaGenerator,
+		route: &'static str,
+		path: &'static str,
+		summary: &'static str,
+	) -> ApiOperation {
+		ApiOperation {
+			route,
+			method: "get",
+			path,
+			summary,
+			params: vec![],
+			request: None,
+			response: Some(schema_for::<T>(gen)),
+		}
+	}
+
+	/// POST operation with the json body and without the response
+	pub fn post<T: JsonSchema>(
+		gen: &mut SchemaGenerator,
+		route: &'static str,
+		path: &'static str,
+		summary: &'static str,
+	) -> ApiOperation {
+		ApiOperation {
+			route,
+			method: "post",
+			path,
+			summary,
+			params: vec![],
+			request: Some(schema_for::<T>(gen)),
+			response: None,
+		}
+	}
+
+	/// Json response of the operation
+	pub fn with_response<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> ApiOperation {
+		self.response = Some(schema_for::<T>(gen));
+		self
+	}
+
+	pub fn path_param(mut self, name: &'static str, description: &'static str) -> ApiOperation {
+		self.params.push(ApiParam {
+			name,
+			location: "path",
+			description,
+		});
+		self
+	}
+
+	pub fn query_param(mut self, name: &'static str, description: &'static str) -> ApiOperation {
+		self.params.push(ApiParam {
+			name,
+			location: "query",
+			description,
+		});
+		self
+	}
+
+	fn to_json(&self) -> Value {
+		let params: Vec<Value> = self
+			.params
+			.iter()
+			.map(|p| {
+				json!({
+					"name": p.name,
+					"in": p.location,
+					"required": p.location == "path",
+					"description": p.description,
+					"schema": { "type": "string" },
+				})
+			})
+			.collect();
+		let mut responses = Map::new();
+		match &self.response {
+			Some(schema) => responses.insert(
+				"200".to_string(),
+				json!({
+					"description": "OK",
+					"content": { "application/json": { "schema": schema } },
+				}),
+			),
+			None => responses.insert("200".to_string(), json!({ "description": "OK" })),
+		};
+		responses.insert(
+			"400".to_string(),
+			json!({ "description": "Invalid request" }),
+		);
+		let mut op = json!({
+			"summary": self.summary,
+			"parameters": params,
+			"responses": responses,
+		});
+		if let Some(schema) = &self.request {
+			op["requestBody"] = json!({
+				"required": true,
+				"content": { "application/json": { "schema": schema } },
+			});
+		}
+		op
+	}
+}
+
+/// Operations of the handler for the OpenAPI document
+pub trait ApiDoc {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation>;
+}
+
+/// Schemas are placed into `components/schemas`
+pub fn schema_generator() -> SchemaGenerator {
+	SchemaSettings::openapi3().into_generator()
+}
+
+fn schema_for<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+	serde_json::to_value(gen.subschema_for::<T>()).unwrap_or(Value::Null)
+}
+
+/// Build the OpenAPI document from the operations
+pub fn openapi_document(operations: &[ApiOperation], gen: SchemaGenerator) -> Value {
+	let mut paths = Map::new();
+	for op in operations {
+		let path = paths
+			.entry(op.path.to_string())
+			.or_insert_with(|| Value::Object(Map::new()));
+		path[op.method] = op.to_json();
+	}
+	json!({
+		"openapi": OPENAPI_VERSION,
+		"info": {
+			"title": "MWC Node API",
+			"version": env!("CARGO_PKG_VERSION"),
+		},
+		"paths": paths,
+		"components": {
+			"schemas": serde_json::to_value(gen.definitions()).unwrap_or(Value::Null),
+		},
+	})
+}
+
+/// OpenAPI document of the served routes
+/// GET /v2/openapi.json
+pub struct OpenApiHandler {
+	pub doc: Value,
+}
+
+impl Handler for OpenApiHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		json_response(&self.doc)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::types::{BlockHeaderPrintable, Tip};
+
+	#[test]
+	fn openapi_doc() {
+		let mut gen = schema_generator();
+		let ops = vec![
+			ApiOperation::get::<Tip>(&mut gen, "/v1/chain", "/v1/chain", "Chain head"),
+			ApiOperation::get::<BlockHeaderPrintable>(
+				&mut gen,
+				"/v1/headers/*",
+				"/v1/headers/{id}",
+				"Block header",
+			)
+			.path_param("id", "Block hash, height or output commitment"),
+		];
+		let doc = openapi_document(&ops, gen);
+		assert_eq!(doc["openapi"], OPENAPI_VERSION);
+		assert_eq!(
+			doc["paths"]["/v1/chain"]["get"]["responses"]["200"]["content"]["application/json"]
+				["schema"]["$ref"],
+			"#/components/schemas/Tip"
+		);
+		assert_eq!(
+			doc["paths"]["/v1/headers/{id}"]["get"]["parameters"][0]["in"],
+			"path"
+		);
+		assert!(doc["components"]["schemas"]["Tip"]["properties"]["height"].is_object());
+		assert!(doc["components"]["schemas"]["BlockHeaderPrintable"].is_object());
+	}
+}
@@ -14,8 +14,10 @@
 // limitations under the License.
 
 use super::utils::w;
-use crate::p2p::types::{PeerAddr, PeerInfoDisplay, ReasonForBan};
-use crate::p2p::{self, PeerData};
+use crate::p2p::bandwidth::{BandwidthLimits, MAX_RELAY_RATIO};
+use crate::p2p::traffic::TrafficReport;
+use crate::p2p::types::{PeerAddr, PeerInfoDisplay, PeerStatsDisplay, ReasonForBan};
+use crate::p2p::{self, PeerChanges, PeerData};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::web::*;
@@ -23,6 +25,7 @@ use hyper::{Body, Request, StatusCode};
 use mwc_p2p::types::Direction;
 use mwc_p2p::types::PeerInfoDisplayLegacy;
 use mwc_p2p::Capabilities;
+use std::cmp;
 use std::net::SocketAddr;
 use std::sync::Weak;
 
@@ -81,6 +84,20 @@ impl PeersConnectedHandler {
 		}
 		Ok(peers_ret)
 	}
+
+	pub fn get_peer_stats(&self) -> Result<Vec<PeerStatsDisplay>, Error> {
+		let peers = w(&self.peers)?
+			.iter()
+			.connected()
+			.into_iter()
+			.map(|p| p.stats())
+			.collect();
+		Ok(peers)
+	}
+
+	pub fn get_peer_changes(&self, since: u64) -> Result<PeerChanges, Error> {
+		Ok(w(&self.peers)?.peer_changes_since(since))
+	}
 }
 
 impl Handler for PeersConnectedHandler {
@@ -124,6 +141,30 @@ impl Handler for PeersConnectedHandler {
 	}
 }
 
+/// Default and max number of peers in the traffic report
+const TRAFFIC_REPORT_TOP: usize = 20;
+const TRAFFIC_REPORT_MAX_TOP: usize = 1000;
+
+/// Top talkers, bytes in/out per peer for the last hour by message class
+/// GET /v2/p2p/traffic?top=20
+pub struct PeersTrafficHandler {
+	pub peers: Weak<p2p::Peers>,
+}
+
+impl PeersTrafficHandler {
+	pub fn get_traffic(&self, top: usize) -> Result<TrafficReport, Error> {
+		Ok(w(&self.peers)?.traffic_report(cmp::min(top, TRAFFIC_REPORT_MAX_TOP)))
+	}
+}
+
+impl Handler for PeersTrafficHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let top = parse_param_no_err!(params, "top", TRAFFIC_REPORT_TOP);
+		result_to_response(self.get_traffic(top))
+	}
+}
+
 /// Peer operations
 /// GET /v1/peers/10.12.12.13
 /// POST /v1/peers/10.12.12.13/ban
@@ -169,6 +210,40 @@ impl PeerHandler {
 			))
 		})
 	}
+
+	pub fn set_tor_only(&self, tor_only: bool) -> Result<usize, Error> {
+		w(&self.peers)?
+			.set_tor_only(tor_only)
+			.map_err(|e| Error::Internal(format!("Unable to switch Tor only mode, {}", e)))
+	}
+
+	pub fn get_bandwidth_limits(&self) -> Result<BandwidthLimits, Error> {
+		Ok(w(&self.peers)?.bandwidth().limits())
+	}
+
+	pub fn set_bandwidth_limits(
+		&self,
+		limit_kbps: u64,
+		relay_ratio: u8,
+	) -> Result<BandwidthLimits, Error> {
+		if relay_ratio > MAX_RELAY_RATIO {
+			return Err(Error::Argument(format!(
+				"relay_ratio {} is too high, max is {}",
+				relay_ratio, MAX_RELAY_RATIO
+			)));
+		}
+		let bandwidth = w(&self.peers)?.bandwidth();
+		bandwidth.set_limits(BandwidthLimits {
+			limit_kbps,
+			relay_ratio,
+		});
+		let limits = bandwidth.limits();
+		info!(
+			"Bandwidth limit is set to {} KB/s, relay ratio {}%",
+			limits.limit_kbps, limits.relay_ratio
+		);
+		Ok(limits)
+	}
 }
 
 impl Handler for PeerHandler {
@@ -13,23 +13,71 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::chain_api::ChainFeesHandler;
+use super::openapi::{ApiDoc, ApiOperation};
 use super::utils::w;
+use crate::chain;
 use crate::core::core::hash::Hashed;
 use crate::core::core::Transaction;
+use crate::core::global;
 use crate::core::ser::{self, DeserializationMode, ProtocolVersion};
+use crate::pool::fee_estimator::{self, BlockFeeSample, PoolTxFee};
 use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
-use crate::util;
 use crate::util::RwLock;
+use crate::util::{self, ToHex};
 use crate::web::*;
 use hyper::{Body, Request, StatusCode};
 use mwc_util::secp::{ContextFlag, Secp256k1};
+use schemars::gen::SchemaGenerator;
+use schemars::JsonSchema;
+use std::cmp;
 use std::sync::Weak;
 
-/// Get basic information about the transaction pool.
+/// Default number of the transactions in the unconfirmed transactions page
+const POOL_TXS_PAGE: usize = 100;
+/// Max number of the transactions in the unconfirmed transactions page
+const POOL_TXS_MAX_PAGE: usize = 1000;
+
+/// Unconfirmed transactions page request of the pool info
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolTxsQuery {
+	pub offset: usize,
+	pub limit: usize,
+	pub sort: PoolTxSort,
+	pub min_fee: u64,
+}
+
+impl PoolTxsQuery {
+	/// The page is requested by any of the page params, None if there are none
+	pub fn from_params(params: &QueryParams) -> Result<Option<PoolTxsQuery>, String> {
+		if ["offset", "limit", "sort", "min_fee"]
+			.iter()
+			.all(|p| params.get(p).is_none())
+		{
+			return Ok(None);
+		}
+		let sort = match params.get("sort").map(|s| s.as_str()) {
+			None | Some("fee_rate") => PoolTxSort::FeeRate,
+			Some("age") => PoolTxSort::Age,
+			Some(s) => return Err(format!("invalid sort {}, expected fee_rate or age", s)),
+		};
+		Ok(Some(PoolTxsQuery {
+			offset: parse_param_no_err!(params, "offset", 0),
+			limit: parse_param_no_err!(params, "limit", POOL_TXS_PAGE),
+			sort,
+			min_fee: parse_param_no_err!(params, "min_fee", 0),
+		}))
+	}
+}
+
+/// Get basic information about the transaction pool. The unconfirmed transactions
+/// summaries are included if the page is requested.
 /// GET /v1/pool
+/// GET /v1/pool?offset=0&limit=100&sort=fee_rate&min_fee=0
+/// `sort` is `fee_rate` (highest first, default) or `age` (oldest first)
 pub struct PoolInfoHandler<B, P>
 where
 	B: BlockChain,
@@ -43,16 +91,165 @@ where
 	B: BlockChain,
 	P: PoolAdapter,
 {
-	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let txs = match PoolTxsQuery::from_params(&params) {
+			Ok(Some(q)) => {
+				let handler = PoolHandler {
+					tx_pool: self.tx_pool.clone(),
+				};
+				match handler
+					.get_unconfirmed_transactions_page(q.offset, q.limit, q.sort, q.min_fee)
+				{
+					Ok(page) => Some(page),
+					Err(e) => return result_to_response(Err::<PoolTxsPage, _>(e)),
+				}
+			}
+			Ok(None) => None,
+			Err(e) => return response(StatusCode::BAD_REQUEST, e),
+		};
+
 		let pool_arc = w_fut!(&self.tx_pool);
 		let pool = pool_arc.read();
 
 		json_response(&PoolInfo {
 			pool_size: pool.total_size(),
+			pool_weight: pool.total_weight(),
+			max_pool_size: pool.config.max_pool_size,
+			max_pool_weight: pool.config.max_pool_weight,
+			eviction_floor: pool.eviction_floor(),
+			is_full: pool.is_full(),
+			orphans: pool.orphans.len(),
+			txs,
+		})
+	}
+}
+
+impl<B, P> ApiDoc for PoolInfoHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<PoolInfo>(
+			gen,
+			"/v1/pool",
+			"/v1/pool",
+			"Transaction pool size and eviction floor, with the unconfirmed transactions page",
+		)
+		.query_param("offset", "Page offset")
+		.query_param("limit", "Page size, default 100, max 1000")
+		.query_param("sort", "fee_rate (default) or age")
+		.query_param("min_fee", "Minimal transaction fee")]
+	}
+}
+
+/// Default number of the blocks to get the transaction mined in
+const FEE_ESTIMATE_BLOCKS_TARGET: u64 = 1;
+/// Fee estimate target is limited to a day of blocks
+const FEE_ESTIMATE_MAX_BLOCKS_TARGET: u64 = 1440;
+/// Recent blocks fullness is checked for that number of the blocks
+const FEE_ESTIMATE_RECENT_BLOCKS: u64 = 30;
+
+/// Fee recommendation from the pool content and the recent blocks fullness
+/// GET /v2/pool/fee_estimate?blocks_target=1
+pub struct FeeEstimateHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	pub chain: Weak<chain::Chain>,
+	pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
+}
+
+impl<B, P> FeeEstimateHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	pub fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error> {
+		let blocks_target = cmp::min(cmp::max(blocks_target, 1), FEE_ESTIMATE_MAX_BLOCKS_TARGET);
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+
+		// Blocks with transactions have the fee rate of the mined transactions
+		let recent_blocks: Vec<BlockFeeSample> = ChainFeesHandler {
+			chain: self.chain.clone(),
+		}
+		.get_fees(FEE_ESTIMATE_RECENT_BLOCKS)?
+		.blocks
+		.iter()
+		.map(|b| BlockFeeSample {
+			utilization: b.utilization,
+			fee_base: if b.tx_kernels > 0 {
+				Some(b.fee_rate)
+			} else {
+				None
+			},
+		})
+		.collect();
+
+		let pool_arc = w(&self.tx_pool)?;
+		let tx_pool = pool_arc.read();
+		let pool_txs: Vec<PoolTxFee> = tx_pool
+			.txpool
+			.entries
+			.iter()
+			.map(|e| PoolTxFee::from_entry(e, head.height))
+			.collect();
+		let block_weight = tx_pool.config.mineable_max_weight;
+		let min_fee_base = global::get_accept_fee_base();
+		drop(tx_pool);
+
+		Ok(FeeEstimate {
+			blocks_target,
+			fee_base: fee_estimator::estimate_fee_base(
+				&pool_txs,
+				&recent_blocks,
+				block_weight,
+				blocks_target,
+				min_fee_base,
+			),
+			min_fee_base,
+			pool_txs: pool_txs.len(),
+			pool_weight: pool_txs.iter().map(|t| t.weight).sum(),
+			block_weight,
+			recent_utilization: fee_estimator::average_utilization(&recent_blocks),
 		})
 	}
 }
 
+impl<B, P> Handler for FeeEstimateHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let blocks_target =
+			parse_param_no_err!(params, "blocks_target", FEE_ESTIMATE_BLOCKS_TARGET);
+		result_to_response(self.get_fee_estimate(blocks_target))
+	}
+}
+
+impl<B, P> ApiDoc for FeeEstimateHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<FeeEstimate>(
+			gen,
+			"/v2/pool/fee_estimate",
+			"/v2/pool/fee_estimate",
+			"Recommended fee base of the new transaction",
+		)
+		.query_param("blocks_target", "Mined in this number of blocks, default 1")]
+	}
+}
+
 pub struct PoolHandler<B, P>
 where
 	B: BlockChain,
@@ -82,6 +279,47 @@ where
 		let txpool = pool_arc.read();
 		Ok(txpool.txpool.entries.clone())
 	}
+	/// Summaries of the unconfirmed transactions with the fee at least `min_fee`,
+	/// the full transactions are not copied, so it is fine for the large pools.
+	pub fn get_unconfirmed_transactions_page(
+		&self,
+		offset: usize,
+		limit: usize,
+		sort: PoolTxSort,
+		min_fee: u64,
+	) -> Result<PoolTxsPage, Error> {
+		let pool_arc = w(&self.tx_pool)?;
+		let txpool = pool_arc.read();
+		let height = txpool
+			.blockchain
+			.chain_head()
+			.map_err(|e| Error::Internal(format!("Failed to get chain head, {}", e)))?
+			.height;
+		let packages = txpool.txpool.packages();
+		let mut entries: Vec<(&PoolEntry, &pool::TxPackage)> = txpool
+			.txpool
+			.entries
+			.iter()
+			.zip(packages.iter())
+			.filter(|(e, _)| e.tx.fee(height) >= min_fee)
+			.collect();
+		match sort {
+			PoolTxSort::FeeRate => {
+				entries.sort_by_cached_key(|(e, _)| cmp::Reverse(e.tx.fee_rate(height)))
+			}
+			PoolTxSort::Age => entries.sort_by_key(|(e, _)| e.tx_at),
+		}
+		Ok(PoolTxsPage {
+			total: entries.len(),
+			offset,
+			txs: entries
+				.iter()
+				.skip(offset)
+				.take(limit.min(POOL_TXS_MAX_PAGE))
+				.map(|(e, package)| PoolTxSummary::from_entry(e, package, height))
+				.collect(),
+		})
+	}
 	pub fn push_transaction(
 		&self,
 		tx: Transaction,
@@ -116,7 +354,7 @@ where
 	}
 }
 /// Dummy wrapper for the hex-encoded serialized transaction.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 struct TxWrapper {
 	tx_hex: String,
 }
@@ -131,16 +369,8 @@ where
 	pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
 }
 
-async fn update_pool<B, P>(
-	pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
-	req: Request<Body>,
-	secp: &Secp256k1,
-) -> Result<(), Error>
-where
-	B: BlockChain,
-	P: PoolAdapter,
-{
-	let pool = w(&pool)?;
+// Hex serialized transaction from the body and the fluff flag from the query
+async fn parse_tx_request(req: Request<Body>) -> Result<(Transaction, bool), Error> {
 	let params = QueryParams::from(req.uri().query());
 	let fluff = params.get("fluff").is_some();
 
@@ -163,6 +393,20 @@ where
 				))
 			},
 		)?;
+	Ok((tx, fluff))
+}
+
+async fn update_pool<B, P>(
+	pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
+	req: Request<Body>,
+	secp: &Secp256k1,
+) -> Result<(), Error>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	let pool = w(&pool)?;
+	let (tx, fluff) = parse_tx_request(req).await?;
 
 	let source = pool::TxSource::PushApi;
 	info!(
@@ -204,3 +448,124 @@ where
 		})
 	}
 }
+
+impl<B, P> ApiDoc for PoolPushHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::post::<TxWrapper>(
+			gen,
+			"/v1/pool/push_tx",
+			"/v1/pool/push_tx",
+			"Push the hex serialized transaction to the pool",
+		)
+		.query_param("fluff", "Skip the dandelion stem phase")]
+	}
+}
+
+/// Validate the transaction the same way as it is pushed, without adding it to the pool.
+/// The rejected transaction is not an error, the response has the rejection reason.
+/// POST /v2/pool/check
+pub struct PoolCheckHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
+}
+
+async fn check_tx<B, P>(
+	pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
+	req: Request<Body>,
+	secp: &Secp256k1,
+) -> Result<PoolCheckResult, Error>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	let pool = w(&pool)?;
+	let (tx, fluff) = parse_tx_request(req).await?;
+	let tx_hash = tx.hash().to_hex();
+
+	let tx_pool = pool.read();
+	let header = tx_pool
+		.blockchain
+		.chain_head()
+		.map_err(|e| Error::Internal(format!("Failed to get chain head: {}", e)))?;
+	let res = tx_pool.check_transaction(pool::TxSource::PushApi, tx, !fluff, &header, secp);
+	debug!("Pool check of transaction {}: {:?}", tx_hash, res);
+	Ok(PoolCheckResult::new(tx_hash, res))
+}
+
+impl<B, P> Handler for PoolCheckHandler<B, P>
+where
+	B: BlockChain + 'static,
+	P: PoolAdapter + 'static,
+{
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		let pool = self.tx_pool.clone();
+		Box::pin(async move {
+			let secp = Secp256k1::with_caps(ContextFlag::Commit);
+			let res = check_tx(pool, req, &secp).await;
+			result_to_response(res).await
+		})
+	}
+}
+
+impl<B, P> ApiDoc for PoolCheckHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::post::<TxWrapper>(
+			gen,
+			"/v2/pool/check",
+			"/v2/pool/check",
+			"Validate the hex serialized transaction without adding it to the pool",
+		)
+		.with_response::<PoolCheckResult>(gen)
+		.query_param(
+			"fluff",
+			"Validate as the fluffed transaction, not the stem one",
+		)]
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn pool_txs_query() {
+		// Pool info only
+		assert_eq!(PoolTxsQuery::from_params(&QueryParams::from("")), Ok(None));
+		assert_eq!(
+			PoolTxsQuery::from_params(&QueryParams::from("other=1")),
+			Ok(None)
+		);
+
+		// Any of the page params requests the page, the rest are the defaults
+		assert_eq!(
+			PoolTxsQuery::from_params(&QueryParams::from("min_fee=100")),
+			Ok(Some(PoolTxsQuery {
+				offset: 0,
+				limit: POOL_TXS_PAGE,
+				sort: PoolTxSort::FeeRate,
+				min_fee: 100,
+			}))
+		);
+		assert_eq!(
+			PoolTxsQuery::from_params(&QueryParams::from("offset=20&limit=10&sort=age")),
+			Ok(Some(PoolTxsQuery {
+				offset: 20,
+				limit: 10,
+				sort: PoolTxSort::Age,
+				min_fee: 0,
+			}))
+		);
+		assert!(PoolTxsQuery::from_params(&QueryParams::from("sort=size")).is_err());
+	}
+}
@@ -0,0 +1,118 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only public API profile. Only the allowlisted read endpoints are added to
+//! the router, the owner, stratum, pool and peers endpoints are not served at all.
+//! Intended for the nodes that serve explorer backends from the public internet.
+
+use serde_json::{json, Value};
+
+/// JSON-RPC 'Method not found' error code
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Read-only API profile configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReadOnlyApiConfig {
+	/// Serve only the allowlisted endpoints. Default: false
+	pub enabled: bool,
+	/// v1 REST routes, `/v2/ws`, `/v2/health` and `/v2/ready` that are served, as they are
+	/// registered at the router. Default: tip, headers, blocks, kernels, outputs and the
+	/// health probes
+	pub allowed_routes: Vec<String>,
+	/// Foreign API v2 methods that are served, no methods means that `/v2/foreign` is not served.
+	/// Default: tip, headers, blocks, kernels and outputs
+	pub allowed_foreign_methods: Vec<String>,
+}
+
+impl Default for ReadOnlyApiConfig {
+	fn default() -> ReadOnlyApiConfig {
+		ReadOnlyApiConfig {
+			enabled: false,
+			allowed_routes: vec![
+				"/v1/chain".to_string(),
+				"/v1/headers/*".to_string(),
+				"/v1/blocks/*".to_string(),
+				"/v1/chain/kernels/*".to_string(),
+				"/v1/chain/outputs/*".to_string(),
+				"/v2/health".to_string(),
+				"/v2/ready".to_string(),
+			],
+			allowed_foreign_methods: vec![
+				"get_tip".to_string(),
+				"get_header".to_string(),
+				"get_block".to_string(),
+				"get_blocks".to_string(),
+				"get_kernel".to_string(),
+				"get_outputs".to_string(),
+				"get_unspent_outputs".to_string(),
+			],
+		}
+	}
+}
+
+impl ReadOnlyApiConfig {
+	/// Whether the router route is served
+	pub fn is_route_allowed(&self, route: &str) -> bool {
+		self.allowed_routes.iter().any(|r| r == route)
+	}
+}
+
+/// Error reply if the JSON-RPC request (or any request of the batch) calls a method
+/// that is not in `allowed_methods`.
+pub fn check_allowed_methods(request: &Value, allowed_methods: &[String]) -> Option<Value> {
+	let requests: Vec<&Value> = match request {
+		Value::Array(requests) => requests.iter().collect(),
+		request => vec![request],
+	};
+	for r in requests {
+		let method = r.get("method").and_then(|m| m.as_str()).unwrap_or("");
+		if !allowed_methods.iter().any(|m| m == method) {
+			return Some(json!({
+				"jsonrpc": "2.0",
+				"id": r.get("id").cloned().unwrap_or(Value::Null),
+				"error": {
+					"code": METHOD_NOT_FOUND,
+					"message": format!("Method '{}' is not available", method),
+				},
+			}));
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn read_only_allowed_methods() {
+		let config = ReadOnlyApiConfig::default();
+		assert!(config.is_route_allowed("/v1/chain"));
+		assert!(!config.is_route_allowed("/v1/pool/push_tx"));
+
+		let allowed = &config.allowed_foreign_methods;
+		let tip = json!({"jsonrpc": "2.0", "method": "get_tip", "params": [], "id": 1});
+		assert!(check_allowed_methods(&tip, allowed).is_none());
+
+		let push = json!({"jsonrpc": "2.0", "method": "push_transaction", "params": [], "id": 2});
+		let res = check_allowed_methods(&push, allowed).unwrap();
+		assert_eq!(res["id"], 2);
+		assert_eq!(res["error"]["code"], METHOD_NOT_FOUND);
+
+		// Single not allowed request rejects the batch
+		let batch = json!([tip.clone(), push]);
+		assert!(check_allowed_methods(&batch, allowed).is_some());
+		assert!(check_allowed_methods(&json!([tip]), allowed).is_none());
+	}
+}
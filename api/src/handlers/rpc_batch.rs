@@ -0,0 +1,121 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON-RPC 2.0 batch requests for the v2 APIs. The calls of a batch are dispatched
+//! one by one, the replies are returned as an array in the same order. Notifications
+//! don't have a reply.
+
+use easy_jsonrpc_mw::MaybeReply;
+use serde_json::{json, Value};
+
+/// Max number of the calls in a single batch
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// JSON-RPC 2.0 'Invalid Request' error code
+const INVALID_REQUEST: i64 = -32600;
+
+fn invalid_request(message: &str) -> Value {
+	json!({
+		"jsonrpc": "2.0",
+		"id": Value::Null,
+		"error": {
+			"code": INVALID_REQUEST,
+			"message": message,
+		},
+	})
+}
+
+/// Process the single call or the batch with `handle`
+pub fn handle_rpc_request<F>(request: Value, handle: F) -> Value
+where
+	F: Fn(Value) -> MaybeReply,
+{
+	match request {
+		Value::Array(calls) => {
+			if calls.is_empty() {
+				return invalid_request("Empty batch");
+			}
+			if calls.len() > MAX_BATCH_SIZE {
+				return invalid_request(&format!(
+					"Batch is too large, {} calls, max is {}",
+					calls.len(),
+					MAX_BATCH_SIZE
+				));
+			}
+			let replies: Vec<Value> = calls
+				.into_iter()
+				.filter_map(|call| {
+					if call.is_array() {
+						return Some(invalid_request("Nested batch"));
+					}
+					match handle(call) {
+						MaybeReply::Reply(r) => Some(r),
+						MaybeReply::DontReply => None,
+					}
+				})
+				.collect();
+			Value::Array(replies)
+		}
+		call => match handle(call) {
+			MaybeReply::Reply(r) => r,
+			MaybeReply::DontReply => {
+				// Since it's http, we need to return something. We return [] because jsonrpc
+				// clients will parse it as an empty batch response.
+				json!([])
+			}
+		},
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn echo(call: Value) -> MaybeReply {
+		match call.get("id") {
+			Some(id) => {
+				MaybeReply::Reply(json!({"jsonrpc": "2.0", "id": id, "result": {"Ok": id}}))
+			}
+			None => MaybeReply::DontReply,
+		}
+	}
+
+	#[test]
+	fn rpc_batch_requests() {
+		let call = json!({"jsonrpc": "2.0", "method": "get_tip", "params": [], "id": 1});
+		let notification = json!({"jsonrpc": "2.0", "method": "get_tip", "params": []});
+
+		assert_eq!(handle_rpc_request(call.clone(), echo)["id"], 1);
+		assert_eq!(handle_rpc_request(notification.clone(), echo), json!([]));
+
+		// Replies keep the order, notifications are skipped
+		let mut call2 = call.clone();
+		call2["id"] = json!(2);
+		let res = handle_rpc_request(json!([call, notification, call2]), echo);
+		let replies = res.as_array().unwrap();
+		assert_eq!(replies.len(), 2);
+		assert_eq!(replies[0]["id"], 1);
+		assert_eq!(replies[1]["id"], 2);
+
+		let res = handle_rpc_request(json!([]), echo);
+		assert_eq!(res["error"]["code"], INVALID_REQUEST);
+
+		let batch: Vec<Value> = (0..MAX_BATCH_SIZE + 1).map(|i| json!({"id": i})).collect();
+		let res = handle_rpc_request(Value::Array(batch), echo);
+		assert_eq!(res["error"]["code"], INVALID_REQUEST);
+
+		let res = handle_rpc_request(json!([[{"id": 1}]]), echo);
+		assert_eq!(res[0]["error"]["code"], INVALID_REQUEST);
+	}
+}
@@ -13,19 +13,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::openapi::{ApiDoc, ApiOperation};
 use super::utils::w;
-use crate::chain::{Chain, SyncState, SyncStatus};
+use crate::chain::{Chain, SyncEvent, SyncProgress, SyncState, SyncStatus};
+use crate::core::ser;
+use crate::node_control::NodeControl;
 use crate::p2p;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
 use crate::web::*;
+use chrono::Utc;
 use hyper::{Body, Request, StatusCode};
 use mwc_core::global;
+use schemars::gen::SchemaGenerator;
 use serde_json::json;
 use std::convert::TryInto;
 use std::sync::atomic::Ordering;
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
+
+// Bans of the status are counted over that window
+const BANS_WINDOW_SECS: i64 = 3600;
 
 // RESTful index of available api endpoints
 // GET /v1/
@@ -53,10 +61,10 @@ pub struct StatusHandler {
 impl StatusHandler {
 	pub fn get_status(&self) -> Result<Status, Error> {
 		let head = w(&self.chain)?
-			.head()
+			.node_head()
 			.map_err(|e| Error::Internal(format!("Unable to get chain tip, {}", e)))?;
-		let sync_status = w(&self.sync_state)?.status();
-		let (api_sync_status, api_sync_info) = sync_status_to_api(sync_status);
+		let sync_state = w(&self.sync_state)?;
+		let (api_sync_status, api_sync_info) = sync_status_to_api(sync_state.status());
 		Ok(Status::from_tip_and_peers(
 			head,
 			w(&self.peers)?
@@ -67,8 +75,64 @@ impl StatusHandler {
 				.unwrap(),
 			api_sync_status,
 			api_sync_info,
+			sync_state.forks(),
 		))
 	}
+
+	/// Status with the subsystem details. Pool, stratum, Tor and disk come from the node
+	/// control, they are skipped without it.
+	pub fn get_status_v2(
+		&self,
+		node_control: Option<&Arc<dyn NodeControl>>,
+	) -> Result<StatusV2, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("Unable to get chain tip, {}", e)))?;
+		let peers = w(&self.peers)?;
+		let ban_cutoff = Utc::now().timestamp() - BANS_WINDOW_SECS;
+		let bans_last_hour = peers
+			.find_peers(p2p::State::Banned, p2p::Capabilities::UNKNOWN)
+			.iter()
+			.filter(|p| p.last_banned >= ban_cutoff)
+			.count();
+		let sync_state = w(&self.sync_state)?;
+		let progress = sync_state.progress();
+		let (status, _) = sync_status_to_api(sync_state.status());
+
+		Ok(StatusV2 {
+			protocol_version: ser::ProtocolVersion::local().into(),
+			user_agent: p2p::msg::USER_AGENT.to_string(),
+			p2p: P2pSubsystemStatus {
+				inbound: peers.iter().inbound().connected().count(),
+				outbound: peers.iter().outbound().connected().count(),
+				bans_last_hour,
+			},
+			sync: SyncSubsystemStatus {
+				phase: progress.phase,
+				status,
+				done: progress.done,
+				total: progress.total,
+				eta_secs: progress.eta_secs,
+			},
+			chain: ChainSubsystemStatus {
+				tip: Tip::from_tip(head),
+				last_reorg: chain.last_reorg(),
+			},
+			pool: node_control.map(|c| c.pool_status()),
+			stratum: node_control.and_then(|c| c.stratum_status()),
+			tor: node_control.and_then(|c| c.tor_status()),
+			disk: node_control.map(|c| c.disk_status()),
+		})
+	}
+
+	pub fn get_sync_progress(&self) -> Result<SyncProgress, Error> {
+		Ok(w(&self.sync_state)?.progress())
+	}
+
+	pub fn get_sync_events(&self) -> Result<Vec<SyncEvent>, Error> {
+		Ok(w(&self.sync_state)?.events())
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -115,6 +179,17 @@ impl Handler for StatusHandler {
 	}
 }
 
+impl ApiDoc for StatusHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<Status>(
+			gen,
+			"/v1/status",
+			"/v1/status",
+			"Node status",
+		)]
+	}
+}
+
 /// Convert a SyncStatus in a readable API representation
 fn sync_status_to_api(sync_status: SyncStatus) -> (String, Option<serde_json::Value>) {
 	match sync_status {
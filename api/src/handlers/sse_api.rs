@@ -0,0 +1,181 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server-Sent Events stream of the chain tip updates, for the clients that can't
+//! use the WebSocket subscriptions. Every new head is sent as a `tip` event:
+//! `event: tip`
+//! `data: {"height": 1, "hash": "...", "total_difficulty": 1}`
+
+use super::utils::w;
+use crate::chain;
+use crate::rest::*;
+use crate::router::{Handler, ResponseFuture};
+use crate::util::ToHex;
+use crate::web::*;
+use hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Max number of the connected SSE clients
+const MAX_SSE_CLIENTS: usize = 100;
+/// Tip updates queue. The client that is behind skips the missed updates,
+/// only the latest tip matters.
+const TIP_EVENTS_QUEUE: usize = 16;
+/// Comment line is sent that often to keep the connection open and to detect
+/// the disconnected clients
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Chain tip update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TipEvent {
+	pub height: u64,
+	pub hash: String,
+	pub total_difficulty: u64,
+}
+
+impl TipEvent {
+	fn from_tip(tip: &chain::Tip) -> TipEvent {
+		TipEvent {
+			height: tip.height,
+			hash: tip.last_block_h.to_hex(),
+			total_difficulty: tip.total_difficulty.to_num(),
+		}
+	}
+
+	fn to_sse(&self) -> String {
+		format!(
+			"event: tip\ndata: {}\n\n",
+			serde_json::to_string(self).unwrap_or_default()
+		)
+	}
+}
+
+/// Broadcast channel of the chain tip updates, fed by the chain adapter
+pub struct TipEventBus {
+	sender: broadcast::Sender<TipEvent>,
+}
+
+impl TipEventBus {
+	pub fn new() -> TipEventBus {
+		let (sender, _) = broadcast::channel(TIP_EVENTS_QUEUE);
+		TipEventBus { sender }
+	}
+
+	/// Send the tip update to all connected clients
+	pub fn publish(&self, event: TipEvent) {
+		// Error means there are no clients
+		let _ = self.sender.send(event);
+	}
+
+	fn subscribe(&self) -> Option<broadcast::Receiver<TipEvent>> {
+		if self.sender.receiver_count() >= MAX_SSE_CLIENTS {
+			return None;
+		}
+		Some(self.sender.subscribe())
+	}
+}
+
+impl Default for TipEventBus {
+	fn default() -> TipEventBus {
+		TipEventBus::new()
+	}
+}
+
+/// Chain tip updates stream
+/// GET /v1/chain/tip/stream
+pub struct ChainTipStreamHandler {
+	pub chain: Weak<chain::Chain>,
+	pub events: Arc<TipEventBus>,
+}
+
+impl Handler for ChainTipStreamHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		// Current tip goes first, so the client doesn't wait for the next block
+		let head = match w(&self.chain).and_then(|chain| {
+			chain
+				.head()
+				.map_err(|e| Error::Internal(format!("can't get head: {}", e)))
+		}) {
+			Ok(head) => TipEvent::from_tip(&head),
+			Err(e) => return response(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)),
+		};
+		let mut events = match self.events.subscribe() {
+			Some(events) => events,
+			None => {
+				return response(StatusCode::SERVICE_UNAVAILABLE, "Too many SSE clients");
+			}
+		};
+
+		let (mut sender, body) = Body::channel();
+		tokio::spawn(async move {
+			let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+			// first tick is immediate
+			keep_alive.tick().await;
+			let mut data = Some(head.to_sse());
+			while let Some(sse) = data {
+				if sender.send_data(sse.into()).await.is_err() {
+					debug!("SSE client is disconnected");
+					break;
+				}
+				data = loop {
+					tokio::select! {
+						event = events.recv() => match event {
+							Ok(event) => break Some(event.to_sse()),
+							Err(broadcast::error::RecvError::Lagged(_)) => continue,
+							Err(broadcast::error::RecvError::Closed) => break None,
+						},
+						_ = keep_alive.tick() => break Some(":\n\n".to_string()),
+					}
+				};
+			}
+		});
+
+		let mut resp = Response::new(body);
+		let headers = resp.headers_mut();
+		headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+		headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+		Box::pin(async move { Ok(resp) })
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn tip_event_bus() {
+		let bus = TipEventBus::new();
+		// No clients, event is dropped
+		bus.publish(TipEvent {
+			height: 1,
+			hash: "00".to_string(),
+			total_difficulty: 1,
+		});
+
+		let mut rx = bus.subscribe().unwrap();
+		let event = TipEvent {
+			height: 2,
+			hash: "01".to_string(),
+			total_difficulty: 3,
+		};
+		bus.publish(event.clone());
+		assert_eq!(rx.try_recv().unwrap(), event);
+		assert_eq!(
+			event.to_sse(),
+			"event: tip\ndata: {\"height\":2,\"hash\":\"01\",\"total_difficulty\":3}\n\n"
+		);
+	}
+}
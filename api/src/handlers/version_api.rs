@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::openapi::{ApiDoc, ApiOperation};
 use super::utils::w;
 use crate::chain;
 use crate::rest::*;
@@ -20,6 +21,7 @@ use crate::router::{Handler, ResponseFuture};
 use crate::types::Version;
 use crate::web::*;
 use hyper::{Body, Request};
+use schemars::gen::SchemaGenerator;
 use std::sync::Weak;
 
 const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -48,3 +50,14 @@ impl Handler for VersionHandler {
 		result_to_response(self.get_version())
 	}
 }
+
+impl ApiDoc for VersionHandler {
+	fn api_operations(gen: &mut SchemaGenerator) -> Vec<ApiOperation> {
+		vec![ApiOperation::get::<Version>(
+			gen,
+			"/v1/version",
+			"/v1/version",
+			"Node and block header versions",
+		)]
+	}
+}
@@ -0,0 +1,391 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reverse proxy to the local wallet listener. Requests to `/wallet/<path>` are
+//! forwarded to `<wallet_listener_url>/<path>`, so the node onion address can
+//! serve both node API and wallet receive traffic.
+
+use crate::router::{Handler, ResponseFuture};
+use crate::util::file::get_first_line;
+use crate::util::to_base64;
+use crate::web::*;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderValue, AUTHORIZATION, HOST, WWW_AUTHENTICATE};
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use ring::constant_time::verify_slices_are_equal;
+use std::time::Duration;
+
+/// Route prefix for the proxied wallet requests
+pub const WALLET_PROXY_PREFIX: &str = "/wallet";
+
+/// Timeout for the wallet listener response
+const WALLET_PROXY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Basic auth user name for the proxy and wallet listener secrets
+const WALLET_BASIC_AUTH_USER: &str = "mwc";
+
+/// Wallet foreign API, the only path that can be proxied without the proxy secret
+const WALLET_FOREIGN_API_PATH: &str = "/v2/foreign";
+
+/// Wallet listener proxy configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WalletProxyConfig {
+	/// Forward the wallet requests. Default: false
+	pub enabled: bool,
+	/// Url of the local wallet listener
+	pub wallet_listener_url: String,
+	/// Wallet listener paths that can be reached through the proxy. Paths other than
+	/// the wallet foreign API require proxy_secret_path.
+	/// Default: only wallet foreign API
+	pub allowed_paths: Vec<String>,
+	/// File with the secret that callers must provide with basic auth (user 'mwc') to use
+	/// the proxy. Not set means the proxy is open, same as the wallet foreign API normally is.
+	pub proxy_secret_path: Option<String>,
+	/// File with the wallet listener secret, it replaces caller credentials for the
+	/// forwarded request. Requires proxy_secret_path, otherwise anybody could use it.
+	pub wallet_secret_path: Option<String>,
+}
+
+impl Default for WalletProxyConfig {
+	fn default() -> WalletProxyConfig {
+		WalletProxyConfig {
+			enabled: false,
+			wallet_listener_url: "http://127.0.0.1:3415".to_string(),
+			allowed_paths: vec!["/v2/foreign".to_string()],
+			proxy_secret_path: None,
+			wallet_secret_path: None,
+		}
+	}
+}
+
+fn basic_auth(secret: &str) -> String {
+	format!(
+		"Basic {}",
+		to_base64(&format!("{}:{}", WALLET_BASIC_AUTH_USER, secret))
+	)
+}
+
+/// Forwards the requests under WALLET_PROXY_PREFIX to the wallet listener
+pub struct WalletProxyHandler {
+	wallet_listener_url: String,
+	allowed_paths: Vec<String>,
+	proxy_auth: Option<String>,
+	wallet_auth: Option<HeaderValue>,
+	client: Client<HttpConnector>,
+}
+
+impl WalletProxyHandler {
+	pub fn new(config: &WalletProxyConfig) -> Result<WalletProxyHandler, String> {
+		let proxy_auth = match &config.proxy_secret_path {
+			Some(path) => {
+				let secret = get_first_line(Some(path.clone()))
+					.ok_or_else(|| format!("Unable to read proxy secret from {}", path))?;
+				Some(basic_auth(&secret))
+			}
+			None => None,
+		};
+		if proxy_auth.is_none() {
+			// Open proxy can't attach the wallet credentials or reach the wallet owner paths
+			if config.wallet_secret_path.is_some() {
+				return Err("wallet_secret_path requires proxy_secret_path".to_string());
+			}
+			if let Some(path) = config
+				.allowed_paths
+				.iter()
+				.find(|p| p.as_str() != WALLET_FOREIGN_API_PATH)
+			{
+				return Err(format!(
+					"wallet path {} requires proxy_secret_path, only {} can be open",
+					path, WALLET_FOREIGN_API_PATH
+				));
+			}
+		}
+		let wallet_auth = match &config.wallet_secret_path {
+			Some(path) => {
+				let secret = get_first_line(Some(path.clone()))
+					.ok_or_else(|| format!("Unable to read wallet secret from {}", path))?;
+				Some(
+					HeaderValue::from_str(&basic_auth(&secret))
+						.map_err(|e| format!("Invalid wallet secret, {}", e))?,
+				)
+			}
+			None => None,
+		};
+		Ok(WalletProxyHandler {
+			wallet_listener_url: config.wallet_listener_url.trim_end_matches('/').to_string(),
+			allowed_paths: config.allowed_paths.clone(),
+			proxy_auth,
+			wallet_auth,
+			client: Client::new(),
+		})
+	}
+
+	/// Wallet path for the proxied request, None if it is not allowed
+	fn wallet_path(&self, path: &str) -> Option<String> {
+		let wallet_path = path.strip_prefix(WALLET_PROXY_PREFIX)?;
+		if self.allowed_paths.iter().any(|p| p == wallet_path) {
+			Some(wallet_path.to_string())
+		} else {
+			None
+		}
+	}
+
+	fn is_authorized(&self, req: &Request<Body>) -> bool {
+		match &self.proxy_auth {
+			None => true,
+			Some(auth) => match req.headers().get(AUTHORIZATION) {
+				Some(header) => verify_slices_are_equal(header.as_bytes(), auth.as_bytes()).is_ok(),
+				None => false,
+			},
+		}
+	}
+
+	fn forward(&self, req: Request<Body>) -> ResponseFuture {
+		let wallet_path = match self.wallet_path(req.uri().path()) {
+			Some(p) => p,
+			None => {
+				return response(
+					StatusCode::NOT_FOUND,
+					format!("wallet path {} is not allowed", req.uri().path()),
+				)
+			}
+		};
+
+		if !self.is_authorized(&req) {
+			let resp = Response::builder()
+				.status(StatusCode::UNAUTHORIZED)
+				.header(WWW_AUTHENTICATE, "Basic realm=MWCWalletProxy")
+				.body(Body::empty())
+				.unwrap();
+			return Box::pin(async move { Ok(resp) });
+		}
+
+		let uri = match req.uri().query() {
+			Some(q) => format!("{}{}?{}", self.wallet_listener_url, wallet_path, q),
+			None => format!("{}{}", self.wallet_listener_url, wallet_path),
+		};
+		let uri: Uri = match uri.parse() {
+			Ok(uri) => uri,
+			Err(e) => {
+				return response(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					format!("invalid wallet listener url, {}", e),
+				)
+			}
+		};
+
+		let (mut parts, body) = req.into_parts();
+		parts.uri = uri;
+		parts.headers.remove(HOST);
+		// Caller credentials are for the proxy, never pass them to the wallet
+		if self.proxy_auth.is_some() {
+			parts.headers.remove(AUTHORIZATION);
+		}
+		// Wallet credentials are set only together with the proxy secret, so the
+		// request is authorized by the caller at this point
+		if let Some(auth) = &self.wallet_auth {
+			parts.headers.insert(AUTHORIZATION, auth.clone());
+		}
+
+		let client = self.client.clone();
+		let request = Request::from_parts(parts, body);
+		Box::pin(async move {
+			match tokio::time::timeout(WALLET_PROXY_TIMEOUT, client.request(request)).await {
+				Ok(Ok(resp)) => Ok(resp),
+				Ok(Err(e)) => {
+					warn!("Wallet proxy request {} failed, {}", wallet_path, e);
+					Ok(just_response(
+						StatusCode::BAD_GATEWAY,
+						format!("wallet listener is not reachable, {}", e),
+					))
+				}
+				Err(_) => {
+					warn!("Wallet proxy request {} timed out", wallet_path);
+					Ok(just_response(
+						StatusCode::GATEWAY_TIMEOUT,
+						"wallet listener timed out",
+					))
+				}
+			}
+		})
+	}
+}
+
+impl Handler for WalletProxyHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		self.forward(req)
+	}
+
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		self.forward(req)
+	}
+
+	fn options(&self, req: Request<Body>) -> ResponseFuture {
+		self.forward(req)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use hyper::service::{make_service_fn, service_fn};
+	use hyper::Server;
+	use std::convert::Infallible;
+	use std::fs;
+	use std::net::SocketAddr;
+
+	/// Wallet listener stub, responds with the Authorization header it got
+	fn start_listener(rt: &tokio::runtime::Runtime) -> SocketAddr {
+		let _guard = rt.enter();
+		let make_svc = make_service_fn(|_| async {
+			Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+				let auth = req
+					.headers()
+					.get(AUTHORIZATION)
+					.map(|h| h.to_str().unwrap().to_string())
+					.unwrap_or_default();
+				Ok::<_, Infallible>(Response::new(Body::from(auth)))
+			}))
+		});
+		let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+		let addr = server.local_addr();
+		rt.spawn(server);
+		addr
+	}
+
+	fn call(
+		rt: &tokio::runtime::Runtime,
+		handler: &WalletProxyHandler,
+		path: &str,
+		auth: Option<&str>,
+	) -> (StatusCode, String) {
+		let mut req = Request::post(path);
+		if let Some(auth) = auth {
+			req = req.header(AUTHORIZATION, auth);
+		}
+		let req = req.body(Body::empty()).unwrap();
+		rt.block_on(async {
+			let resp = handler.post(req).await.unwrap();
+			let status = resp.status();
+			let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+			(status, String::from_utf8(body.to_vec()).unwrap())
+		})
+	}
+
+	fn write_secret(dir: &tempfile::TempDir, name: &str, secret: &str) -> Option<String> {
+		let path = dir.path().join(name);
+		fs::write(&path, secret).unwrap();
+		Some(path.to_str().unwrap().to_string())
+	}
+
+	#[test]
+	fn wallet_proxy_config_checks() {
+		let dir = tempfile::tempdir().unwrap();
+		let config = WalletProxyConfig {
+			enabled: true,
+			wallet_secret_path: write_secret(&dir, "wallet", "wallet_secret"),
+			..WalletProxyConfig::default()
+		};
+		assert!(WalletProxyHandler::new(&config).is_err());
+
+		let config = WalletProxyConfig {
+			enabled: true,
+			allowed_paths: vec!["/v2/foreign".to_string(), "/v3/owner".to_string()],
+			..WalletProxyConfig::default()
+		};
+		assert!(WalletProxyHandler::new(&config).is_err());
+
+		let config = WalletProxyConfig {
+			enabled: true,
+			proxy_secret_path: Some(dir.path().join("missing").to_str().unwrap().to_string()),
+			..WalletProxyConfig::default()
+		};
+		assert!(WalletProxyHandler::new(&config).is_err());
+
+		let config = WalletProxyConfig {
+			enabled: true,
+			allowed_paths: vec!["/v2/foreign".to_string(), "/v3/owner".to_string()],
+			proxy_secret_path: write_secret(&dir, "proxy", "proxy_secret"),
+			wallet_secret_path: write_secret(&dir, "wallet", "wallet_secret"),
+			..WalletProxyConfig::default()
+		};
+		assert!(WalletProxyHandler::new(&config).is_ok());
+	}
+
+	#[test]
+	fn wallet_proxy_open() {
+		let rt = tokio::runtime::Runtime::new().unwrap();
+		let addr = start_listener(&rt);
+		let config = WalletProxyConfig {
+			enabled: true,
+			wallet_listener_url: format!("http://{}", addr),
+			..WalletProxyConfig::default()
+		};
+		let handler = WalletProxyHandler::new(&config).unwrap();
+
+		// No wallet credentials are attached, caller ones go to the wallet as is
+		assert_eq!(
+			call(&rt, &handler, "/wallet/v2/foreign", None),
+			(StatusCode::OK, String::new())
+		);
+		assert_eq!(
+			call(&rt, &handler, "/wallet/v2/foreign", Some("Basic abc")),
+			(StatusCode::OK, "Basic abc".to_string())
+		);
+		assert_eq!(
+			call(&rt, &handler, "/wallet/v3/owner", None).0,
+			StatusCode::NOT_FOUND
+		);
+	}
+
+	#[test]
+	fn wallet_proxy_with_secret() {
+		let rt = tokio::runtime::Runtime::new().unwrap();
+		let addr = start_listener(&rt);
+		let dir = tempfile::tempdir().unwrap();
+		let config = WalletProxyConfig {
+			enabled: true,
+			wallet_listener_url: format!("http://{}", addr),
+			allowed_paths: vec!["/v2/foreign".to_string(), "/v3/owner".to_string()],
+			proxy_secret_path: write_secret(&dir, "proxy", "proxy_secret"),
+			wallet_secret_path: write_secret(&dir, "wallet", "wallet_secret"),
+		};
+		let handler = WalletProxyHandler::new(&config).unwrap();
+
+		assert_eq!(
+			call(&rt, &handler, "/wallet/v3/owner", None).0,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			call(
+				&rt,
+				&handler,
+				"/wallet/v3/owner",
+				Some(&basic_auth("wrong"))
+			)
+			.0,
+			StatusCode::UNAUTHORIZED
+		);
+		// Caller credentials are replaced with the wallet ones
+		assert_eq!(
+			call(
+				&rt,
+				&handler,
+				"/wallet/v3/owner",
+				Some(&basic_auth("proxy_secret"))
+			),
+			(StatusCode::OK, basic_auth("wallet_secret"))
+		);
+	}
+}
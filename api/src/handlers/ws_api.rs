@@ -0,0 +1,359 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! WebSocket subscriptions for the node events. Clients send JSON-RPC calls
+//! `subscribe_blocks`, `subscribe_mempool`, `subscribe_sync_status` (and the matching
+//! `unsubscribe_*`) and get the events pushed as JSON-RPC notifications, the method
+//! is the subscription name:
+//! `{"jsonrpc": "2.0", "method": "blocks", "params": {...}}`
+
+use super::utils::w;
+use crate::chain::SyncState;
+use crate::router::{Handler, ResponseFuture};
+use crate::util::RwLock;
+use crate::web::*;
+use futures::{SinkExt, StreamExt};
+use hyper::header::{HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Max number of the connected WebSocket clients
+const MAX_WS_CLIENTS: usize = 100;
+/// Events queue of a single client. Events are dropped for the client that can't keep up.
+const WS_EVENTS_QUEUE: usize = 256;
+/// Sync status is checked that often, the notification is sent only if it is changed
+const SYNC_STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Event types that a client can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WsSubscription {
+	Blocks,
+	Mempool,
+	SyncStatus,
+}
+
+impl WsSubscription {
+	fn name(&self) -> &'static str {
+		match self {
+			WsSubscription::Blocks => "blocks",
+			WsSubscription::Mempool => "mempool",
+			WsSubscription::SyncStatus => "sync_status",
+		}
+	}
+
+	fn from_name(name: &str) -> Option<WsSubscription> {
+		match name {
+			"blocks" => Some(WsSubscription::Blocks),
+			"mempool" => Some(WsSubscription::Mempool),
+			"sync_status" => Some(WsSubscription::SyncStatus),
+			_ => None,
+		}
+	}
+}
+
+/// Node event for the WebSocket subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum WsEvent {
+	/// Block was accepted by the chain
+	Block {
+		hash: String,
+		height: u64,
+		prev_hash: String,
+		/// "head", "fork" or "reorg"
+		status: String,
+		/// Number of the blocks that were rewound, for the reorg only
+		#[serde(skip_serializing_if = "Option::is_none")]
+		depth: Option<u64>,
+	},
+	/// Transaction was accepted into the mempool
+	MempoolTx {
+		hash: String,
+		fee: u64,
+		/// Excess commitments of the transaction kernels
+		kernels: Vec<String>,
+	},
+}
+
+impl WsEvent {
+	fn subscription(&self) -> WsSubscription {
+		match self {
+			WsEvent::Block { .. } => WsSubscription::Blocks,
+			WsEvent::MempoolTx { .. } => WsSubscription::Mempool,
+		}
+	}
+}
+
+/// Delivers the node events to the connected WebSocket clients. Clients that
+/// disconnected are removed on the next event.
+#[derive(Default)]
+pub struct WsEventBus {
+	subscribers: RwLock<Vec<mpsc::Sender<WsEvent>>>,
+}
+
+impl WsEventBus {
+	pub fn new() -> WsEventBus {
+		WsEventBus {
+			subscribers: RwLock::new(vec![]),
+		}
+	}
+
+	/// Send the event to all connected clients
+	pub fn publish(&self, event: WsEvent) {
+		let mut subscribers = self.subscribers.write();
+		subscribers.retain(|tx| match tx.try_send(event.clone()) {
+			Ok(()) => true,
+			Err(mpsc::error::TrySendError::Full(_)) => {
+				debug!("WebSocket client events queue is full, dropping the event");
+				true
+			}
+			Err(mpsc::error::TrySendError::Closed(_)) => false,
+		});
+	}
+
+	fn subscribe(&self) -> Option<mpsc::Receiver<WsEvent>> {
+		let mut subscribers = self.subscribers.write();
+		subscribers.retain(|tx| !tx.is_closed());
+		if subscribers.len() >= MAX_WS_CLIENTS {
+			return None;
+		}
+		let (tx, rx) = mpsc::channel(WS_EVENTS_QUEUE);
+		subscribers.push(tx);
+		Some(rx)
+	}
+}
+
+/// WebSocket endpoint for the node events subscriptions
+/// GET /v2/ws
+pub struct WsHandler {
+	pub events: Arc<WsEventBus>,
+	pub sync_state: Weak<SyncState>,
+}
+
+impl Handler for WsHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let is_upgrade = req
+			.headers()
+			.get(UPGRADE)
+			.and_then(|v| v.to_str().ok())
+			.map(|v| v.eq_ignore_ascii_case("websocket"))
+			.unwrap_or(false);
+		let accept_key = match req.headers().get(SEC_WEBSOCKET_KEY) {
+			Some(key) if is_upgrade => derive_accept_key(key.as_bytes()),
+			_ => return response(StatusCode::BAD_REQUEST, "WebSocket upgrade is expected"),
+		};
+		let events = match self.events.subscribe() {
+			Some(events) => events,
+			None => {
+				return response(
+					StatusCode::SERVICE_UNAVAILABLE,
+					"Too many WebSocket clients",
+				)
+			}
+		};
+		let sync_state = self.sync_state.clone();
+
+		Box::pin(async move {
+			tokio::spawn(async move {
+				match hyper::upgrade::on(req).await {
+					Ok(upgraded) => {
+						let ws =
+							WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+						serve_client(ws, events, sync_state).await;
+					}
+					Err(e) => debug!("WebSocket upgrade failed, {}", e),
+				}
+			});
+			let mut resp = Response::new(Body::empty());
+			*resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+			let headers = resp.headers_mut();
+			headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+			headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+			if let Ok(accept_key) = HeaderValue::from_str(&accept_key) {
+				headers.insert(SEC_WEBSOCKET_ACCEPT, accept_key);
+			}
+			Ok(resp)
+		})
+	}
+}
+
+fn notification(subscription: WsSubscription, params: Value) -> Message {
+	Message::Text(
+		json!({
+			"jsonrpc": "2.0",
+			"method": subscription.name(),
+			"params": params,
+		})
+		.to_string(),
+	)
+}
+
+/// Process the subscribe/unsubscribe call, returns the reply
+fn handle_call(text: &str, subscriptions: &mut HashSet<WsSubscription>) -> Value {
+	let call: Value = match serde_json::from_str(text) {
+		Ok(call) => call,
+		Err(e) => {
+			return json!({
+				"jsonrpc": "2.0",
+				"id": Value::Null,
+				"error": {"code": PARSE_ERROR, "message": format!("Parse error, {}", e)},
+			})
+		}
+	};
+	let id = call.get("id").cloned().unwrap_or(Value::Null);
+	let method = call.get("method").and_then(|m| m.as_str()).unwrap_or("");
+
+	let done = if let Some(name) = method.strip_prefix("subscribe_") {
+		WsSubscription::from_name(name).map(|s| subscriptions.insert(s))
+	} else if let Some(name) = method.strip_prefix("unsubscribe_") {
+		WsSubscription::from_name(name).map(|s| subscriptions.remove(&s))
+	} else {
+		None
+	};
+	match done {
+		Some(_) => json!({"jsonrpc": "2.0", "id": id, "result": {"Ok": Value::Null}}),
+		None => json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"error": {
+				"code": METHOD_NOT_FOUND,
+				"message": format!("Method '{}' is not available", method),
+			},
+		}),
+	}
+}
+
+async fn serve_client(
+	ws: WebSocketStream<Upgraded>,
+	mut events: mpsc::Receiver<WsEvent>,
+	sync_state: Weak<SyncState>,
+) {
+	let (mut sink, mut stream) = ws.split();
+	let mut subscriptions: HashSet<WsSubscription> = HashSet::new();
+	let mut sync_interval = tokio::time::interval(SYNC_STATUS_INTERVAL);
+	let mut last_sync_status: Option<Value> = None;
+
+	loop {
+		let msg = tokio::select! {
+			msg = stream.next() => match msg {
+				Some(Ok(Message::Text(text))) => {
+					Message::Text(handle_call(&text, &mut subscriptions).to_string())
+				}
+				Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+				// pings are answered by the protocol
+				Some(Ok(_)) => continue,
+			},
+			event = events.recv() => match event {
+				Some(event) => {
+					let subscription = event.subscription();
+					if !subscriptions.contains(&subscription) {
+						continue;
+					}
+					notification(subscription, json!(event))
+				}
+				None => break,
+			},
+			_ = sync_interval.tick() => {
+				if !subscriptions.contains(&WsSubscription::SyncStatus) {
+					continue;
+				}
+				let progress = match w(&sync_state) {
+					Ok(sync_state) => json!({
+						"status": sync_state.status(),
+						"progress": sync_state.progress(),
+					}),
+					Err(_) => break,
+				};
+				if last_sync_status.as_ref() == Some(&progress) {
+					continue;
+				}
+				last_sync_status = Some(progress.clone());
+				notification(WsSubscription::SyncStatus, progress)
+			}
+		};
+		if let Err(e) = sink.send(msg).await {
+			debug!("WebSocket client is disconnected, {}", e);
+			break;
+		}
+	}
+	let _ = sink.close().await;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn ws_subscription_calls() {
+		let mut subscriptions = HashSet::new();
+		let res = handle_call(
+			r#"{"jsonrpc": "2.0", "method": "subscribe_blocks", "id": 1}"#,
+			&mut subscriptions,
+		);
+		assert_eq!(res["id"], 1);
+		assert!(res.get("error").is_none());
+		assert!(subscriptions.contains(&WsSubscription::Blocks));
+
+		let res = handle_call(
+			r#"{"jsonrpc": "2.0", "method": "unsubscribe_blocks", "id": 2}"#,
+			&mut subscriptions,
+		);
+		assert!(res.get("error").is_none());
+		assert!(subscriptions.is_empty());
+
+		let res = handle_call(
+			r#"{"jsonrpc": "2.0", "method": "subscribe_kernels", "id": 3}"#,
+			&mut subscriptions,
+		);
+		assert_eq!(res["error"]["code"], METHOD_NOT_FOUND);
+
+		let res = handle_call("not a json", &mut subscriptions);
+		assert_eq!(res["error"]["code"], PARSE_ERROR);
+	}
+
+	#[test]
+	fn ws_event_bus() {
+		let bus = WsEventBus::new();
+		let mut rx = bus.subscribe().unwrap();
+		bus.publish(WsEvent::MempoolTx {
+			hash: "00".to_string(),
+			fee: 1,
+			kernels: vec![],
+		});
+		let event = rx.try_recv().unwrap();
+		assert_eq!(event.subscription(), WsSubscription::Mempool);
+
+		// Disconnected client is removed
+		drop(rx);
+		bus.publish(WsEvent::MempoolTx {
+			hash: "01".to_string(),
+			fee: 1,
+			kernels: vec![],
+		});
+		assert!(bus.subscribers.read().is_empty());
+	}
+}
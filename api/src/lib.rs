@@ -33,12 +33,20 @@ extern crate log;
 mod web;
 pub mod auth;
 pub mod client;
+pub mod cors;
+mod dandelion;
 mod foreign;
 pub mod foreign_rpc;
 mod handlers;
 pub mod json_rpc;
+mod mining;
+mod mining_rpc;
+mod node_control;
 mod owner;
 pub mod owner_rpc;
+mod payment_proof;
+mod proxy;
+pub mod rate_limit;
 mod rest;
 mod router;
 mod stratum;
@@ -46,16 +54,31 @@ mod stratum_rpc;
 pub mod types;
 
 pub use crate::auth::{
-	BasicAuthMiddleware, BasicAuthURIMiddleware, MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
+	ApiScope, ApiTokenConfig, ApiTokenStore, BasicAuthMiddleware, BasicAuthURIMiddleware,
+	ClientCertMiddleware, TlsClientCert, TokenAuthMiddleware, MWC_BASIC_REALM,
+	MWC_FOREIGN_BASIC_REALM,
 };
+pub use crate::client::HttpClientConfig;
+pub use crate::cors::CorsConfig;
+pub use crate::dandelion::{validate_dandelion_config, DandelionControl};
 pub use crate::foreign::Foreign;
 pub use crate::foreign_rpc::ForeignRpc;
+pub use crate::handlers::health_api::HealthConfig;
 pub use crate::handlers::node_apis;
+pub use crate::handlers::read_only::ReadOnlyApiConfig;
+pub use crate::handlers::sse_api::{TipEvent, TipEventBus};
+pub use crate::handlers::wallet_proxy::WalletProxyConfig;
+pub use crate::handlers::ws_api::{WsEvent, WsEventBus};
+pub use crate::mining::{BlockTemplateProvider, MiningStatsProvider};
+pub use crate::mining_rpc::MiningRpc;
+pub use crate::node_control::NodeControl;
 pub use crate::owner::Owner;
 pub use crate::owner::{
 	get_server_onion_address, reset_server_onion_address, set_server_onion_address,
 };
 pub use crate::owner_rpc::OwnerRpc;
+pub use crate::payment_proof::payment_proof_message;
+pub use crate::rate_limit::{ApiRateLimiter, RateLimitConfig, RateLimitMiddleware};
 pub use crate::rest::*;
 pub use crate::router::*;
 pub use crate::types::*;
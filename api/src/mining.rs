@@ -0,0 +1,139 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mining API for the external mining controllers that don't speak stratum
+
+use crate::chain::SyncState;
+use crate::handlers::utils::w;
+use crate::rest::Error;
+use crate::types::{BlockSolution, BlockTemplate, MiningStats, MiningStatsSample};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// Default window of the mining stats, minutes
+const MINING_STATS_DEFAULT_WINDOW: u32 = 60;
+/// The stats history is kept for a day
+const MINING_STATS_MAX_WINDOW: u32 = 24 * 60;
+/// Default and maximal wait of the block template long poll, seconds
+const LONGPOLL_DEFAULT_TIMEOUT: u64 = 60;
+const LONGPOLL_MAX_TIMEOUT: u64 = 300;
+
+/// Source of the block templates, implemented by the node mining module
+pub trait BlockTemplateProvider: Send + Sync {
+	/// Current candidate block. Template is rebuilt when the chain head is changed
+	/// or when its TTL is expired.
+	fn get_block_template(&self) -> Result<BlockTemplate, Error>;
+
+	/// Wait until the template with `longpoll_id` is outdated by the new chain head or by
+	/// the new pool transactions, then return the current template. The current template
+	/// is returned when the timeout is expired.
+	fn wait_block_template(
+		&self,
+		longpoll_id: &str,
+		timeout: Duration,
+	) -> Result<BlockTemplate, Error>;
+
+	/// Validate the solution and submit the block to the chain, returns the block hash
+	fn submit_block(&self, solution: BlockSolution) -> Result<String, Error>;
+}
+
+/// Source of the stratum hashrate and shares history, implemented by the stratum stats
+pub trait MiningStatsProvider: Send + Sync {
+	/// Per minute samples of the last `minutes`, the oldest first. None if the stratum
+	/// server is disabled.
+	fn mining_stats(&self, minutes: u32) -> Option<Vec<MiningStatsSample>>;
+}
+
+pub struct Mining {
+	provider: Option<Arc<dyn BlockTemplateProvider>>,
+	stats: Option<Arc<dyn MiningStatsProvider>>,
+	sync_state: Weak<SyncState>,
+}
+
+impl Mining {
+	/// Create a new API instance with the block templates provider
+	///
+	/// # Arguments
+	/// * `provider` - block templates provider, None if the node mining is not configured
+	/// * `stats` - stratum stats history provider
+	/// * `sync_state` - A non-owning reference of the `sync_state`.
+	///
+	pub fn new(
+		provider: Option<Arc<dyn BlockTemplateProvider>>,
+		stats: Option<Arc<dyn MiningStatsProvider>>,
+		sync_state: Weak<SyncState>,
+	) -> Self {
+		Mining {
+			provider,
+			stats,
+			sync_state,
+		}
+	}
+
+	fn provider(&self) -> Result<&Arc<dyn BlockTemplateProvider>, Error> {
+		if w(&self.sync_state)?.is_syncing() {
+			return Err(Error::Internal("Node is syncing".to_string()));
+		}
+		self.provider.as_ref().ok_or(Error::Internal(
+			"Mining is not configured, stratum_mining_config is missing".to_string(),
+		))
+	}
+
+	/// Get the current block template
+	pub fn get_block_template(&self) -> Result<BlockTemplate, Error> {
+		self.provider()?.get_block_template()
+	}
+
+	/// Wait for the block template that replaces the one with the `longpoll_id`. The call
+	/// returns when there is a new chain head, the pool fees are grown or the `timeout`
+	/// (in seconds, 60 by default) is expired.
+	pub fn get_block_template_longpoll(
+		&self,
+		longpoll_id: String,
+		timeout: Option<u64>,
+	) -> Result<BlockTemplate, Error> {
+		let timeout = timeout.unwrap_or(LONGPOLL_DEFAULT_TIMEOUT);
+		if timeout > LONGPOLL_MAX_TIMEOUT {
+			return Err(Error::Argument(format!(
+				"timeout must be at most {} seconds, got {}",
+				LONGPOLL_MAX_TIMEOUT, timeout
+			)));
+		}
+		self.provider()?
+			.wait_block_template(&longpoll_id, Duration::from_secs(timeout))
+	}
+
+	/// Submit the solved block template
+	pub fn submit_block(&self, solution: BlockSolution) -> Result<String, Error> {
+		self.provider()?.submit_block(solution)
+	}
+
+	/// Get the stratum hashrate and shares history of the last `window` minutes,
+	/// 60 minutes by default
+	pub fn get_mining_stats(&self, window: Option<u32>) -> Result<MiningStats, Error> {
+		let window = window.unwrap_or(MINING_STATS_DEFAULT_WINDOW);
+		if window == 0 || window > MINING_STATS_MAX_WINDOW {
+			return Err(Error::Argument(format!(
+				"window must be in 1..={} minutes, got {}",
+				MINING_STATS_MAX_WINDOW, window
+			)));
+		}
+		let samples = self
+			.stats
+			.as_ref()
+			.and_then(|stats| stats.mining_stats(window))
+			.ok_or(Error::NotFound("Stratum server is disabled".to_string()))?;
+		Ok(MiningStats::from_samples(window, samples))
+	}
+}
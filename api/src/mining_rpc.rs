@@ -0,0 +1,198 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON-RPC Stub generation for the Mining API
+
+use crate::mining::Mining;
+use crate::rest::*;
+use crate::types::{BlockSolution, BlockTemplate, MiningStats};
+
+/// Public definition used to generate Node jsonrpc api.
+/// * When running `mwc` with defaults, the V2 api is available at
+/// `localhost:3413/v2/mining`
+/// * The endpoint only supports POST operations, with the json-rpc request as the body
+/// * The endpoint requires the owner API secret
+#[easy_jsonrpc_mw::rpc]
+pub trait MiningRpc: Sync + Send {
+	/**
+	Get the current candidate block to mine. The coinbase is built by the node with the
+	wallet listener from `stratum_mining_config`. The template is rebuilt on the new chain
+	head or when `ttl` seconds are passed, the solutions for the previous templates of the
+	same height are still accepted.
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "get_block_template",
+		"params": [],
+		"id": 1
+	}
+
+	Respond:
+	{
+	  "id": 1,
+	  "jsonrpc": "2.0",
+	  "result": {
+		"Ok": {
+		  "template_id": 0,
+		  "height": 374274,
+		  "prev_hash": "000002b72ed6b5bbd1dfbcd2bd4a6e18e5f3a3b06afd0c0d8b9d6db26a8d39e1",
+		  "pre_pow": "0001000000000005b5820000000065d3d1a2...",
+		  "difficulty": 1163289,
+		  "ttl": 60,
+		  "longpoll_id": "000002b72ed6b5bbd1dfbcd2bd4a6e18e5f3a3b06afd0c0d8b9d6db26a8d39e1-0",
+		  "coinbase": {
+			"reward": 2380952380,
+			"fees": 0,
+			"commit": "08b7e57c448db5ef25aa119dde2312c64d7ff1b890c416c6dda5ec73cbfed2edea",
+			"excess": "09a4cdc2f1d1cf6d0c2ff5d3c8e8a0f2d8b2e97f3d2c0d5f2ab4c61e4f1e3a9b7c"
+		  }
+		}
+	  }
+	}
+	*/
+	fn get_block_template(&self) -> Result<BlockTemplate, Error>;
+
+	/**
+	Long poll for the block template. The call waits until the template with `longpoll_id`
+	(from the previous template) is replaced: there is a new chain head or the fees of the
+	pool transactions are grown by 10%. The current template is returned when the `timeout`
+	(seconds, 60 by default, at most 300) is expired, so the miner can call it in a loop.
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "get_block_template_longpoll",
+		"params": {
+			"longpoll_id": "000002b72ed6b5bbd1dfbcd2bd4a6e18e5f3a3b06afd0c0d8b9d6db26a8d39e1-0",
+			"timeout": 120
+		},
+		"id": 1
+	}
+
+	Respond is the same as for `get_block_template`, with the new `longpoll_id`.
+	*/
+	fn get_block_template_longpoll(
+		&self,
+		longpoll_id: String,
+		timeout: Option<u64>,
+	) -> Result<BlockTemplate, Error>;
+
+	/**
+	Submit the solution for the block template. The block is validated and broadcasted,
+	the block hash is returned.
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "submit_block",
+		"params": {
+			"solution": {
+				"template_id": 0,
+				"height": 374274,
+				"nonce": 8834235952393448269,
+				"edge_bits": 31,
+				"pow": [4170276, 18798889, 28385325, 32484716, 48271539, 56153214, 60302618, 84087765,
+					97419340, 135706386, 151101939, 167329946, 187391652, 207233302, 232318406,
+					238449498, 254312386, 264232651, 281434022, 291609627, 311962296, 329447548,
+					341051587, 365519467, 374036548, 382437574, 404137946, 418153394, 425628307,
+					457125862, 461768839, 473893916, 487302530, 495290005, 503908530, 524049577,
+					530564618, 547017022, 553698541, 565044126, 586574006, 604232002]
+			}
+		},
+		"id": 1
+	}
+
+	Respond:
+	{
+	  "id": 1,
+	  "jsonrpc": "2.0",
+	  "result": {
+		"Ok": "0000165b3d1e2bc2a4b1c6e1a2d29c5c7f3f54c62bd5ab0c2b6b1e1b1c3c4d5e"
+	  }
+	}
+	*/
+	fn submit_block(&self, solution: BlockSolution) -> Result<String, Error>;
+
+	/**
+	Get the stratum hashrate and shares history. The stratum server takes a sample every
+	minute, the history is kept for a day. `window` is in minutes, 60 by default. The worker
+	hashrate is estimated from its accepted shares, in graphs per second.
+
+	Request:
+	{
+		"jsonrpc": "2.0",
+		"method": "get_mining_stats",
+		"params": [30],
+		"id": 1
+	}
+
+	Respond:
+	{
+	  "id": 1,
+	  "jsonrpc": "2.0",
+	  "result": {
+		"Ok": {
+		  "window": 30,
+		  "accepted": 182,
+		  "rejected": 2,
+		  "stale": 4,
+		  "acceptance_rate": 0.9680851063829787,
+		  "samples": [
+			{
+			  "time": "2024-05-20T10:15:00Z",
+			  "network_hashrate": 12.41,
+			  "network_difficulty": 1163289,
+			  "workers": 1,
+			  "accepted": 6,
+			  "rejected": 0,
+			  "stale": 1,
+			  "blocks_found": 0,
+			  "worker_hashrates": [
+				{
+				  "id": "rig1",
+				  "hashrate": 67.2,
+				  "accepted": 6
+				}
+			  ]
+			}
+		  ]
+		}
+	  }
+	}
+	*/
+	fn get_mining_stats(&self, window: Option<u32>) -> Result<MiningStats, Error>;
+}
+
+impl MiningRpc for Mining {
+	fn get_block_template(&self) -> Result<BlockTemplate, Error> {
+		Mining::get_block_template(self)
+	}
+
+	fn get_block_template_longpoll(
+		&self,
+		longpoll_id: String,
+		timeout: Option<u64>,
+	) -> Result<BlockTemplate, Error> {
+		Mining::get_block_template_longpoll(self, longpoll_id, timeout)
+	}
+
+	fn submit_block(&self, solution: BlockSolution) -> Result<String, Error> {
+		Mining::submit_block(self, solution)
+	}
+
+	fn get_mining_stats(&self, window: Option<u32>) -> Result<MiningStats, Error> {
+		Mining::get_mining_stats(self, window)
+	}
+}
@@ -0,0 +1,53 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remote management of the node process: the shutdown, the sync restart and the config
+//! reload. They live in the server, so the owner API reaches them through this trait. The
+//! pool, stratum and Tor state for the node status comes from here for the same reason.
+
+use crate::types::{
+	ConfigReload, DiskSubsystemStatus, PoolSubsystemStatus, StratumSubsystemStatus,
+	StratumWorkerStatus, TorSubsystemStatus,
+};
+
+/// Node process control, implemented by the server
+pub trait NodeControl: Send + Sync {
+	/// Request the graceful shutdown. The node stops its threads and exits, the call
+	/// returns right away.
+	fn shutdown(&self);
+
+	/// Drop the sync progress and the downloaded state, then restart the headers and the
+	/// PIBD sync from the scratch. The headers chain is kept. The sync thread does the
+	/// restart, the call returns right away.
+	fn restart_sync(&self) -> Result<(), String>;
+
+	/// Read the config file again and apply the settings that can be changed at runtime.
+	/// The others are reported as requiring the restart.
+	fn reload_config(&self) -> Result<ConfigReload, String>;
+
+	/// Transaction pool size and the minimal fee
+	fn pool_status(&self) -> PoolSubsystemStatus;
+
+	/// Stratum server state, None if it is disabled
+	fn stratum_status(&self) -> Option<StratumSubsystemStatus>;
+
+	/// Stats of the stratum worker logins, None if stratum is disabled
+	fn stratum_workers(&self) -> Option<Vec<StratumWorkerStatus>>;
+
+	/// Tor state, None if Tor is disabled
+	fn tor_status(&self) -> Option<TorSubsystemStatus>;
+
+	/// Chain data size and the free disk space
+	fn disk_status(&self) -> DiskSubsystemStatus;
+}
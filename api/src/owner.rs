@@ -15,18 +15,34 @@
 
 //! Owner API External Definition
 
-use crate::chain::{Chain, SyncState};
+use crate::chain::reorg_guard::{self, PendingReorg};
+use crate::chain::{Chain, SyncEvent, SyncProgress, SyncState};
 use crate::core::core::hash::Hash;
-use crate::handlers::chain_api::{ChainCompactHandler, ChainResetHandler, ChainValidationHandler};
+use crate::dandelion::{validate_dandelion_config, DandelionControl};
+use crate::handlers::chain_api::{
+	ChainCompactHandler, ChainResetHandler, ChainSnapshotHandler, ChainUtxoArchiveHandler,
+	ChainUtxoDumpHandler, ChainValidationHandler,
+};
 use crate::handlers::peers_api::{PeerHandler, PeersConnectedHandler};
 use crate::handlers::server_api::StatusHandler;
-use crate::p2p::{self, PeerData};
+use crate::handlers::utils::w;
+use crate::node_control::NodeControl;
+use crate::p2p::bandwidth::BandwidthLimits;
+use crate::p2p::{self, PeerChanges, PeerData};
+use crate::pool::DandelionConfig;
 use crate::rest::*;
-use crate::types::Status;
-use mwc_p2p::types::PeerInfoDisplayLegacy;
+use crate::types::{
+	ChainCompaction, ConfigReload, DandelionStats, LogLevels, ReorgEvent, SnapshotInfo, Status,
+	StatusV2, StratumWorkerStatus, UtxoArchiveInfo, UtxoDumpInfo,
+};
+use log::LevelFilter;
+use mwc_p2p::types::{PeerInfoDisplayLegacy, PeerStatsDisplay};
+use mwc_util::logger;
 use mwc_util::Mutex;
 use std::net::SocketAddr;
-use std::sync::Weak;
+use std::str::FromStr;
+use std::sync::{Arc, Weak};
+use std::time::Instant;
 
 lazy_static! {
 	static ref SERVER_ONION_ADDRESS: Mutex<Option<String>> = Mutex::new(None);
@@ -56,6 +72,8 @@ pub struct Owner {
 	pub chain: Weak<Chain>,
 	pub peers: Weak<p2p::Peers>,
 	pub sync_state: Weak<SyncState>,
+	pub dandelion: Option<Arc<dyn DandelionControl>>,
+	pub node_control: Option<Arc<dyn NodeControl>>,
 }
 
 impl Owner {
@@ -67,16 +85,26 @@ impl Owner {
 	/// * `tx_pool` - A non-owning reference of the transaction pool.
 	/// * `peers` - A non-owning reference of the peers.
 	/// * `sync_state` - A non-owning reference of the `sync_state`.
+	/// * `dandelion` - Dandelion settings control, None if not available.
+	/// * `node_control` - Shutdown and sync restart control, None if not available.
 	///
 	/// # Returns
 	/// * An instance of the Node holding references to the current chain, transaction pool, peers and sync_state.
 	///
 
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		peers: Weak<p2p::Peers>,
+		sync_state: Weak<SyncState>,
+		dandelion: Option<Arc<dyn DandelionControl>>,
+		node_control: Option<Arc<dyn NodeControl>>,
+	) -> Self {
 		Owner {
 			chain,
 			peers,
 			sync_state,
+			dandelion,
+			node_control,
 		}
 	}
 
@@ -98,6 +126,26 @@ impl Owner {
 		status_handler.get_status()
 	}
 
+	/// Returns the node status by subsystem: p2p connections and recent bans, sync phase
+	/// and ETA, chain tip and the last reorg, pool size and minimal fee, stratum workers
+	/// and hashrate, Tor bootstrap and the onion address, chain data size and free disk space.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`StatusV2`](types/struct.StatusV2.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_status_v2(&self) -> Result<StatusV2, Error> {
+		let status_handler = StatusHandler {
+			chain: self.chain.clone(),
+			peers: self.peers.clone(),
+			sync_state: self.sync_state.clone(),
+			allow_to_stop: false,
+		};
+		status_handler.get_status_v2(self.node_control.as_ref())
+	}
+
 	/// Trigger a validation of the chain state.
 	///
 	/// # Arguments
@@ -131,6 +179,103 @@ impl Owner {
 		chain_compact_handler.compact_chain()
 	}
 
+	/// Compact the chain state right away. Unlike `compact_chain`, the compaction is not
+	/// skipped when the node was compacted recently. The call blocks until it is done.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ChainCompaction`](types/struct.ChainCompaction.html) with the tail heights
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn compact_now(&self) -> Result<ChainCompaction, Error> {
+		let chain = w(&self.chain)?;
+		let tail_before = chain
+			.tail()
+			.map_err(|e| Error::Internal(format!("Unable to get chain tail, {}", e)))?;
+		let started = Instant::now();
+		chain
+			.compact_now()
+			.map_err(|e| Error::Internal(format!("compact chain error {}", e)))?;
+		let duration_ms = started.elapsed().as_millis() as u64;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("Unable to get chain tip, {}", e)))?;
+		let tail_after = chain
+			.tail()
+			.map_err(|e| Error::Internal(format!("Unable to get chain tail, {}", e)))?;
+		info!(
+			"Chain is compacted by the owner API in {} ms, tail {} -> {}",
+			duration_ms, tail_before.height, tail_after.height
+		);
+		Ok(ChainCompaction {
+			height: head.height,
+			tail_height_before: tail_before.height,
+			tail_height_after: tail_after.height,
+			duration_ms,
+		})
+	}
+
+	/// Write the canonical dump of the UTXO set at the current head, with the MMR roots,
+	/// into the file. Dumps from different nodes can be compared with `mwc client verify-utxo-dump`.
+	///
+	/// # Arguments
+	/// * `path` - the dump file name. The file is written into the `utxo_dumps` directory
+	/// of the node data, the paths are rejected.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`UtxoDumpInfo`](types/struct.UtxoDumpInfo.html) with the dumped block and roots
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn export_utxo_set(&self, path: String) -> Result<UtxoDumpInfo, Error> {
+		let handler = ChainUtxoDumpHandler {
+			chain: self.chain.clone(),
+		};
+		handler.export_utxo_set(&path)
+	}
+
+	/// Write the consistent snapshot of the chain data (LMDB and the txhashset files) into
+	/// the empty directory while the node runs. The snapshot is restored with
+	/// `mwc server snapshot --restore` on the stopped node.
+	///
+	/// # Arguments
+	/// * `path` - the snapshot directory at the node host, it must not exist or be empty.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`SnapshotInfo`](types/struct.SnapshotInfo.html) with the snapshot head
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn create_snapshot(&self, path: String) -> Result<SnapshotInfo, Error> {
+		let handler = ChainSnapshotHandler {
+			chain: self.chain.clone(),
+		};
+		handler.create_snapshot(&path)
+	}
+
+	/// Write the portable UTXO archive: the headers and the PIBD segments of the txhashset
+	/// at the archive header. A new node is bootstrapped from it with
+	/// `mwc server utxo-archive --import`, the data is fully validated there.
+	///
+	/// # Arguments
+	/// * `path` - the archive file at the node host, it must not exist.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`UtxoArchiveInfo`](types/struct.UtxoArchiveInfo.html) with the archive header
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn write_utxo_archive(&self, path: String) -> Result<UtxoArchiveInfo, Error> {
+		let handler = ChainUtxoArchiveHandler {
+			chain: self.chain.clone(),
+		};
+		handler.write_utxo_archive(&path)
+	}
+
 	pub fn reset_chain_head(&self, hash: String) -> Result<(), Error> {
 		let hash =
 			Hash::from_hex(&hash).map_err(|_| Error::RequestError("invalid header hash".into()))?;
@@ -151,6 +296,55 @@ impl Owner {
 		handler.invalidate_header(hash)
 	}
 
+	/// Returns the reorgs deeper than the max reorg depth that are waiting for the approval.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`PendingReorg`](../mwc_chain/reorg_guard/struct.PendingReorg.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_pending_reorgs(&self) -> Result<Vec<PendingReorg>, Error> {
+		Ok(reorg_guard::pending_reorgs())
+	}
+
+	/// Returns the reorgs of the chain head that this node went through, the oldest first.
+	/// A deposit at some height can be checked against the reorgs that rolled it back.
+	///
+	/// # Arguments
+	/// * `height` - if provided, only the reorgs that rolled back the block at this height.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`ReorgEvent`](types/struct.ReorgEvent.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_reorg_history(&self, height: Option<u64>) -> Result<Vec<ReorgEvent>, Error> {
+		let events = w(&self.chain)?
+			.reorg_history(height)
+			.map_err(|e| Error::Internal(format!("Unable to read the reorg history, {}", e)))?;
+		Ok(events.iter().map(ReorgEvent::from_event).collect())
+	}
+
+	/// Approves the pending deep reorg. The fork is applied with the next fork block
+	/// that the node gets from the peers.
+	///
+	/// # Arguments
+	/// * `fork_hash` - hash of the first fork block, as reported by `get_pending_reorgs`.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the reorg is approved
+	/// * or [`Error`](struct.Error.html) if there is no such pending reorg.
+	///
+
+	pub fn approve_reorg(&self, fork_hash: String) -> Result<(), Error> {
+		let hash = Hash::from_hex(&fork_hash)
+			.map_err(|_| Error::RequestError("invalid fork hash".into()))?;
+		reorg_guard::approve_reorg(&hash).map_err(|e| Error::RequestError(e.to_string()))
+	}
+
 	/// Retrieves information about stored peers.
 	/// If `None` is provided, will list all stored peers.
 	///
@@ -185,6 +379,43 @@ impl Owner {
 		peers_connected_handler.get_connected_peers()
 	}
 
+	/// Retrieves protocol statistics for all connected peers: message and byte rates
+	/// for the last minute, last ping round trip time and capabilities.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`PeerStatsDisplay`](types/struct.PeerStatsDisplay.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_peer_stats(&self) -> Result<Vec<PeerStatsDisplay>, Error> {
+		let peers_connected_handler = PeersConnectedHandler {
+			peers: self.peers.clone(),
+		};
+		peers_connected_handler.get_peer_stats()
+	}
+
+	/// Retrieves the changes of the connected peers set (connects, disconnects and
+	/// height updates) since the provided sequence number. Monitoring clients can
+	/// poll it instead of reloading the full peers list.
+	///
+	/// # Arguments
+	/// * `since` - the last sequence number that the client has seen, 0 for the first call.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * [`PeerChanges`](types/struct.PeerChanges.html). If `resync` is set, the client
+	/// needs to reload the connected peers with `get_connected_peers`.
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_peer_changes(&self, since: u64) -> Result<PeerChanges, Error> {
+		let peers_connected_handler = PeersConnectedHandler {
+			peers: self.peers.clone(),
+		};
+		peers_connected_handler.get_peer_changes(since)
+	}
+
 	/// Bans a specific peer.
 	///
 	/// # Arguments
@@ -220,4 +451,270 @@ impl Owner {
 		};
 		peer_handler.unban_peer(addr)
 	}
+
+	/// Switches the node between clearnet+Tor and Tor only operation without the restart.
+	/// In Tor only mode the clearnet peers are closed, only onion addresses are dialed
+	/// and our IP address is not advertised in the handshakes.
+	///
+	/// # Arguments
+	/// * `tor_only` - true to switch to Tor only mode, false to allow the clearnet peers again.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * Number of the closed clearnet peers
+	/// * or [`Error`](struct.Error.html) if Tor is not running or an error is encountered.
+	///
+
+	pub fn set_tor_only(&self, tor_only: bool) -> Result<usize, Error> {
+		let peer_handler = PeerHandler {
+			peers: self.peers.clone(),
+		};
+		peer_handler.set_tor_only(tor_only)
+	}
+
+	/// Returns the node bandwidth budget that is shared by the sync and the blocks and
+	/// transactions relay.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BandwidthLimits`](../mwc_p2p/bandwidth/struct.BandwidthLimits.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_bandwidth_limits(&self) -> Result<BandwidthLimits, Error> {
+		let peer_handler = PeerHandler {
+			peers: self.peers.clone(),
+		};
+		peer_handler.get_bandwidth_limits()
+	}
+
+	/// Changes the node bandwidth budget without the restart. The sync traffic is paused
+	/// when it would use the share that is reserved for the relay, so the initial sync
+	/// can't starve the transactions relay on a limited link.
+	///
+	/// # Arguments
+	/// * `limit_kbps` - total limit for all peers and both directions, KB/s. 0 is unlimited.
+	/// * `relay_ratio` - share of the limit reserved for the relay, percent, up to 90.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The applied [`BandwidthLimits`](../mwc_p2p/bandwidth/struct.BandwidthLimits.html)
+	/// * or [`Error`](struct.Error.html) if the ratio is out of range or an error is encountered.
+	///
+
+	pub fn set_bandwidth_limits(
+		&self,
+		limit_kbps: u64,
+		relay_ratio: u8,
+	) -> Result<BandwidthLimits, Error> {
+		let peer_handler = PeerHandler {
+			peers: self.peers.clone(),
+		};
+		peer_handler.set_bandwidth_limits(limit_kbps, relay_ratio)
+	}
+
+	/// Returns the sync progress: the current phase (header hashes, headers, PIBD state,
+	/// validation, block bodies), items done/total, download rate and the phase ETA.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`SyncProgress`](../mwc_chain/types/struct.SyncProgress.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn sync_status(&self) -> Result<SyncProgress, Error> {
+		let status_handler = StatusHandler {
+			chain: self.chain.clone(),
+			peers: self.peers.clone(),
+			sync_state: self.sync_state.clone(),
+			allow_to_stop: false,
+		};
+		status_handler.get_sync_progress()
+	}
+
+	/// Returns the recent sync events, the oldest first: sync status changes, selected
+	/// sync peers and timed out requests. Up to 500 last events are kept.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`SyncEvent`](../mwc_chain/types/struct.SyncEvent.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_sync_events(&self) -> Result<Vec<SyncEvent>, Error> {
+		let status_handler = StatusHandler {
+			chain: self.chain.clone(),
+			peers: self.peers.clone(),
+			sync_state: self.sync_state.clone(),
+			allow_to_stop: false,
+		};
+		status_handler.get_sync_events()
+	}
+
+	fn dandelion(&self) -> Result<&Arc<dyn DandelionControl>, Error> {
+		self.dandelion.as_ref().ok_or(Error::Internal(
+			"Dandelion control is not available".to_string(),
+		))
+	}
+
+	/// Returns the current Dandelion settings, initially from `[server.dandelion_config]`.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`DandelionConfig`](../mwc_pool/types/struct.DandelionConfig.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_dandelion_config(&self) -> Result<DandelionConfig, Error> {
+		Ok(self.dandelion()?.get_dandelion_config())
+	}
+
+	/// Replace the Dandelion settings without the node restart. The embargo and aggregation
+	/// timers are applied by the next run of the Dandelion monitor, the epoch duration
+	/// is applied to the current epoch, the stem probability from the next epoch.
+	/// The settings are not saved to the config file.
+	///
+	/// # Arguments
+	/// * `config` - new Dandelion settings.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The applied [`DandelionConfig`](../mwc_pool/types/struct.DandelionConfig.html)
+	/// * or [`Error`](struct.Error.html) if the settings are invalid or an error is encountered.
+	///
+
+	pub fn set_dandelion_config(&self, config: DandelionConfig) -> Result<DandelionConfig, Error> {
+		validate_dandelion_config(&config)?;
+		let dandelion = self.dandelion()?;
+		dandelion.set_dandelion_config(config);
+		Ok(dandelion.get_dandelion_config())
+	}
+
+	/// Returns the Dandelion epochs stats: the stem/fluff state and the stem relay peers
+	/// of the recent epochs.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`DandelionStats`](types/struct.DandelionStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_dandelion_stats(&self) -> Result<DandelionStats, Error> {
+		Ok(self.dandelion()?.get_dandelion_stats())
+	}
+
+	fn node_control(&self) -> Result<&Arc<dyn NodeControl>, Error> {
+		self.node_control
+			.as_ref()
+			.ok_or(Error::Internal("Node control is not available".to_string()))
+	}
+
+	/// Request the graceful shutdown of the node. The node stops its threads, flushes
+	/// the chain db and exits. The call returns before the node is stopped.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the shutdown is requested
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn shutdown(&self) -> Result<(), Error> {
+		let node_control = self.node_control()?;
+		warn!("Node shutdown is requested by the owner API");
+		node_control.shutdown();
+		Ok(())
+	}
+
+	/// Wipe the sync state and restart the sync. The chain body and the downloaded PIBD
+	/// segments are dropped, then the headers sync is restarted and the state is
+	/// downloaded again. The headers chain is kept. Use it to recover a node that is stuck
+	/// with the broken state.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the sync restart is requested, the sync thread restarts it shortly
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn restart_sync(&self) -> Result<(), Error> {
+		let node_control = self.node_control()?;
+		warn!("Sync restart is requested by the owner API");
+		node_control
+			.restart_sync()
+			.map_err(|e| Error::Internal(format!("Unable to restart sync, {}", e)))
+	}
+
+	/// Read the config file again and apply the settings that can be changed without the
+	/// restart: the peer counts, the log levels, the Dandelion settings, the API rate limits
+	/// and the pool `tx_fee_base`. The same as sending SIGHUP to the node process.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ConfigReload`](types/struct.ConfigReload.html) with the applied changes and
+	/// the changes that require the restart
+	/// * or [`Error`](struct.Error.html) if the config can't be loaded or is invalid.
+	///
+
+	pub fn reload_config(&self) -> Result<ConfigReload, Error> {
+		self.node_control()?
+			.reload_config()
+			.map_err(|e| Error::Internal(format!("Unable to reload config, {}", e)))
+	}
+
+	/// Returns the log levels: the stdout and file levels from the config and the module
+	/// levels that override them.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`LogLevels`](types/struct.LogLevels.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_log_levels(&self) -> Result<LogLevels, Error> {
+		Ok(LogLevels::current())
+	}
+
+	/// Sets the log level of the module and its submodules at runtime, the level is used
+	/// instead of the stdout and file levels. The levels are not saved to the config file.
+	///
+	/// # Arguments
+	/// * `module` - module path, like `mwc_p2p` or `mwc_servers::mwc::sync`.
+	/// * `level` - one of `off`, `error`, `warn`, `info`, `debug`, `trace`. None restores
+	/// the stdout and file levels for the module.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The updated [`LogLevels`](types/struct.LogLevels.html)
+	/// * or [`Error`](struct.Error.html) if the module or the level is invalid.
+	///
+
+	pub fn set_log_level(&self, module: String, level: Option<String>) -> Result<LogLevels, Error> {
+		if module.is_empty() || module.contains(char::is_whitespace) {
+			return Err(Error::Argument(format!("Invalid module path '{}'", module)));
+		}
+		let level = match level {
+			Some(level) => Some(
+				LevelFilter::from_str(&level)
+					.map_err(|_| Error::Argument(format!("Invalid log level '{}'", level)))?,
+			),
+			None => None,
+		};
+		logger::set_module_log_level(&module, level);
+		Ok(LogLevels::current())
+	}
+
+	/// Returns the stratum worker logins with their share stats, summed over all the
+	/// connections of the login. The registered workers are listed before they connect.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`StratumWorkerStatus`](types/struct.StratumWorkerStatus.html)
+	/// * or [`Error`](struct.Error.html) if the stratum server is disabled.
+	///
+
+	pub fn get_stratum_workers(&self) -> Result<Vec<StratumWorkerStatus>, Error> {
+		self.node_control()?
+			.stratum_workers()
+			.ok_or(Error::NotFound("Stratum server is disabled".to_string()))
+	}
 }
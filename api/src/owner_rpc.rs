@@ -15,11 +15,18 @@
 
 //! JSON-RPC Stub generation for the Owner API
 
+use crate::chain::reorg_guard::PendingReorg;
+use crate::chain::{SyncEvent, SyncProgress};
 use crate::owner::Owner;
-use crate::p2p::PeerData;
+use crate::p2p::bandwidth::BandwidthLimits;
+use crate::p2p::{PeerChanges, PeerData};
+use crate::pool::DandelionConfig;
 use crate::rest::Error;
-use crate::types::Status;
-use mwc_p2p::types::PeerInfoDisplayLegacy;
+use crate::types::{
+	ChainCompaction, ConfigReload, DandelionStats, LogLevels, ReorgEvent, SnapshotInfo, Status,
+	StatusV2, StratumWorkerStatus, UtxoArchiveInfo, UtxoDumpInfo,
+};
+use mwc_p2p::types::{PeerInfoDisplayLegacy, PeerStatsDisplay};
 use std::net::SocketAddr;
 
 /// Public definition used to generate Node jsonrpc api.
@@ -73,6 +80,84 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn get_status(&self) -> Result<Status, Error>;
 
+	/**
+	Networked version of [Owner::get_status_v2](struct.Owner.html#method.get_status_v2).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_status_v2",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"protocol_version": 3,
+				"user_agent": "MW/MWC 5.x.x",
+				"p2p": {
+					"inbound": 12,
+					"outbound": 8,
+					"bans_last_hour": 1
+				},
+				"sync": {
+					"phase": "Synced",
+					"status": "no_sync",
+					"done": 0,
+					"total": 0,
+					"eta_secs": null
+				},
+				"chain": {
+					"tip": {
+						"height": 2231540,
+						"last_block_pushed": "00001d1623db988d7ed10c5b6319360a52f20c89b4710474145806ba0e8455ec",
+						"prev_block_to_last": "0000029f51bacee81c49a27b4bc9c6c446e03183867c922890f90bb17108d89f",
+						"total_difficulty": 1127628411943045
+					},
+					"last_reorg": {
+						"height": 2231102,
+						"fork_height": 2231100,
+						"depth": 1,
+						"time": "2024-05-02T09:41:13.201Z"
+					}
+				},
+				"pool": {
+					"size": 3,
+					"stem_size": 0,
+					"min_fee_base": 1000,
+					"eviction_fee_rate": null
+				},
+				"stratum": null,
+				"tor": {
+					"external": false,
+					"bootstrapped": true,
+					"onion_address": "fxtsf2p3pdrenpozrxmlxwxwbslo6j5pvyqcstaxmlgvmlvqs4uoloid.onion"
+				},
+				"disk": {
+					"chain_data_bytes": 10452367361,
+					"free_bytes": 52354318336,
+					"min_free_bytes": 1073741824,
+					"blocks_paused": false,
+					"state_download_refused": false
+				}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_status_v2(&self) -> Result<StatusV2, Error>;
+
 	/**
 	Networked version of [Owner::validate_chain](struct.Owner.html#method.validate_chain).
 
@@ -133,10 +218,161 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn compact_chain(&self) -> Result<(), Error>;
 
+	/**
+	Networked version of [Owner::compact_now](struct.Owner.html#method.compact_now).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "compact_now",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"height": 2231540,
+				"tail_height_before": 2219180,
+				"tail_height_after": 2221460,
+				"duration_ms": 8125
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn compact_now(&self) -> Result<ChainCompaction, Error>;
+
+	/**
+	Networked version of [Owner::shutdown](struct.Owner.html#method.shutdown).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "shutdown",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn shutdown(&self) -> Result<(), Error>;
+
+	/**
+	Networked version of [Owner::restart_sync](struct.Owner.html#method.restart_sync).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "restart_sync",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn restart_sync(&self) -> Result<(), Error>;
+
+	/**
+	Networked version of [Owner::reload_config](struct.Owner.html#method.reload_config).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "reload_config",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"applied": [
+					"logging.stdout_log_level",
+					"server.p2p_config.peer_max_outbound_count"
+				],
+				"restart_required": [
+					"server.api_http_addr"
+				]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn reload_config(&self) -> Result<ConfigReload, Error>;
+
 	fn reset_chain_head(&self, hash: String) -> Result<(), Error>;
 
+	/// Networked version of [Owner::export_utxo_set](struct.Owner.html#method.export_utxo_set).
+	fn export_utxo_set(&self, path: String) -> Result<UtxoDumpInfo, Error>;
+
+	/// Networked version of [Owner::create_snapshot](struct.Owner.html#method.create_snapshot).
+	fn create_snapshot(&self, path: String) -> Result<SnapshotInfo, Error>;
+
+	/// Networked version of [Owner::write_utxo_archive](struct.Owner.html#method.write_utxo_archive).
+	fn write_utxo_archive(&self, path: String) -> Result<UtxoArchiveInfo, Error>;
+
 	fn invalidate_header(&self, hash: String) -> Result<(), Error>;
 
+	/// Networked version of [Owner::get_pending_reorgs](struct.Owner.html#method.get_pending_reorgs).
+	fn get_pending_reorgs(&self) -> Result<Vec<PendingReorg>, Error>;
+
+	/// Networked version of [Owner::get_reorg_history](struct.Owner.html#method.get_reorg_history).
+	fn get_reorg_history(&self, height: Option<u64>) -> Result<Vec<ReorgEvent>, Error>;
+
+	/// Networked version of [Owner::approve_reorg](struct.Owner.html#method.approve_reorg).
+	fn approve_reorg(&self, fork_hash: String) -> Result<(), Error>;
+
 	/**
 	Networked version of [Owner::get_peers](struct.Owner.html#method.get_peers).
 
@@ -168,7 +404,9 @@ pub trait OwnerRpc: Sync + Send {
 				"flags": "Defunct",
 				"last_banned": 0,
 				"last_connected": 1570129317,
-				"user_agent": "MW/MWC 2.0.0"
+				"drop_reason": "None",
+				"user_agent": "MW/MWC 2.0.0",
+				"verified": true
 			}
 			]
 		}
@@ -298,6 +536,103 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, Error>;
 
+	/**
+	Networked version of [Owner::get_peer_stats](struct.Owner.html#method.get_peer_stats).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_peer_stats",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+			{
+				"addr": "35.176.195.242:3414",
+				"capabilities": {
+				"bits": 15
+				},
+				"connected_since": 1700000000,
+				"direction": "Outbound",
+				"height": 374510,
+				"ping_rtt_ms": 84,
+				"received_bytes_per_min": 12840,
+				"received_msg_per_min": 9,
+				"sent_bytes_per_min": 1720,
+				"sent_msg_per_min": 8,
+				"total_difficulty": 1133954621205750,
+				"user_agent": "MW/MWC 2.0.0",
+				"version": 1
+			}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_peer_stats(&self) -> Result<Vec<PeerStatsDisplay>, Error>;
+
+	/**
+	Networked version of [Owner::get_peer_changes](struct.Owner.html#method.get_peer_changes).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_peer_changes",
+		"params": [1520],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"changes": [
+				{
+					"addr": "35.176.195.242:3414",
+					"height": 374511,
+					"kind": "HeightUpdated",
+					"seq": 1521,
+					"total_difficulty": 1133954633405101
+				},
+				{
+					"addr": "47.97.198.21:3414",
+					"height": 374510,
+					"kind": "Disconnected",
+					"seq": 1522,
+					"total_difficulty": 1133954621205750
+				}
+				],
+				"resync": false,
+				"seq": 1522
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_peer_changes(&self, since: u64) -> Result<PeerChanges, Error>;
+
 	/**
 	Networked version of [Owner::ban_peer](struct.Owner.html#method.ban_peer).
 
@@ -357,37 +692,441 @@ pub trait OwnerRpc: Sync + Send {
 	```
 	 */
 	fn unban_peer(&self, peer_addr: SocketAddr) -> Result<(), Error>;
-}
 
-impl OwnerRpc for Owner {
-	fn get_status(&self) -> Result<Status, Error> {
-		Owner::get_status(self)
-	}
+	/**
+	Networked version of [Owner::set_tor_only](struct.Owner.html#method.set_tor_only).
 
-	fn validate_chain(&self, assume_valid_rangeproofs_kernels: bool) -> Result<(), Error> {
-		Owner::validate_chain(self, assume_valid_rangeproofs_kernels)
-	}
+	# Json rpc example
 
-	fn reset_chain_head(&self, hash: String) -> Result<(), Error> {
-		Owner::reset_chain_head(self, hash)
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_tor_only",
+		"params": [true],
+		"id": 1
 	}
-
-	fn invalidate_header(&self, hash: String) -> Result<(), Error> {
-		Owner::invalidate_header(self, hash)
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": 3
+		}
 	}
+	# "#
+	# );
+	```
+	 */
+	fn set_tor_only(&self, tor_only: bool) -> Result<usize, Error>;
 
-	fn compact_chain(&self) -> Result<(), Error> {
-		Owner::compact_chain(self)
-	}
+	/// Networked version of [Owner::get_bandwidth_limits](struct.Owner.html#method.get_bandwidth_limits).
+	fn get_bandwidth_limits(&self) -> Result<BandwidthLimits, Error>;
 
-	fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, Error> {
-		Owner::get_peers(self, addr)
-	}
+	/**
+	Networked version of [Owner::set_bandwidth_limits](struct.Owner.html#method.set_bandwidth_limits).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_bandwidth_limits",
+		"params": [512, 40],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"limit_kbps": 512,
+				"relay_ratio": 40
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn set_bandwidth_limits(
+		&self,
+		limit_kbps: u64,
+		relay_ratio: u8,
+	) -> Result<BandwidthLimits, Error>;
+
+	/**
+	Networked version of [Owner::sync_status](struct.Owner.html#method.sync_status).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "sync_status",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"phase": "PibdState",
+				"done": 1520,
+				"total": 3410,
+				"bytes_per_sec": 1843200,
+				"eta_secs": 412,
+				"pibd_outputs": {
+					"done": 610,
+					"total": 1024
+				},
+				"pibd_rangeproofs": {
+					"done": 880,
+					"total": 2048
+				},
+				"pibd_kernels": {
+					"done": 30,
+					"total": 338
+				},
+				"stage": "state"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn sync_status(&self) -> Result<SyncProgress, Error>;
+
+	/// Networked version of [Owner::get_sync_events](struct.Owner.html#method.get_sync_events).
+	fn get_sync_events(&self) -> Result<Vec<SyncEvent>, Error>;
+	/**
+	Networked version of [Owner::get_dandelion_config](struct.Owner.html#method.get_dandelion_config).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_dandelion_config",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"epoch_secs": 600,
+				"embargo_secs": 180,
+				"aggregation_secs": 30,
+				"stem_probability": 90,
+				"always_stem_our_txs": true
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_dandelion_config(&self) -> Result<DandelionConfig, Error>;
+
+	/**
+	Networked version of [Owner::set_dandelion_config](struct.Owner.html#method.set_dandelion_config).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_dandelion_config",
+		"params": [{
+			"epoch_secs": 300,
+			"embargo_secs": 120,
+			"aggregation_secs": 15,
+			"stem_probability": 80,
+			"always_stem_our_txs": true
+		}],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"epoch_secs": 300,
+				"embargo_secs": 120,
+				"aggregation_secs": 15,
+				"stem_probability": 80,
+				"always_stem_our_txs": true
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn set_dandelion_config(&self, config: DandelionConfig) -> Result<DandelionConfig, Error>;
+
+	/**
+	Networked version of [Owner::get_dandelion_stats](struct.Owner.html#method.get_dandelion_stats).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_dandelion_stats",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"epochs": 14,
+				"stem_epochs": 12,
+				"relay_changes": 15,
+				"relay_peer": "192.168.0.12:13414",
+				"recent_epochs": [
+					{
+						"start_time": 1718280000,
+						"is_stem": true,
+						"relay_peers": ["192.168.0.7:13414", "192.168.0.12:13414"]
+					}
+				]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_dandelion_stats(&self) -> Result<DandelionStats, Error>;
+
+	/**
+	Networked version of [Owner::get_log_levels](struct.Owner.html#method.get_log_levels).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_log_levels",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"stdout_level": "WARN",
+				"file_level": "INFO",
+				"modules": {}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_log_levels(&self) -> Result<LogLevels, Error>;
+
+	/**
+	Networked version of [Owner::set_log_level](struct.Owner.html#method.set_log_level).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_log_level",
+		"params": ["mwc_p2p", "debug"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"stdout_level": "WARN",
+				"file_level": "INFO",
+				"modules": {
+					"mwc_p2p": "DEBUG"
+				}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn set_log_level(&self, module: String, level: Option<String>) -> Result<LogLevels, Error>;
+
+	/**
+	Networked version of [Owner::get_stratum_workers](struct.Owner.html#method.get_stratum_workers).
+
+	# Json rpc example
+
+	```
+	# mwc_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_stratum_workers",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+				{
+					"login": "rig1",
+					"connected": true,
+					"last_seen": "2024-05-20T10:15:02Z",
+					"difficulty": 16,
+					"accepted": 1250,
+					"rejected": 3,
+					"stale": 11,
+					"blocks_found": 1
+				}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_stratum_workers(&self) -> Result<Vec<StratumWorkerStatus>, Error>;
+}
+
+impl OwnerRpc for Owner {
+	fn get_status(&self) -> Result<Status, Error> {
+		Owner::get_status(self)
+	}
+
+	fn get_status_v2(&self) -> Result<StatusV2, Error> {
+		Owner::get_status_v2(self)
+	}
+
+	fn validate_chain(&self, assume_valid_rangeproofs_kernels: bool) -> Result<(), Error> {
+		Owner::validate_chain(self, assume_valid_rangeproofs_kernels)
+	}
+
+	fn reset_chain_head(&self, hash: String) -> Result<(), Error> {
+		Owner::reset_chain_head(self, hash)
+	}
+
+	fn export_utxo_set(&self, path: String) -> Result<UtxoDumpInfo, Error> {
+		Owner::export_utxo_set(self, path)
+	}
+
+	fn create_snapshot(&self, path: String) -> Result<SnapshotInfo, Error> {
+		Owner::create_snapshot(self, path)
+	}
+
+	fn write_utxo_archive(&self, path: String) -> Result<UtxoArchiveInfo, Error> {
+		Owner::write_utxo_archive(self, path)
+	}
+
+	fn invalidate_header(&self, hash: String) -> Result<(), Error> {
+		Owner::invalidate_header(self, hash)
+	}
+
+	fn get_pending_reorgs(&self) -> Result<Vec<PendingReorg>, Error> {
+		Owner::get_pending_reorgs(self)
+	}
+
+	fn get_reorg_history(&self, height: Option<u64>) -> Result<Vec<ReorgEvent>, Error> {
+		Owner::get_reorg_history(self, height)
+	}
+
+	fn approve_reorg(&self, fork_hash: String) -> Result<(), Error> {
+		Owner::approve_reorg(self, fork_hash)
+	}
+
+	fn compact_chain(&self) -> Result<(), Error> {
+		Owner::compact_chain(self)
+	}
+
+	fn compact_now(&self) -> Result<ChainCompaction, Error> {
+		Owner::compact_now(self)
+	}
+
+	fn shutdown(&self) -> Result<(), Error> {
+		Owner::shutdown(self)
+	}
+
+	fn restart_sync(&self) -> Result<(), Error> {
+		Owner::restart_sync(self)
+	}
+
+	fn reload_config(&self) -> Result<ConfigReload, Error> {
+		Owner::reload_config(self)
+	}
+
+	fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, Error> {
+		Owner::get_peers(self, addr)
+	}
 
 	fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, Error> {
 		Owner::get_connected_peers(self)
 	}
 
+	fn get_peer_stats(&self) -> Result<Vec<PeerStatsDisplay>, Error> {
+		Owner::get_peer_stats(self)
+	}
+
+	fn get_peer_changes(&self, since: u64) -> Result<PeerChanges, Error> {
+		Owner::get_peer_changes(self, since)
+	}
+
 	fn ban_peer(&self, addr: SocketAddr) -> Result<(), Error> {
 		Owner::ban_peer(self, addr)
 	}
@@ -395,6 +1134,54 @@ impl OwnerRpc for Owner {
 	fn unban_peer(&self, addr: SocketAddr) -> Result<(), Error> {
 		Owner::unban_peer(self, addr)
 	}
+
+	fn set_tor_only(&self, tor_only: bool) -> Result<usize, Error> {
+		Owner::set_tor_only(self, tor_only)
+	}
+
+	fn get_bandwidth_limits(&self) -> Result<BandwidthLimits, Error> {
+		Owner::get_bandwidth_limits(self)
+	}
+
+	fn set_bandwidth_limits(
+		&self,
+		limit_kbps: u64,
+		relay_ratio: u8,
+	) -> Result<BandwidthLimits, Error> {
+		Owner::set_bandwidth_limits(self, limit_kbps, relay_ratio)
+	}
+
+	fn sync_status(&self) -> Result<SyncProgress, Error> {
+		Owner::sync_status(self)
+	}
+
+	fn get_sync_events(&self) -> Result<Vec<SyncEvent>, Error> {
+		Owner::get_sync_events(self)
+	}
+
+	fn get_dandelion_config(&self) -> Result<DandelionConfig, Error> {
+		Owner::get_dandelion_config(self)
+	}
+
+	fn set_dandelion_config(&self, config: DandelionConfig) -> Result<DandelionConfig, Error> {
+		Owner::set_dandelion_config(self, config)
+	}
+
+	fn get_dandelion_stats(&self) -> Result<DandelionStats, Error> {
+		Owner::get_dandelion_stats(self)
+	}
+
+	fn get_log_levels(&self) -> Result<LogLevels, Error> {
+		Owner::get_log_levels(self)
+	}
+
+	fn set_log_level(&self, module: String, level: Option<String>) -> Result<LogLevels, Error> {
+		Owner::set_log_level(self, module, level)
+	}
+
+	fn get_stratum_workers(&self) -> Result<Vec<StratumWorkerStatus>, Error> {
+		Owner::get_stratum_workers(self)
+	}
 }
 
 #[doc(hidden)]
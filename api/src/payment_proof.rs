@@ -0,0 +1,123 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payment proof signatures. The recipient (and optionally the sender) signs with the
+//! ed25519 key of its address the message `amount | kernel excess | sender address`.
+//! The amount is not visible on chain, it is proven only by the recipient signature,
+//! the kernel existence is checked against the chain by the caller.
+
+use crate::rest::Error;
+use crate::types::PaymentProofSignatures;
+use crate::util::{self, secp::pedersen::Commitment};
+use ed25519_dalek::{PublicKey as DalekPublicKey, Signature as DalekSignature, Verifier};
+
+/// Message that is signed by the payment proof signatures
+pub fn payment_proof_message(
+	amount: u64,
+	excess: &Commitment,
+	sender_address: &DalekPublicKey,
+) -> Vec<u8> {
+	let mut msg = Vec::with_capacity(8 + excess.0.len() + 32);
+	msg.extend_from_slice(&amount.to_be_bytes());
+	msg.extend_from_slice(&excess.0);
+	msg.extend_from_slice(sender_address.as_bytes());
+	msg
+}
+
+fn parse_address(address: &str, who: &str) -> Result<DalekPublicKey, Error> {
+	let bytes = util::from_hex(address)
+		.map_err(|e| Error::Argument(format!("invalid {} address {}, {}", who, address, e)))?;
+	DalekPublicKey::from_bytes(&bytes)
+		.map_err(|e| Error::Argument(format!("invalid {} address {}, {}", who, address, e)))
+}
+
+fn verify_signature(
+	msg: &[u8],
+	address: &DalekPublicKey,
+	sig: &str,
+	who: &str,
+) -> Result<(), Error> {
+	let bytes = util::from_hex(sig)
+		.map_err(|e| Error::Argument(format!("invalid {} signature {}, {}", who, sig, e)))?;
+	let sig = DalekSignature::from_bytes(&bytes)
+		.map_err(|e| Error::Argument(format!("invalid {} signature {}, {}", who, sig, e)))?;
+	address
+		.verify(msg, &sig)
+		.map_err(|_| Error::Argument(format!("{} signature doesn't match the payment", who)))
+}
+
+/// Verify the recipient signature and the sender signature if there is one.
+/// Returns true if the sender signature was verified.
+pub fn verify_signatures(
+	amount: u64,
+	excess: &Commitment,
+	signatures: &PaymentProofSignatures,
+) -> Result<bool, Error> {
+	let sender_address = parse_address(&signatures.sender_address, "sender")?;
+	let recipient_address = parse_address(&signatures.recipient_address, "recipient")?;
+	let msg = payment_proof_message(amount, excess, &sender_address);
+	verify_signature(
+		&msg,
+		&recipient_address,
+		&signatures.recipient_sig,
+		"recipient",
+	)?;
+	match &signatures.sender_sig {
+		Some(sig) => {
+			verify_signature(&msg, &sender_address, sig, "sender")?;
+			Ok(true)
+		}
+		None => Ok(false),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::util::ToHex;
+	use ed25519_dalek::{ExpandedSecretKey, SecretKey as DalekSecretKey};
+
+	fn keys(seed: u8) -> (ExpandedSecretKey, DalekPublicKey) {
+		let secret = DalekSecretKey::from_bytes(&[seed; 32]).unwrap();
+		(
+			ExpandedSecretKey::from(&secret),
+			DalekPublicKey::from(&secret),
+		)
+	}
+
+	#[test]
+	fn payment_proof_signatures() {
+		let excess = Commitment::from_vec(vec![9; 33]);
+		let (sender_sk, sender_pk) = keys(1);
+		let (recipient_sk, recipient_pk) = keys(2);
+		let msg = payment_proof_message(1_000_000_000, &excess, &sender_pk);
+
+		let mut signatures = PaymentProofSignatures {
+			sender_address: sender_pk.as_bytes().to_hex(),
+			sender_sig: None,
+			recipient_address: recipient_pk.as_bytes().to_hex(),
+			recipient_sig: recipient_sk.sign(&msg, &recipient_pk).to_bytes().to_hex(),
+		};
+		assert!(!verify_signatures(1_000_000_000, &excess, &signatures).unwrap());
+		// the amount is covered by the signature
+		assert!(verify_signatures(1_000_000_001, &excess, &signatures).is_err());
+
+		signatures.sender_sig = Some(sender_sk.sign(&msg, &sender_pk).to_bytes().to_hex());
+		assert!(verify_signatures(1_000_000_000, &excess, &signatures).unwrap());
+
+		// signed by the recipient instead of the sender
+		signatures.sender_sig = Some(signatures.recipient_sig.clone());
+		assert!(verify_signatures(1_000_000_000, &excess, &signatures).is_err());
+	}
+}
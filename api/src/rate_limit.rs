@@ -0,0 +1,291 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Requests rate limiting for the public API. Every client has a token bucket, a request
+//! takes a token, tokens are refilled at the configured rate per minute. Requests with
+//! a known API key are limited per key, the others per source IP.
+
+use crate::rest::RemoteAddr;
+use crate::router::{Handler, HandlerObj, ResponseFuture};
+use crate::util::Mutex;
+use crate::web::response;
+use futures::future::ok;
+use hyper::header::{AUTHORIZATION, RETRY_AFTER};
+use hyper::{Body, Request, Response, StatusCode};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Buckets are not tracked for more clients than that, idle ones are dropped first
+const RATE_LIMIT_MAX_CLIENTS: usize = 4096;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Rate limiting configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RateLimitConfig {
+	/// Requests per minute from a single IP. Default: 120
+	pub requests_per_min: u32,
+	/// Requests burst from a single IP. Default: 30
+	pub burst: u32,
+	/// Requests per minute with a single API key. Default: 600
+	pub token_requests_per_min: u32,
+	/// Requests burst with a single API key. Default: 100
+	pub token_burst: u32,
+	/// Limited routes prefixes. Default: foreign API
+	pub routes: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+	fn default() -> RateLimitConfig {
+		RateLimitConfig {
+			requests_per_min: 120,
+			burst: 30,
+			token_requests_per_min: 600,
+			token_burst: 100,
+			routes: vec!["/v2/foreign".to_string()],
+		}
+	}
+}
+
+/// Rate limited client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+	Ip(IpAddr),
+	/// Hash of the Authorization header
+	Token(u64),
+}
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+/// Per client token buckets
+pub struct ApiRateLimiter {
+	config: RateLimitConfig,
+	buckets: HashMap<RateLimitKey, Bucket>,
+}
+
+impl ApiRateLimiter {
+	pub fn new(config: RateLimitConfig) -> ApiRateLimiter {
+		ApiRateLimiter {
+			config,
+			buckets: HashMap::new(),
+		}
+	}
+
+	pub fn config(&self) -> &RateLimitConfig {
+		&self.config
+	}
+
+	/// Replace the limits at runtime. The buckets are kept, they are capped by the new
+	/// burst on the next request.
+	pub fn set_config(&mut self, config: RateLimitConfig) {
+		self.config = config;
+	}
+
+	fn limits(&self, key: &RateLimitKey) -> (f64, f64) {
+		let (rate_per_min, burst) = match key {
+			RateLimitKey::Ip(_) => (self.config.requests_per_min, self.config.burst),
+			RateLimitKey::Token(_) => (self.config.token_requests_per_min, self.config.token_burst),
+		};
+		(
+			rate_per_min.max(1) as f64 / MINUTE.as_secs_f64(),
+			burst.max(1) as f64,
+		)
+	}
+
+	/// Take a token for the request, the error is the time until the next token
+	pub fn check_at(&mut self, key: RateLimitKey, now: Instant) -> Result<(), Duration> {
+		if self.buckets.len() >= RATE_LIMIT_MAX_CLIENTS && !self.buckets.contains_key(&key) {
+			self.cleanup(now);
+		}
+
+		let (rate_per_sec, burst) = self.limits(&key);
+		let bucket = self.buckets.entry(key).or_insert(Bucket {
+			tokens: burst,
+			updated: now,
+		});
+
+		let elapsed = now.saturating_duration_since(bucket.updated);
+		bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate_per_sec).min(burst);
+		bucket.updated = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			Ok(())
+		} else {
+			Err(Duration::from_secs_f64(
+				(1.0 - bucket.tokens) / rate_per_sec,
+			))
+		}
+	}
+
+	// Drop the buckets that are full again, they are the same as the new ones.
+	// If all clients are active, drop the least recently updated.
+	fn cleanup(&mut self, now: Instant) {
+		let config = &self.config;
+		self.buckets.retain(|key, b| {
+			let (rate_per_min, burst) = match key {
+				RateLimitKey::Ip(_) => (config.requests_per_min, config.burst),
+				RateLimitKey::Token(_) => (config.token_requests_per_min, config.token_burst),
+			};
+			let full_after = MINUTE.mul_f64(burst.max(1) as f64 / rate_per_min.max(1) as f64);
+			now.saturating_duration_since(b.updated) < full_after
+		});
+		if self.buckets.len() >= RATE_LIMIT_MAX_CLIENTS {
+			if let Some(key) = self
+				.buckets
+				.iter()
+				.min_by_key(|(_, b)| b.updated)
+				.map(|(key, _)| *key)
+			{
+				self.buckets.remove(&key);
+			}
+		}
+	}
+}
+
+// Rate limiting Middleware
+pub struct RateLimitMiddleware {
+	/// Hashes of the Authorization headers of the API keys
+	known_tokens: HashSet<u64>,
+	limiter: Arc<Mutex<ApiRateLimiter>>,
+}
+
+impl RateLimitMiddleware {
+	pub fn new(config: RateLimitConfig) -> RateLimitMiddleware {
+		RateLimitMiddleware::with_limiter(Arc::new(Mutex::new(ApiRateLimiter::new(config))))
+	}
+
+	/// Middleware with the limiter shared with the node, so the limits and the routes
+	/// can be changed at runtime
+	pub fn with_limiter(limiter: Arc<Mutex<ApiRateLimiter>>) -> RateLimitMiddleware {
+		RateLimitMiddleware {
+			known_tokens: HashSet::new(),
+			limiter,
+		}
+	}
+
+	/// Authorization header values that are limited per key. Other headers are limited
+	/// per IP, so the clients can't get a fresh bucket with a random header.
+	pub fn with_known_tokens(mut self, auth_headers: &[String]) -> RateLimitMiddleware {
+		self.known_tokens = auth_headers
+			.iter()
+			.map(|h| hash_header(h.as_bytes()))
+			.collect();
+		self
+	}
+
+	// Loopback clients are not limited, the Tor connections come from the local proxy
+	// and are limited by the key only
+	fn key(&self, req: &Request<Body>) -> Option<RateLimitKey> {
+		if let Some(auth) = req.headers().get(AUTHORIZATION) {
+			let hash = hash_header(auth.as_bytes());
+			if self.known_tokens.contains(&hash) {
+				return Some(RateLimitKey::Token(hash));
+			}
+		}
+		req.extensions()
+			.get::<RemoteAddr>()
+			.map(|addr| addr.0.ip())
+			.filter(|ip| !ip.is_loopback())
+			.map(RateLimitKey::Ip)
+	}
+}
+
+impl Handler for RateLimitMiddleware {
+	fn call(
+		&self,
+		req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		let limited = self
+			.limiter
+			.lock()
+			.config()
+			.routes
+			.iter()
+			.any(|prefix| req.uri().path().starts_with(prefix.as_str()));
+		if !limited {
+			return next_handler.call(req, handlers);
+		}
+		let key = match self.key(&req) {
+			Some(key) => key,
+			None => return next_handler.call(req, handlers),
+		};
+		let res = self.limiter.lock().check_at(key, Instant::now());
+		match res {
+			Ok(_) => next_handler.call(req, handlers),
+			Err(retry_after) => {
+				debug!("API request {} from {:?} is rate limited", req.uri(), key);
+				let response = Response::builder()
+					.status(StatusCode::TOO_MANY_REQUESTS)
+					.header(RETRY_AFTER, retry_after.as_secs().max(1))
+					.body(Body::from("Too many requests"))
+					.unwrap();
+				Box::pin(ok(response))
+			}
+		}
+	}
+}
+
+fn hash_header(header: &[u8]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	header.hash(&mut hasher);
+	hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn api_rate_limiter() {
+		let mut limiter = ApiRateLimiter::new(RateLimitConfig {
+			requests_per_min: 60,
+			burst: 2,
+			token_requests_per_min: 120,
+			token_burst: 4,
+			..RateLimitConfig::default()
+		});
+		let ip = RateLimitKey::Ip("10.0.0.1".parse().unwrap());
+		let token = RateLimitKey::Token(1);
+		let now = Instant::now();
+
+		assert!(limiter.check_at(ip, now).is_ok());
+		assert!(limiter.check_at(ip, now).is_ok());
+		let retry = limiter.check_at(ip, now).unwrap_err();
+		assert_eq!(retry.as_secs(), 1);
+		// Token has its own bucket
+		for _ in 0..4 {
+			assert!(limiter.check_at(token, now).is_ok());
+		}
+		assert!(limiter.check_at(token, now).is_err());
+
+		// Refilled at one request per second
+		let later = now + Duration::from_secs(1);
+		assert!(limiter.check_at(ip, later).is_ok());
+		assert!(limiter.check_at(ip, later).is_err());
+		assert!(limiter.check_at(token, later).is_ok());
+	}
+}
@@ -19,22 +19,27 @@
 //! To use it, just have your service(s) implement the ApiEndpoint trait and
 //! register them on a ApiServer.
 
+use crate::auth::TlsClientCert;
 use crate::router::{Handler, HandlerObj, ResponseFuture, Router, RouterError};
 use crate::web::response;
 use futures::channel::oneshot;
 use hyper::server::accept;
+use hyper::server::conn::AddrStream;
 use hyper::service::make_service_fn;
+use hyper::service::service_fn;
 use hyper::{Body, Request, Server, StatusCode};
-use rustls::ServerConfig;
+use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use rustls::RootCertStore;
 use rustls_pemfile as pemfile;
 use std::convert::Infallible;
 use std::fs::File;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::{io, thread};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::runtime::Runtime;
 use tokio_rustls::rustls::{Certificate, PrivateKey};
+use tokio_rustls::server::TlsStream;
 use tokio_rustls::TlsAcceptor;
 
 /// Errors that can be returned by an ApiEndpoint implementation.
@@ -65,11 +70,19 @@ impl From<crate::chain::Error> for Error {
 	}
 }
 
+/// Request extension, address of the client connection
+#[derive(Debug, Clone, Copy)]
+pub struct RemoteAddr(pub SocketAddr);
+
 /// TLS config
 #[derive(Clone)]
 pub struct TLSConfig {
 	pub certificate: String,
 	pub private_key: String,
+	/// CA certificates for the client certificates. The clients without a certificate are
+	/// still accepted, the routes that require the client certificate are checked by
+	/// `ClientCertMiddleware`.
+	pub client_ca: Option<String>,
 }
 
 impl TLSConfig {
@@ -77,7 +90,39 @@ impl TLSConfig {
 		TLSConfig {
 			certificate,
 			private_key,
+			client_ca: None,
+		}
+	}
+
+	/// Verify the client certificates with the CA certificates from the file
+	pub fn with_client_ca(mut self, client_ca: Option<String>) -> TLSConfig {
+		self.client_ca = client_ca;
+		self
+	}
+
+	fn load_client_roots(&self, client_ca: &str) -> Result<RootCertStore, Error> {
+		let cafile = File::open(client_ca).map_err(|e| {
+			Error::Internal(format!(
+				"load_client_roots failed to open file {}, {}",
+				client_ca, e
+			))
+		})?;
+		let mut reader = io::BufReader::new(cafile);
+		let certs = pemfile::certs(&mut reader)
+			.map_err(|_| Error::Internal("failed to load client CA certificate".to_string()))?;
+		if certs.is_empty() {
+			return Err(Error::Internal(format!(
+				"no client CA certificates found at {}",
+				client_ca
+			)));
 		}
+		let mut roots = RootCertStore::empty();
+		for cert in certs {
+			roots
+				.add(&Certificate(cert))
+				.map_err(|e| Error::Internal(format!("invalid client CA certificate, {}", e)))?;
+		}
+		Ok(roots)
 	}
 
 	fn load_certs(&self) -> Result<Vec<Certificate>, Error> {
@@ -119,18 +164,30 @@ impl TLSConfig {
 		let certs = self.load_certs()?;
 		let key = self.load_private_key()?;
 
-		let cfg = rustls::ServerConfig::builder()
-			.with_safe_defaults()
-			.with_no_client_auth()
+		let builder = rustls::ServerConfig::builder().with_safe_defaults();
+		let builder = match &self.client_ca {
+			Some(client_ca) => builder.with_client_cert_verifier(
+				AllowAnyAnonymousOrAuthenticatedClient::new(self.load_client_roots(client_ca)?),
+			),
+			None => builder.with_no_client_auth(),
+		};
+		let cfg = builder
 			.with_single_cert(certs, key)
 			.map_err(|e| Error::Internal(format!("set single certificate failed, {}", e)))?;
 		Ok(Arc::new(cfg))
 	}
 }
 
+/// Runs the threads that are restarted after a panic, implemented by the node supervisor
+pub trait ThreadSupervisor: Send + Sync {
+	/// Run `f` in the named thread, `f` is called again if it panics
+	fn spawn(&self, name: &str, f: Box<dyn FnMut() + Send>) -> io::Result<thread::JoinHandle<()>>;
+}
+
 /// HTTP server allowing the registration of ApiEndpoint implementations.
 pub struct ApiServer {
 	shutdown_sender: Option<oneshot::Sender<()>>,
+	supervisor: Option<Arc<dyn ThreadSupervisor>>,
 }
 
 impl ApiServer {
@@ -139,7 +196,27 @@ impl ApiServer {
 	pub fn new() -> ApiServer {
 		ApiServer {
 			shutdown_sender: None,
+			supervisor: None,
+		}
+	}
+
+	/// Restart the server thread with the supervisor if it panics
+	pub fn set_supervisor(&mut self, supervisor: Arc<dyn ThreadSupervisor>) {
+		self.supervisor = Some(supervisor);
+	}
+
+	// Server thread is supervised if the supervisor is set
+	fn spawn<F>(&self, mut f: F) -> Result<thread::JoinHandle<()>, Error>
+	where
+		F: FnMut() + Send + 'static,
+	{
+		match &self.supervisor {
+			Some(supervisor) => supervisor.spawn("apis", Box::new(f)),
+			None => thread::Builder::new()
+				.name("apis".to_string())
+				.spawn(move || f()),
 		}
+		.map_err(|e| Error::Internal(format!("failed to spawn API thread. {}", e)))
 	}
 
 	/// Starts ApiServer at the provided address.
@@ -149,7 +226,7 @@ impl ApiServer {
 		addr: SocketAddr,
 		router: Router,
 		conf: Option<TLSConfig>,
-		api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 	) -> Result<thread::JoinHandle<()>, Error> {
 		match conf {
 			Some(conf) => self.start_tls(addr, router, conf, api_chan),
@@ -162,45 +239,47 @@ impl ApiServer {
 		&mut self,
 		addr: SocketAddr,
 		router: Router,
-		api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 	) -> Result<thread::JoinHandle<()>, Error> {
 		if self.shutdown_sender.is_some() {
 			return Err(Error::Internal(
 				"Can't start HTTP API server, it's running already".to_string(),
 			));
 		}
-		let rx = &mut api_chan.1;
-		let tx = &mut api_chan.0;
-
-		// Jones's trick to update memory
-		let m = oneshot::channel::<()>();
-		let tx = std::mem::replace(tx, m.0);
+		let (tx, mut rx) = api_chan;
 		self.shutdown_sender = Some(tx);
-
-		thread::Builder::new()
-			.name("apis".to_string())
-			.spawn(move || {
-				let server = async move {
-					let server = Server::bind(&addr)
-						.serve(make_service_fn(move |_| {
-							let router = router.clone();
-							async move { Ok::<_, Infallible>(router) }
-						}))
-						.with_graceful_shutdown(async {
-							rx.await.ok();
-						});
-
-					server.await
-				};
-
-				let rt = Runtime::new()
-					.map_err(|e| error!("HTTP API server error: {}", e))
-					.unwrap();
-				if let Err(e) = rt.block_on(server) {
-					error!("HTTP API server error: {}", e)
-				}
-			})
-			.map_err(|e| Error::Internal(format!("failed to spawn API thread. {}", e)))
+		let router = Arc::new(router);
+
+		self.spawn(move || {
+			let router = router.clone();
+			// the server thread can be restarted, it waits for the same shutdown signal
+			let rx = &mut rx;
+			let server = async move {
+				let server = Server::bind(&addr)
+					.serve(make_service_fn(move |conn: &AddrStream| {
+						let remote_addr = conn.remote_addr();
+						let router = router.clone();
+						async move {
+							Ok::<_, Infallible>(service_fn(move |mut req: Request<Body>| {
+								req.extensions_mut().insert(RemoteAddr(remote_addr));
+								router.handle(req)
+							}))
+						}
+					}))
+					.with_graceful_shutdown(async {
+						rx.await.ok();
+					});
+
+				server.await
+			};
+
+			let rt = Runtime::new()
+				.map_err(|e| error!("HTTP API server error: {}", e))
+				.unwrap();
+			if let Err(e) = rt.block_on(server) {
+				error!("HTTP API server error: {}", e)
+			}
+		})
 	}
 
 	/// Starts the TLS ApiServer at the provided address.
@@ -210,7 +289,7 @@ impl ApiServer {
 		addr: SocketAddr,
 		router: Router,
 		conf: TLSConfig,
-		api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 	) -> Result<thread::JoinHandle<()>, Error> {
 		if self.shutdown_sender.is_some() {
 			return Err(Error::Internal(
@@ -218,70 +297,73 @@ impl ApiServer {
 			));
 		}
 
-		let rx = &mut api_chan.1;
-		let tx = &mut api_chan.0;
-
-		// Jones's trick to update memory
-		let m = oneshot::channel::<()>();
-		let tx = std::mem::replace(tx, m.0);
+		let (tx, mut rx) = api_chan;
 		self.shutdown_sender = Some(tx);
 
 		// Building certificates here because we want to handle certificates failures with panic.
 		// It is a fatal error on node start, not a regular error to log
-		let certs = conf.load_certs()?;
-		let keys = conf.load_private_key()?;
-
-		let config = ServerConfig::builder()
-			.with_safe_defaults() // <- cipher suites
-			.with_no_client_auth()
-			.with_single_cert(certs, keys)
-			.expect("invalid key or certificate");
-
-		let acceptor = TlsAcceptor::from(Arc::new(config));
-
-		thread::Builder::new()
-			.name("apis".to_string())
-			.spawn(move || {
-				let server = async move {
-					let listener = TcpListener::bind(&addr).await.expect("failed to bind");
-
-					let tls_stream = async_stream::stream! {
-						loop {
-							let (socket, _addr) = match listener.accept().await {
-								Ok(conn) => conn,
-								Err(e) => {
-									eprintln!("Error accepting connection: {}", e);
-									continue;
-								}
-							};
-
-							match acceptor.accept(socket).await {
-								Ok(stream) => yield Ok::<_, std::io::Error>(stream),
-								Err(_) => continue,
+		let config = conf.build_server_config()?;
+
+		let acceptor = TlsAcceptor::from(config);
+		let router = Arc::new(router);
+
+		self.spawn(move || {
+			let router = router.clone();
+			let acceptor = acceptor.clone();
+			// the server thread can be restarted, it waits for the same shutdown signal
+			let rx = &mut rx;
+			let server = async move {
+				let listener = TcpListener::bind(&addr).await.expect("failed to bind");
+
+				let tls_stream = async_stream::stream! {
+					loop {
+						let (socket, _addr) = match listener.accept().await {
+							Ok(conn) => conn,
+							Err(e) => {
+								eprintln!("Error accepting connection: {}", e);
+								continue;
 							}
+						};
+
+						match acceptor.accept(socket).await {
+							Ok(stream) => yield Ok::<_, std::io::Error>(stream),
+							Err(_) => continue,
 						}
-					};
-
-					let server = Server::builder(accept::from_stream(tls_stream))
-						.serve(make_service_fn(move |_| {
-							let router = router.clone();
-							async move { Ok::<_, Infallible>(router) }
-						}))
-						.with_graceful_shutdown(async {
-							rx.await.ok();
-						});
-
-					server.await
+					}
 				};
 
-				let rt = Runtime::new()
-					.map_err(|e| error!("HTTP API server error: {}", e))
-					.unwrap();
-				if let Err(e) = rt.block_on(server) {
-					error!("HTTP API server error: {}", e)
-				}
-			})
-			.map_err(|e| Error::Internal(format!("failed to spawn API thread. {}", e)))
+				let server = Server::builder(accept::from_stream(tls_stream))
+					.serve(make_service_fn(move |conn: &TlsStream<TcpStream>| {
+						let remote_addr = conn.get_ref().0.peer_addr().ok();
+						// client certificate is verified by the TLS handshake
+						let client_cert = conn.get_ref().1.peer_certificates().is_some();
+						let router = router.clone();
+						async move {
+							Ok::<_, Infallible>(service_fn(move |mut req: Request<Body>| {
+								if let Some(remote_addr) = remote_addr {
+									req.extensions_mut().insert(RemoteAddr(remote_addr));
+								}
+								if client_cert {
+									req.extensions_mut().insert(TlsClientCert);
+								}
+								router.handle(req)
+							}))
+						}
+					}))
+					.with_graceful_shutdown(async {
+						rx.await.ok();
+					});
+
+				server.await
+			};
+
+			let rt = Runtime::new()
+				.map_err(|e| error!("HTTP API server error: {}", e))
+				.unwrap();
+			if let Err(e) = rt.block_on(server) {
+				error!("HTTP API server error: {}", e)
+			}
+		})
 	}
 
 	/// Stops the API server, it panics in case of error
@@ -13,7 +13,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::cors::CorsConfig;
 use futures::future::{self, Future};
+use hyper::header::ORIGIN;
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
 use std::collections::hash_map::DefaultHasher;
@@ -100,6 +102,7 @@ pub enum RouterError {
 #[derive(Clone)]
 pub struct Router {
 	nodes: Vec<Node>,
+	cors: Option<Arc<CorsConfig>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -123,7 +126,12 @@ impl Router {
 		let root = Node::new(calculate_hash(&""), None);
 		let mut nodes = vec![];
 		nodes.push(root);
-		Router { nodes }
+		Router { nodes, cors: None }
+	}
+
+	/// Apply the cross-origin requests policy to all routes
+	pub fn set_cors(&mut self, cors: CorsConfig) {
+		self.cors = Some(Arc::new(cors));
 	}
 
 	pub fn add_middleware(&mut self, mw: HandlerObj) {
@@ -217,6 +225,30 @@ impl Service<Request<Body>> for Router {
 	}
 
 	fn call(&mut self, req: Request<Body>) -> Self::Future {
+		self.handle(req)
+	}
+}
+
+impl Router {
+	/// Route the request through the middleware to the handler
+	pub fn handle(&self, req: Request<Body>) -> ResponseFuture {
+		let (cors, origin) = match (&self.cors, req.headers().get(ORIGIN)) {
+			(Some(cors), Some(origin)) => (cors.clone(), origin.clone()),
+			_ => return self.route(req),
+		};
+		// Preflight doesn't have the credentials, it is answered before the auth
+		if CorsConfig::is_preflight(&req) {
+			return Box::pin(future::ok(cors.preflight_response(&req, &origin)));
+		}
+		let resp = self.route(req);
+		Box::pin(async move {
+			let mut resp = resp.await?;
+			cors.apply(&origin, resp.headers_mut());
+			Ok(resp)
+		})
+	}
+
+	fn route(&self, req: Request<Body>) -> ResponseFuture {
 		match self.get(req.uri().path()) {
 			Err(_) => not_found(),
 			Ok(mut handlers) => match handlers.next() {
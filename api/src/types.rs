@@ -19,13 +19,19 @@ use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::{FeeFields, KernelFeatures, TxKernel};
 use crate::core::{core, ser};
 use crate::p2p;
+use crate::pool;
 use crate::util::secp::pedersen;
 use crate::util::{self, ToHex};
+use chrono::{DateTime, Utc};
 #[cfg(feature = "libp2p")]
 use mwc_p2p::libp2p_connection;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde;
 use serde::de::MapAccess;
 use serde::ser::SerializeStruct;
+use std::collections::BTreeMap;
 use std::fmt;
 
 macro_rules! no_dup {
@@ -37,7 +43,7 @@ macro_rules! no_dup {
 }
 
 /// API Version Information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Version {
 	/// Current node API Version (api crate version)
 	pub node_version: String,
@@ -46,7 +52,7 @@ pub struct Version {
 }
 
 /// The state of the current fork tip
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Tip {
 	/// Height of the tip (max height of the fork)
 	pub height: u64,
@@ -69,8 +75,134 @@ impl Tip {
 	}
 }
 
-/// Status page containing different server information
+/// Summary of the UTXO set dump that was written by the node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UtxoDumpInfo {
+	/// Dump file path at the node
+	pub path: String,
+	/// Height of the dumped block
+	pub height: u64,
+	/// Hash of the dumped block
+	pub hash: String,
+	pub output_root: String,
+	pub rangeproof_root: String,
+	pub kernel_root: String,
+	/// Number of the unspent outputs
+	pub utxos: u64,
+}
+
+impl UtxoDumpInfo {
+	pub fn from_dump(path: &str, dump: &chain::utxo_dump::UtxoDump) -> UtxoDumpInfo {
+		UtxoDumpInfo {
+			path: path.to_string(),
+			height: dump.height,
+			hash: dump.hash.to_hex(),
+			output_root: dump.roots.output_root.to_hex(),
+			rangeproof_root: dump.roots.rproof_root.to_hex(),
+			kernel_root: dump.roots.kernel_root.to_hex(),
+			utxos: dump.utxos.len() as u64,
+		}
+	}
+}
+
+/// Chain data snapshot that was written by the node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotInfo {
+	/// Snapshot directory at the node
+	pub path: String,
+	/// Head height of the snapshot
+	pub height: u64,
+	/// Head hash of the snapshot
+	pub hash: String,
+	/// Number of the hardlinked files
+	pub linked_files: u64,
+	/// Number of the copied bytes
+	pub copied_bytes: u64,
+}
+
+impl SnapshotInfo {
+	pub fn from_snapshot(snapshot: &chain::snapshot::SnapshotInfo) -> SnapshotInfo {
+		SnapshotInfo {
+			path: snapshot.path.display().to_string(),
+			height: snapshot.height,
+			hash: snapshot.hash.to_hex(),
+			linked_files: snapshot.linked_files,
+			copied_bytes: snapshot.copied_bytes,
+		}
+	}
+}
+
+/// Reorg of the chain head that was recorded by the node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReorgEvent {
+	/// Sequence number of the reorg
+	pub id: u64,
+	/// Head hash before the reorg
+	pub old_tip_hash: String,
+	/// Head height before the reorg
+	pub old_tip_height: u64,
+	/// Head hash after the reorg
+	pub new_tip_hash: String,
+	/// Head height after the reorg
+	pub new_tip_height: u64,
+	/// Hash of the last common block
+	pub fork_hash: String,
+	/// Height of the last common block
+	pub fork_height: u64,
+	/// Number of the blocks that were rolled back
+	pub depth: u64,
+	/// Hashes of the kernels of the rolled back blocks
+	pub kernels: Vec<String>,
+	/// Time of the reorg
+	pub time: DateTime<Utc>,
+}
+
+impl ReorgEvent {
+	pub fn from_event(event: &chain::reorg_history::ReorgEvent) -> ReorgEvent {
+		ReorgEvent {
+			id: event.id,
+			old_tip_hash: event.old_tip.hash.to_hex(),
+			old_tip_height: event.old_tip.height,
+			new_tip_hash: event.new_tip.hash.to_hex(),
+			new_tip_height: event.new_tip.height,
+			fork_hash: event.fork_point.hash.to_hex(),
+			fork_height: event.fork_point.height,
+			depth: event.depth,
+			kernels: event.kernels.iter().map(|k| k.to_hex()).collect(),
+			time: event.time,
+		}
+	}
+}
+
+/// UTXO archive that was written by the node
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UtxoArchiveInfo {
+	/// Archive file at the node
+	pub path: String,
+	/// Height of the archive header
+	pub height: u64,
+	/// Hash of the archive header, the trusted hash for the import
+	pub hash: String,
+	/// Number of the headers
+	pub headers: u64,
+	/// Number of the bitmap, output, rangeproof and kernel segments
+	pub segments: u64,
+}
+
+impl UtxoArchiveInfo {
+	pub fn from_archive(archive: &chain::utxo_archive::UtxoArchiveInfo) -> UtxoArchiveInfo {
+		UtxoArchiveInfo {
+			path: archive.path.display().to_string(),
+			height: archive.height,
+			hash: archive.hash.to_hex(),
+			headers: archive.headers,
+			segments: archive.segments,
+		}
+	}
+}
+
+/// Status page containing different server information
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Status {
 	// The protocol version
 	pub protocol_version: u32,
@@ -85,6 +217,10 @@ pub struct Status {
 	// Additional sync information
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub sync_info: Option<serde_json::Value>,
+	// Forks deeper than the alert depth that the peers are on
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[schemars(with = "Vec<serde_json::Value>")]
+	pub fork_warnings: Vec<chain::ForkInfo>,
 }
 
 impl Status {
@@ -93,6 +229,7 @@ impl Status {
 		connections: u32,
 		sync_status: String,
 		sync_info: Option<serde_json::Value>,
+		fork_warnings: Vec<chain::ForkInfo>,
 	) -> Status {
 		Status {
 			protocol_version: ser::ProtocolVersion::local().into(),
@@ -101,6 +238,7 @@ impl Status {
 			tip: Tip::from_tip(current_tip),
 			sync_status,
 			sync_info,
+			fork_warnings,
 		}
 	}
 }
@@ -171,13 +309,13 @@ impl TxHashSetNode {
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub enum OutputType {
 	Coinbase,
 	Transaction,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Output {
 	/// The output commitment representing the amount
 	pub commit: PrintableCommitment,
@@ -227,6 +365,17 @@ impl serde::ser::Serialize for PrintableCommitment {
 	}
 }
 
+// Serialized as the hex string
+impl JsonSchema for PrintableCommitment {
+	fn schema_name() -> String {
+		"PrintableCommitment".to_string()
+	}
+
+	fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+		String::json_schema(gen)
+	}
+}
+
 impl<'de> serde::de::Deserialize<'de> for PrintableCommitment {
 	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
 	where
@@ -362,6 +511,29 @@ impl OutputPrintable {
 	}
 }
 
+// Schema of the serialized form, the commitment and the merkle proof are hex strings
+impl JsonSchema for OutputPrintable {
+	fn schema_name() -> String {
+		"OutputPrintable".to_string()
+	}
+
+	fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+		#[derive(JsonSchema)]
+		#[allow(dead_code)]
+		struct OutputPrintable {
+			output_type: OutputType,
+			commit: String,
+			spent: bool,
+			proof: Option<String>,
+			proof_hash: String,
+			block_height: Option<u64>,
+			merkle_proof: Option<String>,
+			mmr_index: u64,
+		}
+		OutputPrintable::json_schema(gen)
+	}
+}
+
 impl serde::ser::Serialize for OutputPrintable {
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 	where
@@ -527,7 +699,7 @@ impl<'de> serde::de::Deserialize<'de> for OutputPrintable {
 }
 
 // Printable representation of a block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TxKernelPrintable {
 	pub features: String,
 	pub fee_shift: u8,
@@ -564,7 +736,7 @@ impl TxKernelPrintable {
 }
 
 // Just the information required for wallet reconstruction
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockHeaderDifficultyInfo {
 	// Hash
 	pub hash: String,
@@ -584,7 +756,7 @@ impl BlockHeaderDifficultyInfo {
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockHeaderPrintable {
 	// Hash
 	pub hash: String,
@@ -647,7 +819,7 @@ impl BlockHeaderPrintable {
 }
 
 // Printable representation of a block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockPrintable {
 	/// The block header
 	pub header: BlockHeaderPrintable,
@@ -737,7 +909,7 @@ impl CompactBlockPrintable {
 
 // For wallet reconstruction, include the header info along with the
 // transactions in the block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockOutputs {
 	/// The block header
 	pub header: BlockHeaderDifficultyInfo,
@@ -766,17 +938,551 @@ pub struct BlockListing {
 	pub blocks: Vec<BlockPrintable>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct LocatedTxKernel {
+	#[schemars(with = "serde_json::Value")]
 	pub tx_kernel: TxKernel,
 	pub height: u64,
 	pub mmr_index: u64,
+	/// Inclusion proof, only if it was requested
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub merkle_proof: Option<KernelMerkleProof>,
+}
+
+/// Merkle proof of the unspent output. The proof is verified against `output_root`
+/// of the `header`, the output leaf is at MMR position `mmr_index - 1`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutputMerkleProof {
+	pub commit: String,
+	/// Height of the block that created the output
+	pub height: u64,
+	pub mmr_index: u64,
+	pub mmr_size: u64,
+	/// Siblings from the output up to its peak, followed by the other peaks (right peaks are bagged)
+	pub path: Vec<String>,
+	/// Serialized `MerkleProof`, hex
+	pub proof: String,
+	/// Chain head the proof is built for
+	pub header: BlockHeaderPrintable,
+}
+
+/// Main chain block from the kernel/commitment index
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct IndexedBlock {
+	pub height: u64,
+	pub hash: String,
+}
+
+/// Kernel excess or output commitment index lookup result
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct IndexLookup {
+	pub commit: String,
+	/// Blocks with the kernel or output, ordered by height
+	pub blocks: Vec<IndexedBlock>,
+}
+
+impl IndexLookup {
+	pub fn new(commit: &str, headers: &[core::BlockHeader]) -> IndexLookup {
+		IndexLookup {
+			commit: commit.to_string(),
+			blocks: headers
+				.iter()
+				.map(|h| IndexedBlock {
+					height: h.height,
+					hash: h.hash().to_hex(),
+				})
+				.collect(),
+		}
+	}
+}
+
+/// Merkle proof of the kernel inclusion into the kernel MMR. The proof is verified
+/// against `kernel_root` of the block header at `height`, the kernel leaf is at
+/// MMR position `mmr_index - 1`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct KernelMerkleProof {
+	pub height: u64,
+	pub block_hash: String,
+	pub kernel_root: String,
+	pub mmr_size: u64,
+	/// Kernel MMR peaks, left to right
+	pub peaks: Vec<String>,
+	/// Siblings from the kernel up to its peak, followed by the other peaks (right peaks are bagged)
+	pub path: Vec<String>,
+	/// Serialized `MerkleProof`, hex
+	pub proof: String,
+}
+
+impl KernelMerkleProof {
+	pub fn new(
+		header: &core::BlockHeader,
+		proof: &MerkleProof,
+		peaks: &[core::hash::Hash],
+	) -> KernelMerkleProof {
+		KernelMerkleProof {
+			height: header.height,
+			block_hash: header.hash().to_hex(),
+			kernel_root: header.kernel_root.to_hex(),
+			mmr_size: proof.mmr_size,
+			peaks: peaks.iter().map(|h| h.to_hex()).collect(),
+			path: proof.path.iter().map(|h| h.to_hex()).collect(),
+			proof: proof.to_hex(),
+		}
+	}
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PoolInfo {
 	/// Size of the pool
 	pub pool_size: usize,
+	/// Total weight of the pool transactions
+	#[serde(default)]
+	pub pool_weight: u64,
+	/// Maximum number of the transactions in the pool
+	#[serde(default)]
+	pub max_pool_size: usize,
+	/// Maximum total weight of the pool transactions
+	#[serde(default)]
+	pub max_pool_weight: u64,
+	/// Fee rate (fee per weight unit) of the transaction that is evicted first,
+	/// None for the empty pool
+	#[serde(default)]
+	pub eviction_floor: Option<u64>,
+	/// Pool is at capacity, new transactions need the fee rate above the eviction floor
+	#[serde(default)]
+	pub is_full: bool,
+	/// Number of the orphan transactions that wait for their parents
+	#[serde(default)]
+	pub orphans: usize,
+	/// Page of the unconfirmed transactions, only if it is requested
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub txs: Option<PoolTxsPage>,
+}
+
+/// Order of the unconfirmed transactions page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolTxSort {
+	/// Highest fee rate first, the order the transactions are mined
+	FeeRate,
+	/// Oldest first
+	Age,
+}
+
+/// Summary of the transaction in the pool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PoolTxSummary {
+	pub hash: String,
+	pub fee: u64,
+	/// Fee per weight unit, comparable between the transactions only
+	pub fee_rate: u64,
+	pub weight: u64,
+	pub kernels: usize,
+	pub inputs: usize,
+	pub outputs: usize,
+	#[schemars(with = "String")]
+	pub src: pool::TxSource,
+	pub tx_at: DateTime<Utc>,
+	/// Number of the pool transactions this one depends on
+	#[serde(default)]
+	pub ancestors: usize,
+	/// Weight of the pool transactions this one depends on
+	#[serde(default)]
+	pub ancestors_weight: u64,
+	/// Number of the pool transactions that depend on this one
+	#[serde(default)]
+	pub descendants: usize,
+	/// Weight of the pool transactions that depend on this one
+	#[serde(default)]
+	pub descendants_weight: u64,
+}
+
+impl PoolTxSummary {
+	pub fn from_entry(
+		entry: &pool::PoolEntry,
+		package: &pool::TxPackage,
+		height: u64,
+	) -> PoolTxSummary {
+		let tx = &entry.tx;
+		PoolTxSummary {
+			hash: tx.hash().to_hex(),
+			fee: tx.fee(height),
+			fee_rate: tx.fee_rate(height),
+			weight: tx.weight_size(),
+			kernels: tx.kernels().len(),
+			inputs: tx.inputs().len(),
+			outputs: tx.outputs().len(),
+			src: entry.src,
+			tx_at: entry.tx_at,
+			ancestors: package.ancestors,
+			ancestors_weight: package.ancestors_weight,
+			descendants: package.descendants,
+			descendants_weight: package.descendants_weight,
+		}
+	}
+}
+
+/// Fee recommendation. The transaction fee is
+/// `fee_base * Transaction::weight_for_fee(inputs, outputs, kernels)`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeeEstimate {
+	/// The transaction is expected to be mined in this number of blocks
+	pub blocks_target: u64,
+	/// Recommended fee per fee weight unit
+	pub fee_base: u64,
+	/// Minimal fee per fee weight unit that the pool accepts
+	pub min_fee_base: u64,
+	/// Number of the transactions in the pool
+	pub pool_txs: usize,
+	/// Weight of the transactions in the pool
+	pub pool_weight: u64,
+	/// Weight of the transactions that are mined in the block
+	pub block_weight: u64,
+	/// Average utilization of the recent blocks
+	pub recent_utilization: f64,
+}
+
+/// Payment proof signatures, see [`payment_proof_message`](fn.payment_proof_message.html)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PaymentProofSignatures {
+	/// Ed25519 public key of the sender address, hex
+	pub sender_address: String,
+	/// Signature of the sender, hex. Optional, the recipient signature is the proof.
+	#[serde(default)]
+	pub sender_sig: Option<String>,
+	/// Ed25519 public key of the recipient address, hex
+	pub recipient_address: String,
+	/// Signature of the recipient, hex
+	pub recipient_sig: String,
+}
+
+/// Verified payment proof
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PaymentProofVerification {
+	/// Kernel excess of the payment
+	pub excess: String,
+	/// Amount that the recipient signed
+	pub amount: u64,
+	/// Fee of the kernel
+	pub fee: u64,
+	/// Height of the block with the kernel
+	pub height: u64,
+	/// Kernel MMR index
+	pub mmr_index: u64,
+	/// Number of the blocks from the kernel block to the head, including both
+	pub confirmations: u64,
+	/// Whether the sender signature was verified too
+	pub sender_signed: bool,
+}
+
+/// Reason of the transaction rejection by the pool
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolRejectReason {
+	/// Transaction is invalid, like the wrong signature or the weight above the limit
+	InvalidTx,
+	/// Transaction lock height is above the chain height
+	ImmatureTransaction,
+	/// Transaction spends the coinbase output that is not mature yet
+	ImmatureCoinbase,
+	/// Fee is below the minimal fee the pool accepts
+	LowFee,
+	/// Pool is full and the fee rate doesn't outbid the pool transactions
+	OverCapacity,
+	/// Transaction is already in the pool
+	DuplicateTx,
+	/// Output or kernel is already in the pool or in the chain, or the input is spent
+	DoubleSpend,
+	/// NRD kernels are not accepted at the current height or by this node
+	NrdKernel,
+	/// Transaction makes too long chain of the dependent pool transactions
+	PackageLimit,
+	/// Spent outputs are neither in the pool nor in the chain
+	MissingInputs,
+	/// Inputs are not found in the chain or the pool, or other chain state check failed
+	ChainState,
+}
+
+impl From<&pool::PoolError> for PoolRejectReason {
+	fn from(e: &pool::PoolError) -> PoolRejectReason {
+		match e {
+			pool::PoolError::InvalidTx(_)
+			| pool::PoolError::InvalidBlock(_)
+			| pool::PoolError::Keychain(_)
+			| pool::PoolError::Committed(_) => PoolRejectReason::InvalidTx,
+			pool::PoolError::ImmatureTransaction => PoolRejectReason::ImmatureTransaction,
+			pool::PoolError::ImmatureCoinbase => PoolRejectReason::ImmatureCoinbase,
+			pool::PoolError::LowFeeTransaction(_) => PoolRejectReason::LowFee,
+			pool::PoolError::OverCapacity => PoolRejectReason::OverCapacity,
+			pool::PoolError::DuplicateTx => PoolRejectReason::DuplicateTx,
+			pool::PoolError::DuplicateCommitment
+			| pool::PoolError::DuplicateKernelOrDuplicateSpent(_) => PoolRejectReason::DoubleSpend,
+			pool::PoolError::NRDKernelPreHF3
+			| pool::PoolError::NRDKernelNotEnabled
+			| pool::PoolError::NRDKernelRelativeHeight => PoolRejectReason::NrdKernel,
+			pool::PoolError::PackageLimit(_) => PoolRejectReason::PackageLimit,
+			pool::PoolError::MissingInputs(_) => PoolRejectReason::MissingInputs,
+			pool::PoolError::DandelionError | pool::PoolError::Other(_) => {
+				PoolRejectReason::ChainState
+			}
+		}
+	}
+}
+
+/// Result of the transaction validation by the pool, the transaction is not added
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PoolCheckResult {
+	/// Transaction hash
+	pub tx_hash: String,
+	/// The pool would accept the transaction
+	pub accepted: bool,
+	/// Why the transaction is rejected
+	pub reason: Option<PoolRejectReason>,
+	/// Pool error message
+	pub message: Option<String>,
+}
+
+impl PoolCheckResult {
+	pub fn new(tx_hash: String, res: Result<(), pool::PoolError>) -> PoolCheckResult {
+		match res {
+			Ok(_) => PoolCheckResult {
+				tx_hash,
+				accepted: true,
+				reason: None,
+				message: None,
+			},
+			Err(e) => PoolCheckResult {
+				tx_hash,
+				accepted: false,
+				reason: Some(PoolRejectReason::from(&e)),
+				message: Some(e.to_string()),
+			},
+		}
+	}
+}
+
+/// Dandelion epoch, the relay peers are listed in the order they were chosen. The peer
+/// is replaced during the epoch when it is disconnected.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DandelionEpochStats {
+	/// Epoch start, unix timestamp
+	pub start_time: i64,
+	/// Transactions are stemmed (or fluffed) during the epoch
+	pub is_stem: bool,
+	/// Stem relay peers
+	pub relay_peers: Vec<String>,
+}
+
+/// Dandelion stem relay rotation stats
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DandelionStats {
+	/// Number of the epochs since the node start
+	pub epochs: u64,
+	/// Number of the stem epochs since the node start
+	pub stem_epochs: u64,
+	/// Number of the relay peers chosen since the node start, at the epoch start or
+	/// when the relay is disconnected
+	pub relay_changes: u64,
+	/// Current relay peer
+	pub relay_peer: Option<String>,
+	/// Recent epochs, the oldest first. The last one is the current epoch.
+	pub recent_epochs: Vec<DandelionEpochStats>,
+}
+
+/// Log levels of the node
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LogLevels {
+	/// Level of the stdout log
+	pub stdout_level: String,
+	/// Level of the file log
+	pub file_level: String,
+	/// Levels of the modules, they are used instead of the stdout and file levels
+	pub modules: BTreeMap<String, String>,
+}
+
+impl LogLevels {
+	pub fn current() -> LogLevels {
+		let config = util::logger::logging_config();
+		LogLevels {
+			stdout_level: config.stdout_log_level.to_string(),
+			file_level: config.file_log_level.to_string(),
+			modules: util::logger::module_log_levels()
+				.into_iter()
+				.map(|(module, level)| (module, level.to_string()))
+				.collect(),
+		}
+	}
+}
+
+/// Liveness of the node, GET /v2/health
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NodeHealth {
+	pub healthy: bool,
+	/// The chain db accepts the writes
+	pub db_writable: bool,
+	/// Why the node is not healthy
+	pub error: Option<String>,
+}
+
+/// Readiness of the node to serve the traffic, GET /v2/ready
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NodeReadiness {
+	pub ready: bool,
+	pub syncing: bool,
+	/// Height of our chain head
+	pub height: u64,
+	/// Height reached by the majority of the connected peers
+	pub network_height: u64,
+	pub blocks_behind: u64,
+	pub connected_peers: usize,
+	/// Why the node is not ready
+	pub reasons: Vec<String>,
+}
+
+/// Result of the chain compaction requested by the owner API
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChainCompaction {
+	/// Height of our chain head
+	pub height: u64,
+	/// Lowest full block height before the compaction
+	pub tail_height_before: u64,
+	/// Lowest full block height after the compaction
+	pub tail_height_after: u64,
+	pub duration_ms: u64,
+}
+
+/// Settings changed by the config reload, as `section.field` paths
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigReload {
+	/// Changes that are applied to the running node
+	pub applied: Vec<String>,
+	/// Changes that take effect after the node restart
+	pub restart_required: Vec<String>,
+}
+
+/// Node status with the state of every subsystem, see `get_status_v2`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StatusV2 {
+	pub protocol_version: u32,
+	pub user_agent: String,
+	pub p2p: P2pSubsystemStatus,
+	pub sync: SyncSubsystemStatus,
+	pub chain: ChainSubsystemStatus,
+	/// None if the server doesn't provide it
+	pub pool: Option<PoolSubsystemStatus>,
+	/// None if the stratum server is disabled
+	pub stratum: Option<StratumSubsystemStatus>,
+	/// None if Tor is disabled
+	pub tor: Option<TorSubsystemStatus>,
+	/// None if the server doesn't provide it
+	pub disk: Option<DiskSubsystemStatus>,
+}
+
+/// Connected peers and the recent bans
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct P2pSubsystemStatus {
+	pub inbound: usize,
+	pub outbound: usize,
+	/// Peers banned during the last hour
+	pub bans_last_hour: usize,
+}
+
+/// Sync phase with its progress
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncSubsystemStatus {
+	#[schemars(with = "String")]
+	pub phase: chain::SyncPhase,
+	/// Sync status, the same as `sync_status` of `get_status`
+	pub status: String,
+	/// Items of the phase that are done
+	pub done: u64,
+	/// Items of the phase in total
+	pub total: u64,
+	/// Estimated seconds to finish the phase, None if not known yet
+	pub eta_secs: Option<u64>,
+}
+
+/// Chain head and the last reorg
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChainSubsystemStatus {
+	pub tip: Tip,
+	/// Last reorg since the node start
+	#[schemars(with = "Option<serde_json::Value>")]
+	pub last_reorg: Option<chain::ReorgInfo>,
+}
+
+/// Transaction pool size and the fee that it accepts
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PoolSubsystemStatus {
+	/// Number of the txpool transactions
+	pub size: usize,
+	/// Number of the stempool transactions
+	pub stem_size: usize,
+	/// Minimal fee per fee weight unit, the `accept_fee_base`
+	pub min_fee_base: u64,
+	/// If the txpool is full, the new transaction needs the fee rate above that
+	pub eviction_fee_rate: Option<u64>,
+}
+
+/// Stratum server workers and the hashrate
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StratumSubsystemStatus {
+	pub running: bool,
+	pub workers: usize,
+	/// Network hashrate at the current edge bits
+	pub network_hashrate: f64,
+	/// Blocks found by the workers
+	pub blocks_found: usize,
+}
+
+/// Registered stratum worker, the stats are summed over all the worker connections
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StratumWorkerStatus {
+	pub login: String,
+	/// At least one connection of the worker is logged in
+	pub connected: bool,
+	pub last_seen: DateTime<Utc>,
+	/// Share difficulty of the last login or retarget
+	pub difficulty: u64,
+	pub accepted: u64,
+	pub rejected: u64,
+	pub stale: u64,
+	pub blocks_found: u64,
+}
+
+/// Chain data size and the free disk space
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DiskSubsystemStatus {
+	/// Size of the chain data directory
+	pub chain_data_bytes: u64,
+	/// Free space of the chain data disk
+	pub free_bytes: u64,
+	/// Hard floor of the free space
+	pub min_free_bytes: u64,
+	/// New blocks are not accepted, the free space is below the floor
+	pub blocks_paused: bool,
+	/// State download doesn't start, the projected state size doesn't fit
+	pub state_download_refused: bool,
+}
+
+/// Tor state of the node
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TorSubsystemStatus {
+	/// Tor is run by the operator, not by the node
+	pub external: bool,
+	/// Onion service of the node is published. None for the external Tor, the node
+	/// can't check it.
+	pub bootstrapped: Option<bool>,
+	pub onion_address: Option<String>,
+}
+
+/// Page of the unconfirmed transactions
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PoolTxsPage {
+	/// Number of the transactions that pass the filter
+	pub total: usize,
+	pub offset: usize,
+	pub txs: Vec<PoolTxSummary>,
 }
 
 /// Libp2p peers from the node
@@ -800,6 +1506,352 @@ pub struct Libp2pMessages {
 	pub libp2p_messages: Vec<libp2p_connection::ReceivedMessage>,
 }
 
+/// Fees and fullness of a single block
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockFees {
+	pub height: u64,
+	pub hash: String,
+	/// Sum of the transaction fees
+	pub total_fees: u64,
+	/// Number of the transaction kernels, coinbase is not included
+	pub tx_kernels: u64,
+	/// Fee per fee weight unit, the same units as the transaction base fee.
+	/// 0 for the blocks without transactions.
+	pub fee_rate: u64,
+	/// Block weight
+	pub weight: u64,
+	/// Block weight relative to the max block weight, from 0 to 1
+	pub utilization: f64,
+}
+
+impl BlockFees {
+	pub fn from_block(block: &core::Block, max_block_weight: u64) -> BlockFees {
+		let body = &block.body;
+		let inputs = body.inputs().len() as u64;
+		let outputs = body.outputs().len() as u64;
+		let kernels = body.kernels().len() as u64;
+		let tx_outputs = body.outputs().iter().filter(|o| !o.is_coinbase()).count() as u64;
+		let tx_kernels = body.kernels().iter().filter(|k| !k.is_coinbase()).count() as u64;
+
+		let total_fees = block.total_fees();
+		let fee_rate = if tx_kernels > 0 {
+			total_fees / core::Transaction::weight_for_fee(inputs, tx_outputs, tx_kernels)
+		} else {
+			0
+		};
+		let weight = core::Transaction::weight_for_size(inputs, outputs, kernels);
+		BlockFees {
+			height: block.header.height,
+			hash: block.hash().to_hex(),
+			total_fees,
+			tx_kernels,
+			fee_rate,
+			weight,
+			utilization: weight as f64 / max_block_weight.max(1) as f64,
+		}
+	}
+}
+
+/// Fees and fullness of the recent blocks, newest first
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ChainFees {
+	/// Number of the blocks in the summary, can be less than requested if the node
+	/// doesn't have older blocks
+	pub blocks_num: u64,
+	/// Sum of the fees of all blocks
+	pub total_fees: u64,
+	/// Median fee rate of the blocks with transactions, 0 if there were no transactions
+	pub median_fee_rate: u64,
+	/// Average weight utilization of the blocks
+	pub average_utilization: f64,
+	pub blocks: Vec<BlockFees>,
+}
+
+impl ChainFees {
+	pub fn from_blocks(blocks: Vec<BlockFees>) -> ChainFees {
+		let mut fee_rates: Vec<u64> = blocks
+			.iter()
+			.filter(|b| b.tx_kernels > 0)
+			.map(|b| b.fee_rate)
+			.collect();
+		fee_rates.sort_unstable();
+		let median_fee_rate = if fee_rates.is_empty() {
+			0
+		} else if fee_rates.len() % 2 == 0 {
+			let mid = fee_rates.len() / 2;
+			(fee_rates[mid - 1] + fee_rates[mid]) / 2
+		} else {
+			fee_rates[fee_rates.len() / 2]
+		};
+		let average_utilization = if blocks.is_empty() {
+			0.0
+		} else {
+			blocks.iter().map(|b| b.utilization).sum::<f64>() / blocks.len() as f64
+		};
+		ChainFees {
+			blocks_num: blocks.len() as u64,
+			total_fees: blocks.iter().map(|b| b.total_fees).sum(),
+			median_fee_rate,
+			average_utilization,
+			blocks,
+		}
+	}
+}
+
+/// Blocks count with the interval up to `up_to_secs`, the last bucket is unbounded
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct IntervalBucket {
+	pub up_to_secs: Option<i64>,
+	pub blocks: u64,
+}
+
+/// Intervals between the blocks, in seconds
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockIntervals {
+	pub average: f64,
+	pub median: i64,
+	pub min: i64,
+	pub max: i64,
+	pub distribution: Vec<IntervalBucket>,
+}
+
+/// Block difficulty over the window
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DifficultyTrend {
+	pub first: u64,
+	pub last: u64,
+	pub average: u64,
+	/// Average difficulty of the equal parts of the window, oldest first
+	pub segments: Vec<u64>,
+}
+
+/// Aggregate statistics of the last blocks, for the explorers dashboards
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ChainStats {
+	/// Number of the blocks in the stats, less than requested for the short chain
+	pub window: u64,
+	pub from_height: u64,
+	pub to_height: u64,
+	pub to_hash: String,
+	pub block_intervals: BlockIntervals,
+	pub difficulty: DifficultyTrend,
+	/// Blocks that the node has the bodies for. Compacted node doesn't have the old
+	/// blocks, the fees and the kernels are counted for these blocks only.
+	pub blocks_with_body: u64,
+	pub total_fees: u64,
+	/// Average fees of the block
+	pub average_fees: u64,
+	/// Transaction kernels in the window, coinbase kernels are not counted
+	pub tx_kernels: u64,
+	/// All kernels on the chain
+	pub total_kernels: u64,
+	/// Block rewards in the window, without the fees
+	pub emission: u64,
+	/// Block rewards up to the chain head
+	pub total_emission: u64,
+}
+
+/// Output with its position in the block that created it
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockOutputPosition {
+	pub block_height: u64,
+	pub block_hash: String,
+	/// Index of the output in the block outputs
+	pub output_index: u64,
+	pub output: OutputPrintable,
+}
+
+/// Page of the outputs created in the blocks height range, ascending by height
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutputsByHeight {
+	/// The last block height included into this page
+	pub last_retrieved_height: u64,
+	/// Start height of the next page, None if the range is complete
+	pub next_height: Option<u64>,
+	pub outputs: Vec<BlockOutputPosition>,
+}
+
+/// Kernel features decoded with their lock semantics, for the explorers
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct KernelFeaturesPrintable {
+	pub excess: String,
+	/// Plain, Coinbase, HeightLocked or NoRecentDuplicate
+	pub features: String,
+	/// Fee at the kernel block height
+	pub fee: u64,
+	pub fee_shift: u8,
+	/// HeightLocked only, the kernel is valid from this height
+	pub lock_height: Option<u64>,
+	/// NoRecentDuplicate only, the same excess is rejected within this number of blocks
+	pub relative_height: Option<u64>,
+	/// NoRecentDuplicate only, the first height that accepts the same excess again
+	pub duplicate_allowed_height: Option<u64>,
+	/// Human readable lock semantics
+	pub description: String,
+}
+
+impl KernelFeaturesPrintable {
+	/// Decode the features of the kernel that is mined at the height
+	pub fn from_kernel(k: &core::TxKernel, height: u64) -> KernelFeaturesPrintable {
+		let (fee_fields, lock_height, relative_height) = match k.features {
+			KernelFeatures::Plain { fee } => (fee, None, None),
+			KernelFeatures::Coinbase => (FeeFields::zero(), None, None),
+			KernelFeatures::HeightLocked { fee, lock_height } => (fee, Some(lock_height), None),
+			KernelFeatures::NoRecentDuplicate {
+				fee,
+				relative_height,
+			} => (fee, None, Some(u64::from(relative_height))),
+		};
+		let fee = fee_fields.fee(height);
+		let duplicate_allowed_height = relative_height.map(|rh| height + rh);
+		let description = match (lock_height, relative_height) {
+			(Some(lock_height), _) => format!(
+				"Height locked kernel, valid from height {}, fee {}",
+				lock_height, fee
+			),
+			(_, Some(rh)) => format!(
+				"No recent duplicate kernel, the same excess is rejected for {} blocks until height {}, fee {}",
+				rh,
+				height + rh,
+				fee
+			),
+			_ if k.is_coinbase() => "Coinbase kernel, no fee".to_string(),
+			_ => format!("Plain kernel, fee {}", fee),
+		};
+		KernelFeaturesPrintable {
+			excess: k.excess.to_hex(),
+			features: k.features.as_string(),
+			fee,
+			fee_shift: fee_fields.fee_shift(height),
+			lock_height,
+			relative_height,
+			duplicate_allowed_height,
+			description,
+		}
+	}
+}
+
+/// Decoded kernel features of the block
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockKernelFeatures {
+	pub height: u64,
+	pub hash: String,
+	pub kernels: Vec<KernelFeaturesPrintable>,
+}
+
+/// Page of the decoded kernel features of the blocks height range, ascending by height
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct KernelFeaturesByHeight {
+	/// The last block height included into this page
+	pub last_retrieved_height: u64,
+	/// Start height of the next page, None if the range is complete
+	pub next_height: Option<u64>,
+	pub blocks: Vec<BlockKernelFeatures>,
+}
+
+/// Coinbase of the block template. It is built by the node with the configured
+/// wallet listener, miners don't need to provide it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoinbasePrintable {
+	/// Block reward plus the transactions fees
+	pub reward: u64,
+	pub fees: u64,
+	/// Coinbase output commitment
+	pub commit: String,
+	/// Coinbase kernel excess
+	pub excess: String,
+}
+
+/// Candidate block for the external miners
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockTemplate {
+	/// Id to submit the solution for this template
+	pub template_id: u64,
+	pub height: u64,
+	pub prev_hash: String,
+	/// Header serialized up to the PoW nonce, hex
+	pub pre_pow: String,
+	/// Scaled difficulty of the block
+	pub difficulty: u64,
+	/// Seconds until the template is rebuilt with the new transactions
+	pub ttl: u64,
+	/// Id of this template version for `get_block_template_longpoll`
+	pub longpoll_id: String,
+	pub coinbase: CoinbasePrintable,
+}
+
+/// Solution for the block template
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockSolution {
+	pub template_id: u64,
+	pub height: u64,
+	pub nonce: u64,
+	pub edge_bits: u8,
+	/// Cuckoo cycle nonces
+	pub pow: Vec<u64>,
+}
+
+/// Estimated hashrate of the stratum worker login over the sample interval
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkerHashrate {
+	pub id: String,
+	/// Graphs per second, estimated from the accepted shares difficulty
+	pub hashrate: f64,
+	pub accepted: u64,
+}
+
+/// Stratum mining stats of the single minute
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MiningStatsSample {
+	/// End of the sample interval
+	pub time: DateTime<Utc>,
+	pub network_hashrate: f64,
+	pub network_difficulty: u64,
+	/// Number of the workers that were connected or submitted the shares
+	pub workers: usize,
+	pub accepted: u64,
+	pub rejected: u64,
+	pub stale: u64,
+	pub blocks_found: u64,
+	pub worker_hashrates: Vec<WorkerHashrate>,
+}
+
+/// Stratum mining stats history over the window
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MiningStats {
+	/// Window in minutes
+	pub window: u32,
+	pub accepted: u64,
+	pub rejected: u64,
+	pub stale: u64,
+	/// Share of the accepted shares among all the submitted ones, 1.0 if there are none
+	pub acceptance_rate: f64,
+	/// Per minute samples, the oldest first
+	pub samples: Vec<MiningStatsSample>,
+}
+
+impl MiningStats {
+	pub fn from_samples(window: u32, samples: Vec<MiningStatsSample>) -> MiningStats {
+		let accepted = samples.iter().map(|s| s.accepted).sum();
+		let rejected = samples.iter().map(|s| s.rejected).sum();
+		let stale = samples.iter().map(|s| s.stale).sum();
+		let submitted = accepted + rejected + stale;
+		let acceptance_rate = if submitted == 0 {
+			1.0
+		} else {
+			accepted as f64 / submitted as f64
+		};
+		MiningStats {
+			window,
+			accepted,
+			rejected,
+			stale,
+			acceptance_rate,
+			samples,
+		}
+	}
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
@@ -833,4 +1885,68 @@ mod test {
 		let serialized = serde_json::to_string(&deserialized).unwrap();
 		assert_eq!(serialized, hex_commit);
 	}
+
+	#[test]
+	fn kernel_features_printable() {
+		let kernel = |features| TxKernel::with_features(features);
+
+		let k = KernelFeaturesPrintable::from_kernel(&kernel(KernelFeatures::Coinbase), 10);
+		assert_eq!(k.features, "Coinbase");
+		assert_eq!(k.fee, 0);
+		assert_eq!(k.lock_height, None);
+
+		let k = KernelFeaturesPrintable::from_kernel(
+			&kernel(KernelFeatures::HeightLocked {
+				fee: 7.into(),
+				lock_height: 100,
+			}),
+			120,
+		);
+		assert_eq!(k.features, "HeightLocked");
+		assert_eq!(k.fee, 7);
+		assert_eq!(k.lock_height, Some(100));
+		assert_eq!(k.relative_height, None);
+
+		let k = KernelFeaturesPrintable::from_kernel(
+			&kernel(KernelFeatures::NoRecentDuplicate {
+				fee: 3.into(),
+				relative_height: core::NRDRelativeHeight::new(1440).unwrap(),
+			}),
+			500,
+		);
+		assert_eq!(k.features, "NoRecentDuplicate");
+		assert_eq!(k.fee, 3);
+		assert_eq!(k.lock_height, None);
+		assert_eq!(k.relative_height, Some(1440));
+		assert_eq!(k.duplicate_allowed_height, Some(1940));
+	}
+
+	#[test]
+	fn chain_fees_summary() {
+		let block = |height: u64, tx_kernels: u64, fee_rate: u64| BlockFees {
+			height,
+			hash: String::new(),
+			total_fees: fee_rate * tx_kernels * 10,
+			tx_kernels,
+			fee_rate,
+			weight: 100 * (tx_kernels + 1),
+			utilization: 0.1 * (tx_kernels + 1) as f64,
+		};
+		let fees = ChainFees::from_blocks(vec![
+			block(4, 1, 30),
+			block(3, 0, 0),
+			block(2, 2, 10),
+			block(1, 1, 20),
+			block(0, 3, 50),
+		]);
+		assert_eq!(fees.blocks_num, 5);
+		assert_eq!(fees.total_fees, 300 + 200 + 200 + 1500);
+		// Block without transactions doesn't count
+		assert_eq!(fees.median_fee_rate, 25);
+		assert!((fees.average_utilization - 0.24).abs() < 1e-9);
+
+		let empty = ChainFees::from_blocks(vec![]);
+		assert_eq!(empty.median_fee_rate, 0);
+		assert_eq!(empty.average_utilization, 0.0);
+	}
 }
@@ -2,6 +2,7 @@ use crate::rest::*;
 use crate::router::ResponseFuture;
 use futures::future::ok;
 use hyper::body;
+use hyper::header::CONTENT_TYPE;
 use hyper::{Body, Request, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -75,6 +76,109 @@ where
 	}
 }
 
+/// Streamed responses are sent in the chunks of about that size
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// JSON array response that is streamed with the chunked transfer encoding. The items are
+/// serialized while the response is sent, so the large results are never built in memory.
+/// The status is sent with the first chunk, an item error aborts the response and the
+/// client gets the truncated body.
+/// The items are read from the store, so every chunk is built on the blocking thread pool,
+/// the next one after the previous is taken by the connection.
+pub fn json_stream_response<I, T>(items: I) -> ResponseFuture
+where
+	I: Iterator<Item = Result<T, Error>> + Send + 'static,
+	T: Serialize + 'static,
+{
+	let mut chunks = Some(JsonArrayChunks {
+		items,
+		started: false,
+		first_item: true,
+		done: false,
+	});
+	let stream = async_stream::stream! {
+		while let Some(mut c) = chunks.take() {
+			match tokio::task::spawn_blocking(move || {
+				let chunk = c.next();
+				(c, chunk)
+			})
+			.await
+			{
+				Ok((c, Some(chunk))) => {
+					chunks = Some(c);
+					yield chunk;
+				}
+				Ok((_, None)) => (),
+				Err(e) => {
+					error!("Streamed response is aborted, {}", e);
+					yield Err(Error::Internal(format!("Streamed response is aborted, {}", e)));
+				}
+			}
+		}
+	};
+	let resp = Response::builder()
+		.status(StatusCode::OK)
+		.header(CONTENT_TYPE, "application/json")
+		.body(Body::wrap_stream(stream))
+		.unwrap();
+	Box::pin(ok(resp))
+}
+
+/// JSON array serialized into the chunks
+struct JsonArrayChunks<I> {
+	items: I,
+	started: bool,
+	first_item: bool,
+	done: bool,
+}
+
+impl<I, T> Iterator for JsonArrayChunks<I>
+where
+	I: Iterator<Item = Result<T, Error>>,
+	T: Serialize,
+{
+	type Item = Result<Vec<u8>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+		let mut chunk = Vec::with_capacity(STREAM_CHUNK_SIZE);
+		if !self.started {
+			chunk.push(b'[');
+			self.started = true;
+		}
+		while chunk.len() < STREAM_CHUNK_SIZE {
+			match self.items.next() {
+				Some(Ok(item)) => {
+					if !self.first_item {
+						chunk.push(b',');
+					}
+					self.first_item = false;
+					if let Err(e) = serde_json::to_writer(&mut chunk, &item) {
+						self.done = true;
+						return Some(Err(Error::Internal(format!(
+							"Unable to build respond json, {}",
+							e
+						))));
+					}
+				}
+				Some(Err(e)) => {
+					error!("Streamed response is aborted, {}", e);
+					self.done = true;
+					return Some(Err(e));
+				}
+				None => {
+					chunk.push(b']');
+					self.done = true;
+					break;
+				}
+			}
+		}
+		Some(Ok(chunk))
+	}
+}
+
 /// Text response as HTTP response
 pub fn just_response<T: Into<Body> + Debug>(status: StatusCode, text: T) -> Response<Body> {
 	let mut resp = Response::new(text.into());
@@ -189,3 +293,53 @@ macro_rules! w_fut(
 			Err(_) => return response(StatusCode::INTERNAL_SERVER_ERROR, "weak reference upgrade failed" ),
 		}
 	));
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn json_array_chunks() {
+		let items = (0..20_000u64).map(|i| Ok(vec![i, i]));
+		let chunks = JsonArrayChunks {
+			items,
+			started: false,
+			first_item: true,
+			done: false,
+		};
+		let chunks: Vec<Vec<u8>> = chunks.map(|c| c.unwrap()).collect();
+		assert!(chunks.len() > 1);
+		let json: Vec<u8> = chunks.concat();
+		let parsed: Vec<Vec<u64>> = serde_json::from_slice(&json).unwrap();
+		assert_eq!(parsed.len(), 20_000);
+		assert_eq!(parsed[19_999], vec![19_999, 19_999]);
+
+		let empty = JsonArrayChunks {
+			items: std::iter::empty::<Result<u64, Error>>(),
+			started: false,
+			first_item: true,
+			done: false,
+		};
+		let json: Vec<u8> = empty.map(|c| c.unwrap()).collect::<Vec<_>>().concat();
+		assert_eq!(json, b"[]");
+
+		let failed = JsonArrayChunks {
+			items: vec![Ok(1u64), Err(Error::Internal("db".to_string()))].into_iter(),
+			started: false,
+			first_item: true,
+			done: false,
+		};
+		assert!(failed.last().unwrap().is_err());
+	}
+
+	#[test]
+	fn json_stream_body() {
+		let rt = tokio::runtime::Runtime::new().unwrap();
+		let json = rt.block_on(async {
+			let resp = json_stream_response((0..20_000u64).map(Ok)).await.unwrap();
+			body::to_bytes(resp.into_body()).await.unwrap()
+		});
+		let parsed: Vec<u64> = serde_json::from_slice(&json).unwrap();
+		assert_eq!(parsed, (0..20_000u64).collect::<Vec<_>>());
+	}
+}
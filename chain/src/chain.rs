@@ -27,35 +27,47 @@ use crate::core::ser::ProtocolVersion;
 use crate::error::Error;
 use crate::pibd_params::PibdParams;
 use crate::pipe;
+use crate::reorg_history::{ReorgEvent, ReorgHistory};
+use crate::snapshot::{self, SnapshotInfo};
 use crate::store;
 use crate::txhashset;
 use crate::txhashset::{Desegmenter, PMMRHandle, Segmenter, TxHashSet};
-use crate::types::{BlockStatus, ChainAdapter, CommitPos, Options, Tip, HEADERS_PER_BATCH};
+use crate::types::{
+	BlockStatus, ChainAdapter, CommitPos, HashHeight, IntegrityReport, Options, ReorgInfo,
+	SyncState, SyncStatus, Tip, HEADERS_PER_BATCH,
+};
 use crate::util::secp::pedersen::{Commitment, RangeProof};
-use crate::util::RwLock;
+use crate::util::{Mutex, RwLock, StopState};
+use crate::utxo_archive::{self, UtxoArchiveInfo};
+use crate::utxo_dump::{UtxoDump, UtxoEntry};
 use crate::ChainStore;
 use crate::{
 	core::core::hash::{Hash, Hashed},
 	store::Batch,
 	txhashset::{ExtensionPair, HeaderExtension},
 };
+use chrono::Utc;
 use mwc_core::consensus::HeaderDifficultyInfo;
-use mwc_core::core::pmmr::{VecBackend, PMMR};
-use mwc_core::ser;
+use mwc_core::core::pmmr::{self, ReadablePMMR, VecBackend, PMMR};
 use mwc_store::Error::NotFoundErr;
 use mwc_util::secp::Secp256k1;
 use mwc_util::{secp, ToHex};
+use std::cmp;
+use std::collections::HashMap;
 use std::collections::{HashSet, VecDeque};
 use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use std::{collections::HashMap, io::Cursor};
 
 /// When evicting, very old orphans are evicted first
 const MAX_ORPHAN_AGE_SECS: u64 = 3000;
 
+/// Number of the blocks that are compressed in one db transaction
+const BLOCK_COMPRESSION_BATCH: usize = 50;
+
 /// Banned block. We don't accept any blockchain with this has
 pub const BLOCK_TO_BAN: &str = "00020440a401086e57e1b7a92ebb0277c7f7fd47a38269ecc6789c2a80333725";
 
@@ -204,14 +216,29 @@ pub struct Chain {
 	txhashset: Arc<RwLock<txhashset::TxHashSet>>, // Lock order (with childrer):   2
 	header_pmmr: Arc<RwLock<txhashset::PMMRHandle<BlockHeader>>>, // Lock order  (with childrer):  1
 	pibd_segmenter: Arc<RwLock<Option<Segmenter>>>,
+	// Serialize txhashset zip builds, they are heavy and use the same temp files
+	txhashset_zip_lock: Arc<Mutex<()>>,
 	// POW verification function
 	pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 	denylist: Arc<RwLock<Vec<Hash>>>,
-	archive_mode: bool,
+	// Switched on when the backfilled history reaches genesis
+	archive_mode: AtomicBool,
+	// Blocks below the tail are backfilled from the peers and kept on compaction
+	history_backfill: bool,
+	// Number of the recent blocks to keep, if it is larger than the cut-through horizon
+	archive_depth: u64,
+	// Headers only node, blocks and txhashset are never downloaded
+	header_only: bool,
+	// Kernel excess and output commitment to blocks index is maintained
+	index_enabled: bool,
 	genesis: Block,
 	cache_header_difficulty: Arc<RwLock<VecDeque<HeaderDifficultyInfo>>>,
 	secp: Secp256k1,
 	pibd_params: Arc<PibdParams>,
+	last_reorg: Arc<RwLock<Option<ReorgInfo>>>,
+	reorg_history: Arc<ReorgHistory>,
+	// New blocks are refused while it is set, for example when the disk is almost full
+	block_acceptance_paused: AtomicBool,
 }
 
 impl Chain {
@@ -225,14 +252,12 @@ impl Chain {
 		pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 		archive_mode: bool,
 	) -> Result<Chain, Error> {
+		// Pending schema migrations are applied when the store is opened
 		let store = Arc::new(store::ChainStore::new(&db_root)?);
+		let reorg_history = Arc::new(ReorgHistory::new(&db_root)?);
 
 		let pibd_params = Arc::new(PibdParams::new());
 
-		// DB migrations to be run prior to the chain being used.
-		// Migrate full blocks to protocol version v3.
-		Chain::migrate_db_v2_v3(&store)?;
-
 		let secp = Secp256k1::with_caps(secp::ContextFlag::Commit);
 
 		// open the txhashset, creating a new one if necessary
@@ -265,13 +290,21 @@ impl Chain {
 			txhashset: Arc::new(RwLock::new(txhashset)),
 			header_pmmr: Arc::new(RwLock::new(header_pmmr)),
 			pibd_segmenter: Arc::new(RwLock::new(None)),
+			txhashset_zip_lock: Arc::new(Mutex::new(())),
 			pow_verifier,
 			denylist: Arc::new(RwLock::new(vec![])),
-			archive_mode,
+			archive_mode: AtomicBool::new(archive_mode),
+			history_backfill: false,
+			archive_depth: 0,
+			header_only: false,
+			index_enabled: false,
 			genesis: genesis,
 			cache_header_difficulty: Arc::new(RwLock::new(VecDeque::new())),
 			secp,
 			pibd_params,
+			last_reorg: Arc::new(RwLock::new(None)),
+			reorg_history,
+			block_acceptance_paused: AtomicBool::new(false),
 		};
 
 		// If known bad block exists on "current chain" then rewind prior to this.
@@ -283,6 +316,58 @@ impl Chain {
 		Ok(chain)
 	}
 
+	/// Keep the last `blocks` full blocks on compaction instead of the cut-through
+	/// horizon. Values below the horizon have no effect.
+	pub fn with_archive_depth(mut self, blocks: u64) -> Chain {
+		self.archive_depth = blocks;
+		self
+	}
+
+	/// Backfill the full blocks history below the tail from the peers. The backfilled blocks
+	/// are kept on compaction and the node switches to the archive mode once the history
+	/// reaches genesis.
+	pub fn with_history_backfill(mut self, history_backfill: bool) -> Chain {
+		self.history_backfill = history_backfill;
+		if history_backfill && !self.archive_mode() {
+			if let Ok(history_tail) = self.store.history_tail() {
+				if history_tail.height == 0 {
+					info!("Blocks history is backfilled, running in archive mode");
+					self.archive_mode.store(true, Ordering::Relaxed);
+				}
+			}
+		}
+		self
+	}
+
+	/// Headers only node. It syncs and validates the headers, the blocks and
+	/// txhashset are not downloaded, so the body head stays at genesis.
+	pub fn with_header_only(mut self, header_only: bool) -> Chain {
+		self.header_only = header_only;
+		self
+	}
+
+	/// Maintain the kernel excess and output commitment index on block accept.
+	/// The index covers the blocks accepted after it was enabled, the older
+	/// blocks are still searched in the kernel MMR.
+	pub fn with_index(mut self, enabled: bool) -> Result<Chain, Error> {
+		self.index_enabled = enabled;
+		let batch = self.store.batch_write()?;
+		if enabled {
+			if self.store.index_from()?.is_none() {
+				let from = self.head()?.height + 1;
+				info!(
+					"Kernel and commitment index is enabled from height {}",
+					from
+				);
+				batch.save_index_from(from)?;
+			}
+		} else {
+			batch.delete_index_from()?;
+		}
+		batch.commit()?;
+		Ok(self)
+	}
+
 	/// Secp instance
 	pub fn secp(&self) -> &Secp256k1 {
 		&self.secp
@@ -366,6 +451,8 @@ impl Chain {
 		{
 			let head = Tip::from_header(&self.genesis.header);
 			batch.save_body_head(&head)?;
+			// Partially downloaded PIBD state is rewound as well
+			batch.delete_pibd_progress()?;
 			batch.commit()?;
 		}
 
@@ -399,7 +486,48 @@ impl Chain {
 
 	/// Are we running with archive_mode enabled?
 	pub fn archive_mode(&self) -> bool {
-		self.archive_mode
+		self.archive_mode.load(Ordering::Relaxed)
+	}
+
+	/// Is the blocks history backfill enabled?
+	pub fn history_backfill(&self) -> bool {
+		self.history_backfill
+	}
+
+	/// Are we running as a headers only node?
+	pub fn header_only(&self) -> bool {
+		self.header_only
+	}
+
+	/// Is the kernel and commitment index maintained?
+	pub fn index_enabled(&self) -> bool {
+		self.index_enabled
+	}
+
+	/// Head of the chain that this node follows. It is the header head for
+	/// a headers only node and the body head otherwise.
+	pub fn node_head(&self) -> Result<Tip, Error> {
+		if self.header_only {
+			self.header_head()
+		} else {
+			self.head()
+		}
+	}
+
+	/// Number of the recent full blocks that compaction keeps
+	fn blocks_retention(&self) -> u64 {
+		self.archive_depth.max(global::cut_through_horizon() as u64)
+	}
+
+	/// Depth of the full blocks history that we have and can serve to the peers.
+	/// Archive node has the whole history.
+	pub fn history_depth(&self) -> Result<u64, Error> {
+		if self.archive_mode() {
+			return Ok(u64::MAX);
+		}
+		let head = self.head()?;
+		let tail = self.history_tail()?;
+		Ok(head.height.saturating_sub(tail.height))
 	}
 
 	/// Return our shared header MMR handle.
@@ -425,6 +553,12 @@ impl Chain {
 		self.store.clone()
 	}
 
+	/// Check that the db accepts the writes, an empty write transaction is committed
+	pub fn check_store_writable(&self) -> Result<(), Error> {
+		let batch = self.store.batch_write()?;
+		batch.commit()
+	}
+
 	/// Known bad block that we must rewind prior to if seen on "current chain".
 	fn rewind_bad_block(&self) -> Result<(), Error> {
 		let hash = Hash::from_hex(BLOCK_TO_BAN)?;
@@ -545,6 +679,10 @@ impl Chain {
 	/// Processes a single block, then checks for orphans, processing
 	/// those as well if they're found
 	pub fn process_block(&self, b: Block, opts: Options) -> Result<Option<Tip>, Error> {
+		if self.is_block_acceptance_paused() {
+			return Err(Error::Other("block acceptance is paused".to_string()));
+		}
+
 		// Check if block can be processed now. Overwise add it to orphans and returns error
 		self.check_block(&b, opts)?;
 
@@ -678,6 +816,13 @@ impl Chain {
 			if self.is_on_current_chain(prev_head, head).is_ok() {
 				BlockStatus::Next { prev }
 			} else {
+				*self.last_reorg.write() = Some(ReorgInfo {
+					height: head.height,
+					fork_height: fork_point.height,
+					depth: prev_head.height.saturating_sub(fork_point.height),
+					time: Utc::now(),
+				});
+				self.record_reorg(&prev_head, &head, &fork_point);
 				BlockStatus::Reorg {
 					prev,
 					prev_head,
@@ -693,6 +838,72 @@ impl Chain {
 		}
 	}
 
+	// Failure to record the reorg doesn't fail the block, the block is already committed
+	fn record_reorg(&self, prev_head: &Tip, head: &Tip, fork_point: &Tip) {
+		// kernels of the rolled back blocks, they are kept in the db as the fork blocks
+		let mut kernels = vec![];
+		let mut hash = prev_head.last_block_h;
+		let mut height = prev_head.height;
+		while height > fork_point.height {
+			match self.store.get_block(&hash) {
+				Ok(block) => {
+					kernels.extend(block.kernels().iter().map(|k| k.hash()));
+					hash = block.header.prev_hash;
+				}
+				Err(_) => match self.get_block_header(&hash) {
+					Ok(header) => hash = header.prev_hash,
+					Err(_) => break,
+				},
+			}
+			height -= 1;
+		}
+
+		let event = ReorgEvent {
+			id: 0,
+			old_tip: HashHeight {
+				hash: prev_head.last_block_h,
+				height: prev_head.height,
+			},
+			new_tip: HashHeight {
+				hash: head.last_block_h,
+				height: head.height,
+			},
+			fork_point: HashHeight {
+				hash: fork_point.last_block_h,
+				height: fork_point.height,
+			},
+			depth: prev_head.height.saturating_sub(fork_point.height),
+			kernels,
+			time: Utc::now(),
+		};
+		match self.reorg_history.add(event) {
+			Ok(event) => info!(
+				"Reorg {} is recorded: {} at {} -> {} at {}, fork point at {}, {} rolled back kernels",
+				event.id,
+				event.old_tip.hash,
+				event.old_tip.height,
+				event.new_tip.hash,
+				event.new_tip.height,
+				event.fork_point.height,
+				event.kernels.len()
+			),
+			Err(e) => error!("Unable to record the reorg, {}", e),
+		}
+	}
+
+	/// Recorded reorgs of the chain head, the oldest first. If `height` is provided, only
+	/// the reorgs that rolled back the block at this height.
+	pub fn reorg_history(&self, height: Option<u64>) -> Result<Vec<ReorgEvent>, Error> {
+		let events = self.reorg_history.events()?;
+		Ok(match height {
+			Some(height) => events
+				.into_iter()
+				.filter(|e| e.affects_height(height))
+				.collect(),
+			None => events,
+		})
+	}
+
 	/// Quick check for "known" duplicate block up to and including current chain head.
 	/// Returns an error if this block is "known".
 	pub fn is_known(&self, header: &BlockHeader) -> Result<(), Error> {
@@ -778,6 +989,10 @@ impl Chain {
 				self.secp(),
 			)?;
 
+			if self.index_enabled {
+				ctx.batch.save_block_index(&bv[0])?;
+			}
+
 			ctx.batch.commit()?;
 
 			// release the lock and let the batch go before post-processing
@@ -825,6 +1040,12 @@ impl Chain {
 				self.secp(),
 			)?;
 
+			if self.index_enabled {
+				for b in blocks {
+					ctx.batch.save_block_index(b)?;
+				}
+			}
+
 			ctx.batch.commit()?;
 
 			// release the lock and let the batch go before post-processing
@@ -1134,6 +1355,76 @@ impl Chain {
 		})
 	}
 
+	/// Offline chain data integrity check. The MMR roots and sizes, the kernel sums and the
+	/// output bitmap are validated at the head. If the head state is inconsistent, the first
+	/// inconsistent height is found with the bisection, down to the lowest height that the
+	/// txhashset can be rewound to. A "fast validation" skips the rangeproofs and the kernel
+	/// signatures at the head.
+	pub fn check_integrity(&self, fast_validation: bool) -> Result<IntegrityReport, Error> {
+		let head = self.head_header()?;
+		// No tail if the chain was never compacted
+		let mut lowest_height = self.tail().map(|t| t.height).unwrap_or(0);
+		if !self.archive_mode() {
+			lowest_height = cmp::max(
+				lowest_height,
+				head.height
+					.saturating_sub(global::cut_through_horizon() as u64),
+			);
+		}
+		let mut report = IntegrityReport {
+			head_height: head.height,
+			lowest_height,
+			first_bad_height: None,
+			error: None,
+		};
+
+		info!("Validating chain state at the head {}", head.height);
+		if let Err(e) = self.validate_state_at(&head, fast_validation) {
+			warn!("Chain state at the head {} is invalid, {}", head.height, e);
+			report.error = Some(e.to_string());
+
+			let lowest = self.get_header_by_height(lowest_height)?;
+			if lowest.height == head.height || self.validate_state_at(&lowest, true).is_err() {
+				report.first_bad_height = Some(lowest_height);
+				return Ok(report);
+			}
+
+			// The state is valid at 'good' and invalid at 'bad'
+			let mut good = lowest_height;
+			let mut bad = head.height;
+			while bad - good > 1 {
+				let mid = good + (bad - good) / 2;
+				info!("Validating chain state at {}", mid);
+				let header = self.get_header_by_height(mid)?;
+				match self.validate_state_at(&header, true) {
+					Ok(_) => good = mid,
+					Err(_) => bad = mid,
+				}
+			}
+			report.first_bad_height = Some(bad);
+		}
+		Ok(report)
+	}
+
+	// Validate the txhashset state and the output bitmap at the header of our chain
+	fn validate_state_at(&self, header: &BlockHeader, fast_validation: bool) -> Result<(), Error> {
+		let mut header_pmmr = self.header_pmmr.write();
+		let mut txhashset = self.txhashset.write();
+		txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
+			self.rewind_and_apply_fork(header, ext, batch)?;
+			ext.extension.validate(
+				&self.genesis.header,
+				fast_validation,
+				None,
+				header,
+				None,
+				self.secp(),
+			)?;
+			ext.extension.validate_output_bitmap(batch)?;
+			Ok(())
+		})
+	}
+
 	/// Sets prev_root on a brand new block header by applying the previous header to the header MMR.
 	pub fn set_prev_root_only(&self, header: &mut BlockHeader) -> Result<(), Error> {
 		let mut header_pmmr = self.header_pmmr.write();
@@ -1219,6 +1510,43 @@ impl Chain {
 		txhashset.merkle_proof(commit)
 	}
 
+	/// Return a merkle proof for the unspent output and the chain head header, the proof is
+	/// valid for the output root of this header. None if the output is spent or doesn't exist.
+	pub fn get_output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<Option<(CommitPos, MerkleProof, BlockHeader)>, Error> {
+		// Head can't be updated while the txhashset lock is held
+		let txhashset = self.txhashset.read();
+		let header = self.head_header()?;
+		match txhashset.output_merkle_proof(commit)? {
+			Some((pos, proof)) => {
+				if proof.mmr_size != header.output_mmr_size {
+					return Err(Error::MerkleProof(format!(
+						"Output MMR size {} doesn't match the head {} at {}",
+						proof.mmr_size,
+						header.hash(),
+						header.height
+					)));
+				}
+				Ok(Some((pos, proof, header)))
+			}
+			None => Ok(None),
+		}
+	}
+
+	/// Return a merkle proof and the MMR peaks for the kernel at the given (1-based) MMR index.
+	/// The proof is valid for the kernel root of the header, kernel MMR is never pruned,
+	/// so the header can be any header of the main chain at or above the kernel block.
+	pub fn get_kernel_merkle_proof(
+		&self,
+		kernel_mmr_index: u64,
+		header: &BlockHeader,
+	) -> Result<(MerkleProof, Vec<Hash>), Error> {
+		let txhashset = self.txhashset.read();
+		txhashset.kernel_merkle_proof(kernel_mmr_index, header)
+	}
+
 	/// Rewind and apply fork with the chain specific header validation (denylist) rules.
 	/// If we rewind and re-apply a "denied" block then validation will fail.
 	fn rewind_and_apply_fork(
@@ -1249,7 +1577,8 @@ impl Chain {
 		let denylist = self.denylist.read().clone();
 		pipe::rewind_and_apply_header_fork(header, ext, batch, &|header| {
 			pipe::validate_header_denylist(header, &denylist)
-		})
+		})?;
+		Ok(())
 	}
 
 	/// Provides a reading view into the current txhashset state as well as
@@ -1263,17 +1592,55 @@ impl Chain {
 		// to rewind after receiving the txhashset zip.
 		let header = self.get_block_header(&h)?;
 
-		let mut header_pmmr = self.header_pmmr.write();
-		let mut txhashset = self.txhashset.write();
+		// Prebuilt zip (or one that was built for the previous request) doesn't
+		// need the txhashset rewind, so no chain locks are needed.
+		if let Some(file) = txhashset::zip_cached(&self.db_root, &header) {
+			return Ok((header.output_mmr_size, header.kernel_mmr_size, file));
+		}
 
-		txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
-			self.rewind_and_apply_fork(&header, ext, batch)?;
-			ext.extension.snapshot(batch)?;
+		// Only one zip build at a time, the other requests are waiting for its result.
+		let _zip_guard = self.txhashset_zip_lock.lock();
+		if let Some(file) = txhashset::zip_cached(&self.db_root, &header) {
+			return Ok((header.output_mmr_size, header.kernel_mmr_size, file));
+		}
 
-			// prepare the zip
-			txhashset::zip_read(self.db_root.clone(), &header)
-				.map(|file| (header.output_mmr_size, header.kernel_mmr_size, file))
-		})
+		// Chain locks are held only while the rewound data files are copied,
+		// compression is done without them.
+		let snapshot_path = {
+			let mut header_pmmr = self.header_pmmr.write();
+			let mut txhashset = self.txhashset.write();
+
+			txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
+				self.rewind_and_apply_fork(&header, ext, batch)?;
+				ext.extension.snapshot(batch)?;
+
+				// prepare the zip
+				txhashset::zip_snapshot(&self.db_root, &header)
+			})?
+		};
+
+		txhashset::zip_from_snapshot(&self.db_root, &header, snapshot_path)
+			.map(|file| (header.output_mmr_size, header.kernel_mmr_size, file))
+	}
+
+	/// Build the txhashset zip for the current archive header if it is not built yet.
+	/// Normally the zip is built by the first peer request, it takes a while, so nodes
+	/// can build it in advance when the archive header changes.
+	/// Returns the archive header if the zip was built by this call.
+	pub fn prebuild_txhashset_zip(&self) -> Result<Option<BlockHeader>, Error> {
+		let archive_header = self.txhashset_archive_header()?;
+		if txhashset::zip_cached(&self.db_root, &archive_header).is_some() {
+			return Ok(None);
+		}
+		let start = Instant::now();
+		self.txhashset_read(archive_header.hash())?;
+		info!(
+			"prebuild_txhashset_zip: zip for {} at {} is built in {:?}",
+			archive_header.hash(),
+			archive_header.height,
+			start.elapsed()
+		);
+		Ok(Some(archive_header))
 	}
 
 	/// The segmenter is responsible for generation PIBD segments.
@@ -1413,9 +1780,34 @@ impl Chain {
 			self.genesis.header.clone(),
 			self.store.clone(),
 			self.pibd_params.clone(),
+			self.secp().clone(),
 		))
 	}
 
+	/// Drop the saved PIBD download progress, the next download starts from the scratch
+	pub fn delete_pibd_progress(&self) -> Result<(), Error> {
+		let batch = self.store.batch_write()?;
+		batch.delete_pibd_progress()?;
+		batch.commit()?;
+		Ok(())
+	}
+
+	/// Initialize a desegmenter that continues the PIBD download saved before restart.
+	/// Returns None if there is no saved download for this archive header and bitmap root,
+	/// or the txhashset doesn't match it.
+	pub fn resume_desegmenter(
+		&self,
+		archive_header_hegiht: u64,
+		bitmap_root_hash: Hash,
+	) -> Result<Option<Desegmenter>, Error> {
+		let desegmenter = self.init_desegmenter(archive_header_hegiht, bitmap_root_hash)?;
+		if desegmenter.resume()? {
+			Ok(Some(desegmenter))
+		} else {
+			Ok(None)
+		}
+	}
+
 	/// Static method to convert height to archive height. Used in chain and also in Sync process
 	pub fn height_2_archive_height(height: u64) -> u64 {
 		let sync_threshold = global::state_sync_threshold() as u64;
@@ -1513,6 +1905,15 @@ impl Chain {
 		tmp_dir
 	}
 
+	/// Directory of the UTXO set dumps that are written by the owner API.
+	/// Normally it's ~/.mwc/main/utxo_dumps for mainnet
+	/// or ~/.mwc/floo/utxo_dumps for floonet
+	pub fn get_utxo_dump_dir(&self) -> PathBuf {
+		let mut dump_dir = self.get_tmp_dir();
+		dump_dir.set_file_name("utxo_dumps");
+		dump_dir
+	}
+
 	/// Get a tmp file path in above specific tmp dir (create tmp dir if not exist)
 	/// Delete file if tmp file already exists
 	pub fn get_tmpfile_pathname(&self, tmpfile_name: String) -> PathBuf {
@@ -1535,35 +1936,34 @@ impl Chain {
 	/// If we're willing to accept that new state, the data stream will be
 	/// read as a zip file, unzipped and the resulting state files should be
 	/// rewound to the provided indexes.
-	//  Note, if there are updates in this code, please check Sync code, probably it needs to be updates as well
-	/*	pub fn txhashset_write(
+	/// The state is validated in the sandbox (tmp dir) and replaces our txhashset
+	/// only when the validation is passed.
+	//  Note, if there are updates in this code, please check Desegmenter::validate_complete_state, probably it needs to be updates as well
+	pub fn txhashset_write(
 		&self,
 		h: Hash,
 		txhashset_data: File,
-		status: &dyn TxHashsetWriteStatus,
-	) -> Result<bool, Error> {
-		status.on_setup(None, None, None, None);
+		status: Arc<SyncState>,
+		stop_state: Arc<StopState>,
+	) -> Result<(), Error> {
+		let header = self.get_block_header(&h).map_err(|e| {
+			Error::InvalidTxHashSet(format!("txhashset header {} is unknown, {}", h, e))
+		})?;
 
 		// Initial check whether this txhashset is needed or not
-		let fork_point = self.fork_point()?;
-		if !self.check_txhashset_needed(&fork_point)? {
+		let head = self.head()?;
+		if head.height >= header.height {
 			warn!("txhashset_write: txhashset received but it's not needed! ignored.");
-			return Err(Error::InvalidTxHashSet("not needed".to_owned()));
+			return Err(Error::Unfit(format!(
+				"txhashset at {} is not needed, head is at {}",
+				header.height, head.height
+			)));
 		}
 
-		let header = match self.get_block_header(&h) {
-			Ok(header) => header,
-			Err(e) => {
-				warn!("txhashset_write: cannot find block header, {}", e);
-				// This is a bannable reason
-				return Ok(true);
-			}
-		};
-
 		// Write txhashset to sandbox (in the Mwc specific tmp dir)
 		let sandbox_dir = self.get_tmp_dir();
 		txhashset::clean_txhashset_folder(&sandbox_dir);
-		txhashset::zip_write(sandbox_dir.clone(), txhashset_data.try_clone()?, &header)?;
+		txhashset::zip_write(sandbox_dir.clone(), txhashset_data, &header)?;
 
 		let mut txhashset = txhashset::TxHashSet::open(
 			sandbox_dir
@@ -1575,66 +1975,73 @@ impl Chain {
 			&self.secp,
 		)?;
 
+		// Quick root check first
+		txhashset.roots()?.validate(&header)?;
+
 		// Validate the full kernel history.
 		// Check kernel MMR root for every block header.
 		// Check NRD relative height rules for full kernel history.
 		{
-			self.validate_kernel_history(&header, &txhashset)?;
+			status.update(SyncStatus::ValidatingKernelsHistory);
+			Self::validate_kernel_history(&header, &txhashset)?;
 
 			let header_pmmr = self.header_pmmr.read();
-			let batch = self.store.batch_write()?;
+			let batch = self.store.batch_read()?;
 			txhashset.verify_kernel_pos_index(
 				&self.genesis.header,
 				&header_pmmr,
 				&batch,
-				None,
-				None,
+				Some(status.clone()),
+				Some(stop_state.clone()),
 			)?;
 		}
 
-		// all good, prepare a new batch and update all the required records
-		debug!("txhashset_write: rewinding a 2nd time (writeable)");
+		if stop_state.is_stopped() {
+			return Err(Error::Stopped);
+		}
 
-		let mut header_pmmr = self.header_pmmr.write();
-		let mut batch = self.store.batch_write()?;
-		txhashset::extending(
-			&mut header_pmmr,
+		// Full validation, including rangeproofs and kernel signature verification, runs
+		// on the extracted copy, so the chain keeps processing blocks and headers meanwhile.
+		let (utxo_sum, kernel_sum) = txhashset::validate_sandbox(
 			&mut txhashset,
-			&mut batch,
-			|ext, batch| {
-				let extension = &mut ext.extension;
-				extension.rewind(&header, batch)?;
-
-				// Validate the extension, generating the utxo_sum and kernel_sum.
-				// Full validation, including rangeproofs and kernel signature verification.
-				let (utxo_sum, kernel_sum) = extension.validate(
-					&self.genesis.header,
-					false,
-					status,
-					None,
-					None,
-					&header,
-					None,
-					self.secp(),
-				)?;
+			&self.genesis.header,
+			&header,
+			Some(status.clone()),
+			Some(stop_state.clone()),
+			self.secp(),
+		)?;
 
-				// Save the block_sums (utxo_sum, kernel_sum) to the db for use later.
-				batch.save_block_sums(
-					&header.hash(),
-					BlockSums {
-						utxo_sum,
-						kernel_sum,
-					},
-				)?;
+		if stop_state.is_stopped() {
+			return Err(Error::Stopped);
+		}
 
-				Ok(())
+		// all good, the locks are taken only to update the records and swap the txhashset
+		// Note, locking order is: header_pmmr->txhashset->batch !!!
+		let header_pmmr = self.header_pmmr.write();
+		let mut txhashset_ref = self.txhashset.write();
+		let batch = self.store.batch_write()?;
+
+		// The chain could move while the sandbox was validated
+		let head = batch.head()?;
+		if head.height >= header.height {
+			warn!("txhashset_write: txhashset is not needed anymore, ignored.");
+			return Err(Error::Unfit(format!(
+				"txhashset at {} is not needed, head is at {}",
+				header.height, head.height
+			)));
+		}
+
+		// Save the block_sums (utxo_sum, kernel_sum) to the db for use later.
+		batch.save_block_sums(
+			&header.hash(),
+			BlockSums {
+				utxo_sum,
+				kernel_sum,
 			},
 		)?;
 
 		debug!("txhashset_write: finished validating and rebuilding");
 
-		status.on_save();
-
 		// Save the new head to the db and rebuild the header by height index.
 		{
 			let tip = Tip::from_header(&header);
@@ -1642,6 +2049,10 @@ impl Chain {
 
 			// Reset the body tail to the body head after a txhashset write
 			batch.save_body_tail(&tip)?;
+			batch.delete_history_tail()?;
+
+			// Partially downloaded PIBD state is replaced with this one
+			batch.delete_pibd_progress()?;
 		}
 
 		// Rebuild our output_pos index in the db based on fresh UTXO set.
@@ -1657,8 +2068,6 @@ impl Chain {
 
 		// Sandbox full validation ok, go to overwrite txhashset on db root
 		{
-			let mut txhashset_ref = self.txhashset.write();
-
 			// Before overwriting, drop file handlers in underlying txhashset
 			txhashset_ref.release_backend_files();
 
@@ -1678,12 +2087,14 @@ impl Chain {
 			*txhashset_ref = txhashset;
 		}
 
-		debug!("txhashset_write: replaced our txhashset with the new one");
-
-		status.on_done();
+		info!(
+			"txhashset_write: replaced our txhashset with the one for {} at {}",
+			header.hash(),
+			header.height
+		);
 
-		Ok(false)
-	}*/
+		Ok(())
+	}
 
 	/// Cleanup old blocks from the db.
 	/// Determine the cutoff height from the horizon and the current block height.
@@ -1718,6 +2129,52 @@ impl Chain {
 		Ok(())
 	}
 
+	/// Compress up to `max_blocks` stored blocks below the cut-through horizon. The blocks
+	/// are committed by small batches, so the block processing is not held for long.
+	/// Compressed blocks are read transparently. Returns the number of compressed blocks.
+	pub fn compress_old_blocks(&self, max_blocks: usize) -> Result<usize, Error> {
+		let head = self.head()?;
+		let horizon_height = head
+			.height
+			.saturating_sub(global::cut_through_horizon() as u64);
+		if horizon_height == 0 {
+			return Ok(0);
+		}
+
+		let candidates: Vec<Hash> = {
+			let batch = self.store.batch_read()?;
+			let mut candidates = vec![];
+			for hash in batch.uncompressed_blocks_iter()? {
+				if candidates.len() >= max_blocks {
+					break;
+				}
+				match batch.get_block_header(&hash) {
+					Ok(header) if header.height < horizon_height => candidates.push(hash),
+					_ => (),
+				}
+			}
+			candidates
+		};
+
+		let mut count = 0;
+		for chunk in candidates.chunks(BLOCK_COMPRESSION_BATCH) {
+			let batch = self.store.batch_write()?;
+			for hash in chunk {
+				if batch.compress_block(hash)? {
+					count += 1;
+				}
+			}
+			batch.commit()?;
+		}
+		if count > 0 {
+			debug!(
+				"compress_old_blocks: compressed {} blocks below {}",
+				count, horizon_height
+			);
+		}
+		Ok(count)
+	}
+
 	/// Triggers chain compaction.
 	///
 	/// * compacts the txhashset based on current prune_list
@@ -1729,8 +2186,9 @@ impl Chain {
 		// current "head" and "tail" height to our cut-through horizon and
 		// allowing an additional 60 blocks in height before allowing a further compaction.
 		if let (Ok(tail), Ok(head)) = (self.tail(), self.head()) {
-			let horizon = global::cut_through_horizon() as u64;
-			let threshold = horizon.saturating_add(global::cut_through_horizon() as u64 / 10);
+			let threshold = self
+				.blocks_retention()
+				.saturating_add(global::cut_through_horizon() as u64 / 10);
 			let next_compact = tail.height.saturating_add(threshold);
 			if next_compact > head.height {
 				debug!(
@@ -1740,7 +2198,12 @@ impl Chain {
 				return Ok(());
 			}
 		}
+		self.compact_now()
+	}
 
+	/// Compact the chain state right away, without the startup throttling of `compact`.
+	/// Used by the owner API.
+	pub fn compact_now(&self) -> Result<(), Error> {
 		// Retrieve archive header here, so as not to attempt a read
 		// lock while removing historical blocks
 		let archive_header = self.txhashset_archive_header()?;
@@ -1768,12 +2231,32 @@ impl Chain {
 		// Archive is 2 days + 12 hours.  horizon is a week. Guaranteed that archive_header.height is larger than horizon height
 		debug_assert!(archive_header.height > horizon_header.height);
 
+		// Full blocks might be kept deeper than the horizon, the txhashset is compacted
+		// at the horizon anyway.
+		let retention = self.blocks_retention();
+		let blocks_tail = if retention > global::cut_through_horizon() as u64 {
+			let tail_hash =
+				header_pmmr.get_header_hash_by_height(current_height.saturating_sub(retention))?;
+			batch.get_block_header(&tail_hash)?
+		} else {
+			horizon_header
+		};
+
 		// If we are not in archival mode remove historical blocks from the db.
-		if !self.archive_mode() {
-			self.remove_historical_blocks(&horizon_header, &batch)?;
+		// The backfilled history is kept, its lowest block is tracked apart from the tail.
+		if self.history_backfill {
+			if batch.history_tail().is_err() {
+				let history_tail = match batch.tail() {
+					Ok(tail) => tail,
+					Err(_) => Tip::from_header(&self.genesis.header),
+				};
+				batch.save_history_tail(&history_tail)?;
+			}
+		} else if !self.archive_mode() {
+			self.remove_historical_blocks(&blocks_tail, &batch)?;
 		}
 
-		batch.save_body_tail(&Tip::from_header(&horizon_header))?;
+		batch.save_body_tail(&Tip::from_header(&blocks_tail))?;
 
 		// Make sure our output_pos index is consistent with the UTXO set.
 		txhashset.init_output_pos_index(&header_pmmr, &batch)?;
@@ -1788,6 +2271,71 @@ impl Chain {
 		Ok(())
 	}
 
+	/// Save the historical block below the history tail that is backfilled from a peer.
+	/// The block must be on our header chain, valid, and its kernels must match the kernel
+	/// MMR. The history tail is moved down over the continuous backfilled blocks, the node
+	/// switches to the archive mode when it reaches genesis. Returns the new history tail.
+	pub fn backfill_block(&self, b: &Block) -> Result<Tip, Error> {
+		let bhash = b.hash();
+		if b.header.height == 0 || self.get_header_by_height(b.header.height)?.hash() != bhash {
+			return Err(Error::Unfit(format!(
+				"backfill block {} at {} is not on our chain",
+				bhash, b.header.height
+			)));
+		}
+
+		let prev = self.get_previous_header(&b.header)?;
+		b.validate(&prev.total_kernel_offset, &self.secp)?;
+
+		// The header commits to the kernel MMR, and we have all the kernels
+		{
+			let txhashset = self.txhashset.read();
+			let kernel_pmmr = txhashset.kernel_pmmr_at(&b.header);
+			let mmr_kernels: Vec<Hash> = (prev.kernel_mmr_size..b.header.kernel_mmr_size)
+				.filter(|pos0| pmmr::is_leaf(*pos0))
+				.filter_map(|pos0| kernel_pmmr.get_data(pos0))
+				.map(|k| k.hash())
+				.collect();
+			let block_kernels: Vec<Hash> = b.kernels().iter().map(|k| k.hash()).collect();
+			if mmr_kernels != block_kernels {
+				return Err(Error::InvalidRoot(format!(
+					"kernels of the backfill block {} don't match the kernel MMR",
+					bhash
+				)));
+			}
+		}
+
+		let batch = self.store.batch_write()?;
+		let mut history_tail = match batch.history_tail() {
+			Ok(history_tail) => history_tail,
+			Err(_) => batch.tail()?,
+		};
+		if b.header.height >= history_tail.height {
+			return Ok(history_tail);
+		}
+		batch.save_block(b)?;
+
+		while history_tail.height > 0 {
+			let tail_header = batch.get_block_header(&history_tail.last_block_h)?;
+			let prev = batch.get_previous_header(&tail_header)?;
+			if !batch.block_exists(&prev.hash())? {
+				if prev.height > 0 {
+					break;
+				}
+				batch.save_block(&self.genesis)?;
+			}
+			history_tail = Tip::from_header(&prev);
+		}
+		batch.save_history_tail(&history_tail)?;
+		batch.commit()?;
+
+		if history_tail.height == 0 && !self.archive_mode() {
+			info!("Blocks history is backfilled down to genesis, switching to archive mode");
+			self.archive_mode.store(true, Ordering::Relaxed);
+		}
+		Ok(history_tail)
+	}
+
 	/// returns the last n nodes inserted into the output sum tree
 	pub fn get_last_n_output(&self, distance: u64) -> Vec<(Hash, OutputIdentifier)> {
 		self.txhashset.read().last_n_output(distance)
@@ -1835,6 +2383,320 @@ impl Chain {
 		Ok((outputs.0, last_index, output_vec))
 	}
 
+	/// Canonical dump of the full UTXO set at the current head, with the MMR roots.
+	/// Used for the cross checks between the node implementations.
+	pub fn export_utxo_set(&self) -> Result<UtxoDump, Error> {
+		// head is updated under the txhashset write lock, so it can't move while we hold the read lock
+		let txhashset = self.txhashset.read();
+		let header = self.head_header()?;
+		let roots = txhashset.roots()?;
+		roots.validate(&header)?;
+
+		let mut utxos = vec![];
+		let mut start_index = 1;
+		loop {
+			let (last_index, outputs) = txhashset.outputs_by_pmmr_index(start_index, 1000, None);
+			if outputs.is_empty() {
+				break;
+			}
+			for out in outputs {
+				let pos = txhashset.get_unspent(out.commitment())?.ok_or_else(|| {
+					Error::TxHashSetErr(format!(
+						"Output {:?} is not in the commit index",
+						out.commitment()
+					))
+				})?;
+				utxos.push(UtxoEntry {
+					commit: out.commitment(),
+					features: out.features,
+					height: pos.1.height,
+				});
+			}
+			start_index = last_index + 1;
+		}
+		Ok(UtxoDump::new(header.height, header.hash(), roots, utxos))
+	}
+
+	/// Consistent snapshot of the chain data (see chain::snapshot) into the empty directory,
+	/// while the node runs. The blocks and the headers are not processed during the copy.
+	pub fn create_snapshot(&self, out_dir: &Path) -> Result<SnapshotInfo, Error> {
+		snapshot::prepare_out_dir(out_dir)?;
+		let lmdb_dir = out_dir.join(snapshot::LMDB_SUBDIR);
+		let lmdb_path = lmdb_dir
+			.to_str()
+			.ok_or_else(|| Error::Other(format!("Invalid snapshot path {}", lmdb_dir.display())))?;
+
+		let (head, header_head, pmmr_copy) = {
+			// Block and header processing take these write locks. The LMDB copy is the
+			// consistent point of the snapshot, the MMR files are only linked or opened.
+			let _header_pmmr = self.header_pmmr.read();
+			let _txhashset = self.txhashset.read();
+			let head = self.head()?;
+			let header_head = self.header_head()?;
+			self.store.copy_to(lmdb_path)?;
+			let pmmr_copy = snapshot::start_pmmr_copy(Path::new(&self.db_root), out_dir)?;
+			(head, header_head, pmmr_copy)
+		};
+		let (linked_files, copied_bytes) = pmmr_copy.finish()?;
+
+		// The copied MMR files are only appended while the chain isn't rewound below
+		// the snapshot heads, the rewound data is rewritten in place
+		let header_reorged = self
+			.get_header_hash_by_height(header_head.height)
+			.map(|h| h != header_head.last_block_h)
+			.unwrap_or(true);
+		let mut current = self.head_header()?;
+		while current.height > head.height {
+			current = self.get_previous_header(&current)?;
+		}
+		if header_reorged || current.hash() != head.last_block_h {
+			return Err(Error::ChainInSyncing(
+				"Chain was reorged while the snapshot was written".to_string(),
+			));
+		}
+
+		let lmdb_bytes = fs::metadata(lmdb_dir.join("data.mdb"))
+			.map(|m| m.len())
+			.unwrap_or(0);
+
+		snapshot::write_manifest(out_dir, head.height, &head.last_block_h)?;
+		info!(
+			"Chain snapshot at {} {} is written to {}",
+			head.height,
+			head.last_block_h,
+			out_dir.display()
+		);
+		Ok(SnapshotInfo {
+			path: out_dir.to_path_buf(),
+			height: head.height,
+			hash: head.last_block_h,
+			linked_files,
+			copied_bytes: copied_bytes + lmdb_bytes,
+		})
+	}
+
+	/// Write the UTXO archive (see chain::utxo_archive) at the txhashset archive header.
+	/// The file is written under the temporary name and renamed when it is complete.
+	pub fn write_utxo_archive(&self, path: &Path) -> Result<UtxoArchiveInfo, Error> {
+		if path.exists() {
+			return Err(Error::Other(format!(
+				"UTXO archive {} already exists",
+				path.display()
+			)));
+		}
+		let segmenter = self.segmenter()?;
+		let header = segmenter.header().clone();
+		let bitmap_root = segmenter.bitmap_root()?;
+		let version = ProtocolVersion::local();
+
+		let bitmap_ids = Desegmenter::bitmap_segment_ids(&header);
+		let (output_ids, rangeproof_ids, kernel_ids) =
+			Desegmenter::data_segment_ids(&header, segmenter.bitmap());
+
+		let tmp_path = path.with_extension("tmp");
+		let mut file = BufWriter::new(File::create(&tmp_path)?);
+		utxo_archive::write_prelude(&mut file, version, &header, &bitmap_root)?;
+
+		utxo_archive::write_item(&mut file, version, &header.height)?;
+		for height in 1..=header.height {
+			let h = self.get_header_by_height(height)?;
+			utxo_archive::write_item(&mut file, version, &h)?;
+		}
+		// headers by height must be from the same fork as the archive header
+		if self.get_header_by_height(header.height)?.hash() != header.hash() {
+			let _ = fs::remove_file(&tmp_path);
+			return Err(Error::ChainInSyncing(
+				"Chain was reorged while the UTXO archive was written".to_string(),
+			));
+		}
+
+		utxo_archive::write_item(&mut file, version, &(bitmap_ids.len() as u64))?;
+		for id in bitmap_ids.iter() {
+			utxo_archive::write_item(&mut file, version, &segmenter.bitmap_segment(id.clone())?)?;
+		}
+		utxo_archive::write_item(&mut file, version, &(output_ids.len() as u64))?;
+		for id in output_ids.iter() {
+			utxo_archive::write_item(&mut file, version, &segmenter.output_segment(id.clone())?)?;
+		}
+		utxo_archive::write_item(&mut file, version, &(rangeproof_ids.len() as u64))?;
+		for id in rangeproof_ids.iter() {
+			utxo_archive::write_item(
+				&mut file,
+				version,
+				&segmenter.rangeproof_segment(id.clone())?,
+			)?;
+		}
+		utxo_archive::write_item(&mut file, version, &(kernel_ids.len() as u64))?;
+		for id in kernel_ids.iter() {
+			utxo_archive::write_item(&mut file, version, &segmenter.kernel_segment(id.clone())?)?;
+		}
+		file.flush()?;
+		drop(file);
+		fs::rename(&tmp_path, path)?;
+
+		let segments =
+			(bitmap_ids.len() + output_ids.len() + rangeproof_ids.len() + kernel_ids.len()) as u64;
+		info!(
+			"UTXO archive at {} {} is written to {}, {} segments",
+			header.height,
+			header.hash(),
+			path.display(),
+			segments
+		);
+		Ok(UtxoArchiveInfo {
+			path: path.to_path_buf(),
+			height: header.height,
+			hash: header.hash(),
+			headers: header.height,
+			segments,
+		})
+	}
+
+	/// Bootstrap the empty chain from the UTXO archive. The archive header must have
+	/// `trusted_hash`. The headers are processed like the synced ones and the segments
+	/// are validated and applied like the PIBD ones, so the chain ends up at the trusted
+	/// header with the fully validated txhashset.
+	pub fn import_utxo_archive(
+		&self,
+		path: &Path,
+		trusted_hash: Hash,
+		status: Arc<SyncState>,
+		stop_state: Arc<StopState>,
+	) -> Result<UtxoArchiveInfo, Error> {
+		if self.head()?.height > 0 {
+			return Err(Error::Other(
+				"UTXO archive can be imported into the empty chain only".to_string(),
+			));
+		}
+		let mut file = BufReader::new(File::open(path).map_err(|e| {
+			Error::FileReadErr(format!(
+				"Unable to open UTXO archive {}, {}",
+				path.display(),
+				e
+			))
+		})?);
+		let prelude = utxo_archive::read_prelude(&mut file)?;
+		let version = prelude.version;
+		let header = prelude.header;
+		if header.hash() != trusted_hash {
+			return Err(Error::Other(format!(
+				"UTXO archive header {} at {} is not the trusted one {}",
+				header.hash(),
+				header.height,
+				trusted_hash
+			)));
+		}
+		info!(
+			"Importing UTXO archive at {} {} from {}",
+			header.height,
+			header.hash(),
+			path.display()
+		);
+
+		let headers = utxo_archive::read_count(&mut file, version, "headers", header.height)?;
+		let mut chunk: Vec<BlockHeader> = Vec::with_capacity(HEADERS_PER_BATCH as usize);
+		for i in 0..headers {
+			chunk.push(utxo_archive::read_item(&mut file, version)?);
+			if chunk.len() == HEADERS_PER_BATCH as usize || i + 1 == headers {
+				self.sync_block_headers(&chunk, self.header_head()?, Options::NONE)?;
+				chunk.clear();
+			}
+			if stop_state.is_stopped() {
+				return Err(Error::Stopped);
+			}
+		}
+		if self.get_header_by_height(header.height)?.hash() != trusted_hash {
+			return Err(Error::Other(
+				"UTXO archive headers don't lead to the trusted header".to_string(),
+			));
+		}
+		// From here the header is the one of the validated header chain
+		let header = self.get_block_header(&trusted_hash)?;
+
+		self.reset_pibd_chain()?;
+		let desegmenter = self.init_desegmenter(header.height, prelude.bitmap_root)?;
+		let bitmap_root = prelude.bitmap_root;
+		let mut segments = 0;
+
+		let expected = Desegmenter::bitmap_segment_ids(&header).len() as u64;
+		for _ in 0..utxo_archive::read_count(&mut file, version, "bitmap segments", expected)? {
+			desegmenter
+				.add_bitmap_segment(utxo_archive::read_item(&mut file, version)?, &bitmap_root)?;
+			segments += 1;
+		}
+		let bitmap = desegmenter
+			.get_outputs_bitmap()
+			.ok_or(Error::BitmapNotReady)?;
+		let (output_ids, rangeproof_ids, kernel_ids) =
+			Desegmenter::data_segment_ids(&header, &bitmap);
+		for _ in 0..utxo_archive::read_count(
+			&mut file,
+			version,
+			"output segments",
+			output_ids.len() as u64,
+		)? {
+			desegmenter
+				.add_output_segment(utxo_archive::read_item(&mut file, version)?, &bitmap_root)?;
+			segments += 1;
+		}
+		for _ in 0..utxo_archive::read_count(
+			&mut file,
+			version,
+			"rangeproof segments",
+			rangeproof_ids.len() as u64,
+		)? {
+			desegmenter.add_rangeproof_segment(
+				utxo_archive::read_item(&mut file, version)?,
+				&bitmap_root,
+			)?;
+			segments += 1;
+		}
+		for _ in 0..utxo_archive::read_count(
+			&mut file,
+			version,
+			"kernel segments",
+			kernel_ids.len() as u64,
+		)? {
+			desegmenter
+				.add_kernel_segment(utxo_archive::read_item(&mut file, version)?, &bitmap_root)?;
+			segments += 1;
+		}
+		if !desegmenter.is_complete() {
+			return Err(Error::Other(
+				"UTXO archive doesn't have all the segments".to_string(),
+			));
+		}
+
+		desegmenter.check_update_leaf_set_state()?;
+		desegmenter.validate_complete_state(status, stop_state.clone(), self.secp())?;
+		if stop_state.is_stopped() {
+			return Err(Error::Stopped);
+		}
+		// The imported state must have the roots of the trusted header
+		self.txhashset.read().roots()?.validate(&header)?;
+		if self.head()?.last_block_h != trusted_hash {
+			return Err(Error::Other(format!(
+				"UTXO archive import ended up at {}, expected the trusted header {}",
+				self.head()?.last_block_h,
+				trusted_hash
+			)));
+		}
+
+		info!(
+			"UTXO archive at {} {} is imported, {} segments",
+			header.height,
+			header.hash(),
+			segments
+		);
+		Ok(UtxoArchiveInfo {
+			path: path.to_path_buf(),
+			height: header.height,
+			hash: header.hash(),
+			headers,
+			segments,
+		})
+	}
+
 	/// Return unspent outputs as above, but bounded between a particular range of blocks
 	pub fn block_height_range_to_pmmr_indices(
 		&self,
@@ -1862,6 +2724,23 @@ impl Chain {
 		self.orphans.len()
 	}
 
+	/// Pause or resume the blocks processing. Blocks are refused while it is paused,
+	/// the peers are not banned for them.
+	pub fn pause_block_acceptance(&self, paused: bool) {
+		self.block_acceptance_paused
+			.store(paused, Ordering::Relaxed);
+	}
+
+	/// Whether the blocks processing is paused
+	pub fn is_block_acceptance_paused(&self) -> bool {
+		self.block_acceptance_paused.load(Ordering::Relaxed)
+	}
+
+	/// Last reorg of the chain head since the node start
+	pub fn last_reorg(&self) -> Option<ReorgInfo> {
+		self.last_reorg.read().clone()
+	}
+
 	/// Tip (head) of the block chain.
 	pub fn head(&self) -> Result<Tip, Error> {
 		self.store
@@ -1876,6 +2755,15 @@ impl Chain {
 			.map_err(|e| Error::StoreErr(e, "chain tail".to_owned()))
 	}
 
+	/// Lowest block of the full blocks history. It is below the tail if the history
+	/// was backfilled.
+	pub fn history_tail(&self) -> Result<Tip, Error> {
+		match self.store.history_tail() {
+			Ok(history_tail) => Ok(history_tail),
+			Err(_) => self.tail(),
+		}
+	}
+
 	/// Tip (head) of the header chain.
 	pub fn header_head(&self) -> Result<Tip, Error> {
 		self.store
@@ -1938,64 +2826,6 @@ impl Chain {
 		self.header_pmmr.read().get_header_hash_by_height(height)
 	}
 
-	/// Migrate our local db from v2 to v3.
-	/// "commit only" inputs.
-	fn migrate_db_v2_v3(store: &ChainStore) -> Result<(), Error> {
-		if store.batch_read()?.is_blocks_v3_migrated()? {
-			// Previously migrated so skipping.
-			debug!("migrate_db_v2_v3: previously migrated, skipping");
-			return Ok(());
-		}
-		let mut total = 0;
-		let mut keys_to_migrate = vec![];
-		for (k, v) in store.batch_read()?.blocks_raw_iter()? {
-			total += 1;
-
-			// We want to migrate all blocks that cannot be read via v3 protocol version.
-			let block_v3: Result<Block, _> = ser::deserialize(
-				&mut Cursor::new(&v),
-				ProtocolVersion(3),
-				ser::DeserializationMode::default(),
-			);
-			if block_v3.is_err() {
-				let block_v2: Result<Block, _> = ser::deserialize(
-					&mut Cursor::new(&v),
-					ProtocolVersion(2),
-					ser::DeserializationMode::default(),
-				);
-				if block_v2.is_ok() {
-					keys_to_migrate.push(k);
-				}
-			}
-		}
-		debug!(
-			"migrate_db_v2_v3: {} (of {}) blocks to migrate",
-			keys_to_migrate.len(),
-			total,
-		);
-		let mut count = 0;
-		keys_to_migrate
-			.chunks(100)
-			.try_for_each(|keys| {
-				let batch = store.batch_write()?;
-				for key in keys {
-					batch.migrate_block(&key, ProtocolVersion(2), ProtocolVersion(3))?;
-					count += 1;
-				}
-				batch.commit()?;
-				debug!("migrate_db_v2_v3: successfully migrated {} blocks", count);
-				Ok(())
-			})
-			.and_then(|_| {
-				// Set flag to indicate we have migrated all blocks in the db.
-				// We will skip migration in the future.
-				let batch = store.batch_write()?;
-				batch.set_blocks_v3_migrated(true)?;
-				batch.commit()?;
-				Ok(())
-			})
-	}
-
 	/// Gets the block header in which a given output appears in the txhashset.
 	pub fn get_header_for_output(&self, commit: Commitment) -> Result<BlockHeader, Error> {
 		let header_pmmr = self.header_pmmr.read();
@@ -2053,6 +2883,34 @@ impl Chain {
 			None => None,
 		};
 
+		// Blocks above the index start are checked with the index, the scan covers the rest
+		let mut max_index = max_index;
+		if let Some(index_from) = self.index_from()? {
+			let min = min_height.unwrap_or(0).max(index_from);
+			let max = max_height.unwrap_or(head.height).min(head.height);
+			for header in self.get_kernel_index(excess)?.iter().rev() {
+				if header.height < min || header.height > max {
+					continue;
+				}
+				let prev_header = self.get_previous_header(header)?;
+				if let Some((kernel, mmr_index)) = self.txhashset.read().find_kernel(
+					&excess,
+					Some(prev_header.kernel_mmr_size + 1),
+					Some(header.kernel_mmr_size),
+				) {
+					return Ok(Some((kernel, header.height, mmr_index)));
+				}
+			}
+			if min_height.unwrap_or(0) >= index_from {
+				return Ok(None);
+			}
+			let header = self.get_header_by_height((index_from - 1).min(head.height))?;
+			max_index = Some(match max_index {
+				Some(i) => i.min(header.kernel_mmr_size),
+				None => header.kernel_mmr_size,
+			});
+		}
+
 		let (kernel, mmr_index) = match self
 			.txhashset
 			.read()
@@ -2066,6 +2924,50 @@ impl Chain {
 
 		Ok(Some((kernel, header.height, mmr_index)))
 	}
+
+	/// First block height covered by the kernel/commitment index, None if the index is disabled
+	fn index_from(&self) -> Result<Option<u64>, Error> {
+		if !self.index_enabled {
+			return Ok(None);
+		}
+		Ok(self.store.index_from()?)
+	}
+
+	/// Main chain blocks with the kernel from the kernel index, latest last.
+	pub fn get_kernel_index(&self, excess: &Commitment) -> Result<Vec<BlockHeader>, Error> {
+		let index = self.store.get_kernel_index(excess)?;
+		self.main_chain_index(index)
+	}
+
+	/// Main chain blocks with the output from the commitment index, latest last.
+	/// The output can be spent already.
+	pub fn get_output_index(&self, commit: &Commitment) -> Result<Vec<BlockHeader>, Error> {
+		let index = self.store.get_output_index(commit)?;
+		self.main_chain_index(index)
+	}
+
+	// The index is append only, the entries of the reorged blocks are filtered out here
+	fn main_chain_index(&self, index: Vec<HashHeight>) -> Result<Vec<BlockHeader>, Error> {
+		if !self.index_enabled {
+			return Err(Error::Other(
+				"Kernel and commitment index is disabled".to_string(),
+			));
+		}
+		let head = self.head()?;
+		let header_pmmr = self.header_pmmr.read();
+		let mut headers = vec![];
+		for hh in index {
+			if hh.height > head.height {
+				continue;
+			}
+			if header_pmmr.get_header_hash_by_height(hh.height)? == hh.hash {
+				headers.push(self.get_block_header(&hh.hash)?);
+			}
+		}
+		headers.sort_by_key(|h| h.height);
+		Ok(headers)
+	}
+
 	/// Gets the block header in which a given kernel mmr index appears in the txhashset.
 	pub fn get_header_for_kernel_index(
 		&self,
@@ -2253,6 +3155,13 @@ fn setup_head(
 		Ok(h) => {
 			head = h;
 			loop {
+				// PIBD state sync is in progress, the downloaded segments are kept in the MMR
+				// files, so the download can be resumed.
+				if head.height == 0 && batch.get_pibd_progress().is_ok() {
+					info!("init: PIBD state sync is in progress, keeping the txhashset");
+					break;
+				}
+
 				// Use current chain tip if we have one.
 				// Note: We are rewinding and validating against a writeable extension.
 				// If validation is successful we will truncate the backend files
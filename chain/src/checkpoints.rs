@@ -0,0 +1,150 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Header checkpoints. A header at the checkpoint height must match the checkpoint
+//! hash, so the checkpoint header and its ancestors are committed by it and the
+//! expensive PoW verification can be skipped for them during the initial sync.
+//! The forks below the checkpoint are not committed, their PoW is verified.
+
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::{block, BlockHeader};
+use crate::core::global::ChainTypes;
+use crate::error::Error;
+use crate::util::RwLock;
+use std::collections::BTreeMap;
+
+/// Hard-coded mainnet checkpoints, (height, header hash). Updated with the releases.
+const MAINNET_CHECKPOINTS: &[(u64, &str)] = &[];
+
+/// Hard-coded floonet checkpoints, (height, header hash). Updated with the releases.
+const FLOONET_CHECKPOINTS: &[(u64, &str)] = &[];
+
+lazy_static! {
+	static ref CHECKPOINTS: RwLock<BTreeMap<u64, Hash>> = RwLock::new(BTreeMap::new());
+}
+
+fn parse_hash(hash: &str) -> Result<Hash, Error> {
+	Hash::from_hex(hash)
+		.map_err(|e| Error::Other(format!("Unable to parse checkpoint hash {}, {}", hash, e)))
+}
+
+/// Parse the config checkpoint in the format 'height:hash'
+pub fn parse_checkpoint(checkpoint: &str) -> Result<(u64, Hash), Error> {
+	let mut parts = checkpoint.splitn(2, ':');
+	let height = parts.next().unwrap_or("").trim();
+	let hash = parts.next().ok_or(Error::Other(format!(
+		"Invalid checkpoint {}, expected 'height:hash'",
+		checkpoint
+	)))?;
+	let height = height.parse::<u64>().map_err(|e| {
+		Error::Other(format!(
+			"Invalid checkpoint {} height {}, {}",
+			checkpoint, height, e
+		))
+	})?;
+	Ok((height, parse_hash(hash.trim())?))
+}
+
+/// Setup the checkpoints for the chain type. The config checkpoints are added to the
+/// hard-coded ones and override them at the same height. If not enabled, no checkpoints are used.
+pub fn init_checkpoints(
+	chain_type: &ChainTypes,
+	enabled: bool,
+	config_checkpoints: &Option<Vec<String>>,
+) -> Result<(), Error> {
+	let mut checkpoints = BTreeMap::new();
+	if enabled {
+		let hard_coded = match chain_type {
+			ChainTypes::Mainnet => MAINNET_CHECKPOINTS,
+			ChainTypes::Floonet => FLOONET_CHECKPOINTS,
+			_ => &[],
+		};
+		for (height, hash) in hard_coded {
+			checkpoints.insert(*height, parse_hash(hash)?);
+		}
+		if let Some(config_checkpoints) = config_checkpoints.as_ref() {
+			for c in config_checkpoints {
+				let (height, hash) = parse_checkpoint(c)?;
+				checkpoints.insert(height, hash);
+			}
+		}
+	}
+	if let Some((height, hash)) = checkpoints.iter().next_back() {
+		info!(
+			"Using {} header checkpoints, the last one is {} at {}",
+			checkpoints.len(),
+			hash,
+			height
+		);
+	}
+	*CHECKPOINTS.write() = checkpoints;
+	Ok(())
+}
+
+/// Height of the last checkpoint, None if there are no checkpoints
+pub fn last_checkpoint_height() -> Option<u64> {
+	CHECKPOINTS.read().keys().next_back().cloned()
+}
+
+/// Height of the first checkpoint above `tip_height` and not further than `max_span`
+/// headers from it. The headers up to that height should be applied in one chunk,
+/// so all of them are committed by the checkpoint.
+pub fn checkpoint_span_end(tip_height: u64, max_span: u64) -> Option<u64> {
+	CHECKPOINTS
+		.read()
+		.range(tip_height + 1..=tip_height.saturating_add(max_span))
+		.next()
+		.map(|(height, _)| *height)
+}
+
+/// Number of the first headers of the chunk that are committed by a checkpoint, the
+/// checkpoint header in the chunk and its linked ancestors before it. The ancestry is
+/// known only after the checkpoint hash was seen on the chain of the chunk, so the
+/// headers above the last checkpoint in the chunk are not committed.
+pub fn checkpointed_headers(headers: &[BlockHeader]) -> usize {
+	let checkpoints = CHECKPOINTS.read();
+	let last_height = match checkpoints.keys().next_back() {
+		Some(height) => *height,
+		None => return 0,
+	};
+	let mut res = 0;
+	let mut prev_hash = None;
+	for (i, header) in headers.iter().enumerate() {
+		if header.height > last_height {
+			break;
+		}
+		// Not a chain, the ancestry can't be checked further
+		if prev_hash.is_some() && prev_hash != Some(header.prev_hash) {
+			break;
+		}
+		let hash = header.hash();
+		if checkpoints.get(&header.height) == Some(&hash) {
+			res = i + 1;
+		}
+		prev_hash = Some(hash);
+	}
+	res
+}
+
+/// Header at the checkpoint height must match the checkpoint hash.
+/// Returns a "Block" error which is "bad_data" and will result in peer being banned.
+pub fn validate_header_checkpoint(header: &BlockHeader, hash: &Hash) -> Result<(), Error> {
+	match CHECKPOINTS.read().get(&header.height) {
+		Some(checkpoint) if checkpoint != hash => Err(Error::Block(block::Error::Other(format!(
+			"header {} at {} doesn't match the checkpoint {}",
+			hash, header.height, checkpoint
+		)))),
+		_ => Ok(()),
+	}
+}
@@ -226,6 +226,9 @@ pub enum Error {
 	/// Chain is in sync mode
 	#[error("Chain is in sync mode")]
 	ChainInSync,
+	/// Reorg is deeper than the max reorg depth and not approved by the operator
+	#[error("Reorg is not approved, {0}")]
+	ReorgNotApproved(String),
 }
 
 impl Error {
@@ -239,6 +242,7 @@ impl Error {
 			| Error::SerErr { .. }
 			| Error::TxHashSetErr(_)
 			| Error::GenesisBlockRequired
+			| Error::ReorgNotApproved(_)
 			| Error::Other(_) => false,
 			_ => true,
 		}
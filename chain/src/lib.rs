@@ -39,13 +39,20 @@ use mwc_keychain as keychain;
 use mwc_util as util;
 
 mod chain;
+pub mod checkpoints;
 mod error;
 pub mod linked_list;
 pub mod pibd_params;
 pub mod pipe;
+pub mod reorg_guard;
+pub mod reorg_history;
+pub mod snapshot;
 pub mod store;
 pub mod txhashset;
 pub mod types;
+pub mod utxo_archive;
+pub mod utxo_dump;
+pub mod validation_pool;
 
 // Re-export the base interface
 
@@ -53,5 +60,7 @@ pub use crate::chain::{Chain, BLOCK_TO_BAN};
 pub use crate::error::Error;
 pub use crate::store::ChainStore;
 pub use crate::types::{
-	BlockStatus, ChainAdapter, Options, SyncState, SyncStatus, Tip, TxHashsetDownloadStats,
+	BlockStatus, ChainAdapter, ForkInfo, IntegrityReport, Options, PibdProgress, ReorgInfo,
+	SegmentsProgress, SyncEvent, SyncEventKind, SyncPhase, SyncProgress, SyncState, SyncStatus,
+	Tip, TxHashsetDownloadStats,
 };
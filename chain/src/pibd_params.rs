@@ -37,6 +37,8 @@ const SEGMENTS_BUFFER_LEN: [usize; 4] = [30, 40, 50, 60];
 
 // One block can be up to 1.5Mb in size. We still need some to run the node
 const ORPHANS_BUFFER_LEN: [usize; 4] = [20, 100, 250, 500];
+// Memory cap for the blocks that we can't process yet, in MB
+const ORPHANS_BYTES_LIMIT_MB: [usize; 4] = [16, 64, 128, 256];
 
 const SEGMENTS_REQUEST_LIMIT: [usize; 4] = [20, 30, 40, 40];
 
@@ -141,6 +143,15 @@ impl PibdParams {
 		)
 	}
 
+	/// Max total size of the blocks that are waiting for the headers, in bytes
+	pub fn get_orphans_bytes_limit(&self) -> usize {
+		Self::calc_mem_adequate_val2(
+			&ORPHANS_BYTES_LIMIT_MB,
+			self.get_available_memory_mb(),
+			self.cpu_num,
+		) * 1024 * 1024
+	}
+
 	/// Number of simultaneous requests for blocks we should make per available peer.
 	pub fn get_blocks_request_per_peer(&self) -> usize {
 		cmp::min(8, self.cpu_num * 2)
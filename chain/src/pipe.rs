@@ -15,6 +15,7 @@
 
 //! Implementation of the chain block acceptance (or refusal) pipeline.
 
+use crate::checkpoints;
 use crate::core::consensus;
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::Committed;
@@ -24,9 +25,11 @@ use crate::core::core::{
 use crate::core::global;
 use crate::core::pow;
 use crate::error::Error;
+use crate::reorg_guard;
 use crate::store;
 use crate::txhashset;
 use crate::types::{CommitPos, Options, Tip};
+use crate::validation_pool;
 use mwc_core::consensus::HeaderDifficultyInfo;
 use mwc_core::core::Transaction;
 use mwc_util::secp::Secp256k1;
@@ -217,10 +220,15 @@ pub fn process_blocks_series(
 		// Note: We still want to process the full block if we have seen this header before
 		// as we may have processed it "header first" and not yet processed the full block.
 		process_block_header(&b.header, ctx, cache_values)?;
+	}
+
+	// Rangeproofs and kernel signatures of the whole series are verified on the validation
+	// pool once the headers are valid, the rest of the validation is done in the block order.
+	let verified = validation_pool::verify_blocks(blocks, secp)?;
 
+	for (b, verified) in blocks.iter().zip(verified) {
 		// Validate the block itself, make sure it is internally consistent.
-		// Use the verifier_cache for verifying rangeproofs and kernel signatures.
-		validate_block(b, ctx, secp)?;
+		validate_block(b, verified, ctx, secp)?;
 	}
 
 	// Get previous header from the db.
@@ -239,6 +247,12 @@ pub fn process_blocks_series(
 		let fork_point = fork_point_local_blocks.0;
 		let mut local_branch_blocks = fork_point_local_blocks.1;
 
+		// Deep reorg must be approved by the operator
+		let last_header = &blocks.last().unwrap().header;
+		if fork_point.hash() != head.last_block_h && has_more_work(last_header, &head) {
+			reorg_guard::check_reorg(&head, &fork_point, last_header, batch)?;
+		}
+
 		for b in blocks {
 			replay_attack_check(b, fork_point.height, &local_branch_blocks, ext, batch)?;
 
@@ -338,11 +352,20 @@ pub fn process_block_headers(
 
 	let head = ctx.batch.header_head()?;
 
+	// Headers committed by a checkpoint in this chunk skip the PoW verification.
+	// The chunk is committed only if every header is valid, so they are the ancestors
+	// of the checkpoint in the db.
+	let checkpointed = if ctx.opts.contains(Options::CHECKPOINTS) {
+		checkpoints::checkpointed_headers(headers)
+	} else {
+		0
+	};
+
 	// Validate each header in the chunk and add to our db.
 	// Note: This batch may be rolled back later if the MMR does not validate successfully.
 	// Note: This batch may later be committed even if the MMR itself is rollbacked.
-	for header in headers {
-		validate_header(header, ctx, cache_values)?;
+	for (i, header) in headers.iter().enumerate() {
+		validate_header(header, ctx, cache_values, i < checkpointed)?;
 		add_block_header(header, &ctx.batch)?;
 	}
 
@@ -350,7 +373,13 @@ pub fn process_block_headers(
 
 	// Now apply this entire chunk of headers to the header MMR.
 	txhashset::header_extending(&mut ctx.header_pmmr, &mut ctx.batch, |ext, batch| {
-		rewind_and_apply_header_fork(&last_header, ext, batch, ctx_specific_validation)?;
+		let fork_point =
+			rewind_and_apply_header_fork(&last_header, ext, batch, ctx_specific_validation)?;
+
+		// Deep reorg of the header chain must be approved by the operator
+		if fork_point.hash() != head.last_block_h && has_more_work(last_header, &head) {
+			reorg_guard::check_reorg(&head, &fork_point, last_header, batch)?;
+		}
 
 		// If previous sync_head is not on the "current" chain then
 		// these headers are on an alternative fork to sync_head.
@@ -407,14 +436,18 @@ pub fn process_block_header(
 	}
 
 	// We want to validate this individual header before applying it to our header PMMR.
-	validate_header(header, ctx, cache_values)?;
+	validate_header(header, ctx, cache_values, false)?;
 
 	let ctx_specific_validation = &ctx.header_allowed;
 
 	// Apply the header to the header PMMR, making sure we put the extension in the correct state
 	// based on previous header first.
 	txhashset::header_extending(&mut ctx.header_pmmr, &mut ctx.batch, |ext, batch| {
-		rewind_and_apply_header_fork(&prev_header, ext, batch, ctx_specific_validation)?;
+		let fork_point =
+			rewind_and_apply_header_fork(&prev_header, ext, batch, ctx_specific_validation)?;
+		if fork_point.hash() != header_head.last_block_h && has_more_work(header, &header_head) {
+			reorg_guard::check_reorg(&header_head, &fork_point, header, batch)?;
+		}
 		ext.validate_root(header)?;
 		ext.apply_header(header)?;
 		if !has_more_work(&header, &header_head) {
@@ -522,10 +555,12 @@ pub fn validate_header_denylist(header: &BlockHeader, denylist: &[Hash]) -> Resu
 /// First level of block validation that only needs to act on the block header
 /// to make it as cheap as possible. The different validations are also
 /// arranged by order of cost to have as little DoS surface as possible.
+/// `checkpointed` headers are committed by a checkpoint, their PoW is not verified.
 fn validate_header(
 	header: &BlockHeader,
 	ctx: &BlockContext<'_>,
 	cache_values: &mut VecDeque<HeaderDifficultyInfo>,
+	checkpointed: bool,
 ) -> Result<(), Error> {
 	// Apply any ctx specific header validation (denylist) rules.
 	validate_header_ctx(header, ctx)?;
@@ -552,6 +587,9 @@ fn validate_header(
 	// Check the header hash against a list of known bad headers.
 	check_bad_header(header)?;
 
+	// Header at the checkpoint height must match the checkpoint.
+	checkpoints::validate_header_checkpoint(header, &header.hash())?;
+
 	// We can determine output and kernel counts for this block based on mmr sizes from previous header.
 	// Assume 0 inputs and estimate a lower bound on the full block weight.
 	let num_outputs = header
@@ -581,7 +619,10 @@ fn validate_header(
 	if !ctx.opts.contains(Options::SKIP_POW) {
 		// Quick check of this header in isolation. No point proceeding if this fails.
 		// We can do this without needing to iterate over previous headers.
-		validate_pow_only(header, ctx)?;
+		// Checkpoint ancestors are committed by the checkpoint hash.
+		if !checkpointed {
+			validate_pow_only(header, ctx)?;
+		}
 
 		if header.total_difficulty() <= prev.total_difficulty() {
 			return Err(Error::DifficultyTooLow);
@@ -619,14 +660,18 @@ fn validate_header(
 	Ok(())
 }
 
+// Validation of the block with the result of its rangeproofs and kernel signatures
+// verification from the validation pool
 fn validate_block(
 	block: &Block,
+	verified: Result<(), block::Error>,
 	ctx: &mut BlockContext<'_>,
 	secp: &Secp256k1,
 ) -> Result<(), Error> {
 	let prev = ctx.batch.get_previous_header(&block.header)?;
 	block
-		.validate(&prev.total_kernel_offset, secp)
+		.validate_without_signatures(&prev.total_kernel_offset, secp)
+		.and(verified)
 		.map_err(|e| Error::Block(e))?;
 	Ok(())
 }
@@ -741,12 +786,13 @@ fn has_more_work(header: &BlockHeader, head: &Tip) -> bool {
 }
 
 /// Rewind the header chain and reapply headers on a fork.
+/// Returns the "fork point" that we rewound to.
 pub fn rewind_and_apply_header_fork(
 	header: &BlockHeader,
 	ext: &mut txhashset::HeaderExtension<'_>,
 	batch: &store::Batch<'_>,
 	ctx_specific_validation: &dyn Fn(&BlockHeader) -> Result<(), Error>,
-) -> Result<(), Error> {
+) -> Result<BlockHeader, Error> {
 	let mut fork_hashes = vec![];
 	let mut current = header.clone();
 	while current.height > 0 && !ext.is_on_current_chain(&current, batch)? {
@@ -774,7 +820,7 @@ pub fn rewind_and_apply_header_fork(
 		ext.apply_header(&header)?;
 	}
 
-	Ok(())
+	Ok(forked_header)
 }
 
 /// Utility function to handle forks. From the forked block, jump backward
@@ -0,0 +1,134 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reorg depth guard. A reorg deeper than the max reorg depth is not applied until
+//! the operator approves the fork with the owner API. It prevents the silent deep
+//! reorgs from a majority fork at the low hashrate periods. Both the header chain and
+//! the block chain are checked, the fork is approved for both of them at once.
+
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::BlockHeader;
+use crate::error::Error;
+use crate::store;
+use crate::types::Tip;
+use crate::util::{RwLock, ToHex};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Deep reorg that is waiting for the operator approval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingReorg {
+	/// Hash of the first fork block above the fork point, the reorg is approved by it
+	pub fork_hash: String,
+	/// Height of the last common block with our chain
+	pub fork_height: u64,
+	/// Number of our blocks that the reorg would roll back
+	pub depth: u64,
+	/// Hash of the fork block that would be the new head
+	pub tip_hash: String,
+	/// Height of the fork block that would be the new head
+	pub tip_height: u64,
+	/// Last time the fork block was processed
+	pub last_seen: DateTime<Utc>,
+}
+
+lazy_static! {
+	static ref MAX_REORG_DEPTH: RwLock<Option<u64>> = RwLock::new(None);
+	static ref PENDING_REORGS: RwLock<HashMap<Hash, PendingReorg>> = RwLock::new(HashMap::new());
+	static ref APPROVED_REORGS: RwLock<HashSet<Hash>> = RwLock::new(HashSet::new());
+}
+
+/// Setup the max reorg depth. None disables the guard.
+pub fn init_max_reorg_depth(max_depth: Option<u64>) {
+	if let Some(depth) = max_depth {
+		info!("Reorgs deeper than {} blocks need the approval", depth);
+	}
+	*MAX_REORG_DEPTH.write() = max_depth;
+}
+
+/// Deep reorgs that are waiting for the approval
+pub fn pending_reorgs() -> Vec<PendingReorg> {
+	PENDING_REORGS.read().values().cloned().collect()
+}
+
+/// Approve the pending reorg by the first fork block hash. The fork is applied
+/// with the next block of it that we get.
+pub fn approve_reorg(fork_hash: &Hash) -> Result<(), Error> {
+	match PENDING_REORGS.write().remove(fork_hash) {
+		Some(reorg) => {
+			warn!(
+				"Reorg of depth {} at {} to {} is approved",
+				reorg.depth, reorg.fork_height, fork_hash
+			);
+			APPROVED_REORGS.write().insert(*fork_hash);
+			Ok(())
+		}
+		None => Err(Error::Other(format!(
+			"There is no pending reorg for the fork {}",
+			fork_hash
+		))),
+	}
+}
+
+/// Check the reorg from `head` (the head or the header head) to the fork `tip` with the
+/// fork point `fork_point`. Reorgs deeper than the max depth are registered as pending and rejected, unless approved.
+pub fn check_reorg(
+	head: &Tip,
+	fork_point: &BlockHeader,
+	tip: &BlockHeader,
+	batch: &store::Batch<'_>,
+) -> Result<(), Error> {
+	let max_depth = match *MAX_REORG_DEPTH.read() {
+		Some(max_depth) => max_depth,
+		None => return Ok(()),
+	};
+	let depth = head.height.saturating_sub(fork_point.height);
+	if depth <= max_depth {
+		return Ok(());
+	}
+
+	// First fork block above the fork point identifies the fork
+	let mut fork_block = tip.clone();
+	while fork_block.height > fork_point.height + 1 {
+		fork_block = batch.get_previous_header(&fork_block)?;
+	}
+	let fork_hash = fork_block.hash();
+	if APPROVED_REORGS.read().contains(&fork_hash) {
+		return Ok(());
+	}
+
+	warn!(
+		"Reorg of depth {} at {} to {} at {} is waiting for the approval of the fork {}",
+		depth,
+		fork_point.height,
+		tip.hash(),
+		tip.height,
+		fork_hash
+	);
+	PENDING_REORGS.write().insert(
+		fork_hash,
+		PendingReorg {
+			fork_hash: fork_hash.to_hex(),
+			fork_height: fork_point.height,
+			depth,
+			tip_hash: tip.hash().to_hex(),
+			tip_height: tip.height,
+			last_seen: Utc::now(),
+		},
+	);
+	Err(Error::ReorgNotApproved(format!(
+		"reorg depth {} exceeds the max {}, approve the fork {}",
+		depth, max_depth, fork_hash
+	)))
+}
@@ -0,0 +1,149 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! History of the chain head reorgs. Every reorg is recorded into its own store, so the
+//! services that credit the deposits can check whether a height was ever reorged by
+//! this node. The records are never pruned, the reorgs are rare.
+
+use crate::core::core::hash::Hash;
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::types::HashHeight;
+use chrono::{DateTime, Utc};
+use mwc_store as store;
+use mwc_store::{u64_to_key, Error};
+
+// Own environment, the chain environment is opened by the chain store
+const STORE_ENV: &str = "reorgs";
+const STORE_SUBPATH: &str = "reorgs";
+
+const REORG_EVENT_PREFIX: u8 = b'r';
+
+/// Recorded reorg of the chain head
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReorgEvent {
+	/// Sequence number of the reorg
+	pub id: u64,
+	/// Head before the reorg
+	pub old_tip: HashHeight,
+	/// Head after the reorg
+	pub new_tip: HashHeight,
+	/// Last common block of the old and the new head
+	pub fork_point: HashHeight,
+	/// Number of the blocks that were rolled back
+	pub depth: u64,
+	/// Hashes of the kernels of the rolled back blocks
+	pub kernels: Vec<Hash>,
+	/// Time of the reorg
+	pub time: DateTime<Utc>,
+}
+
+impl ReorgEvent {
+	/// Whether the block at the height was rolled back by this reorg
+	pub fn affects_height(&self, height: u64) -> bool {
+		height > self.fork_point.height && height <= self.old_tip.height
+	}
+}
+
+impl Readable for ReorgEvent {
+	fn read<R: Reader>(reader: &mut R) -> Result<ReorgEvent, ser::Error> {
+		let id = reader.read_u64()?;
+		let old_tip = HashHeight::read(reader)?;
+		let new_tip = HashHeight::read(reader)?;
+		let fork_point = HashHeight::read(reader)?;
+		let depth = reader.read_u64()?;
+		let kernels_len = reader.read_u64()?;
+		let mut kernels = Vec::new();
+		for _ in 0..kernels_len {
+			kernels.push(Hash::read(reader)?);
+		}
+		let time = reader.read_i64()?;
+		let time = DateTime::from_timestamp_millis(time)
+			.ok_or_else(|| ser::Error::CorruptedData(format!("Invalid reorg time {}", time)))?;
+		Ok(ReorgEvent {
+			id,
+			old_tip,
+			new_tip,
+			fork_point,
+			depth,
+			kernels,
+			time,
+		})
+	}
+}
+
+impl Writeable for ReorgEvent {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.id)?;
+		self.old_tip.write(writer)?;
+		self.new_tip.write(writer)?;
+		self.fork_point.write(writer)?;
+		writer.write_u64(self.depth)?;
+		writer.write_u64(self.kernels.len() as u64)?;
+		for k in &self.kernels {
+			k.write(writer)?;
+		}
+		writer.write_i64(self.time.timestamp_millis())?;
+		Ok(())
+	}
+}
+
+/// Store of the reorg events
+pub struct ReorgHistory {
+	db: store::Store,
+}
+
+impl ReorgHistory {
+	/// Open (or create) the reorg history store under the chain data directory
+	pub fn new(db_root: &str) -> Result<ReorgHistory, Error> {
+		let db = store::Store::new(db_root, Some(STORE_ENV), Some(STORE_SUBPATH), None)?;
+		Ok(ReorgHistory { db })
+	}
+
+	/// Record the reorg, the id of the event is assigned here. Returns the recorded event.
+	pub fn add(&self, mut event: ReorgEvent) -> Result<ReorgEvent, Error> {
+		let batch = self.db.batch_write()?;
+		let protocol_version = batch.protocol_version();
+		let last_id = batch
+			.iter(&[REORG_EVENT_PREFIX], move |_, mut v| {
+				ser::deserialize(
+					&mut v,
+					protocol_version,
+					ser::DeserializationMode::default(),
+				)
+				.map(|e: ReorgEvent| e.id)
+				.map_err(From::from)
+			})?
+			.last();
+		event.id = last_id.map(|id| id + 1).unwrap_or(0);
+		batch.put_ser(&u64_to_key(REORG_EVENT_PREFIX, event.id), &event)?;
+		batch.commit()?;
+		Ok(event)
+	}
+
+	/// All the recorded reorgs, the oldest first
+	pub fn events(&self) -> Result<Vec<ReorgEvent>, Error> {
+		let protocol_version = self.db.protocol_version();
+		Ok(self
+			.db
+			.iter(&[REORG_EVENT_PREFIX], move |_, mut v| {
+				ser::deserialize(
+					&mut v,
+					protocol_version,
+					ser::DeserializationMode::default(),
+				)
+				.map_err(From::from)
+			})?
+			.collect())
+	}
+}
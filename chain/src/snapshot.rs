@@ -0,0 +1,377 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Chain data snapshot. The snapshot directory has the same layout as the chain
+//! data directory (`lmdb`, `txhashset` and `header`) plus the manifest:
+//! ```text
+//! mwc-chain-snapshot 1
+//! height <height>
+//! hash <block hash hex>
+//! ```
+//! The PMMR files that are appended and truncated in place are copied, the files
+//! that are only replaced by rename are hardlinked when the file system allows it.
+//! The chain locks are held only to hardlink the files and to open the copied ones,
+//! they are copied up to the lengths of that moment afterwards.
+
+use crate::core::core::hash::Hash;
+use crate::error::Error;
+use crate::util::ToHex;
+use chrono::Utc;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_MAGIC: &str = "mwc-chain-snapshot";
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot manifest file name
+pub const MANIFEST_FILE: &str = "snapshot.txt";
+/// LMDB environment directory of the chain data
+pub const LMDB_SUBDIR: &str = "lmdb";
+// PMMR data directories of the chain data
+const PMMR_SUBDIRS: [&str; 2] = ["txhashset", "header"];
+// Files that are modified in place, a hardlink would follow the changes
+const IN_PLACE_FILES: [&str; 3] = ["pmmr_hash.bin", "pmmr_data.bin", "pmmr_size.bin"];
+// Existing chain data is moved there on restore
+const PRE_RESTORE_PREFIX: &str = "pre_restore";
+// Snapshot is copied there first on restore, the chain data is replaced only by renames
+const RESTORE_TMP_PREFIX: &str = "restore_tmp";
+
+/// Snapshot that was written by the node
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+	/// Snapshot directory
+	pub path: PathBuf,
+	/// Head height of the snapshot
+	pub height: u64,
+	/// Head hash of the snapshot
+	pub hash: Hash,
+	/// Number of the hardlinked files
+	pub linked_files: u64,
+	/// Number of the copied bytes, LMDB copy included
+	pub copied_bytes: u64,
+}
+
+/// Result of the snapshot restore
+#[derive(Debug, Clone)]
+pub struct SnapshotRestore {
+	/// Head height of the restored snapshot
+	pub height: u64,
+	/// Head hash of the restored snapshot
+	pub hash: Hash,
+	/// Directory with the chain data that was there before the restore
+	pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct CopyStats {
+	linked_files: u64,
+	copied_bytes: u64,
+}
+
+/// PMMR file of the snapshot that is copied after the chain locks are released. The file
+/// is opened under the locks, so a compaction that replaces it doesn't affect the copy.
+pub struct PendingCopy {
+	file: File,
+	len: u64,
+	dst: PathBuf,
+}
+
+/// PMMR data of the snapshot, the files that are modified in place are not copied yet
+pub struct PendingPmmrCopy {
+	/// Number of the hardlinked files
+	pub linked_files: u64,
+	/// Number of the copied bytes
+	pub copied_bytes: u64,
+	pending: Vec<PendingCopy>,
+}
+
+impl PendingPmmrCopy {
+	/// Copy the files that are modified in place up to their lengths at the time they
+	/// were opened. The chain locks are not needed, but the caller must check that the
+	/// chain wasn't rewound below the snapshot meanwhile, the truncated data is rewritten.
+	pub fn finish(mut self) -> Result<(u64, u64), Error> {
+		for copy in self.pending.drain(..) {
+			let mut dst = File::create(&copy.dst)?;
+			let copied = io::copy(&mut (&copy.file).take(copy.len), &mut dst)?;
+			if copied != copy.len {
+				return Err(Error::Other(format!(
+					"PMMR file {} was truncated while the snapshot was written",
+					copy.dst.display()
+				)));
+			}
+			dst.sync_all()?;
+			self.copied_bytes += copied;
+		}
+		Ok((self.linked_files, self.copied_bytes))
+	}
+}
+
+fn manifest_err(line: usize, msg: &str) -> Error {
+	Error::Other(format!(
+		"Invalid snapshot manifest at line {}, {}",
+		line, msg
+	))
+}
+
+/// Create the snapshot directory. Existing directory must be empty, the snapshot
+/// never overwrites the data.
+pub fn prepare_out_dir(out_dir: &Path) -> Result<(), Error> {
+	if out_dir.exists() {
+		if fs::read_dir(out_dir)?.next().is_some() {
+			return Err(Error::Other(format!(
+				"Snapshot directory {} is not empty",
+				out_dir.display()
+			)));
+		}
+	} else {
+		fs::create_dir_all(out_dir)?;
+	}
+	fs::create_dir(out_dir.join(LMDB_SUBDIR))?;
+	Ok(())
+}
+
+/// Start the copy of the PMMR data directories into the snapshot. The caller must hold
+/// the chain locks, the files that are replaced by rename are hardlinked (or copied) and
+/// the files that are modified in place are opened and their lengths are recorded.
+/// The copy is completed by `PendingPmmrCopy::finish` after the locks are released.
+pub fn start_pmmr_copy(db_root: &Path, out_dir: &Path) -> Result<PendingPmmrCopy, Error> {
+	let mut stats = CopyStats::default();
+	let mut pending = vec![];
+	for subdir in PMMR_SUBDIRS.iter() {
+		let from = db_root.join(subdir);
+		if from.is_dir() {
+			link_or_defer_dir(&from, &out_dir.join(subdir), &mut stats, &mut pending)?;
+		}
+	}
+	Ok(PendingPmmrCopy {
+		linked_files: stats.linked_files,
+		copied_bytes: stats.copied_bytes,
+		pending,
+	})
+}
+
+/// Write the manifest, it is the last file of the complete snapshot
+pub fn write_manifest(out_dir: &Path, height: u64, hash: &Hash) -> Result<(), Error> {
+	let manifest = format!(
+		"{} {}\nheight {}\nhash {}\n",
+		SNAPSHOT_MAGIC,
+		SNAPSHOT_VERSION,
+		height,
+		hash.to_hex()
+	);
+	fs::write(out_dir.join(MANIFEST_FILE), manifest)?;
+	Ok(())
+}
+
+/// Read the snapshot head from the manifest
+pub fn read_manifest(dir: &Path) -> Result<(u64, Hash), Error> {
+	let path = dir.join(MANIFEST_FILE);
+	let content = fs::read_to_string(&path).map_err(|e| {
+		Error::Other(format!(
+			"Unable to read snapshot manifest {}, {}",
+			path.display(),
+			e
+		))
+	})?;
+	let mut lines = content.lines();
+
+	let header = lines.next().ok_or_else(|| manifest_err(1, "it is empty"))?;
+	if header != format!("{} {}", SNAPSHOT_MAGIC, SNAPSHOT_VERSION) {
+		return Err(manifest_err(1, "unknown format or version"));
+	}
+	let height = lines
+		.next()
+		.and_then(|l| l.strip_prefix("height "))
+		.and_then(|h| h.parse::<u64>().ok())
+		.ok_or_else(|| manifest_err(2, "expected height"))?;
+	let hash = lines
+		.next()
+		.and_then(|l| l.strip_prefix("hash "))
+		.and_then(|h| Hash::from_hex(h).ok())
+		.ok_or_else(|| manifest_err(3, "expected hash"))?;
+	Ok((height, hash))
+}
+
+/// Replace the chain data under `db_root` with the snapshot. The node must not be running.
+/// Existing chain data is moved into the `pre_restore_<time>` directory under `db_root`.
+/// The snapshot is copied into a temporary directory under `db_root` first, so a failed
+/// copy doesn't touch the chain data, then the directories are swapped by renames that
+/// are rolled back on failure.
+pub fn restore_snapshot(snapshot_dir: &Path, db_root: &Path) -> Result<SnapshotRestore, Error> {
+	let (height, hash) = read_manifest(snapshot_dir)?;
+	if !snapshot_dir.join(LMDB_SUBDIR).is_dir() {
+		return Err(Error::Other(format!(
+			"Snapshot {} has no {} directory",
+			snapshot_dir.display(),
+			LMDB_SUBDIR
+		)));
+	}
+
+	let subdirs: Vec<&str> = std::iter::once(LMDB_SUBDIR)
+		.chain(PMMR_SUBDIRS.iter().cloned())
+		.collect();
+	let time = Utc::now().format("%Y%m%d_%H%M%S");
+
+	fs::create_dir_all(db_root)?;
+	let tmp_dir = db_root.join(format!("{}_{}", RESTORE_TMP_PREFIX, time));
+	let mut stats = CopyStats::default();
+	let copied = subdirs.iter().try_for_each(|subdir| {
+		let from = snapshot_dir.join(subdir);
+		if from.is_dir() {
+			// LMDB data file is modified in place, so it is always copied
+			let allow_link = *subdir != LMDB_SUBDIR;
+			link_or_copy_dir(&from, &tmp_dir.join(subdir), allow_link, &mut stats)?;
+		}
+		Ok::<(), io::Error>(())
+	});
+	if let Err(e) = copied {
+		let _ = fs::remove_dir_all(&tmp_dir);
+		return Err(Error::Other(format!(
+			"Unable to copy the snapshot {} into {}, {}",
+			snapshot_dir.display(),
+			db_root.display(),
+			e
+		)));
+	}
+
+	let backup = db_root.join(format!("{}_{}", PRE_RESTORE_PREFIX, time));
+	let mut backed_up: Vec<&str> = vec![];
+	let mut restored: Vec<&str> = vec![];
+	let swapped = subdirs.iter().try_for_each(|subdir| {
+		let current = db_root.join(subdir);
+		if current.exists() {
+			fs::create_dir_all(&backup)?;
+			fs::rename(&current, backup.join(subdir))?;
+			backed_up.push(*subdir);
+		}
+		let from = tmp_dir.join(subdir);
+		if from.exists() {
+			fs::rename(&from, &current)?;
+			restored.push(*subdir);
+		}
+		Ok::<(), io::Error>(())
+	});
+	if let Err(e) = swapped {
+		// Put the chain data back, the partially restored directories go away
+		for subdir in restored {
+			let _ = fs::remove_dir_all(db_root.join(subdir));
+		}
+		for subdir in backed_up {
+			if let Err(e) = fs::rename(backup.join(subdir), db_root.join(subdir)) {
+				error!(
+					"Unable to move {} back into {}, {}",
+					backup.join(subdir).display(),
+					db_root.display(),
+					e
+				);
+			}
+		}
+		let _ = fs::remove_dir(&backup);
+		let _ = fs::remove_dir_all(&tmp_dir);
+		return Err(Error::Other(format!(
+			"Unable to restore the snapshot into {}, {}",
+			db_root.display(),
+			e
+		)));
+	}
+	let _ = fs::remove_dir_all(&tmp_dir);
+	let backup_dir = if backed_up.is_empty() {
+		None
+	} else {
+		Some(backup)
+	};
+
+	info!(
+		"Chain snapshot at {} {} is restored into {}, {} files are linked, {} bytes are copied",
+		height,
+		hash,
+		db_root.display(),
+		stats.linked_files,
+		stats.copied_bytes
+	);
+	Ok(SnapshotRestore {
+		height,
+		hash,
+		backup_dir,
+	})
+}
+
+fn link_or_copy_dir(
+	from: &Path,
+	to: &Path,
+	allow_link: bool,
+	stats: &mut CopyStats,
+) -> io::Result<()> {
+	fs::create_dir_all(to)?;
+	for entry in fs::read_dir(from)? {
+		let entry = entry?;
+		let file_type = entry.file_type()?;
+		let src = entry.path();
+		let dst = to.join(entry.file_name());
+		if file_type.is_dir() {
+			link_or_copy_dir(&src, &dst, allow_link, stats)?;
+		} else if file_type.is_file() {
+			let in_place = entry
+				.file_name()
+				.to_str()
+				.map(|name| IN_PLACE_FILES.contains(&name))
+				.unwrap_or(true);
+			// Hardlink fails across the file systems, copy is the fallback
+			if allow_link && !in_place && fs::hard_link(&src, &dst).is_ok() {
+				stats.linked_files += 1;
+			} else {
+				stats.copied_bytes += fs::copy(&src, &dst)?;
+			}
+		}
+	}
+	Ok(())
+}
+
+// Like link_or_copy_dir, but the files that are modified in place are only opened,
+// they are copied later by PendingPmmrCopy::finish
+fn link_or_defer_dir(
+	from: &Path,
+	to: &Path,
+	stats: &mut CopyStats,
+	pending: &mut Vec<PendingCopy>,
+) -> io::Result<()> {
+	fs::create_dir_all(to)?;
+	for entry in fs::read_dir(from)? {
+		let entry = entry?;
+		let file_type = entry.file_type()?;
+		let src = entry.path();
+		let dst = to.join(entry.file_name());
+		if file_type.is_dir() {
+			link_or_defer_dir(&src, &dst, stats, pending)?;
+		} else if file_type.is_file() {
+			let in_place = entry
+				.file_name()
+				.to_str()
+				.map(|name| IN_PLACE_FILES.contains(&name))
+				.unwrap_or(true);
+			if in_place {
+				let file = File::open(&src)?;
+				let len = file.metadata()?.len();
+				pending.push(PendingCopy { file, len, dst });
+			} else if fs::hard_link(&src, &dst).is_ok() {
+				stats.linked_files += 1;
+			} else {
+				// Hardlink fails across the file systems, copy is the fallback
+				stats.copied_bytes += fs::copy(&src, &dst)?;
+			}
+		}
+	}
+	Ok(())
+}
@@ -21,23 +21,32 @@ use crate::core::core::{Block, BlockHeader, BlockSums, Inputs};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{DeserializationMode, ProtocolVersion, Readable, Writeable};
 use crate::linked_list::MultiIndex;
-use crate::types::{CommitPos, HashHeight, Tip};
+use crate::types::{CommitPos, HashHeight, PibdProgress, Tip};
 use crate::util::secp::pedersen::Commitment;
 use croaring::Bitmap;
 use mwc_core::ser;
 use mwc_store as store;
+use mwc_store::migration::{self, Migration, MigrationOptions, MigrationReport};
 use mwc_store::{option_to_not_found, to_key, Error};
 use std::convert::TryInto;
+use std::io::Cursor;
 use std::sync::Arc;
 
 const STORE_SUBPATH: &str = "chain";
 
+// zstd level of the compressed blocks, they are compressed once and read rarely
+const BLOCK_COMPRESSION_LEVEL: i32 = 9;
+
 const BLOCK_HEADER_PREFIX: u8 = b'h';
 const BLOCK_PREFIX: u8 = b'b';
+/// Blocks that are compressed with zstd, see Batch::compress_block
+const COMPRESSED_BLOCK_PREFIX: u8 = b'z';
 const HEAD_PREFIX: u8 = b'H';
 const TAIL_PREFIX: u8 = b'T';
 const HEADER_HEAD_PREFIX: u8 = b'G';
 const OUTPUT_POS_PREFIX: u8 = b'p';
+/// Prefix for the lowest block of the backfilled blocks history.
+const HISTORY_TAIL_PREFIX: u8 = b'L';
 
 /// Prefix for NRD kernel pos index lists.
 pub const NRD_KERNEL_LIST_PREFIX: u8 = b'K';
@@ -49,23 +58,123 @@ const BLOCK_SUMS_PREFIX: u8 = b'M';
 const BLOCK_SPENT_PREFIX: u8 = b'S';
 const BLOCK_SPENT_COMMITMENT_PREFIX: u8 = b'C';
 
+/// Prefix for the optional kernel excess to blocks index.
+const KERNEL_INDEX_PREFIX: u8 = b'x';
+/// Prefix for the optional output commitment to blocks index.
+const OUTPUT_INDEX_PREFIX: u8 = b'o';
+/// Prefix for the first block height covered by the kernel/commitment index.
+const INDEX_FROM_PREFIX: u8 = b'i';
+
+/// Prefix for the PIBD state sync progress.
+const PIBD_PREFIX: u8 = b'P';
+/// PIBD progress record.
+const PIBD_PROGRESS: &str = "progress";
+/// Outputs bitmap of the PIBD session.
+const PIBD_BITMAP: &str = "bitmap";
+
 /// Prefix for various boolean flags stored in the db.
 const BOOL_FLAG_PREFIX: u8 = b'F';
 /// Boolean flag for v3 migration.
 const BLOCKS_V3_MIGRATED: &str = "blocks_v3_migrated";
 
+/// Schema migrations of the chain store, in order. Append only, never change the released ones.
+const CHAIN_MIGRATIONS: [Migration<ChainStore>; 1] = [Migration {
+	version: 1,
+	description: "full blocks to protocol version 3",
+	run: ChainStore::migrate_blocks_v2_v3,
+}];
+
 /// All chain-related database operations
 pub struct ChainStore {
 	db: store::Store,
 }
 
 impl ChainStore {
-	/// Create new chain store
+	/// Create new chain store, the pending schema migrations are applied
 	pub fn new(db_root: &str) -> Result<ChainStore, Error> {
+		let store = ChainStore::open(db_root)?;
+		store.migrate(&MigrationOptions::default())?;
+		Ok(store)
+	}
+
+	fn open(db_root: &str) -> Result<ChainStore, Error> {
 		let db = store::Store::new(db_root, None, Some(STORE_SUBPATH), None)?;
 		Ok(ChainStore { db })
 	}
 
+	/// Run the pending schema migrations of the chain store at `db_root` with the options.
+	/// The store must not be open.
+	pub fn run_migrations(
+		db_root: &str,
+		options: &MigrationOptions,
+	) -> Result<MigrationReport, Error> {
+		ChainStore::open(db_root)?.migrate(options)
+	}
+
+	fn migrate(&self, options: &MigrationOptions) -> Result<MigrationReport, Error> {
+		let report = migration::run_migrations(&self.db, self, &CHAIN_MIGRATIONS, options)?;
+		if report.to_version != report.from_version {
+			info!(
+				"Chain store is migrated from schema version {} to {}",
+				report.from_version, report.to_version
+			);
+		}
+		Ok(report)
+	}
+
+	/// Migrate full blocks from protocol version v2 to v3, "commit only" inputs.
+	fn migrate_blocks_v2_v3(&self) -> Result<(), Error> {
+		if self.batch_read()?.is_blocks_v3_migrated()? {
+			// Migrated before the schema versions were introduced
+			debug!("migrate_blocks_v2_v3: previously migrated, skipping");
+			return Ok(());
+		}
+		let mut total = 0;
+		let mut keys_to_migrate = vec![];
+		for (k, v) in self.batch_read()?.blocks_raw_iter()? {
+			total += 1;
+
+			// We want to migrate all blocks that cannot be read via v3 protocol version.
+			let block_v3: Result<Block, _> = ser::deserialize(
+				&mut Cursor::new(&v),
+				ProtocolVersion(3),
+				DeserializationMode::default(),
+			);
+			if block_v3.is_err() {
+				let block_v2: Result<Block, _> = ser::deserialize(
+					&mut Cursor::new(&v),
+					ProtocolVersion(2),
+					DeserializationMode::default(),
+				);
+				if block_v2.is_ok() {
+					keys_to_migrate.push(k);
+				}
+			}
+		}
+		debug!(
+			"migrate_blocks_v2_v3: {} (of {}) blocks to migrate",
+			keys_to_migrate.len(),
+			total,
+		);
+		let mut count = 0;
+		for keys in keys_to_migrate.chunks(100) {
+			let batch = self.batch_write()?;
+			for key in keys {
+				batch.migrate_block(&key, ProtocolVersion(2), ProtocolVersion(3))?;
+				count += 1;
+			}
+			batch.commit()?;
+			debug!(
+				"migrate_blocks_v2_v3: successfully migrated {} blocks",
+				count
+			);
+		}
+		// The flag is kept for the nodes that are downgraded
+		let batch = self.batch_write()?;
+		batch.set_blocks_v3_migrated(true)?;
+		batch.commit()
+	}
+
 	/// The current chain head.
 	pub fn head(&self) -> Result<Tip, Error> {
 		option_to_not_found(self.db.get_ser(&[HEAD_PREFIX], None), || "HEAD".to_owned())
@@ -83,21 +192,32 @@ impl ChainStore {
 		option_to_not_found(self.db.get_ser(&[TAIL_PREFIX], None), || "TAIL".to_owned())
 	}
 
+	/// The lowest block of the backfilled blocks history.
+	pub fn history_tail(&self) -> Result<Tip, Error> {
+		option_to_not_found(self.db.get_ser(&[HISTORY_TAIL_PREFIX], None), || {
+			"HISTORY_TAIL".to_owned()
+		})
+	}
+
 	/// Header of the block at the head of the block chain (not the same thing as header_head).
 	pub fn head_header(&self) -> Result<BlockHeader, Error> {
 		self.get_block_header(&self.head()?.last_block_h)
 	}
 
-	/// Get full block.
+	/// Get full block, compressed or not.
 	pub fn get_block(&self, h: &Hash) -> Result<Block, Error> {
-		option_to_not_found(self.db.get_ser(&to_key(BLOCK_PREFIX, h), None), || {
-			format!("BLOCK: {}", h)
-		})
+		if let Some(block) = self.db.get_ser(&to_key(BLOCK_PREFIX, h), None)? {
+			return Ok(block);
+		}
+		let block: Option<CompressedBlock> =
+			self.db.get_ser(&to_key(COMPRESSED_BLOCK_PREFIX, h), None)?;
+		option_to_not_found(Ok(block.map(|b| b.0)), || format!("BLOCK: {}", h))
 	}
 
 	/// Does this full block exist?
 	pub fn block_exists(&self, h: &Hash) -> Result<bool, Error> {
-		self.db.exists(&to_key(BLOCK_PREFIX, h))
+		Ok(self.db.exists(&to_key(BLOCK_PREFIX, h))?
+			|| self.db.exists(&to_key(COMPRESSED_BLOCK_PREFIX, h))?)
 	}
 
 	/// Get block_sums for the block hash.
@@ -156,6 +276,32 @@ impl ChainStore {
 		self.db.get_ser(&to_key(OUTPUT_POS_PREFIX, commit), None)
 	}
 
+	/// First block height covered by the kernel/commitment index, None if the index is disabled
+	pub fn index_from(&self) -> Result<Option<u64>, Error> {
+		self.db.get_ser(&[INDEX_FROM_PREFIX], None)
+	}
+
+	/// Blocks with the kernel from the kernel index
+	pub fn get_kernel_index(&self, excess: &Commitment) -> Result<Vec<HashHeight>, Error> {
+		Ok(self
+			.db
+			.get_ser(&to_key(KERNEL_INDEX_PREFIX, excess), None)?
+			.unwrap_or_default())
+	}
+
+	/// Blocks with the output from the commitment index
+	pub fn get_output_index(&self, commit: &Commitment) -> Result<Vec<HashHeight>, Error> {
+		Ok(self
+			.db
+			.get_ser(&to_key(OUTPUT_INDEX_PREFIX, commit), None)?
+			.unwrap_or_default())
+	}
+
+	/// Consistent copy of the db into the existing empty directory
+	pub fn copy_to(&self, path: &str) -> Result<(), Error> {
+		self.db.copy_to(path)
+	}
+
 	/// Builds a new batch for read only access with this store.
 	pub fn batch_read(&self) -> Result<Batch<'_>, Error> {
 		Ok(Batch {
@@ -189,6 +335,13 @@ impl<'a> Batch<'a> {
 		option_to_not_found(self.db.get_ser(&[TAIL_PREFIX], None), || "TAIL".to_owned())
 	}
 
+	/// The lowest block of the backfilled blocks history.
+	pub fn history_tail(&self) -> Result<Tip, Error> {
+		option_to_not_found(self.db.get_ser(&[HISTORY_TAIL_PREFIX], None), || {
+			"HISTORY_TAIL".to_owned()
+		})
+	}
+
 	/// The current header head (may differ from chain head).
 	pub fn header_head(&self) -> Result<Tip, Error> {
 		option_to_not_found(self.db.get_ser(&[HEADER_HEAD_PREFIX], None), || {
@@ -211,21 +364,56 @@ impl<'a> Batch<'a> {
 		self.db.put_ser(&[TAIL_PREFIX], t)
 	}
 
+	/// Save the lowest block of the backfilled history to db.
+	pub fn save_history_tail(&self, t: &Tip) -> Result<(), Error> {
+		self.db.put_ser(&[HISTORY_TAIL_PREFIX], t)
+	}
+
+	/// Delete the backfilled history tail, the history is not continuous with the state any more.
+	pub fn delete_history_tail(&self) -> Result<(), Error> {
+		if self.db.exists(&[HISTORY_TAIL_PREFIX])? {
+			self.db.delete(&[HISTORY_TAIL_PREFIX])?;
+		}
+		Ok(())
+	}
+
 	/// Save header head to db.
 	pub fn save_header_head(&self, t: &Tip) -> Result<(), Error> {
 		self.db.put_ser(&[HEADER_HEAD_PREFIX], t)
 	}
 
-	/// get block
+	/// get block, compressed or not
 	pub fn get_block(&self, h: &Hash) -> Result<Block, Error> {
-		option_to_not_found(self.db.get_ser(&to_key(BLOCK_PREFIX, h), None), || {
-			format!("Block with hash: {}", h)
-		})
+		if let Some(block) = self.db.get_ser(&to_key(BLOCK_PREFIX, h), None)? {
+			return Ok(block);
+		}
+		let block: Option<CompressedBlock> =
+			self.db.get_ser(&to_key(COMPRESSED_BLOCK_PREFIX, h), None)?;
+		option_to_not_found(Ok(block.map(|b| b.0)), || format!("Block with hash: {}", h))
 	}
 
 	/// Does the block exist?
 	pub fn block_exists(&self, h: &Hash) -> Result<bool, Error> {
-		self.db.exists(&to_key(BLOCK_PREFIX, h))
+		Ok(self.db.exists(&to_key(BLOCK_PREFIX, h))?
+			|| self.db.exists(&to_key(COMPRESSED_BLOCK_PREFIX, h))?)
+	}
+
+	/// Replace the stored block with its zstd compressed copy, it is still read by
+	/// get_block. Returns false if there is no uncompressed block with this hash.
+	pub fn compress_block(&self, h: &Hash) -> Result<bool, Error> {
+		let key = to_key(BLOCK_PREFIX, h);
+		let block: Option<Block> = self.db.get_ser(&key, None)?;
+		match block {
+			Some(block) => {
+				self.db.put_ser(
+					&to_key(COMPRESSED_BLOCK_PREFIX, h)[..],
+					&CompressedBlock(block),
+				)?;
+				self.db.delete(&key)?;
+				Ok(true)
+			}
+			None => Ok(false),
+		}
 	}
 
 	/// Save the block to the db.
@@ -270,6 +458,45 @@ impl<'a> Batch<'a> {
 		Ok(())
 	}
 
+	/// Add the block kernels and outputs to the optional kernel/commitment index.
+	/// The index is append only, entries of the blocks that are not on the main chain
+	/// any more are filtered out by the caller.
+	pub fn save_block_index(&self, b: &Block) -> Result<(), Error> {
+		let hh = HashHeight {
+			hash: b.hash(),
+			height: b.header.height,
+		};
+		for kernel in b.kernels() {
+			self.add_index_entry(&to_key(KERNEL_INDEX_PREFIX, kernel.excess), hh)?;
+		}
+		for output in b.outputs() {
+			self.add_index_entry(&to_key(OUTPUT_INDEX_PREFIX, output.commitment()), hh)?;
+		}
+		Ok(())
+	}
+
+	/// Save the first block height covered by the kernel/commitment index
+	pub fn save_index_from(&self, height: u64) -> Result<(), Error> {
+		self.db.put_ser(&[INDEX_FROM_PREFIX], &height)
+	}
+
+	/// Index is disabled, it will have a gap if enabled again
+	pub fn delete_index_from(&self) -> Result<(), Error> {
+		if self.db.exists(&[INDEX_FROM_PREFIX])? {
+			self.db.delete(&[INDEX_FROM_PREFIX])?;
+		}
+		Ok(())
+	}
+
+	fn add_index_entry(&self, key: &[u8], hh: HashHeight) -> Result<(), Error> {
+		let mut list: Vec<HashHeight> = self.db.get_ser(key, None)?.unwrap_or_default();
+		if !list.contains(&hh) {
+			list.push(hh);
+			self.db.put_ser(key, &list)?;
+		}
+		Ok(())
+	}
+
 	/// get spent commitment
 	pub fn get_spent_commitments(
 		&self,
@@ -347,7 +574,12 @@ impl<'a> Batch<'a> {
 			}
 		}
 
-		self.db.delete(&to_key(BLOCK_PREFIX, bh)[..])?;
+		let key = to_key(BLOCK_PREFIX, bh);
+		if self.db.exists(&key)? {
+			self.db.delete(&key)?;
+		} else {
+			self.db.delete(&to_key(COMPRESSED_BLOCK_PREFIX, bh)[..])?;
+		}
 
 		// Best effort at deleting associated data for this block.
 		// Not an error if these fail.
@@ -526,6 +758,50 @@ impl<'a> Batch<'a> {
 		)
 	}
 
+	/// Save the PIBD state sync progress with the outputs bitmap of the session
+	pub fn save_pibd_progress(
+		&self,
+		progress: &PibdProgress,
+		bitmap: &Bitmap,
+	) -> Result<(), Error> {
+		self.db
+			.put_ser(&to_key(PIBD_PREFIX, PIBD_PROGRESS)[..], progress)?;
+		self.db.put(
+			&to_key(PIBD_PREFIX, PIBD_BITMAP)[..],
+			&bitmap.serialize::<croaring::Portable>(),
+		)
+	}
+
+	/// Get the saved PIBD state sync progress
+	pub fn get_pibd_progress(&self) -> Result<PibdProgress, Error> {
+		option_to_not_found(
+			self.db.get_ser(&to_key(PIBD_PREFIX, PIBD_PROGRESS), None),
+			|| "PIBD progress".to_owned(),
+		)
+	}
+
+	/// Get the outputs bitmap of the saved PIBD session
+	pub fn get_pibd_bitmap(&self) -> Result<Bitmap, Error> {
+		option_to_not_found(
+			self.db
+				.get_with(&to_key(PIBD_PREFIX, PIBD_BITMAP), move |_, data| {
+					Ok(Bitmap::deserialize::<croaring::Portable>(data))
+				}),
+			|| "PIBD bitmap".to_owned(),
+		)
+	}
+
+	/// Delete the PIBD state sync progress, the download starts from the scratch
+	pub fn delete_pibd_progress(&self) -> Result<(), Error> {
+		for key in &[PIBD_PROGRESS, PIBD_BITMAP] {
+			let key = to_key(PIBD_PREFIX, key);
+			if self.db.exists(&key)? {
+				self.db.delete(&key)?;
+			}
+		}
+		Ok(())
+	}
+
 	/// Get the "spent index" from the db for the specified block.
 	/// If we need to rewind a block then we use this to "unspend" the spent outputs.
 	pub fn get_spent_index(&self, bh: &Hash) -> Result<Vec<CommitPos>, Error> {
@@ -549,18 +825,32 @@ impl<'a> Batch<'a> {
 		})
 	}
 
-	/// Iterator over all full blocks in the db.
+	/// Iterator over all full blocks in the db, the compressed ones are after the rest.
 	/// Uses default db serialization strategy via db protocol version.
 	pub fn blocks_iter(&self) -> Result<impl Iterator<Item = Block>, Error> {
 		let key = to_key(BLOCK_PREFIX, "");
 		let protocol_version = self.db.protocol_version();
-		self.db.iter(&key, move |_, mut v| {
+		let blocks = self.db.iter(&key, move |_, mut v| {
 			ser::deserialize(&mut v, protocol_version, DeserializationMode::default())
 				.map_err(From::from)
-		})
+		})?;
+		let key = to_key(COMPRESSED_BLOCK_PREFIX, "");
+		let compressed = self.db.iter(&key, move |_, mut v| {
+			ser::deserialize(&mut v, protocol_version, DeserializationMode::default())
+				.map(|b: CompressedBlock| b.0)
+				.map_err(From::from)
+		})?;
+		Ok(blocks.chain(compressed))
+	}
+
+	/// Hashes of the uncompressed blocks in the db
+	pub fn uncompressed_blocks_iter(&self) -> Result<impl Iterator<Item = Hash>, Error> {
+		let key = to_key(BLOCK_PREFIX, "");
+		self.db.iter(&key, |k, _| Ok(Hash::from_vec(&k[1..])))
 	}
 
-	/// Iterator over raw data for full blocks in the db.
+	/// Iterator over raw data for the uncompressed full blocks in the db. The blocks
+	/// are compressed with the current protocol version only, after the migrations.
 	/// Used during block migration (we need flexibility around deserialization).
 	pub fn blocks_raw_iter(&self) -> Result<impl Iterator<Item = (Vec<u8>, Vec<u8>)>, Error> {
 		let key = to_key(BLOCK_PREFIX, "");
@@ -690,6 +980,41 @@ pub fn nrd_recent_kernel_index() -> MultiIndex<CommitPos> {
 	MultiIndex::init(NRD_KERNEL_LIST_PREFIX, NRD_KERNEL_ENTRY_PREFIX)
 }
 
+// Block that is stored compressed: the length prefixed zstd frame of the block
+// serialized with the db protocol version
+struct CompressedBlock(Block);
+
+impl Readable for CompressedBlock {
+	fn read<R: ser::Reader>(reader: &mut R) -> Result<Self, ser::Error> {
+		let len = reader.read_u64()? as usize;
+		let mut data = Vec::with_capacity(len);
+		while data.len() < len {
+			let chunk = (len - data.len()).min(ser::READ_CHUNK_LIMIT);
+			data.extend(reader.read_fixed_bytes(chunk)?);
+		}
+		let data = zstd::decode_all(&data[..]).map_err(|e| {
+			ser::Error::CorruptedData(format!("Unable to decompress the block, {}", e))
+		})?;
+		let block = ser::deserialize(
+			&mut &data[..],
+			reader.protocol_version(),
+			reader.deserialization_mode(),
+		)?;
+		Ok(CompressedBlock(block))
+	}
+}
+
+impl Writeable for CompressedBlock {
+	fn write<W: ser::Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		let data = ser::ser_vec(&self.0, writer.protocol_version())?;
+		let data = zstd::encode_all(&data[..], BLOCK_COMPRESSION_LEVEL).map_err(|e| {
+			ser::Error::CorruptedData(format!("Unable to compress the block, {}", e))
+		})?;
+		writer.write_u64(data.len() as u64)?;
+		writer.write_fixed_bytes(&data)
+	}
+}
+
 struct BoolFlag(bool);
 
 impl From<BoolFlag> for bool {
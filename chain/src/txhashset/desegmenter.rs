@@ -25,12 +25,13 @@ use crate::core::core::{
 use crate::error::Error;
 use crate::txhashset;
 use crate::txhashset::{BitmapAccumulator, BitmapChunk, TxHashSet};
-use crate::types::Tip;
-use crate::util::secp::pedersen::RangeProof;
-use crate::util::{RwLock, StopState};
-use crate::{pibd_params, store};
+use crate::types::{PibdProgress, SegmentsProgress, Tip};
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use crate::util::{Mutex, RwLock, StopState};
+use crate::{pibd_params, store, validation_pool};
 use crate::{Chain, SyncState, SyncStatus};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::pibd_params::PibdParams;
@@ -42,6 +43,18 @@ use mwc_util::secp::{constants, Secp256k1};
 use tokio::runtime::Builder;
 use tokio::task;
 
+/// Max number of the outputs or rangeproofs that wait for their pair. Above it the
+/// desegmenter stops the pairing, the rangeproofs are verified with the complete state.
+const PENDING_RANGEPROOFS_LIMIT: usize = 200_000;
+
+/// Output commitments and rangeproofs of the applied segments that wait for the pair,
+/// keyed by the MMR position.
+#[derive(Default)]
+struct PendingRangeproofs {
+	commits: BTreeMap<u64, Commitment>,
+	proofs: BTreeMap<u64, RangeProof>,
+}
+
 /// Desegmenter for rebuilding a txhashset from PIBD segments
 /// Note!!! header_pmmr, txhashset & store are from the Chain. Same locking rules are applicable
 pub struct Desegmenter {
@@ -63,7 +76,13 @@ pub struct Desegmenter {
 	rangeproof_segment_cache: RwLock<Option<SegmentsCache<RangeProof>>>,
 	kernel_segment_cache: RwLock<Option<SegmentsCache<TxKernel>>>,
 
+	pending_rangeproofs: Mutex<PendingRangeproofs>,
+	/// Some rangeproofs of the state were not verified with the segments (resumed
+	/// download or the pending limit)
+	rangeproofs_unverified: AtomicBool,
+
 	pibd_params: Arc<PibdParams>,
+	secp: Secp256k1,
 }
 
 impl Desegmenter {
@@ -76,6 +95,7 @@ impl Desegmenter {
 		genesis: BlockHeader,
 		store: Arc<store::ChainStore>,
 		pibd_params: Arc<PibdParams>,
+		secp: Secp256k1,
 	) -> Desegmenter {
 		info!(
 			"Creating new desegmenter for bitmap_root_hash {}, height {}",
@@ -83,12 +103,7 @@ impl Desegmenter {
 		);
 
 		let bitmap_mmr_size = Self::calc_bitmap_mmr_size(&archive_header);
-		let bitmap_segments = Self::generate_segments(
-			BitmapChunk::LEN_BYTES,
-			pibd_params::PIBD_MESSAGE_SIZE_LIMIT,
-			bitmap_mmr_size,
-			None,
-		);
+		let bitmap_segments = Self::bitmap_segment_ids(&archive_header);
 
 		Desegmenter {
 			txhashset,
@@ -107,7 +122,10 @@ impl Desegmenter {
 			rangeproof_segment_cache: RwLock::new(None),
 			kernel_segment_cache: RwLock::new(None),
 			outputs_bitmap: RwLock::new(None),
+			pending_rangeproofs: Mutex::new(PendingRangeproofs::default()),
+			rangeproofs_unverified: AtomicBool::new(false),
 			pibd_params,
+			secp,
 		}
 	}
 
@@ -124,6 +142,8 @@ impl Desegmenter {
 		*self.kernel_segment_cache.write() = None;
 		*self.outputs_bitmap.write() = None;
 		self.outputs_bitmap_accumulator.write().reset();
+		*self.pending_rangeproofs.lock() = PendingRangeproofs::default();
+		self.rangeproofs_unverified.store(false, Ordering::Relaxed);
 	}
 
 	/// Return reference to the header used for validation
@@ -220,6 +240,28 @@ impl Desegmenter {
 		}
 	}
 
+	/// Received and required segments of the outputs, rangeproofs and kernels MMRs.
+	/// None for the MMRs that are waiting for the bitmap.
+	pub fn get_segments_progress(
+		&self,
+	) -> (
+		Option<SegmentsProgress>,
+		Option<SegmentsProgress>,
+		Option<SegmentsProgress>,
+	) {
+		fn progress<T>(cache: &Option<SegmentsCache<T>>) -> Option<SegmentsProgress> {
+			cache.as_ref().map(|cache| SegmentsProgress {
+				done: cache.get_received_segments() as u64,
+				total: cache.get_required_segments_num() as u64,
+			})
+		}
+		(
+			progress(&self.output_segment_cache.read()),
+			progress(&self.rangeproof_segment_cache.read()),
+			progress(&self.kernel_segment_cache.read()),
+		)
+	}
+
 	/// Once the PIBD set is downloaded, we need to ensure that the respective leaf sets
 	/// match the bitmap (particularly in the case of outputs being spent after a PIBD catch-up)
 	pub fn check_update_leaf_set_state(&self) -> Result<(), Error> {
@@ -251,6 +293,14 @@ impl Desegmenter {
 			txhashset.roots()?.validate(&self.archive_header)?;
 		}
 
+		// All the rangeproofs were paired with their outputs and verified with the segments
+		let rangeproofs_verified = {
+			let pending = self.pending_rangeproofs.lock();
+			!self.rangeproofs_unverified.load(Ordering::Relaxed)
+				&& pending.commits.is_empty()
+				&& pending.proofs.is_empty()
+		};
+
 		status.update(SyncStatus::ValidatingKernelsHistory);
 
 		// Validate kernel history
@@ -372,10 +422,12 @@ impl Desegmenter {
 					extension.rewind(&self.archive_header, batch, header_extension)?;
 
 					// Validate the extension, generating the utxo_sum and kernel_sum.
-					// Full validation, including rangeproofs and kernel signature verification.
-					let (utxo_sum, kernel_sum) = extension.validate(
+					// Full validation, including kernel signature verification. The rangeproofs
+					// are verified here only if some of them were not verified with the segments.
+					let (utxo_sum, kernel_sum) = extension.validate_with(
 						&self.genesis,
-						false,
+						!rangeproofs_verified,
+						true,
 						Some(status.clone()),
 						&self.archive_header,
 						Some(stop_state.clone()),
@@ -412,6 +464,10 @@ impl Desegmenter {
 
 				// Reset the body tail to the body head after a txhashset write
 				batch.save_body_tail(&tip)?;
+				batch.delete_history_tail()?;
+
+				// State is complete, nothing to resume any more
+				batch.delete_pibd_progress()?;
 			}
 
 			// Rebuild our output_pos index in the db based on fresh UTXO set.
@@ -558,6 +614,34 @@ impl Desegmenter {
 			self.outputs_bitmap_accumulator.read().root()
 		);
 		let bitmap = self.outputs_bitmap_accumulator.read().build_bitmap();
+		self.init_segment_caches(bitmap)
+	}
+
+	/// Outputs bitmap, it is known once all the bitmap segments are applied
+	pub fn get_outputs_bitmap(&self) -> Option<Bitmap> {
+		self.outputs_bitmap.read().clone()
+	}
+
+	/// Bitmap segments that are expected for the archive header
+	pub fn bitmap_segment_ids(archive_header: &BlockHeader) -> Vec<SegmentIdentifier> {
+		Self::generate_segments(
+			BitmapChunk::LEN_BYTES,
+			pibd_params::PIBD_MESSAGE_SIZE_LIMIT,
+			Self::calc_bitmap_mmr_size(archive_header),
+			None,
+		)
+	}
+
+	/// Output, rangeproof and kernel segments that are expected for the archive header
+	/// and its outputs bitmap
+	pub fn data_segment_ids(
+		archive_header: &BlockHeader,
+		bitmap: &Bitmap,
+	) -> (
+		Vec<SegmentIdentifier>,
+		Vec<SegmentIdentifier>,
+		Vec<SegmentIdentifier>,
+	) {
 		let mut bitmap_pairs: Bitmap = Bitmap::new();
 
 		for bit in bitmap.iter() {
@@ -570,24 +654,31 @@ impl Desegmenter {
 			}
 		}
 
-		let rangeproof_segments = Self::generate_segments(
-			constants::SINGLE_BULLET_PROOF_SIZE,
+		let output_segments = Self::generate_segments(
+			constants::PEDERSEN_COMMITMENT_SIZE,
 			pibd_params::PIBD_MESSAGE_SIZE_LIMIT,
-			self.archive_header.output_mmr_size,
+			archive_header.output_mmr_size,
 			Some(&bitmap_pairs),
 		);
-		let output_segments = Self::generate_segments(
-			constants::PEDERSEN_COMMITMENT_SIZE,
+		let rangeproof_segments = Self::generate_segments(
+			constants::SINGLE_BULLET_PROOF_SIZE,
 			pibd_params::PIBD_MESSAGE_SIZE_LIMIT,
-			self.archive_header.output_mmr_size,
+			archive_header.output_mmr_size,
 			Some(&bitmap_pairs),
 		);
 		let kernel_segments = Self::generate_segments(
 			TxKernel::DATA_SIZE,
 			pibd_params::PIBD_MESSAGE_SIZE_LIMIT,
-			self.archive_header.kernel_mmr_size,
+			archive_header.kernel_mmr_size,
 			None,
 		);
+		(output_segments, rangeproof_segments, kernel_segments)
+	}
+
+	// Generate the output, rangeproof and kernel segments for the outputs bitmap
+	fn init_segment_caches(&self, bitmap: Bitmap) -> Result<(), Error> {
+		let (output_segments, rangeproof_segments, kernel_segments) =
+			Self::data_segment_ids(&self.archive_header, &bitmap);
 
 		info!("Bitmap data is arrived. Generating other segments - rangeproof_segments: {}, output_segments: {}, kernel_segments: {}", rangeproof_segments.len(), output_segments.len(), kernel_segments.len());
 
@@ -603,6 +694,135 @@ impl Desegmenter {
 		Ok(())
 	}
 
+	/// Save the download progress, so it can be resumed after restart. Nothing is saved
+	/// until the bitmap is complete, bitmap segments are small and fast to download.
+	pub fn save_progress(&self) -> Result<(), Error> {
+		let outputs_bitmap = self.outputs_bitmap.read();
+		let bitmap = match outputs_bitmap.as_ref() {
+			Some(bitmap) => bitmap,
+			None => return Ok(()),
+		};
+		// Holding the caches locks, so no segments are applied while the sizes are read
+		let output_cache = self.output_segment_cache.read();
+		let rangeproof_cache = self.rangeproof_segment_cache.read();
+		let kernel_cache = self.kernel_segment_cache.read();
+		let (output_cache, rangeproof_cache, kernel_cache) =
+			match (&*output_cache, &*rangeproof_cache, &*kernel_cache) {
+				(Some(o), Some(r), Some(k)) => (o, r, k),
+				_ => return Ok(()),
+			};
+
+		let progress = {
+			let txhashset = self.txhashset.read();
+			PibdProgress {
+				archive_hash: self.archive_header.hash(),
+				bitmap_root_hash: self.bitmap_root_hash.clone(),
+				output_segments: output_cache.get_received_segments() as u64,
+				rangeproof_segments: rangeproof_cache.get_received_segments() as u64,
+				kernel_segments: kernel_cache.get_received_segments() as u64,
+				output_mmr_size: txhashset.output_mmr_size(),
+				rangeproof_mmr_size: txhashset.rangeproof_mmr_size(),
+				kernel_mmr_size: txhashset.kernel_mmr_size(),
+			}
+		};
+
+		let batch = self.store.batch_write()?;
+		batch.save_pibd_progress(&progress, bitmap)?;
+		batch.commit()?;
+		debug!("pibd_desegmenter: saved progress {:?}", progress);
+		Ok(())
+	}
+
+	/// Continue the download from the saved progress. Returns false if the saved progress
+	/// is for another session or the MMR files don't match it, the download must start
+	/// from the scratch then.
+	pub fn resume(&self) -> Result<bool, Error> {
+		let (progress, bitmap) = {
+			let batch = self.store.batch_read()?;
+			let progress = match batch.get_pibd_progress() {
+				Ok(progress) => progress,
+				Err(_) => return Ok(false),
+			};
+			if progress.archive_hash != self.archive_header.hash()
+				|| progress.bitmap_root_hash != self.bitmap_root_hash
+			{
+				info!(
+					"pibd_desegmenter: saved progress is for another PIBD session, {:?}",
+					progress
+				);
+				return Ok(false);
+			}
+			let bitmap = match batch.get_pibd_bitmap() {
+				Ok(bitmap) => bitmap,
+				Err(_) => return Ok(false),
+			};
+			(progress, bitmap)
+		};
+
+		{
+			let txhashset = self.txhashset.read();
+			let sizes = (
+				txhashset.output_mmr_size(),
+				txhashset.rangeproof_mmr_size(),
+				txhashset.kernel_mmr_size(),
+			);
+			if sizes
+				!= (
+					progress.output_mmr_size,
+					progress.rangeproof_mmr_size,
+					progress.kernel_mmr_size,
+				) {
+				warn!(
+					"pibd_desegmenter: MMR sizes {:?} don't match the saved progress {:?}",
+					sizes, progress
+				);
+				return Ok(false);
+			}
+		}
+
+		let bitmap_segments = self.bitmap_segment_cache.read().get_required_segments_num();
+		if !self
+			.bitmap_segment_cache
+			.write()
+			.set_received_segments(bitmap_segments)
+		{
+			return Ok(false);
+		}
+		self.init_segment_caches(bitmap)?;
+
+		let resumed = self
+			.output_segment_cache
+			.write()
+			.as_mut()
+			.map(|c| c.set_received_segments(progress.output_segments as usize))
+			.unwrap_or(false)
+			&& self
+				.rangeproof_segment_cache
+				.write()
+				.as_mut()
+				.map(|c| c.set_received_segments(progress.rangeproof_segments as usize))
+				.unwrap_or(false)
+			&& self
+				.kernel_segment_cache
+				.write()
+				.as_mut()
+				.map(|c| c.set_received_segments(progress.kernel_segments as usize))
+				.unwrap_or(false);
+		if !resumed {
+			warn!(
+				"pibd_desegmenter: saved progress doesn't match the segments, {:?}",
+				progress
+			);
+			self.reset();
+			return Ok(false);
+		}
+
+		// The proofs of the segments before the restart were not verified
+		self.rangeproofs_unverified.store(true, Ordering::Relaxed);
+		info!("pibd_desegmenter: resumed PIBD download, {:?}", progress);
+		Ok(true)
+	}
+
 	// Calculate and store number of leaves and positions in the bitmap mmr given the number of
 	// outputs specified in the header. Should be called whenever the header changes
 	fn calc_bitmap_mmr_size(archive_header: &BlockHeader) -> u64 {
@@ -678,6 +898,63 @@ impl Desegmenter {
 		Ok(())
 	}
 
+	// Pair the outputs and the rangeproofs of the new segment with the pending ones and
+	// batch verify the pairs on the validation pool. Output and rangeproof segments have
+	// different sizes, so a pair can be completed by any later segment.
+	fn verify_segment_rangeproofs(
+		&self,
+		commits: Vec<(u64, Commitment)>,
+		proofs: Vec<(u64, RangeProof)>,
+	) -> Result<(), Error> {
+		if self.rangeproofs_unverified.load(Ordering::Relaxed) {
+			return Ok(());
+		}
+		let (commits, proofs): (Vec<Commitment>, Vec<RangeProof>) = {
+			let mut pending = self.pending_rangeproofs.lock();
+			let mut pairs = vec![];
+			for (pos, commit) in commits {
+				match pending.proofs.remove(&pos) {
+					Some(proof) => pairs.push((commit, proof)),
+					None => {
+						pending.commits.insert(pos, commit);
+					}
+				}
+			}
+			for (pos, proof) in proofs {
+				match pending.commits.remove(&pos) {
+					Some(commit) => pairs.push((commit, proof)),
+					None => {
+						pending.proofs.insert(pos, proof);
+					}
+				}
+			}
+			if pending.commits.len() > PENDING_RANGEPROOFS_LIMIT
+				|| pending.proofs.len() > PENDING_RANGEPROOFS_LIMIT
+			{
+				info!(
+					"pibd_desegmenter: too many unpaired outputs {} and rangeproofs {}, verifying rangeproofs with the complete state",
+					pending.commits.len(),
+					pending.proofs.len()
+				);
+				*pending = PendingRangeproofs::default();
+				self.rangeproofs_unverified.store(true, Ordering::Relaxed);
+			}
+			pairs.into_iter().unzip()
+		};
+		if commits.is_empty() {
+			return Ok(());
+		}
+		validation_pool::verify_rangeproofs(&commits, &proofs, &self.secp).map_err(|e| {
+			error!(
+				"pibd_desegmenter: rangeproofs verification failed for {} outputs, {}",
+				commits.len(),
+				e
+			);
+			self.rangeproofs_unverified.store(true, Ordering::Relaxed);
+			e
+		})
+	}
+
 	/// Adds a output segment
 	pub fn add_output_segment(
 		&self,
@@ -700,6 +977,13 @@ impl Desegmenter {
 					Some(outputs_bitmap),
 					&self.archive_header.output_root, // Output root we're checking for
 				)?;
+				self.verify_segment_rangeproofs(
+					segment
+						.leaf_iter()
+						.map(|(pos, o)| (pos, o.commit))
+						.collect(),
+					vec![],
+				)?;
 
 				let mut header_pmmr = self.header_pmmr.write();
 				let mut txhashset = self.txhashset.write();
@@ -753,6 +1037,13 @@ impl Desegmenter {
 					Some(outputs_bitmap),
 					&self.archive_header.range_proof_root, // Range proof root we're checking for
 				)?;
+				self.verify_segment_rangeproofs(
+					vec![],
+					segment
+						.leaf_iter()
+						.map(|(pos, p)| (pos, p.clone()))
+						.collect(),
+				)?;
 
 				let mut header_pmmr = self.header_pmmr.write();
 				let mut txhashset = self.txhashset.write();
@@ -15,6 +15,7 @@
 //! Manages the reconsitution of a headers from segments produced by the
 //! segmenter
 
+use crate::checkpoints;
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::pmmr;
 use crate::core::core::{BlockHeader, Segment};
@@ -458,8 +459,34 @@ impl<T> HeadersRecieveCache<T> {
 
 		let mut tip_height = tip.height;
 
+		// Headers below the next checkpoint are committed by it (no PoW verification) only
+		// if they are applied in the same chunk with it. If the checkpoint is close enough
+		// for the cache, let's wait until the cache has all of them.
+		let span_end = checkpoints::checkpoint_span_end(
+			tip.height,
+			self.chain.get_pibd_params().get_headers_buffer_len() as u64 * HEADERS_PER_BATCH as u64,
+		)
+		.filter(|height| *height <= self.archive_header_height);
+
 		{
 			let mut main_headers_cache = self.main_headers_cache.write();
+			if let Some(span_end) = span_end {
+				let mut run_end = tip.height;
+				for (height, (headers, _)) in main_headers_cache.iter() {
+					let ending_height = headers.last().expect("headers can't empty").height;
+					if ending_height <= run_end {
+						continue;
+					}
+					if *height > run_end + 1 {
+						break;
+					}
+					run_end = ending_height;
+				}
+				if run_end < span_end {
+					return Ok(false);
+				}
+			}
+
 			while let Some((height, (headers, _))) = main_headers_cache.first_key_value() {
 				debug_assert!(!headers.is_empty());
 				debug_assert!(headers.len() == HEADERS_PER_BATCH as usize);
@@ -479,8 +506,9 @@ impl<T> HeadersRecieveCache<T> {
 				headers_by_peer.push((bhs.clone(), peer));
 				headers_all.append(&mut bhs);
 
-				if headers_all.len() > 2000 {
-					break; //  we don't want add too much at a single session.
+				// we don't want add too much at a single session, but the checkpoint span goes together
+				if headers_all.len() > 2000 && span_end.map(|h| tip_height >= h).unwrap_or(true) {
+					break;
 				}
 			}
 		}
@@ -488,7 +516,7 @@ impl<T> HeadersRecieveCache<T> {
 		if !headers_all.is_empty() {
 			match self
 				.chain
-				.sync_block_headers(&headers_all, tip, Options::NONE)
+				.sync_block_headers(&headers_all, tip, Options::CHECKPOINTS)
 			{
 				Ok(_) => {}
 				Err(e) => {
@@ -503,7 +531,10 @@ impl<T> HeadersRecieveCache<T> {
 							.header_head()
 							.expect("Header head must be always defined");
 
-						match self.chain.sync_block_headers(&hdr, tip, Options::NONE) {
+						match self
+							.chain
+							.sync_block_headers(&hdr, tip, Options::CHECKPOINTS)
+						{
 							Ok(_) => {}
 							Err(e) => return Err((peer, e)),
 						}
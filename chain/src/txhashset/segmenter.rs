@@ -63,6 +63,11 @@ impl Segmenter {
 		&self.header
 	}
 
+	/// Outputs bitmap at the header
+	pub fn bitmap(&self) -> &Bitmap {
+		&self.bitmap
+	}
+
 	/// Root hash for headers Hashes MMR
 	pub fn headers_root(&self) -> Result<Hash, Error> {
 		let header_pmmr = self.header_pmmr.read();
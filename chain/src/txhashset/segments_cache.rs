@@ -65,6 +65,17 @@ impl<T> SegmentsCache<T> {
 		self.received_segments = 0;
 	}
 
+	/// Continue from the segments that are already applied. Used to resume the download
+	/// after restart. Returns false if the number doesn't fit the required segments.
+	pub fn set_received_segments(&mut self, received_segments: usize) -> bool {
+		if received_segments > self.required_segments.len() {
+			return false;
+		}
+		self.segment_cache.clear();
+		self.received_segments = received_segments;
+		true
+	}
+
 	/// Check if the requests are completed
 	pub fn is_complete(&self) -> bool {
 		self.received_segments >= self.required_segments.len()
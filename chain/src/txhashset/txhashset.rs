@@ -475,6 +475,44 @@ impl TxHashSet {
 			.map_err(|e| Error::MerkleProof(format!("Commit {:?}, pos {}, {}", commit, pos0, e)))
 	}
 
+	/// Build a merkle proof for the unspent output, valid for the current output MMR.
+	/// None if the output is spent or doesn't exist.
+	pub fn output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<Option<(CommitPos, MerkleProof)>, Error> {
+		let (_, pos) = match self.get_unspent(commit)? {
+			Some(unspent) => unspent,
+			None => return Ok(None),
+		};
+		let output_pmmr: ReadonlyPMMR<'_, OutputIdentifier, _> =
+			ReadonlyPMMR::at(&self.output_pmmr_h.backend, self.output_pmmr_h.size);
+		let proof = output_pmmr.merkle_proof(pos.pos - 1).map_err(|e| {
+			Error::MerkleProof(format!("Commit {:?}, pos {}, {}", commit, pos.pos, e))
+		})?;
+		Ok(Some((pos, proof)))
+	}
+
+	/// Build a merkle proof and the MMR peaks for the kernel at the given (1-based) MMR index,
+	/// valid for the kernel root of the header
+	pub fn kernel_merkle_proof(
+		&self,
+		kernel_mmr_index: u64,
+		header: &BlockHeader,
+	) -> Result<(MerkleProof, Vec<Hash>), Error> {
+		if kernel_mmr_index == 0 || kernel_mmr_index > header.kernel_mmr_size {
+			return Err(Error::MerkleProof(format!(
+				"Kernel index {} is out of the kernel MMR size {} at height {}",
+				kernel_mmr_index, header.kernel_mmr_size, header.height
+			)));
+		}
+		let pmmr = self.kernel_pmmr_at(header);
+		let proof = pmmr
+			.merkle_proof(kernel_mmr_index - 1)
+			.map_err(|e| Error::MerkleProof(format!("Kernel index {}, {}", kernel_mmr_index, e)))?;
+		Ok((proof, pmmr.peaks()))
+	}
+
 	/// Compact the MMR data files and flush the rm logs
 	pub fn compact(
 		&mut self,
@@ -738,6 +776,51 @@ where
 	res
 }
 
+/// Full validation of the txhashset that was extracted at the header into a sandbox,
+/// including rangeproofs and kernel signatures. The txhashset isn't shared with the chain
+/// yet, so it is validated without any chain locks. The MMRs are truncated at the header
+/// sizes, that is kept only if the validation passes.
+/// Returns the utxo_sum and kernel_sum of the header.
+pub fn validate_sandbox(
+	trees: &mut TxHashSet,
+	genesis: &BlockHeader,
+	header: &BlockHeader,
+	status: Option<Arc<SyncState>>,
+	stop_state: Option<Arc<StopState>>,
+	secp: &Secp256k1,
+) -> Result<(Commitment, Commitment), Error> {
+	trace!("Starting txhashset sandbox validation.");
+
+	let sizes: (u64, u64, u64);
+	let res = {
+		let mut extension = Extension::new(trees, Tip::from_header(header));
+		let res = extension
+			.rewind_mmrs_to_pos(header.output_mmr_size, header.kernel_mmr_size, &[])
+			.and_then(|_| extension.validate(genesis, false, status, header, stop_state, secp));
+		sizes = extension.sizes();
+		res
+	};
+
+	match res {
+		Err(e) => {
+			debug!("Sandbox txhashset validation failed: {}", e);
+			trees.output_pmmr_h.backend.discard();
+			trees.rproof_pmmr_h.backend.discard();
+			trees.kernel_pmmr_h.backend.discard();
+			Err(e)
+		}
+		Ok(sums) => {
+			trees.output_pmmr_h.backend.sync()?;
+			trees.rproof_pmmr_h.backend.sync()?;
+			trees.kernel_pmmr_h.backend.sync()?;
+			trees.output_pmmr_h.size = sizes.0;
+			trees.rproof_pmmr_h.size = sizes.1;
+			trees.kernel_pmmr_h.size = sizes.2;
+			Ok(sums)
+		}
+	}
+}
+
 /// Readonly view on the UTXO set.
 /// Based on the current txhashset output_pmmr.
 pub fn utxo_view<F, T>(
@@ -1022,6 +1105,36 @@ impl<'a> HeaderExtension<'a> {
 		Ok(chain_header.hash() == t.hash())
 	}
 
+	/// Validate the output bitmap (the unspent leaves of the output MMR) against
+	/// the output_pos index. Every unspent output must be indexed at its MMR position.
+	pub fn validate_output_bitmap(&self, batch: &Batch<'_>) -> Result<(), Error> {
+		let now = Instant::now();
+		let mut outputs = 0;
+		for pos0 in self.output_pmmr.leaf_pos_iter() {
+			let out = self.output_pmmr.get_data(pos0).ok_or_else(|| {
+				Error::InvalidTxHashSet(format!("Unspent output at {} is missing", pos0))
+			})?;
+			match batch.get_output_pos(&out.commitment()) {
+				Ok(pos) if pos == pos0 => {}
+				_ => {
+					return Err(Error::InvalidTxHashSet(format!(
+						"Unspent output {:?} at {} doesn't match the output_pos index",
+						out.commitment(),
+						pos0
+					)))
+				}
+			}
+			outputs += 1;
+		}
+
+		debug!(
+			"txhashset: validated output bitmap of {} outputs, took {}s",
+			outputs,
+			now.elapsed().as_secs(),
+		);
+		Ok(())
+	}
+
 	/// Force the rollback of this extension, no matter the result.
 	pub fn force_rollback(&mut self) {
 		self.rollback = true;
@@ -1795,6 +1908,29 @@ impl<'a> Extension<'a> {
 		header: &BlockHeader,
 		stop_state: Option<Arc<StopState>>,
 		secp: &Secp256k1,
+	) -> Result<(Commitment, Commitment), Error> {
+		self.validate_with(
+			genesis,
+			!fast_validation,
+			!fast_validation,
+			status,
+			header,
+			stop_state,
+			secp,
+		)
+	}
+
+	/// Validate the txhashset state against the provided block header, the expensive
+	/// rangeproof and kernel signature verifications are selected separately.
+	pub fn validate_with(
+		&self,
+		genesis: &BlockHeader,
+		verify_rangeproofs: bool,
+		verify_kernel_signatures: bool,
+		status: Option<Arc<SyncState>>,
+		header: &BlockHeader,
+		stop_state: Option<Arc<StopState>>,
+		secp: &Secp256k1,
 	) -> Result<(Commitment, Commitment), Error> {
 		self.validate_mmrs()?;
 		self.validate_roots(header)?;
@@ -1810,7 +1946,7 @@ impl<'a> Extension<'a> {
 		let (output_sum, kernel_sum) = self.validate_kernel_sums(genesis, header, secp)?;
 
 		// These are expensive verification step (skipped for "fast validation").
-		if !fast_validation {
+		if verify_rangeproofs {
 			// Verify the rangeproof associated with each unspent output.
 			self.verify_rangeproofs(status.clone(), None, stop_state.clone(), secp)?;
 			if let Some(ref s) = stop_state {
@@ -1818,7 +1954,8 @@ impl<'a> Extension<'a> {
 					return Err(Error::Stopped.into());
 				}
 			}
-
+		}
+		if verify_kernel_signatures {
 			// Verify all the kernel signatures.
 			self.verify_kernel_signatures(status, stop_state.clone(), secp)?;
 			if let Some(ref s) = stop_state {
@@ -2149,59 +2286,89 @@ impl<'a> Extension<'a> {
 /// Packages the txhashset data files into a zip and returns a Read to the
 /// resulting file
 pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
-	let txhashset_zip = format!("{}_{}.zip", TXHASHSET_ZIP, header.hash().to_string());
+	if let Some(zip) = zip_cached(&root_dir, header) {
+		return Ok(zip);
+	}
+	let snapshot_path = zip_snapshot(&root_dir, header)?;
+	zip_from_snapshot(&root_dir, header, snapshot_path)
+}
 
-	let txhashset_path = Path::new(&root_dir).join(TXHASHSET_SUBDIR);
-	let zip_path = Path::new(&root_dir).join(txhashset_zip);
+fn zip_path(root_dir: &str, header: &BlockHeader) -> PathBuf {
+	let txhashset_zip = format!("{}_{}.zip", TXHASHSET_ZIP, header.hash().to_string());
+	Path::new(root_dir).join(txhashset_zip)
+}
 
-	// if file exist, just re-use it
-	let zip_file = File::open(zip_path.clone());
-	if let Ok(zip) = zip_file {
-		debug!(
-			"zip_read: {} at {}: reusing existing zip file: {:?}",
-			header.hash(),
-			header.height,
-			zip_path
-		);
-		return Ok(zip);
-	} else {
-		// clean up old zips.
-		// Theoretically, we only need clean-up those zip files older than STATE_SYNC_THRESHOLD.
-		// But practically, these zip files are not small ones, we just keep the zips in last 24 hours
-		let data_dir = Path::new(&root_dir);
-		let pattern = format!("{}_", TXHASHSET_ZIP);
-		if let Ok(n) = clean_files_by_prefix(data_dir, &pattern, 24 * 60 * 60) {
+/// Already built txhashset zip for the header, if there is one. Zip files are
+/// complete when they appear, so it is safe to read them without any locks.
+pub fn zip_cached(root_dir: &str, header: &BlockHeader) -> Option<File> {
+	let zip_path = zip_path(root_dir, header);
+	match File::open(zip_path.clone()) {
+		Ok(zip) => {
 			debug!(
-				"{} zip files have been clean up in folder: {:?}",
-				n, data_dir
+				"zip_read: {} at {}: reusing existing zip file: {:?}",
+				header.hash(),
+				header.height,
+				zip_path
 			);
+			Some(zip)
 		}
+		Err(_) => None,
 	}
+}
 
-	// otherwise, create the zip archive
-	let path_to_be_cleanup = {
-		// Temp txhashset directory
-		let temp_txhashset_path = Path::new(&root_dir).join(format!(
-			"{}_zip_{}",
-			TXHASHSET_SUBDIR,
-			header.hash().to_string()
-		));
-		// Remove temp dir if it exist
-		if temp_txhashset_path.exists() {
-			fs::remove_dir_all(&temp_txhashset_path)?;
-		}
-		// Copy file to another dir
-		file::copy_dir_to(&txhashset_path, &temp_txhashset_path)?;
+/// Copy the txhashset data files into a temp directory. Txhashset must be rewound
+/// to the header (and leaf set snapshot taken) while we are copying, so the caller
+/// is expected to hold the txhashset lock. The zip can be built from the copy later,
+/// without the lock.
+pub fn zip_snapshot(root_dir: &str, header: &BlockHeader) -> Result<PathBuf, Error> {
+	// clean up old zips.
+	// Theoretically, we only need clean-up those zip files older than STATE_SYNC_THRESHOLD.
+	// But practically, these zip files are not small ones, we just keep the zips in last 24 hours
+	let data_dir = Path::new(root_dir);
+	let pattern = format!("{}_", TXHASHSET_ZIP);
+	if let Ok(n) = clean_files_by_prefix(data_dir, &pattern, 24 * 60 * 60) {
+		debug!(
+			"{} zip files have been clean up in folder: {:?}",
+			n, data_dir
+		);
+	}
+
+	let txhashset_path = Path::new(root_dir).join(TXHASHSET_SUBDIR);
+	// Temp txhashset directory
+	let temp_txhashset_path = Path::new(root_dir).join(format!(
+		"{}_zip_{}",
+		TXHASHSET_SUBDIR,
+		header.hash().to_string()
+	));
+	// Remove temp dir if it exist
+	if temp_txhashset_path.exists() {
+		fs::remove_dir_all(&temp_txhashset_path)?;
+	}
+	// Copy file to another dir
+	file::copy_dir_to(&txhashset_path, &temp_txhashset_path)?;
+	Ok(temp_txhashset_path)
+}
 
-		let zip_file = File::create(zip_path.clone())?;
+/// Build the txhashset zip from the snapshot made by zip_snapshot. The snapshot
+/// directory is removed when done.
+pub fn zip_from_snapshot(
+	root_dir: &str,
+	header: &BlockHeader,
+	snapshot_path: PathBuf,
+) -> Result<File, Error> {
+	let zip_path = zip_path(root_dir, header);
+	// Zip is written under the temp name and renamed when complete, so zip_cached
+	// never returns a partially written file.
+	let temp_zip_path = zip_path.with_extension("zip.tmp");
+	{
+		let zip_file = File::create(temp_zip_path.clone())?;
 
 		// Explicit list of files to add to our zip archive.
 		let files = file_list(header);
 
-		zip::create_zip(&zip_file, &temp_txhashset_path, files)?;
-
-		temp_txhashset_path
-	};
+		zip::create_zip(&zip_file, &snapshot_path, files)?;
+	}
+	fs::rename(&temp_zip_path, &zip_path)?;
 
 	debug!(
 		"zip_read: {} at {}: created zip file: {:?}",
@@ -2214,7 +2381,7 @@ pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
 	let zip_file = File::open(zip_path.clone())?;
 
 	// clean-up temp txhashset directory.
-	if let Err(e) = fs::remove_dir_all(&path_to_be_cleanup) {
+	if let Err(e) = fs::remove_dir_all(&snapshot_path) {
 		warn!(
 			"txhashset zip file: {:?} fail to remove, err: {}",
 			zip_path.to_str(),
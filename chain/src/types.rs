@@ -23,6 +23,8 @@ use crate::core::pow::Difficulty;
 use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
 use crate::error::Error;
 use crate::util::{RwLock, RwLockWriteGuard};
+use std::collections::VecDeque;
+use std::mem;
 
 bitflags! {
 /// Options for block validation
@@ -35,6 +37,8 @@ bitflags! {
 		const SYNC = 0b0000_0010;
 		/// Block validation on a block we mined ourselves
 		const MINE = 0b0000_0100;
+		/// Checkpoint headers and their ancestors skip the PoW verification (initial headers sync).
+		const CHECKPOINTS = 0b0000_1000;
 	}
 }
 
@@ -74,6 +78,9 @@ pub enum SyncStatus {
 		/// total number of segments required
 		total_segments: usize,
 	},
+	/// Downloading the txhashset zip archive from a single peer.
+	/// Fallback for the PIBD when there are not enough PIBD peers.
+	TxHashsetDownload(TxHashsetDownloadStats),
 	/// Validating kernels history
 	ValidatingKernelsHistory,
 	/// Setting up before validation
@@ -147,9 +154,223 @@ impl Default for TxHashsetDownloadStats {
 	}
 }
 
+/// Sync phase reported by the sync progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SyncPhase {
+	/// Waiting for peers or the sync start
+	Waiting,
+	/// Downloading header hashes
+	HeaderHashes,
+	/// Downloading headers
+	Headers,
+	/// Downloading the txhashset state (outputs, rangeproofs, kernels) with PIBD
+	PibdState,
+	/// Downloading the txhashset state as a zip archive from a single peer
+	StateArchive,
+	/// Validating the downloaded txhashset state
+	PibdValidation,
+	/// Downloading blocks above the archive height
+	Bodies,
+	/// Node is synced
+	Synced,
+}
+
+/// Received and required segments of the single PIBD MMR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SegmentsProgress {
+	/// received segments
+	pub done: u64,
+	/// required segments
+	pub total: u64,
+}
+
+/// Sync progress of the current phase with the ETA estimation
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SyncProgress {
+	/// current sync phase
+	pub phase: SyncPhase,
+	/// items of the phase that are done
+	pub done: u64,
+	/// items of the phase in total
+	pub total: u64,
+	/// data received from the peers, bytes per second
+	pub bytes_per_sec: u64,
+	/// estimated seconds to finish the phase, None if not known yet
+	pub eta_secs: Option<u64>,
+	/// PIBD output segments
+	pub pibd_outputs: Option<SegmentsProgress>,
+	/// PIBD rangeproof segments
+	pub pibd_rangeproofs: Option<SegmentsProgress>,
+	/// PIBD kernel segments
+	pub pibd_kernels: Option<SegmentsProgress>,
+	/// sync pipeline stage that is in progress, None if all stages are done
+	#[serde(default)]
+	pub stage: Option<String>,
+}
+
+impl SyncProgress {
+	/// Phase with items done/total for the sync status
+	pub fn phase_items(status: &SyncStatus) -> (SyncPhase, u64, u64) {
+		match *status {
+			SyncStatus::Initial | SyncStatus::AwaitingPeers | SyncStatus::Shutdown => {
+				(SyncPhase::Waiting, 0, 0)
+			}
+			SyncStatus::NoSync => (SyncPhase::Synced, 0, 0),
+			SyncStatus::HeaderHashSync {
+				completed_blocks,
+				total_blocks,
+			} => (
+				SyncPhase::HeaderHashes,
+				completed_blocks as u64,
+				total_blocks as u64,
+			),
+			SyncStatus::HeaderSync {
+				current_height,
+				archive_height,
+			} => (SyncPhase::Headers, current_height, archive_height),
+			SyncStatus::TxHashsetPibd {
+				recieved_segments,
+				total_segments,
+			} => (
+				SyncPhase::PibdState,
+				recieved_segments as u64,
+				total_segments as u64,
+			),
+			SyncStatus::TxHashsetDownload(stats) => (
+				SyncPhase::StateArchive,
+				stats.downloaded_size,
+				stats.total_size,
+			),
+			SyncStatus::ValidatingKernelsHistory => (SyncPhase::PibdValidation, 0, 0),
+			SyncStatus::TxHashsetHeadersValidation {
+				headers,
+				headers_total,
+			} => (SyncPhase::PibdValidation, headers, headers_total),
+			SyncStatus::TxHashsetKernelsPosValidation {
+				kernel_pos,
+				kernel_pos_total,
+			} => (SyncPhase::PibdValidation, kernel_pos, kernel_pos_total),
+			SyncStatus::TxHashsetRangeProofsValidation {
+				rproofs,
+				rproofs_total,
+			} => (SyncPhase::PibdValidation, rproofs, rproofs_total),
+			SyncStatus::TxHashsetKernelsValidation {
+				kernels,
+				kernels_total,
+			} => (SyncPhase::PibdValidation, kernels, kernels_total),
+			SyncStatus::BodySync {
+				archive_height,
+				current_height,
+				highest_height,
+			} => (
+				SyncPhase::Bodies,
+				current_height.saturating_sub(archive_height),
+				highest_height.saturating_sub(archive_height),
+			),
+		}
+	}
+}
+
+impl Default for SyncProgress {
+	fn default() -> Self {
+		SyncProgress {
+			phase: SyncPhase::Waiting,
+			done: 0,
+			total: 0,
+			bytes_per_sec: 0,
+			eta_secs: None,
+			pibd_outputs: None,
+			pibd_rangeproofs: None,
+			pibd_kernels: None,
+			stage: None,
+		}
+	}
+}
+
+/// Competing chain tip that a peer is on
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ForkInfo {
+	/// peer that is on the fork
+	pub peer: String,
+	/// fork tip header hash, hex
+	pub tip_hash: String,
+	/// fork tip height
+	pub tip_height: u64,
+	/// height of the last common header with our chain
+	pub fork_height: u64,
+	/// number of our headers above the fork point
+	pub depth: u64,
+	/// number of the fork headers above the fork point
+	pub length: u64,
+	/// fork tip total difficulty, None if the fork headers are unknown
+	pub tip_difficulty: Option<u64>,
+	/// our header head total difficulty
+	pub head_difficulty: u64,
+	/// last time the fork was reported
+	pub last_seen: DateTime<Utc>,
+}
+
+/// Last reorg of the chain head
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReorgInfo {
+	/// new head height
+	pub height: u64,
+	/// height of the fork point
+	pub fork_height: u64,
+	/// number of the blocks of the previous head above the fork point
+	pub depth: u64,
+	/// time of the reorg
+	pub time: DateTime<Utc>,
+}
+
+/// Result of the offline chain data integrity check
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct IntegrityReport {
+	/// height of the chain head that was checked
+	pub head_height: u64,
+	/// lowest height that the chain state can be rewound to and checked at
+	pub lowest_height: u64,
+	/// first height with the inconsistent chain state, None if the state is valid.
+	/// If it is equal to `lowest_height`, the data is broken at or below it.
+	pub first_bad_height: Option<u64>,
+	/// validation error at the head
+	pub error: Option<String>,
+}
+
+/// Max number of the sync events that are kept
+pub const SYNC_EVENTS_LIMIT: usize = 500;
+
+/// Kind of the sync event
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEventKind {
+	/// Sync status is changed to another stage
+	StatusChanged,
+	/// Peer (or peers group) is selected to sync from
+	PeerSelected,
+	/// Requests are timed out and will be retried
+	RequestsTimedOut,
+}
+
+/// Sync process event, the recent ones are available with the owner API
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SyncEvent {
+	/// event time
+	pub time: DateTime<Utc>,
+	/// event kind
+	pub kind: SyncEventKind,
+	/// peers that the event is about
+	pub peers: Vec<String>,
+	/// event details
+	pub message: String,
+}
+
 /// Current sync state. Encapsulates the current SyncStatus.
 pub struct SyncState {
 	current: RwLock<SyncStatus>,
+	progress: RwLock<SyncProgress>,
+	forks: RwLock<Vec<ForkInfo>>,
+	events: RwLock<VecDeque<SyncEvent>>,
 }
 
 impl SyncState {
@@ -157,6 +378,9 @@ impl SyncState {
 	pub fn new() -> SyncState {
 		SyncState {
 			current: RwLock::new(SyncStatus::Initial),
+			progress: RwLock::new(SyncProgress::default()),
+			forks: RwLock::new(vec![]),
+			events: RwLock::new(VecDeque::new()),
 		}
 	}
 
@@ -200,6 +424,45 @@ impl SyncState {
 		*self.current.read()
 	}
 
+	/// Sync progress of the current phase
+	pub fn progress(&self) -> SyncProgress {
+		self.progress.read().clone()
+	}
+
+	/// Update the sync progress
+	pub fn update_progress(&self, progress: SyncProgress) {
+		*self.progress.write() = progress;
+	}
+
+	/// Forks deeper than the alert depth that the peers are on
+	pub fn forks(&self) -> Vec<ForkInfo> {
+		self.forks.read().clone()
+	}
+
+	/// Update the forks that the peers are on
+	pub fn update_forks(&self, forks: Vec<ForkInfo>) {
+		*self.forks.write() = forks;
+	}
+
+	/// Recent sync events, the oldest first
+	pub fn events(&self) -> Vec<SyncEvent> {
+		self.events.read().iter().cloned().collect()
+	}
+
+	/// Add the sync event, the oldest one is dropped if there are too many
+	pub fn add_event(&self, kind: SyncEventKind, peers: Vec<String>, message: String) {
+		let mut events = self.events.write();
+		if events.len() >= SYNC_EVENTS_LIMIT {
+			events.pop_front();
+		}
+		events.push_back(SyncEvent {
+			time: Utc::now(),
+			kind,
+			peers,
+			message,
+		});
+	}
+
 	/// Update the syncing status
 	pub fn update(&self, new_status: SyncStatus) -> bool {
 		let status = self.current.write();
@@ -216,6 +479,14 @@ impl SyncState {
 		}
 		// Sync status is needed for QT wallet sync tracking. Please keep this message as info
 		info!("mwc-node sync status: {:?}", new_status);
+		// Progress updates of the same stage are not the events
+		if mem::discriminant(&*status) != mem::discriminant(&new_status) {
+			self.add_event(
+				SyncEventKind::StatusChanged,
+				vec![],
+				format!("{:?} -> {:?}", *status, new_status),
+			);
+		}
 		*status = new_status;
 		true
 	}
@@ -235,7 +506,7 @@ impl SyncState {
 }
 
 /// A helper for the various txhashset MMR roots.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TxHashSetRoots {
 	/// Output roots
 	pub output_root: Hash,
@@ -322,6 +593,58 @@ impl Writeable for CommitPos {
 	}
 }
 
+/// Progress of the PIBD state sync. Segments are applied to the MMR files in order,
+/// so the number of applied segments with the resulting MMR sizes is enough to resume
+/// the download after restart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PibdProgress {
+	/// Archive header the state is downloaded for
+	pub archive_hash: Hash,
+	/// Bitmap root hash that the peers committed to
+	pub bitmap_root_hash: Hash,
+	/// Number of applied output segments
+	pub output_segments: u64,
+	/// Number of applied rangeproof segments
+	pub rangeproof_segments: u64,
+	/// Number of applied kernel segments
+	pub kernel_segments: u64,
+	/// Output MMR size after the applied segments
+	pub output_mmr_size: u64,
+	/// Rangeproof MMR size after the applied segments
+	pub rangeproof_mmr_size: u64,
+	/// Kernel MMR size after the applied segments
+	pub kernel_mmr_size: u64,
+}
+
+impl Readable for PibdProgress {
+	fn read<R: Reader>(reader: &mut R) -> Result<PibdProgress, ser::Error> {
+		Ok(PibdProgress {
+			archive_hash: Hash::read(reader)?,
+			bitmap_root_hash: Hash::read(reader)?,
+			output_segments: reader.read_u64()?,
+			rangeproof_segments: reader.read_u64()?,
+			kernel_segments: reader.read_u64()?,
+			output_mmr_size: reader.read_u64()?,
+			rangeproof_mmr_size: reader.read_u64()?,
+			kernel_mmr_size: reader.read_u64()?,
+		})
+	}
+}
+
+impl Writeable for PibdProgress {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.archive_hash.write(writer)?;
+		self.bitmap_root_hash.write(writer)?;
+		writer.write_u64(self.output_segments)?;
+		writer.write_u64(self.rangeproof_segments)?;
+		writer.write_u64(self.kernel_segments)?;
+		writer.write_u64(self.output_mmr_size)?;
+		writer.write_u64(self.rangeproof_mmr_size)?;
+		writer.write_u64(self.kernel_mmr_size)?;
+		Ok(())
+	}
+}
+
 /// Minimal struct representing a block header hash and height
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct HashHeight {
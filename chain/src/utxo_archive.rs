@@ -0,0 +1,163 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Portable UTXO archive. It has the same data that PIBD downloads from the peers:
+//! the headers and the bitmap, output, rangeproof and kernel segments at the
+//! txhashset archive header, so a new node can be provisioned from the file instead
+//! of the network. The import validates everything exactly like PIBD does, the archive
+//! doesn't need to be trusted, only its header hash that the import requires.
+//!
+//! All the integers are big endian, the items are in the node binary serialization
+//! of the protocol version from the prelude:
+//! ```text
+//! magic            8 bytes  "MWCUTXOA"
+//! format version   u32      1
+//! protocol version u32      serialization version of the items below
+//! archive header   BlockHeader
+//! bitmap root      Hash
+//! headers          u64 count, BlockHeader * count (heights 1..=archive height)
+//! bitmap           u64 count, Segment<BitmapChunk> * count
+//! outputs          u64 count, Segment<OutputIdentifier> * count
+//! rangeproofs      u64 count, Segment<RangeProof> * count
+//! kernels          u64 count, Segment<TxKernel> * count
+//! ```
+//! The segments are the ones that `Desegmenter` expects for the archive header, in the
+//! segment id order.
+
+use crate::core::core::hash::Hash;
+use crate::core::core::BlockHeader;
+use crate::core::ser::{self, DeserializationMode, ProtocolVersion, Readable, Writeable};
+use crate::error::Error;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// UTXO archive file magic
+pub const ARCHIVE_MAGIC: [u8; 8] = *b"MWCUTXOA";
+/// UTXO archive format version
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// UTXO archive that was written or imported by the node
+#[derive(Debug, Clone)]
+pub struct UtxoArchiveInfo {
+	/// Archive file
+	pub path: PathBuf,
+	/// Height of the archive header
+	pub height: u64,
+	/// Hash of the archive header
+	pub hash: Hash,
+	/// Number of the headers
+	pub headers: u64,
+	/// Number of the bitmap, output, rangeproof and kernel segments
+	pub segments: u64,
+}
+
+/// Beginning of the archive, before the headers
+pub struct ArchivePrelude {
+	/// Serialization version of the archive items
+	pub version: ProtocolVersion,
+	/// Header the txhashset is validated against
+	pub header: BlockHeader,
+	/// Root of the outputs bitmap at the archive header
+	pub bitmap_root: Hash,
+}
+
+fn archive_err(msg: String) -> Error {
+	Error::Other(format!("Invalid UTXO archive, {}", msg))
+}
+
+/// Write the archive prelude
+pub fn write_prelude(
+	sink: &mut dyn Write,
+	version: ProtocolVersion,
+	header: &BlockHeader,
+	bitmap_root: &Hash,
+) -> Result<(), Error> {
+	sink.write_all(&ARCHIVE_MAGIC)?;
+	write_item(sink, version, &ARCHIVE_VERSION)?;
+	write_item(sink, version, &version.value())?;
+	write_item(sink, version, header)?;
+	write_item(sink, version, bitmap_root)?;
+	Ok(())
+}
+
+/// Read and check the archive prelude
+pub fn read_prelude<R: Read>(source: &mut R) -> Result<ArchivePrelude, Error> {
+	let mut magic = [0u8; 8];
+	source.read_exact(&mut magic)?;
+	if magic != ARCHIVE_MAGIC {
+		return Err(archive_err("unknown file format".to_string()));
+	}
+	// format version and protocol version are always in the version 1 layout
+	let format_version: u32 = read_item(source, ProtocolVersion(1))?;
+	if format_version != ARCHIVE_VERSION {
+		return Err(archive_err(format!(
+			"unsupported format version {}",
+			format_version
+		)));
+	}
+	let version: u32 = read_item(source, ProtocolVersion(1))?;
+	let version = ProtocolVersion(version);
+	if version > ProtocolVersion::local() {
+		return Err(archive_err(format!(
+			"protocol version {} is newer than ours",
+			version
+		)));
+	}
+	let header = read_item(source, version)?;
+	let bitmap_root = read_item(source, version)?;
+	Ok(ArchivePrelude {
+		version,
+		header,
+		bitmap_root,
+	})
+}
+
+/// Write a single item (count, header or segment) of the archive
+pub fn write_item<T: Writeable>(
+	sink: &mut dyn Write,
+	version: ProtocolVersion,
+	item: &T,
+) -> Result<(), Error> {
+	ser::serialize(sink, version, item)?;
+	Ok(())
+}
+
+/// Read a single item (count, header or segment) of the archive
+pub fn read_item<T: Readable, R: Read>(
+	source: &mut R,
+	version: ProtocolVersion,
+) -> Result<T, Error> {
+	Ok(ser::deserialize(
+		source,
+		version,
+		DeserializationMode::default(),
+	)?)
+}
+
+/// Read the count of the next archive section and check it against the expected one
+pub fn read_count<R: Read>(
+	source: &mut R,
+	version: ProtocolVersion,
+	section: &str,
+	expected: u64,
+) -> Result<u64, Error> {
+	let count: u64 = read_item(source, version)?;
+	if count != expected {
+		return Err(archive_err(format!(
+			"{} {} are expected, found {}",
+			expected, section, count
+		)));
+	}
+	Ok(count)
+}
@@ -0,0 +1,298 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Canonical dump of the UTXO set, for the cross checks between independent node
+//! implementations and between archive nodes after upgrades.
+//!
+//! The format is a plain text, so any implementation can produce it:
+//! ```text
+//! mwc-utxo-dump 1
+//! height <height>
+//! hash <block hash hex>
+//! output_root <hex> <mmr size>
+//! rangeproof_root <hex> <mmr size>
+//! kernel_root <hex> <mmr size>
+//! utxos <count>
+//! <commitment hex> <plain|coinbase> <height>
+//! ...
+//! ```
+//! UTXO lines are sorted by the commitment.
+
+use crate::core::core::hash::Hash;
+use crate::core::core::OutputFeatures;
+use crate::error::Error;
+use crate::types::TxHashSetRoots;
+use crate::util::secp::constants::PEDERSEN_COMMITMENT_SIZE;
+use crate::util::secp::pedersen::Commitment;
+use crate::util::{self, ToHex};
+use std::cmp::Ordering;
+use std::io::{BufRead, Write};
+
+const DUMP_MAGIC: &str = "mwc-utxo-dump";
+const DUMP_VERSION: u32 = 1;
+
+/// Single unspent output of the dump
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoEntry {
+	/// Output commitment
+	pub commit: Commitment,
+	/// Output features
+	pub features: OutputFeatures,
+	/// Height of the block that created the output
+	pub height: u64,
+}
+
+/// UTXO set with the roots at the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoDump {
+	/// Block height
+	pub height: u64,
+	/// Block hash
+	pub hash: Hash,
+	/// MMR roots and sizes at the block
+	pub roots: TxHashSetRoots,
+	/// Unspent outputs, sorted by the commitment
+	pub utxos: Vec<UtxoEntry>,
+}
+
+/// Result of the two dumps comparison
+#[derive(Debug, Default)]
+pub struct UtxoDumpDiff {
+	/// Dumps are made for different blocks, roots comparison doesn't make sense
+	pub block_mismatch: bool,
+	/// Names of the roots that don't match
+	pub roots_mismatch: Vec<String>,
+	/// Outputs that exist in the first dump only
+	pub only_first: Vec<UtxoEntry>,
+	/// Outputs that exist in the second dump only
+	pub only_second: Vec<UtxoEntry>,
+	/// Outputs with the same commitment but different features or height
+	pub mismatch: Vec<(UtxoEntry, UtxoEntry)>,
+}
+
+impl UtxoDumpDiff {
+	/// True if the dumps are identical
+	pub fn is_match(&self) -> bool {
+		!self.block_mismatch
+			&& self.roots_mismatch.is_empty()
+			&& self.only_first.is_empty()
+			&& self.only_second.is_empty()
+			&& self.mismatch.is_empty()
+	}
+}
+
+fn features_to_str(features: OutputFeatures) -> &'static str {
+	match features {
+		OutputFeatures::Plain => "plain",
+		OutputFeatures::Coinbase => "coinbase",
+	}
+}
+
+fn parse_err(line: usize, msg: &str) -> Error {
+	Error::Other(format!("Invalid UTXO dump at line {}, {}", line, msg))
+}
+
+impl UtxoDump {
+	/// Build the dump, sorts the outputs
+	pub fn new(height: u64, hash: Hash, roots: TxHashSetRoots, mut utxos: Vec<UtxoEntry>) -> Self {
+		utxos.sort_by(|a, b| a.commit.as_ref().cmp(b.commit.as_ref()));
+		UtxoDump {
+			height,
+			hash,
+			roots,
+			utxos,
+		}
+	}
+
+	/// Write the dump in the canonical text format
+	pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+		let io_err = |e: std::io::Error| Error::Other(format!("Unable to write UTXO dump, {}", e));
+		writeln!(w, "{} {}", DUMP_MAGIC, DUMP_VERSION).map_err(io_err)?;
+		writeln!(w, "height {}", self.height).map_err(io_err)?;
+		writeln!(w, "hash {}", self.hash.to_hex()).map_err(io_err)?;
+		writeln!(
+			w,
+			"output_root {} {}",
+			self.roots.output_root.to_hex(),
+			self.roots.output_mmr_size
+		)
+		.map_err(io_err)?;
+		writeln!(
+			w,
+			"rangeproof_root {} {}",
+			self.roots.rproof_root.to_hex(),
+			self.roots.rproof_mmr_size
+		)
+		.map_err(io_err)?;
+		writeln!(
+			w,
+			"kernel_root {} {}",
+			self.roots.kernel_root.to_hex(),
+			self.roots.kernel_mmr_size
+		)
+		.map_err(io_err)?;
+		writeln!(w, "utxos {}", self.utxos.len()).map_err(io_err)?;
+		for utxo in &self.utxos {
+			writeln!(
+				w,
+				"{} {} {}",
+				utxo.commit.as_ref().to_hex(),
+				features_to_str(utxo.features),
+				utxo.height
+			)
+			.map_err(io_err)?;
+		}
+		Ok(())
+	}
+
+	/// Read the dump in the canonical text format
+	pub fn read<R: BufRead>(r: R) -> Result<UtxoDump, Error> {
+		let mut lines = r.lines().enumerate().map(|(i, l)| {
+			l.map(|l| (i + 1, l))
+				.map_err(|e| Error::Other(format!("Unable to read UTXO dump, {}", e)))
+		});
+		let mut next_fields = |name: &str, count: usize| -> Result<Vec<String>, Error> {
+			let (n, line) = lines
+				.next()
+				.ok_or_else(|| parse_err(0, &format!("missing '{}'", name)))??;
+			let fields: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+			if fields.len() != count + 1 || fields[0] != name {
+				return Err(parse_err(n, &format!("expected '{}'", name)));
+			}
+			Ok(fields[1..].to_vec())
+		};
+		let parse_u64 = |s: &str| {
+			s.parse::<u64>()
+				.map_err(|_| parse_err(0, &format!("invalid number {}", s)))
+		};
+		let parse_hash =
+			|s: &str| Hash::from_hex(s).map_err(|_| parse_err(0, &format!("invalid hash {}", s)));
+
+		let version = next_fields(DUMP_MAGIC, 1)?;
+		if version[0] != DUMP_VERSION.to_string() {
+			return Err(parse_err(1, &format!("unsupported version {}", version[0])));
+		}
+		let height = parse_u64(&next_fields("height", 1)?[0])?;
+		let hash = parse_hash(&next_fields("hash", 1)?[0])?;
+		let output = next_fields("output_root", 2)?;
+		let rproof = next_fields("rangeproof_root", 2)?;
+		let kernel = next_fields("kernel_root", 2)?;
+		let roots = TxHashSetRoots {
+			output_root: parse_hash(&output[0])?,
+			output_mmr_size: parse_u64(&output[1])?,
+			rproof_root: parse_hash(&rproof[0])?,
+			rproof_mmr_size: parse_u64(&rproof[1])?,
+			kernel_root: parse_hash(&kernel[0])?,
+			kernel_mmr_size: parse_u64(&kernel[1])?,
+		};
+		let count = parse_u64(&next_fields("utxos", 1)?[0])?;
+
+		let mut utxos: Vec<UtxoEntry> = vec![];
+		for line in lines {
+			let (n, line) = line?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			if fields.len() != 3 {
+				return Err(parse_err(n, "expected '<commitment> <features> <height>'"));
+			}
+			let commit = match util::from_hex(fields[0]) {
+				Ok(c) if c.len() == PEDERSEN_COMMITMENT_SIZE => Commitment::from_vec(c),
+				_ => return Err(parse_err(n, "invalid commitment")),
+			};
+			let features = match fields[1] {
+				"plain" => OutputFeatures::Plain,
+				"coinbase" => OutputFeatures::Coinbase,
+				f => return Err(parse_err(n, &format!("invalid features {}", f))),
+			};
+			let height = fields[2]
+				.parse::<u64>()
+				.map_err(|_| parse_err(n, "invalid height"))?;
+			if let Some(prev) = utxos.last() {
+				if prev.commit.as_ref() >= commit.as_ref() {
+					return Err(parse_err(n, "outputs are not sorted or not unique"));
+				}
+			}
+			utxos.push(UtxoEntry {
+				commit,
+				features,
+				height,
+			});
+		}
+		if utxos.len() as u64 != count {
+			return Err(parse_err(
+				0,
+				&format!("expected {} outputs, found {}", count, utxos.len()),
+			));
+		}
+
+		Ok(UtxoDump {
+			height,
+			hash,
+			roots,
+			utxos,
+		})
+	}
+
+	/// Compare with another dump
+	pub fn compare(&self, other: &UtxoDump) -> UtxoDumpDiff {
+		let mut diff = UtxoDumpDiff::default();
+		if self.height != other.height || self.hash != other.hash {
+			diff.block_mismatch = true;
+		} else {
+			if self.roots.output_root != other.roots.output_root
+				|| self.roots.output_mmr_size != other.roots.output_mmr_size
+			{
+				diff.roots_mismatch.push("output_root".to_string());
+			}
+			if self.roots.rproof_root != other.roots.rproof_root
+				|| self.roots.rproof_mmr_size != other.roots.rproof_mmr_size
+			{
+				diff.roots_mismatch.push("rangeproof_root".to_string());
+			}
+			if self.roots.kernel_root != other.roots.kernel_root
+				|| self.roots.kernel_mmr_size != other.roots.kernel_mmr_size
+			{
+				diff.roots_mismatch.push("kernel_root".to_string());
+			}
+		}
+
+		// Both lists are sorted, merge them
+		let (mut i, mut j) = (0, 0);
+		while i < self.utxos.len() && j < other.utxos.len() {
+			let (a, b) = (&self.utxos[i], &other.utxos[j]);
+			match a.commit.as_ref().cmp(b.commit.as_ref()) {
+				Ordering::Less => {
+					diff.only_first.push(a.clone());
+					i += 1;
+				}
+				Ordering::Greater => {
+					diff.only_second.push(b.clone());
+					j += 1;
+				}
+				Ordering::Equal => {
+					if a != b {
+						diff.mismatch.push((a.clone(), b.clone()));
+					}
+					i += 1;
+					j += 1;
+				}
+			}
+		}
+		diff.only_first.extend_from_slice(&self.utxos[i..]);
+		diff.only_second.extend_from_slice(&other.utxos[j..]);
+		diff
+	}
+}
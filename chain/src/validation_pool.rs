@@ -0,0 +1,162 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Worker pool for the expensive stateless part of the block validation: the rangeproofs
+//! and the kernel signatures. The blocks of a series (and the large blocks) are split
+//! into the chunks that are verified in parallel. The results are collected per block in
+//! the block order, so the rest of the pipeline and the commit stay sequential and the
+//! reported error doesn't depend on the threads timing.
+//! The state sync verifies the rangeproofs of the PIBD segments on the same pool.
+
+use crate::core::core::block;
+use crate::core::core::{Block, Output, TxKernel};
+use crate::error::Error;
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use crate::util::secp::Secp256k1;
+use crate::util::RwLock;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+
+/// Number of outputs in a rangeproofs batch of one job
+const PROOFS_CHUNK_SIZE: usize = 64;
+/// Number of kernels in a signatures batch of one job
+const KERNELS_CHUNK_SIZE: usize = 256;
+/// Number of the state sync rangeproofs in a batch of one job
+const STATE_PROOFS_CHUNK_SIZE: usize = 256;
+
+lazy_static! {
+	// None until init_validation_threads or the first use
+	static ref POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+}
+
+/// Setup the number of the validation threads. None is the number of CPUs,
+/// 1 validates in the calling thread.
+pub fn init_validation_threads(threads: Option<usize>) -> Result<(), Error> {
+	let pool = build_pool(threads)?;
+	info!(
+		"Block validation pool with {} threads",
+		pool.current_num_threads()
+	);
+	*POOL.write() = Some(pool);
+	Ok(())
+}
+
+fn build_pool(threads: Option<usize>) -> Result<Arc<ThreadPool>, Error> {
+	let threads = threads.unwrap_or_else(num_cpus::get).max(1);
+	let pool = ThreadPoolBuilder::new()
+		.num_threads(threads)
+		.thread_name(|i| format!("validation-{}", i))
+		.build()
+		.map_err(|e| Error::Other(format!("Unable to start the validation pool, {}", e)))?;
+	Ok(Arc::new(pool))
+}
+
+fn pool() -> Result<Arc<ThreadPool>, Error> {
+	if let Some(pool) = POOL.read().as_ref() {
+		return Ok(pool.clone());
+	}
+	let mut w = POOL.write();
+	if w.is_none() {
+		*w = Some(build_pool(None)?);
+	}
+	Ok(w.as_ref().unwrap().clone())
+}
+
+enum Job<'a> {
+	Proofs(&'a [Output]),
+	Kernels(&'a [TxKernel]),
+}
+
+impl<'a> Job<'a> {
+	fn run(&self, secp: &Secp256k1) -> Result<(), block::Error> {
+		match self {
+			Job::Proofs(outputs) => Block::verify_proofs(outputs, secp),
+			Job::Kernels(kernels) => Block::verify_kernel_signatures(kernels, secp),
+		}
+	}
+}
+
+/// Verify the rangeproofs and the kernel signatures of the blocks. Returns the result
+/// for every block, in the same order. The rest of the block validation is done by
+/// `Block::validate_without_signatures`.
+pub fn verify_blocks(
+	blocks: &[Block],
+	secp: &Secp256k1,
+) -> Result<Vec<Result<(), block::Error>>, Error> {
+	// jobs in the block order, the rangeproofs first like in Block::validate
+	let mut jobs: Vec<(usize, Job<'_>)> = vec![];
+	for (i, b) in blocks.iter().enumerate() {
+		for chunk in b.outputs().chunks(PROOFS_CHUNK_SIZE) {
+			jobs.push((i, Job::Proofs(chunk)));
+		}
+		for chunk in b.kernels().chunks(KERNELS_CHUNK_SIZE) {
+			jobs.push((i, Job::Kernels(chunk)));
+		}
+	}
+
+	let pool = pool()?;
+	let results: Vec<(usize, Result<(), block::Error>)> =
+		if jobs.len() < 2 || pool.current_num_threads() < 2 {
+			jobs.iter().map(|(i, job)| (*i, job.run(secp))).collect()
+		} else {
+			pool.install(|| {
+				jobs.par_iter()
+					.map(|(i, job)| (*i, job.run(secp)))
+					.collect()
+			})
+		};
+
+	// first failure of every block
+	let mut res: Vec<Result<(), block::Error>> = blocks.iter().map(|_| Ok(())).collect();
+	for (i, r) in results {
+		if let Err(e) = r {
+			if res[i].is_ok() {
+				res[i] = Err(e);
+			}
+		}
+	}
+	Ok(res)
+}
+
+/// Batch verify the rangeproofs of the commitments, the state sync proofs come with
+/// the PIBD segments. `commits` and `proofs` are pairs with the same index.
+pub fn verify_rangeproofs(
+	commits: &[Commitment],
+	proofs: &[RangeProof],
+	secp: &Secp256k1,
+) -> Result<(), Error> {
+	if commits.len() != proofs.len() {
+		return Err(Error::Other(format!(
+			"{} commitments for {} rangeproofs",
+			commits.len(),
+			proofs.len()
+		)));
+	}
+	let chunks: Vec<(&[Commitment], &[RangeProof])> = commits
+		.chunks(STATE_PROOFS_CHUNK_SIZE)
+		.zip(proofs.chunks(STATE_PROOFS_CHUNK_SIZE))
+		.collect();
+
+	let pool = pool()?;
+	let verify = |(commits, proofs): &(&[Commitment], &[RangeProof])| {
+		Output::batch_verify_proofs(commits, proofs, secp)
+	};
+	if chunks.len() < 2 || pool.current_num_threads() < 2 {
+		chunks.iter().try_for_each(verify)?;
+	} else {
+		pool.install(|| chunks.par_iter().try_for_each(verify))?;
+	}
+	Ok(())
+}
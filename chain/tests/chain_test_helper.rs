@@ -82,7 +82,7 @@ pub fn mine_chain(dir_name: &str, chain_length: u64) -> Chain {
 }
 
 #[allow(dead_code)]
-fn mine_some_on_top<K>(chain: &mut Chain, chain_length: u64, keychain: &K)
+pub fn mine_some_on_top<K>(chain: &mut Chain, chain_length: u64, keychain: &K)
 where
 	K: Keychain,
 {
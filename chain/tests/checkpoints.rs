@@ -0,0 +1,83 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use self::core::core::hash::Hashed;
+use self::core::global::ChainTypes;
+use mwc_chain::checkpoints;
+use mwc_core as core;
+use mwc_util as util;
+use mwc_util::ToHex;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+
+#[test]
+fn test_header_checkpoints() {
+	util::init_test_logger();
+
+	let chain_dir = ".mwc_checkpoints";
+	clean_output_dir(chain_dir);
+
+	let chain = mine_chain(chain_dir, 4);
+	let headers: Vec<_> = (1..4)
+		.map(|h| chain.get_header_by_height(h).unwrap())
+		.collect();
+	let header = headers[1].clone();
+	let checkpoint = format!("2:{}", header.hash().to_hex());
+
+	assert!(checkpoints::parse_checkpoint("2").is_err());
+	assert!(checkpoints::parse_checkpoint("a:00").is_err());
+	assert_eq!(
+		checkpoints::parse_checkpoint(&checkpoint).unwrap(),
+		(2, header.hash())
+	);
+
+	checkpoints::init_checkpoints(
+		&ChainTypes::AutomatedTesting,
+		true,
+		&Some(vec![checkpoint.clone()]),
+	)
+	.unwrap();
+	assert_eq!(checkpoints::last_checkpoint_height(), Some(2));
+	// The checkpoint and its ancestors, not the headers above it
+	assert_eq!(checkpoints::checkpointed_headers(&headers), 2);
+	// The checkpoint hash is not seen on the chain of the chunk
+	assert_eq!(checkpoints::checkpointed_headers(&headers[..1]), 0);
+	assert_eq!(checkpoints::checkpointed_headers(&headers[2..]), 0);
+	// A fork below the checkpoint is not committed by it
+	let mut fork = headers[0].clone();
+	fork.timestamp = fork.timestamp + chrono::Duration::seconds(1);
+	assert_eq!(
+		checkpoints::checkpointed_headers(&[fork, headers[1].clone()]),
+		0
+	);
+	assert!(checkpoints::validate_header_checkpoint(&header, &header.hash()).is_ok());
+	// Another header at the checkpoint height is rejected
+	assert!(checkpoints::validate_header_checkpoint(&header, &header.prev_hash).is_err());
+
+	// --no-checkpoints
+	checkpoints::init_checkpoints(
+		&ChainTypes::AutomatedTesting,
+		false,
+		&Some(vec![checkpoint]),
+	)
+	.unwrap();
+	assert_eq!(checkpoints::last_checkpoint_height(), None);
+	assert_eq!(checkpoints::checkpointed_headers(&headers), 0);
+	assert!(checkpoints::validate_header_checkpoint(&header, &header.prev_hash).is_ok());
+
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
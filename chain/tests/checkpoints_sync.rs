@@ -0,0 +1,112 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use self::chain::types::NoopAdapter;
+use self::chain::{checkpoints, Chain, Options};
+use self::core::core::hash::Hashed;
+use self::core::core::BlockHeader;
+use self::core::global::ChainTypes;
+use self::core::pow;
+use self::keychain::{ExtKeychain, Keychain};
+use mwc_chain as chain;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_util as util;
+use mwc_util::ToHex;
+use std::sync::Arc;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain, mine_some_on_top};
+
+// Every PoW verification fails, so the headers are accepted only if the PoW is skipped
+fn no_pow(_: &BlockHeader) -> Result<(), pow::Error> {
+	Err(pow::Error::Verification(
+		"PoW is not expected to be verified".to_string(),
+	))
+}
+
+#[test]
+fn test_checkpoint_sync_without_pow() {
+	util::init_test_logger();
+
+	let src_dir = ".mwc_checkpoints_sync_src";
+	let fork_dir = ".mwc_checkpoints_sync_fork";
+	let dst_dir = ".mwc_checkpoints_sync_dst";
+	clean_output_dir(src_dir);
+	clean_output_dir(fork_dir);
+	clean_output_dir(dst_dir);
+
+	{
+		let src = mine_chain(src_dir, 10);
+		let genesis = src.get_block(&src.genesis().hash()).unwrap();
+		let mut fork = init_chain(fork_dir, genesis.clone());
+		mine_some_on_top(
+			&mut fork,
+			10,
+			&ExtKeychain::from_random_seed(false).unwrap(),
+		);
+
+		let headers: Vec<BlockHeader> = (1..10)
+			.map(|h| src.get_header_by_height(h).unwrap())
+			.collect();
+		let forged: Vec<BlockHeader> = (1..10)
+			.map(|h| fork.get_header_by_height(h).unwrap())
+			.collect();
+		assert_ne!(headers[0].hash(), forged[0].hash());
+
+		let checkpoint = format!("9:{}", headers[8].hash().to_hex());
+		checkpoints::init_checkpoints(&ChainTypes::AutomatedTesting, true, &Some(vec![checkpoint]))
+			.unwrap();
+		// The headers cache waits for the whole span up to the checkpoint if it can hold it
+		assert_eq!(checkpoints::checkpoint_span_end(0, 100), Some(9));
+		assert_eq!(checkpoints::checkpoint_span_end(0, 5), None);
+		assert_eq!(checkpoints::checkpoint_span_end(9, 100), None);
+
+		let dst = Chain::init(
+			dst_dir.to_string(),
+			Arc::new(NoopAdapter {}),
+			genesis,
+			no_pow,
+			false,
+		)
+		.unwrap();
+		let dst_head = dst.header_head().unwrap();
+
+		// Headers below the checkpoint are not committed without it, the PoW is verified
+		assert!(dst
+			.sync_block_headers(&headers[..8], dst_head, Options::CHECKPOINTS)
+			.is_err());
+		// Forged chain doesn't reach the checkpoint
+		assert!(dst
+			.sync_block_headers(&forged, dst_head, Options::CHECKPOINTS)
+			.is_err());
+		// Forged headers are not the ancestors of the checkpoint
+		let mut forged_span = forged[..8].to_vec();
+		forged_span.push(headers[8].clone());
+		assert!(dst
+			.sync_block_headers(&forged_span, dst_head, Options::CHECKPOINTS)
+			.is_err());
+		assert_eq!(dst.header_head().unwrap(), dst_head);
+
+		// Full pre-checkpoint range is committed by the checkpoint, no PoW is verified
+		dst.sync_block_headers(&headers, dst_head, Options::CHECKPOINTS)
+			.unwrap();
+		assert_eq!(dst.header_head().unwrap().last_block_h, headers[8].hash());
+	}
+
+	clean_output_dir(src_dir);
+	clean_output_dir(fork_dir);
+	clean_output_dir(dst_dir);
+}
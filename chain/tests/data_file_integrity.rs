@@ -45,3 +45,21 @@ fn data_files() {
 	// Cleanup chain directory
 	clean_output_dir(chain_dir);
 }
+
+#[test]
+fn integrity_check_of_valid_chain() {
+	util::init_test_logger();
+
+	let chain_dir = ".mwc_integrity";
+	clean_output_dir(chain_dir);
+
+	{
+		let chain = mine_chain(chain_dir, 10);
+		let report = chain.check_integrity(false).unwrap();
+		assert_eq!(report.head_height, 9);
+		assert_eq!(report.first_bad_height, None);
+		assert_eq!(report.error, None);
+	}
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,71 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use self::core::core::hash::Hashed;
+use self::core::core::Block;
+use mwc_core as core;
+use mwc_util as util;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+
+mod chain_test_helper;
+
+#[test]
+fn backfill_pruned_history_to_archive() {
+	util::init_test_logger();
+
+	let chain_dir = ".mwc_history_backfill";
+	clean_output_dir(chain_dir);
+
+	{
+		let chain = mine_chain(chain_dir, 100);
+		let history: Vec<Block> = (1..100)
+			.map(|height| {
+				let header = chain.get_header_by_height(height).unwrap();
+				chain.get_block(&header.hash()).unwrap()
+			})
+			.collect();
+
+		// Pruned node, the blocks below the tail are removed by compaction
+		chain.compact().unwrap();
+		let tail = chain.tail().unwrap();
+		assert!(tail.height > 1);
+		assert!(!chain.block_exists(&history[0].hash()).unwrap());
+
+		let chain = chain.with_history_backfill(true);
+		assert_eq!(chain.history_tail().unwrap(), tail);
+		assert!(!chain.archive_mode());
+
+		// Block that is not continuous with the history doesn't move the tail
+		let history_tail = chain.backfill_block(&history[0]).unwrap();
+		assert_eq!(history_tail.height, tail.height);
+
+		// Block from another height is rejected
+		let mut bad_block = history[(tail.height - 2) as usize].clone();
+		bad_block.header = history[(tail.height - 3) as usize].header.clone();
+		assert!(chain.backfill_block(&bad_block).is_err());
+
+		for b in history[1..(tail.height - 1) as usize].iter().rev() {
+			let history_tail = chain.backfill_block(b).unwrap();
+			if b.header.height > 2 {
+				assert_eq!(history_tail.height, b.header.height);
+			}
+		}
+		assert_eq!(chain.history_tail().unwrap().height, 0);
+		assert!(chain.archive_mode());
+		assert_eq!(chain.history_depth().unwrap(), u64::MAX);
+	}
+
+	clean_output_dir(chain_dir);
+}
@@ -373,6 +373,7 @@ fn mine_reorg() {
 		let head = chain.head().unwrap();
 		assert_eq!(head.height, NUM_BLOCKS_MAIN);
 		assert_eq!(head.hash(), prev.hash());
+		let head_before_reorg = head;
 
 		// Reorg chain should exceed main chain's total difficulty to be considered
 		let reorg_difficulty = head.total_difficulty.to_num();
@@ -400,6 +401,34 @@ fn mine_reorg() {
 		let head = chain.head().unwrap();
 		assert_eq!(head.height, NUM_BLOCKS_MAIN - REORG_DEPTH + 1);
 		assert_eq!(head.hash(), reorg_head.hash());
+
+		// Last reorg is recorded by the chain
+		let last_reorg = chain.last_reorg().unwrap();
+		assert_eq!(last_reorg.height, NUM_BLOCKS_MAIN - REORG_DEPTH + 1);
+		assert_eq!(last_reorg.fork_height, 1);
+		assert_eq!(last_reorg.depth, REORG_DEPTH);
+
+		// and kept in the reorg history with the kernels of the rolled back blocks
+		let history = chain.reorg_history(None).unwrap();
+		assert_eq!(history.len(), 1);
+		assert_eq!(history[0].old_tip.hash, head_before_reorg.hash());
+		assert_eq!(history[0].new_tip.hash, reorg_head.hash());
+		assert_eq!(history[0].fork_point.height, 1);
+		assert_eq!(history[0].depth, REORG_DEPTH);
+		assert_eq!(history[0].kernels.len() as u64, REORG_DEPTH);
+		assert_eq!(chain.reorg_history(Some(2)).unwrap().len(), 1);
+		assert_eq!(chain.reorg_history(Some(NUM_BLOCKS_MAIN)).unwrap().len(), 1);
+		assert!(chain.reorg_history(Some(1)).unwrap().is_empty());
+		assert!(chain
+			.reorg_history(Some(NUM_BLOCKS_MAIN + 1))
+			.unwrap()
+			.is_empty());
+	}
+
+	// reorg history survives the restart
+	{
+		let chain = init_chain(DIR_NAME, genesis);
+		assert_eq!(chain.reorg_history(None).unwrap().len(), 1);
 	}
 
 	// Cleanup chain directory
@@ -454,6 +483,33 @@ fn mine_forks() {
 	clean_output_dir(".mwc2");
 }
 
+#[test]
+fn block_acceptance_pause() {
+	let chain_dir = ".mwc_acceptance_pause";
+	clean_output_dir(chain_dir);
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	{
+		let chain = init_chain(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let prev = chain.head_header().unwrap();
+		let b = prepare_block(&kc, &prev, &chain, 2);
+
+		// block is refused while paused, it is not the block's fault
+		chain.pause_block_acceptance(true);
+		let e = chain
+			.process_block(b.clone(), chain::Options::SKIP_POW)
+			.unwrap_err();
+		assert!(!e.is_bad_data());
+		assert_eq!(chain.head().unwrap().height, 0);
+
+		chain.pause_block_acceptance(false);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 1);
+	}
+	clean_output_dir(chain_dir);
+}
+
 #[test]
 fn mine_losing_fork() {
 	clean_output_dir(".mwc3");
@@ -0,0 +1,170 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use self::chain::types::Tip;
+use self::chain::{reorg_guard, Chain, Error, Options};
+use self::core::core::hash::Hashed;
+use self::core::core::{Block, BlockHeader};
+use self::core::global::{self, ChainTypes};
+use self::core::libtx::{self, reward};
+use self::core::pow::{self, Difficulty};
+use self::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
+use chrono::Duration;
+use mwc_chain as chain;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_util as util;
+use mwc_util::ToHex;
+
+use self::chain_test_helper::{clean_output_dir, init_chain};
+
+mod chain_test_helper;
+
+// All tests of this file use the same max depth, the guard setting is global
+const MAX_REORG_DEPTH: u64 = 2;
+
+// Block on top of `prev` with the difficulty `diff`, diff is the key index as well
+fn prepare_block<K: Keychain>(kc: &K, prev: &BlockHeader, chain: &Chain, diff: u64) -> Block {
+	let key_id = ExtKeychainPath::new(1, diff as u32, 0, 0, 0).to_identifier();
+	let reward = reward::output(
+		kc,
+		&libtx::ProofBuilder::new(kc),
+		&key_id,
+		0,
+		false,
+		prev.height + 1,
+		kc.secp(),
+	)
+	.unwrap();
+	let mut b = Block::new(prev, &[], Difficulty::from_num(diff), reward, kc.secp()).unwrap();
+	b.header.timestamp = prev.timestamp + Duration::seconds(60);
+	b.header.pow.total_difficulty = prev.total_difficulty() + Difficulty::from_num(diff);
+	b.header.pow.proof = pow::Proof::random(global::proofsize());
+	chain.set_txhashset_roots(&mut b).unwrap();
+	b
+}
+
+// Chain with the blocks 1..=6 of the difficulty 1..=6
+fn setup(dir_name: &str, kc: &ExtKeychain) -> Chain {
+	util::init_test_logger();
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	reorg_guard::init_max_reorg_depth(Some(MAX_REORG_DEPTH));
+	clean_output_dir(dir_name);
+	let chain = init_chain(dir_name, pow::mine_genesis_block().unwrap());
+	let mut prev = chain.head_header().unwrap();
+	for diff in 1..=6 {
+		let b = prepare_block(kc, &prev, &chain, diff);
+		prev = b.header.clone();
+		chain.process_block(b, Options::SKIP_POW).unwrap();
+	}
+	chain
+}
+
+// Fork block on top of `height` with more work than the current header head
+fn fork_block(chain: &Chain, kc: &ExtKeychain, height: u64) -> Block {
+	let fork_prev = chain.get_header_by_height(height).unwrap();
+	let diff = chain.header_head().unwrap().total_difficulty.to_num();
+	prepare_block(kc, &fork_prev, chain, diff)
+}
+
+fn is_not_approved<T>(res: Result<T, Error>) -> bool {
+	matches!(res, Err(Error::ReorgNotApproved(_)))
+}
+
+#[test]
+fn deep_header_reorg_needs_approval() {
+	let dir_name = ".mwc_reorg_guard_header";
+	let kc = ExtKeychain::from_random_seed(false).unwrap();
+	{
+		let chain = setup(dir_name, &kc);
+		let header_head = chain.header_head().unwrap();
+
+		// Fork at 1 rolls back 5 headers
+		let fork = fork_block(&chain, &kc, 1);
+		assert!(is_not_approved(
+			chain.process_block_header(&fork.header, Options::SKIP_POW)
+		));
+		assert!(is_not_approved(chain.sync_block_headers(
+			&[fork.header.clone()],
+			header_head,
+			Options::SKIP_POW
+		)));
+		assert_eq!(chain.header_head().unwrap(), header_head);
+
+		let pending = reorg_guard::pending_reorgs();
+		let reorg = pending
+			.iter()
+			.find(|r| r.tip_hash == fork.hash().to_hex())
+			.unwrap();
+		assert_eq!(reorg.fork_hash, fork.hash().to_hex());
+		assert_eq!(reorg.fork_height, 1);
+		assert_eq!(reorg.depth, 5);
+
+		// Approved fork is accepted
+		reorg_guard::approve_reorg(&fork.hash()).unwrap();
+		chain
+			.process_block_header(&fork.header, Options::SKIP_POW)
+			.unwrap();
+		assert_eq!(chain.header_head().unwrap(), Tip::from_header(&fork.header));
+
+		// Fork within the max depth doesn't need the approval
+		let fork = fork_block(&chain, &kc, 1);
+		chain
+			.process_block_header(&fork.header, Options::SKIP_POW)
+			.unwrap();
+		assert_eq!(chain.header_head().unwrap(), Tip::from_header(&fork.header));
+	}
+	clean_output_dir(dir_name);
+}
+
+#[test]
+fn deep_block_reorg_needs_approval() {
+	let dir_name = ".mwc_reorg_guard_block";
+	let kc = ExtKeychain::from_random_seed(false).unwrap();
+	{
+		let chain = setup(dir_name, &kc);
+		let head = chain.head().unwrap();
+
+		// Fork at 1 rolls back 5 blocks
+		let fork = fork_block(&chain, &kc, 1);
+		assert!(is_not_approved(
+			chain.process_block(fork.clone(), Options::SKIP_POW)
+		));
+		assert_eq!(chain.head().unwrap(), head);
+		assert!(reorg_guard::pending_reorgs()
+			.iter()
+			.any(|r| r.fork_hash == fork.hash().to_hex()));
+
+		// Approved fork is accepted
+		reorg_guard::approve_reorg(&fork.hash()).unwrap();
+		assert!(!reorg_guard::pending_reorgs()
+			.iter()
+			.any(|r| r.fork_hash == fork.hash().to_hex()));
+		chain
+			.process_block(fork.clone(), Options::SKIP_POW)
+			.unwrap();
+		assert_eq!(chain.head().unwrap(), Tip::from_header(&fork.header));
+
+		// Fork within the max depth doesn't need the approval
+		let fork = fork_block(&chain, &kc, 1);
+		chain
+			.process_block(fork.clone(), Options::SKIP_POW)
+			.unwrap();
+		assert_eq!(chain.head().unwrap(), Tip::from_header(&fork.header));
+
+		// Approval of an unknown fork fails
+		assert!(reorg_guard::approve_reorg(&head.last_block_h).is_err());
+	}
+	clean_output_dir(dir_name);
+}
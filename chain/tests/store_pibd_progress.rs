@@ -0,0 +1,78 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use self::core::core::hash::Hashed;
+use croaring::Bitmap;
+use mwc_chain::PibdProgress;
+use mwc_core as core;
+use mwc_util as util;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+
+#[test]
+fn test_store_pibd_progress() {
+	util::init_test_logger();
+
+	let chain_dir = ".mwc_pibd_progress";
+	clean_output_dir(chain_dir);
+
+	let chain = mine_chain(chain_dir, 4);
+	let header = chain.get_header_by_height(3).unwrap();
+
+	let progress = PibdProgress {
+		archive_hash: header.hash(),
+		bitmap_root_hash: header.prev_hash,
+		output_segments: 3,
+		rangeproof_segments: 2,
+		kernel_segments: 5,
+		output_mmr_size: header.output_mmr_size,
+		rangeproof_mmr_size: header.output_mmr_size,
+		kernel_mmr_size: header.kernel_mmr_size,
+	};
+	let bitmap: Bitmap = vec![1u32, 3, 4, 1000].into_iter().collect();
+
+	{
+		let store = chain.get_store_for_tests();
+		let batch = store.batch_write().unwrap();
+		assert!(batch.get_pibd_progress().is_err());
+		batch.save_pibd_progress(&progress, &bitmap).unwrap();
+		batch.commit().unwrap();
+	}
+
+	{
+		let store = chain.get_store_for_tests();
+		let batch = store.batch_read().unwrap();
+		assert_eq!(batch.get_pibd_progress().unwrap(), progress);
+		assert_eq!(batch.get_pibd_bitmap().unwrap(), bitmap);
+	}
+
+	chain.delete_pibd_progress().unwrap();
+	{
+		let store = chain.get_store_for_tests();
+		let batch = store.batch_read().unwrap();
+		assert!(batch.get_pibd_progress().is_err());
+		assert!(batch.get_pibd_bitmap().is_err());
+	}
+
+	// Nothing to resume, the MMRs don't match the archive header
+	assert!(chain
+		.resume_desegmenter(header.height, header.prev_hash)
+		.unwrap()
+		.is_none());
+
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
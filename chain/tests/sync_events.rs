@@ -0,0 +1,53 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::types::SYNC_EVENTS_LIMIT;
+use mwc_chain::{SyncEventKind, SyncState, SyncStatus};
+
+#[test]
+fn sync_status_changes_are_recorded() {
+	let sync_state = SyncState::new();
+	sync_state.update(SyncStatus::HeaderSync {
+		current_height: 10,
+		archive_height: 100,
+	});
+	// Progress of the same stage is not an event
+	sync_state.update(SyncStatus::HeaderSync {
+		current_height: 20,
+		archive_height: 100,
+	});
+	sync_state.update(SyncStatus::NoSync);
+
+	let events = sync_state.events();
+	assert_eq!(events.len(), 2);
+	assert!(events
+		.iter()
+		.all(|e| e.kind == SyncEventKind::StatusChanged));
+	assert!(events[1].message.ends_with("-> NoSync"));
+}
+
+#[test]
+fn sync_events_are_limited() {
+	let sync_state = SyncState::new();
+	for i in 0..SYNC_EVENTS_LIMIT + 10 {
+		sync_state.add_event(
+			SyncEventKind::RequestsTimedOut,
+			vec!["127.0.0.1:3414".to_string()],
+			format!("event {}", i),
+		);
+	}
+	let events = sync_state.events();
+	assert_eq!(events.len(), SYNC_EVENTS_LIMIT);
+	assert_eq!(events[0].message, "event 10");
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hashed;
+use mwc_core::core::Block;
+use mwc_core::global::{self, ChainTypes};
+use mwc_core::ser::{self, ProtocolVersion};
+use mwc_keychain::{ExtKeychain, Keychain};
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, genesis_block, init_chain, mine_some_on_top};
+
+fn block_bytes(block: &Block) -> Vec<u8> {
+	ser::ser_vec(block, ProtocolVersion::local()).unwrap()
+}
+
+#[test]
+fn test_block_compression() {
+	let chain_dir = ".mwc.block_compression";
+	clean_output_dir(chain_dir);
+
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = genesis_block(&keychain);
+	let horizon = global::cut_through_horizon() as u64;
+
+	let old_blocks: Vec<Vec<u8>> = {
+		let mut chain = init_chain(chain_dir, genesis.clone());
+		mine_some_on_top(&mut chain, horizon + 5, &keychain);
+		assert_eq!(chain.head().unwrap().height, horizon + 5);
+
+		// heights 0..5 are below the horizon
+		let old_blocks: Vec<Block> = (0..5)
+			.map(|h| {
+				let header = chain.get_header_by_height(h).unwrap();
+				chain.get_block(&header.hash()).unwrap()
+			})
+			.collect();
+
+		assert_eq!(chain.compress_old_blocks(2).unwrap(), 2);
+		assert_eq!(chain.compress_old_blocks(100).unwrap(), 3);
+		assert_eq!(chain.compress_old_blocks(100).unwrap(), 0);
+
+		// compressed blocks are read as before
+		for b in &old_blocks {
+			assert!(chain.block_exists(&b.hash()).unwrap());
+			let read = chain.get_block(&b.hash()).unwrap();
+			assert_eq!(block_bytes(&read), block_bytes(b));
+		}
+		let store = chain.get_store_for_tests();
+		let batch = store.batch_read().unwrap();
+		assert_eq!(batch.blocks_iter().unwrap().count() as u64, horizon + 5 + 1);
+		assert_eq!(
+			batch.uncompressed_blocks_iter().unwrap().count() as u64,
+			horizon + 5 + 1 - 5
+		);
+		old_blocks.iter().map(block_bytes).collect()
+	};
+
+	// and after the restart
+	{
+		let mut chain = init_chain(chain_dir, genesis);
+		for bytes in &old_blocks {
+			let block: Block = ser::deserialize(
+				&mut &bytes[..],
+				ProtocolVersion::local(),
+				ser::DeserializationMode::default(),
+			)
+			.unwrap();
+			let read = chain.get_block(&block.hash()).unwrap();
+			assert_eq!(&block_bytes(&read), bytes);
+		}
+		chain.validate(false).unwrap();
+
+		// the chain keeps growing on top of the compressed blocks
+		mine_some_on_top(&mut chain, 3, &keychain);
+		assert_eq!(chain.compress_old_blocks(100).unwrap(), 3);
+	}
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,86 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::snapshot;
+use mwc_core::global::{self, ChainTypes};
+use mwc_keychain::{ExtKeychain, Keychain};
+use std::fs;
+use std::path::Path;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, genesis_block, init_chain, mine_some_on_top};
+
+#[test]
+fn test_chain_snapshot() {
+	let chain_dir = ".mwc.snapshot_src";
+	let snapshot_dir = ".mwc.snapshot";
+	let restore_dir = ".mwc.snapshot_dst";
+	clean_output_dir(chain_dir);
+	clean_output_dir(snapshot_dir);
+	clean_output_dir(restore_dir);
+
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = genesis_block(&keychain);
+	{
+		let mut chain = init_chain(chain_dir, genesis.clone());
+		mine_some_on_top(&mut chain, 5, &keychain);
+		let head = chain.head().unwrap();
+
+		let info = chain.create_snapshot(Path::new(snapshot_dir)).unwrap();
+		assert_eq!(info.height, head.height);
+		assert_eq!(info.hash, head.last_block_h);
+		assert_eq!(
+			snapshot::read_manifest(Path::new(snapshot_dir)).unwrap(),
+			(head.height, head.last_block_h)
+		);
+
+		// existing snapshot is never overwritten
+		assert!(chain.create_snapshot(Path::new(snapshot_dir)).is_err());
+
+		// the source chain keeps growing, the snapshot doesn't follow it
+		mine_some_on_top(&mut chain, 3, &keychain);
+		assert!(chain.head().unwrap().height > head.height);
+	}
+
+	// restore into an existing chain dir moves its data aside
+	{
+		let chain = init_chain(restore_dir, genesis.clone());
+		assert_eq!(chain.head().unwrap().height, 0);
+	}
+	let restore =
+		snapshot::restore_snapshot(Path::new(snapshot_dir), Path::new(restore_dir)).unwrap();
+	assert!(restore.backup_dir.unwrap().join("lmdb").is_dir());
+	// the snapshot was copied aside first, nothing is left of that copy
+	for entry in fs::read_dir(restore_dir).unwrap() {
+		let name = entry.unwrap().file_name().into_string().unwrap();
+		assert!(!name.starts_with("restore_tmp"), "{} is left", name);
+	}
+	{
+		let chain = init_chain(restore_dir, genesis);
+		let head = chain.head().unwrap();
+		assert_eq!(head.height, restore.height);
+		assert_eq!(head.last_block_h, restore.hash);
+		chain.validate(false).unwrap();
+	}
+
+	// snapshot without the manifest is rejected
+	fs::remove_file(Path::new(snapshot_dir).join(snapshot::MANIFEST_FILE)).unwrap();
+	assert!(snapshot::restore_snapshot(Path::new(snapshot_dir), Path::new(restore_dir)).is_err());
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(snapshot_dir);
+	clean_output_dir(restore_dir);
+}
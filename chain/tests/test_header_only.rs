@@ -0,0 +1,59 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use mwc_chain as chain;
+use mwc_core::core::hash::Hashed;
+use mwc_util as util;
+
+#[test]
+fn header_only_node_head() {
+	let chain_dir = ".mwc.header_only";
+	let header_only_dir = ".mwc.header_only.headers";
+	util::init_test_logger();
+	clean_output_dir(chain_dir);
+	clean_output_dir(header_only_dir);
+
+	let (headers, genesis) = {
+		let chain = mine_chain(chain_dir, 4);
+		let genesis = chain
+			.get_block(&chain.get_header_by_height(0).unwrap().hash())
+			.unwrap();
+		let headers: Vec<_> = (1..4)
+			.map(|h| chain.get_header_by_height(h).unwrap())
+			.collect();
+		(headers, genesis)
+	};
+
+	{
+		let chain = init_chain(header_only_dir, genesis).with_header_only(true);
+		assert!(chain.header_only());
+		for header in &headers {
+			chain
+				.process_block_header(header, chain::Options::NONE)
+				.unwrap();
+		}
+
+		// Only the headers are known, the body head stays at genesis
+		assert_eq!(chain.head().unwrap().height, 0);
+		let node_head = chain.node_head().unwrap();
+		assert_eq!(node_head.height, 3);
+		assert_eq!(node_head.last_block_h, headers[2].hash());
+		assert_eq!(chain.get_header_by_height(2).unwrap(), headers[1]);
+	}
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(header_only_dir);
+}
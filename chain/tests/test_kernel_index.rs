@@ -0,0 +1,92 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hashed;
+use mwc_core::global::{self, ChainTypes};
+use mwc_keychain as keychain;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, genesis_block, init_chain, mine_some_on_top};
+
+#[test]
+fn test_kernel_index() {
+	let chain_dir = ".mwc.kernel_index";
+	clean_output_dir(chain_dir);
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let keychain = keychain::ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = genesis_block(&keychain);
+
+	// Index is disabled by default
+	let mut chain = init_chain(chain_dir, genesis.clone());
+	mine_some_on_top(&mut chain, 5, &keychain);
+	assert!(!chain.index_enabled());
+	let genesis_excess = genesis.kernels()[0].excess;
+	assert!(chain.get_kernel_index(&genesis_excess).is_err());
+
+	// Index covers the blocks above the current head
+	let mut chain = chain.with_index(true).unwrap();
+	assert_eq!(chain.head().unwrap().height, 4);
+	// Another keychain, so the coinbase commitments are not duplicated
+	let keychain = keychain::ExtKeychain::from_random_seed(false).unwrap();
+	mine_some_on_top(&mut chain, 5, &keychain);
+	let head = chain.head().unwrap();
+	assert_eq!(head.height, 8);
+
+	for height in 1..=head.height {
+		let header = chain.get_header_by_height(height).unwrap();
+		let block = chain.get_block(&header.hash()).unwrap();
+		let kernel = block.kernels()[0].clone();
+		let output = block.outputs()[0].commitment();
+
+		let indexed = chain.get_kernel_index(&kernel.excess).unwrap();
+		let outputs = chain.get_output_index(&output).unwrap();
+		if height > 4 {
+			assert_eq!(indexed.len(), 1);
+			assert_eq!(indexed[0].hash(), header.hash());
+			assert_eq!(outputs.len(), 1);
+			assert_eq!(outputs[0].hash(), header.hash());
+		} else {
+			assert!(indexed.is_empty());
+			assert!(outputs.is_empty());
+		}
+
+		// Indexed and scanned kernels are found the same way
+		let (found, kernel_height, _) = chain
+			.get_kernel_height(&kernel.excess, None, None)
+			.unwrap()
+			.unwrap();
+		assert_eq!(found, kernel);
+		assert_eq!(kernel_height, height);
+		assert!(chain
+			.get_kernel_height(&kernel.excess, Some(height + 1), None)
+			.unwrap()
+			.is_none());
+		assert!(chain
+			.get_kernel_height(&kernel.excess, None, Some(height - 1))
+			.unwrap()
+			.is_none());
+	}
+
+	// Index survives the restart, the start height is kept
+	drop(chain);
+	let chain = init_chain(chain_dir, genesis).with_index(true).unwrap();
+	let header = chain.get_header_by_height(5).unwrap();
+	let block = chain.get_block(&header.hash()).unwrap();
+	let indexed = chain.get_kernel_index(&block.kernels()[0].excess).unwrap();
+	assert_eq!(indexed.len(), 1);
+	assert_eq!(indexed[0].height, 5);
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,61 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hashed;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+
+#[test]
+fn test_kernel_merkle_proof() {
+	let chain_dir = ".mwc.kernel_merkle_proof";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 8);
+	let head = chain.head().unwrap();
+	assert_eq!(head.height, 7);
+
+	for height in 1..=head.height {
+		let header = chain.get_header_by_height(height).unwrap();
+		let block = chain.get_block(&header.hash()).unwrap();
+		let kernel = block.kernels()[0].clone();
+		let (found, kernel_height, mmr_index) = chain
+			.get_kernel_height(&kernel.excess, None, None)
+			.unwrap()
+			.unwrap();
+		assert_eq!(found, kernel);
+		assert_eq!(kernel_height, height);
+
+		// Proof is valid for the kernel block and for any block above it
+		for proof_height in height..=head.height {
+			let proof_header = chain.get_header_by_height(proof_height).unwrap();
+			let (proof, peaks) = chain
+				.get_kernel_merkle_proof(mmr_index, &proof_header)
+				.unwrap();
+			assert_eq!(proof.mmr_size, proof_header.kernel_mmr_size);
+			assert!(!peaks.is_empty());
+			assert!(proof
+				.verify(proof_header.kernel_root, &kernel, mmr_index - 1)
+				.is_ok());
+		}
+
+		// Kernel is not in the MMR of the previous block
+		let prev_header = chain.get_header_by_height(height - 1).unwrap();
+		assert!(chain
+			.get_kernel_merkle_proof(mmr_index, &prev_header)
+			.is_err());
+	}
+
+	clean_output_dir(chain_dir);
+}
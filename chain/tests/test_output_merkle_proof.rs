@@ -0,0 +1,53 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hashed;
+use mwc_util as util;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use util::secp::pedersen::Commitment;
+
+#[test]
+fn test_output_merkle_proof() {
+	let chain_dir = ".mwc.output_merkle_proof";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 6);
+	let head = chain.head_header().unwrap();
+
+	for height in 1..=head.height {
+		let header = chain.get_header_by_height(height).unwrap();
+		let block = chain.get_block(&header.hash()).unwrap();
+		let output = block.outputs()[0].identifier();
+		let (pos, proof, proof_header) = chain
+			.get_output_merkle_proof(output.commitment())
+			.unwrap()
+			.unwrap();
+		assert_eq!(pos.height, height);
+		assert_eq!(proof_header.hash(), head.hash());
+		assert_eq!(proof.mmr_size, head.output_mmr_size);
+		assert!(proof
+			.verify(proof_header.output_root, &output, pos.pos - 1)
+			.is_ok());
+	}
+
+	// Unknown output has no proof
+	assert!(chain
+		.get_output_merkle_proof(Commitment::from_vec(vec![0; 33]))
+		.unwrap()
+		.is_none());
+
+	clean_output_dir(chain_dir);
+}
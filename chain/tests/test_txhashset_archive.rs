@@ -15,7 +15,13 @@
 
 mod chain_test_helper;
 
-use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use mwc_chain::types::Options;
+use mwc_chain::SyncState;
+use mwc_core::core::hash::Hashed;
+use mwc_core::core::BlockHeader;
+use mwc_util::{self as util, StopState};
+use std::sync::Arc;
 
 #[test]
 fn test() {
@@ -26,3 +32,45 @@ fn test() {
 	assert_eq!(10, header.height);
 	clean_output_dir(chain_dir);
 }
+
+#[test]
+fn txhashset_archive_write() {
+	util::init_test_logger();
+	// Received archive is unpacked into the tmp dir next to the chain dir
+	let root_dir = ".txhashset_archive_write";
+	let src_dir = ".txhashset_archive_write/src";
+	let dst_dir = ".txhashset_archive_write/dst";
+	clean_output_dir(root_dir);
+	{
+		let src_chain = mine_chain(src_dir, 35);
+		let header = src_chain.txhashset_archive_header().unwrap();
+		let (_, _, zip) = src_chain.txhashset_read(header.hash()).unwrap();
+
+		// Node that has the headers only
+		let genesis = src_chain.get_block(&src_chain.genesis().hash()).unwrap();
+		let dst_chain = init_chain(dst_dir, genesis);
+		let headers: Vec<BlockHeader> = (1..=src_chain.head().unwrap().height)
+			.map(|height| src_chain.get_header_by_height(height).unwrap())
+			.collect();
+		dst_chain
+			.sync_block_headers(&headers, dst_chain.header_head().unwrap(), Options::NONE)
+			.unwrap();
+
+		let sync_state = Arc::new(SyncState::new());
+		let stop_state = Arc::new(StopState::new());
+		dst_chain
+			.txhashset_write(header.hash(), zip, sync_state.clone(), stop_state.clone())
+			.unwrap();
+		let head = dst_chain.head().unwrap();
+		assert_eq!(head.height, header.height);
+		assert_eq!(head.last_block_h, header.hash());
+		assert_eq!(dst_chain.tail().unwrap().height, header.height);
+
+		// The state is already there, the same archive is not needed any more
+		let (_, _, zip) = src_chain.txhashset_read(header.hash()).unwrap();
+		assert!(dst_chain
+			.txhashset_write(header.hash(), zip, sync_state, stop_state)
+			.is_err());
+	}
+	clean_output_dir(root_dir);
+}
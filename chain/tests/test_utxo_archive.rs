@@ -0,0 +1,97 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::{Chain, SyncState};
+use mwc_core::core::hash::{Hash, Hashed, ZERO_HASH};
+use mwc_core::global::{self, ChainTypes};
+use mwc_keychain::{ExtKeychain, Keychain};
+use mwc_util::StopState;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, genesis_block, init_chain, mine_some_on_top};
+
+fn import(chain: &Chain, path: &Path, trusted_hash: Hash) -> bool {
+	chain
+		.import_utxo_archive(
+			path,
+			trusted_hash,
+			Arc::new(SyncState::new()),
+			Arc::new(StopState::new()),
+		)
+		.is_ok()
+}
+
+#[test]
+fn test_utxo_archive() {
+	let chain_dir = ".mwc.utxo_archive_src";
+	let archive_dir = ".mwc.utxo_archive";
+	let import_dir = ".mwc.utxo_archive_dst";
+	clean_output_dir(chain_dir);
+	clean_output_dir(archive_dir);
+	clean_output_dir(import_dir);
+	fs::create_dir_all(archive_dir).unwrap();
+	let archive = Path::new(archive_dir).join("utxo.bin");
+
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = genesis_block(&keychain);
+	let archive_header = {
+		let mut chain = init_chain(chain_dir, genesis.clone());
+		mine_some_on_top(&mut chain, 40, &keychain);
+		let archive_header = chain.txhashset_archive_header().unwrap();
+		assert!(archive_header.height > 0);
+
+		let info = chain.write_utxo_archive(&archive).unwrap();
+		assert_eq!(info.height, archive_header.height);
+		assert_eq!(info.hash, archive_header.hash());
+		assert_eq!(info.headers, archive_header.height);
+
+		// existing archive is never overwritten
+		assert!(chain.write_utxo_archive(&archive).is_err());
+		archive_header
+	};
+
+	{
+		let chain = init_chain(import_dir, genesis.clone());
+		// archive header must be the trusted one
+		assert!(!import(&chain, &archive, ZERO_HASH));
+		assert_eq!(chain.head().unwrap().height, 0);
+
+		assert!(import(&chain, &archive, archive_header.hash()));
+		let head = chain.head().unwrap();
+		assert_eq!(head.height, archive_header.height);
+		assert_eq!(head.last_block_h, archive_header.hash());
+		assert_eq!(chain.header_head().unwrap().height, archive_header.height);
+
+		// only the empty chain can be bootstrapped
+		assert!(!import(&chain, &archive, archive_header.hash()));
+	}
+
+	// truncated archive is rejected
+	clean_output_dir(import_dir);
+	let data = fs::read(&archive).unwrap();
+	fs::write(&archive, &data[..data.len() / 2]).unwrap();
+	{
+		let chain = init_chain(import_dir, genesis);
+		assert!(!import(&chain, &archive, archive_header.hash()));
+	}
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(archive_dir);
+	clean_output_dir(import_dir);
+}
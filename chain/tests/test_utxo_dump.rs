@@ -0,0 +1,67 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::utxo_dump::UtxoDump;
+use mwc_core::core::OutputFeatures;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+
+#[test]
+fn test_utxo_dump() {
+	let chain_dir = ".mwc.utxo_dump";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+
+	let dump = chain.export_utxo_set().unwrap();
+	let head = chain.head_header().unwrap();
+	assert_eq!(dump.height, head.height);
+	assert_eq!(dump.roots.output_root, head.output_root);
+	// genesis and 4 mined coinbase outputs
+	assert_eq!(dump.utxos.len(), 5);
+	assert!(dump
+		.utxos
+		.iter()
+		.all(|u| u.features == OutputFeatures::Coinbase));
+	assert!(dump
+		.utxos
+		.windows(2)
+		.all(|w| w[0].commit.as_ref() < w[1].commit.as_ref()));
+
+	// text format round trip
+	let mut buf = vec![];
+	dump.write(&mut buf).unwrap();
+	let dump2 = UtxoDump::read(&buf[..]).unwrap();
+	assert_eq!(dump, dump2);
+	assert!(dump.compare(&dump2).is_match());
+
+	// missing and changed outputs are reported
+	let mut dump3 = dump2.clone();
+	let removed = dump3.utxos.remove(0);
+	dump3.utxos[0].height += 1;
+	let diff = dump.compare(&dump3);
+	assert!(!diff.is_match());
+	assert_eq!(diff.only_first, vec![removed]);
+	assert!(diff.only_second.is_empty());
+	assert_eq!(diff.mismatch.len(), 1);
+
+	// broken count is rejected
+	let text = String::from_utf8(buf)
+		.unwrap()
+		.replace("utxos 5", "utxos 6");
+	assert!(UtxoDump::read(text.as_bytes()).is_err());
+
+	clean_output_dir(chain_dir);
+}
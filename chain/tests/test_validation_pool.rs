@@ -0,0 +1,88 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::{validation_pool, Options};
+use mwc_core::core::hash::Hashed;
+use mwc_core::core::Block;
+use mwc_core::global::{self, ChainTypes};
+use mwc_keychain::{ExtKeychain, Keychain};
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, genesis_block, init_chain, mine_some_on_top};
+
+#[test]
+fn test_validation_pool() {
+	let chain_dir = ".mwc.validation_pool";
+	let replay_dir = ".mwc.validation_pool_replay";
+	clean_output_dir(chain_dir);
+	clean_output_dir(replay_dir);
+
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = genesis_block(&keychain);
+	let mut chain = init_chain(chain_dir, genesis.clone());
+	mine_some_on_top(&mut chain, 7, &keychain);
+
+	let blocks: Vec<Block> = (1..=6)
+		.map(|h| {
+			let header = chain.get_header_by_height(h).unwrap();
+			chain.get_block(&header.hash()).unwrap()
+		})
+		.collect();
+
+	// block 2 has a foreign kernel signature, block 4 a foreign rangeproof
+	let mut broken = blocks.clone();
+	broken[2].body.kernels[0].excess_sig = blocks[1].kernels()[0].excess_sig;
+	broken[4].body.outputs[0].proof = blocks[3].outputs()[0].proof;
+
+	for threads in vec![Some(4), Some(1)] {
+		validation_pool::init_validation_threads(threads).unwrap();
+
+		let res = validation_pool::verify_blocks(&blocks, chain.secp()).unwrap();
+		assert!(res.iter().all(|r| r.is_ok()));
+
+		let res = validation_pool::verify_blocks(&broken, chain.secp()).unwrap();
+		let failed: Vec<usize> = res
+			.iter()
+			.enumerate()
+			.filter(|(_, r)| r.is_err())
+			.map(|(i, _)| i)
+			.collect();
+		assert_eq!(failed, vec![2, 4]);
+
+		// state sync rangeproofs, the pairs come from the output and rangeproof segments
+		let outputs: Vec<_> = blocks.iter().flat_map(|b| b.outputs().iter()).collect();
+		let commits: Vec<_> = outputs.iter().map(|o| o.commitment()).collect();
+		let mut proofs: Vec<_> = outputs.iter().map(|o| o.proof).collect();
+		validation_pool::verify_rangeproofs(&commits, &proofs, chain.secp()).unwrap();
+		assert!(validation_pool::verify_rangeproofs(&commits, &proofs[1..], chain.secp()).is_err());
+		proofs.swap(1, 2);
+		assert!(validation_pool::verify_rangeproofs(&commits, &proofs, chain.secp()).is_err());
+	}
+
+	// the broken block is refused by the chain
+	validation_pool::init_validation_threads(Some(4)).unwrap();
+	let replay = init_chain(replay_dir, genesis);
+	for b in &broken[..2] {
+		replay.process_block(b.clone(), Options::SKIP_POW).unwrap();
+	}
+	assert!(replay
+		.process_block(broken[2].clone(), Options::SKIP_POW)
+		.is_err());
+	assert_eq!(replay.head().unwrap().height, 2);
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(replay_dir);
+}
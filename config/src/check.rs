@@ -0,0 +1,725 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cross validation of the loaded node configuration. Parsing of the config file
+//! only checks the syntax, here we are looking for the settings that are valid
+//! separately but don't work together.
+
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::core::global;
+use crate::p2p::{PeerAddr, Seeding};
+use crate::servers::{CoinbasePayout, ServerConfig};
+use crate::types::GlobalConfig;
+
+/// Fee base above this value (0.01 MWC) most likely is a typo
+const MAX_SANE_FEE_BASE: u64 = 10_000_000;
+
+/// How serious the found configuration problem is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueLevel {
+	/// Node can run, but likely not the way the operator expects
+	Warning,
+	/// Node will fail to start or will be misconfigured
+	Error,
+}
+
+/// Single problem found in the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+	/// Severity of the problem
+	pub level: IssueLevel,
+	/// Config section or key where the problem is, e.g. `server.p2p_config`
+	pub section: String,
+	/// What is wrong and how to fix it
+	pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let level = match self.level {
+			IssueLevel::Warning => "WARNING",
+			IssueLevel::Error => "ERROR",
+		};
+		write!(f, "{:<7} [{}] {}", level, self.section, self.message)
+	}
+}
+
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+	fn error(&mut self, section: &str, message: String) {
+		self.0.push(ConfigIssue {
+			level: IssueLevel::Error,
+			section: section.to_string(),
+			message,
+		});
+	}
+
+	fn warning(&mut self, section: &str, message: String) {
+		self.0.push(ConfigIssue {
+			level: IssueLevel::Warning,
+			section: section.to_string(),
+			message,
+		});
+	}
+}
+
+/// Validate the whole configuration. Returns the list of found issues, empty list
+/// means that configuration is good.
+pub fn check_config(config: &GlobalConfig) -> Vec<ConfigIssue> {
+	let mut issues = Issues(vec![]);
+	match config.members.as_ref() {
+		Some(members) => {
+			let server = &members.server;
+			check_ports(server, &mut issues);
+			check_tor(server, &mut issues);
+			check_peers(server, &mut issues);
+			check_fee_base(server, &mut issues);
+			check_cut_through_horizon(server, &mut issues);
+			check_api_auth(server, &mut issues);
+			check_stratum_auth(server, &mut issues);
+			if members.logging.is_none() {
+				issues.warning(
+					"logging",
+					"logging section is missing, node will use default logging settings"
+						.to_string(),
+				);
+			}
+		}
+		None => issues.error("server", "server configuration is missing".to_string()),
+	}
+	issues.0
+}
+
+fn check_ports(server: &ServerConfig, issues: &mut Issues) {
+	let mut ports: Vec<(&str, u16)> = vec![("server.p2p_config.port", server.p2p_config.port)];
+
+	match server.api_http_addr.parse::<SocketAddr>() {
+		Ok(addr) => ports.push(("server.api_http_addr", addr.port())),
+		Err(e) => issues.error(
+			"server.api_http_addr",
+			format!(
+				"'{}' is not a valid ip:port address, {}",
+				server.api_http_addr, e
+			),
+		),
+	}
+
+	if let Some(stratum) = server.stratum_mining_config.as_ref() {
+		if stratum.enable_stratum_server.unwrap_or(false) {
+			match stratum.stratum_server_addr.as_ref() {
+				Some(addr) => match addr.parse::<SocketAddr>() {
+					Ok(addr) => ports.push((
+						"server.stratum_mining_config.stratum_server_addr",
+						addr.port(),
+					)),
+					Err(e) => issues.error(
+						"server.stratum_mining_config.stratum_server_addr",
+						format!("'{}' is not a valid ip:port address, {}", addr, e),
+					),
+				},
+				None => issues.error(
+					"server.stratum_mining_config",
+					"stratum server is enabled but stratum_server_addr is not set".to_string(),
+				),
+			}
+			if let Some(addr) = stratum.stratum_ws_server_addr.as_ref() {
+				match addr.parse::<SocketAddr>() {
+					Ok(addr) => ports.push((
+						"server.stratum_mining_config.stratum_ws_server_addr",
+						addr.port(),
+					)),
+					Err(e) => issues.error(
+						"server.stratum_mining_config.stratum_ws_server_addr",
+						format!("'{}' is not a valid ip:port address, {}", addr, e),
+					),
+				}
+			}
+		}
+	}
+
+	if server.tor_config.tor_enabled {
+		ports.push(("server.tor_config.socks_port", server.tor_config.socks_port));
+	}
+	if server.libp2p_enabled.unwrap_or(true) && server.tor_config.tor_enabled {
+		ports.push(("server.libp2p_port", server.libp2p_port.unwrap_or(3417)));
+	}
+
+	for (i, (name1, port1)) in ports.iter().enumerate() {
+		if *port1 == 0 {
+			issues.error(name1, "port 0 is not allowed".to_string());
+			continue;
+		}
+		for (name2, port2) in ports.iter().skip(i + 1) {
+			if port1 == port2 {
+				issues.error(name1, format!("port {} is also used by {}", port1, name2));
+			}
+		}
+	}
+}
+
+fn check_tor(server: &ServerConfig, issues: &mut Issues) {
+	let tor = &server.tor_config;
+	let onion_address = tor
+		.onion_address
+		.clone()
+		.unwrap_or_default()
+		.trim()
+		.to_string();
+
+	if !tor.tor_enabled {
+		if tor.tor_external {
+			issues.warning(
+				"server.tor_config",
+				"tor_external is set but tor_enabled is false, tor settings will be ignored"
+					.to_string(),
+			);
+		}
+		return;
+	}
+
+	if tor.socks_port == 0 {
+		issues.error(
+			"server.tor_config.socks_port",
+			"socks_port must be set when tor is enabled".to_string(),
+		);
+	}
+
+	if tor.control_port == Some(0) {
+		issues.error(
+			"server.tor_config.control_port",
+			"port 0 is not allowed".to_string(),
+		);
+	}
+	if tor.control_port.is_some() && !tor.tor_external {
+		issues.warning(
+			"server.tor_config.control_port",
+			"control_port is used with external tor only, it will be ignored".to_string(),
+		);
+	}
+
+	if tor.tor_external && tor.control_port.is_some() {
+		if !onion_address.is_empty() {
+			issues.warning(
+				"server.tor_config.onion_address",
+				"the onion service is created with control_port, onion_address will be ignored"
+					.to_string(),
+			);
+		}
+	} else if tor.tor_external {
+		if onion_address.is_empty() {
+			issues.error(
+				"server.tor_config.onion_address",
+				"onion_address must be specified with external tor".to_string(),
+			);
+		}
+	} else if !onion_address.is_empty() {
+		issues.warning(
+			"server.tor_config.onion_address",
+			"onion_address is used with external tor only, it will be ignored".to_string(),
+		);
+	}
+
+	if !onion_address.is_empty() {
+		let name = onion_address.trim_end_matches(".onion");
+		if name.len() != 56
+			|| !name
+				.chars()
+				.all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+		{
+			issues.warning(
+				"server.tor_config.onion_address",
+				format!("'{}' doesn't look like a v3 onion address", onion_address),
+			);
+		}
+	}
+}
+
+fn check_peers(server: &ServerConfig, issues: &mut Issues) {
+	let p2p = &server.p2p_config;
+	let tor_enabled = server.tor_config.tor_enabled;
+
+	let lists = [
+		("server.p2p_config.seeds", &p2p.seeds),
+		("server.p2p_config.peers_allow", &p2p.peers_allow),
+		("server.p2p_config.peers_deny", &p2p.peers_deny),
+		("server.p2p_config.peers_preferred", &p2p.peers_preferred),
+	];
+
+	for (name, list) in lists.iter() {
+		if let Some(list) = list {
+			for peer in &list.peers {
+				if let PeerAddr::Onion(onion) = peer {
+					if !tor_enabled {
+						issues.warning(
+							name,
+							format!(
+								"onion peer {} can't be reached because tor is not enabled",
+								onion
+							),
+						);
+					}
+				}
+			}
+		}
+	}
+
+	if p2p.seeding_type == Seeding::List
+		&& p2p
+			.seeds
+			.as_ref()
+			.map(|s| s.peers.is_empty())
+			.unwrap_or(true)
+	{
+		issues.error(
+			"server.p2p_config.seeds",
+			"seeding_type is List, but no seeds are specified".to_string(),
+		);
+	}
+
+	if let (Some(allow), Some(deny)) = (p2p.peers_allow.as_ref(), p2p.peers_deny.as_ref()) {
+		for peer in &allow.peers {
+			if deny.peers.contains(peer) {
+				issues.error(
+					"server.p2p_config.peers_deny",
+					format!("peer {} is in both peers_allow and peers_deny", peer),
+				);
+			}
+		}
+	}
+
+	if let (Some(preferred), Some(deny)) = (p2p.peers_preferred.as_ref(), p2p.peers_deny.as_ref()) {
+		for peer in &preferred.peers {
+			if deny.peers.contains(peer) {
+				issues.error(
+					"server.p2p_config.peers_deny",
+					format!("peer {} is in both peers_preferred and peers_deny", peer),
+				);
+			}
+		}
+	}
+
+	if let (Some(preferred), Some(allow)) = (p2p.peers_preferred.as_ref(), p2p.peers_allow.as_ref())
+	{
+		for peer in &preferred.peers {
+			if !allow.peers.contains(peer) {
+				issues.warning(
+					"server.p2p_config.peers_preferred",
+					format!(
+						"preferred peer {} is not in peers_allow, node will not connect to it",
+						peer
+					),
+				);
+			}
+		}
+	}
+
+	for sync_mode in &[false, true] {
+		let max_outbound = p2p.peer_max_outbound_count(*sync_mode);
+		let min_preferred = p2p.peer_min_preferred_outbound_count(*sync_mode);
+		if min_preferred > max_outbound {
+			issues.warning(
+				"server.p2p_config.peer_min_preferred_outbound_count",
+				format!(
+					"min preferred outbound count {} is greater than max outbound count {}",
+					min_preferred, max_outbound
+				),
+			);
+			break;
+		}
+	}
+}
+
+fn check_fee_base(server: &ServerConfig, issues: &mut Issues) {
+	if let Some(fee_base) = server.pool_config.tx_fee_base {
+		if fee_base == 0 {
+			issues.warning(
+				"server.pool_config.tx_fee_base",
+				"zero fee base, the pool will accept transactions without fees".to_string(),
+			);
+		} else if fee_base < global::DEFAULT_ACCEPT_FEE_BASE {
+			issues.warning(
+				"server.pool_config.tx_fee_base",
+				format!(
+					"fee base {} is lower than the network default {}, transactions accepted by this node might not be relayed by other peers",
+					fee_base,
+					global::DEFAULT_ACCEPT_FEE_BASE
+				),
+			);
+		} else if fee_base > MAX_SANE_FEE_BASE {
+			issues.warning(
+				"server.pool_config.tx_fee_base",
+				format!(
+					"fee base {} is much higher than the network default {}, most transactions will be rejected",
+					fee_base,
+					global::DEFAULT_ACCEPT_FEE_BASE
+				),
+			);
+		}
+	}
+}
+
+fn check_cut_through_horizon(server: &ServerConfig, issues: &mut Issues) {
+	if let Some(horizon) = server.cut_through_horizon {
+		let (min, max) = global::cut_through_horizon_bounds(server.chain_type);
+		if horizon < min || horizon > max {
+			issues.error(
+				"server.cut_through_horizon",
+				format!(
+					"cut through horizon {} is out of the valid range {}..{}",
+					horizon, min, max
+				),
+			);
+		}
+	}
+}
+
+fn check_api_auth(server: &ServerConfig, issues: &mut Issues) {
+	let is_local = server
+		.api_http_addr
+		.parse::<SocketAddr>()
+		.map(|addr| addr.ip().is_loopback())
+		.unwrap_or(true);
+
+	match server.api_secret_path.as_ref() {
+		Some(path) => check_secret_file("server.api_secret_path", path, issues),
+		None => {
+			if is_local {
+				issues.warning(
+					"server.api_secret_path",
+					"owner API is not protected with a secret".to_string(),
+				);
+			} else {
+				issues.error(
+					"server.api_secret_path",
+					format!(
+						"owner API is listening on {} without a secret, anybody can manage this node",
+						server.api_http_addr
+					),
+				);
+			}
+		}
+	}
+
+	match server.foreign_api_secret_path.as_ref() {
+		Some(path) => check_secret_file("server.foreign_api_secret_path", path, issues),
+		None => {
+			if !is_local {
+				issues.warning(
+					"server.foreign_api_secret_path",
+					"foreign API is not protected with a secret".to_string(),
+				);
+			}
+		}
+	}
+
+	match (
+		server.tls_certificate_file.as_ref(),
+		server.tls_certificate_key.as_ref(),
+	) {
+		(Some(cert), Some(key)) => {
+			for (name, file) in &[
+				("server.tls_certificate_file", cert),
+				("server.tls_certificate_key", key),
+			] {
+				if !Path::new(file).exists() {
+					issues.error(name, format!("file {} not found", file));
+				}
+			}
+		}
+		(Some(_), None) => issues.error(
+			"server.tls_certificate_key",
+			"tls_certificate_file is set, but tls_certificate_key is missing".to_string(),
+		),
+		(None, Some(_)) => issues.error(
+			"server.tls_certificate_file",
+			"tls_certificate_key is set, but tls_certificate_file is missing".to_string(),
+		),
+		(None, None) => {
+			if !is_local {
+				issues.warning(
+					"server.tls_certificate_file",
+					format!(
+						"API is listening on {} without TLS, secrets are sent in clear text",
+						server.api_http_addr
+					),
+				);
+			}
+		}
+	}
+}
+
+fn check_stratum_auth(server: &ServerConfig, issues: &mut Issues) {
+	let stratum = match server.stratum_mining_config.as_ref() {
+		Some(stratum) if stratum.enable_stratum_server.unwrap_or(false) => stratum,
+		_ => return,
+	};
+
+	match (
+		stratum.tls_certificate_file.as_ref(),
+		stratum.tls_certificate_key.as_ref(),
+	) {
+		(Some(cert), Some(key)) => {
+			for (name, file) in &[
+				("server.stratum_mining_config.tls_certificate_file", cert),
+				("server.stratum_mining_config.tls_certificate_key", key),
+			] {
+				if !Path::new(file).exists() {
+					issues.error(name, format!("file {} not found", file));
+				}
+			}
+		}
+		(Some(_), None) => issues.error(
+			"server.stratum_mining_config.tls_certificate_key",
+			"tls_certificate_file is set, but tls_certificate_key is missing".to_string(),
+		),
+		(None, Some(_)) => issues.error(
+			"server.stratum_mining_config.tls_certificate_file",
+			"tls_certificate_key is set, but tls_certificate_file is missing".to_string(),
+		),
+		(None, None) => {}
+	}
+
+	if let Some(payouts) = stratum.coinbase_payouts.as_ref() {
+		check_coinbase_payouts(payouts, issues);
+	}
+
+	if let Some(workers) = stratum.workers.as_ref() {
+		if workers.is_empty() {
+			issues.warning(
+				"server.stratum_mining_config.workers",
+				"workers list is empty, no worker is able to log in".to_string(),
+			);
+		}
+		for (i, worker) in workers.iter().enumerate() {
+			if workers.iter().skip(i + 1).any(|w| w.login == worker.login) {
+				issues.error(
+					"server.stratum_mining_config.workers",
+					format!("worker {} is registered more than once", worker.login),
+				);
+			}
+			check_secret_file(
+				"server.stratum_mining_config.workers",
+				&worker.password_path,
+				issues,
+			);
+		}
+	}
+}
+
+fn check_coinbase_payouts(payouts: &[CoinbasePayout], issues: &mut Issues) {
+	let section = "server.stratum_mining_config.coinbase_payouts";
+	if payouts.is_empty() {
+		issues.error(section, "coinbase_payouts list is empty".to_string());
+		return;
+	}
+	for payout in payouts {
+		if !(payout.percent > 0.0 && payout.percent <= 100.0) {
+			issues.error(
+				section,
+				format!(
+					"payout to {} has percent {}, expected a value in (0, 100]",
+					payout.wallet_listener_url, payout.percent
+				),
+			);
+		}
+		if !payout.wallet_listener_url.starts_with("http://")
+			&& !payout.wallet_listener_url.starts_with("https://")
+		{
+			issues.error(
+				section,
+				format!(
+					"'{}' is not a valid wallet listener url",
+					payout.wallet_listener_url
+				),
+			);
+		}
+	}
+	let total: f64 = payouts.iter().map(|p| p.percent).sum();
+	if (total - 100.0).abs() > 1e-6 {
+		issues.error(
+			section,
+			format!("payout percents sum up to {}, expected 100", total),
+		);
+	}
+}
+
+fn check_secret_file(section: &str, path: &str, issues: &mut Issues) {
+	match fs::read_to_string(path) {
+		Ok(secret) => {
+			let first_line = secret.lines().next().unwrap_or("").trim().to_string();
+			if first_line.is_empty() {
+				issues.error(section, format!("secret file {} is empty", path));
+			} else if first_line.len() < 10 {
+				issues.warning(
+					section,
+					format!("secret in file {} is too short, it is easy to guess", path),
+				);
+			}
+		}
+		Err(e) => issues.error(
+			section,
+			format!("unable to read secret file {}, {}", path, e),
+		),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn test_config() -> GlobalConfig {
+		let mut config = GlobalConfig::for_chain(&global::ChainTypes::Floonet);
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.api_secret_path = None;
+		server.foreign_api_secret_path = None;
+		config
+	}
+
+	fn has_issue(issues: &[ConfigIssue], level: IssueLevel, section: &str) -> bool {
+		issues
+			.iter()
+			.any(|i| i.level == level && i.section == section)
+	}
+
+	#[test]
+	fn test_default_config_has_no_errors() {
+		let issues = check_config(&test_config());
+		assert!(issues.iter().all(|i| i.level == IssueLevel::Warning));
+	}
+
+	#[test]
+	fn test_port_conflict() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.api_http_addr = format!("127.0.0.1:{}", server.p2p_config.port);
+		let issues = check_config(&config);
+		assert!(has_issue(
+			&issues,
+			IssueLevel::Error,
+			"server.p2p_config.port"
+		));
+	}
+
+	#[test]
+	fn test_external_tor_without_onion() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.tor_config.tor_enabled = true;
+		server.tor_config.tor_external = true;
+		server.tor_config.onion_address = None;
+		let issues = check_config(&config);
+		assert!(has_issue(
+			&issues,
+			IssueLevel::Error,
+			"server.tor_config.onion_address"
+		));
+	}
+
+	#[test]
+	fn test_external_tor_with_control_port() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.tor_config.tor_enabled = true;
+		server.tor_config.tor_external = true;
+		server.tor_config.onion_address = None;
+		server.tor_config.control_port = Some(9051);
+		let issues = check_config(&config);
+		assert!(!has_issue(
+			&issues,
+			IssueLevel::Error,
+			"server.tor_config.onion_address"
+		));
+	}
+
+	#[test]
+	fn test_cut_through_horizon_bounds() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		let (min, max) = global::cut_through_horizon_bounds(server.chain_type);
+		server.cut_through_horizon = Some(max);
+		assert!(!has_issue(
+			&check_config(&config),
+			IssueLevel::Error,
+			"server.cut_through_horizon"
+		));
+
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.cut_through_horizon = Some(min - 1);
+		assert!(has_issue(
+			&check_config(&config),
+			IssueLevel::Error,
+			"server.cut_through_horizon"
+		));
+	}
+
+	#[test]
+	fn test_public_api_without_secret() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		server.api_http_addr = "0.0.0.0:13413".to_string();
+		let issues = check_config(&config);
+		assert!(has_issue(
+			&issues,
+			IssueLevel::Error,
+			"server.api_secret_path"
+		));
+	}
+
+	#[test]
+	fn test_stratum_tls_without_key() {
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		let stratum = server.stratum_mining_config.as_mut().unwrap();
+		stratum.enable_stratum_server = Some(true);
+		stratum.tls_certificate_file = Some("stratum.crt".to_string());
+		let issues = check_config(&config);
+		assert!(has_issue(
+			&issues,
+			IssueLevel::Error,
+			"server.stratum_mining_config.tls_certificate_key"
+		));
+	}
+
+	#[test]
+	fn test_coinbase_payouts_sum() {
+		let payout = |percent: f64| CoinbasePayout {
+			wallet_listener_url: "http://127.0.0.1:3415".to_string(),
+			percent,
+		};
+		let mut config = test_config();
+		let server = &mut config.members.as_mut().unwrap().server;
+		let stratum = server.stratum_mining_config.as_mut().unwrap();
+		stratum.enable_stratum_server = Some(true);
+		stratum.coinbase_payouts = Some(vec![payout(97.5), payout(2.5)]);
+		assert!(!has_issue(
+			&check_config(&config),
+			IssueLevel::Error,
+			"server.stratum_mining_config.coinbase_payouts"
+		));
+
+		let server = &mut config.members.as_mut().unwrap().server;
+		let stratum = server.stratum_mining_config.as_mut().unwrap();
+		stratum.coinbase_payouts = Some(vec![payout(90.0), payout(2.5)]);
+		assert!(has_issue(
+			&check_config(&config),
+			IssueLevel::Error,
+			"server.stratum_mining_config.coinbase_payouts"
+		));
+	}
+}
@@ -42,6 +42,10 @@ fn comments() -> HashMap<String, String> {
 ### SERVER CONFIGURATION              ###
 #########################################
 
+#The config is read again on SIGHUP or by the owner API reload_config. The peer counts,
+#log levels, dandelion_config, rate_limit_config and tx_fee_base are applied at runtime,
+#other changes require the node restart.
+
 #Server connection details
 "
 		.to_string(),
@@ -54,6 +58,10 @@ fn comments() -> HashMap<String, String> {
 #tls_certificate_file = \"\"
 #private key for the TLS certificate
 #tls_certificate_key = \"\"
+#CA certificates for the client certificates, the owner scoped routes (v2 Owner API,
+#stratum, mining, peers ban, compaction, chain validation, txhashset) require the client
+#certificate signed by this CA
+#tls_client_ca_file = \"\"
 
 #the address on which services will listen, e.g. Transaction Pool
 "
@@ -87,6 +95,16 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"db_backend".to_string(),
+		"
+#key-value backend of the chain and the peer stores, \"lmdb\" (default) or \"rocksdb\".
+#rocksdb requires the node built with the rocksdb feature. The existing data
+#is not converted, switching the backend needs a fresh db_root or a resync
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"chain_type".to_string(),
 		"
@@ -111,6 +129,16 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"run_mode".to_string(),
+		"
+#node run mode, \"full\" (default) or \"header_only\". Headers only node syncs and
+#validates the headers only, the blocks and txhashset are not downloaded and the
+#transactions are not accepted. Mining is not available.
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"archive_mode".to_string(),
 		"
@@ -119,6 +147,67 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"archive_depth".to_string(),
+		"
+#number of the recent full blocks to keep on a pruned node, if it is larger than
+#the cut-through horizon (one week of blocks). The depth is advertised to the peers,
+#so they can sync old blocks from us. It applies going forward, a freshly synced
+#node grows its history up to this depth over time.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"enable_index".to_string(),
+		"
+#maintain the kernel excess and output commitment index, so the kernel and output
+#lookups don't scan the kernel MMR. The index covers the blocks accepted after it is
+#enabled, it needs extra disk space.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"history_backfill".to_string(),
+		"
+#backfill the full blocks history of a pruned node from the peers in the background,
+#without a resync. The blocks are verified against the headers and kept. Once the
+#history reaches genesis, the node runs and advertises itself as the full archive.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"cut_through_horizon".to_string(),
+		"
+#cut through horizon in blocks for a pruned node, the txhashset is compacted and the
+#full blocks are kept up to that depth. Larger horizon needs more disk space, but the
+#node can handle deeper reorgs. Can't be lower than the network horizon (one week of
+#blocks), max is 52 weeks. Default is the network horizon.
+#cut_through_horizon = 10080
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"txhashset_zip_prebuild".to_string(),
+		"
+#build the txhashset zip for syncing peers in advance, every time the archive
+#header changes (every 12 hours), instead of on the first peer request
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"compress_old_blocks".to_string(),
+		"
+#compress the stored blocks below the cut-through horizon in the background,
+#saves the disk space of the archive nodes. The compressed blocks are read as usual
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"skip_sync_wait".to_string(),
 		"
@@ -160,6 +249,71 @@ fn comments() -> HashMap<String, String> {
                 .to_string(),
 	);
 
+	retval.insert(
+		"fork_alert_depth".to_string(),
+		"
+#forks that the peers are on and that are longer than that many blocks are reported
+#with the status API and the fork_detected_url webhook
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_reorg_depth".to_string(),
+		"
+#reorgs deeper than that many blocks are not applied until the operator approves
+#the fork with the owner API approve_reorg call (see get_pending_reorgs)
+#max_reorg_depth = 60
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"validation_threads".to_string(),
+		"
+#number of threads verifying the rangeproofs and the kernel signatures of the new
+#blocks, the number of CPUs by default. 1 verifies them in the chain thread.
+#validation_threads = 4
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"verifier_cache_size".to_string(),
+		"
+#number of the verified kernel signatures and of the rangeproofs kept in the cache.
+#the transactions verified by the pool are not verified again with the block. 0 disables it
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"stale_tip_timeout_minutes".to_string(),
+		"
+#if the chain tip doesn't advance for that many minutes while the peers report more
+#work, the stuck peers are dropped and the headers sync is restarted. 0 disables it.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"use_checkpoints".to_string(),
+		"
+#Checkpoint headers and their ancestors skip the PoW verification during the initial sync.
+#Set to false (or run with --no-checkpoints) to verify every header.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"checkpoints".to_string(),
+		"
+#Additional header checkpoints 'height:hash', override the built-in ones at the same height.
+#checkpoints = [\"1000000:735cf2a4492b437e292a295549c31df5f1e8e6d09e58ed20abdd808c2261d1f1\"]
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"libp2p_enabled".to_string(),
 		"
@@ -195,6 +349,17 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"tx_generator_rate".to_string(),
+		"
+#Developer load testing (usertesting and floonet only). Number of synthetic
+#transactions per minute that the node generates and pushes into its own pool,
+#relay latency to the peers is logged every minute. Transactions are funded by
+#the test miner running without test_miner_wallet_url. 0 disables the generator
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.webhook_config]".to_string(),
 		"
@@ -220,6 +385,9 @@ fn comments() -> HashMap<String, String> {
 #The url where a POST request will be sent when a new block is received by a peer.
 #block_received_url = \"http://127.0.0.1:8080/block\"
 
+#The url where a POST request will be sent when a peer is on a fork longer than fork_alert_depth.
+#fork_detected_url = \"http://127.0.0.1:8080/fork\"
+
 #The number of worker threads that will be assigned to making the http requests.
 "
 		.to_string(),
@@ -233,6 +401,132 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"[server.webhook_dispatcher_config]".to_string(),
+		"
+#########################################
+### WEBHOOK DISPATCHER CONFIGURATION  ###
+#########################################
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_retries".to_string(),
+		"
+#The events are POSTed as JSON {\"event\", \"timestamp\", \"data\"} to every endpoint
+#subscribed to them. Events: block, reorg, sync_complete, peer_banned, kernel_accepted.
+#The failed deliveries are retried, the delay is doubled after every attempt.
+#Number of the retries after the first attempt.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"retry_delay_secs".to_string(),
+		"
+#Delay before the first retry in seconds.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"request_timeout".to_string(),
+		"
+#The timeout of the http request in seconds.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"queue_size".to_string(),
+		"
+#Number of the deliveries waiting in the queue, the oldest ones are dropped above it.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"watched_kernels".to_string(),
+		"
+#Kernel excesses (hex) that trigger the kernel_accepted event when the transaction
+#is accepted by the pool.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"endpoints".to_string(),
+		"
+#Endpoints, with the optional secret the X-Mwc-Signature header is sha256=<hex HMAC-SHA256>
+#of the payload. All events are delivered if the events list is empty.
+#[[server.webhook_dispatcher_config.endpoints]]
+#url = \"https://127.0.0.1:8080/events\"
+#secret = \"change_me\"
+#events = [\"block\", \"reorg\"]
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.disk_monitor_config]".to_string(),
+		"
+#########################################
+### DISK MONITOR CONFIGURATION        ###
+#########################################
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"min_free_mb".to_string(),
+		"
+#The chain data size and the free disk space are tracked. The PIBD or the txhashset
+#archive download doesn't start if the projected state size doesn't fit.
+#Hard floor of the free disk space in MB, the new blocks are not accepted below it
+#until the space is freed.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"check_interval_secs".to_string(),
+		"
+#Disk usage is checked that often, in seconds.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.zmq_config]".to_string(),
+		"
+#########################################
+### ZEROMQ NOTIFICATIONS              ###
+#########################################
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"high_water_mark".to_string(),
+		"
+#ZeroMQ PUB endpoints, the node must be built with the zmq feature. Every message
+#has 3 parts: the topic, the body and the 4 bytes little endian sequence number.
+
+#Topic \"hashblock\", the hash of the block accepted by our node.
+#pub_hash_block = \"tcp://127.0.0.1:28332\"
+
+#Topic \"rawblock\", the serialized block accepted by our node.
+#pub_raw_block = \"tcp://127.0.0.1:28332\"
+
+#Topic \"txkernel\", the serialized kernel of the transaction accepted by our pool.
+#pub_tx_kernel = \"tcp://127.0.0.1:28333\"
+
+#The number of the messages queued per subscriber, the slow subscriber loses the rest.
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.dandelion_config]".to_string(),
 		"
@@ -353,6 +647,45 @@ fn comments() -> HashMap<String, String> {
 #amount of incoming connections temporarily allowed to exceed peer_max_inbound_count
 #peer_listener_buffer_count = 8
 
+#maximum number of peers connected from the same IP address (nodes behind one NAT)
+#peer_max_per_ip = 1
+
+#inbound connections rate limit for a single IP address: connections per minute
+#and the burst allowed at once. The address is banned if more than
+#inbound_conn_ban_threshold of its connections are rejected within a minute
+#(0 disables the ban). Loopback addresses, including inbound Tor, are not limited.
+#inbound_conn_rate = 10
+#inbound_conn_burst = 20
+#inbound_conn_ban_threshold = 60
+
+#transactions and tx announcements rate limit for a single peer: messages per minute
+#and the burst allowed at once. The same transaction is not validated again for two
+#minutes. Peers that exceed the rate or repeat the same transactions get misbehavior
+#points and are banned when the points add up.
+#tx_relay_rate = 600
+#tx_relay_burst = 500
+
+#relay the header of a new block to all peers as soon as the header is valid,
+#before the full block is validated. Reduces block propagation time for miners.
+#header_first_relay = false
+
+#per peer send queue sizes. Every message priority has its own queue, control
+#messages (pings, bans) are sent first, then blocks and transactions, then sync data.
+#Sync data still gets a share of the writes when the relay never stops.
+#A relay or sync message is dropped if the queue of its priority is full. Control
+#messages wait for the space, the peer that doesn't read them is disconnected.
+#send_queue_control_cap = 16
+#send_queue_consensus_cap = 32
+#send_queue_bulk_cap = 40
+
+#total bandwidth limit for all peers and both directions, KB/s (0 is unlimited).
+#bandwidth_relay_ratio percent of it is reserved for the blocks and transactions
+#relay: sync traffic is paused when it would eat into the reserve, so the initial
+#sync can't starve the relay on a limited link. Both can be changed at runtime
+#with the owner API set_bandwidth_limits.
+#bandwidth_limit = 0
+#bandwidth_relay_ratio = 30
+
 # A preferred dandelion_peer, mainly used for testing dandelion
 # dandelion_peer = \"10.0.0.1:13144\"
 
@@ -391,6 +724,66 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"max_pool_weight".to_string(),
+		"
+#maximum total weight of the transactions in the pool. When the pool is full, by
+#the number of transactions or by the weight, the lowest fee rate transactions are
+#evicted and the new transaction has to pay more than the evicted ones
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_ancestors".to_string(),
+		"
+#maximum number of the pool transactions that a new transaction depends on, directly
+#or through the other pool transactions
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_ancestors_weight".to_string(),
+		"
+#maximum weight of a new transaction together with the pool transactions it depends on
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_descendants".to_string(),
+		"
+#maximum number of the pool transactions that depend on a pool transaction
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_descendants_weight".to_string(),
+		"
+#maximum weight of a pool transaction together with the pool transactions that depend on it
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_orphan_txs".to_string(),
+		"
+#maximum number of the relayed transactions that spend the outputs of the transactions
+#that were not seen yet. They are added to the pool when the parent arrives
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_orphan_bytes".to_string(),
+		"
+#maximum total size of the orphan transactions in bytes
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"mineable_max_weight".to_string(),
 		"
@@ -428,6 +821,9 @@ fn comments() -> HashMap<String, String> {
 	retval.insert(
 		"attempt_time_per_block".to_string(),
 		"
+#what port and address for the stratum WebSocket listener, disabled if not set
+#stratum_ws_server_addr = \"127.0.0.1:3418\"
+
 #the amount of time, in seconds, to attempt to mine on a particular
 #header before stopping and re-collecting transactions from the pool
 "
@@ -530,6 +926,43 @@ fn comments() -> HashMap<String, String> {
 			.to_string(),
 	);
 
+	retval.insert(
+		"vardiff_enabled".to_string(),
+		"
+#retarget every worker share difficulty toward vardiff_shares_per_minute, for the mixed mining fleets
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"vardiff_shares_per_minute".to_string(),
+		"
+#goal of the worker shares rate for the variable difficulty, shares per minute
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"vardiff_retarget_s".to_string(),
+		"
+#TLS certificate and its private key for the stratum listener, the miners have to
+#connect with TLS if they are set
+#tls_certificate_file = \"\"
+#tls_certificate_key = \"\"
+
+#registered worker logins, the password or the token is the first line of the file.
+#If set, only these workers can log in and get the jobs
+#workers = [{login = \"rig1\", password_path = \"/home/mwc/.mwc/rig1_secret\"}]
+
+#split of the block reward between several wallets instead of wallet_listener_url, the
+#percents sum up to 100. The payout wallets must support the coinbase amount
+#coinbase_payouts = [{wallet_listener_url = \"http://127.0.0.1:3415\", percent = 97.5}, {wallet_listener_url = \"http://10.0.0.5:3415\", percent = 2.5}]
+
+#how often the worker difficulty is retargeted, in seconds
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[logging]".to_string(),
 		"
@@ -605,6 +1038,22 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"log_json".to_string(),
+		"
+#log levels of the modules, they are used instead of stdout_log_level and file_log_level.
+#The levels can be changed at runtime with the owner API set_log_level. The table goes
+#after all the other logging settings:
+#[logging.module_log_levels]
+#mwc_p2p = \"Debug\"
+#\"mwc_servers::mwc::sync\" = \"Trace\"
+
+#whether to write the stdout and file logs as JSON lines (time, level, module, thread,
+#message and the record fields like peer, height, event) for the log pipelines
+"
+		.to_string(),
+	);
+
 	retval
 }
 
@@ -41,7 +41,8 @@ pub const API_SECRET_FILE_NAME: &str = ".api_secret";
 /// Foreign API secret
 pub const FOREIGN_API_SECRET_FILE_NAME: &str = ".foreign_api_secret";
 
-fn get_mwc_path(chain_type: &global::ChainTypes) -> Result<PathBuf, ConfigError> {
+/// Node home directory for the chain type, created if doesn't exist
+pub fn get_mwc_path(chain_type: &global::ChainTypes) -> Result<PathBuf, ConfigError> {
 	// Check if mwc dir exists
 	let mut mwc_path = match dirs::home_dir() {
 		Some(p) => p,
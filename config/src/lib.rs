@@ -29,9 +29,13 @@ use mwc_p2p as p2p;
 use mwc_servers as servers;
 use mwc_util as util;
 
+pub mod check;
 mod comments;
 pub mod config;
+pub mod setup;
 pub mod types;
 
+pub use crate::check::{check_config, ConfigIssue, IssueLevel};
 pub use crate::config::initial_setup_server;
+pub use crate::setup::{ResourceProfile, SetupOptions, TorMode};
 pub use crate::types::{ConfigError, ConfigMembers, GlobalConfig};
@@ -0,0 +1,203 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Building of a tuned node configuration from the answers of the first run
+//! setup (`mwc server init`).
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_mwc_path, init_api_secret};
+use crate::core::global;
+use crate::types::{ConfigError, GlobalConfig};
+
+/// How the node should use Tor
+#[derive(Debug, Clone, PartialEq)]
+pub enum TorMode {
+	/// Tor is not used, clearnet only
+	Disabled,
+	/// Node starts and manages its own Tor process
+	Embedded,
+	/// Tor is running externally, node uses provided socks port and onion address
+	External {
+		/// Socks port of the external Tor
+		socks_port: u16,
+		/// Onion address that external Tor is publishing for this node
+		onion_address: String,
+	},
+}
+
+/// How much of the host resources the node is allowed to use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceProfile {
+	/// Small devices and slow connections
+	Low,
+	/// Defaults, good for most desktops
+	Standard,
+	/// Well connected servers that want to serve many peers
+	High,
+}
+
+/// Answers from the setup wizard
+#[derive(Debug, Clone)]
+pub struct SetupOptions {
+	/// Network to run on
+	pub chain_type: global::ChainTypes,
+	/// Tor usage
+	pub tor: TorMode,
+	/// Keep full history (archive node) instead of pruned
+	pub archive_mode: bool,
+	/// Bind API to all interfaces instead of localhost only
+	pub public_api: bool,
+	/// Resource usage profile
+	pub profile: ResourceProfile,
+}
+
+/// Build node configuration for the setup options. Paths are updated relative to `mwc_home`.
+pub fn build_setup_config(options: &SetupOptions, mwc_home: &Path) -> GlobalConfig {
+	let mut config = GlobalConfig::for_chain(&options.chain_type);
+	config.update_paths(&mwc_home.to_path_buf());
+
+	let server = &mut config.members.as_mut().unwrap().server;
+	server.archive_mode = Some(options.archive_mode);
+
+	if options.public_api {
+		let port = server
+			.api_http_addr
+			.rsplit(':')
+			.next()
+			.unwrap_or("3413")
+			.to_string();
+		server.api_http_addr = format!("0.0.0.0:{}", port);
+	}
+
+	match &options.tor {
+		TorMode::Disabled => {
+			server.tor_config.tor_enabled = false;
+			server.tor_config.tor_external = false;
+		}
+		TorMode::Embedded => {
+			server.tor_config.tor_enabled = true;
+			server.tor_config.tor_external = false;
+		}
+		TorMode::External {
+			socks_port,
+			onion_address,
+		} => {
+			server.tor_config.tor_enabled = true;
+			server.tor_config.tor_external = true;
+			server.tor_config.socks_port = *socks_port;
+			server.tor_config.onion_address = Some(onion_address.clone());
+		}
+	}
+
+	let p2p = &mut server.p2p_config;
+	match options.profile {
+		ResourceProfile::Low => {
+			p2p.peer_max_inbound_count = Some(16);
+			p2p.peer_max_outbound_count = Some(6);
+			p2p.peer_min_preferred_outbound_count = Some(4);
+			p2p.peer_listener_buffer_count = Some(4);
+			server.pool_config.max_pool_size = 20_000;
+			server.pool_config.max_stempool_size = 5_000;
+		}
+		ResourceProfile::Standard => {}
+		ResourceProfile::High => {
+			p2p.peer_max_inbound_count = Some(256);
+			p2p.peer_max_outbound_count = Some(16);
+			p2p.peer_min_preferred_outbound_count = Some(12);
+			p2p.peer_listener_buffer_count = Some(16);
+		}
+	}
+
+	config
+}
+
+/// Generate config and api secret files for the setup options at the default node
+/// home directory. Returns the path of the written config file.
+/// Existing config file is overwritten only if `overwrite` is true, existing api
+/// secrets are always kept.
+pub fn write_setup_config(
+	options: &SetupOptions,
+	config_file_name: &str,
+	overwrite: bool,
+) -> Result<PathBuf, ConfigError> {
+	let mwc_path = get_mwc_path(&options.chain_type)?;
+	let mut config_path = mwc_path.clone();
+	config_path.push(config_file_name);
+	if config_path.exists() && !overwrite {
+		return Err(ConfigError::FileIOError(
+			config_path.to_str().unwrap_or("").to_string(),
+			"config file already exists".to_string(),
+		));
+	}
+
+	let mut config = build_setup_config(options, &mwc_path);
+	let server = &config.members.as_ref().unwrap().server;
+	let secret_paths = [
+		server.api_secret_path.clone(),
+		server.foreign_api_secret_path.clone(),
+	];
+	for secret_path in secret_paths.iter().flatten() {
+		let secret_path = PathBuf::from(secret_path);
+		if !secret_path.exists() {
+			init_api_secret(&secret_path)?;
+		}
+	}
+
+	config.write_to_file(config_path.to_str().unwrap())?;
+	Ok(config_path)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn options() -> SetupOptions {
+		SetupOptions {
+			chain_type: global::ChainTypes::Floonet,
+			tor: TorMode::Disabled,
+			archive_mode: false,
+			public_api: false,
+			profile: ResourceProfile::Standard,
+		}
+	}
+
+	#[test]
+	fn test_setup_defaults() {
+		let config = build_setup_config(&options(), Path::new("/tmp/mwc"));
+		let server = &config.members.as_ref().unwrap().server;
+		assert_eq!(server.api_http_addr, "127.0.0.1:13413");
+		assert_eq!(server.archive_mode, Some(false));
+		assert!(!server.tor_config.tor_enabled);
+	}
+
+	#[test]
+	fn test_setup_public_api_external_tor() {
+		let mut options = options();
+		options.public_api = true;
+		options.archive_mode = true;
+		options.profile = ResourceProfile::Low;
+		options.tor = TorMode::External {
+			socks_port: 9050,
+			onion_address: "abc.onion".to_string(),
+		};
+		let config = build_setup_config(&options, Path::new("/tmp/mwc"));
+		let server = &config.members.as_ref().unwrap().server;
+		assert_eq!(server.api_http_addr, "0.0.0.0:13413");
+		assert_eq!(server.archive_mode, Some(true));
+		assert!(server.tor_config.tor_enabled && server.tor_config.tor_external);
+		assert_eq!(server.tor_config.socks_port, 9050);
+		assert_eq!(server.p2p_config.peer_max_inbound_count, Some(16));
+	}
+}
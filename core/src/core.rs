@@ -24,6 +24,7 @@ pub mod id;
 pub mod merkle_proof;
 pub mod pmmr;
 pub mod transaction;
+pub mod verifier_cache;
 
 use crate::consensus::MWC_BASE;
 use util::secp::pedersen::Commitment;
@@ -20,8 +20,8 @@ use crate::core::committed::{self, Committed};
 use crate::core::compact_block::CompactBlock;
 use crate::core::hash::{DefaultHashable, Hash, Hashed, ZERO_HASH};
 use crate::core::{
-	pmmr, transaction, Commitment, Inputs, KernelFeatures, Output, Transaction, TransactionBody,
-	TxKernel, Weighting,
+	pmmr, transaction, verifier_cache, Commitment, Inputs, KernelFeatures, Output, Transaction,
+	TransactionBody, TxKernel, Weighting,
 };
 use crate::global;
 use crate::pow::{verify_size, Difficulty, Proof, ProofOfWork};
@@ -793,7 +793,39 @@ impl Block {
 		secp: &Secp256k1,
 	) -> Result<Commitment, Error> {
 		self.body.validate(Weighting::AsBlock, secp)?;
+		self.validate_rules(prev_kernel_offset, secp)
+	}
+
+	/// Same as `validate` but without the rangeproofs and the kernel signatures
+	/// verification. The caller is responsible for verifying them with
+	/// `verify_proofs` and `verify_kernel_signatures`, for example on the other threads.
+	pub fn validate_without_signatures(
+		&self,
+		prev_kernel_offset: &BlindingFactor,
+		secp: &Secp256k1,
+	) -> Result<Commitment, Error> {
+		self.body.validate_read(Weighting::AsBlock)?;
+		self.validate_rules(prev_kernel_offset, secp)
+	}
+
+	/// Batch verify the rangeproofs of the outputs, skipping the ones in the verifier cache
+	pub fn verify_proofs(outputs: &[Output], secp: &Secp256k1) -> Result<(), Error> {
+		verifier_cache::verify_rangeproofs(outputs, secp)?;
+		Ok(())
+	}
+
+	/// Batch verify the kernel signatures, skipping the ones in the verifier cache
+	pub fn verify_kernel_signatures(kernels: &[TxKernel], secp: &Secp256k1) -> Result<(), Error> {
+		verifier_cache::verify_kernel_signatures(kernels, secp)?;
+		Ok(())
+	}
 
+	// Validation of the block rules on top of the transaction body
+	fn validate_rules(
+		&self,
+		prev_kernel_offset: &BlindingFactor,
+		secp: &Secp256k1,
+	) -> Result<Commitment, Error> {
 		self.verify_kernel_lock_heights()?;
 		self.verify_nrd_kernels_for_header_version()?;
 		self.verify_coinbase(secp)?;
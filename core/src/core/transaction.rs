@@ -17,7 +17,7 @@
 
 use crate::core::block::HeaderVersion;
 use crate::core::hash::{DefaultHashable, Hashed};
-use crate::core::{committed, Committed};
+use crate::core::{committed, verifier_cache, Committed};
 use crate::global::get_accept_fee_base;
 use crate::libtx::{aggsig, secp_ser};
 use crate::ser::{
@@ -1301,18 +1301,10 @@ impl TransactionBody {
 		self.validate_read(weighting)?;
 
 		// Now batch verify all those unverified rangeproofs
-		if !self.outputs.is_empty() {
-			let mut commits = vec![];
-			let mut proofs = vec![];
-			for x in &self.outputs {
-				commits.push(x.commitment());
-				proofs.push(x.proof);
-			}
-			Output::batch_verify_proofs(&commits, &proofs, secp)?;
-		}
+		verifier_cache::verify_rangeproofs(&self.outputs, secp)?;
 
 		// Verify the unverified tx kernels.
-		TxKernel::batch_sig_verify(&self.kernels, secp)?;
+		verifier_cache::verify_kernel_signatures(&self.kernels, secp)?;
 		Ok(())
 	}
 }
@@ -0,0 +1,148 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cache of the verified kernel signatures and rangeproofs. A transaction verified by the
+//! pool at the relay time is not verified again when it comes with the block.
+//! The kernel is keyed by its hash (features, excess and signature), the rangeproof by
+//! the hash of the output commitment with the proof, so a changed signature or proof
+//! is always a cache miss. Only the successful verifications are cached.
+
+use crate::core::hash::{Hash, Hashed};
+use crate::core::transaction::{Error, Output, TxKernel};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use util::secp::Secp256k1;
+use util::Mutex;
+
+/// Default number of the kernels and of the rangeproofs in the cache
+pub const DEFAULT_VERIFIER_CACHE_SIZE: usize = 50_000;
+
+struct VerifierCache {
+	kernel_sigs: LruCache<Hash, ()>,
+	rangeproofs: LruCache<Hash, ()>,
+}
+
+lazy_static! {
+	// None if the cache is disabled
+	static ref CACHE: Mutex<Option<VerifierCache>> = Mutex::new(new_cache(DEFAULT_VERIFIER_CACHE_SIZE));
+}
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Verifier cache hit and miss counters since the start
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VerifierCacheStats {
+	/// Signatures and rangeproofs found in the cache
+	pub hits: u64,
+	/// Signatures and rangeproofs verified
+	pub misses: u64,
+}
+
+fn new_cache(size: usize) -> Option<VerifierCache> {
+	NonZeroUsize::new(size).map(|size| VerifierCache {
+		kernel_sigs: LruCache::new(size),
+		rangeproofs: LruCache::new(size),
+	})
+}
+
+/// Setup the number of the kernels and of the rangeproofs in the cache. 0 disables it.
+/// The cached entries are dropped.
+pub fn init_verifier_cache(size: usize) {
+	*CACHE.lock() = new_cache(size);
+}
+
+/// Hit and miss counters
+pub fn stats() -> VerifierCacheStats {
+	VerifierCacheStats {
+		hits: HITS.load(Ordering::Relaxed),
+		misses: MISSES.load(Ordering::Relaxed),
+	}
+}
+
+fn rangeproof_key(output: &Output) -> Hash {
+	(output.identifier(), output.proof()).hash()
+}
+
+// Items which keys are not in the cache, with the keys
+fn unverified<T, F>(items: &[T], key: F, kernels: bool) -> Vec<(Hash, &T)>
+where
+	F: Fn(&T) -> Hash,
+{
+	let keyed: Vec<(Hash, &T)> = items.iter().map(|x| (key(x), x)).collect();
+	let res: Vec<(Hash, &T)> = {
+		let mut cache = CACHE.lock();
+		match cache.as_mut() {
+			None => keyed,
+			Some(cache) => {
+				let lru = match kernels {
+					true => &mut cache.kernel_sigs,
+					false => &mut cache.rangeproofs,
+				};
+				keyed
+					.into_iter()
+					.filter(|(k, _)| lru.get(k).is_none())
+					.collect()
+			}
+		}
+	};
+	HITS.fetch_add((items.len() - res.len()) as u64, Ordering::Relaxed);
+	MISSES.fetch_add(res.len() as u64, Ordering::Relaxed);
+	res
+}
+
+fn add_verified(keys: Vec<Hash>, kernels: bool) {
+	if let Some(cache) = CACHE.lock().as_mut() {
+		let lru = match kernels {
+			true => &mut cache.kernel_sigs,
+			false => &mut cache.rangeproofs,
+		};
+		for k in keys {
+			lru.put(k, ());
+		}
+	}
+}
+
+/// Batch verify the kernel signatures that are not in the cache, and cache them
+pub fn verify_kernel_signatures(kernels: &[TxKernel], secp: &Secp256k1) -> Result<(), Error> {
+	let unverified = unverified(kernels, |k| k.hash(), true);
+	if unverified.is_empty() {
+		return Ok(());
+	}
+	let (keys, kernels): (Vec<Hash>, Vec<TxKernel>) =
+		unverified.into_iter().map(|(h, k)| (h, k.clone())).unzip();
+	TxKernel::batch_sig_verify(&kernels, secp)?;
+	add_verified(keys, true);
+	Ok(())
+}
+
+/// Batch verify the rangeproofs of the outputs that are not in the cache, and cache them
+pub fn verify_rangeproofs(outputs: &[Output], secp: &Secp256k1) -> Result<(), Error> {
+	let unverified = unverified(outputs, rangeproof_key, false);
+	if unverified.is_empty() {
+		return Ok(());
+	}
+	let mut keys = Vec::with_capacity(unverified.len());
+	let mut commits = Vec::with_capacity(unverified.len());
+	let mut proofs = Vec::with_capacity(unverified.len());
+	for (h, output) in unverified {
+		keys.push(h);
+		commits.push(output.commitment());
+		proofs.push(output.proof());
+	}
+	Output::batch_verify_proofs(&commits, &proofs, secp)?;
+	add_verified(keys, false);
+	Ok(())
+}
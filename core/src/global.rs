@@ -73,6 +73,9 @@ pub const AUTOMATED_TESTING_CUT_THROUGH_HORIZON: u32 = 70;
 /// Testing cut through horizon in blocks
 pub const USER_TESTING_CUT_THROUGH_HORIZON: u32 = 70;
 
+/// Max configurable cut through horizon, in the default horizons (a year for mainnet)
+pub const MAX_CUT_THROUGH_HORIZON_FACTOR: u32 = 52;
+
 /// Testing state sync threshold in blocks
 pub const TESTING_STATE_SYNC_THRESHOLD: u32 = 20;
 
@@ -193,6 +196,10 @@ lazy_static! {
 	/// If disabled NRD kernels are invalid regardless of header version or block height.
 	pub static ref GLOBAL_NRD_FEATURE_ENABLED: OneTime<bool> = OneTime::new();
 
+	/// Global cut through horizon that can be initialized once on node startup.
+	/// If not initialized, the default horizon for the chain type is used.
+	pub static ref GLOBAL_CUT_THROUGH_HORIZON: OneTime<u32> = OneTime::new();
+
 	/// Running flag for MWC node.
 	pub static ref SERVER_RUNNING: Arc<AtomicBool> =
 			Arc::new(AtomicBool::new(true));
@@ -305,14 +312,13 @@ pub fn set_local_accept_fee_base(new_base: u64) {
 /// Default to mwc-cent/20 if global config unset.
 pub fn get_accept_fee_base() -> u64 {
 	ACCEPT_FEE_BASE.with(|base| match base.get() {
+		// The global value is not cached by the thread, the config reload can change it
 		None => {
-			let base = if GLOBAL_ACCEPT_FEE_BASE.is_init() {
+			if GLOBAL_ACCEPT_FEE_BASE.is_init() {
 				GLOBAL_ACCEPT_FEE_BASE.borrow()
 			} else {
 				DEFAULT_ACCEPT_FEE_BASE
-			};
-			set_local_accept_fee_base(base);
-			base
+			}
 		}
 		Some(base) => base,
 	})
@@ -417,13 +423,39 @@ pub fn max_tx_weight() -> u64 {
 
 /// Horizon at which we can cut-through and do full local pruning
 pub fn cut_through_horizon() -> u32 {
-	match get_chain_type() {
+	if GLOBAL_CUT_THROUGH_HORIZON.is_init() {
+		GLOBAL_CUT_THROUGH_HORIZON.borrow()
+	} else {
+		default_cut_through_horizon(get_chain_type())
+	}
+}
+
+/// Network cut through horizon for the chain type. The peers expect at least that
+/// many full blocks and the txhashset data from us.
+pub fn default_cut_through_horizon(chain_type: ChainTypes) -> u32 {
+	match chain_type {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_CUT_THROUGH_HORIZON,
 		ChainTypes::UserTesting => USER_TESTING_CUT_THROUGH_HORIZON,
 		_ => CUT_THROUGH_HORIZON,
 	}
 }
 
+/// Valid range (min, max) of the configured cut through horizon. Horizon below the
+/// network one would break the PIBD data serving and the reorgs handling.
+pub fn cut_through_horizon_bounds(chain_type: ChainTypes) -> (u32, u32) {
+	let horizon = default_cut_through_horizon(chain_type);
+	(
+		horizon,
+		horizon.saturating_mul(MAX_CUT_THROUGH_HORIZON_FACTOR),
+	)
+}
+
+/// One time initialization of the global cut through horizon.
+/// Will panic if we attempt to re-initialize this (via OneTime).
+pub fn init_global_cut_through_horizon(horizon: u32) {
+	GLOBAL_CUT_THROUGH_HORIZON.init(horizon)
+}
+
 /// Threshold at which we can request a txhashset (and full blocks from)
 pub fn state_sync_threshold() -> u32 {
 	match get_chain_type() {
@@ -0,0 +1,59 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verifier cache tests
+
+pub mod common;
+use crate::common::{tx1i1o, tx1i2o};
+use crate::core::core::verifier_cache;
+use crate::core::core::Weighting;
+use crate::core::global;
+use mwc_core as core;
+use util::secp::{ContextFlag, Secp256k1};
+
+#[test]
+fn test_verifier_cache() {
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let secp = Secp256k1::with_caps(ContextFlag::Commit);
+	verifier_cache::init_verifier_cache(100);
+
+	let tx = tx1i2o();
+	let before = verifier_cache::stats();
+	tx.validate(Weighting::AsTransaction, 1, &secp).unwrap();
+	let after = verifier_cache::stats();
+	// 2 rangeproofs and 1 kernel are verified
+	assert_eq!(after.misses - before.misses, 3);
+
+	// everything is cached now
+	tx.validate(Weighting::AsTransaction, 1, &secp).unwrap();
+	let cached = verifier_cache::stats();
+	assert_eq!(cached.hits - after.hits, 3);
+	assert_eq!(cached.misses, after.misses);
+
+	// foreign signature and rangeproof are not taken from the cache
+	let other = tx1i1o();
+	other.validate(Weighting::AsTransaction, 1, &secp).unwrap();
+	let mut broken = other.clone();
+	broken.body.kernels[0].excess_sig = tx.kernels()[0].excess_sig;
+	assert!(broken.validate(Weighting::AsTransaction, 1, &secp).is_err());
+	let mut broken = other.clone();
+	broken.body.outputs[0].proof = tx.outputs()[0].proof;
+	assert!(broken.validate(Weighting::AsTransaction, 1, &secp).is_err());
+
+	// disabled cache verifies every time
+	verifier_cache::init_verifier_cache(0);
+	let before = verifier_cache::stats();
+	tx.validate(Weighting::AsTransaction, 1, &secp).unwrap();
+	assert_eq!(verifier_cache::stats().misses - before.misses, 3);
+}
@@ -0,0 +1,154 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the addresses that peers gossip to us. Any peer can claim
+//! arbitrary addresses, so a sample of them is dialed back before they are
+//! stored as Healthy. Addresses that were not sampled are stored as unverified
+//! and go after the verified ones when we share our peers.
+
+use crate::handshake::Handshake;
+use crate::mwc_core::pow::Difficulty;
+use crate::types::PeerAddr;
+use crate::util::Mutex;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpStream};
+use std::time::{Duration, Instant};
+
+/// Probability that the received address is dialed back before we store it
+pub const ADDR_VERIFY_PROBABILITY: f64 = 0.25;
+/// Max number of the addresses waiting for the dial back
+const ADDR_VERIFY_QUEUE_SIZE: usize = 256;
+/// Number of the addresses dialed back by a single run
+pub const ADDR_VERIFY_BATCH: usize = 8;
+/// Dial back connection timeout
+const ADDR_VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Max number of the dial backs to a single subnet (/16 for IPv4, /32 for IPv6)
+/// during ADDR_VERIFY_SUBNET_PERIOD. A peer can't use us to scan a network.
+pub const ADDR_VERIFY_SUBNET_LIMIT: usize = 4;
+/// Period of the subnet dial backs limit
+const ADDR_VERIFY_SUBNET_PERIOD: Duration = Duration::from_secs(600);
+
+struct VerifyQueue {
+	queue: VecDeque<PeerAddr>,
+	queued: HashSet<PeerAddr>,
+	// dial backs by the subnet, the start of the period and the number of the dials
+	subnets: HashMap<IpAddr, (Instant, usize)>,
+}
+
+/// Queue of the gossiped addresses that should be dialed back
+pub struct AddrVerify {
+	queue: Mutex<VerifyQueue>,
+}
+
+impl AddrVerify {
+	pub fn new() -> AddrVerify {
+		AddrVerify {
+			queue: Mutex::new(VerifyQueue {
+				queue: VecDeque::new(),
+				queued: HashSet::new(),
+				subnets: HashMap::new(),
+			}),
+		}
+	}
+
+	/// Randomly pick the address for the dial back. Returns true if the address
+	/// is queued and must not be stored until it is verified. Only IP addresses
+	/// can be dialed back, onion addresses are never sampled.
+	pub fn sample(&self, addr: &PeerAddr) -> bool {
+		if let PeerAddr::Onion(_) = addr {
+			return false;
+		}
+		if !thread_rng().gen_bool(ADDR_VERIFY_PROBABILITY) {
+			return false;
+		}
+		let mut q = self.queue.lock();
+		if q.queued.contains(addr) {
+			return true;
+		}
+		if q.queue.len() >= ADDR_VERIFY_QUEUE_SIZE {
+			return false;
+		}
+		q.queued.insert(addr.clone());
+		q.queue.push_back(addr.clone());
+		true
+	}
+
+	/// Number of the addresses waiting for the dial back
+	pub fn pending(&self) -> usize {
+		self.queue.lock().queue.len()
+	}
+
+	/// Take the next addresses to dial back. Addresses of the subnets that reached
+	/// ADDR_VERIFY_SUBNET_LIMIT are dropped, they are never stored.
+	pub fn next_batch(&self, max: usize) -> Vec<PeerAddr> {
+		let now = Instant::now();
+		let mut q = self.queue.lock();
+		q.subnets
+			.retain(|_, (start, _)| now.duration_since(*start) < ADDR_VERIFY_SUBNET_PERIOD);
+
+		let mut batch = vec![];
+		while batch.len() < max {
+			let addr = match q.queue.pop_front() {
+				Some(addr) => addr,
+				None => break,
+			};
+			q.queued.remove(&addr);
+			if let PeerAddr::Ip(ip) = &addr {
+				let dials = q.subnets.entry(subnet(&ip.ip())).or_insert((now, 0));
+				if dials.1 >= ADDR_VERIFY_SUBNET_LIMIT {
+					debug!("Too many dial backs to the subnet of {}, dropping it", addr);
+					continue;
+				}
+				dials.1 += 1;
+			}
+			batch.push(addr);
+		}
+		batch
+	}
+
+	/// Check that the node on the address is a peer on our chain: the hand is sent and
+	/// the shake with our genesis is expected. The connection is closed right after that.
+	pub fn dial(addr: &PeerAddr, handshake: &Handshake, total_difficulty: Difficulty) -> bool {
+		let ip = match addr {
+			PeerAddr::Ip(ip) => ip,
+			PeerAddr::Onion(_) => return false,
+		};
+		let mut conn = match TcpStream::connect_timeout(ip, ADDR_VERIFY_TIMEOUT) {
+			Ok(conn) => conn,
+			Err(_) => return false,
+		};
+		match handshake.probe(total_difficulty, &mut conn) {
+			Ok(_) => true,
+			Err(e) => {
+				debug!("Dial back handshake with {} failed, {:?}", addr, e);
+				false
+			}
+		}
+	}
+}
+
+/// Subnet of the address, the dial backs limit is applied to it
+fn subnet(ip: &IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V4(ip) => {
+			let o = ip.octets();
+			IpAddr::V4(Ipv4Addr::new(o[0], o[1], 0, 0))
+		}
+		IpAddr::V6(ip) => {
+			let s = ip.segments();
+			IpAddr::V6(Ipv6Addr::new(s[0], s[1], 0, 0, 0, 0, 0, 0))
+		}
+	}
+}
@@ -0,0 +1,168 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Node wide bandwidth budget shared by the sync and the blocks/transactions relay.
+//! All peer traffic is taken from a single token bucket that is refilled at the
+//! configured limit. Part of the bucket is reserved for the relay: the sync traffic
+//! is paused when the bucket drops below the reserve, the relay is never delayed.
+//! As a result the initial sync can't starve the tx relay on a limited link, while
+//! the relay can use the whole budget if there is no sync.
+
+use crate::msg::MsgPriority;
+use crate::util::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default share of the bandwidth reserved for the relay, percent
+pub const DEFAULT_RELAY_RATIO: u8 = 30;
+/// Sync always gets some share of the bandwidth, otherwise the node never syncs
+pub const MAX_RELAY_RATIO: u8 = 90;
+
+/// Traffic class for the bandwidth budget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrafficClass {
+	/// Sync data: header batches, segments, txhashset archives
+	Sync,
+	/// Blocks and transactions relay and the connection control messages
+	Relay,
+}
+
+impl From<MsgPriority> for TrafficClass {
+	fn from(priority: MsgPriority) -> TrafficClass {
+		match priority {
+			MsgPriority::Bulk => TrafficClass::Sync,
+			MsgPriority::Control | MsgPriority::Consensus => TrafficClass::Relay,
+		}
+	}
+}
+
+/// Bandwidth budget settings
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BandwidthLimits {
+	/// Total limit for all peers and both directions, KB/s. 0 is unlimited.
+	pub limit_kbps: u64,
+	/// Share of the limit reserved for the relay, percent
+	pub relay_ratio: u8,
+}
+
+struct Bucket {
+	limits: BandwidthLimits,
+	/// Available bytes, negative if the traffic is over the budget
+	tokens: i64,
+	last_refill: Instant,
+}
+
+// Bytes per second of the limit, a huge limit from the config or the API saturates
+fn limit_bytes(limit_kbps: u64) -> i64 {
+	limit_kbps.saturating_mul(1024).min(i64::MAX as u64) as i64
+}
+
+impl Bucket {
+	fn capacity(&self) -> i64 {
+		limit_bytes(self.limits.limit_kbps)
+	}
+
+	fn relay_reserve(&self) -> i64 {
+		(self.capacity() as i128 * self.limits.relay_ratio as i128 / 100) as i64
+	}
+
+	fn refill(&mut self) {
+		let now = Instant::now();
+		let elapsed = now.duration_since(self.last_refill);
+		let capacity = self.capacity();
+		let refill =
+			(capacity as u128 * elapsed.as_micros() / 1_000_000).min(capacity as u128) as i64;
+		// Frequent calls on a slow limit would never refill, keeping the time for the next call
+		if refill > 0 {
+			self.last_refill = now;
+			self.tokens = self.tokens.saturating_add(refill).min(capacity);
+		}
+	}
+}
+
+/// Bandwidth scheduler, one per node, shared by all peer connections
+pub struct BandwidthScheduler {
+	bucket: Mutex<Bucket>,
+}
+
+impl BandwidthScheduler {
+	/// New scheduler, `limit_kbps` 0 disables the limit
+	pub fn new(limit_kbps: u64, relay_ratio: u8) -> BandwidthScheduler {
+		let limits = BandwidthLimits {
+			limit_kbps,
+			relay_ratio: relay_ratio.min(MAX_RELAY_RATIO),
+		};
+		BandwidthScheduler {
+			bucket: Mutex::new(Bucket {
+				limits,
+				tokens: limit_bytes(limit_kbps),
+				last_refill: Instant::now(),
+			}),
+		}
+	}
+
+	/// Current settings
+	pub fn limits(&self) -> BandwidthLimits {
+		self.bucket.lock().limits
+	}
+
+	/// Update the settings at runtime. The bucket starts full with the new limit.
+	pub fn set_limits(&self, limits: BandwidthLimits) {
+		let mut bucket = self.bucket.lock();
+		bucket.limits = BandwidthLimits {
+			limit_kbps: limits.limit_kbps,
+			relay_ratio: limits.relay_ratio.min(MAX_RELAY_RATIO),
+		};
+		bucket.tokens = bucket.capacity();
+		bucket.last_refill = Instant::now();
+	}
+
+	/// Account sent or received bytes. The traffic that is already on the wire
+	/// can't be delayed, so the bucket can go below zero. The debt is limited
+	/// to a second of the traffic, so a large attachment doesn't stall the sync for long.
+	pub fn consume(&self, bytes: u64) {
+		let mut bucket = self.bucket.lock();
+		if bucket.limits.limit_kbps == 0 {
+			return;
+		}
+		bucket.refill();
+		let capacity = bucket.capacity();
+		let bytes = bytes.min(i64::MAX as u64) as i64;
+		bucket.tokens = bucket.tokens.saturating_sub(bytes).max(-capacity);
+	}
+
+	/// Check if the traffic of the class can be started now. The relay is always allowed,
+	/// the sync only if the budget is above the relay reserve.
+	pub fn allowed(&self, class: TrafficClass) -> bool {
+		self.delay(class).is_none()
+	}
+
+	/// Time to wait until the traffic of the class is allowed, None if it is allowed now
+	pub fn delay(&self, class: TrafficClass) -> Option<Duration> {
+		if class == TrafficClass::Relay {
+			return None;
+		}
+		let mut bucket = self.bucket.lock();
+		if bucket.limits.limit_kbps == 0 {
+			return None;
+		}
+		bucket.refill();
+		let reserve = bucket.relay_reserve();
+		if bucket.tokens > reserve {
+			return None;
+		}
+		let missing = (reserve as i128 - bucket.tokens as i128 + 1) as u128;
+		let ms = missing * 1000 / bucket.capacity() as u128;
+		Some(Duration::from_millis(ms.max(1) as u64))
+	}
+}
@@ -21,7 +21,7 @@
 //! forces us to go through some additional gymnastic to loop over the async
 //! stream and make sure we get the right number of bytes out.
 
-use crate::msg::{Message, MsgHeader, MsgHeaderWrapper, Type};
+use crate::msg::{Message, MsgHeader, MsgHeaderWrapper, MsgPriority, Type};
 use crate::mwc_core::global::header_size_bytes;
 use crate::mwc_core::ser::{BufReader, ProtocolVersion, Readable};
 use crate::types::{AttachmentMeta, AttachmentUpdate, Error};
@@ -70,6 +70,7 @@ pub struct Codec {
 	buffer: BytesMut,
 	state: State,
 	bytes_read: usize,
+	priority: MsgPriority,
 }
 
 impl Codec {
@@ -80,9 +81,15 @@ impl Codec {
 			buffer: BytesMut::with_capacity(8 * 1024),
 			state: None,
 			bytes_read: 0,
+			priority: MsgPriority::Control,
 		}
 	}
 
+	/// Class of the message that the last read bytes belong to
+	pub fn priority(&self) -> MsgPriority {
+		self.priority
+	}
+
 	/// Destroy the codec and return the reader
 	pub fn stream(self) -> TcpStream {
 		self.stream
@@ -93,6 +100,7 @@ impl Codec {
 	pub fn expect_attachment(&mut self, meta: Arc<AttachmentMeta>) {
 		debug_assert!(self.state.is_none());
 		self.state = Attachment(meta.size, meta, Instant::now());
+		self.priority = MsgPriority::Bulk;
 	}
 
 	/// Length of the next item we are expecting, could be msg header, body, block header or attachment chunk
@@ -150,6 +158,10 @@ impl Codec {
 					let mut raw = self.buffer.split_to(next_len).freeze();
 					let mut reader = BufReader::new(&mut raw, self.version);
 					let header = MsgHeaderWrapper::read(&mut reader)?;
+					self.priority = match &header {
+						Known(h) => h.msg_type.priority(),
+						Unknown(_, _) => MsgPriority::Control,
+					};
 					self.state = Header(header);
 				}
 				Header(Known(header)) => {
@@ -252,6 +264,7 @@ fn decode_message(
 		Type::Ping => Message::Ping(msg.body()?),
 		Type::Pong => Message::Pong(msg.body()?),
 		Type::BanReason => Message::BanReason(msg.body()?),
+		Type::Disconnect => Message::Disconnect(msg.body()?),
 		Type::TransactionKernel => Message::TransactionKernel(msg.body()?),
 		Type::GetTransaction => Message::GetTransaction(msg.body()?),
 		Type::Transaction => Message::Transaction(msg.body()?),
@@ -266,6 +279,12 @@ fn decode_message(
 		Type::PeerAddrs => Message::PeerAddrs(msg.body()?),
 		Type::TxHashSetRequest => Message::TxHashSetRequest(msg.body()?),
 		Type::TxHashSetArchive => Message::TxHashSetArchive(msg.body()?),
+		Type::TxHashSetRangeRequest => Message::TxHashSetRangeRequest(msg.body()?),
+		Type::TxHashSetRangeArchive => Message::TxHashSetRangeArchive(msg.body()?),
+		Type::TxReconcileRequest => Message::TxReconcileRequest(msg.body()?),
+		Type::TxReconcileSketch => Message::TxReconcileSketch(msg.body()?),
+		Type::TxReconcileDiff => Message::TxReconcileDiff(msg.body()?),
+		Type::TxReconcileHashes => Message::TxReconcileHashes(msg.body()?),
 		Type::GetHeadersHashesSegment => Message::GetHeadersHashesSegment(msg.body()?),
 		Type::OutputHeadersHashesSegment => Message::OutputHeadersHashesSegment(msg.body()?),
 		Type::GetOutputBitmapSegment => Message::GetOutputBitmapSegment(msg.body()?),
@@ -21,17 +21,21 @@
 //! forces us to go through some additional gymnastic to loop over the async
 //! stream and make sure we get the right number of bytes out.
 
+use crate::bandwidth::{BandwidthScheduler, TrafficClass};
 use crate::codec::{Codec, BODY_IO_TIMEOUT};
-use crate::msg::{write_message, Consumed, Message, Msg};
+use crate::msg::{write_message, Consumed, Message, MsgPriority};
 use crate::mwc_core::ser::ProtocolVersion;
-use crate::types::Error;
-use crate::util::{RateCounter, RwLock};
-use crossbeam::channel::{RecvTimeoutError, TryRecvError};
+use crate::send_queue::{send_queues, SendQueues};
+pub use crate::send_queue::{ConnHandle, SendQueueCaps};
+use crate::traffic::TrafficCounter;
+use crate::types::{AttachmentMeta, Error};
+use crate::util::{Mutex, RateCounter, RwLock};
+use crossbeam::channel::RecvTimeoutError;
 use mwc_chain::SyncState;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::{self, Seek, SeekFrom, Write};
 use std::net::{Shutdown, TcpStream};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
@@ -42,6 +46,9 @@ pub const SEND_CHANNEL_CAP: usize = 32 + 8; // Every request for 512 headers tak
 
 const CHANNEL_TIMEOUT: Duration = Duration::from_millis(15000);
 
+// Sync data waiting for the bandwidth budget checks it at least that often
+const BANDWIDTH_WAIT_STEP: Duration = Duration::from_millis(100);
+
 /// A trait to be implemented in order to receive messages from the
 /// connection. Allows providing an optional response.
 pub trait MessageHandler: Send + 'static {
@@ -110,39 +117,17 @@ impl StopHandle {
 	}
 }
 
-#[derive(Clone)]
-pub struct ConnHandle {
-	/// Channel to allow sending data through the connection
-	pub send_channel: crossbeam::channel::Sender<Msg>,
-}
-
-impl ConnHandle {
-	/// Send msg via the synchronous, bounded channel (sync_sender).
-	/// Two possible failure cases -
-	/// * Disconnected: Propagate this up to the caller so the peer connection can be closed.
-	/// * Full: Our internal msg buffer is full. This is not a problem with the peer connection
-	/// and we do not want to close the connection. We drop the msg rather than blocking here.
-	/// If the buffer is full because there is an underlying issue with the peer
-	/// and potentially the peer connection. We assume this will be handled at the peer level.
-	pub fn send(&self, msg: Msg) -> Result<(), Error> {
-		match self.send_channel.try_send(msg) {
-			Ok(()) => Ok(()),
-			Err(crossbeam::channel::TrySendError::Disconnected(_)) => {
-				Err(Error::Send("try_send disconnected".to_owned()))
-			}
-			Err(crossbeam::channel::TrySendError::Full(_msg)) => {
-				debug!("conn_handle: try_send but buffer is full, dropping msg");
-				Ok(())
-			}
-		}
-	}
-}
-
 pub struct Tracker {
 	/// Bytes we've sent.
 	pub sent_bytes: Arc<RwLock<RateCounter>>,
 	/// Bytes we've received.
 	pub received_bytes: Arc<RwLock<RateCounter>>,
+	/// All bytes by the message class for the traffic report
+	pub traffic: Mutex<TrafficCounter>,
+	/// Total sync data bytes received, for the sync throughput estimation
+	pub bulk_received: AtomicU64,
+	/// Node bandwidth budget, None for the connections that are not peers yet
+	bandwidth: Option<Arc<BandwidthScheduler>>,
 }
 
 impl Tracker {
@@ -152,6 +137,44 @@ impl Tracker {
 		Tracker {
 			received_bytes,
 			sent_bytes,
+			traffic: Mutex::new(TrafficCounter::new()),
+			bulk_received: AtomicU64::new(0),
+			bandwidth: None,
+		}
+	}
+
+	/// Tracker that accounts the traffic in the node bandwidth budget
+	pub fn with_bandwidth(bandwidth: Arc<BandwidthScheduler>) -> Tracker {
+		Tracker {
+			bandwidth: Some(bandwidth),
+			..Tracker::new()
+		}
+	}
+
+	pub fn inc_traffic_received(&self, priority: MsgPriority, size: u64) {
+		if priority == MsgPriority::Bulk {
+			self.bulk_received.fetch_add(size, Ordering::Relaxed);
+		}
+		if let Some(bandwidth) = &self.bandwidth {
+			bandwidth.consume(size);
+		}
+		self.traffic.lock().add_received(priority, size);
+	}
+
+	pub fn inc_traffic_sent(&self, priority: MsgPriority, size: u64) {
+		if let Some(bandwidth) = &self.bandwidth {
+			bandwidth.consume(size);
+		}
+		self.traffic.lock().add_sent(priority, size);
+	}
+
+	/// Wait until the traffic of that priority fits into the bandwidth budget.
+	/// Only the sync data is delayed, the relay is sent right away.
+	pub fn wait_bandwidth(&self, priority: MsgPriority) {
+		if let Some(bandwidth) = &self.bandwidth {
+			while let Some(delay) = bandwidth.delay(TrafficClass::from(priority)) {
+				thread::sleep(delay.min(BANDWIDTH_WAIT_STEP));
+			}
 		}
 	}
 
@@ -180,25 +203,21 @@ pub fn listen<H>(
 	version: ProtocolVersion,
 	tracker: Arc<Tracker>,
 	sync_state: Arc<SyncState>,
+	queue_caps: SendQueueCaps,
 	handler: H,
 ) -> io::Result<(ConnHandle, StopHandle)>
 where
 	H: MessageHandler,
 {
-	let (send_tx, send_rx) = crossbeam::channel::bounded(SEND_CHANNEL_CAP);
-
 	let stopped = Arc::new(AtomicBool::new(false));
-
-	let conn_handle = ConnHandle {
-		send_channel: send_tx,
-	};
+	let (conn_handle, send_queues) = send_queues(queue_caps, stopped.clone());
 
 	let (reader_thread, writer_thread) = poll(
 		stream,
 		conn_handle.clone(),
 		version,
 		handler,
-		send_rx,
+		send_queues,
 		stopped.clone(),
 		tracker,
 		sync_state,
@@ -219,7 +238,7 @@ fn poll<H>(
 	conn_handle: ConnHandle,
 	version: ProtocolVersion,
 	handler: H,
-	send_rx: crossbeam::channel::Receiver<Msg>,
+	mut send_queues: SendQueues,
 	stopped: Arc<AtomicBool>,
 	tracker: Arc<Tracker>,
 	sync_state: Arc<SyncState>,
@@ -244,6 +263,8 @@ where
 				.unwrap_or_else(|_| "?".to_owned());
 			let mut codec = Codec::new(version, reader);
 			let mut attachment: Option<File> = None;
+			// meta and bytes received for the attachment in progress
+			let mut attachment_progress: Option<(Arc<AttachmentMeta>, usize)> = None;
 			loop {
 				// check the close channel
 				if reader_stopped.load(Ordering::Relaxed) {
@@ -255,6 +276,7 @@ where
 
 				// check the read end
 				let (next, bytes_read) = codec.read();
+				reader_tracker.inc_traffic_received(codec.priority(), bytes_read);
 
 				// During sync process we don't want to ban peers becasue of abuse. It is expected to maintain high traffic for fast sync
 				if !sync_state.is_syncing() {
@@ -298,12 +320,16 @@ where
 							error!("Unable to write attachment file: {}", e);
 							break;
 						}
+						if let Some((_, received)) = &mut attachment_progress {
+							*received += update.read;
+						}
 						if update.left == 0 {
 							if let Err(e) = a.sync_all() {
 								error!("Unable to sync attachment file: {}", e);
 								break;
 							}
 							attachment.take();
+							attachment_progress.take();
 						}
 
 						Message::Attachment(update, None)
@@ -322,8 +348,14 @@ where
 					Consumed::Response(resp_msg) => {
 						try_break!(conn_handle.send(resp_msg));
 					}
-					Consumed::Attachment(meta, file) => {
-						// Start attachment
+					Consumed::Attachment(meta, mut file) => {
+						// Start attachment. For resumed transfer continue from the offset,
+						// data that we already have is kept.
+						if let Err(e) = file.seek(SeekFrom::Start(meta.offset)) {
+							error!("Unable to seek attachment file: {}", e);
+							break;
+						}
+						attachment_progress = Some((meta.clone(), 0));
 						codec.expect_attachment(meta);
 						attachment = Some(file);
 					}
@@ -332,6 +364,21 @@ where
 				}
 			}
 
+			if let Some((meta, received)) = attachment_progress {
+				// Keeping what we got, the rest can be requested with a range request
+				if let Some(mut a) = attachment {
+					let _ = a.flush();
+				}
+				warn!(
+					"Attachment {} from {} interrupted, got {} of {} bytes. Transfer can be resumed from offset {}",
+					meta.path.display(),
+					peer_addr,
+					received,
+					meta.size,
+					meta.offset + received as u64
+				);
+			}
+
 			debug!("Shutting down reader connection with {}", peer_addr);
 			let _ = codec.stream().shutdown(Shutdown::Both);
 		})?;
@@ -342,32 +389,15 @@ where
 			let mut retry_send = Err(());
 			let _ = writer.set_write_timeout(Some(BODY_IO_TIMEOUT));
 			loop {
-				let maybe_data = retry_send.or_else(|_| {
-					let mut data = match send_rx.recv_timeout(CHANNEL_TIMEOUT) {
-						Ok(msg) => vec![msg],
-						Err(e) => return Err(e),
-					};
-					// send_rx expected to have capacuty. Capacity will limit the number of message that we can read form the stream
-					loop {
-						match send_rx.try_recv() {
-							Ok(msg) => {
-								data.push(msg);
-							}
-							Err(TryRecvError::Empty) => break,
-							Err(TryRecvError::Disconnected) => {
-								return Err(RecvTimeoutError::Disconnected)
-							} // All other error are fatal, report as disconnected
-						}
-					}
-					Ok(data)
-				});
+				let maybe_data = retry_send.or_else(|_| send_queues.recv_batch(CHANNEL_TIMEOUT));
 				retry_send = Err(());
 				match maybe_data {
 					Ok(data) => {
 						let written =
 							try_break!(write_message(&mut writer, &data, writer_tracker.clone()));
-						if written.is_none() {
-							retry_send = Ok(data);
+						match written {
+							Some(()) => data.iter().for_each(|msg| msg.complete()),
+							None => retry_send = Ok(data),
 						}
 					}
 					Err(RecvTimeoutError::Disconnected) => {
@@ -0,0 +1,138 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rate limiting for the inbound connections. Every source IP has a token bucket,
+//! a connection takes a token, tokens are refilled at the configured rate per
+//! minute. Sources that keep connecting after their bucket is empty are banned.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Buckets are not tracked for more sources than that, idle ones are dropped first
+const CONN_LIMIT_MAX_SOURCES: usize = 4096;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Decision for the new inbound connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnLimit {
+	/// Connection can be processed
+	Accept,
+	/// Too many connections from the source, drop this one
+	Reject,
+	/// Source exceeded the hard threshold, drop the connection and ban the source
+	Ban,
+}
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+	// Rejected connections during the current minute
+	rejected: u32,
+	rejected_since: Instant,
+}
+
+/// Per source IP token buckets for the accept loop
+pub struct ConnRateLimiter {
+	rate_per_min: u32,
+	burst: u32,
+	ban_threshold: u32,
+	buckets: HashMap<IpAddr, Bucket>,
+}
+
+impl ConnRateLimiter {
+	/// `rate_per_min` connections per minute are allowed from a single IP, with bursts
+	/// up to `burst`. The source is banned when more than `ban_threshold` of its
+	/// connections are rejected within a minute, 0 disables the ban.
+	pub fn new(rate_per_min: u32, burst: u32, ban_threshold: u32) -> ConnRateLimiter {
+		ConnRateLimiter {
+			rate_per_min: rate_per_min.max(1),
+			burst: burst.max(1),
+			ban_threshold,
+			buckets: HashMap::new(),
+		}
+	}
+
+	/// Check the new connection from the address.
+	/// Loopback addresses are not limited, inbound Tor connections come from the
+	/// local proxy.
+	pub fn check(&mut self, ip: IpAddr) -> ConnLimit {
+		self.check_at(ip, Instant::now())
+	}
+
+	/// Same as `check`, at the provided time
+	pub fn check_at(&mut self, ip: IpAddr, now: Instant) -> ConnLimit {
+		if ip.is_loopback() {
+			return ConnLimit::Accept;
+		}
+		if self.buckets.len() >= CONN_LIMIT_MAX_SOURCES && !self.buckets.contains_key(&ip) {
+			self.cleanup(now);
+		}
+
+		let rate_per_sec = self.rate_per_min as f64 / MINUTE.as_secs_f64();
+		let burst = self.burst as f64;
+		let bucket = self.buckets.entry(ip).or_insert(Bucket {
+			tokens: burst,
+			updated: now,
+			rejected: 0,
+			rejected_since: now,
+		});
+
+		let elapsed = now.saturating_duration_since(bucket.updated);
+		bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate_per_sec).min(burst);
+		bucket.updated = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			return ConnLimit::Accept;
+		}
+
+		if now.saturating_duration_since(bucket.rejected_since) >= MINUTE {
+			bucket.rejected = 0;
+			bucket.rejected_since = now;
+		}
+		bucket.rejected += 1;
+		if self.ban_threshold > 0 && bucket.rejected > self.ban_threshold {
+			// Start over, the ban itself keeps the source away
+			self.buckets.remove(&ip);
+			ConnLimit::Ban
+		} else {
+			ConnLimit::Reject
+		}
+	}
+
+	/// Number of the tracked sources
+	pub fn sources(&self) -> usize {
+		self.buckets.len()
+	}
+
+	// Drop the buckets that are full again, they are the same as the new ones.
+	// If all sources are active, drop the least recently updated.
+	fn cleanup(&mut self, now: Instant) {
+		let full_after = MINUTE.mul_f64(self.burst as f64 / self.rate_per_min as f64);
+		self.buckets
+			.retain(|_, b| now.saturating_duration_since(b.updated) < full_after);
+		if self.buckets.len() >= CONN_LIMIT_MAX_SOURCES {
+			if let Some(ip) = self
+				.buckets
+				.iter()
+				.min_by_key(|(_, b)| b.updated)
+				.map(|(ip, _)| *ip)
+			{
+				self.buckets.remove(&ip);
+			}
+		}
+	}
+}
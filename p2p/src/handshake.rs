@@ -19,6 +19,7 @@ use crate::mwc_core::core::hash::Hash;
 use crate::mwc_core::pow::Difficulty;
 use crate::mwc_core::ser::ProtocolVersion;
 use crate::peer::Peer;
+use crate::types::next_session_id;
 use crate::types::{
 	Capabilities, Direction, Error, P2PConfig, PeerAddr, PeerAddr::Ip, PeerAddr::Onion, PeerInfo,
 	PeerLiveInfo,
@@ -167,9 +168,10 @@ impl Handshake {
 		let negotiated_version = self.negotiate_protocol_version(shake.version)?;
 
 		let peer_info = PeerInfo {
-			capabilities: shake.capabilities,
+			capabilities: shake.capabilities.with_implied_history(),
 			user_agent: shake.user_agent,
 			addr: peer_addr,
+			session_id: next_session_id(),
 			version: negotiated_version,
 			live_info: Arc::new(RwLock::new(PeerLiveInfo::new(shake.total_difficulty))),
 			direction: if self.onion_address.is_some() {
@@ -240,9 +242,10 @@ impl Handshake {
 
 		// all good, keep peer info
 		let peer_info = PeerInfo {
-			capabilities: hand.capabilities,
+			capabilities: hand.capabilities.with_implied_history(),
 			user_agent: hand.user_agent,
 			addr: resolve_peer_addr(hand.sender_addr.clone(), &conn),
+			session_id: next_session_id(),
 			version: negotiated_version,
 			live_info: Arc::new(RwLock::new(PeerLiveInfo::new(hand.total_difficulty))),
 			direction: if self.onion_address.is_some() {
@@ -281,6 +284,45 @@ impl Handshake {
 		Ok(peer_info)
 	}
 
+	/// Check that the node on the other end of the connection is a peer on our chain.
+	/// The hand is sent without any capabilities and the shake genesis is validated,
+	/// the connection is not used for anything else after that.
+	pub fn probe(
+		&self,
+		total_difficulty: Difficulty,
+		conn: &mut TcpStream,
+	) -> Result<Shake, Error> {
+		let _ = conn.set_write_timeout(Some(HAND_WRITE_TIMEOUT));
+		let _ = conn.set_read_timeout(Some(SHAKE_READ_TIMEOUT));
+
+		let receiver_addr =
+			PeerAddr::Ip(conn.peer_addr().map_err(|e| {
+				Error::ConnectionClose(format!("unable to get peer address, {}", e))
+			})?);
+		let hand = Hand {
+			version: self.protocol_version,
+			capabilities: Capabilities::UNKNOWN,
+			nonce: self.next_nonce(),
+			genesis: self.genesis,
+			total_difficulty,
+			sender_addr: PeerAddr::Ip(SocketAddr::new(self.config.host, self.config.port)),
+			receiver_addr,
+			user_agent: USER_AGENT.to_string(),
+			tx_fee_base: global::get_accept_fee_base(),
+		};
+		let msg = Msg::new(Type::Hand, hand, self.protocol_version)?;
+		write_message(conn, &vec![msg], self.tracker.clone())?;
+
+		let shake: Shake = read_message(conn, self.protocol_version, Type::Shake)?;
+		if shake.genesis != self.genesis {
+			return Err(Error::GenesisMismatch {
+				us: self.genesis,
+				peer: shake.genesis,
+			});
+		}
+		Ok(shake)
+	}
+
 	/// Generate a new random nonce and store it in our ring buffer
 	fn next_nonce(&self) -> u64 {
 		let nonce = thread_rng().gen();
@@ -41,27 +41,41 @@ extern crate log;
 #[macro_use]
 extern crate lazy_static;
 
+#[macro_use]
+pub mod log_throttle;
+
+pub mod addr_verify;
+pub mod bandwidth;
 mod codec;
 mod conn;
+pub mod conn_limit;
 pub mod handshake;
 #[cfg(feature = "libp2p")]
 pub mod libp2p_connection;
 pub mod msg;
 mod peer;
+mod peer_changes;
 mod peers;
 mod protocol;
+pub mod send_queue;
 mod serv;
 pub mod store;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod traffic;
+pub mod tx_reconciliation;
+pub mod tx_relay;
 pub mod types;
 
 pub use crate::conn::SEND_CHANNEL_CAP;
 pub use crate::peer::Peer;
+pub use crate::peer_changes::{PeerChange, PeerChangeKind, PeerChangeLog, PeerChanges};
 pub use crate::peers::Peers;
 pub use crate::serv::{DummyAdapter, Server};
 pub use crate::store::{PeerData, State};
 pub use crate::types::{
-	Capabilities, ChainAdapter, Direction, Error, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	Seeding, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
+	Capabilities, ChainAdapter, Direction, DropReason, Error, P2PConfig, PeerAddr, PeerInfo,
+	ReasonForBan, Seeding, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
 };
 
 #[cfg(feature = "libp2p")]
@@ -0,0 +1,140 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Throttling for the log messages on the hot error paths. A single misbehaving
+//! peer can trigger the same error thousands of times per minute. Only the first
+//! message per key and interval is logged, the repeats are counted and reported
+//! with the next logged message or with the periodic summary.
+
+use crate::util::Mutex;
+use log::Level;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Messages with the same key are logged at most once per interval
+pub const LOG_THROTTLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keys that were quiet for that many intervals are forgotten
+const LOG_THROTTLE_EXPIRE_INTERVALS: u32 = 10;
+
+lazy_static::lazy_static! {
+	/// Shared throttle for the p2p log messages
+	pub static ref LOG_THROTTLE: LogThrottle = LogThrottle::new(LOG_THROTTLE_INTERVAL);
+}
+
+/// Log the message at most once per LOG_THROTTLE_INTERVAL for the key. Suppressed
+/// repeats are counted and the count is added to the next logged message.
+/// Usage: `throttled_log!(Level::Error, "key", "format {}", args)`
+macro_rules! throttled_log {
+	($level:expr, $key:expr, $($arg:tt)+) => {
+		if let Some(suppressed) = $crate::log_throttle::LOG_THROTTLE.check($key, $level) {
+			if suppressed > 0 {
+				log!(
+					$level,
+					"{} ({} similar messages were suppressed)",
+					format_args!($($arg)+),
+					suppressed
+				);
+			} else {
+				log!($level, $($arg)+);
+			}
+		}
+	};
+}
+
+struct ThrottleEntry {
+	level: Level,
+	last_logged: Instant,
+	suppressed: u64,
+}
+
+/// Counts the log messages by key and decides which of them should be logged
+pub struct LogThrottle {
+	interval: Duration,
+	entries: Mutex<HashMap<String, ThrottleEntry>>,
+}
+
+impl LogThrottle {
+	pub fn new(interval: Duration) -> LogThrottle {
+		LogThrottle {
+			interval,
+			entries: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Register the message with the key. Returns the number of suppressed repeats
+	/// if the message should be logged now, None if it should be suppressed.
+	pub fn check(&self, key: &str, level: Level) -> Option<u64> {
+		let now = Instant::now();
+		let mut entries = self.entries.lock();
+		match entries.get_mut(key) {
+			Some(entry) => {
+				if now.duration_since(entry.last_logged) < self.interval {
+					entry.suppressed += 1;
+					None
+				} else {
+					let suppressed = entry.suppressed;
+					entry.suppressed = 0;
+					entry.last_logged = now;
+					Some(suppressed)
+				}
+			}
+			None => {
+				entries.insert(
+					key.to_string(),
+					ThrottleEntry {
+						level,
+						last_logged: now,
+						suppressed: 0,
+					},
+				);
+				Some(0)
+			}
+		}
+	}
+
+	/// Log the summary for the keys that have suppressed messages older than the
+	/// interval, and forget the keys that are quiet for a long time. Expected to be
+	/// called periodically, otherwise the last repeats are reported only when the
+	/// message with the same key appears again.
+	pub fn flush(&self) {
+		let now = Instant::now();
+		let mut entries = self.entries.lock();
+		for (key, entry) in entries.iter_mut() {
+			let elapsed = now.duration_since(entry.last_logged);
+			if entry.suppressed > 0 && elapsed >= self.interval {
+				log!(
+					entry.level,
+					"{}: {} similar messages were suppressed in the last {} seconds",
+					key,
+					entry.suppressed,
+					elapsed.as_secs()
+				);
+				entry.suppressed = 0;
+				entry.last_logged = now;
+			}
+		}
+		let expire = self.interval * LOG_THROTTLE_EXPIRE_INTERVALS;
+		entries.retain(|_, entry| now.duration_since(entry.last_logged) < expire);
+	}
+
+	/// Number of suppressed messages for the key since it was logged last time
+	pub fn suppressed(&self, key: &str) -> u64 {
+		self.entries
+			.lock()
+			.get(key)
+			.map(|e| e.suppressed)
+			.unwrap_or(0)
+	}
+}
@@ -29,17 +29,20 @@ use crate::mwc_core::ser::{
 	Writer,
 };
 use crate::mwc_core::{consensus, global};
+use crate::tx_reconciliation::{TxSketch, MAX_RECONCILIATION_SET, MAX_SKETCH_CELLS};
 use crate::types::{
-	AttachmentMeta, AttachmentUpdate, Capabilities, Error, PeerAddr, ReasonForBan,
+	AttachmentMeta, AttachmentUpdate, Capabilities, DropReason, Error, PeerAddr, ReasonForBan,
 	MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
 };
 use crate::util::secp::pedersen::RangeProof;
 use bytes::Bytes;
+use crossbeam::channel::{Receiver, Sender};
 use num::FromPrimitive;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
-use std::{fmt, thread, time::Duration};
+use std::time::{Duration, Instant};
+use std::{fmt, thread};
 
 /// Mwc's user agent with current version
 pub const USER_AGENT: &str = concat!("MW/MWC ", env!("CARGO_PKG_VERSION"));
@@ -93,6 +96,77 @@ enum_from_primitive! {
 		StartHeadersHashResponse = 36,
 		GetHeadersHashesSegment = 37,
 		OutputHeadersHashesSegment = 38,
+		Disconnect = 39,
+		TxHashSetRangeRequest = 40,
+		TxHashSetRangeArchive = 41,
+		TxReconcileRequest = 42,
+		TxReconcileSketch = 43,
+		TxReconcileDiff = 44,
+		TxReconcileHashes = 45,
+	}
+}
+
+/// Priority of the outgoing message. Every priority has its own send queue, the
+/// writer thread sends the higher priority messages first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsgPriority {
+	/// Connection management: pings, bans, disconnects, peer addresses
+	Control,
+	/// New blocks and transactions relay, small requests
+	Consensus,
+	/// Sync data: header batches, segments, txhashset archives
+	Bulk,
+}
+
+impl Type {
+	/// Send queue priority for the message type
+	pub fn priority(&self) -> MsgPriority {
+		match self {
+			Type::Error
+			| Type::Hand
+			| Type::Shake
+			| Type::Ping
+			| Type::Pong
+			| Type::BanReason
+			| Type::Disconnect
+			| Type::GetPeerAddrs
+			| Type::PeerAddrs
+			| Type::TorAddress => MsgPriority::Control,
+			Type::GetHeaders
+			| Type::Header
+			| Type::GetBlock
+			| Type::Block
+			| Type::GetCompactBlock
+			| Type::CompactBlock
+			| Type::StemTransaction
+			| Type::Transaction
+			| Type::GetTransaction
+			| Type::TransactionKernel
+			| Type::TxReconcileRequest
+			| Type::TxReconcileSketch
+			| Type::TxReconcileDiff
+			| Type::TxReconcileHashes => MsgPriority::Consensus,
+			Type::Headers
+			| Type::TxHashSetRequest
+			| Type::TxHashSetArchive
+			| Type::TxHashSetRangeRequest
+			| Type::TxHashSetRangeArchive
+			| Type::StartPibdSyncRequest
+			| Type::GetOutputBitmapSegment
+			| Type::OutputBitmapSegment
+			| Type::GetOutputSegment
+			| Type::OutputSegment
+			| Type::GetRangeProofSegment
+			| Type::RangeProofSegment
+			| Type::GetKernelSegment
+			| Type::KernelSegment
+			| Type::HasAnotherArchiveHeader
+			| Type::PibdSyncState
+			| Type::StartHeadersHashRequest
+			| Type::StartHeadersHashResponse
+			| Type::GetHeadersHashesSegment
+			| Type::OutputHeadersHashesSegment => MsgPriority::Bulk,
+		}
 	}
 }
 
@@ -146,6 +220,15 @@ fn max_msg_size(msg_type: Type) -> u64 {
 		Type::StartPibdSyncRequest => 40, // 32+8=40
 		Type::HasAnotherArchiveHeader => 40,
 		Type::PibdSyncState => 72, // 32 + 8 + 32 = 72
+		Type::Disconnect => 16,
+		Type::TxHashSetRangeRequest => 56, // 32+8+8+8=56
+		Type::TxHashSetRangeArchive => 64, // 32+8+8+8+8=64
+		Type::TxReconcileRequest => 16,    // 8+8=16
+		Type::TxReconcileSketch => 4 + 20 * MAX_SKETCH_CELLS as u64,
+		Type::TxReconcileDiff => {
+			1 + 4 + 8 * MAX_RECONCILIATION_SET as u64 + 4 + 32 * MAX_RECONCILIATION_SET as u64
+		}
+		Type::TxReconcileHashes => 4 + 32 * MAX_RECONCILIATION_SET as u64,
 	}
 }
 
@@ -161,7 +244,11 @@ pub struct Msg {
 	header: MsgHeader,
 	body: Vec<u8>,
 	attachment: Option<File>,
+	/// (offset, length) of the attachment file to send, the whole file if None
+	attachment_range: Option<(u64, u64)>,
 	version: ProtocolVersion,
+	/// Notified when the message is written to the connection
+	completion: Option<Sender<()>>,
 }
 
 impl Msg {
@@ -175,13 +262,65 @@ impl Msg {
 			header: MsgHeader::new(msg_type, body.len() as u64),
 			body,
 			attachment: None,
+			attachment_range: None,
 			version,
+			completion: None,
 		})
 	}
 
+	/// Request the delivery feedback for this message. Intended for the critical
+	/// messages that must be sent before the connection is closed.
+	pub fn set_completion(&mut self) -> SendCompletion {
+		let (tx, rx) = crossbeam::channel::bounded(1);
+		self.completion = Some(tx);
+		SendCompletion { rx }
+	}
+
+	/// Report that the message is written to the connection
+	pub fn complete(&self) {
+		if let Some(tx) = &self.completion {
+			let _ = tx.try_send(());
+		}
+	}
+
 	pub fn add_attachment(&mut self, attachment: File) {
 		self.attachment = Some(attachment)
 	}
+
+	/// Attach only `length` bytes of the file starting from `offset`. Used to resume
+	/// interrupted transfers.
+	pub fn add_attachment_range(&mut self, attachment: File, offset: u64, length: u64) {
+		self.attachment = Some(attachment);
+		self.attachment_range = Some((offset, length));
+	}
+
+	/// Type of the message
+	pub fn msg_type(&self) -> Type {
+		self.header.msg_type
+	}
+}
+
+/// Delivery feedback of the message. The message that is dropped from the send
+/// queue or the closed connection is reported as not delivered.
+pub struct SendCompletion {
+	rx: Receiver<()>,
+}
+
+impl SendCompletion {
+	/// Wait until the message is written to the connection. Returns false if the message
+	/// was dropped or wasn't written during the timeout.
+	pub fn wait(&self, timeout: Duration) -> bool {
+		self.rx.recv_timeout(timeout).is_ok()
+	}
+
+	/// Wait for all messages with the common timeout, returns the number of delivered ones
+	pub fn wait_all(completions: &[SendCompletion], timeout: Duration) -> usize {
+		let deadline = Instant::now() + timeout;
+		completions
+			.iter()
+			.filter(|c| c.wait(deadline.saturating_duration_since(Instant::now())))
+			.count()
+	}
 }
 
 /// Read a header from the provided stream without blocking if the
@@ -273,8 +412,12 @@ pub fn write_message<W: Write>(
 	let mut tmp_buf: Vec<u8> = vec![];
 
 	for msg in msgs {
+		let priority = msg.msg_type().priority();
+		tracker.wait_bandwidth(priority);
+		let buf_len = tmp_buf.len();
 		tmp_buf.extend(ser::ser_vec(&msg.header, msg.version)?);
 		tmp_buf.extend(&msg.body[..]);
+		tracker.inc_traffic_sent(priority, (tmp_buf.len() - buf_len) as u64);
 		if let Some(file) = &msg.attachment {
 			// finalize what we have before attachments...
 			if !tmp_buf.is_empty() {
@@ -283,15 +426,25 @@ pub fn write_message<W: Write>(
 				tmp_buf.clear();
 			}
 			let mut file = file.try_clone()?;
+			let length = match msg.attachment_range {
+				Some((offset, length)) => {
+					file.seek(SeekFrom::Start(offset))?;
+					length
+				}
+				None => u64::MAX,
+			};
+			let mut file = file.take(length);
 			let mut buf = [0u8; 8000];
 			loop {
 				match file.read(&mut buf[..]) {
 					Ok(0) => break,
 					Ok(n) => {
+						tracker.wait_bandwidth(priority);
 						stream.write_all(&buf[..n])?;
 						// Increase sent bytes "quietly" without incrementing the counter.
 						// (In a loop here for the single attachment).
 						tracker.inc_quiet_sent(n as u64);
+						tracker.inc_traffic_sent(priority, n as u64);
 					}
 					Err(e) => return Err(From::from(e)),
 				}
@@ -821,6 +974,29 @@ impl Readable for BanReason {
 	}
 }
 
+/// Sent to the peer before we close a connection that we don't want to keep
+/// any more. Unlike BanReason, the peer is not banned and can reconnect later.
+#[derive(Debug)]
+pub struct Disconnect {
+	/// the reason for the disconnect
+	pub drop_reason: DropReason,
+}
+
+impl Writeable for Disconnect {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u8(self.drop_reason as u8)
+	}
+}
+
+impl Readable for Disconnect {
+	fn read<R: Reader>(reader: &mut R) -> Result<Disconnect, ser::Error> {
+		let drop_reason = DropReason::from_u8(reader.read_u8()?).ok_or(
+			ser::Error::CorruptedData("Fail to read drop reason".to_string()),
+		)?;
+		Ok(Disconnect { drop_reason })
+	}
+}
+
 #[derive(Debug)]
 pub struct HashHeadersData {
 	/// Height of the archive block to what we are expecting to get headers hashes
@@ -1048,6 +1224,7 @@ pub enum Message {
 	Ping(Ping),
 	Pong(Pong),
 	BanReason(BanReason),
+	Disconnect(Disconnect),
 	TransactionKernel(Hash),
 	GetTransaction(Hash),
 	Transaction(Transaction),
@@ -1063,6 +1240,12 @@ pub enum Message {
 	PeerAddrs(PeerAddrs),
 	TxHashSetRequest(ArchiveHeaderData),
 	TxHashSetArchive(TxHashSetArchive),
+	TxHashSetRangeRequest(TxHashSetRangeRequest),
+	TxHashSetRangeArchive(TxHashSetRangeArchive),
+	TxReconcileRequest(TxReconcileRequest),
+	TxReconcileSketch(TxSketch),
+	TxReconcileDiff(TxReconcileDiff),
+	TxReconcileHashes(TxReconcileHashes),
 	Attachment(AttachmentUpdate, Option<Bytes>),
 	TorAddress(TorAddress),
 	StartHeadersHashRequest(HashHeadersData),
@@ -1100,6 +1283,7 @@ impl fmt::Display for Message {
 			Message::Ping(ping) => write!(f, "{:?}", ping),
 			Message::Pong(pong) => write!(f, "{:?}", pong),
 			Message::BanReason(ban_reason) => write!(f, "{:?}", ban_reason),
+			Message::Disconnect(disconnect) => write!(f, "{:?}", disconnect),
 			Message::TransactionKernel(hash) => write!(f, "TransactionKernel({})", hash),
 			Message::GetTransaction(hash) => write!(f, "GetTransaction({})", hash),
 			Message::Transaction(tx) => write!(f, "{:?}", tx),
@@ -1124,6 +1308,20 @@ impl fmt::Display for Message {
 			Message::PeerAddrs(peer_addrs) => write!(f, "{:?}", peer_addrs),
 			Message::TxHashSetRequest(arch) => write!(f, "TxHashSetRequest({:?})", arch),
 			Message::TxHashSetArchive(hash_set) => write!(f, "{:?}", hash_set),
+			Message::TxHashSetRangeRequest(req) => write!(f, "{:?}", req),
+			Message::TxHashSetRangeArchive(arch) => write!(f, "{:?}", arch),
+			Message::TxReconcileRequest(req) => write!(f, "{:?}", req),
+			Message::TxReconcileSketch(sketch) => write!(f, "TxReconcileSketch({})", sketch.len()),
+			Message::TxReconcileDiff(diff) => write!(
+				f,
+				"TxReconcileDiff(decoded: {}, want: {}, announce: {})",
+				diff.decoded,
+				diff.want.len(),
+				diff.announce.len()
+			),
+			Message::TxReconcileHashes(hashes) => {
+				write!(f, "TxReconcileHashes({})", hashes.hashes.len())
+			}
 			Message::Attachment(meta, _) => write!(f, "Attachment({:?})", meta),
 			Message::TorAddress(addr) => write!(f, "{:?}", addr),
 			Message::StartHeadersHashRequest(req) => {
@@ -1201,6 +1399,93 @@ impl fmt::Debug for Consumed {
 	}
 }
 
+/// Request for a part of the txhashset archive. Used to resume a transfer that
+/// was interrupted, so only the missing bytes are downloaded again.
+#[derive(Debug)]
+pub struct TxHashSetRangeRequest {
+	/// Hash of the block of the archive that was partially downloaded
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Position in the archive to start from
+	pub offset: u64,
+	/// Number of bytes requested, 0 means up to the end of the archive
+	pub length: u64,
+}
+
+impl Writeable for TxHashSetRangeRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		ser_multiwrite!(
+			writer,
+			[write_u64, self.height],
+			[write_u64, self.offset],
+			[write_u64, self.length]
+		);
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetRangeRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxHashSetRangeRequest, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let (height, offset, length) = ser_multiread!(reader, read_u64, read_u64, read_u64);
+		Ok(TxHashSetRangeRequest {
+			hash,
+			height,
+			offset,
+			length,
+		})
+	}
+}
+
+/// Response to a txhashset range request, must include `length` bytes of the
+/// archive zip stream (starting at `offset`) after the message body.
+/// If `hash` doesn't match the request, the archive was rebuilt and the
+/// transfer must be restarted from scratch.
+#[derive(Debug)]
+pub struct TxHashSetRangeArchive {
+	/// Hash of the block for which the txhashset are provided
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Total size in bytes of the archive
+	pub bytes: u64,
+	/// Position in the archive of the attached data
+	pub offset: u64,
+	/// Size in bytes of the attached data
+	pub length: u64,
+}
+
+impl Writeable for TxHashSetRangeArchive {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		ser_multiwrite!(
+			writer,
+			[write_u64, self.height],
+			[write_u64, self.bytes],
+			[write_u64, self.offset],
+			[write_u64, self.length]
+		);
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetRangeArchive {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxHashSetRangeArchive, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let (height, bytes, offset, length) =
+			ser_multiread!(reader, read_u64, read_u64, read_u64, read_u64);
+		Ok(TxHashSetRangeArchive {
+			hash,
+			height,
+			bytes,
+			offset,
+			length,
+		})
+	}
+}
+
 /// Response to a txhashset archive request, must include a zip stream of the
 /// archive after the message body.
 #[derive(Debug)]
@@ -1263,3 +1548,122 @@ impl Readable for TorAddress {
 		}
 	}
 }
+
+/// Start of the tx announcements reconciliation round
+#[derive(Debug)]
+pub struct TxReconcileRequest {
+	/// Number of announcements in the initiator set
+	pub set_size: u64,
+	/// Salt for the short ids of this round
+	pub salt: u64,
+}
+
+impl Writeable for TxReconcileRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		ser_multiwrite!(writer, [write_u64, self.set_size], [write_u64, self.salt]);
+		Ok(())
+	}
+}
+
+impl Readable for TxReconcileRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxReconcileRequest, ser::Error> {
+		let (set_size, salt) = ser_multiread!(reader, read_u64, read_u64);
+		Ok(TxReconcileRequest { set_size, salt })
+	}
+}
+
+fn write_reconcile_hashes<W: Writer>(writer: &mut W, hashes: &[Hash]) -> Result<(), ser::Error> {
+	if hashes.len() > MAX_RECONCILIATION_SET {
+		return Err(ser::Error::TooLargeWriteErr(
+			"hashes len larger then the limit".to_string(),
+		));
+	}
+	writer.write_u32(hashes.len() as u32)?;
+	for h in hashes {
+		h.write(writer)?;
+	}
+	Ok(())
+}
+
+fn read_reconcile_hashes<R: Reader>(reader: &mut R) -> Result<Vec<Hash>, ser::Error> {
+	let len = reader.read_u32()? as usize;
+	if len > MAX_RECONCILIATION_SET {
+		return Err(ser::Error::TooLargeReadErr(
+			"hashes len larger then the limit".to_string(),
+		));
+	}
+	let mut hashes = Vec::with_capacity(len);
+	for _ in 0..len {
+		hashes.push(Hash::read(reader)?);
+	}
+	Ok(hashes)
+}
+
+/// Result of the reconciliation on the initiator side
+#[derive(Debug)]
+pub struct TxReconcileDiff {
+	/// Sketch was decoded. If not, responder must announce all its round hashes
+	pub decoded: bool,
+	/// Short ids of the hashes that initiator wants to get announced
+	pub want: Vec<u64>,
+	/// Kernel hashes that responder doesn't have
+	pub announce: Vec<Hash>,
+}
+
+impl Writeable for TxReconcileDiff {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		if self.want.len() > MAX_RECONCILIATION_SET {
+			return Err(ser::Error::TooLargeWriteErr(
+				"want len larger then the limit".to_string(),
+			));
+		}
+		writer.write_u8(self.decoded as u8)?;
+		writer.write_u32(self.want.len() as u32)?;
+		for id in &self.want {
+			writer.write_u64(*id)?;
+		}
+		write_reconcile_hashes(writer, &self.announce)
+	}
+}
+
+impl Readable for TxReconcileDiff {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxReconcileDiff, ser::Error> {
+		let decoded = reader.read_u8()? != 0;
+		let len = reader.read_u32()? as usize;
+		if len > MAX_RECONCILIATION_SET {
+			return Err(ser::Error::TooLargeReadErr(
+				"want len larger then the limit".to_string(),
+			));
+		}
+		let mut want = Vec::with_capacity(len);
+		for _ in 0..len {
+			want.push(reader.read_u64()?);
+		}
+		let announce = read_reconcile_hashes(reader)?;
+		Ok(TxReconcileDiff {
+			decoded,
+			want,
+			announce,
+		})
+	}
+}
+
+/// Kernel hashes requested by the reconciliation initiator
+#[derive(Debug)]
+pub struct TxReconcileHashes {
+	pub hashes: Vec<Hash>,
+}
+
+impl Writeable for TxReconcileHashes {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		write_reconcile_hashes(writer, &self.hashes)
+	}
+}
+
+impl Readable for TxReconcileHashes {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxReconcileHashes, ser::Error> {
+		Ok(TxReconcileHashes {
+			hashes: read_reconcile_hashes(reader)?,
+		})
+	}
+}
@@ -19,6 +19,7 @@ use std::fmt;
 use std::net::{Shutdown, TcpStream};
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use lru::LruCache;
@@ -28,8 +29,8 @@ use crate::chain::txhashset::BitmapChunk;
 use crate::conn;
 use crate::handshake::Handshake;
 use crate::msg::{
-	self, ArchiveHeaderData, BanReason, GetPeerAddrs, HashHeadersData, Locator, Msg, Ping,
-	SegmentRequest, Type,
+	self, ArchiveHeaderData, BanReason, Disconnect, GetPeerAddrs, HashHeadersData, Locator, Msg,
+	Ping, SegmentRequest, SendCompletion, TxHashSetRangeRequest, TxReconcileRequest, Type,
 };
 use crate::mwc_core::core::hash::{Hash, Hashed};
 use crate::mwc_core::core::{OutputIdentifier, Segment, SegmentIdentifier, TxKernel};
@@ -37,9 +38,12 @@ use crate::mwc_core::pow::Difficulty;
 use crate::mwc_core::ser::Writeable;
 use crate::mwc_core::{core, global};
 use crate::protocol::Protocol;
+use crate::traffic::PeerTraffic;
+use crate::tx_reconciliation::TxReconciliation;
+use crate::tx_relay::MisbehaviorScore;
 use crate::types::{
-	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	TxHashSetRead,
+	Capabilities, ChainAdapter, DropReason, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo,
+	PeerStatsDisplay, ReasonForBan, TxHashSetRead,
 };
 use crate::util::secp::pedersen::RangeProof;
 use chrono::prelude::Utc;
@@ -69,6 +73,9 @@ pub struct Peer {
 	// because it may be locked by different reasons, so we should wait for that, close
 	// mutex can be taken only during shutdown, it happens once
 	stop_handle: Mutex<conn::StopHandle>,
+	// tx announcements that are waiting for the reconciliation with this peer
+	tx_reconciliation: Arc<TxReconciliation>,
+	misbehavior: Mutex<MisbehaviorScore>,
 }
 
 impl fmt::Debug for Peer {
@@ -88,10 +95,27 @@ impl Peer {
 	) -> std::io::Result<Peer> {
 		let state = Arc::new(RwLock::new(State::Connected));
 		let tracking_adapter = TrackingAdapter::new(adapter);
-		let handler = Protocol::new(Arc::new(tracking_adapter.clone()), info.clone(), server);
-		let tracker = Arc::new(conn::Tracker::new());
-		let (sendh, stoph) =
-			conn::listen(conn, info.version, tracker.clone(), sync_state, handler)?;
+		let tx_reconciliation = Arc::new(TxReconciliation::new());
+		let queue_caps = conn::SendQueueCaps {
+			control: server.config.send_queue_control_cap() as usize,
+			consensus: server.config.send_queue_consensus_cap() as usize,
+			bulk: server.config.send_queue_bulk_cap() as usize,
+		};
+		let tracker = Arc::new(conn::Tracker::with_bandwidth(server.peers.bandwidth()));
+		let handler = Protocol::new(
+			Arc::new(tracking_adapter.clone()),
+			info.clone(),
+			server,
+			tx_reconciliation.clone(),
+		);
+		let (sendh, stoph) = conn::listen(
+			conn,
+			info.version,
+			tracker.clone(),
+			sync_state,
+			queue_caps,
+			handler,
+		)?;
 		let send_handle = Mutex::new(sendh);
 		let stop_handle = Mutex::new(stoph);
 		Ok(Peer {
@@ -101,6 +125,8 @@ impl Peer {
 			tracker,
 			send_handle,
 			stop_handle,
+			tx_reconciliation,
+			misbehavior: Mutex::new(MisbehaviorScore::new()),
 		})
 	}
 
@@ -220,6 +246,11 @@ impl Peer {
 		State::Banned == *self.state.read()
 	}
 
+	/// Add the misbehavior points, returns the peer score
+	pub fn misbehaved(&self, points: u32) -> u32 {
+		self.misbehavior.lock().add(points)
+	}
+
 	/// Whether this peer is stuck on sync.
 	pub fn is_stuck(&self) -> (bool, Difficulty) {
 		let peer_live_info = self.info.live_info.read();
@@ -243,6 +274,50 @@ impl Peer {
 		&self.tracker
 	}
 
+	/// Protocol statistics of this peer for the API.
+	pub fn stats(&self) -> PeerStatsDisplay {
+		let (sent_msg_per_min, sent_bytes_per_min) = {
+			let sent = self.tracker.sent_bytes.read();
+			(sent.count_per_min(), sent.bytes_per_min())
+		};
+		let (received_msg_per_min, received_bytes_per_min) = {
+			let received = self.tracker.received_bytes.read();
+			(received.count_per_min(), received.bytes_per_min())
+		};
+		let live_info = self.info.live_info.read();
+		PeerStatsDisplay {
+			addr: self.info.addr.clone(),
+			direction: self.info.direction,
+			user_agent: self.info.user_agent.clone(),
+			version: self.info.version,
+			capabilities: self.info.capabilities,
+			height: live_info.height,
+			total_difficulty: live_info.total_difficulty,
+			connected_since: live_info.first_seen.timestamp(),
+			sent_msg_per_min,
+			sent_bytes_per_min,
+			received_msg_per_min,
+			received_bytes_per_min,
+			ping_rtt_ms: live_info.ping_rtt_ms,
+		}
+	}
+
+	/// Traffic of this peer for the report period
+	pub fn traffic(&self) -> PeerTraffic {
+		PeerTraffic {
+			addr: self.info.addr.clone(),
+			direction: self.info.direction,
+			user_agent: self.info.user_agent.clone(),
+			connected: self.is_connected(),
+			traffic: self.tracker.traffic.lock().stats(),
+		}
+	}
+
+	/// Total sync data bytes received from this peer
+	pub fn bulk_received_bytes(&self) -> u64 {
+		self.tracker.bulk_received.load(Ordering::Relaxed)
+	}
+
 	/// Set this peer status to banned
 	pub fn set_banned(&self) {
 		*self.state.write() = State::Banned;
@@ -254,6 +329,18 @@ impl Peer {
 		self.send_handle.lock().send(msg)
 	}
 
+	/// Send the message and get the feedback when it is written to the connection
+	fn send_with_completion<T: Writeable>(
+		&self,
+		msg: T,
+		msg_type: Type,
+	) -> Result<SendCompletion, Error> {
+		let mut msg = Msg::new(msg_type, msg, self.info.version)?;
+		let completion = msg.set_completion();
+		self.send_handle.lock().send(msg)?;
+		Ok(completion)
+	}
+
 	/// Send a ping to the remote peer, providing our local difficulty and
 	/// height
 	pub fn send_ping(&self, total_difficulty: Difficulty, height: u64) -> Result<(), Error> {
@@ -261,13 +348,23 @@ impl Peer {
 			total_difficulty,
 			height,
 		};
-		self.send(ping_msg, msg::Type::Ping)
+		self.send(ping_msg, msg::Type::Ping)?;
+		self.info.ping_sent();
+		Ok(())
 	}
 
-	/// Send the ban reason before banning
-	pub fn send_ban_reason(&self, ban_reason: ReasonForBan) -> Result<(), Error> {
+	/// Send the ban reason before banning. The connection should be closed after the
+	/// completion, otherwise the peer might never know why it was banned.
+	pub fn send_ban_reason(&self, ban_reason: ReasonForBan) -> Result<SendCompletion, Error> {
 		let ban_reason_msg = BanReason { ban_reason };
-		self.send(ban_reason_msg, msg::Type::BanReason).map(|_| ())
+		self.send_with_completion(ban_reason_msg, msg::Type::BanReason)
+	}
+
+	/// Send the drop reason before closing the connection with a healthy peer. The
+	/// connection should be closed after the completion, otherwise the message is lost.
+	pub fn send_disconnect(&self, drop_reason: DropReason) -> Result<SendCompletion, Error> {
+		let disconnect_msg = Disconnect { drop_reason };
+		self.send_with_completion(disconnect_msg, msg::Type::Disconnect)
 	}
 
 	pub fn send_compact_block(&self, b: &core::CompactBlock) -> Result<bool, Error> {
@@ -285,6 +382,21 @@ impl Peer {
 		}
 	}
 
+	/// Send the compact block of the block we mined, with the delivery feedback.
+	/// None if the peer already has the block.
+	pub fn send_mined_block(
+		&self,
+		b: &core::CompactBlock,
+	) -> Result<Option<SendCompletion>, Error> {
+		if !self.tracking_adapter.has_recv(b.hash()) {
+			debug!("Send mined block {} to {}", b.hash(), self.info.addr);
+			let completion = self.send_with_completion(b, msg::Type::CompactBlock)?;
+			Ok(Some(completion))
+		} else {
+			Ok(None)
+		}
+	}
+
 	pub fn send_header(&self, bh: &core::BlockHeader) -> Result<bool, Error> {
 		if !self.tracking_adapter.has_recv(bh.hash()) {
 			debug!("Send header {} to {}", bh.hash(), self.info.addr);
@@ -321,6 +433,24 @@ impl Peer {
 	pub fn send_transaction(&self, tx: &core::Transaction) -> Result<bool, Error> {
 		let kernel = &tx.kernels()[0];
 
+		if self
+			.info
+			.capabilities
+			.contains(Capabilities::TX_RECONCILIATION)
+		{
+			let kernel_hash = kernel.hash();
+			if self.tracking_adapter.has_recv(kernel_hash) {
+				return Ok(false);
+			}
+			if self.tx_reconciliation.add(kernel_hash) {
+				debug!(
+					"Tx kernel hash {} is queued for reconciliation with {}",
+					kernel_hash, self.info.addr
+				);
+				return Ok(true);
+			}
+		}
+
 		if self
 			.info
 			.capabilities
@@ -343,6 +473,33 @@ impl Peer {
 		}
 	}
 
+	/// Start the tx announcements reconciliation round. Only the outbound side of the
+	/// connection starts the rounds. Returns true if the request was sent.
+	pub fn send_tx_reconcile_request(&self) -> Result<bool, Error> {
+		if !self.info.is_outbound()
+			|| !self
+				.info
+				.capabilities
+				.contains(Capabilities::TX_RECONCILIATION)
+		{
+			return Ok(false);
+		}
+		match self.tx_reconciliation.start_round() {
+			Some((set_size, salt)) => {
+				debug!(
+					"Send tx reconcile request to {}, set size {}",
+					self.info.addr, set_size
+				);
+				self.send(
+					&TxReconcileRequest { set_size, salt },
+					msg::Type::TxReconcileRequest,
+				)?;
+				Ok(true)
+			}
+			None => Ok(false),
+		}
+	}
+
 	/// Sends the provided stem transaction to the remote peer.
 	/// Note: tracking adapter is ignored for stem transactions (while under
 	/// embargo).
@@ -408,6 +565,41 @@ impl Peer {
 		)
 	}
 
+	/// Sends a request for the full txhashset archive. Fallback for the PIBD.
+	pub fn send_txhashset_request(&self, height: u64, hash: Hash) -> Result<(), Error> {
+		info!(
+			"Asking peer {} for txhashset archive at {} {}.",
+			self.info.addr, height, hash
+		);
+		self.send(
+			&ArchiveHeaderData { hash, height },
+			msg::Type::TxHashSetRequest,
+		)
+	}
+
+	/// Sends a request for the rest of the txhashset archive starting from `offset`,
+	/// resumes the interrupted archive download.
+	pub fn send_txhashset_range_request(
+		&self,
+		height: u64,
+		hash: Hash,
+		offset: u64,
+	) -> Result<(), Error> {
+		info!(
+			"Asking peer {} for txhashset archive at {} {} from offset {}.",
+			self.info.addr, height, hash, offset
+		);
+		self.send(
+			&TxHashSetRangeRequest {
+				hash,
+				height,
+				offset,
+				length: 0,
+			},
+			msg::Type::TxHashSetRangeRequest,
+		)
+	}
+
 	pub fn send_start_headers_hash_sync_request(&self, archive_height: u64) -> Result<(), Error> {
 		info!(
 			"Asking peer {} for headers hash sync for archive_height {}.",
@@ -596,6 +788,7 @@ impl ChainAdapter for TrackingAdapter {
 	fn transaction_received(
 		&self,
 		tx: core::Transaction,
+		peer_info: &PeerInfo,
 		stem: bool,
 	) -> Result<bool, chain::Error> {
 		// Do not track the tx hash for stem txs.
@@ -605,7 +798,7 @@ impl ChainAdapter for TrackingAdapter {
 			let kernel = &tx.kernels()[0];
 			self.push_recv(kernel.hash());
 		}
-		self.adapter.transaction_received(tx, stem)
+		self.adapter.transaction_received(tx, peer_info, stem)
 	}
 
 	fn block_received(
@@ -680,6 +873,32 @@ impl ChainAdapter for TrackingAdapter {
 		self.adapter.get_tmpfile_pathname(tmpfile_name)
 	}
 
+	fn txhashset_receive_ready(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool {
+		self.adapter
+			.txhashset_receive_ready(peer, header_hash, header_height)
+	}
+
+	fn txhashset_download_update(&self, peer: &PeerAddr, downloaded_size: u64, total_size: u64) {
+		self.adapter
+			.txhashset_download_update(peer, downloaded_size, total_size)
+	}
+
+	fn txhashset_received(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) -> Result<(), chain::Error> {
+		self.adapter
+			.txhashset_received(peer, header_hash, header_height, path)
+	}
+
 	fn recieve_pibd_status(
 		&self,
 		peer: &PeerAddr,
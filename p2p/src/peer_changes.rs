@@ -0,0 +1,132 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log of the changes to the connected peer set. Monitoring clients poll it with
+//! the last sequence number they have seen and get only the changes after it.
+
+use crate::mwc_core::pow::Difficulty;
+use crate::types::PeerAddr;
+use crate::util::RwLock;
+use std::collections::VecDeque;
+
+/// Number of changes that we keep. Clients that are further behind need to resync.
+const MAX_PEER_CHANGES: usize = 2000;
+
+/// What happened to the connected peer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PeerChangeKind {
+	/// New peer was connected
+	Connected,
+	/// Peer was disconnected
+	Disconnected,
+	/// Peer reported a new height
+	HeightUpdated,
+	/// Peer was banned, the connected peer is disconnected after that
+	Banned,
+}
+
+/// Single change of the connected peer set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerChange {
+	/// Sequence number of the change
+	pub seq: u64,
+	pub addr: PeerAddr,
+	pub kind: PeerChangeKind,
+	/// Peer height at the moment of the change
+	pub height: u64,
+	/// Peer total difficulty at the moment of the change
+	pub total_difficulty: Difficulty,
+}
+
+/// Changes since the requested sequence number
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerChanges {
+	/// Last sequence number, client should use it for the next request
+	pub seq: u64,
+	/// Requested changes are not available any more (or the node was restarted),
+	/// client needs to reload the full connected peers list.
+	pub resync: bool,
+	pub changes: Vec<PeerChange>,
+}
+
+#[derive(Default)]
+struct PeerChangeLogInner {
+	seq: u64,
+	changes: VecDeque<PeerChange>,
+}
+
+/// Bounded log of the connected peers changes
+#[derive(Default)]
+pub struct PeerChangeLog {
+	inner: RwLock<PeerChangeLogInner>,
+}
+
+impl PeerChangeLog {
+	pub fn new() -> PeerChangeLog {
+		PeerChangeLog::default()
+	}
+
+	/// Add the change, returns its sequence number
+	pub fn record(
+		&self,
+		addr: &PeerAddr,
+		kind: PeerChangeKind,
+		height: u64,
+		total_difficulty: Difficulty,
+	) -> u64 {
+		let mut inner = self.inner.write();
+		inner.seq += 1;
+		let seq = inner.seq;
+		inner.changes.push_back(PeerChange {
+			seq,
+			addr: addr.clone(),
+			kind,
+			height,
+			total_difficulty,
+		});
+		while inner.changes.len() > MAX_PEER_CHANGES {
+			inner.changes.pop_front();
+		}
+		seq
+	}
+
+	/// Changes after `since`. If some of them were already dropped from the log,
+	/// `resync` is set and no changes are returned.
+	pub fn changes_since(&self, since: u64) -> PeerChanges {
+		let inner = self.inner.read();
+		let first_seq = inner
+			.changes
+			.front()
+			.map(|c| c.seq)
+			.unwrap_or(inner.seq + 1);
+		// 'since' from the future means that the node was restarted
+		if since > inner.seq || (since + 1 < first_seq && since < inner.seq) {
+			return PeerChanges {
+				seq: inner.seq,
+				resync: true,
+				changes: vec![],
+			};
+		}
+		PeerChanges {
+			seq: inner.seq,
+			resync: false,
+			changes: inner
+				.changes
+				.iter()
+				.filter(|c| c.seq > since)
+				.cloned()
+				.collect(),
+		}
+	}
+}
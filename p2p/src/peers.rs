@@ -13,36 +13,65 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::util::RwLock;
+use crate::util::{Mutex, RwLock};
 use std::cmp;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use rand::prelude::*;
 
+use crate::addr_verify::{AddrVerify, ADDR_VERIFY_BATCH};
+use crate::bandwidth::BandwidthScheduler;
 use crate::chain;
 use crate::chain::txhashset::BitmapChunk;
-use crate::msg::PeerAddrs;
+use crate::handshake::Handshake;
+use crate::log_throttle::LOG_THROTTLE;
+use crate::msg::{PeerAddrs, SendCompletion};
 use crate::mwc_core::core;
 use crate::mwc_core::core::hash::{Hash, Hashed};
 use crate::mwc_core::core::{OutputIdentifier, Segment, SegmentIdentifier, TxKernel};
 use crate::mwc_core::global;
 use crate::mwc_core::pow::Difficulty;
 use crate::peer::Peer;
+use crate::peer_changes::{PeerChangeKind, PeerChangeLog, PeerChanges};
 use crate::store::{PeerData, PeerStore, State};
+use crate::traffic::{PeerTraffic, TrafficReport, TrafficStats, TRAFFIC_PERIOD_SECS};
+use crate::tx_relay::{TxRelayCheck, TxRelayFilter, TxRelayKind, MISBEHAVIOR_BAN_SCORE};
 use crate::types::{
-	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	TxHashSetRead, MAX_PEER_ADDRS,
+	Capabilities, ChainAdapter, DropReason, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo,
+	ReasonForBan, TxHashSetRead, MAX_PEER_ADDRS,
 };
 use crate::util::secp::pedersen::RangeProof;
 use chrono::prelude::*;
 use chrono::Duration;
+use log::Level;
+use lru::LruCache;
 use mwc_chain::txhashset::Segmenter;
 use mwc_util::StopState;
 
 const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
 
+/// Time to wait for the ban reason to be written before the banned peer is disconnected
+const BAN_REASON_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Time to wait for the drop reasons to be written before the dropped peers are disconnected
+const DISCONNECT_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Number of outbound peers that we keep as anchors between restarts
+const MAX_ANCHORS: usize = 3;
+/// Minimal connection time (seconds) for the outbound peer to become an anchor,
+/// test networks take any connected peer
+const ANCHOR_MIN_CONNECTION_TIME: i64 = 600;
+/// Number of headers that were relayed ahead of their blocks that we remember
+const MAX_RELAYED_HEADERS: usize = 100;
+/// Misbehavior points for the transaction that the peer already sent
+const TX_DUPLICATE_SCORE: u32 = 2;
+/// Misbehavior points for the transaction above the peer relay rate
+const TX_RATE_LIMITED_SCORE: u32 = 5;
+
 struct PeersCapabilities {
 	capabilities: Capabilities,
 	time: DateTime<Utc>,
@@ -51,12 +80,32 @@ struct PeersCapabilities {
 pub struct Peers {
 	pub adapter: Arc<dyn ChainAdapter>,
 	store: PeerStore,
-	peers: RwLock<HashMap<PeerAddr, Arc<Peer>>>,
-	config: P2PConfig,
+	/// Connected peers by the session id
+	peers: RwLock<HashMap<u64, Arc<Peer>>>,
+	/// The peer counts can be changed at runtime by the config reload
+	config: RwLock<P2PConfig>,
 	stop_state: Arc<StopState>,
 	boost_peers_capabilities: RwLock<PeersCapabilities>,
 	excluded_peers: Arc<RwLock<HashSet<PeerAddr>>>,
 	out_peers_failures: Arc<RwLock<HashMap<PeerAddr, u32>>>,
+	peer_changes: PeerChangeLog,
+	/// Headers that were already relayed with header first relay
+	relayed_headers: RwLock<LruCache<Hash, ()>>,
+	/// Gossiped addresses waiting for the dial back
+	addr_verify: AddrVerify,
+	/// Node has the onion address and the socks proxy, so it can run Tor only
+	tor_available: AtomicBool,
+	/// Only the onion peers are allowed, clearnet peers are closed and never dialed
+	tor_only: AtomicBool,
+	/// Traffic of the peers disconnected during the traffic report period, with
+	/// the disconnect time
+	disconnected_traffic: Mutex<VecDeque<(i64, PeerTraffic)>>,
+	/// Blocks history depth bits that we advertise, updated after the compaction
+	history_capabilities: RwLock<Capabilities>,
+	/// Bandwidth budget shared by all connections
+	bandwidth: Arc<BandwidthScheduler>,
+	/// Relayed transactions dedup and per peer rate limits
+	tx_relay: Mutex<TxRelayFilter>,
 }
 
 impl Peers {
@@ -66,10 +115,18 @@ impl Peers {
 		config: P2PConfig,
 		stop_state: Arc<StopState>,
 	) -> Peers {
+		let bandwidth = Arc::new(BandwidthScheduler::new(
+			config.bandwidth_limit(),
+			config.bandwidth_relay_ratio(),
+		));
+		let tx_relay = Mutex::new(TxRelayFilter::new(
+			config.tx_relay_rate(),
+			config.tx_relay_burst(),
+		));
 		Peers {
 			adapter,
 			store,
-			config,
+			config: RwLock::new(config),
 			peers: RwLock::new(HashMap::new()),
 			stop_state,
 			boost_peers_capabilities: RwLock::new(PeersCapabilities {
@@ -78,9 +135,40 @@ impl Peers {
 			}),
 			excluded_peers: Arc::new(RwLock::new(HashSet::new())),
 			out_peers_failures: Arc::new(RwLock::new(HashMap::new())),
+			peer_changes: PeerChangeLog::new(),
+			relayed_headers: RwLock::new(LruCache::new(
+				NonZeroUsize::new(MAX_RELAYED_HEADERS).unwrap(),
+			)),
+			addr_verify: AddrVerify::new(),
+			tor_available: AtomicBool::new(false),
+			tor_only: AtomicBool::new(false),
+			disconnected_traffic: Mutex::new(VecDeque::new()),
+			history_capabilities: RwLock::new(Capabilities::UNKNOWN),
+			bandwidth,
+			tx_relay,
 		}
 	}
 
+	/// Bandwidth budget for the sync and the relay traffic
+	pub fn bandwidth(&self) -> Arc<BandwidthScheduler> {
+		self.bandwidth.clone()
+	}
+
+	/// Current p2p settings
+	pub fn config(&self) -> P2PConfig {
+		self.config.read().clone()
+	}
+
+	/// Replace the peer count limits. The extra peers are dropped by the next run of
+	/// the peers monitor.
+	pub fn set_peer_counts(&self, config: &P2PConfig) {
+		let mut current = self.config.write();
+		current.peer_max_inbound_count = config.peer_max_inbound_count;
+		current.peer_max_outbound_count = config.peer_max_outbound_count;
+		current.peer_min_preferred_outbound_count = config.peer_min_preferred_outbound_count;
+		current.peer_listener_buffer_count = config.peer_listener_buffer_count;
+	}
+
 	/// Mark those peers as excluded, so the will never be in 'connected' list
 	pub fn set_excluded_peers(&self, peers: &Vec<PeerAddr>) {
 		let mut excluded_peers = self.excluded_peers.write();
@@ -133,7 +221,11 @@ impl Peers {
 		{
 			// Scope for peers vector lock - dont hold the peers lock while adding to lmdb
 			let mut peers = self.peers.try_write_for(LOCK_TIMEOUT).ok_or_else(|| {
-				error!("add_connected: failed to get peers lock");
+				throttled_log!(
+					Level::Error,
+					"add_connected_lock",
+					"add_connected: failed to get peers lock"
+				);
 				Error::Timeout
 			})?;
 			peer_data = PeerData {
@@ -144,9 +236,12 @@ impl Peers {
 				last_banned: 0,
 				ban_reason: ReasonForBan::None,
 				last_connected: Utc::now().timestamp(),
+				drop_reason: DropReason::None,
+				verified: true,
 			};
 			info!("Adding newly connected Healthy peer {}.", peer_data.addr);
-			peers.insert(peer_data.addr.clone(), peer);
+			self.record_change(&peer, PeerChangeKind::Connected);
+			peers.insert(peer.info.session_id, peer);
 		}
 		if let Err(e) = self.save_peer(&peer_data) {
 			error!("Could not save connected peer address: {:?}", e);
@@ -165,6 +260,8 @@ impl Peers {
 			last_banned: Utc::now().timestamp(),
 			ban_reason,
 			last_connected: Utc::now().timestamp(),
+			drop_reason: DropReason::None,
+			verified: false,
 		};
 		info!("Banning peer {}, ban_reason={:?}", addr, ban_reason);
 		self.save_peer(&peer_data)
@@ -176,10 +273,51 @@ impl Peers {
 	/// to decide how best to handle this.
 	pub fn is_known(&self, addr: &PeerAddr) -> Result<bool, Error> {
 		let peers = self.peers.try_read_for(LOCK_TIMEOUT).ok_or_else(|| {
-			error!("is_known: failed to get peers lock");
+			throttled_log!(
+				Level::Error,
+				"is_known_lock",
+				"is_known: failed to get peers lock"
+			);
 			Error::Internal("is_known: failed to get peers lock".to_string())
 		})?;
-		Ok(peers.contains_key(addr))
+		Ok(peers.values().any(|p| p.info.addr.same_endpoint(addr))
+			|| Peers::ip_connections(&peers, addr) >= self.config.read().peer_max_per_ip() as usize)
+	}
+
+	/// Number of the connected peers with the same IP. Loopback and onion addresses
+	/// are not counted, for them only exact address match matters.
+	fn ip_connections(peers: &HashMap<u64, Arc<Peer>>, addr: &PeerAddr) -> usize {
+		match addr {
+			PeerAddr::Ip(ip) if !ip.ip().is_loopback() => peers
+				.values()
+				.filter(|p| match &p.info.addr {
+					PeerAddr::Ip(p_ip) => p_ip.ip() == ip.ip(),
+					PeerAddr::Onion(_) => false,
+				})
+				.count(),
+			_ => 0,
+		}
+	}
+
+	/// Is there a connected session with the same peer. The peer is identified by its
+	/// advertised listening (or onion) address and the user agent, a reconnecting node
+	/// has the same ones while the handshake nonce is new for every connection.
+	pub fn is_session_connected(&self, info: &PeerInfo) -> bool {
+		match self.peers.try_read_for(LOCK_TIMEOUT) {
+			Some(peers) => peers.values().any(|p| {
+				p.info.session_id != info.session_id
+					&& p.info.addr.same_endpoint(&info.addr)
+					&& p.info.user_agent == info.user_agent
+			}),
+			None => {
+				throttled_log!(
+					Level::Error,
+					"is_session_connected_lock",
+					"is_session_connected: failed to get peers lock"
+				);
+				false
+			}
+		}
 	}
 
 	/// Iterator over our current peers.
@@ -196,7 +334,11 @@ impl Peers {
 			None => {
 				if !self.stop_state.is_stopped() {
 					// When stopped, peers access is locked by stopped thread
-					error!("connected_peers: failed to get peers lock");
+					throttled_log!(
+						Level::Error,
+						"connected_peers_lock",
+						"connected_peers: failed to get peers lock"
+					);
 				}
 				vec![]
 			}
@@ -225,6 +367,7 @@ impl Peers {
 		message: &str,
 	) -> Result<(), Error> {
 		info!(
+			event = "peer_banned", peer:% = peer_addr;
 			"Banning peer {}, ban_reason {:?}, {}",
 			peer_addr, ban_reason, message
 		);
@@ -239,17 +382,97 @@ impl Peers {
 					peer_addr, ban_reason
 				);
 				// setting peer status will get it removed at the next clean_peer
-				peer.send_ban_reason(ban_reason)?;
+				let completion = peer.send_ban_reason(ban_reason)?;
 				peer.set_banned();
+				self.record_change(&peer, PeerChangeKind::Banned);
+				// Closing the connection drops the messages that are not written yet
+				if !completion.wait(BAN_REASON_SEND_TIMEOUT) {
+					debug!(
+						"ban_peer: ban reason wasn't delivered to {} before disconnect",
+						peer_addr
+					);
+				}
 				peer.stop();
 				let mut peers = self.peers.try_write_for(LOCK_TIMEOUT).ok_or_else(|| {
-					error!("ban_peer: failed to get peers lock");
+					throttled_log!(
+						Level::Error,
+						"ban_peer_lock",
+						"ban_peer: failed to get peers lock"
+					);
 					Error::PeerException("ban_peer: failed to get peers lock".to_string())
 				})?;
-				peers.remove(&peer.info.addr);
+				if let Some(peer) = peers.remove(&peer.info.session_id) {
+					self.record_change(&peer, PeerChangeKind::Disconnected);
+				}
 				Ok(())
 			}
-			None => Err(Error::PeerNotFound),
+			None => {
+				self.peer_changes
+					.record(peer_addr, PeerChangeKind::Banned, 0, Difficulty::zero());
+				Err(Error::PeerNotFound)
+			}
+		}
+	}
+
+	/// Add the misbehavior points to the connected peer, the peer is banned when
+	/// its score reaches MISBEHAVIOR_BAN_SCORE
+	pub fn report_misbehavior(
+		&self,
+		peer_addr: &PeerAddr,
+		points: u32,
+		ban_reason: ReasonForBan,
+		message: &str,
+	) {
+		let peer = match self.get_connected_peer(peer_addr) {
+			Some(peer) => peer,
+			None => return,
+		};
+		let score = peer.misbehaved(points);
+		debug!(
+			"Peer {} misbehavior score {}, {}",
+			peer_addr, score, message
+		);
+		if score >= MISBEHAVIOR_BAN_SCORE {
+			if let Err(e) = self.ban_peer(peer_addr, ban_reason, message) {
+				error!("Unable to ban peer {}, Error: {}", peer_addr, e);
+			}
+		}
+	}
+
+	// Check the relayed transaction, the violations are reported as the misbehavior.
+	// Returns true if the transaction should be passed to the pool.
+	fn check_tx_relay(&self, kernel_hash: Hash, peer_info: &PeerInfo, kind: TxRelayKind) -> bool {
+		let res = self
+			.tx_relay
+			.lock()
+			.check(&peer_info.addr, kernel_hash, kind);
+		match res {
+			TxRelayCheck::Accept => true,
+			TxRelayCheck::Known => false,
+			TxRelayCheck::Duplicate => {
+				self.report_misbehavior(
+					&peer_info.addr,
+					TX_DUPLICATE_SCORE,
+					ReasonForBan::TxFlood,
+					&format!("repeated tx {} {:?}", kernel_hash, kind),
+				);
+				false
+			}
+			TxRelayCheck::RateLimited => {
+				throttled_log!(
+					Level::Debug,
+					"tx_relay_rate_limited",
+					"Peer {} exceeded the tx relay rate",
+					peer_info.addr
+				);
+				self.report_misbehavior(
+					&peer_info.addr,
+					TX_RATE_LIMITED_SCORE,
+					ReasonForBan::TxFlood,
+					"tx relay rate exceeded",
+				);
+				false
+			}
 		}
 	}
 
@@ -276,20 +499,30 @@ impl Peers {
 				Ok(true) => count += 1,
 				Ok(false) => (),
 				Err(e) => {
-					debug!(
+					throttled_log!(
+						Level::Debug,
+						&format!("broadcast_{}", obj_name),
 						"Error sending {:?} to peer {:?}: {:?}",
-						obj_name, &p.info.addr, e
+						obj_name,
+						&p.info.addr,
+						e
 					);
 
 					let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
 						Some(peers) => peers,
 						None => {
-							error!("broadcast: failed to get peers lock");
+							throttled_log!(
+								Level::Error,
+								"broadcast_lock",
+								"broadcast: failed to get peers lock"
+							);
 							break;
 						}
 					};
 					p.stop();
-					peers.remove(&p.info.addr);
+					if peers.remove(&p.info.session_id).is_some() {
+						self.record_change(&p, PeerChangeKind::Disconnected);
+					}
 				}
 			}
 		}
@@ -309,10 +542,39 @@ impl Peers {
 		);
 	}
 
+	/// Broadcast the compact block of the block we mined to all our connected peers.
+	/// Returns the delivery feedback for every peer the block was sent to.
+	pub fn broadcast_mined_block(&self, b: &core::CompactBlock) -> Vec<SendCompletion> {
+		let completions = Mutex::new(vec![]);
+		let count = self.broadcast("mined block", |p| match p.send_mined_block(b)? {
+			Some(completion) => {
+				completions.lock().push(completion);
+				Ok(true)
+			}
+			None => Ok(false),
+		});
+		debug!(
+			"broadcast_mined_block: {}, {} at {}, to {} peers, done.",
+			b.hash(),
+			b.header.pow.total_difficulty,
+			b.header.height,
+			count,
+		);
+		completions.into_inner()
+	}
+
 	/// Broadcast a block header to all our connected peers.
 	/// A peer implementation may drop the broadcast request
 	/// if it knows the remote peer already has the header.
 	pub fn broadcast_header(&self, bh: &core::BlockHeader) {
+		if self.relayed_headers.read().contains(&bh.hash()) {
+			debug!(
+				"broadcast_header: {} at {} was already relayed",
+				bh.hash(),
+				bh.height
+			);
+			return;
+		}
 		let count = self.broadcast("header", |p| p.send_header(bh));
 		debug!(
 			"broadcast_header: {}, {} at {}, to {} peers, done.",
@@ -323,6 +585,25 @@ impl Peers {
 		);
 	}
 
+	/// Relay the validated header of the block that is not processed yet, so peers can
+	/// request the block early. Header is relayed only once, broadcast_header for the
+	/// accepted block will skip it.
+	pub fn relay_header_first(&self, bh: &core::BlockHeader) {
+		let bh_hash = bh.hash();
+		{
+			let mut relayed_headers = self.relayed_headers.write();
+			if relayed_headers.contains(&bh_hash) {
+				return;
+			}
+			relayed_headers.put(bh_hash, ());
+		}
+		let count = self.broadcast("header", |p| p.send_header(bh));
+		debug!(
+			"relay_header_first: {}, {} at {}, to {} peers, done.",
+			bh_hash, bh.pow.total_difficulty, bh.height, count,
+		);
+	}
+
 	/// Broadcasts the provided transaction to all our connected peers.
 	/// A peer implementation may drop the broadcast request
 	/// if it knows the remote peer already has the transaction.
@@ -337,7 +618,7 @@ impl Peers {
 			}
 		});
 		if count == 0 {
-			warn!("Unable to broadcast transaction. Not found any connected peers that accepts Tx with base fee {}", base_fee);
+			throttled_log!(Level::Warn, "broadcast_transaction_no_peers", "Unable to broadcast transaction. Not found any connected peers that accepts Tx with base fee {}", base_fee);
 		}
 		debug!(
 			"broadcast_transaction: {} to {} peers, done.",
@@ -346,21 +627,40 @@ impl Peers {
 		);
 	}
 
+	/// Start the tx announcements reconciliation round with the connected peers
+	/// that support it. Only outbound peers are requested, the inbound ones are
+	/// reconciled by their side.
+	pub fn reconcile_transactions(&self) {
+		let count = self.broadcast("tx reconcile request", |p| p.send_tx_reconcile_request());
+		if count > 0 {
+			debug!("reconcile_transactions: requested {} peers", count);
+		}
+	}
+
 	/// Ping all our connected peers. Always automatically expects a pong back
 	/// or disconnects. This acts as a liveness test.
 	pub fn check_all(&self, total_difficulty: Difficulty, height: u64) {
+		// Report the repeated errors that were suppressed since the last check
+		LOG_THROTTLE.flush();
+
 		for p in self.iter().connected() {
 			if let Err(e) = p.send_ping(total_difficulty, height) {
 				debug!("Error pinging peer {:?}: {:?}", &p.info.addr, e);
 				let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
 					Some(peers) => peers,
 					None => {
-						error!("check_all: failed to get peers lock");
+						throttled_log!(
+							Level::Error,
+							"check_all_lock",
+							"check_all: failed to get peers lock"
+						);
 						break;
 					}
 				};
 				p.stop();
-				peers.remove(&p.info.addr);
+				if peers.remove(&p.info.session_id).is_some() {
+					self.record_change(&p, PeerChangeKind::Disconnected);
+				}
 			}
 		}
 	}
@@ -398,6 +698,208 @@ impl Peers {
 		}
 	}
 
+	/// Update the blocks history depth that we advertise to the new peers.
+	/// Full history (u64::MAX) is advertised as the archive node.
+	pub fn set_history_depth(&self, depth: u64) {
+		let mut caps = Capabilities::from_history_depth(depth);
+		if depth == u64::MAX {
+			caps |= Capabilities::BLOCK_HIST;
+		}
+		let mut history_capabilities = self.history_capabilities.write();
+		if *history_capabilities != caps {
+			debug!("Blocks history depth is {}, advertising {:?}", depth, caps);
+			*history_capabilities = caps;
+		}
+	}
+
+	/// Blocks history depth bits that we advertise
+	pub fn history_capabilities(&self) -> Capabilities {
+		*self.history_capabilities.read()
+	}
+
+	/// Set if the node can connect to the onion peers, i.e. it has the onion
+	/// address and the socks proxy
+	pub fn set_tor_available(&self, available: bool) {
+		self.tor_available.store(available, Ordering::Relaxed);
+	}
+
+	/// True if only the onion peers are allowed
+	pub fn is_tor_only(&self) -> bool {
+		self.tor_only.load(Ordering::Relaxed)
+	}
+
+	/// Switch between clearnet+Tor and Tor only modes. In Tor only mode all
+	/// connected clearnet peers are closed. Returns the number of closed peers.
+	pub fn set_tor_only(&self, tor_only: bool) -> Result<usize, Error> {
+		if tor_only && !self.tor_available.load(Ordering::Relaxed) {
+			return Err(Error::Internal(
+				"Tor is not running, unable to switch to Tor only mode".to_string(),
+			));
+		}
+		let prev = self.tor_only.swap(tor_only, Ordering::Relaxed);
+		if prev != tor_only {
+			info!("Tor only mode is {}", if tor_only { "on" } else { "off" });
+		}
+		if !tor_only {
+			return Ok(0);
+		}
+
+		let mut peers = self
+			.peers
+			.try_write_for(LOCK_TIMEOUT)
+			.ok_or_else(|| Error::Internal("set_tor_only: failed to get peers lock".to_string()))?;
+		let clearnet: Vec<u64> = peers
+			.iter()
+			.filter(|(_, p)| matches!(p.info.addr, PeerAddr::Ip(_)))
+			.map(|(k, _)| *k)
+			.collect();
+		for key in &clearnet {
+			if let Some(peer) = peers.remove(key) {
+				debug!("Closing clearnet peer {}, Tor only mode", peer.info.addr);
+				peer.stop();
+				self.record_change(&peer, PeerChangeKind::Disconnected);
+			}
+		}
+		Ok(clearnet.len())
+	}
+
+	/// Number of the gossiped addresses waiting for the dial back
+	pub fn addr_verify_pending(&self) -> usize {
+		self.addr_verify.pending()
+	}
+
+	/// Dial back the next batch of the gossiped addresses. Addresses that complete the
+	/// handshake on our chain are stored as Healthy and verified, others are dropped.
+	/// Blocking call, the dial back can take several seconds per address.
+	pub fn verify_addrs(&self, handshake: &Handshake) {
+		let total_difficulty = self.total_difficulty().unwrap_or(Difficulty::min());
+		for addr in self.addr_verify.next_batch(ADDR_VERIFY_BATCH) {
+			if self.stop_state.is_stopped() {
+				break;
+			}
+			if let Ok(true) = self.exists_peer(&addr) {
+				continue;
+			}
+			if !AddrVerify::dial(&addr, handshake, total_difficulty) {
+				debug!("Gossiped address {} is not a peer, dropping it", addr);
+				continue;
+			}
+			let peer = PeerData {
+				addr: addr.clone(),
+				capabilities: Capabilities::UNKNOWN,
+				user_agent: "".to_string(),
+				flags: State::Healthy,
+				last_banned: 0,
+				ban_reason: ReasonForBan::None,
+				last_connected: 0,
+				drop_reason: DropReason::None,
+				verified: true,
+			};
+			if let Err(e) = self.save_peer(&peer) {
+				error!("Could not save verified peer address {}: {:?}", addr, e);
+			}
+		}
+	}
+
+	/// Save the longest connected outbound peers as anchors, so on restart we can
+	/// reconnect to them before asking the seeds. If there are no long lived peers
+	/// (node was running for a short time), previous anchors are kept.
+	pub fn save_anchors(&self) {
+		let now = Utc::now();
+		let min_connection_time = if global::is_production_mode() {
+			ANCHOR_MIN_CONNECTION_TIME
+		} else {
+			0
+		};
+		let mut candidates: Vec<(DateTime<Utc>, PeerAddr)> = self
+			.iter()
+			.outbound()
+			.connected()
+			.filter(|p| !p.is_banned())
+			.map(|p| (p.info.live_info.read().first_seen, p.info.addr.clone()))
+			.filter(|(first_seen, _)| now - *first_seen >= Duration::seconds(min_connection_time))
+			.collect();
+		if candidates.is_empty() {
+			debug!("No long lived outbound peers, keeping previous anchors");
+			return;
+		}
+		candidates.sort_by_key(|(first_seen, _)| *first_seen);
+		let anchors: Vec<PeerAddr> = candidates
+			.into_iter()
+			.take(MAX_ANCHORS)
+			.map(|(_, addr)| addr)
+			.collect();
+		info!("Saving anchor peers {:?}", anchors);
+		if let Err(e) = self.store.save_anchors(&anchors) {
+			error!("failed to save anchor peers: {:?}", e);
+		}
+	}
+
+	/// Anchor peers saved on the last clean shutdown
+	pub fn get_anchors(&self) -> Vec<PeerAddr> {
+		match self.store.get_anchors() {
+			Ok(anchors) => anchors,
+			Err(e) => {
+				error!("failed to read anchor peers: {:?}", e);
+				vec![]
+			}
+		}
+	}
+
+	fn record_change(&self, peer: &Peer, kind: PeerChangeKind) {
+		self.peer_changes.record(
+			&peer.info.addr,
+			kind,
+			peer.info.height(),
+			peer.info.total_difficulty(),
+		);
+		if kind == PeerChangeKind::Disconnected {
+			let mut traffic = peer.traffic();
+			traffic.connected = false;
+			let now = Utc::now().timestamp();
+			let mut disconnected = self.disconnected_traffic.lock();
+			while disconnected
+				.front()
+				.map(|(t, _)| now - *t > TRAFFIC_PERIOD_SECS)
+				.unwrap_or(false)
+			{
+				disconnected.pop_front();
+			}
+			disconnected.push_back((now, traffic));
+		}
+	}
+
+	/// Top talkers for the last TRAFFIC_PERIOD_SECS. Includes connected peers and
+	/// the peers that were disconnected during this period.
+	pub fn traffic_report(&self, top: usize) -> TrafficReport {
+		let now = Utc::now().timestamp();
+		let mut peers: Vec<PeerTraffic> = self.iter().into_iter().map(|p| p.traffic()).collect();
+		peers.extend(
+			self.disconnected_traffic
+				.lock()
+				.iter()
+				.filter(|(t, _)| now - *t <= TRAFFIC_PERIOD_SECS)
+				.map(|(_, p)| p.clone()),
+		);
+		let mut total = TrafficStats::default();
+		for p in &peers {
+			total.merge(&p.traffic);
+		}
+		peers.sort_by_key(|p| cmp::Reverse(p.traffic.total()));
+		peers.truncate(top);
+		TrafficReport {
+			period_secs: TRAFFIC_PERIOD_SECS,
+			total,
+			peers,
+		}
+	}
+
+	/// Changes of the connected peers (connects, disconnects, height updates)
+	/// after the sequence number `since`.
+	pub fn peer_changes_since(&self, since: u64) -> PeerChanges {
+		self.peer_changes.changes_since(since)
+	}
+
 	/// Get peer in store by address
 	pub fn get_peer(&self, peer_addr: &PeerAddr) -> Result<PeerData, Error> {
 		self.store.get_peer(peer_addr).map_err(From::from)
@@ -450,10 +952,10 @@ impl Peers {
 				let ref peer: &Peer = peer.as_ref();
 				if peer.is_banned() {
 					info!("clean_peers {:?}, peer banned", peer.info.addr);
-					rm.push(peer.info.addr.clone());
+					rm.push((peer.info.clone(), DropReason::None));
 				} else if !peer.is_connected() {
 					info!("clean_peers {:?}, not connected", peer.info.addr);
-					rm.push(peer.info.addr.clone());
+					rm.push((peer.info.clone(), DropReason::None));
 				} else if peer.is_abusive() {
 					let received = peer.tracker().received_bytes.read().count_per_min();
 					let sent = peer.tracker().sent_bytes.read().count_per_min();
@@ -462,7 +964,7 @@ impl Peers {
 						peer.info.addr, sent, received,
 					);
 					let _ = self.update_state(&peer.info.addr, State::Banned);
-					rm.push(peer.info.addr.clone());
+					rm.push((peer.info.clone(), DropReason::None));
 				} else {
 					let (stuck, diff) = peer.is_stuck();
 					match self.adapter.total_difficulty() {
@@ -470,7 +972,7 @@ impl Peers {
 							if stuck && diff < total_difficulty {
 								info!("clean_peers {:?}, stuck peer", peer.info.addr);
 								let _ = self.update_state(&peer.info.addr, State::Defunct);
-								rm.push(peer.info.addr.clone());
+								rm.push((peer.info.clone(), DropReason::Stuck));
 							}
 						}
 						Err(e) => error!("failed to get total difficulty: {:?}", e),
@@ -492,7 +994,7 @@ impl Peers {
 				.filter(|x| {
 					!preferred_peers.contains(&x.addr) && !x.capabilities.contains(boost_capability)
 				})
-				.map(|x| x.addr)
+				.map(|x| (x, DropReason::CapabilityMismatch))
 				.take(excess_outgoing_count)
 				.collect();
 			rm.append(&mut addrs);
@@ -534,7 +1036,7 @@ impl Peers {
 						"Requesting disconnect for outband peer {:?} because of low performance",
 						peer.addr
 					);
-					rm.push(peer.addr.clone());
+					rm.push((peer.clone(), DropReason::LowPerformance));
 				}
 				next_failures.insert(peer.addr.clone(), fail_counter);
 			}
@@ -553,7 +1055,7 @@ impl Peers {
 			});
 			let mut addrs = peer_infos
 				.into_iter()
-				.map(|x| x.addr)
+				.map(|x| (x, DropReason::ExcessSlots))
 				.take(excess_outgoing_count)
 				.collect();
 			rm.append(&mut addrs);
@@ -568,23 +1070,70 @@ impl Peers {
 			let mut addrs: Vec<_> = inbound_peers()
 				.filter(|x| !preferred_peers.contains(&x.info.addr))
 				.take(excess_incoming_count)
-				.map(|x| x.info.addr.clone())
+				.map(|x| (x.info.clone(), DropReason::ExcessSlots))
 				.collect();
 			rm.append(&mut addrs);
 		}
 
 		// now clean up peer map based on the list to remove
+		let mut drop_reasons = vec![];
+		let mut stopped_peers = vec![];
+		let mut completions = vec![];
 		{
 			let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
 				Some(peers) => peers,
 				None => {
-					error!("clean_peers: failed to get peers lock");
+					throttled_log!(
+						Level::Error,
+						"clean_peers_lock",
+						"clean_peers: failed to get peers lock"
+					);
 					return;
 				}
 			};
-			for addr in rm {
-				let _ = peers.get(&addr).map(|peer| peer.stop());
-				peers.remove(&addr);
+			for (info, drop_reason) in rm {
+				if let Some(peer) = peers.get(&info.session_id) {
+					if drop_reason != DropReason::None {
+						info!(
+							"clean_peers {:?}, dropping with reason {:?}",
+							info.addr, drop_reason
+						);
+						if let Ok(completion) = peer.send_disconnect(drop_reason) {
+							completions.push(completion);
+						}
+						drop_reasons.push((info.addr.clone(), drop_reason));
+					}
+				}
+				if let Some(peer) = peers.remove(&info.session_id) {
+					self.record_change(&peer, PeerChangeKind::Disconnected);
+					stopped_peers.push(peer);
+				}
+			}
+		}
+
+		// Closing the connection drops the messages that are not written yet, so the
+		// drop reasons are flushed first. Waiting is done outside of the peers lock.
+		if !completions.is_empty() {
+			let delivered = SendCompletion::wait_all(&completions, DISCONNECT_SEND_TIMEOUT);
+			if delivered < completions.len() {
+				debug!(
+					"clean_peers: {} of {} drop reasons weren't delivered before disconnect",
+					completions.len() - delivered,
+					completions.len()
+				);
+			}
+		}
+		for peer in stopped_peers {
+			peer.stop();
+		}
+
+		// record drop reasons outside of the peers lock
+		for (addr, drop_reason) in drop_reasons {
+			if let Err(e) = self.store.update_drop_reason(&addr, drop_reason) {
+				debug!(
+					"clean_peers: unable to save drop reason for {}, {}",
+					addr, e
+				);
 			}
 		}
 	}
@@ -595,6 +1144,7 @@ impl Peers {
 			peer.stop();
 		}
 		for (_, peer) in peers.drain() {
+			self.record_change(&peer, PeerChangeKind::Disconnected);
 			peer.wait();
 		}
 	}
@@ -613,6 +1163,7 @@ impl Peers {
 
 		let need_count = self
 			.config
+			.read()
 			.peer_min_preferred_outbound_count(self.is_sync_mode());
 		if self.is_sync_mode() {
 			count >= need_count
@@ -666,15 +1217,30 @@ impl ChainAdapter for Peers {
 		kernel_hash: Hash,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
+		if !self.check_tx_relay(kernel_hash, peer_info, TxRelayKind::Announce) {
+			return Ok(true);
+		}
 		self.adapter.tx_kernel_received(kernel_hash, peer_info)
 	}
 
 	fn transaction_received(
 		&self,
 		tx: core::Transaction,
+		peer_info: &PeerInfo,
 		stem: bool,
 	) -> Result<bool, chain::Error> {
-		self.adapter.transaction_received(tx, stem)
+		let kind = if stem {
+			TxRelayKind::StemTransaction
+		} else {
+			TxRelayKind::Transaction
+		};
+		// Transaction without kernels is rejected by the pool
+		if let Some(kernel) = tx.kernels().first() {
+			if !self.check_tx_relay(kernel.hash(), peer_info, kind) {
+				return Ok(true);
+			}
+		}
+		self.adapter.transaction_received(tx, peer_info, stem)
 	}
 
 	fn block_received(
@@ -773,6 +1339,32 @@ impl ChainAdapter for Peers {
 		self.adapter.get_tmpfile_pathname(tmpfile_name)
 	}
 
+	fn txhashset_receive_ready(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool {
+		self.adapter
+			.txhashset_receive_ready(peer, header_hash, header_height)
+	}
+
+	fn txhashset_download_update(&self, peer: &PeerAddr, downloaded_size: u64, total_size: u64) {
+		self.adapter
+			.txhashset_download_update(peer, downloaded_size, total_size)
+	}
+
+	fn txhashset_received(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) -> Result<(), chain::Error> {
+		self.adapter
+			.txhashset_received(peer, header_hash, header_height, path)
+	}
+
 	/// For MWC handshake we need to have a segmenter ready with output bitmap ready and commited.
 	fn prepare_segmenter(&self) -> Result<Segmenter, chain::Error> {
 		self.adapter.prepare_segmenter()
@@ -902,7 +1494,11 @@ impl ChainAdapter for Peers {
 
 	fn peer_difficulty(&self, addr: &PeerAddr, diff: Difficulty, height: u64) {
 		if let Some(peer) = self.get_connected_peer(addr) {
+			let height_changed = peer.info.height() != height;
 			peer.info.update(height, diff);
+			if height_changed {
+				self.record_change(&peer, PeerChangeKind::HeightUpdated);
+			}
 		}
 		self.adapter.peer_difficulty(addr, diff, height)
 	}
@@ -911,10 +1507,15 @@ impl ChainAdapter for Peers {
 impl NetAdapter for Peers {
 	/// Find good peers we know with the provided capability and return their
 	/// addresses.
+	/// Verified addresses go first.
 	fn find_peer_addrs(&self, capab: Capabilities) -> Vec<PeerAddr> {
-		let peers: Vec<PeerData> = self
+		let (verified, unverified): (Vec<PeerData>, Vec<PeerData>) = self
 			.find_peers(State::Healthy, capab)
 			.into_iter()
+			.partition(|p| p.verified);
+		let peers: Vec<PeerData> = verified
+			.into_iter()
+			.chain(unverified)
 			.take(MAX_PEER_ADDRS as usize)
 			.collect();
 		trace!("find_peer_addrs: {} healthy peers picked", peers.len());
@@ -931,6 +1532,10 @@ impl NetAdapter for Peers {
 					continue;
 				}
 			}
+			// Sampled addresses are stored after the dial back
+			if self.addr_verify.sample(&pa) {
+				continue;
+			}
 			let peer = PeerData {
 				addr: pa,
 				capabilities: Capabilities::UNKNOWN,
@@ -939,6 +1544,8 @@ impl NetAdapter for Peers {
 				last_banned: 0,
 				ban_reason: ReasonForBan::None,
 				last_connected: 0,
+				drop_reason: DropReason::None,
+				verified: false,
 			};
 			to_save.push(peer);
 		}
@@ -1058,8 +1665,19 @@ impl<I: Iterator<Item = Arc<Peer>>> PeersIter<I> {
 		}
 	}
 
+	/// Peer with the address. The same ip and port is preferred, there can be several
+	/// peers connected from the same IP.
 	pub fn by_addr(&mut self, addr: &PeerAddr) -> Option<Arc<Peer>> {
-		self.iter.find(|p| p.info.addr == *addr)
+		let mut same_ip = None;
+		for p in &mut self.iter {
+			if p.info.addr.same_endpoint(addr) {
+				return Some(p);
+			}
+			if same_ip.is_none() && p.info.addr == *addr {
+				same_ip = Some(p);
+			}
+		}
+		same_ip
 	}
 
 	/// Choose a random peer from the current (filtered) peers.
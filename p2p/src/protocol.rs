@@ -20,24 +20,35 @@ use crate::{chain, Capabilities, ReasonForBan};
 use crate::msg::{
 	ArchiveHeaderData, Consumed, Headers, HeadersHashSegmentResponse, Message, Msg,
 	OutputBitmapSegmentResponse, OutputSegmentResponse, PeerAddrs, PibdSyncState, Pong,
-	SegmentRequest, SegmentResponse, StartHeadersHashResponse, TxHashSetArchive, Type,
+	SegmentRequest, SegmentResponse, StartHeadersHashResponse, TxHashSetArchive,
+	TxHashSetRangeArchive, TxReconcileDiff, TxReconcileHashes, Type,
 };
 use crate::serv::Server;
-use crate::types::{Error, NetAdapter, PeerAddr, PeerInfo};
+use crate::tx_reconciliation::TxReconciliation;
+use crate::types::{AttachmentMeta, Error, NetAdapter, PeerAddr, PeerInfo};
+use chrono::Utc;
+use std::fs;
 use std::sync::Arc;
 
 pub struct Protocol {
 	adapter: Arc<dyn NetAdapter>,
 	peer_info: PeerInfo,
 	server: Server,
+	tx_reconciliation: Arc<TxReconciliation>,
 }
 
 impl Protocol {
-	pub fn new(adapter: Arc<dyn NetAdapter>, peer_info: PeerInfo, server: Server) -> Protocol {
+	pub fn new(
+		adapter: Arc<dyn NetAdapter>,
+		peer_info: PeerInfo,
+		server: Server,
+		tx_reconciliation: Arc<TxReconciliation>,
+	) -> Protocol {
 		Protocol {
 			adapter,
 			peer_info,
 			server,
+			tx_reconciliation,
 		}
 	}
 }
@@ -58,14 +69,31 @@ impl MessageHandler for Protocol {
 		}
 
 		let consumed = match message {
-			Message::Attachment(_update, _) => {
-				error!("handle_payload: Message::Attachment received but we never requested it. It is disabled in this version of node");
-				adapter.ban_peer(
+			Message::Attachment(update, _) => {
+				// Attachment chunks are accepted by the connection only if the transfer was
+				// started from the TxHashSetArchive or TxHashSetRangeArchive message below.
+				let meta = &update.meta;
+				adapter.txhashset_download_update(
 					&self.peer_info.addr,
-					ReasonForBan::BadRequest,
-					"Message::Attachment received but we never requested it",
+					meta.offset + (meta.size - update.left) as u64,
+					meta.offset + meta.size as u64,
 				);
-				return Err(Error::BadMessage);
+				if update.left == 0 {
+					info!(
+						"handle_payload: txhashset archive for {} at {} is downloaded from {} in {} seconds",
+						meta.hash,
+						meta.height,
+						self.peer_info.addr,
+						(Utc::now() - meta.start_time).num_seconds()
+					);
+					adapter.txhashset_received(
+						&self.peer_info.addr,
+						meta.hash,
+						meta.height,
+						meta.path.clone(),
+					)?;
+				}
+				Consumed::None
 			}
 
 			Message::Ping(ping) => {
@@ -81,6 +109,7 @@ impl MessageHandler for Protocol {
 			}
 
 			Message::Pong(pong) => {
+				self.peer_info.pong_received();
 				adapter.peer_difficulty(&self.peer_info.addr, pong.total_difficulty, pong.height);
 				Consumed::None
 			}
@@ -90,6 +119,14 @@ impl MessageHandler for Protocol {
 				Consumed::Disconnect
 			}
 
+			Message::Disconnect(disconnect) => {
+				info!(
+					"handle_payload: peer {} is disconnecting us, reason {:?}",
+					self.peer_info.addr, disconnect.drop_reason
+				);
+				Consumed::Disconnect
+			}
+
 			Message::TransactionKernel(h) => {
 				debug!("handle_payload: received tx kernel: {}", h);
 				adapter.tx_kernel_received(h, &self.peer_info)?;
@@ -108,13 +145,13 @@ impl MessageHandler for Protocol {
 
 			Message::Transaction(tx) => {
 				debug!("handle_payload: received tx");
-				adapter.transaction_received(tx, false)?;
+				adapter.transaction_received(tx, &self.peer_info, false)?;
 				Consumed::None
 			}
 
 			Message::StemTransaction(tx) => {
 				debug!("handle_payload: received stem tx");
-				adapter.transaction_received(tx, true)?;
+				adapter.transaction_received(tx, &self.peer_info, true)?;
 				Consumed::None
 			}
 
@@ -315,14 +352,239 @@ impl MessageHandler for Protocol {
 				}
 			}
 
-			Message::TxHashSetArchive(_sm_arch) => {
-				error!("handle_payload: txhashset archive received but we never requested it. It is disabled in this version of node");
-				adapter.ban_peer(
+			Message::TxHashSetRangeRequest(range_req) => {
+				debug!(
+					"handle_payload: txhashset range req for {} at {}, offset {}, length {}",
+					range_req.hash, range_req.height, range_req.offset, range_req.length
+				);
+
+				let txhashset_header = self.adapter.txhashset_archive_header()?;
+				let txhashset_header_hash = txhashset_header.hash();
+				let txhashset = self.adapter.txhashset_read(txhashset_header_hash);
+
+				if let Some(txhashset) = txhashset {
+					let file_sz = txhashset.reader.metadata()?.len();
+					// If archive was rebuilt since the first request, the peer gets nothing
+					// and has to restart the transfer for the new archive.
+					let (offset, length) =
+						if range_req.hash != txhashset_header_hash || range_req.offset >= file_sz {
+							(0, 0)
+						} else {
+							let left = file_sz - range_req.offset;
+							let length = if range_req.length == 0 {
+								left
+							} else {
+								std::cmp::min(range_req.length, left)
+							};
+							(range_req.offset, length)
+						};
+					let mut resp = Msg::new(
+						Type::TxHashSetRangeArchive,
+						&TxHashSetRangeArchive {
+							height: txhashset_header.height as u64,
+							hash: txhashset_header_hash,
+							bytes: file_sz,
+							offset,
+							length,
+						},
+						self.peer_info.version,
+					)?;
+					if length > 0 {
+						resp.add_attachment_range(txhashset.reader, offset, length);
+					}
+					Consumed::Response(resp)
+				} else {
+					Consumed::None
+				}
+			}
+
+			Message::TxHashSetRangeArchive(sm_arch) => {
+				info!(
+					"handle_payload: txhashset range archive for {} at {}, offset {}, length {} of {} from {}",
+					sm_arch.hash,
+					sm_arch.height,
+					sm_arch.offset,
+					sm_arch.length,
+					sm_arch.bytes,
+					self.peer_info.addr
+				);
+				if sm_arch.length == 0 {
+					// The peer rebuilt the archive, the download will be restarted
+					warn!(
+						"handle_payload: peer {} has no requested range of txhashset archive",
+						self.peer_info.addr
+					);
+					return Ok(Consumed::None);
+				}
+				if !adapter.txhashset_receive_ready(
+					&self.peer_info.addr,
+					sm_arch.hash,
+					sm_arch.height,
+				) {
+					error!("handle_payload: txhashset range archive received but we never requested it");
+					adapter.ban_peer(
+						&self.peer_info.addr,
+						ReasonForBan::BadRequest,
+						"txhashset range archive received but we never requested it",
+					);
+					return Err(Error::BadMessage);
+				}
+				// The range is always requested up to the end of the archive
+				if sm_arch.offset.checked_add(sm_arch.length) != Some(sm_arch.bytes) {
+					error!(
+						"handle_payload: peer {} sent txhashset range that doesn't end at the archive end",
+						self.peer_info.addr
+					);
+					return Err(Error::BadMessage);
+				}
+
+				let start_time = Utc::now();
+				// The partial archive of the interrupted download, the rest is appended to it
+				let path = adapter
+					.get_tmp_dir()
+					.join(format!("txhashset-{}.zip", sm_arch.hash));
+				let file = fs::OpenOptions::new().write(true).open(&path)?;
+				if file.metadata()?.len() < sm_arch.offset {
+					error!(
+						"handle_payload: txhashset range from {} starts at {} after the end of {}",
+						self.peer_info.addr,
+						sm_arch.offset,
+						path.display()
+					);
+					return Err(Error::BadMessage);
+				}
+				file.set_len(sm_arch.offset)?;
+				adapter.txhashset_download_update(
 					&self.peer_info.addr,
-					ReasonForBan::BadRequest,
-					"txhashset archive received but we never requested it",
+					sm_arch.offset,
+					sm_arch.bytes,
+				);
+				Consumed::Attachment(
+					Arc::new(AttachmentMeta {
+						size: sm_arch.length as usize,
+						offset: sm_arch.offset,
+						hash: sm_arch.hash,
+						height: sm_arch.height,
+						start_time,
+						path,
+					}),
+					file,
+				)
+			}
+
+			Message::TxReconcileRequest(req) => {
+				debug!(
+					"handle_payload: tx reconcile request from {}, set size {}",
+					self.peer_info.addr, req.set_size
+				);
+				let sketch = self
+					.tx_reconciliation
+					.sketch_for_request(req.set_size, req.salt);
+				Consumed::Response(Msg::new(
+					Type::TxReconcileSketch,
+					sketch,
+					self.peer_info.version,
+				)?)
+			}
+
+			Message::TxReconcileSketch(sketch) => {
+				let res = self.tx_reconciliation.process_sketch(&sketch);
+				debug!(
+					"handle_payload: tx reconcile sketch from {}, decoded {}, announce {}, want {}",
+					self.peer_info.addr,
+					res.decoded,
+					res.announce.len(),
+					res.want.len()
+				);
+				Consumed::Response(Msg::new(
+					Type::TxReconcileDiff,
+					TxReconcileDiff {
+						decoded: res.decoded,
+						want: res.want,
+						announce: res.announce,
+					},
+					self.peer_info.version,
+				)?)
+			}
+
+			Message::TxReconcileDiff(diff) => {
+				debug!(
+					"handle_payload: tx reconcile diff from {}, decoded {}, announce {}, want {}",
+					self.peer_info.addr,
+					diff.decoded,
+					diff.announce.len(),
+					diff.want.len()
 				);
-				return Err(Error::BadMessage);
+				for h in diff.announce {
+					adapter.tx_kernel_received(h, &self.peer_info)?;
+				}
+				let hashes = self
+					.tx_reconciliation
+					.process_diff(diff.decoded, &diff.want);
+				Consumed::Response(Msg::new(
+					Type::TxReconcileHashes,
+					TxReconcileHashes { hashes },
+					self.peer_info.version,
+				)?)
+			}
+
+			Message::TxReconcileHashes(resp) => {
+				debug!(
+					"handle_payload: tx reconcile hashes from {}, {} hashes",
+					self.peer_info.addr,
+					resp.hashes.len()
+				);
+				for h in resp.hashes {
+					adapter.tx_kernel_received(h, &self.peer_info)?;
+				}
+				Consumed::None
+			}
+
+			Message::TxHashSetArchive(sm_arch) => {
+				info!(
+					"handle_payload: txhashset archive for {} at {}, size {} from {}",
+					sm_arch.hash, sm_arch.height, sm_arch.bytes, self.peer_info.addr
+				);
+				if !adapter.txhashset_receive_ready(
+					&self.peer_info.addr,
+					sm_arch.hash,
+					sm_arch.height,
+				) {
+					error!("handle_payload: txhashset archive received but we never requested it");
+					adapter.ban_peer(
+						&self.peer_info.addr,
+						ReasonForBan::BadRequest,
+						"txhashset archive received but we never requested it",
+					);
+					return Err(Error::BadMessage);
+				}
+				if sm_arch.bytes == 0 {
+					warn!(
+						"handle_payload: peer {} sent empty txhashset archive",
+						self.peer_info.addr
+					);
+					return Ok(Consumed::None);
+				}
+
+				let start_time = Utc::now();
+				// Leftover of the interrupted download of the same archive is replaced
+				let path = adapter.get_tmpfile_pathname(format!("txhashset-{}.zip", sm_arch.hash));
+				let file = fs::OpenOptions::new()
+					.write(true)
+					.create_new(true)
+					.open(&path)?;
+				adapter.txhashset_download_update(&self.peer_info.addr, 0, sm_arch.bytes);
+				Consumed::Attachment(
+					Arc::new(AttachmentMeta {
+						size: sm_arch.bytes as usize,
+						offset: 0,
+						hash: sm_arch.hash,
+						height: sm_arch.height,
+						start_time,
+						path,
+					}),
+					file,
+				)
 			}
 			Message::StartHeadersHashRequest(sm_req) => {
 				debug!(
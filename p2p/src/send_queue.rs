@@ -0,0 +1,188 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per connection send queues, one per message priority. The connection writer
+//! takes the control messages first, then blocks and transactions, then sync data.
+//! Sync data still gets its share when the relay traffic never stops.
+
+use crate::msg::{Msg, MsgPriority};
+use crate::types::Error;
+use crossbeam::channel::{
+	Receiver, RecvTimeoutError, Select, SendTimeoutError, Sender, TryRecvError, TrySendError,
+};
+use log::Level;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Max number of sync data messages in a single write. Control and consensus messages that
+/// arrive in the meantime don't need to wait for the whole headers batch.
+pub const BULK_WRITE_BATCH: usize = 4;
+
+/// Number of writes without sync data while it is waiting. The next write takes the sync
+/// data together with the relay messages, so the sync can't be starved by the relay.
+pub const BULK_MAX_SKIPPED_BATCHES: usize = 4;
+
+/// Time to wait for the space in the control queue. The peer that doesn't read the
+/// control messages that long is disconnected.
+pub const CONTROL_SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sizes of the per priority send queues
+#[derive(Debug, Clone, Copy)]
+pub struct SendQueueCaps {
+	pub control: usize,
+	pub consensus: usize,
+	pub bulk: usize,
+}
+
+#[derive(Clone)]
+pub struct ConnHandle {
+	/// Channels to allow sending data through the connection, one per message priority
+	control_channel: Sender<Msg>,
+	consensus_channel: Sender<Msg>,
+	bulk_channel: Sender<Msg>,
+	/// Connection close flag, set when the control message can't be queued
+	stopped: Arc<AtomicBool>,
+}
+
+/// Receiving ends of the ConnHandle channels, owned by the connection writer
+pub struct SendQueues {
+	control: Receiver<Msg>,
+	consensus: Receiver<Msg>,
+	bulk: Receiver<Msg>,
+	// writes without sync data while sync data was waiting
+	skipped_bulk: usize,
+}
+
+/// Create the send queues of the connection. `stopped` is the close flag of the connection.
+pub fn send_queues(caps: SendQueueCaps, stopped: Arc<AtomicBool>) -> (ConnHandle, SendQueues) {
+	let (control_tx, control_rx) = crossbeam::channel::bounded(caps.control);
+	let (consensus_tx, consensus_rx) = crossbeam::channel::bounded(caps.consensus);
+	let (bulk_tx, bulk_rx) = crossbeam::channel::bounded(caps.bulk);
+	(
+		ConnHandle {
+			control_channel: control_tx,
+			consensus_channel: consensus_tx,
+			bulk_channel: bulk_tx,
+			stopped,
+		},
+		SendQueues {
+			control: control_rx,
+			consensus: consensus_rx,
+			bulk: bulk_rx,
+			skipped_bulk: 0,
+		},
+	)
+}
+
+impl ConnHandle {
+	/// Send msg via the synchronous, bounded channel (sync_sender) of its priority.
+	/// Every priority has its own buffer, so sync data can't push out pings and ban reasons.
+	/// * Disconnected: Propagate this up to the caller so the peer connection can be closed.
+	/// * Full control queue: the control messages are never dropped. We wait for the space
+	/// up to CONTROL_SEND_TIMEOUT, then the peer is not reading from the connection and
+	/// the connection is closed.
+	/// * Full consensus or sync data queue: Our internal msg buffer is full. This is not a
+	/// problem with the peer connection and we do not want to close the connection. We drop
+	/// the msg rather than blocking here. The relay and the sync requests are retried.
+	pub fn send(&self, msg: Msg) -> Result<(), Error> {
+		let priority = msg.msg_type().priority();
+		let channel = match priority {
+			MsgPriority::Control => {
+				return match self.control_channel.send_timeout(msg, CONTROL_SEND_TIMEOUT) {
+					Ok(()) => Ok(()),
+					Err(SendTimeoutError::Disconnected(_)) => {
+						Err(Error::Send("send disconnected".to_owned()))
+					}
+					Err(SendTimeoutError::Timeout(msg)) => {
+						debug!(
+							"conn_handle: control buffer is full for {:?}, closing the connection, msg {:?}",
+							CONTROL_SEND_TIMEOUT,
+							msg.msg_type()
+						);
+						self.stopped.store(true, Ordering::Relaxed);
+						Err(Error::Send("control buffer is full".to_owned()))
+					}
+				};
+			}
+			MsgPriority::Consensus => &self.consensus_channel,
+			MsgPriority::Bulk => &self.bulk_channel,
+		};
+		match channel.try_send(msg) {
+			Ok(()) => Ok(()),
+			Err(TrySendError::Disconnected(_)) => {
+				Err(Error::Send("try_send disconnected".to_owned()))
+			}
+			Err(TrySendError::Full(msg)) => {
+				throttled_log!(
+					Level::Debug,
+					&format!("conn_send_full_{:?}", priority),
+					"conn_handle: try_send but {:?} buffer is full, dropping msg {:?}",
+					priority,
+					msg.msg_type()
+				);
+				Ok(())
+			}
+		}
+	}
+}
+
+impl SendQueues {
+	/// Wait for the messages to send and read them, higher priority first. Sync data is
+	/// taken when there is nothing else to send, or when it was skipped for
+	/// BULK_MAX_SKIPPED_BATCHES writes. At most BULK_WRITE_BATCH sync data messages.
+	pub fn recv_batch(&mut self, timeout: Duration) -> Result<Vec<Msg>, RecvTimeoutError> {
+		let mut select = Select::new();
+		select.recv(&self.control);
+		select.recv(&self.consensus);
+		select.recv(&self.bulk);
+		if select.ready_timeout(timeout).is_err() {
+			return Err(RecvTimeoutError::Timeout);
+		}
+
+		let mut data = vec![];
+		Self::drain(&self.control, &mut data, usize::MAX)?;
+		Self::drain(&self.consensus, &mut data, usize::MAX)?;
+		if data.is_empty() || self.skipped_bulk >= BULK_MAX_SKIPPED_BATCHES {
+			Self::drain(&self.bulk, &mut data, BULK_WRITE_BATCH)?;
+			self.skipped_bulk = 0;
+		} else if !self.bulk.is_empty() {
+			self.skipped_bulk += 1;
+		}
+		if data.is_empty() {
+			return Err(RecvTimeoutError::Timeout);
+		}
+		Ok(data)
+	}
+
+	fn drain(
+		rx: &Receiver<Msg>,
+		data: &mut Vec<Msg>,
+		limit: usize,
+	) -> Result<(), RecvTimeoutError> {
+		// channel capacity limits the number of the messages that we can read here
+		let mut taken = 0;
+		while taken < limit {
+			match rx.try_recv() {
+				Ok(msg) => {
+					data.push(msg);
+					taken += 1;
+				}
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+			}
+		}
+		Ok(())
+	}
+}
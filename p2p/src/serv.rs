@@ -23,6 +23,7 @@ use std::time::Duration;
 
 use crate::chain;
 use crate::chain::txhashset::BitmapChunk;
+use crate::conn_limit::{ConnLimit, ConnRateLimiter};
 use crate::handshake::Handshake;
 use crate::mwc_core::core;
 use crate::mwc_core::core::hash::Hash;
@@ -39,6 +40,7 @@ use crate::types::{
 use crate::util::secp::pedersen::RangeProof;
 use crate::util::StopState;
 use crate::PeerAddr::Ip;
+use log::Level;
 use mwc_chain::txhashset::Segmenter;
 use mwc_chain::SyncState;
 
@@ -73,6 +75,13 @@ impl Server {
 		socks_port: u16,
 		onion_address: Option<String>,
 	) -> Result<Server, Error> {
+		let peers = Arc::new(Peers::new(
+			PeerStore::new(db_root)?,
+			adapter,
+			config.clone(),
+			stop_state.clone(),
+		));
+		peers.set_tor_available(onion_address.is_some() && socks_port != 0);
 		Ok(Server {
 			config: config.clone(),
 			capabilities,
@@ -81,12 +90,7 @@ impl Server {
 				config.clone(),
 				onion_address.clone(),
 			)),
-			peers: Arc::new(Peers::new(
-				PeerStore::new(db_root)?,
-				adapter,
-				config,
-				stop_state.clone(),
-			)),
+			peers,
 			sync_state,
 			stop_state,
 			socks_port,
@@ -94,6 +98,11 @@ impl Server {
 		})
 	}
 
+	/// Capabilities that we advertise, including the current blocks history depth
+	pub fn capabilities(&self) -> Capabilities {
+		self.capabilities | self.peers.history_capabilities()
+	}
+
 	/// Starts a new TCP server and listen to incoming connections. This is a
 	/// blocking call until the TCP server stops.
 	pub fn listen(&self) -> Result<(), Error> {
@@ -102,6 +111,12 @@ impl Server {
 		let listener = TcpListener::bind(addr)?;
 		listener.set_nonblocking(true)?;
 
+		let mut conn_limiter = ConnRateLimiter::new(
+			self.config.inbound_conn_rate(),
+			self.config.inbound_conn_burst(),
+			self.config.inbound_conn_ban_threshold(),
+		);
+
 		let sleep_time = Duration::from_millis(5);
 		loop {
 			// Pause peer ingress connection request. Only for tests.
@@ -143,6 +158,33 @@ impl Server {
 						_ => {}
 					}
 
+					if let PeerAddr::Ip(socket_addr) = &peer_addr {
+						match conn_limiter.check(socket_addr.ip()) {
+							ConnLimit::Accept => {}
+							limit => {
+								if limit == ConnLimit::Ban {
+									warn!(
+										"Too many connections from {}, banning",
+										socket_addr.ip()
+									);
+									let _ = self.peers.add_banned(
+										peer_addr.clone(),
+										ReasonForBan::ConnectionFlood,
+									);
+								} else {
+									throttled_log!(
+										Level::Debug,
+										"inbound_conn_limit",
+										"Too many connections from {}, refusing connection.",
+										socket_addr.ip()
+									);
+								}
+								let _ = stream.shutdown(Shutdown::Both);
+								continue;
+							}
+						}
+					}
+
 					if self.check_undesirable(&stream) {
 						// Shutdown the incoming TCP connection if it is not desired
 						if let Err(e) = stream.shutdown(Shutdown::Both) {
@@ -190,8 +232,9 @@ impl Server {
 			)));
 		}
 
+		let config = self.peers.config();
 		let max_allowed_connections =
-			self.config.peer_max_inbound_count() + self.config.peer_max_outbound_count(true) + 10;
+			config.peer_max_inbound_count() + config.peer_max_outbound_count(true) + 10;
 		if self.peers.get_number_connected_peers() > max_allowed_connections as usize {
 			return Err(Error::ConnectionClose(String::from(
 				"Too many established connections...",
@@ -244,6 +287,13 @@ impl Server {
 
 		let stream = match addr.clone() {
 			PeerAddr::Ip(address) => {
+				// Tor only mode, we don't want to advertise our IP in the handshake
+				if self.peers.is_tor_only() {
+					return Err(Error::ConnectionClose(format!(
+						"Not connecting to clearnet peer {}, Tor only mode",
+						address
+					)));
+				}
 				// we do this, not a good solution, but for now, we'll use it. Other side usually detects with ip.
 				self_addr = PeerAddr::Ip(SocketAddr::new(self.config.host, self.config.port));
 				if self.socks_port != 0 {
@@ -300,7 +350,7 @@ impl Server {
 
 				let peer = Peer::connect(
 					stream,
-					self.capabilities,
+					self.capabilities(),
 					total_diff,
 					self_addr,
 					&self.handshake,
@@ -331,8 +381,9 @@ impl Server {
 			return Err(Error::ConnectionClose(String::from("Server is stopping")));
 		}
 
+		let config = self.peers.config();
 		let max_allowed_connections =
-			self.config.peer_max_inbound_count() + self.config.peer_max_outbound_count(true) + 10;
+			config.peer_max_inbound_count() + config.peer_max_outbound_count(true) + 10;
 		if self.peers.get_number_connected_peers() > max_allowed_connections as usize {
 			return Err(Error::ConnectionClose(String::from(
 				"Too many established connections...",
@@ -344,13 +395,32 @@ impl Server {
 		// accept the peer and add it to the server map
 		let peer = Peer::accept(
 			stream,
-			self.capabilities,
+			self.capabilities(),
 			total_diff,
 			&self.handshake,
 			self.peers.clone(),
 			self.sync_state.clone(),
 			self.clone(),
 		)?;
+		// The same peer that is already connected (reconnect or a second session), it is
+		// not a misbehaviour, so just drop the new connection instead of banning.
+		if self.peers.is_session_connected(&peer.info) {
+			peer.stop();
+			return Err(Error::ConnectionClose(format!(
+				"duplicate session with {}",
+				peer.info.addr
+			)));
+		}
+		// Tor peers advertise the onion address, everything else is a clearnet peer
+		if self.peers.is_tor_only() {
+			if let PeerAddr::Ip(_) = peer.info.addr {
+				peer.stop();
+				return Err(Error::ConnectionClose(format!(
+					"clearnet peer {} is not accepted, Tor only mode",
+					peer.info.addr
+				)));
+			}
+		}
 		// if we are using TOR, it will be the local addressed because it comes from the proxy
 		// Will still need to save all the peers and renameit after peer will share the TOR address
 		self.peers.add_connected(Arc::new(peer))?;
@@ -365,14 +435,15 @@ impl Server {
 	/// A default buffer of 8 peers is allowed to help with network growth.
 	/// 2. The peer has been previously banned and the ban period hasn't
 	/// expired yet.
-	/// 3. We're already connected to a peer at the same IP. While there are
-	/// many reasons multiple peers can legitimately share identical IP
-	/// addresses (NAT), network distribution is improved if they choose
-	/// different sets of peers themselves. In addition, it prevent potential
-	/// duplicate connections, malicious or not.
+	/// 3. We're already connected to peer_max_per_ip peers at the same IP. Multiple
+	/// peers can legitimately share identical IP addresses (NAT), but network
+	/// distribution is improved if they choose different sets of peers themselves,
+	/// so the default cap is 1. Duplicate sessions are detected after the handshake,
+	/// by the advertised address and the user agent of the peer.
 	fn check_undesirable(&self, stream: &TcpStream) -> bool {
+		let config = self.peers.config();
 		if self.peers.iter().inbound().connected().count() as u32
-			>= self.config.peer_max_inbound_count() + self.config.peer_listener_buffer_count()
+			>= config.peer_max_inbound_count() + config.peer_listener_buffer_count()
 		{
 			debug!("Accepting new connection will exceed peer limit, refusing connection.");
 			return true;
@@ -408,8 +479,14 @@ impl Server {
 		self.sync_state.is_syncing()
 	}
 
+	/// Dial back the next batch of the gossiped addresses with our handshake
+	pub fn verify_addrs(&self) {
+		self.peers.verify_addrs(&self.handshake);
+	}
+
 	pub fn stop(&self) {
 		self.stop_state.stop();
+		self.peers.save_anchors();
 		self.peers.stop();
 	}
 
@@ -442,6 +519,7 @@ impl ChainAdapter for DummyAdapter {
 	fn transaction_received(
 		&self,
 		_: core::Transaction,
+		_peer_info: &PeerInfo,
 		_stem: bool,
 	) -> Result<bool, chain::Error> {
 		Ok(true)
@@ -503,6 +581,28 @@ impl ChainAdapter for DummyAdapter {
 		unimplemented!()
 	}
 
+	fn txhashset_receive_ready(
+		&self,
+		_peer: &PeerAddr,
+		_header_hash: Hash,
+		_header_height: u64,
+	) -> bool {
+		false
+	}
+
+	fn txhashset_download_update(&self, _peer: &PeerAddr, _downloaded_size: u64, _total_size: u64) {
+	}
+
+	fn txhashset_received(
+		&self,
+		_peer: &PeerAddr,
+		_header_hash: Hash,
+		_header_height: u64,
+		_path: PathBuf,
+	) -> Result<(), chain::Error> {
+		Ok(())
+	}
+
 	fn prepare_segmenter(&self) -> Result<Segmenter, chain::Error> {
 		unimplemented!()
 	}
@@ -20,7 +20,8 @@ use num::FromPrimitive;
 use rand::thread_rng;
 
 use crate::mwc_core::ser::{self, DeserializationMode, Readable, Reader, Writeable, Writer};
-use crate::types::{Capabilities, PeerAddr, ReasonForBan};
+use crate::types::{Capabilities, DropReason, PeerAddr, ReasonForBan};
+use mwc_store::migration::{self, Migration, MigrationOptions, MigrationReport};
 use mwc_store::{self, option_to_not_found, to_key, Error};
 use mwc_util::secp::rand::Rng;
 
@@ -28,6 +29,7 @@ const DB_NAME: &str = "peerV2";
 const STORE_SUBPATH: &str = "peers";
 
 const PEER_PREFIX: u8 = b'P';
+const ANCHOR_PREFIX: u8 = b'A';
 
 // Types of messages
 enum_from_primitive! {
@@ -57,6 +59,11 @@ pub struct PeerData {
 	pub ban_reason: ReasonForBan,
 	/// Time when we last connected to this peer.
 	pub last_connected: i64,
+	/// The reason why we dropped the connection with this peer last time
+	pub drop_reason: DropReason,
+	/// The address was confirmed by a connection or a dial back. Addresses that
+	/// we only know from the gossip are not verified.
+	pub verified: bool,
 }
 
 impl Writeable for PeerData {
@@ -75,7 +82,9 @@ impl Writeable for PeerData {
 			[write_u8, self.flags as u8],
 			[write_i64, self.last_banned],
 			[write_i32, self.ban_reason as i32],
-			[write_i64, self.last_connected]
+			[write_i64, self.last_connected],
+			[write_u8, self.drop_reason as u8],
+			[write_u8, self.verified as u8]
 		);
 		Ok(())
 	}
@@ -95,6 +104,16 @@ impl Readable for PeerData {
 			Err(_) => Utc::now().timestamp(),
 			Ok(lc) => lc,
 		};
+		// Same for the drop reason, older records don't have it
+		let drop_reason = match reader.read_u8() {
+			Err(_) => DropReason::None,
+			Ok(dr) => DropReason::from_u8(dr).unwrap_or(DropReason::None),
+		};
+		// And for the verified flag
+		let verified = match reader.read_u8() {
+			Err(_) => false,
+			Ok(v) => v != 0,
+		};
 
 		let user_agent = String::from_utf8(ua)
 			.map_err(|e| ser::Error::CorruptedData(format!("Fail to read user agent, {}", e)))?;
@@ -112,6 +131,8 @@ impl Readable for PeerData {
 				last_banned: lb,
 				ban_reason,
 				last_connected,
+				drop_reason,
+				verified,
 			}),
 			None => Err(ser::Error::CorruptedData(
 				"Unable to read PeerData State".to_string(),
@@ -120,18 +141,43 @@ impl Readable for PeerData {
 	}
 }
 
+/// Schema migrations of the peer store, in order. Append only, never change the released ones.
+const PEER_MIGRATIONS: [Migration<PeerStore>; 0] = [];
+
 /// Storage facility for peer data.
 pub struct PeerStore {
 	db: mwc_store::Store,
 }
 
 impl PeerStore {
-	/// Instantiates a new peer store under the provided root path.
+	/// Instantiates a new peer store under the provided root path. The pending schema
+	/// migrations are applied.
 	pub fn new(db_root: &str) -> Result<PeerStore, Error> {
+		let store = PeerStore::open(db_root)?;
+		migration::run_migrations(
+			&store.db,
+			&store,
+			&PEER_MIGRATIONS,
+			&MigrationOptions::default(),
+		)?;
+		Ok(store)
+	}
+
+	fn open(db_root: &str) -> Result<PeerStore, Error> {
 		let db = mwc_store::Store::new(db_root, Some(DB_NAME), Some(STORE_SUBPATH), None)?;
 		Ok(PeerStore { db: db })
 	}
 
+	/// Run the pending schema migrations of the peer store at `db_root` with the options.
+	/// The store must not be open.
+	pub fn run_migrations(
+		db_root: &str,
+		options: &MigrationOptions,
+	) -> Result<MigrationReport, Error> {
+		let store = PeerStore::open(db_root)?;
+		migration::run_migrations(&store.db, &store, &PEER_MIGRATIONS, options)
+	}
+
 	pub fn save_peer(&self, p: &PeerData) -> Result<(), Error> {
 		debug!("save_peer: {:?} marked {:?}", p.addr, p.flags);
 
@@ -236,6 +282,56 @@ impl PeerStore {
 		batch.commit()
 	}
 
+	/// Convenience method to load a peer data, record the reason why we dropped
+	/// the connection with it and save it back.
+	pub fn update_drop_reason(
+		&self,
+		peer_addr: &PeerAddr,
+		drop_reason: DropReason,
+	) -> Result<(), Error> {
+		let batch = self.db.batch_write()?;
+
+		let mut peer = option_to_not_found(
+			batch.get_ser::<PeerData>(&peer_key(peer_addr)[..], None),
+			|| format!("Peer at address: {}", peer_addr),
+		)?;
+
+		peer.drop_reason = drop_reason;
+
+		batch.put_ser(&peer_key(peer_addr)[..], &peer)?;
+		batch.commit()
+	}
+
+	/// Replace the stored anchor peers with the provided addresses.
+	/// Anchors are long lived outbound peers that we reconnect to first on restart.
+	pub fn save_anchors(&self, anchors: &[PeerAddr]) -> Result<(), Error> {
+		let old_anchors = self.get_anchors()?;
+
+		let batch = self.db.batch_write()?;
+		for addr in &old_anchors {
+			batch.delete(&anchor_key(addr)[..])?;
+		}
+		for addr in anchors {
+			debug!("save_anchors: {:?}", addr);
+			batch.put_ser(&anchor_key(addr)[..], addr)?;
+		}
+		batch.commit()
+	}
+
+	/// Anchor peers that were saved on the last clean shutdown.
+	pub fn get_anchors(&self) -> Result<Vec<PeerAddr>, Error> {
+		let key = to_key(ANCHOR_PREFIX, "");
+		let protocol_version = self.db.protocol_version();
+		let anchors = self
+			.db
+			.iter(&key, move |_, mut v| {
+				ser::deserialize(&mut v, protocol_version, DeserializationMode::default())
+					.map_err(From::from)
+			})?
+			.collect();
+		Ok(anchors)
+	}
+
 	/// Deletes peers from the storage that satisfy some condition `predicate`
 	pub fn delete_peers<F>(&self, predicate: F) -> Result<(), Error>
 	where
@@ -268,3 +364,7 @@ impl PeerStore {
 fn peer_key(peer_addr: &PeerAddr) -> Vec<u8> {
 	to_key(PEER_PREFIX, peer_addr.as_key())
 }
+
+fn anchor_key(peer_addr: &PeerAddr) -> Vec<u8> {
+	to_key(ANCHOR_PREFIX, peer_addr.as_key())
+}
@@ -0,0 +1,492 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for the integration tests. FakePeer talks the wire protocol over a real
+//! loopback connection, so a test can script exactly what the node receives
+//! (including malformed data) without running a full server on the other side.
+//! ArchiveAdapter is the node side of the txhashset archive transfer tests.
+
+use crate::chain;
+use crate::chain::txhashset::{BitmapChunk, Segmenter};
+use crate::msg::{read_header, read_message, Hand, MsgHeader, MsgHeaderWrapper, Shake, Type};
+use crate::mwc_core::core::hash::{Hash, Hashed};
+use crate::mwc_core::core::{
+	Block, BlockHeader, CompactBlock, OutputIdentifier, Segment, SegmentIdentifier, Transaction,
+	TxKernel,
+};
+use crate::mwc_core::global;
+use crate::mwc_core::pow::Difficulty;
+use crate::mwc_core::ser::{self, ProtocolVersion, Readable, Writeable};
+use crate::serv::DummyAdapter;
+use crate::types::{
+	Capabilities, ChainAdapter, Error, NetAdapter, PeerAddr, PeerInfo, ReasonForBan, TxHashSetRead,
+};
+use crate::util::secp::pedersen::RangeProof;
+use crate::util::Mutex;
+use rand::{thread_rng, Rng};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// User agent that fake peer reports in the handshake
+pub const FAKE_PEER_USER_AGENT: &str = "MW/MWC fake peer";
+
+/// Default timeout for the fake peer reads
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connected pair of loopback tcp streams, (node side, fake peer side).
+pub fn socket_pair() -> io::Result<(TcpStream, TcpStream)> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let fake_side = TcpStream::connect(listener.local_addr()?)?;
+	let (node_side, _) = listener.accept()?;
+	Ok((node_side, fake_side))
+}
+
+/// Single action of the fake peer script
+pub enum FakePeerStep {
+	/// Send a well formed message with the serialized body
+	Send(Type, Vec<u8>),
+	/// Send bytes as they are, for malformed messages
+	SendRaw(Vec<u8>),
+	/// Read the next message and check its type, the body is discarded
+	Expect(Type),
+	/// Pause before the next step
+	Sleep(Duration),
+}
+
+/// The other side of the connection for a node under test
+pub struct FakePeer {
+	stream: TcpStream,
+	version: ProtocolVersion,
+}
+
+impl FakePeer {
+	/// Wrap an already connected stream
+	pub fn new(stream: TcpStream, version: ProtocolVersion) -> FakePeer {
+		let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+		FakePeer { stream, version }
+	}
+
+	/// Connect to the listening node
+	pub fn connect(addr: SocketAddr, version: ProtocolVersion) -> io::Result<FakePeer> {
+		let stream = TcpStream::connect_timeout(&addr, READ_TIMEOUT)?;
+		Ok(FakePeer::new(stream, version))
+	}
+
+	/// Address the node sees for this peer
+	pub fn local_addr(&self) -> io::Result<PeerAddr> {
+		Ok(PeerAddr::Ip(self.stream.local_addr()?))
+	}
+
+	/// Protocol version the fake peer is using
+	pub fn version(&self) -> ProtocolVersion {
+		self.version
+	}
+
+	/// Hand message that a regular peer would send. Tests can modify it to misbehave.
+	pub fn hand(&self, genesis: Hash) -> Result<Hand, Error> {
+		let sender_addr = self.local_addr()?;
+		let receiver_addr = PeerAddr::Ip(self.stream.peer_addr()?);
+		Ok(Hand {
+			version: self.version,
+			capabilities: Capabilities::UNKNOWN,
+			nonce: thread_rng().gen(),
+			genesis,
+			total_difficulty: Difficulty::min(),
+			sender_addr,
+			receiver_addr,
+			user_agent: FAKE_PEER_USER_AGENT.to_string(),
+			tx_fee_base: global::get_accept_fee_base(),
+		})
+	}
+
+	/// Send the hand and read the shake reply
+	pub fn handshake(&mut self, hand: Hand) -> Result<Shake, Error> {
+		self.send(Type::Hand, hand)?;
+		self.read(Type::Shake)
+	}
+
+	/// Send a well formed message
+	pub fn send<T: Writeable>(&mut self, msg_type: Type, msg: T) -> Result<(), Error> {
+		let body = ser::ser_vec(&msg, self.version)?;
+		self.send_body(msg_type, &body)
+	}
+
+	/// Send a message with already serialized body
+	pub fn send_body(&mut self, msg_type: Type, body: &[u8]) -> Result<(), Error> {
+		let mut data = ser::ser_vec(&MsgHeader::new(msg_type, body.len() as u64), self.version)?;
+		data.extend_from_slice(body);
+		self.send_raw(&data)
+	}
+
+	/// Send the bytes as they are
+	pub fn send_raw(&mut self, data: &[u8]) -> Result<(), Error> {
+		self.stream.write_all(data)?;
+		self.stream.flush()?;
+		Ok(())
+	}
+
+	/// Read the next message, it must be of the expected type
+	pub fn read<T: Readable>(&mut self, msg_type: Type) -> Result<T, Error> {
+		read_message(&mut self.stream, self.version, msg_type)
+	}
+
+	/// Read `len` bytes as they are, for the attachments
+	pub fn read_raw(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+		let mut data = vec![0u8; len];
+		self.stream.read_exact(&mut data)?;
+		Ok(data)
+	}
+
+	/// Read the next message of the expected type and discard the body
+	pub fn expect(&mut self, msg_type: Type) -> Result<(), Error> {
+		match read_header(&mut self.stream, self.version)? {
+			MsgHeaderWrapper::Known(header) => {
+				let mut body = vec![0u8; header.msg_len as usize];
+				self.stream.read_exact(&mut body)?;
+				if header.msg_type == msg_type {
+					Ok(())
+				} else {
+					Err(Error::UnexpectedMessage(format!(
+						"expected {:?}, got {:?}",
+						msg_type, header.msg_type
+					)))
+				}
+			}
+			MsgHeaderWrapper::Unknown(msg_len, type_byte) => {
+				let mut body = vec![0u8; msg_len as usize];
+				self.stream.read_exact(&mut body)?;
+				Err(Error::UnexpectedMessage(format!(
+					"expected {:?}, got unknown type {}",
+					msg_type, type_byte
+				)))
+			}
+		}
+	}
+
+	/// Check if the node closed the connection. Any pending data is discarded.
+	pub fn is_closed(&mut self, timeout: Duration) -> bool {
+		let _ = self.stream.set_read_timeout(Some(timeout));
+		let mut buf = [0u8; 1024];
+		let closed = loop {
+			match self.stream.read(&mut buf) {
+				Ok(0) => break true,
+				Ok(_) => continue,
+				Err(ref e)
+					if e.kind() == io::ErrorKind::WouldBlock
+						|| e.kind() == io::ErrorKind::TimedOut =>
+				{
+					break false
+				}
+				Err(_) => break true,
+			}
+		};
+		let _ = self.stream.set_read_timeout(Some(READ_TIMEOUT));
+		closed
+	}
+
+	/// Run the steps in order, stop at the first failure
+	pub fn run(&mut self, steps: Vec<FakePeerStep>) -> Result<(), Error> {
+		for step in steps {
+			match step {
+				FakePeerStep::Send(msg_type, body) => self.send_body(msg_type, &body)?,
+				FakePeerStep::SendRaw(data) => self.send_raw(&data)?,
+				FakePeerStep::Expect(msg_type) => self.expect(msg_type)?,
+				FakePeerStep::Sleep(duration) => thread::sleep(duration),
+			}
+		}
+		Ok(())
+	}
+
+	/// Run the steps in a separate thread. The fake peer is returned back when done,
+	/// so the test can continue with it.
+	pub fn spawn(mut self, steps: Vec<FakePeerStep>) -> JoinHandle<(FakePeer, Result<(), Error>)> {
+		thread::spawn(move || {
+			let res = self.run(steps);
+			(self, res)
+		})
+	}
+
+	/// Close the connection from the fake peer side
+	pub fn close(&self) {
+		let _ = self.stream.shutdown(Shutdown::Both);
+	}
+}
+
+/// Chain adapter of the node that has the txhashset archive of `header` in the file.
+/// It serves the archive and accepts the download of it to `tmp_dir`, the received
+/// archive and the bans are recorded. The rest is the no-op DummyAdapter.
+pub struct ArchiveAdapter {
+	dummy: DummyAdapter,
+	header: BlockHeader,
+	archive: PathBuf,
+	tmp_dir: PathBuf,
+	received: Mutex<Option<PathBuf>>,
+	banned: AtomicBool,
+}
+
+impl ArchiveAdapter {
+	pub fn new(header: BlockHeader, archive: PathBuf, tmp_dir: PathBuf) -> ArchiveAdapter {
+		ArchiveAdapter {
+			dummy: DummyAdapter {},
+			header,
+			archive,
+			tmp_dir,
+			received: Mutex::new(None),
+			banned: AtomicBool::new(false),
+		}
+	}
+
+	/// Path of the downloaded archive, None until the download is done
+	pub fn received(&self) -> Option<PathBuf> {
+		self.received.lock().clone()
+	}
+
+	/// Was any peer banned
+	pub fn is_banned_any(&self) -> bool {
+		self.banned.load(Ordering::Relaxed)
+	}
+}
+
+impl ChainAdapter for ArchiveAdapter {
+	fn total_difficulty(&self) -> Result<Difficulty, chain::Error> {
+		self.dummy.total_difficulty()
+	}
+	fn total_height(&self) -> Result<u64, chain::Error> {
+		self.dummy.total_height()
+	}
+	fn get_transaction(&self, h: Hash) -> Option<Transaction> {
+		self.dummy.get_transaction(h)
+	}
+	fn tx_kernel_received(&self, h: Hash, peer_info: &PeerInfo) -> Result<bool, chain::Error> {
+		self.dummy.tx_kernel_received(h, peer_info)
+	}
+	fn transaction_received(
+		&self,
+		tx: Transaction,
+		peer_info: &PeerInfo,
+		stem: bool,
+	) -> Result<bool, chain::Error> {
+		self.dummy.transaction_received(tx, peer_info, stem)
+	}
+	fn compact_block_received(
+		&self,
+		cb: CompactBlock,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.dummy.compact_block_received(cb, peer_info)
+	}
+	fn header_received(&self, bh: BlockHeader, peer_info: &PeerInfo) -> Result<bool, chain::Error> {
+		self.dummy.header_received(bh, peer_info)
+	}
+	fn block_received(
+		&self,
+		b: Block,
+		peer_info: &PeerInfo,
+		opts: chain::Options,
+	) -> Result<bool, chain::Error> {
+		self.dummy.block_received(b, peer_info, opts)
+	}
+	fn headers_received(
+		&self,
+		bh: &[BlockHeader],
+		remaining: u64,
+		peer_info: &PeerInfo,
+	) -> Result<(), chain::Error> {
+		self.dummy.headers_received(bh, remaining, peer_info)
+	}
+	fn header_locator(&self) -> Result<Vec<Hash>, chain::Error> {
+		self.dummy.header_locator()
+	}
+	fn locate_headers(&self, locator: &[Hash]) -> Result<Vec<BlockHeader>, chain::Error> {
+		self.dummy.locate_headers(locator)
+	}
+	fn get_block(&self, h: Hash, peer_info: &PeerInfo) -> Option<Block> {
+		self.dummy.get_block(h, peer_info)
+	}
+	fn txhashset_read(&self, h: Hash) -> Option<TxHashSetRead> {
+		if h != self.header.hash() {
+			return None;
+		}
+		Some(TxHashSetRead {
+			output_index: 0,
+			kernel_index: 0,
+			reader: File::open(&self.archive).ok()?,
+		})
+	}
+	fn txhashset_archive_header(&self) -> Result<BlockHeader, chain::Error> {
+		Ok(self.header.clone())
+	}
+	fn get_tmp_dir(&self) -> PathBuf {
+		self.tmp_dir.clone()
+	}
+	fn get_tmpfile_pathname(&self, tmpfile_name: String) -> PathBuf {
+		self.tmp_dir.join(tmpfile_name)
+	}
+	fn txhashset_receive_ready(
+		&self,
+		_peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool {
+		header_hash == self.header.hash() && header_height == self.header.height
+	}
+	fn txhashset_download_update(&self, peer: &PeerAddr, downloaded_size: u64, total_size: u64) {
+		self.dummy
+			.txhashset_download_update(peer, downloaded_size, total_size)
+	}
+	fn txhashset_received(
+		&self,
+		_peer: &PeerAddr,
+		_header_hash: Hash,
+		_header_height: u64,
+		path: PathBuf,
+	) -> Result<(), chain::Error> {
+		*self.received.lock() = Some(path);
+		Ok(())
+	}
+	fn prepare_segmenter(&self) -> Result<Segmenter, chain::Error> {
+		self.dummy.prepare_segmenter()
+	}
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		self.dummy.get_kernel_segment(hash, id)
+	}
+	fn get_bitmap_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<BitmapChunk>, chain::Error> {
+		self.dummy.get_bitmap_segment(hash, id)
+	}
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<OutputIdentifier>, chain::Error> {
+		self.dummy.get_output_segment(hash, id)
+	}
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.dummy.get_rangeproof_segment(hash, id)
+	}
+	fn receive_bitmap_segment(
+		&self,
+		peer: &PeerAddr,
+		archive_header_hash: Hash,
+		segment: Segment<BitmapChunk>,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_bitmap_segment(peer, archive_header_hash, segment)
+	}
+	fn receive_output_segment(
+		&self,
+		peer: &PeerAddr,
+		archive_header_hash: Hash,
+		segment: Segment<OutputIdentifier>,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_output_segment(peer, archive_header_hash, segment)
+	}
+	fn receive_rangeproof_segment(
+		&self,
+		peer: &PeerAddr,
+		archive_header_hash: Hash,
+		segment: Segment<RangeProof>,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_rangeproof_segment(peer, archive_header_hash, segment)
+	}
+	fn receive_kernel_segment(
+		&self,
+		peer: &PeerAddr,
+		archive_header_hash: Hash,
+		segment: Segment<TxKernel>,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_kernel_segment(peer, archive_header_hash, segment)
+	}
+	fn recieve_pibd_status(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		output_bitmap_root: Hash,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.recieve_pibd_status(peer, header_hash, header_height, output_bitmap_root)
+	}
+	fn recieve_another_archive_header(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.recieve_another_archive_header(peer, header_hash, header_height)
+	}
+	fn receive_headers_hash_response(
+		&self,
+		peer: &PeerAddr,
+		archive_height: u64,
+		headers_hash_root: Hash,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_headers_hash_response(peer, archive_height, headers_hash_root)
+	}
+	fn get_header_hashes_segment(
+		&self,
+		header_hashes_root: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<Hash>, chain::Error> {
+		self.dummy.get_header_hashes_segment(header_hashes_root, id)
+	}
+	fn receive_header_hashes_segment(
+		&self,
+		peer: &PeerAddr,
+		header_hashes_root: Hash,
+		segment: Segment<Hash>,
+	) -> Result<(), chain::Error> {
+		self.dummy
+			.receive_header_hashes_segment(peer, header_hashes_root, segment)
+	}
+	fn peer_difficulty(&self, addr: &PeerAddr, diff: Difficulty, height: u64) {
+		self.dummy.peer_difficulty(addr, diff, height)
+	}
+}
+
+impl NetAdapter for ArchiveAdapter {
+	fn find_peer_addrs(&self, capab: Capabilities) -> Vec<PeerAddr> {
+		self.dummy.find_peer_addrs(capab)
+	}
+	fn peer_addrs_received(&self, addrs: Vec<PeerAddr>) {
+		self.dummy.peer_addrs_received(addrs)
+	}
+	fn is_banned(&self, addr: &PeerAddr) -> bool {
+		self.dummy.is_banned(addr)
+	}
+	fn ban_peer(&self, _addr: &PeerAddr, _ban_reason: ReasonForBan, _message: &str) {
+		self.banned.store(true, Ordering::Relaxed);
+	}
+}
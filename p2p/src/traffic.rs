@@ -0,0 +1,152 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Peer traffic accounting for the bandwidth reports. Unlike the rate counters
+//! that are used for the abuse detection, all bytes are counted here, including
+//! the sync traffic and the attachments, split by the message class.
+
+use crate::msg::MsgPriority;
+use crate::types::{Direction, PeerAddr};
+use chrono::Utc;
+use std::collections::VecDeque;
+
+/// Traffic report period, seconds
+pub const TRAFFIC_PERIOD_SECS: i64 = 3600;
+const BUCKET_SECS: i64 = 60;
+
+/// Bytes by the message class
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassBytes {
+	/// Connection management: pings, peer addresses, handshakes
+	pub control: u64,
+	/// Blocks and transactions relay
+	pub consensus: u64,
+	/// Sync data: header batches, segments, txhashset archives
+	pub bulk: u64,
+}
+
+impl ClassBytes {
+	fn add(&mut self, priority: MsgPriority, bytes: u64) {
+		match priority {
+			MsgPriority::Control => self.control += bytes,
+			MsgPriority::Consensus => self.consensus += bytes,
+			MsgPriority::Bulk => self.bulk += bytes,
+		}
+	}
+
+	fn merge(&mut self, other: &ClassBytes) {
+		self.control += other.control;
+		self.consensus += other.consensus;
+		self.bulk += other.bulk;
+	}
+
+	/// Bytes for all classes
+	pub fn total(&self) -> u64 {
+		self.control + self.consensus + self.bulk
+	}
+}
+
+/// Sent and received bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrafficStats {
+	pub sent: ClassBytes,
+	pub received: ClassBytes,
+}
+
+impl TrafficStats {
+	/// Add other stats to this one
+	pub fn merge(&mut self, other: &TrafficStats) {
+		self.sent.merge(&other.sent);
+		self.received.merge(&other.received);
+	}
+
+	/// Bytes in both directions
+	pub fn total(&self) -> u64 {
+		self.sent.total() + self.received.total()
+	}
+}
+
+/// Traffic of the single connection for the last TRAFFIC_PERIOD_SECS, counted
+/// in the one minute buckets.
+pub struct TrafficCounter {
+	buckets: VecDeque<(i64, TrafficStats)>,
+}
+
+impl TrafficCounter {
+	pub fn new() -> TrafficCounter {
+		TrafficCounter {
+			buckets: VecDeque::new(),
+		}
+	}
+
+	fn bucket(&mut self) -> &mut TrafficStats {
+		let now = Utc::now().timestamp() / BUCKET_SECS;
+		self.truncate(now);
+		if self.buckets.back().map(|(t, _)| *t != now).unwrap_or(true) {
+			self.buckets.push_back((now, TrafficStats::default()));
+		}
+		&mut self.buckets.back_mut().expect("bucket is just added").1
+	}
+
+	fn truncate(&mut self, now: i64) {
+		let oldest = now - TRAFFIC_PERIOD_SECS / BUCKET_SECS;
+		while self
+			.buckets
+			.front()
+			.map(|(t, _)| *t <= oldest)
+			.unwrap_or(false)
+		{
+			self.buckets.pop_front();
+		}
+	}
+
+	pub fn add_sent(&mut self, priority: MsgPriority, bytes: u64) {
+		self.bucket().sent.add(priority, bytes);
+	}
+
+	pub fn add_received(&mut self, priority: MsgPriority, bytes: u64) {
+		self.bucket().received.add(priority, bytes);
+	}
+
+	/// Traffic for the last TRAFFIC_PERIOD_SECS
+	pub fn stats(&mut self) -> TrafficStats {
+		self.truncate(Utc::now().timestamp() / BUCKET_SECS);
+		let mut res = TrafficStats::default();
+		for (_, s) in &self.buckets {
+			res.merge(s);
+		}
+		res
+	}
+}
+
+/// Traffic of a single peer
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeerTraffic {
+	pub addr: PeerAddr,
+	pub direction: Direction,
+	pub user_agent: String,
+	/// False for the peers that were disconnected during the report period
+	pub connected: bool,
+	pub traffic: TrafficStats,
+}
+
+/// Top talkers report, peers are sorted by the total traffic
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrafficReport {
+	/// Report period, seconds
+	pub period_secs: i64,
+	/// Traffic of all peers, including the ones that are not in the list
+	pub total: TrafficStats,
+	pub peers: Vec<PeerTraffic>,
+}
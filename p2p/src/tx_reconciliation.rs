@@ -0,0 +1,395 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Erlay style transaction announcement reconciliation. Instead of flooding every
+//! tx kernel hash to every peer, announcements for the peers with
+//! TX_RECONCILIATION capability are collected and periodically reconciled.
+//! The outbound side of the connection starts the round, the inbound side replies
+//! with a sketch (invertible bloom lookup table) of its set, so only the set
+//! difference is exchanged. If the sketch can't be decoded, both sides fall back
+//! to the announcement of the whole set.
+//!
+//! Round (A is outbound, B is inbound):
+//! 1. A -> B TxReconcileRequest, with A set size and the salt for the short ids.
+//! 2. B -> A TxReconcileSketch of B set, sized for the estimated difference.
+//! 3. A -> B TxReconcileDiff, the hashes that B is missing and the short ids
+//!    of the hashes that A is missing.
+//! 4. B -> A TxReconcileHashes with the requested hashes.
+
+use crate::mwc_core::core::hash::Hash;
+use crate::mwc_core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::util::Mutex;
+use rand::{thread_rng, Rng};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Max number of cells in the sketch. Bigger differences are resolved by the
+/// fall back to the full set announcement.
+pub const MAX_SKETCH_CELLS: usize = 3000;
+/// Max number of the announcements that are waiting for reconciliation with a
+/// single peer. Announcements above the limit are flooded.
+pub const MAX_RECONCILIATION_SET: usize = 10_000;
+
+/// Min number of cells in the sketch
+const MIN_SKETCH_CELLS: usize = 24;
+/// Every id is added into that many cells, one in every sub table
+const SKETCH_HASH_COUNT: usize = 3;
+/// Reconciliation round that didn't finish in time is abandoned
+const RECONCILIATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+// splitmix64 finalizer, good enough to spread the ids over the cells
+fn mix(mut x: u64) -> u64 {
+	x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+	x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+	x ^ (x >> 31)
+}
+
+/// Short id of the kernel hash for the reconciliation round with the salt
+pub fn short_id(kernel_hash: &Hash, salt: u64) -> u64 {
+	mix(kernel_hash.to_u64() ^ salt)
+}
+
+/// Number of sketch cells for the sets of those sizes. The difference is estimated
+/// from the size difference plus a quarter of the smaller set.
+pub fn sketch_capacity(local_size: usize, remote_size: usize) -> usize {
+	let local_size = local_size as i64;
+	let remote_size = remote_size as i64;
+	let diff = (local_size - remote_size).abs() + local_size.min(remote_size) / 4 + 1;
+	let cells = (diff as usize * 3 / 2).max(MIN_SKETCH_CELLS);
+	// cells are split between the sub tables evenly
+	let cells = cells + (SKETCH_HASH_COUNT - cells % SKETCH_HASH_COUNT) % SKETCH_HASH_COUNT;
+	cells.min(MAX_SKETCH_CELLS)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct SketchCell {
+	count: i32,
+	id_sum: u64,
+	check_sum: u64,
+}
+
+impl SketchCell {
+	fn is_empty(&self) -> bool {
+		self.count == 0 && self.id_sum == 0 && self.check_sum == 0
+	}
+
+	fn is_pure(&self) -> bool {
+		(self.count == 1 || self.count == -1) && self.check_sum == mix(self.id_sum)
+	}
+}
+
+/// Sketch of the short ids set. Two sketches of the same size can be subtracted,
+/// the result can be decoded into the set difference if it fits the sketch size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxSketch {
+	cells: Vec<SketchCell>,
+}
+
+impl TxSketch {
+	/// Empty sketch with the number of cells
+	pub fn new(cells: usize) -> TxSketch {
+		let cells = cells.min(MAX_SKETCH_CELLS);
+		TxSketch {
+			cells: vec![SketchCell::default(); cells],
+		}
+	}
+
+	/// Sketch with all the ids
+	pub fn from_ids<'a, I: IntoIterator<Item = &'a u64>>(cells: usize, ids: I) -> TxSketch {
+		let mut sketch = TxSketch::new(cells);
+		for id in ids {
+			sketch.add(*id);
+		}
+		sketch
+	}
+
+	/// Number of the cells
+	pub fn len(&self) -> usize {
+		self.cells.len()
+	}
+
+	/// Sketch without cells can't hold anything
+	pub fn is_empty(&self) -> bool {
+		self.cells.is_empty()
+	}
+
+	fn cell_indexes(&self, id: u64) -> Vec<usize> {
+		let sub_table = self.cells.len() / SKETCH_HASH_COUNT;
+		if sub_table == 0 {
+			return vec![];
+		}
+		(0..SKETCH_HASH_COUNT)
+			.map(|i| i * sub_table + (mix(id ^ (i as u64 + 1)) % sub_table as u64) as usize)
+			.collect()
+	}
+
+	fn update(&mut self, id: u64, count: i32) {
+		let check_sum = mix(id);
+		for idx in self.cell_indexes(id) {
+			let cell = &mut self.cells[idx];
+			cell.count += count;
+			cell.id_sum ^= id;
+			cell.check_sum ^= check_sum;
+		}
+	}
+
+	/// Add the short id
+	pub fn add(&mut self, id: u64) {
+		self.update(id, 1);
+	}
+
+	/// Sketch of the difference between the sets, self minus other
+	pub fn subtract(&self, other: &TxSketch) -> Option<TxSketch> {
+		if self.cells.len() != other.cells.len() {
+			return None;
+		}
+		Some(TxSketch {
+			cells: self
+				.cells
+				.iter()
+				.zip(other.cells.iter())
+				.map(|(a, b)| SketchCell {
+					count: a.count.wrapping_sub(b.count),
+					id_sum: a.id_sum ^ b.id_sum,
+					check_sum: a.check_sum ^ b.check_sum,
+				})
+				.collect(),
+		})
+	}
+
+	/// Decode the difference sketch into (ids only in self, ids only in other).
+	/// None if the difference is too large for the sketch.
+	pub fn decode(mut self) -> Option<(Vec<u64>, Vec<u64>)> {
+		let mut only_local = vec![];
+		let mut only_remote = vec![];
+		let mut pure: Vec<usize> = (0..self.cells.len())
+			.filter(|i| self.cells[*i].is_pure())
+			.collect();
+		while let Some(idx) = pure.pop() {
+			let cell = self.cells[idx];
+			if !cell.is_pure() {
+				continue;
+			}
+			let id = cell.id_sum;
+			if cell.count > 0 {
+				only_local.push(id);
+			} else {
+				only_remote.push(id);
+			}
+			self.update(id, -cell.count);
+			for i in self.cell_indexes(id) {
+				if self.cells[i].is_pure() {
+					pure.push(i);
+				}
+			}
+		}
+		if self.cells.iter().all(|c| c.is_empty()) {
+			Some((only_local, only_remote))
+		} else {
+			None
+		}
+	}
+}
+
+impl Writeable for TxSketch {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		if self.cells.len() > MAX_SKETCH_CELLS {
+			return Err(ser::Error::TooLargeWriteErr(
+				"sketch size larger then the limit".to_string(),
+			));
+		}
+		writer.write_u32(self.cells.len() as u32)?;
+		for cell in &self.cells {
+			writer.write_i32(cell.count)?;
+			writer.write_u64(cell.id_sum)?;
+			writer.write_u64(cell.check_sum)?;
+		}
+		Ok(())
+	}
+}
+
+impl Readable for TxSketch {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxSketch, ser::Error> {
+		let len = reader.read_u32()? as usize;
+		if len > MAX_SKETCH_CELLS {
+			return Err(ser::Error::TooLargeReadErr(
+				"sketch size larger then the limit".to_string(),
+			));
+		}
+		let mut cells = Vec::with_capacity(len);
+		for _ in 0..len {
+			let count = reader.read_i32()?;
+			let (id_sum, check_sum) = ser_multiread!(reader, read_u64, read_u64);
+			cells.push(SketchCell {
+				count,
+				id_sum,
+				check_sum,
+			});
+		}
+		Ok(TxSketch { cells })
+	}
+}
+
+/// Result of the sketch processing by the round initiator
+pub struct SketchResult {
+	/// Sketch was decoded, otherwise both sides announce their full sets
+	pub decoded: bool,
+	/// Hashes to announce to the peer
+	pub announce: Vec<Hash>,
+	/// Short ids of the hashes that the peer needs to announce to us
+	pub want: Vec<u64>,
+}
+
+#[derive(Default)]
+struct ReconciliationState {
+	/// Announcements waiting for the next round
+	pending: HashSet<Hash>,
+	/// Announcements of the current round by short id
+	snapshot: HashMap<u64, Hash>,
+	/// Salt of the current round
+	salt: u64,
+	/// Start of the current round, None if there is no round in progress
+	started: Option<Instant>,
+}
+
+impl ReconciliationState {
+	fn take_snapshot(&mut self, salt: u64) {
+		self.salt = salt;
+		self.snapshot = self
+			.pending
+			.drain()
+			.map(|h| (short_id(&h, salt), h))
+			.collect();
+		self.started = Some(Instant::now());
+	}
+
+	fn finish(&mut self) -> Vec<Hash> {
+		self.started = None;
+		self.snapshot.drain().map(|(_, h)| h).collect()
+	}
+}
+
+/// Per peer reconciliation state
+#[derive(Default)]
+pub struct TxReconciliation {
+	state: Mutex<ReconciliationState>,
+}
+
+impl TxReconciliation {
+	pub fn new() -> TxReconciliation {
+		TxReconciliation::default()
+	}
+
+	/// Queue the announcement for the next round. False if the set is full and
+	/// the announcement needs to be sent right away.
+	pub fn add(&self, kernel_hash: Hash) -> bool {
+		let mut state = self.state.lock();
+		if state.pending.len() >= MAX_RECONCILIATION_SET {
+			return false;
+		}
+		state.pending.insert(kernel_hash);
+		true
+	}
+
+	/// Number of the announcements waiting for the next round
+	pub fn pending_len(&self) -> usize {
+		self.state.lock().pending.len()
+	}
+
+	/// Initiator: start the new round. Returns (set size, salt) for the request,
+	/// None if there is nothing to reconcile or the previous round is in progress.
+	pub fn start_round(&self) -> Option<(u64, u64)> {
+		let mut state = self.state.lock();
+		if let Some(started) = state.started {
+			if started.elapsed() < RECONCILIATION_TIMEOUT {
+				return None;
+			}
+			// Peer never finished the round, the announcements go to the next one
+			let expired = state.finish();
+			state.pending.extend(expired);
+		}
+		if state.pending.is_empty() {
+			return None;
+		}
+		let salt = thread_rng().gen();
+		state.take_snapshot(salt);
+		Some((state.snapshot.len() as u64, salt))
+	}
+
+	/// Responder: sketch of our set for the initiator request
+	pub fn sketch_for_request(&self, remote_size: u64, salt: u64) -> TxSketch {
+		let mut state = self.state.lock();
+		if state.started.is_some() {
+			// Initiator restarted the round, previous snapshot is not reconciled yet
+			let prev = state.finish();
+			state.pending.extend(prev);
+		}
+		state.take_snapshot(salt);
+		let cells = sketch_capacity(state.snapshot.len(), remote_size as usize);
+		TxSketch::from_ids(cells, state.snapshot.keys())
+	}
+
+	/// Initiator: find the difference with the responder sketch
+	pub fn process_sketch(&self, remote: &TxSketch) -> SketchResult {
+		let mut state = self.state.lock();
+		if state.started.is_none() {
+			// Unexpected sketch, let the peer announce everything
+			return SketchResult {
+				decoded: false,
+				announce: vec![],
+				want: vec![],
+			};
+		}
+		let local = TxSketch::from_ids(remote.len(), state.snapshot.keys());
+		let diff = if remote.is_empty() {
+			None
+		} else {
+			local.subtract(remote).and_then(|d| d.decode())
+		};
+		match diff {
+			Some((only_local, only_remote)) => {
+				let announce = only_local
+					.iter()
+					.filter_map(|id| state.snapshot.get(id).cloned())
+					.collect();
+				state.finish();
+				SketchResult {
+					decoded: true,
+					announce,
+					want: only_remote,
+				}
+			}
+			None => SketchResult {
+				decoded: false,
+				announce: state.finish(),
+				want: vec![],
+			},
+		}
+	}
+
+	/// Responder: hashes requested by the initiator. If initiator failed to decode
+	/// the sketch, all the round hashes are returned.
+	pub fn process_diff(&self, decoded: bool, want: &[u64]) -> Vec<Hash> {
+		let mut state = self.state.lock();
+		if !decoded {
+			return state.finish();
+		}
+		let res = want
+			.iter()
+			.filter_map(|id| state.snapshot.get(id).cloned())
+			.collect();
+		state.finish();
+		res
+	}
+}
@@ -0,0 +1,210 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filter for the transactions relayed by the peers, applied before the pool does the
+//! validation work. Every peer has a token bucket for the tx announcements and
+//! transactions, and a rolling set of the kernels it sent recently. Transactions that
+//! were already processed are skipped for a while, whoever sends them.
+
+use crate::mwc_core::core::hash::Hash;
+use crate::types::PeerAddr;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// Processed transactions are not validated again for that time
+pub const TX_SEEN_TTL: Duration = Duration::from_secs(120);
+
+/// Number of the processed transactions kernels that are remembered
+const TX_SEEN_CACHE_SIZE: usize = 10_000;
+
+/// Number of the recent kernels that are remembered per peer
+const TX_PEER_RECENT_SIZE: usize = 512;
+
+/// Peers are not tracked for more than that, idle ones are dropped first
+const TX_RELAY_MAX_PEERS: usize = 1024;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// How the transaction got from the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxRelayKind {
+	/// Kernel hash announcement, by the tx kernel message or the reconciliation
+	Announce,
+	Transaction,
+	StemTransaction,
+}
+
+/// Decision for the relayed transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TxRelayCheck {
+	/// Pass it to the pool
+	Accept,
+	/// Transaction was processed recently, nothing to do
+	Known,
+	/// Peer already sent it recently, misbehavior
+	Duplicate,
+	/// Peer sends too much, misbehavior
+	RateLimited,
+}
+
+struct PeerTxRelay {
+	tokens: f64,
+	updated: Instant,
+	recent: LruCache<(Hash, TxRelayKind), Instant>,
+}
+
+/// Seen transactions cache and per peer rate limits
+pub struct TxRelayFilter {
+	rate_per_min: u32,
+	burst: u32,
+	seen: LruCache<Hash, Instant>,
+	peers: HashMap<PeerAddr, PeerTxRelay>,
+}
+
+impl TxRelayFilter {
+	/// `rate_per_min` transactions and announcements per minute are allowed from a single
+	/// peer, with bursts up to `burst`.
+	pub fn new(rate_per_min: u32, burst: u32) -> TxRelayFilter {
+		TxRelayFilter {
+			rate_per_min: rate_per_min.max(1),
+			burst: burst.max(1),
+			seen: LruCache::new(NonZeroUsize::new(TX_SEEN_CACHE_SIZE).unwrap()),
+			peers: HashMap::new(),
+		}
+	}
+
+	/// Check the transaction or the announcement with the kernel hash from the peer
+	pub fn check(&mut self, peer: &PeerAddr, kernel_hash: Hash, kind: TxRelayKind) -> TxRelayCheck {
+		self.check_at(peer, kernel_hash, kind, Instant::now())
+	}
+
+	/// Same as `check`, at the provided time
+	pub fn check_at(
+		&mut self,
+		peer: &PeerAddr,
+		kernel_hash: Hash,
+		kind: TxRelayKind,
+		now: Instant,
+	) -> TxRelayCheck {
+		if self.peers.len() >= TX_RELAY_MAX_PEERS && !self.peers.contains_key(peer) {
+			self.cleanup(now);
+		}
+
+		let rate_per_sec = self.rate_per_min as f64 / MINUTE.as_secs_f64();
+		let burst = self.burst as f64;
+		let state = self
+			.peers
+			.entry(peer.clone())
+			.or_insert_with(|| PeerTxRelay {
+				tokens: burst,
+				updated: now,
+				recent: LruCache::new(NonZeroUsize::new(TX_PEER_RECENT_SIZE).unwrap()),
+			});
+
+		let key = (kernel_hash, kind);
+		if let Some(sent) = state.recent.get(&key) {
+			if now.saturating_duration_since(*sent) < TX_SEEN_TTL {
+				return TxRelayCheck::Duplicate;
+			}
+		}
+
+		let elapsed = now.saturating_duration_since(state.updated);
+		state.tokens = (state.tokens + elapsed.as_secs_f64() * rate_per_sec).min(burst);
+		state.updated = now;
+		if state.tokens < 1.0 {
+			return TxRelayCheck::RateLimited;
+		}
+		state.tokens -= 1.0;
+		state.recent.put(key, now);
+
+		// Stem transactions are expected again with the fluff, they are not deduplicated
+		if kind != TxRelayKind::Transaction {
+			return TxRelayCheck::Accept;
+		}
+		if let Some(seen) = self.seen.get(&kernel_hash) {
+			if now.saturating_duration_since(*seen) < TX_SEEN_TTL {
+				return TxRelayCheck::Known;
+			}
+		}
+		self.seen.put(kernel_hash, now);
+		TxRelayCheck::Accept
+	}
+
+	/// Number of the tracked peers
+	pub fn peers(&self) -> usize {
+		self.peers.len()
+	}
+
+	// Drop the peers that were quiet longer than the duplicates are tracked.
+	// If all peers are active, drop the least recently updated.
+	fn cleanup(&mut self, now: Instant) {
+		self.peers
+			.retain(|_, p| now.saturating_duration_since(p.updated) < TX_SEEN_TTL);
+		if self.peers.len() >= TX_RELAY_MAX_PEERS {
+			if let Some(peer) = self
+				.peers
+				.iter()
+				.min_by_key(|(_, p)| p.updated)
+				.map(|(peer, _)| peer.clone())
+			{
+				self.peers.remove(&peer);
+			}
+		}
+	}
+}
+
+/// Points of the misbehavior, the peer is banned when the score reaches
+/// MISBEHAVIOR_BAN_SCORE. The score decays over time, so the rare mistakes of the
+/// honest peers don't add up.
+pub struct MisbehaviorScore {
+	score: f64,
+	updated: Instant,
+}
+
+/// Peer with that misbehavior score is banned
+pub const MISBEHAVIOR_BAN_SCORE: u32 = 100;
+
+/// Misbehavior points that are forgiven per minute
+const MISBEHAVIOR_DECAY_PER_MIN: f64 = 10.0;
+
+impl MisbehaviorScore {
+	pub fn new() -> MisbehaviorScore {
+		MisbehaviorScore {
+			score: 0.0,
+			updated: Instant::now(),
+		}
+	}
+
+	/// Add the points, returns the new score
+	pub fn add(&mut self, points: u32) -> u32 {
+		self.add_at(points, Instant::now())
+	}
+
+	/// Same as `add`, at the provided time
+	pub fn add_at(&mut self, points: u32, now: Instant) -> u32 {
+		let elapsed = now.saturating_duration_since(self.updated);
+		let decay = elapsed.as_secs_f64() * MISBEHAVIOR_DECAY_PER_MIN / MINUTE.as_secs_f64();
+		self.score = (self.score - decay).max(0.0) + points as f64;
+		self.updated = now;
+		self.score as u32
+	}
+}
+
+impl Default for MisbehaviorScore {
+	fn default() -> MisbehaviorScore {
+		MisbehaviorScore::new()
+	}
+}
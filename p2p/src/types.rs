@@ -22,6 +22,7 @@ use std::io;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use chrono::prelude::*;
@@ -74,6 +75,34 @@ const PEER_BOOST_OUTBOUND_COUNT: u32 = 20;
 /// than allowed by PEER_MAX_INBOUND_COUNT to encourage network bootstrapping.
 const PEER_LISTENER_BUFFER_COUNT: u32 = 8;
 
+/// Number of peers with the same IP address that we allow to be connected at once.
+/// Multiple nodes behind one NAT need a larger value.
+const PEER_MAX_PER_IP: u32 = 1;
+
+/// Inbound connections per minute allowed from a single IP address
+const INBOUND_CONN_RATE: u32 = 10;
+
+/// Inbound connections from a single IP address allowed at once, before the rate applies
+const INBOUND_CONN_BURST: u32 = 20;
+
+/// Source IP is banned when more connections than that are rejected within a minute
+const INBOUND_CONN_BAN_THRESHOLD: u32 = 60;
+
+/// Transactions and announcements per minute allowed from a single peer
+const TX_RELAY_RATE: u32 = 600;
+
+/// Transactions and announcements from a single peer allowed at once, before the rate applies
+const TX_RELAY_BURST: u32 = 500;
+
+/// Send queue size for the control messages (pings, ban reasons, disconnects)
+const SEND_QUEUE_CONTROL_CAP: u32 = 16;
+
+/// Send queue size for the new blocks and transactions relay
+const SEND_QUEUE_CONSENSUS_CAP: u32 = 32;
+
+/// Send queue size for the sync data
+const SEND_QUEUE_BULK_CAP: u32 = crate::conn::SEND_CHANNEL_CAP as u32;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("p2p Serialization error, {0}")]
@@ -351,6 +380,16 @@ impl PeerAddr {
 		}
 	}
 
+	/// Same ip and port, or the same onion address. Unlike the peer identity for
+	/// the bans and the store (ip only), it is the address that we dial.
+	pub fn same_endpoint(&self, other: &PeerAddr) -> bool {
+		match (self, other) {
+			(Ip(a), Ip(b)) => a == b,
+			(Onion(a), Onion(b)) => a == b,
+			_ => false,
+		}
+	}
+
 	pub fn is_loopback(&self) -> bool {
 		match self {
 			Ip(ip) => ip.ip().is_loopback(),
@@ -391,6 +430,45 @@ pub struct P2PConfig {
 
 	pub peer_listener_buffer_count: Option<u32>,
 
+	/// Maximum number of connected peers with the same IP address (different ports)
+	pub peer_max_per_ip: Option<u32>,
+
+	/// Inbound connections per minute allowed from a single IP address
+	pub inbound_conn_rate: Option<u32>,
+
+	/// Inbound connections from a single IP address allowed in a burst
+	pub inbound_conn_burst: Option<u32>,
+
+	/// Ban the IP address if more connections than that are rejected within a minute.
+	/// 0 disables the ban.
+	pub inbound_conn_ban_threshold: Option<u32>,
+
+	/// Transactions and tx kernel announcements per minute allowed from a single peer
+	pub tx_relay_rate: Option<u32>,
+
+	/// Transactions and tx kernel announcements from a single peer allowed in a burst
+	pub tx_relay_burst: Option<u32>,
+
+	/// Relay the header of a new block to all peers as soon as the header is validated,
+	/// before the full block is validated. Peers request the compact block only if
+	/// they don't have it.
+	pub header_first_relay: Option<bool>,
+
+	/// Per peer send queue sizes. Relay and sync messages are dropped when the queue of
+	/// their priority is full, control messages wait. Higher priority queues are sent first.
+	pub send_queue_control_cap: Option<u32>,
+
+	pub send_queue_consensus_cap: Option<u32>,
+
+	pub send_queue_bulk_cap: Option<u32>,
+
+	/// Total bandwidth limit for all peers, KB/s. 0 or None is unlimited.
+	pub bandwidth_limit: Option<u64>,
+
+	/// Share of the bandwidth limit reserved for the blocks and transactions relay,
+	/// percent. Sync traffic is paused when it would eat into the reserve.
+	pub bandwidth_relay_ratio: Option<u8>,
+
 	pub dandelion_peer: Option<PeerAddr>,
 }
 
@@ -411,6 +489,18 @@ impl Default for P2PConfig {
 			peer_max_outbound_count: None,
 			peer_min_preferred_outbound_count: None,
 			peer_listener_buffer_count: None,
+			peer_max_per_ip: None,
+			inbound_conn_rate: None,
+			inbound_conn_burst: None,
+			inbound_conn_ban_threshold: None,
+			tx_relay_rate: None,
+			tx_relay_burst: None,
+			header_first_relay: None,
+			send_queue_control_cap: None,
+			send_queue_consensus_cap: None,
+			send_queue_bulk_cap: None,
+			bandwidth_limit: None,
+			bandwidth_relay_ratio: None,
 			dandelion_peer: None,
 		}
 	}
@@ -466,6 +556,96 @@ impl P2PConfig {
 			None => PEER_LISTENER_BUFFER_COUNT,
 		}
 	}
+
+	/// return maximum number of connected peers with the same IP
+	pub fn peer_max_per_ip(&self) -> u32 {
+		match self.peer_max_per_ip {
+			Some(n) => n.max(1),
+			None => PEER_MAX_PER_IP,
+		}
+	}
+
+	/// return inbound connections per minute allowed from a single IP
+	pub fn inbound_conn_rate(&self) -> u32 {
+		match self.inbound_conn_rate {
+			Some(n) => n.max(1),
+			None => INBOUND_CONN_RATE,
+		}
+	}
+
+	/// return inbound connections burst allowed from a single IP
+	pub fn inbound_conn_burst(&self) -> u32 {
+		match self.inbound_conn_burst {
+			Some(n) => n.max(1),
+			None => INBOUND_CONN_BURST,
+		}
+	}
+
+	/// return number of rejected connections per minute that gets the IP banned
+	pub fn inbound_conn_ban_threshold(&self) -> u32 {
+		match self.inbound_conn_ban_threshold {
+			Some(n) => n,
+			None => INBOUND_CONN_BAN_THRESHOLD,
+		}
+	}
+
+	/// return transactions and announcements per minute allowed from a single peer
+	pub fn tx_relay_rate(&self) -> u32 {
+		match self.tx_relay_rate {
+			Some(n) => n.max(1),
+			None => TX_RELAY_RATE,
+		}
+	}
+
+	/// return transactions and announcements burst allowed from a single peer
+	pub fn tx_relay_burst(&self) -> u32 {
+		match self.tx_relay_burst {
+			Some(n) => n.max(1),
+			None => TX_RELAY_BURST,
+		}
+	}
+
+	/// return true if the header of a new block should be relayed before the block is validated
+	pub fn header_first_relay(&self) -> bool {
+		self.header_first_relay.unwrap_or(false)
+	}
+
+	/// return send queue size for the control messages
+	pub fn send_queue_control_cap(&self) -> u32 {
+		match self.send_queue_control_cap {
+			Some(n) => n.max(1),
+			None => SEND_QUEUE_CONTROL_CAP,
+		}
+	}
+
+	/// return send queue size for the blocks and transactions relay messages
+	pub fn send_queue_consensus_cap(&self) -> u32 {
+		match self.send_queue_consensus_cap {
+			Some(n) => n.max(1),
+			None => SEND_QUEUE_CONSENSUS_CAP,
+		}
+	}
+
+	/// return send queue size for the sync data messages
+	pub fn send_queue_bulk_cap(&self) -> u32 {
+		match self.send_queue_bulk_cap {
+			Some(n) => n.max(1),
+			None => SEND_QUEUE_BULK_CAP,
+		}
+	}
+
+	/// return total bandwidth limit in KB/s, 0 is unlimited
+	pub fn bandwidth_limit(&self) -> u64 {
+		self.bandwidth_limit.unwrap_or(0)
+	}
+
+	/// return share of the bandwidth reserved for the relay, percent
+	pub fn bandwidth_relay_ratio(&self) -> u8 {
+		match self.bandwidth_relay_ratio {
+			Some(n) => n.min(crate::bandwidth::MAX_RELAY_RATIO),
+			None => crate::bandwidth::DEFAULT_RELAY_RATIO,
+		}
+	}
 }
 
 /// Type of seeding the server will use to find other peers on the network.
@@ -510,9 +690,25 @@ bitflags! {
 		const BLOCK_HIST = 0b0100_0000;
 		/// Can provide PIBD Headers Hashes
 		const HEADERS_HASH = 0b1000_0000;
+		/// Can reconcile tx announcements instead of flooding them.
+		const TX_RECONCILIATION = 0b1_0000_0000;
+		/// Can provide full blocks for the last cut-through horizon (a week).
+		const BLOCK_HIST_WEEK = 0b10_0000_0000;
+		/// Can provide full blocks for the last 4 cut-through horizons (about a month).
+		const BLOCK_HIST_MONTH = 0b100_0000_0000;
+		/// Can provide full blocks for the last 52 cut-through horizons (about a year).
+		const BLOCK_HIST_YEAR = 0b1000_0000_0000;
 	}
 }
 
+/// Blocks history depth buckets, the depth is in cut-through horizons.
+/// Buckets are cumulative, the node with a year history sets all of them.
+const BLOCK_HIST_DEPTH_BUCKETS: [(Capabilities, u64); 3] = [
+	(Capabilities::BLOCK_HIST_WEEK, 1),
+	(Capabilities::BLOCK_HIST_MONTH, 4),
+	(Capabilities::BLOCK_HIST_YEAR, 52),
+];
+
 /// Default capabilities.
 impl Capabilities {
 	/// Capability instance to match node features
@@ -523,7 +719,8 @@ impl Capabilities {
 			| Capabilities::TX_KERNEL_HASH
 			| Capabilities::TOR_ADDRESS
 			| Capabilities::PIBD_HIST
-			| Capabilities::HEADERS_HASH;
+			| Capabilities::HEADERS_HASH
+			| Capabilities::TX_RECONCILIATION;
 		if tor {
 			res |= Capabilities::TOR_ADDRESS;
 		}
@@ -532,6 +729,57 @@ impl Capabilities {
 		}
 		res
 	}
+
+	/// Capabilities of the headers only node. It has no txhashset, blocks or transactions to provide.
+	pub fn header_only(tor: bool) -> Self {
+		Capabilities::new(tor, false)
+			& !(Capabilities::TXHASHSET_HIST
+				| Capabilities::PIBD_HIST
+				| Capabilities::TX_KERNEL_HASH
+				| Capabilities::TX_RECONCILIATION)
+	}
+
+	/// All blocks history depth bits
+	pub fn history_depth_bits() -> Capabilities {
+		Capabilities::BLOCK_HIST_WEEK
+			| Capabilities::BLOCK_HIST_MONTH
+			| Capabilities::BLOCK_HIST_YEAR
+	}
+
+	/// Depth bits for the node that has full blocks for the last `depth` blocks
+	pub fn from_history_depth(depth: u64) -> Capabilities {
+		let horizon = global::default_cut_through_horizon(global::get_chain_type()) as u64;
+		let mut res = Capabilities::UNKNOWN;
+		for (cap, horizons) in BLOCK_HIST_DEPTH_BUCKETS.iter() {
+			if depth >= horizon.saturating_mul(*horizons) {
+				res |= *cap;
+			}
+		}
+		res
+	}
+
+	/// Capability that the peer must have to provide full blocks `depth` blocks
+	/// below the chain head. Full archive is needed for anything deeper than the
+	/// largest bucket.
+	pub fn for_history_depth(depth: u64) -> Capabilities {
+		let horizon = global::default_cut_through_horizon(global::get_chain_type()) as u64;
+		for (cap, horizons) in BLOCK_HIST_DEPTH_BUCKETS.iter() {
+			if depth <= horizon.saturating_mul(*horizons) {
+				return *cap;
+			}
+		}
+		Capabilities::BLOCK_HIST
+	}
+
+	/// Full archive can provide any depth. Older archive nodes don't advertise the
+	/// depth bits, so they are added to the capabilities that we get from the peer.
+	pub fn with_implied_history(self) -> Capabilities {
+		if self.contains(Capabilities::BLOCK_HIST) {
+			self | Capabilities::history_depth_bits()
+		} else {
+			self
+		}
+	}
 }
 
 // Types of connection
@@ -560,6 +808,20 @@ enum_from_primitive! {
 		HeadersHashFailure = 8,
 		PibdFailure = 9,
 		BadRequest = 10,
+		ConnectionFlood = 11,
+		TxFlood = 12,
+	}
+}
+
+// Reason why we dropped a healthy peer (not a ban). Reported to the peer with Disconnect message.
+enum_from_primitive! {
+	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+	pub enum DropReason {
+		None = 0,
+		ExcessSlots = 1,
+		LowPerformance = 2,
+		CapabilityMismatch = 3,
+		Stuck = 4,
 	}
 }
 
@@ -570,6 +832,18 @@ pub struct PeerLiveInfo {
 	pub last_seen: DateTime<Utc>,
 	pub stuck_detector: DateTime<Utc>,
 	pub first_seen: DateTime<Utc>,
+	/// Time when we sent the last ping that wasn't answered yet
+	pub last_ping_sent: Option<DateTime<Utc>>,
+	/// Round trip time of the last ping/pong exchange, ms
+	pub ping_rtt_ms: Option<i64>,
+}
+
+/// Last assigned peer session id
+static LAST_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// New id for the peer session, see PeerInfo::session_id
+pub fn next_session_id() -> u64 {
+	LAST_SESSION_ID.fetch_add(1, Ordering::Relaxed) + 1
 }
 
 /// General information about a connected peer that's useful to other modules.
@@ -579,6 +853,9 @@ pub struct PeerInfo {
 	pub user_agent: String,
 	pub version: ProtocolVersion,
 	pub addr: PeerAddr,
+	/// Local id of the session, unique for every connection. Distinct nodes behind the
+	/// same NAT can advertise the same address, so the address is not the key.
+	pub session_id: u64,
 	pub direction: Direction,
 	pub live_info: Arc<RwLock<PeerLiveInfo>>,
 	pub tx_base_fee: u64,
@@ -592,6 +869,8 @@ impl PeerLiveInfo {
 			first_seen: Utc::now(),
 			last_seen: Utc::now(),
 			stuck_detector: Utc::now(),
+			last_ping_sent: None,
+			ping_rtt_ms: None,
 		}
 	}
 }
@@ -636,6 +915,45 @@ impl PeerInfo {
 		live_info.total_difficulty = total_difficulty;
 		live_info.last_seen = Utc::now()
 	}
+
+	/// Remember the time of the sent ping, so we can calculate round trip time on pong
+	pub fn ping_sent(&self) {
+		self.live_info.write().last_ping_sent = Some(Utc::now());
+	}
+
+	/// Pong was received, update the round trip time for the last sent ping
+	pub fn pong_received(&self) {
+		let mut live_info = self.live_info.write();
+		if let Some(sent) = live_info.last_ping_sent.take() {
+			live_info.ping_rtt_ms = Some((Utc::now() - sent).num_milliseconds());
+		}
+	}
+
+	/// Round trip time of the last ping, ms. None if no pong was received yet.
+	pub fn ping_rtt_ms(&self) -> Option<i64> {
+		self.live_info.read().ping_rtt_ms
+	}
+}
+
+/// Protocol statistics for the connected peer. Rates are calculated
+/// for the last minute.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeerStatsDisplay {
+	pub addr: PeerAddr,
+	pub direction: Direction,
+	pub user_agent: String,
+	pub version: ProtocolVersion,
+	pub capabilities: Capabilities,
+	pub height: u64,
+	pub total_difficulty: Difficulty,
+	/// Unix timestamp when the connection was established
+	pub connected_since: i64,
+	pub sent_msg_per_min: u64,
+	pub sent_bytes_per_min: u64,
+	pub received_msg_per_min: u64,
+	pub received_bytes_per_min: u64,
+	/// Round trip time of the last ping, ms
+	pub ping_rtt_ms: Option<i64>,
 }
 
 /// This is needed for legacy purposes
@@ -699,8 +1017,12 @@ pub trait ChainAdapter: Sync + Send {
 	fn total_height(&self) -> Result<u64, chain::Error>;
 
 	/// A valid transaction has been received from one of our peers
-	fn transaction_received(&self, tx: core::Transaction, stem: bool)
-		-> Result<bool, chain::Error>;
+	fn transaction_received(
+		&self,
+		tx: core::Transaction,
+		peer_info: &PeerInfo,
+		stem: bool,
+	) -> Result<bool, chain::Error>;
 
 	fn get_transaction(&self, kernel_hash: Hash) -> Option<core::Transaction>;
 
@@ -770,6 +1092,27 @@ pub trait ChainAdapter: Sync + Send {
 	/// Delete file if tmp file already exists
 	fn get_tmpfile_pathname(&self, tmpfile_name: String) -> PathBuf;
 
+	/// Whether we requested the txhashset archive for this header from the peer
+	/// and ready to receive it.
+	fn txhashset_receive_ready(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool;
+
+	/// Progress of the txhashset archive download from the peer
+	fn txhashset_download_update(&self, peer: &PeerAddr, downloaded_size: u64, total_size: u64);
+
+	/// The txhashset archive from the peer is downloaded into the file at `path`
+	fn txhashset_received(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) -> Result<(), chain::Error>;
+
 	/// For MWC handshake we need to have a segmenter ready with output bitmap ready and commited.
 	fn prepare_segmenter(&self) -> Result<Segmenter, chain::Error>;
 
@@ -883,7 +1226,11 @@ pub trait NetAdapter: ChainAdapter {
 
 #[derive(Clone, Debug)]
 pub struct AttachmentMeta {
+	/// Number of bytes in this transfer
 	pub size: usize,
+	/// Position in the file where this transfer starts. Non zero when an
+	/// interrupted transfer is resumed.
+	pub offset: u64,
 	pub hash: Hash,
 	pub height: u64,
 	pub start_time: DateTime<Utc>,
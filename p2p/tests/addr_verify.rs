@@ -0,0 +1,175 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain::SyncState;
+use mwc_core::core::hash::Hash;
+use mwc_core::global;
+use mwc_core::pow::Difficulty;
+use mwc_core::ser::{self, ProtocolVersion};
+use mwc_p2p::addr_verify::{AddrVerify, ADDR_VERIFY_SUBNET_LIMIT};
+use mwc_p2p::handshake::Handshake;
+use mwc_p2p::{
+	Capabilities, DropReason, DummyAdapter, P2PConfig, PeerAddr, PeerData, ReasonForBan, Server,
+	State,
+};
+use mwc_util::StopState;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn open_port() -> u16 {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	listener.local_addr().unwrap().port()
+}
+
+fn ip_addr(port: u16) -> PeerAddr {
+	PeerAddr::Ip(SocketAddr::from(([127, 0, 0, 1], port)))
+}
+
+#[test]
+fn addr_verify_queue() {
+	let verify = AddrVerify::new();
+	let onion =
+		PeerAddr::Onion("2a6at2obto3uvkpkitqp4wxcg6u36qf534eucbskqciturczzc5suyid".to_string());
+	for _ in 0..100 {
+		assert!(!verify.sample(&onion));
+	}
+	assert_eq!(verify.pending(), 0);
+
+	// With enough attempts the address is sampled, once
+	let addr = ip_addr(13414);
+	while !verify.sample(&addr) {}
+	assert_eq!(verify.pending(), 1);
+	// Already queued address is not queued again
+	verify.sample(&addr);
+	assert_eq!(verify.pending(), 1);
+
+	assert_eq!(verify.next_batch(8), vec![addr]);
+	assert_eq!(verify.pending(), 0);
+	assert!(verify.next_batch(8).is_empty());
+}
+
+// Only the node that completes the handshake on our chain is verified
+#[test]
+fn addr_verify_dial() {
+	global::init_global_chain_type(global::ChainTypes::AutomatedTesting);
+	let db_root = tempfile::tempdir().unwrap();
+	let genesis = Hash::from_vec(&[]);
+	let config = P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_allow: None,
+		peers_deny: None,
+		..P2PConfig::default()
+	};
+	let server = Arc::new(
+		Server::new(
+			db_root.path().to_str().unwrap(),
+			Capabilities::UNKNOWN,
+			config.clone(),
+			Arc::new(DummyAdapter {}),
+			genesis,
+			Arc::new(SyncState::new()),
+			Arc::new(StopState::new()),
+			0,
+			None,
+		)
+		.unwrap(),
+	);
+	let listener = server.clone();
+	let _ = thread::spawn(move || listener.listen());
+	thread::sleep(Duration::from_secs(1));
+
+	let node = ip_addr(config.port);
+	let handshake = Handshake::new(genesis, P2PConfig::default(), None);
+	assert!(AddrVerify::dial(&node, &handshake, Difficulty::min()));
+	let other_chain = Handshake::new(Hash::from_vec(&[1, 2, 3]), P2PConfig::default(), None);
+	assert!(!AddrVerify::dial(&node, &other_chain, Difficulty::min()));
+
+	// Something is listening, but it is not a peer
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let port = listener.local_addr().unwrap().port();
+	let _ = thread::spawn(move || {
+		for conn in listener.incoming() {
+			drop(conn);
+		}
+	});
+	assert!(!AddrVerify::dial(
+		&ip_addr(port),
+		&handshake,
+		Difficulty::min()
+	));
+	assert!(!AddrVerify::dial(
+		&ip_addr(open_port()),
+		&handshake,
+		Difficulty::min()
+	));
+	server.stop();
+}
+
+// Dial backs to a single subnet are limited, the extra addresses are dropped
+#[test]
+fn addr_verify_subnet_limit() {
+	let verify = AddrVerify::new();
+	let other_subnet = PeerAddr::Ip(SocketAddr::from(([10, 1, 0, 1], 3414)));
+	for port in 0..ADDR_VERIFY_SUBNET_LIMIT as u16 + 2 {
+		while !verify.sample(&ip_addr(20000 + port)) {}
+	}
+	while !verify.sample(&other_subnet) {}
+	assert_eq!(verify.pending(), ADDR_VERIFY_SUBNET_LIMIT + 3);
+
+	let batch = verify.next_batch(ADDR_VERIFY_SUBNET_LIMIT + 3);
+	assert_eq!(batch.len(), ADDR_VERIFY_SUBNET_LIMIT + 1);
+	assert_eq!(batch.last(), Some(&other_subnet));
+	assert_eq!(verify.pending(), 0);
+
+	// The limit is kept between the batches
+	while !verify.sample(&PeerAddr::Ip(SocketAddr::from(([127, 0, 9, 9], 3414)))) {}
+	assert!(verify.next_batch(8).is_empty());
+}
+
+// Records stored before the verified flag was added are read as not verified
+#[test]
+fn peer_data_verified_compat() {
+	let peer = PeerData {
+		addr: ip_addr(3414),
+		capabilities: Capabilities::UNKNOWN,
+		user_agent: "MW/MWC".to_string(),
+		flags: State::Healthy,
+		last_banned: 0,
+		ban_reason: ReasonForBan::None,
+		last_connected: 0,
+		drop_reason: DropReason::None,
+		verified: true,
+	};
+	let mut vec = ser::ser_vec(&peer, ProtocolVersion::local()).unwrap();
+	let peer2: PeerData = ser::deserialize(
+		&mut &vec[..],
+		ProtocolVersion::local(),
+		ser::DeserializationMode::default(),
+	)
+	.unwrap();
+	assert!(peer2.verified);
+
+	vec.pop();
+	let peer3: PeerData = ser::deserialize(
+		&mut &vec[..],
+		ProtocolVersion::local(),
+		ser::DeserializationMode::default(),
+	)
+	.unwrap();
+	assert!(!peer3.verified);
+	assert_eq!(peer3.drop_reason, DropReason::None);
+}
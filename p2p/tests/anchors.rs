@@ -0,0 +1,80 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core as core;
+use mwc_p2p as p2p;
+
+use mwc_util as util;
+use mwc_util::StopState;
+
+use crate::core::core::hash::Hash;
+use crate::core::global;
+use crate::p2p::types::PeerAddr;
+use mwc_chain::SyncState;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::{thread, time};
+
+fn open_port() -> u16 {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	listener.local_addr().unwrap().port()
+}
+
+fn new_server(db_root: &str) -> (Arc<p2p::Server>, p2p::P2PConfig) {
+	let p2p_config = p2p::P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_allow: None,
+		peers_deny: None,
+		..p2p::P2PConfig::default()
+	};
+	let server = p2p::Server::new(
+		db_root,
+		p2p::Capabilities::UNKNOWN,
+		p2p_config.clone(),
+		Arc::new(p2p::DummyAdapter {}),
+		Hash::from_vec(&[]),
+		Arc::new(SyncState::new()),
+		Arc::new(StopState::new()),
+		0,
+		None,
+	)
+	.unwrap();
+	(Arc::new(server), p2p_config)
+}
+
+// Outbound peers are saved as anchors in the peer store on shutdown
+#[test]
+fn anchors_saved_on_shutdown() {
+	global::init_global_chain_type(global::ChainTypes::AutomatedTesting);
+	util::init_test_logger();
+
+	let remote_root = tempfile::tempdir().unwrap();
+	let (remote, remote_config) = new_server(remote_root.path().to_str().unwrap());
+	let listener = remote.clone();
+	let _ = thread::spawn(move || listener.listen());
+	thread::sleep(time::Duration::from_secs(1));
+
+	let local_root = tempfile::tempdir().unwrap();
+	let (local, _) = new_server(local_root.path().to_str().unwrap());
+	let remote_addr = PeerAddr::Ip(SocketAddr::new(remote_config.host, remote_config.port));
+	local.connect(&remote_addr).unwrap();
+	thread::sleep(time::Duration::from_secs(1));
+	assert!(local.peers.get_anchors().is_empty());
+
+	// anchors are read from the peer store
+	local.stop();
+	assert_eq!(local.peers.get_anchors(), vec![remote_addr]);
+	remote.stop();
+}
@@ -0,0 +1,94 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_p2p::bandwidth::{BandwidthLimits, BandwidthScheduler, TrafficClass, MAX_RELAY_RATIO};
+use mwc_p2p::msg::Type;
+
+#[test]
+fn unlimited_bandwidth() {
+	let bandwidth = BandwidthScheduler::new(0, 30);
+	bandwidth.consume(100_000_000);
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+	assert!(bandwidth.allowed(TrafficClass::Relay));
+}
+
+#[test]
+fn sync_is_paused_at_relay_reserve() {
+	// 1000 KB/s, 30% for the relay
+	let bandwidth = BandwidthScheduler::new(1000, 30);
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+
+	// 600 KB used, 400 KB left is above the 300 KB reserve
+	bandwidth.consume(600 * 1024);
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+
+	// Reserve is reached, only the relay can go
+	bandwidth.consume(200 * 1024);
+	assert!(!bandwidth.allowed(TrafficClass::Sync));
+	assert!(bandwidth.allowed(TrafficClass::Relay));
+	let delay = bandwidth.delay(TrafficClass::Sync).unwrap();
+	assert!(delay.as_millis() > 0 && delay.as_millis() <= 1000);
+
+	// Relay is never delayed, even over the budget
+	bandwidth.consume(10_000 * 1024);
+	assert!(bandwidth.delay(TrafficClass::Relay).is_none());
+}
+
+#[test]
+fn huge_limit_saturates() {
+	let bandwidth = BandwidthScheduler::new(u64::MAX, MAX_RELAY_RATIO);
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+	bandwidth.consume(u64::MAX);
+	assert!(bandwidth.allowed(TrafficClass::Relay));
+	assert!(bandwidth.delay(TrafficClass::Sync).is_some());
+
+	bandwidth.set_limits(BandwidthLimits {
+		limit_kbps: u64::MAX / 1000,
+		relay_ratio: 30,
+	});
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+}
+
+#[test]
+fn limits_update() {
+	let bandwidth = BandwidthScheduler::new(100, 30);
+	bandwidth.consume(100 * 1024);
+	assert!(!bandwidth.allowed(TrafficClass::Sync));
+
+	// New limits start with the full budget
+	bandwidth.set_limits(BandwidthLimits {
+		limit_kbps: 200,
+		relay_ratio: 100,
+	});
+	let limits = bandwidth.limits();
+	assert_eq!(limits.limit_kbps, 200);
+	assert_eq!(limits.relay_ratio, MAX_RELAY_RATIO);
+	assert!(bandwidth.allowed(TrafficClass::Sync));
+}
+
+#[test]
+fn traffic_class_by_message() {
+	assert_eq!(
+		TrafficClass::from(Type::OutputSegment.priority()),
+		TrafficClass::Sync
+	);
+	assert_eq!(
+		TrafficClass::from(Type::Transaction.priority()),
+		TrafficClass::Relay
+	);
+	assert_eq!(
+		TrafficClass::from(Type::Ping.priority()),
+		TrafficClass::Relay
+	);
+}
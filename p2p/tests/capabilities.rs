@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use mwc_core::global;
 use mwc_p2p::Capabilities;
 
 // We use `contains()` to filter capabilities bits.
@@ -45,6 +46,7 @@ fn default_capabilities() {
 	assert!(x.contains(Capabilities::TOR_ADDRESS));
 	assert!(x.contains(Capabilities::PIBD_HIST));
 	assert!(x.contains(Capabilities::HEADERS_HASH));
+	assert!(x.contains(Capabilities::TX_RECONCILIATION));
 
 	assert_eq!(
 		x,
@@ -55,5 +57,49 @@ fn default_capabilities() {
 			| Capabilities::TOR_ADDRESS
 			| Capabilities::PIBD_HIST
 			| Capabilities::HEADERS_HASH
+			| Capabilities::TX_RECONCILIATION
 	);
 }
+
+#[test]
+fn history_depth_capabilities() {
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let horizon = global::cut_through_horizon() as u64;
+
+	assert_eq!(Capabilities::from_history_depth(0), Capabilities::UNKNOWN);
+	assert_eq!(
+		Capabilities::from_history_depth(horizon),
+		Capabilities::BLOCK_HIST_WEEK
+	);
+	assert_eq!(
+		Capabilities::from_history_depth(10 * horizon),
+		Capabilities::BLOCK_HIST_WEEK | Capabilities::BLOCK_HIST_MONTH
+	);
+	assert_eq!(
+		Capabilities::from_history_depth(u64::MAX),
+		Capabilities::history_depth_bits()
+	);
+
+	assert_eq!(
+		Capabilities::for_history_depth(horizon / 2),
+		Capabilities::BLOCK_HIST_WEEK
+	);
+	assert_eq!(
+		Capabilities::for_history_depth(4 * horizon),
+		Capabilities::BLOCK_HIST_MONTH
+	);
+	assert_eq!(
+		Capabilities::for_history_depth(100 * horizon),
+		Capabilities::BLOCK_HIST
+	);
+
+	// The node with a month of history can serve blocks a week deep
+	let x = Capabilities::new(false, false) | Capabilities::from_history_depth(5 * horizon);
+	assert!(x.contains(Capabilities::for_history_depth(horizon)));
+	assert!(!x.contains(Capabilities::for_history_depth(6 * horizon)));
+
+	// Full archive serves any depth, even without the depth bits
+	let archive = Capabilities::new(false, true).with_implied_history();
+	assert!(archive.contains(Capabilities::for_history_depth(30 * horizon)));
+	assert!(archive.contains(Capabilities::for_history_depth(100 * horizon)));
+}
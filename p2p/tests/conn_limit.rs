@@ -0,0 +1,78 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_p2p::conn_limit::{ConnLimit, ConnRateLimiter};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+#[test]
+fn conn_limit_token_bucket() {
+	// 6 per minute is one token every 10 seconds
+	let mut limiter = ConnRateLimiter::new(6, 3, 5);
+	let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+	let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+	let now = Instant::now();
+
+	for _ in 0..3 {
+		assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	}
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+	// other sources have their own buckets
+	assert_eq!(limiter.check_at(other, now), ConnLimit::Accept);
+
+	// bucket is refilled with the time
+	let now = now + Duration::from_secs(10);
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+
+	// loopback is never limited
+	let local = IpAddr::V4(Ipv4Addr::LOCALHOST);
+	for _ in 0..100 {
+		assert_eq!(limiter.check_at(local, now), ConnLimit::Accept);
+	}
+	assert_eq!(limiter.sources(), 2);
+}
+
+#[test]
+fn conn_limit_ban() {
+	let mut limiter = ConnRateLimiter::new(6, 1, 3);
+	let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+	let now = Instant::now();
+
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	for _ in 0..3 {
+		assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+	}
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Ban);
+	assert_eq!(limiter.sources(), 0);
+
+	// rejects are counted per minute
+	let mut limiter = ConnRateLimiter::new(6, 1, 3);
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	for _ in 0..3 {
+		assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+	}
+	let now = now + Duration::from_secs(61);
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	for _ in 0..3 {
+		assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+	}
+
+	// zero threshold disables the ban
+	let mut limiter = ConnRateLimiter::new(6, 1, 0);
+	assert_eq!(limiter.check_at(ip, now), ConnLimit::Accept);
+	for _ in 0..100 {
+		assert_eq!(limiter.check_at(ip, now), ConnLimit::Reject);
+	}
+}
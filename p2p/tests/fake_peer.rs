@@ -0,0 +1,301 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core as core;
+use mwc_p2p as p2p;
+
+use mwc_util as util;
+use mwc_util::StopState;
+
+use crate::core::core::hash::Hash;
+use crate::core::global;
+use crate::core::pow::Difficulty;
+use crate::core::ser::ProtocolVersion;
+use crate::p2p::handshake::Handshake;
+use crate::p2p::msg::{Ping, Type};
+use crate::p2p::test_utils::{socket_pair, FakePeer, FakePeerStep, FAKE_PEER_USER_AGENT};
+use crate::p2p::Peer;
+use mwc_chain::SyncState;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::{thread, time};
+
+fn open_port() -> u16 {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	listener.local_addr().unwrap().port()
+}
+
+fn test_setup() {
+	global::init_global_chain_type(global::ChainTypes::AutomatedTesting);
+	util::init_test_logger();
+}
+
+fn genesis() -> Hash {
+	Hash::from_vec(&[])
+}
+
+fn p2p_config() -> p2p::P2PConfig {
+	p2p::P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_allow: None,
+		peers_deny: None,
+		..p2p::P2PConfig::default()
+	}
+}
+
+fn new_server(db_root: &str, p2p_config: &p2p::P2PConfig) -> p2p::Server {
+	p2p::Server::new(
+		db_root,
+		p2p::Capabilities::UNKNOWN,
+		p2p_config.clone(),
+		Arc::new(p2p::DummyAdapter {}),
+		genesis(),
+		Arc::new(SyncState::new()),
+		Arc::new(StopState::new()),
+		0,
+		None,
+	)
+	.unwrap()
+}
+
+// Peer::accept against a fake peer over a socketpair, followed by a ping/pong
+// exchange driven by the fake peer script.
+#[test]
+fn fake_peer_accept() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = new_server(db_root.path().to_str().unwrap(), &p2p_config);
+
+	let (node_side, fake_side) = socket_pair().unwrap();
+	let mut fake = FakePeer::new(fake_side, ProtocolVersion::local());
+	let hand = fake.hand(genesis()).unwrap();
+	let fake_thread = thread::spawn(move || {
+		let shake = fake.handshake(hand);
+		(fake, shake)
+	});
+
+	let peer = Peer::accept(
+		node_side,
+		p2p::Capabilities::UNKNOWN,
+		Difficulty::min(),
+		&Handshake::new(genesis(), p2p_config.clone(), None),
+		Arc::new(p2p::DummyAdapter {}),
+		Arc::new(SyncState::new()),
+		server,
+	)
+	.unwrap();
+	assert_eq!(peer.info.user_agent, FAKE_PEER_USER_AGENT);
+	assert_eq!(peer.info.version, ProtocolVersion::local());
+
+	let (fake, shake) = fake_thread.join().unwrap();
+	let shake = shake.unwrap();
+	assert_eq!(shake.genesis, genesis());
+
+	let ping = core::ser::ser_vec(
+		&Ping {
+			total_difficulty: Difficulty::min(),
+			height: 0,
+		},
+		fake.version(),
+	)
+	.unwrap();
+	let (_fake, res) = fake
+		.spawn(vec![
+			FakePeerStep::Send(Type::Ping, ping),
+			FakePeerStep::Expect(Type::Pong),
+		])
+		.join()
+		.unwrap();
+	res.unwrap();
+	peer.stop();
+}
+
+// Peer on the older protocol version, accept must negotiate down to it.
+#[test]
+fn fake_peer_negotiate_version() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = new_server(db_root.path().to_str().unwrap(), &p2p_config);
+
+	let (node_side, fake_side) = socket_pair().unwrap();
+	let mut fake = FakePeer::new(fake_side, ProtocolVersion(1));
+	let hand = fake.hand(genesis()).unwrap();
+	let fake_thread = thread::spawn(move || fake.handshake(hand));
+
+	let peer = Peer::accept(
+		node_side,
+		p2p::Capabilities::UNKNOWN,
+		Difficulty::min(),
+		&Handshake::new(genesis(), p2p_config.clone(), None),
+		Arc::new(p2p::DummyAdapter {}),
+		Arc::new(SyncState::new()),
+		server,
+	)
+	.unwrap();
+	assert_eq!(peer.info.version, ProtocolVersion(1));
+
+	let shake = fake_thread.join().unwrap().unwrap();
+	assert_eq!(shake.version, ProtocolVersion(1));
+	peer.stop();
+}
+
+// Listening server must refuse the hand with a wrong genesis and ban the sender.
+#[test]
+fn fake_peer_bad_handshake_ban() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = Arc::new(new_server(db_root.path().to_str().unwrap(), &p2p_config));
+
+	let listener = server.clone();
+	let _ = thread::spawn(move || listener.listen());
+	thread::sleep(time::Duration::from_secs(1));
+
+	let addr = SocketAddr::new(p2p_config.host, p2p_config.port);
+	let mut fake = FakePeer::connect(addr, ProtocolVersion::local()).unwrap();
+	let fake_addr = fake.local_addr().unwrap();
+	let hand = fake.hand(Hash::from_vec(&[1, 2, 3])).unwrap();
+	assert!(fake.handshake(hand).is_err());
+
+	thread::sleep(time::Duration::from_secs(1));
+	assert!(server.peers.is_banned(&fake_addr));
+	server.stop();
+}
+
+// Garbage instead of a message header must close the connection.
+#[test]
+fn fake_peer_malformed_message() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = new_server(db_root.path().to_str().unwrap(), &p2p_config);
+
+	let (node_side, fake_side) = socket_pair().unwrap();
+	let mut fake = FakePeer::new(fake_side, ProtocolVersion::local());
+	let hand = fake.hand(genesis()).unwrap();
+	let fake_thread = thread::spawn(move || {
+		let shake = fake.handshake(hand);
+		(fake, shake)
+	});
+
+	let peer = Peer::accept(
+		node_side,
+		p2p::Capabilities::UNKNOWN,
+		Difficulty::min(),
+		&Handshake::new(genesis(), p2p_config.clone(), None),
+		Arc::new(p2p::DummyAdapter {}),
+		Arc::new(SyncState::new()),
+		server,
+	)
+	.unwrap();
+	let (mut fake, shake) = fake_thread.join().unwrap();
+	shake.unwrap();
+
+	fake.run(vec![FakePeerStep::SendRaw(vec![
+		0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 0, 0, 0, 0,
+	])])
+	.unwrap();
+	assert!(fake.is_closed(time::Duration::from_secs(5)));
+	peer.stop();
+}
+
+// A node that connects again with a fresh nonce is a duplicate of its connected session
+// and the new connection is dropped. Distinct nodes behind the same address (different
+// user agents) are separate sessions.
+#[test]
+fn fake_peer_same_address_sessions() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = Arc::new(new_server(db_root.path().to_str().unwrap(), &p2p_config));
+
+	let listener = server.clone();
+	let _ = thread::spawn(move || listener.listen());
+	thread::sleep(time::Duration::from_secs(1));
+
+	let addr = SocketAddr::new(p2p_config.host, p2p_config.port);
+	let mut first = FakePeer::connect(addr, ProtocolVersion::local()).unwrap();
+	let first_hand = first.hand(genesis()).unwrap();
+	let sender_addr = first_hand.sender_addr.clone();
+	let nonce = first_hand.nonce;
+	first.handshake(first_hand).unwrap();
+	thread::sleep(time::Duration::from_secs(1));
+	assert_eq!(server.peers.iter().connected().count(), 1);
+
+	// Same remote, the hand has a new random nonce
+	let mut duplicate = FakePeer::connect(addr, ProtocolVersion::local()).unwrap();
+	let mut duplicate_hand = duplicate.hand(genesis()).unwrap();
+	duplicate_hand.sender_addr = sender_addr.clone();
+	assert_ne!(duplicate_hand.nonce, nonce);
+	let _ = duplicate.handshake(duplicate_hand);
+	assert!(duplicate.is_closed(time::Duration::from_secs(5)));
+	assert_eq!(server.peers.iter().connected().count(), 1);
+	assert!(!first.is_closed(time::Duration::from_millis(100)));
+
+	// Another node behind the same address
+	let mut second = FakePeer::connect(addr, ProtocolVersion::local()).unwrap();
+	let mut second_hand = second.hand(genesis()).unwrap();
+	second_hand.sender_addr = sender_addr;
+	second_hand.user_agent = "MW/MWC fake peer 2".to_string();
+	second.handshake(second_hand).unwrap();
+	thread::sleep(time::Duration::from_secs(1));
+	assert_eq!(server.peers.iter().connected().count(), 2);
+	assert!(!second.is_closed(time::Duration::from_millis(100)));
+	server.stop();
+}
+
+// Excess inbound peer is dropped by clean_peers, the drop reason must be written
+// before the connection is closed.
+#[test]
+fn fake_peer_disconnect_reason() {
+	test_setup();
+	let db_root = tempfile::tempdir().unwrap();
+	let p2p_config = p2p_config();
+	let server = Arc::new(new_server(db_root.path().to_str().unwrap(), &p2p_config));
+
+	let listener = server.clone();
+	let _ = thread::spawn(move || listener.listen());
+	thread::sleep(time::Duration::from_secs(1));
+
+	let addr = SocketAddr::new(p2p_config.host, p2p_config.port);
+	let mut fake = FakePeer::connect(addr, ProtocolVersion::local()).unwrap();
+	let hand = fake.hand(genesis()).unwrap();
+	fake.handshake(hand).unwrap();
+	thread::sleep(time::Duration::from_secs(1));
+	assert_eq!(server.peers.iter().connected().count(), 1);
+
+	server
+		.peers
+		.clean_peers(0, 0, p2p::Capabilities::UNKNOWN, p2p_config.clone());
+	assert_eq!(server.peers.iter().connected().count(), 0);
+
+	// Skip the messages that the node sent after the handshake
+	let mut received = false;
+	for _ in 0..10 {
+		match fake.expect(Type::Disconnect) {
+			Ok(()) => {
+				received = true;
+				break;
+			}
+			Err(p2p::Error::UnexpectedMessage(_)) => continue,
+			Err(_) => break,
+		}
+	}
+	assert!(received);
+	assert!(fake.is_closed(time::Duration::from_secs(5)));
+	server.stop();
+}
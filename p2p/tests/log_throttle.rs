@@ -0,0 +1,55 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::Level;
+use mwc_p2p::log_throttle::LogThrottle;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn log_throttle_suppress_repeats() {
+	let throttle = LogThrottle::new(Duration::from_millis(200));
+
+	// first message is logged, repeats within the interval are counted
+	assert_eq!(throttle.check("lock", Level::Error), Some(0));
+	for _ in 0..5 {
+		assert_eq!(throttle.check("lock", Level::Error), None);
+	}
+	assert_eq!(throttle.suppressed("lock"), 5);
+
+	// other keys are independent
+	assert_eq!(throttle.check("send", Level::Debug), Some(0));
+
+	// after the interval the message is logged with the count of repeats
+	thread::sleep(Duration::from_millis(250));
+	assert_eq!(throttle.check("lock", Level::Error), Some(5));
+	assert_eq!(throttle.suppressed("lock"), 0);
+}
+
+#[test]
+fn log_throttle_flush() {
+	let throttle = LogThrottle::new(Duration::from_millis(100));
+	assert_eq!(throttle.check("lock", Level::Error), Some(0));
+	assert_eq!(throttle.check("lock", Level::Error), None);
+
+	// summary is not reported before the interval
+	throttle.flush();
+	assert_eq!(throttle.suppressed("lock"), 1);
+
+	thread::sleep(Duration::from_millis(150));
+	throttle.flush();
+	assert_eq!(throttle.suppressed("lock"), 0);
+	// summary restarts the interval
+	assert_eq!(throttle.check("lock", Level::Error), None);
+}
@@ -0,0 +1,61 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::pow::Difficulty;
+use mwc_p2p::{PeerAddr, PeerChangeKind, PeerChangeLog};
+
+#[test]
+fn peer_changes_since() {
+	let log = PeerChangeLog::new();
+	let addr = PeerAddr::Ip("127.0.0.1:3414".parse().unwrap());
+
+	let changes = log.changes_since(0);
+	assert!(!changes.resync);
+	assert_eq!(changes.seq, 0);
+	assert!(changes.changes.is_empty());
+
+	log.record(&addr, PeerChangeKind::Connected, 0, Difficulty::min());
+	log.record(&addr, PeerChangeKind::HeightUpdated, 10, Difficulty::min());
+	let seq = log.record(&addr, PeerChangeKind::Disconnected, 10, Difficulty::min());
+	assert_eq!(seq, 3);
+
+	let changes = log.changes_since(1);
+	assert!(!changes.resync);
+	assert_eq!(changes.seq, 3);
+	assert_eq!(changes.changes.len(), 2);
+	assert_eq!(changes.changes[0].kind, PeerChangeKind::HeightUpdated);
+	assert_eq!(changes.changes[0].height, 10);
+
+	assert!(log.changes_since(3).changes.is_empty());
+	// client from the previous node run
+	assert!(log.changes_since(100).resync);
+}
+
+#[test]
+fn peer_changes_overflow() {
+	let log = PeerChangeLog::new();
+	let addr = PeerAddr::Ip("127.0.0.1:3414".parse().unwrap());
+	for height in 0..3000 {
+		log.record(
+			&addr,
+			PeerChangeKind::HeightUpdated,
+			height,
+			Difficulty::min(),
+		);
+	}
+	let changes = log.changes_since(10);
+	assert!(changes.resync);
+	assert_eq!(changes.seq, 3000);
+	assert!(!log.changes_since(2990).resync);
+}
@@ -105,3 +105,70 @@ fn peer_handshake() {
 	assert_eq!(server_peer.info.total_difficulty(), Difficulty::min());
 	assert!(server.peers.iter().connected().count() > 0);
 }
+
+// Switching to Tor only mode closes the clearnet peers and refuses new ones.
+#[test]
+fn peer_tor_only_mode() {
+	test_setup();
+
+	let p2p_config = p2p::P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_allow: None,
+		peers_deny: None,
+		..p2p::P2PConfig::default()
+	};
+	let db_root = tempfile::tempdir().unwrap();
+	let net_adapter = Arc::new(p2p::DummyAdapter {});
+	let server_inner = p2p::Server::new(
+		db_root.path().to_str().unwrap(),
+		p2p::Capabilities::UNKNOWN,
+		p2p_config.clone(),
+		net_adapter.clone(),
+		Hash::from_vec(&vec![]),
+		Arc::new(SyncState::new()),
+		Arc::new(StopState::new()),
+		0,
+		None,
+	)
+	.unwrap();
+	let server = Arc::new(server_inner.clone());
+
+	let p2p_inner = server.clone();
+	let _ = thread::spawn(move || p2p_inner.listen());
+
+	thread::sleep(time::Duration::from_secs(1));
+
+	let addr = SocketAddr::new(p2p_config.host, p2p_config.port);
+	let socket = TcpStream::connect_timeout(&addr, time::Duration::from_secs(10)).unwrap();
+
+	let my_addr = PeerAddr::Ip("127.0.0.1:5001".parse().unwrap());
+	let _peer = Peer::connect(
+		socket,
+		p2p::Capabilities::UNKNOWN,
+		Difficulty::min(),
+		my_addr.clone(),
+		&p2p::handshake::Handshake::new(Hash::from_vec(&vec![]), p2p_config.clone(), None),
+		net_adapter,
+		None,
+		Arc::new(SyncState::new()),
+		server_inner,
+	)
+	.unwrap();
+
+	thread::sleep(time::Duration::from_secs(1));
+	assert_eq!(server.peers.iter().connected().count(), 1);
+
+	// No onion address and socks proxy, Tor only mode is not possible
+	assert!(server.peers.set_tor_only(true).is_err());
+	assert!(!server.peers.is_tor_only());
+
+	server.peers.set_tor_available(true);
+	assert_eq!(server.peers.set_tor_only(true).unwrap(), 1);
+	assert!(server.peers.is_tor_only());
+	assert_eq!(server.peers.iter().connected().count(), 0);
+	assert!(server.connect(&my_addr).is_err());
+
+	assert_eq!(server.peers.set_tor_only(false).unwrap(), 0);
+	assert!(!server.peers.is_tor_only());
+}
@@ -0,0 +1,54 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::pow::Difficulty;
+use mwc_core::ser::ProtocolVersion;
+use mwc_p2p::msg::{Msg, Ping, SendCompletion, Type};
+use std::time::Duration;
+
+fn ping_msg() -> Msg {
+	let ping = Ping {
+		total_difficulty: Difficulty::min(),
+		height: 1,
+	};
+	Msg::new(Type::Ping, ping, ProtocolVersion::local()).unwrap()
+}
+
+#[test]
+fn send_completion_delivered() {
+	let mut msg = ping_msg();
+	let completion = msg.set_completion();
+	assert!(!completion.wait(Duration::from_millis(10)));
+	msg.complete();
+	assert!(completion.wait(Duration::from_millis(10)));
+}
+
+#[test]
+fn send_completion_dropped() {
+	let mut msg = ping_msg();
+	let completion = msg.set_completion();
+	// Message dropped from the queue or with the connection is never delivered
+	drop(msg);
+	assert!(!completion.wait(Duration::from_secs(5)));
+
+	let mut delivered = ping_msg();
+	let mut dropped = ping_msg();
+	let completions = vec![delivered.set_completion(), dropped.set_completion()];
+	delivered.complete();
+	drop(dropped);
+	assert_eq!(
+		SendCompletion::wait_all(&completions, Duration::from_millis(100)),
+		1
+	);
+}
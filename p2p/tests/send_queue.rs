@@ -0,0 +1,148 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::pow::Difficulty;
+use mwc_core::ser::ProtocolVersion;
+use mwc_p2p::msg::{Msg, Ping, Type};
+use mwc_p2p::send_queue::{
+	send_queues, ConnHandle, SendQueueCaps, SendQueues, BULK_MAX_SKIPPED_BATCHES, BULK_WRITE_BATCH,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Only the message type matters for the queues
+fn msg(msg_type: Type) -> Msg {
+	let ping = Ping {
+		total_difficulty: Difficulty::min(),
+		height: 1,
+	};
+	Msg::new(msg_type, ping, ProtocolVersion::local()).unwrap()
+}
+
+fn queues(cap: usize) -> (ConnHandle, SendQueues, Arc<AtomicBool>) {
+	let stopped = Arc::new(AtomicBool::new(false));
+	let caps = SendQueueCaps {
+		control: cap,
+		consensus: cap,
+		bulk: cap,
+	};
+	let (handle, queues) = send_queues(caps, stopped.clone());
+	(handle, queues, stopped)
+}
+
+fn types(batch: Vec<Msg>) -> Vec<Type> {
+	batch.iter().map(|m| m.msg_type()).collect()
+}
+
+#[test]
+fn send_queue_priority_order() {
+	let (handle, mut queues, _) = queues(16);
+	handle.send(msg(Type::TxHashSetRequest)).unwrap();
+	handle.send(msg(Type::Transaction)).unwrap();
+	handle.send(msg(Type::Ping)).unwrap();
+	handle.send(msg(Type::Block)).unwrap();
+
+	let timeout = Duration::from_millis(100);
+	assert_eq!(
+		types(queues.recv_batch(timeout).unwrap()),
+		vec![Type::Ping, Type::Transaction, Type::Block]
+	);
+	assert_eq!(
+		types(queues.recv_batch(timeout).unwrap()),
+		vec![Type::TxHashSetRequest]
+	);
+	assert!(queues.recv_batch(timeout).is_err());
+
+	// Sync data alone goes in the limited batches
+	for _ in 0..BULK_WRITE_BATCH + 1 {
+		handle.send(msg(Type::Headers)).unwrap();
+	}
+	assert_eq!(queues.recv_batch(timeout).unwrap().len(), BULK_WRITE_BATCH);
+	assert_eq!(queues.recv_batch(timeout).unwrap().len(), 1);
+}
+
+#[test]
+fn send_queue_bulk_not_starved() {
+	let (handle, mut queues, _) = queues(64);
+	for _ in 0..2 * BULK_WRITE_BATCH {
+		handle.send(msg(Type::Headers)).unwrap();
+	}
+
+	// Relay never stops, the sync data still goes with every few writes
+	let timeout = Duration::from_millis(100);
+	let mut bulk_batches = vec![];
+	for n in 0..2 * (BULK_MAX_SKIPPED_BATCHES + 1) {
+		handle.send(msg(Type::Transaction)).unwrap();
+		let batch = types(queues.recv_batch(timeout).unwrap());
+		assert_eq!(batch[0], Type::Transaction);
+		let bulk = batch.iter().filter(|t| **t == Type::Headers).count();
+		assert!(bulk <= BULK_WRITE_BATCH);
+		if bulk > 0 {
+			bulk_batches.push((n, bulk));
+		}
+	}
+	assert_eq!(
+		bulk_batches,
+		vec![
+			(BULK_MAX_SKIPPED_BATCHES, BULK_WRITE_BATCH),
+			(2 * BULK_MAX_SKIPPED_BATCHES + 1, BULK_WRITE_BATCH)
+		]
+	);
+}
+
+#[test]
+fn send_queue_lane_drops() {
+	let (handle, mut queues, stopped) = queues(1);
+
+	// Full relay and sync data queues drop the new messages, the connection is fine
+	handle.send(msg(Type::Block)).unwrap();
+	handle.send(msg(Type::Transaction)).unwrap();
+	handle.send(msg(Type::Headers)).unwrap();
+	handle.send(msg(Type::TxHashSetRequest)).unwrap();
+	handle.send(msg(Type::Ping)).unwrap();
+	assert!(!stopped.load(Ordering::Relaxed));
+
+	// Control message is never dropped, the peer that doesn't read is disconnected
+	let start = Instant::now();
+	assert!(handle.send(msg(Type::Pong)).is_err());
+	assert!(start.elapsed() >= Duration::from_secs(1));
+	assert!(stopped.load(Ordering::Relaxed));
+
+	let timeout = Duration::from_millis(100);
+	assert_eq!(
+		types(queues.recv_batch(timeout).unwrap()),
+		vec![Type::Ping, Type::Block]
+	);
+	assert_eq!(
+		types(queues.recv_batch(timeout).unwrap()),
+		vec![Type::Headers]
+	);
+	assert!(queues.recv_batch(timeout).is_err());
+
+	// Control message waits for the space in the queue
+	let sender = handle.clone();
+	let writer = std::thread::spawn(move || {
+		std::thread::sleep(Duration::from_millis(200));
+		let batch = queues.recv_batch(Duration::from_secs(1)).unwrap();
+		(queues, batch)
+	});
+	handle.send(msg(Type::Ping)).unwrap();
+	sender.send(msg(Type::Pong)).unwrap();
+	let (mut queues, mut sent) = writer.join().unwrap();
+	if let Ok(mut batch) = queues.recv_batch(timeout) {
+		sent.append(&mut batch);
+	}
+	assert_eq!(types(sent), vec![Type::Ping, Type::Pong]);
+}
@@ -13,6 +13,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use mwc_core::core::hash::Hash;
+use mwc_core::ser::{self, DeserializationMode, ProtocolVersion};
 use mwc_p2p as p2p;
 
 use num::FromPrimitive;
@@ -36,9 +38,51 @@ fn test_reason_for_ban_enum() {
 	);
 }
 
+#[test]
+fn test_drop_reason_enum() {
+	assert_eq!(
+		p2p::types::DropReason::from_i32(0),
+		Some(p2p::types::DropReason::None)
+	);
+	assert_eq!(
+		p2p::types::DropReason::from_i32(4),
+		Some(p2p::types::DropReason::Stuck)
+	);
+}
+
 #[test]
 fn test_type_enum() {
 	assert_eq!(p2p::msg::Type::from_i32(0), Some(p2p::msg::Type::Error));
+	assert_eq!(
+		p2p::msg::Type::from_i32(39),
+		Some(p2p::msg::Type::Disconnect)
+	);
+	assert_eq!(
+		p2p::msg::Type::from_i32(41),
+		Some(p2p::msg::Type::TxHashSetRangeArchive)
+	);
+}
+
+#[test]
+fn test_txhashset_range_request() {
+	let req = p2p::msg::TxHashSetRangeRequest {
+		hash: Hash::from_vec(&[1, 2, 3]),
+		height: 1000,
+		offset: 12345,
+		length: 0,
+	};
+	let bytes = ser::ser_vec(&req, ProtocolVersion::local()).unwrap();
+	assert_eq!(bytes.len(), 56);
+	let req2: p2p::msg::TxHashSetRangeRequest = ser::deserialize(
+		&mut &bytes[..],
+		ProtocolVersion::local(),
+		DeserializationMode::default(),
+	)
+	.unwrap();
+	assert_eq!(req2.hash, req.hash);
+	assert_eq!(req2.height, 1000);
+	assert_eq!(req2.offset, 12345);
+	assert_eq!(req2.length, 0);
 }
 
 #[test]
@@ -66,3 +110,14 @@ fn test_capabilities() {
 			.contains(p2p::types::Capabilities::TX_KERNEL_HASH)
 	);
 }
+
+#[test]
+fn test_msg_priority() {
+	use p2p::msg::{MsgPriority, Type};
+	assert_eq!(Type::Ping.priority(), MsgPriority::Control);
+	assert_eq!(Type::BanReason.priority(), MsgPriority::Control);
+	assert_eq!(Type::CompactBlock.priority(), MsgPriority::Consensus);
+	assert_eq!(Type::Transaction.priority(), MsgPriority::Consensus);
+	assert_eq!(Type::Headers.priority(), MsgPriority::Bulk);
+	assert_eq!(Type::OutputSegment.priority(), MsgPriority::Bulk);
+}
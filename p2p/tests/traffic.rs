@@ -0,0 +1,42 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_p2p::msg::{MsgPriority, Type};
+use mwc_p2p::traffic::{TrafficCounter, TrafficStats};
+
+#[test]
+fn traffic_by_class() {
+	let mut counter = TrafficCounter::new();
+	assert_eq!(counter.stats(), TrafficStats::default());
+
+	counter.add_sent(Type::Ping.priority(), 10);
+	counter.add_sent(Type::CompactBlock.priority(), 1000);
+	counter.add_received(Type::OutputSegment.priority(), 50_000);
+	counter.add_received(MsgPriority::Bulk, 7);
+	counter.add_received(MsgPriority::Consensus, 300);
+
+	let stats = counter.stats();
+	assert_eq!(stats.sent.control, 10);
+	assert_eq!(stats.sent.consensus, 1000);
+	assert_eq!(stats.sent.bulk, 0);
+	assert_eq!(stats.received.control, 0);
+	assert_eq!(stats.received.consensus, 300);
+	assert_eq!(stats.received.bulk, 50_007);
+	assert_eq!(stats.sent.total(), 1010);
+	assert_eq!(stats.total(), 51_317);
+
+	let mut merged = stats;
+	merged.merge(&stats);
+	assert_eq!(merged.total(), 2 * stats.total());
+}
@@ -0,0 +1,117 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hash;
+use mwc_core::ser::{self, ProtocolVersion};
+use mwc_p2p::tx_reconciliation::{sketch_capacity, TxReconciliation, TxSketch};
+use std::collections::HashSet;
+
+fn hashes(from: u8, to: u8) -> Vec<Hash> {
+	(from..to).map(|i| Hash::from_vec(&[i; 32])).collect()
+}
+
+#[test]
+fn sketch_decode_difference() {
+	let local: Vec<u64> = (0..200).collect();
+	let remote: Vec<u64> = (10..215).collect();
+	let cells = sketch_capacity(local.len(), remote.len());
+
+	let diff = TxSketch::from_ids(cells, &local)
+		.subtract(&TxSketch::from_ids(cells, &remote))
+		.unwrap();
+	let (mut only_local, mut only_remote) = diff.decode().unwrap();
+	only_local.sort();
+	only_remote.sort();
+	assert_eq!(only_local, (0..10).collect::<Vec<u64>>());
+	assert_eq!(only_remote, (200..215).collect::<Vec<u64>>());
+}
+
+#[test]
+fn sketch_too_small() {
+	let local: Vec<u64> = (0..100).collect();
+	let remote: Vec<u64> = (100..200).collect();
+	let diff = TxSketch::from_ids(24, &local)
+		.subtract(&TxSketch::from_ids(24, &remote))
+		.unwrap();
+	assert!(diff.decode().is_none());
+}
+
+#[test]
+fn sketch_ser_deser() {
+	let sketch = TxSketch::from_ids(30, &[1, 2, 3]);
+	let vec = ser::ser_vec(&sketch, ProtocolVersion::local()).unwrap();
+	let sketch2: TxSketch = ser::deserialize(
+		&mut &vec[..],
+		ProtocolVersion::local(),
+		ser::DeserializationMode::default(),
+	)
+	.unwrap();
+	assert_eq!(sketch, sketch2);
+}
+
+// Full round between the initiator and the responder, both sides end up with
+// the union of the announcements.
+#[test]
+fn reconciliation_round() {
+	let initiator = TxReconciliation::new();
+	let responder = TxReconciliation::new();
+	for h in hashes(0, 40) {
+		assert!(initiator.add(h));
+	}
+	for h in hashes(30, 45) {
+		assert!(responder.add(h));
+	}
+
+	let (set_size, salt) = initiator.start_round().unwrap();
+	assert_eq!(set_size, 40);
+	// Only one round at a time
+	assert!(initiator.start_round().is_none());
+
+	let sketch = responder.sketch_for_request(set_size, salt);
+	let res = initiator.process_sketch(&sketch);
+	assert!(res.decoded);
+	let announced: HashSet<Hash> = res.announce.into_iter().collect();
+	assert_eq!(announced, hashes(0, 30).into_iter().collect());
+
+	let requested: HashSet<Hash> = responder
+		.process_diff(res.decoded, &res.want)
+		.into_iter()
+		.collect();
+	assert_eq!(requested, hashes(40, 45).into_iter().collect());
+
+	assert_eq!(initiator.pending_len(), 0);
+	assert_eq!(responder.pending_len(), 0);
+	assert!(initiator.start_round().is_none());
+}
+
+// Failed decode falls back to the full sets announcement.
+#[test]
+fn reconciliation_fallback() {
+	let initiator = TxReconciliation::new();
+	let responder = TxReconciliation::new();
+	for h in hashes(0, 100) {
+		initiator.add(h);
+	}
+	for h in hashes(100, 200) {
+		responder.add(h);
+	}
+
+	let (_, salt) = initiator.start_round().unwrap();
+	// Responder thinks that sets are about the same
+	let sketch = responder.sketch_for_request(100, salt);
+	let res = initiator.process_sketch(&sketch);
+	assert!(!res.decoded);
+	assert_eq!(res.announce.len(), 100);
+	assert_eq!(responder.process_diff(res.decoded, &res.want).len(), 100);
+}
@@ -0,0 +1,121 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core::core::hash::Hash;
+use mwc_p2p::tx_relay::{
+	MisbehaviorScore, TxRelayCheck, TxRelayFilter, TxRelayKind, MISBEHAVIOR_BAN_SCORE, TX_SEEN_TTL,
+};
+use mwc_p2p::PeerAddr;
+use std::time::{Duration, Instant};
+
+fn peer(addr: &str) -> PeerAddr {
+	PeerAddr::Ip(addr.parse().unwrap())
+}
+
+fn kernel(n: u8) -> Hash {
+	Hash::from_vec(&[n; 32])
+}
+
+#[test]
+fn tx_relay_dedup() {
+	let mut filter = TxRelayFilter::new(600, 100);
+	let (a, b) = (peer("10.0.0.1:3414"), peer("10.0.0.2:3414"));
+	let now = Instant::now();
+
+	// Announcement and the requested transaction from the same peer are fine
+	assert_eq!(
+		filter.check_at(&a, kernel(1), TxRelayKind::Announce, now),
+		TxRelayCheck::Accept
+	);
+	assert_eq!(
+		filter.check_at(&a, kernel(1), TxRelayKind::Transaction, now),
+		TxRelayCheck::Accept
+	);
+	// Repeats from the same peer are the misbehavior
+	assert_eq!(
+		filter.check_at(&a, kernel(1), TxRelayKind::Announce, now),
+		TxRelayCheck::Duplicate
+	);
+	assert_eq!(
+		filter.check_at(&a, kernel(1), TxRelayKind::Transaction, now),
+		TxRelayCheck::Duplicate
+	);
+	// Other peer sends the processed transaction, it is skipped without the penalty
+	assert_eq!(
+		filter.check_at(&b, kernel(1), TxRelayKind::Transaction, now),
+		TxRelayCheck::Known
+	);
+	// Stem transaction is passed, it is fluffed later
+	assert_eq!(
+		filter.check_at(&b, kernel(1), TxRelayKind::StemTransaction, now),
+		TxRelayCheck::Accept
+	);
+
+	// After the ttl the transaction can be processed again
+	let later = now + TX_SEEN_TTL;
+	assert_eq!(
+		filter.check_at(&a, kernel(1), TxRelayKind::Transaction, later),
+		TxRelayCheck::Accept
+	);
+}
+
+#[test]
+fn tx_relay_rate_limit() {
+	let mut filter = TxRelayFilter::new(60, 3);
+	let (a, b) = (peer("10.0.0.1:3414"), peer("10.0.0.2:3414"));
+	let now = Instant::now();
+
+	for n in 0..3 {
+		assert_eq!(
+			filter.check_at(&a, kernel(n), TxRelayKind::Announce, now),
+			TxRelayCheck::Accept
+		);
+	}
+	assert_eq!(
+		filter.check_at(&a, kernel(3), TxRelayKind::Announce, now),
+		TxRelayCheck::RateLimited
+	);
+	// Other peer has its own bucket
+	assert_eq!(
+		filter.check_at(&b, kernel(3), TxRelayKind::Announce, now),
+		TxRelayCheck::Accept
+	);
+	assert_eq!(filter.peers(), 2);
+
+	// Refilled at one message per second
+	let later = now + Duration::from_secs(1);
+	assert_eq!(
+		filter.check_at(&a, kernel(3), TxRelayKind::Announce, later),
+		TxRelayCheck::Accept
+	);
+	assert_eq!(
+		filter.check_at(&a, kernel(4), TxRelayKind::Announce, later),
+		TxRelayCheck::RateLimited
+	);
+}
+
+#[test]
+fn misbehavior_score_decay() {
+	let mut score = MisbehaviorScore::new();
+	let now = Instant::now();
+	assert_eq!(score.add_at(50, now), 50);
+	assert_eq!(score.add_at(40, now), 90);
+	// 10 points are forgiven per minute
+	assert_eq!(score.add_at(5, now + Duration::from_secs(60)), 85);
+	assert!(
+		score.add_at(MISBEHAVIOR_BAN_SCORE, now + Duration::from_secs(3600))
+			>= MISBEHAVIOR_BAN_SCORE
+	);
+	assert_eq!(score.add_at(0, now + Duration::from_secs(7200)), 0);
+}
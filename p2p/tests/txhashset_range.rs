@@ -0,0 +1,145 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_chain as chain;
+use mwc_core as core;
+use mwc_p2p as p2p;
+
+use mwc_util as util;
+use mwc_util::StopState;
+
+use crate::chain::SyncState;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::BlockHeader;
+use crate::core::global;
+use crate::core::pow::Difficulty;
+use crate::core::ser::ProtocolVersion;
+use crate::p2p::handshake::Handshake;
+use crate::p2p::msg::{TxHashSetRangeArchive, TxHashSetRangeRequest, Type};
+use crate::p2p::test_utils::{socket_pair, ArchiveAdapter, FakePeer};
+use crate::p2p::types::Capabilities;
+use crate::p2p::Peer;
+use std::fs;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::{thread, time};
+
+fn open_port() -> u16 {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	listener.local_addr().unwrap().port()
+}
+
+fn genesis() -> Hash {
+	Hash::from_vec(&[])
+}
+
+// The archive node serves the rest of the archive from the offset, and the partially
+// downloaded archive is completed with the received range.
+#[test]
+fn txhashset_range_transfer() {
+	global::init_global_chain_type(global::ChainTypes::AutomatedTesting);
+	util::init_test_logger();
+
+	let dir = tempfile::tempdir().unwrap();
+	let archive: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+	let archive_path = dir.path().join("archive.zip");
+	fs::write(&archive_path, &archive).unwrap();
+	let tmp_dir = dir.path().join("tmp");
+	fs::create_dir(&tmp_dir).unwrap();
+	let header = BlockHeader {
+		height: 1440,
+		..BlockHeader::default()
+	};
+	let adapter = Arc::new(ArchiveAdapter::new(
+		header.clone(),
+		archive_path,
+		tmp_dir.clone(),
+	));
+
+	let p2p_config = p2p::P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_allow: None,
+		peers_deny: None,
+		..p2p::P2PConfig::default()
+	};
+	let server = p2p::Server::new(
+		dir.path().join("peers").to_str().unwrap(),
+		Capabilities::UNKNOWN,
+		p2p_config.clone(),
+		adapter.clone(),
+		genesis(),
+		Arc::new(SyncState::new()),
+		Arc::new(StopState::new()),
+		0,
+		None,
+	)
+	.unwrap();
+
+	let (node_side, fake_side) = socket_pair().unwrap();
+	let mut fake = FakePeer::new(fake_side, ProtocolVersion::local());
+	let hand = fake.hand(genesis()).unwrap();
+	let fake_thread = thread::spawn(move || {
+		let shake = fake.handshake(hand);
+		(fake, shake)
+	});
+	let peer = Peer::accept(
+		node_side,
+		Capabilities::UNKNOWN,
+		Difficulty::min(),
+		&Handshake::new(genesis(), p2p_config.clone(), None),
+		adapter.clone(),
+		Arc::new(SyncState::new()),
+		server,
+	)
+	.unwrap();
+	let (mut fake, shake) = fake_thread.join().unwrap();
+	shake.unwrap();
+
+	// Serving the rest of the archive
+	fake.send(
+		Type::TxHashSetRangeRequest,
+		TxHashSetRangeRequest {
+			hash: header.hash(),
+			height: header.height,
+			offset: 5000,
+			length: 0,
+		},
+	)
+	.unwrap();
+	let resp: TxHashSetRangeArchive = fake.read(Type::TxHashSetRangeArchive).unwrap();
+	assert_eq!(resp.hash, header.hash());
+	assert_eq!(
+		(resp.bytes, resp.offset, resp.length),
+		(20_000, 5000, 15_000)
+	);
+	assert_eq!(fake.read_raw(15_000).unwrap(), archive[5000..].to_vec());
+
+	// Resuming the interrupted download of the same archive
+	let partial = tmp_dir.join(format!("txhashset-{}.zip", header.hash()));
+	fs::write(&partial, &archive[..5000]).unwrap();
+	fake.send(Type::TxHashSetRangeArchive, resp).unwrap();
+	fake.send_raw(&archive[5000..]).unwrap();
+	for _ in 0..100 {
+		if adapter.received().is_some() {
+			break;
+		}
+		thread::sleep(time::Duration::from_millis(100));
+	}
+	assert_eq!(adapter.received(), Some(partial.clone()));
+	assert_eq!(fs::read(&partial).unwrap(), archive);
+	assert!(!adapter.is_banned_any());
+
+	peer.stop();
+}
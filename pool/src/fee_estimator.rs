@@ -0,0 +1,99 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fee recommendation for the new transactions. The fee is expressed as the fee base, the fee
+//! per fee weight unit: the transaction fee is `fee_base * Transaction::weight_for_fee(..)`,
+//! the same way as the `accept_fee_base` of the pool.
+//!
+//! The transactions are mined by the fee rate, so the new transaction gets into the next
+//! `blocks_target` blocks if the pool transactions with the higher fee base don't fill them.
+//! If the recent blocks are full, the pool may not reflect the demand (the transactions were
+//! just mined), the fee base of the recent blocks is used as the floor then.
+
+use crate::types::PoolEntry;
+
+/// Blocks are considered full above that utilization
+pub const CONGESTED_UTILIZATION: f64 = 0.9;
+
+/// Transaction in the pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolTxFee {
+	/// Fee per fee weight unit, rounded up
+	pub fee_base: u64,
+	/// Transaction weight, the same units as the block weight
+	pub weight: u64,
+}
+
+impl PoolTxFee {
+	pub fn from_entry(entry: &PoolEntry, height: u64) -> PoolTxFee {
+		PoolTxFee {
+			fee_base: entry.tx.get_base_fee(height),
+			weight: entry.tx.weight_size(),
+		}
+	}
+}
+
+/// Recent block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockFeeSample {
+	/// Weight of the block relative to the max block weight
+	pub utilization: f64,
+	/// Fee base of the block transactions, None for the block without transactions
+	pub fee_base: Option<u64>,
+}
+
+/// Recommended fee base to get into one of the next `blocks_target` blocks. Never less
+/// than the `min_fee_base` that the pool accepts.
+pub fn estimate_fee_base(
+	pool_txs: &[PoolTxFee],
+	recent_blocks: &[BlockFeeSample],
+	block_weight: u64,
+	blocks_target: u64,
+	min_fee_base: u64,
+) -> u64 {
+	let capacity = block_weight.saturating_mul(blocks_target.max(1));
+
+	// Highest fee first, the order the transactions are mined
+	let mut txs = pool_txs.to_vec();
+	txs.sort_unstable_by(|a, b| b.fee_base.cmp(&a.fee_base));
+	let mut weight: u64 = 0;
+	let mut pool_fee_base = min_fee_base;
+	for tx in &txs {
+		weight = weight.saturating_add(tx.weight);
+		if weight >= capacity {
+			// The transactions from this one are not mined in time, need to outbid it
+			pool_fee_base = tx.fee_base.saturating_add(1);
+			break;
+		}
+	}
+
+	let floor = if average_utilization(recent_blocks) >= CONGESTED_UTILIZATION {
+		let mut fee_bases: Vec<u64> = recent_blocks.iter().filter_map(|b| b.fee_base).collect();
+		fee_bases.sort_unstable();
+		fee_bases.get(fee_bases.len() / 2).cloned().unwrap_or(0)
+	} else {
+		0
+	};
+
+	min_fee_base.max(pool_fee_base).max(floor)
+}
+
+/// Average utilization of the blocks, 0 for no blocks
+pub fn average_utilization(blocks: &[BlockFeeSample]) -> f64 {
+	if blocks.is_empty() {
+		0.0
+	} else {
+		blocks.iter().map(|b| b.utilization).sum::<f64>() / blocks.len() as f64
+	}
+}
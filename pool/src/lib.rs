@@ -31,6 +31,8 @@ extern crate serde_derive;
 #[macro_use]
 extern crate log;
 
+pub mod fee_estimator;
+pub mod orphans;
 mod pool;
 pub mod transaction_pool;
 pub mod types;
@@ -38,5 +40,5 @@ pub mod types;
 pub use crate::pool::Pool;
 pub use crate::transaction_pool::TransactionPool;
 pub use crate::types::{
-	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource,
+	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxPackage, TxSource,
 };
@@ -0,0 +1,179 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Orphan transactions, the ones that spend the outputs that are neither in the pool nor
+//! in the chain. Peers relay the transactions in any order, so the child can arrive
+//! before its parent. The orphan is kept for a while, indexed by the missing outputs,
+//! and is added to the pool again when a transaction or a block creates them.
+
+use crate::types::PoolEntry;
+use mwc_core::core::hash::{Hash, Hashed};
+use mwc_core::ser;
+use mwc_util::secp::pedersen::Commitment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Orphans are dropped after that time
+pub const ORPHAN_TX_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Transaction waiting for its parents
+#[derive(Clone, Debug)]
+pub struct OrphanTx {
+	pub entry: PoolEntry,
+	/// Spent outputs that were not found
+	pub missing: Vec<Commitment>,
+	added: Instant,
+	size: usize,
+}
+
+/// Orphan transactions indexed by the missing outputs, limited by the number of the
+/// transactions and by their serialized size. The oldest orphans are evicted first.
+pub struct OrphanTxPool {
+	max_count: usize,
+	max_bytes: usize,
+	orphans: HashMap<Hash, OrphanTx>,
+	missing_idx: HashMap<Commitment, Vec<Hash>>,
+	bytes: usize,
+	// accumulated number of the evicted orphans because of the limits or the TTL
+	evicted: usize,
+}
+
+impl OrphanTxPool {
+	pub fn new(max_count: usize, max_bytes: usize) -> OrphanTxPool {
+		OrphanTxPool {
+			max_count,
+			max_bytes,
+			orphans: HashMap::new(),
+			missing_idx: HashMap::new(),
+			bytes: 0,
+			evicted: 0,
+		}
+	}
+
+	/// Number of the orphans
+	pub fn len(&self) -> usize {
+		self.orphans.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.orphans.is_empty()
+	}
+
+	/// Serialized size of the orphans
+	pub fn bytes(&self) -> usize {
+		self.bytes
+	}
+
+	/// Accumulated number of the evicted orphans
+	pub fn evicted(&self) -> usize {
+		self.evicted
+	}
+
+	pub fn contains(&self, tx_hash: &Hash) -> bool {
+		self.orphans.contains_key(tx_hash)
+	}
+
+	/// Keep the transaction until the missing outputs are created. Returns false if the
+	/// transaction is too large to be kept.
+	pub fn add(&mut self, entry: PoolEntry, missing: Vec<Commitment>) -> bool {
+		self.add_at(entry, missing, Instant::now())
+	}
+
+	/// Same as `add`, at the provided time
+	pub fn add_at(&mut self, entry: PoolEntry, missing: Vec<Commitment>, now: Instant) -> bool {
+		let size = ser::ser_vec(&entry.tx, ser::ProtocolVersion::local())
+			.map(|x| x.len())
+			.unwrap_or(usize::MAX);
+		if missing.is_empty() || size > self.max_bytes || self.max_count == 0 {
+			return false;
+		}
+
+		let hash = entry.tx.hash();
+		self.remove(&hash);
+		for commit in &missing {
+			self.missing_idx.entry(*commit).or_default().push(hash);
+		}
+		self.bytes += size;
+		self.orphans.insert(
+			hash,
+			OrphanTx {
+				entry,
+				missing,
+				added: now,
+				size,
+			},
+		);
+
+		self.evict(now);
+		true
+	}
+
+	/// Remove the orphans that wait for any of the outputs, they should be added to
+	/// the pool again. The oldest orphans go first.
+	pub fn remove_by_outputs(&mut self, outputs: &[Commitment]) -> Vec<OrphanTx> {
+		let mut hashes = vec![];
+		for commit in outputs {
+			if let Some(hs) = self.missing_idx.get(commit) {
+				hashes.extend_from_slice(hs);
+			}
+		}
+		let mut ready: Vec<OrphanTx> = hashes.iter().filter_map(|h| self.remove(h)).collect();
+		ready.sort_by_key(|x| x.added);
+		ready
+	}
+
+	/// Drop the orphans that waited longer than ORPHAN_TX_TTL
+	pub fn evict_expired(&mut self, now: Instant) {
+		let expired: Vec<Hash> = self
+			.orphans
+			.iter()
+			.filter(|(_, x)| now.saturating_duration_since(x.added) >= ORPHAN_TX_TTL)
+			.map(|(h, _)| *h)
+			.collect();
+		for h in expired {
+			self.remove(&h);
+			self.evicted += 1;
+		}
+	}
+
+	// Drop the expired orphans, then the oldest ones until the pool fits the limits
+	fn evict(&mut self, now: Instant) {
+		if self.orphans.len() <= self.max_count && self.bytes <= self.max_bytes {
+			return;
+		}
+		self.evict_expired(now);
+		while self.orphans.len() > self.max_count || self.bytes > self.max_bytes {
+			let oldest = match self.orphans.iter().min_by_key(|(_, x)| x.added) {
+				Some((h, _)) => *h,
+				None => break,
+			};
+			self.remove(&oldest);
+			self.evicted += 1;
+		}
+	}
+
+	fn remove(&mut self, hash: &Hash) -> Option<OrphanTx> {
+		let orphan = self.orphans.remove(hash)?;
+		for commit in &orphan.missing {
+			if let Some(hs) = self.missing_idx.get_mut(commit) {
+				hs.retain(|h| h != hash);
+				if hs.is_empty() {
+					self.missing_idx.remove(commit);
+				}
+			}
+		}
+		self.bytes -= orphan.size;
+		Some(orphan)
+	}
+}
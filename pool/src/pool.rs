@@ -22,11 +22,13 @@ use self::core::core::transaction;
 use self::core::core::{
 	Block, BlockHeader, BlockSums, Committed, OutputIdentifier, Transaction, TxKernel, Weighting,
 };
-use crate::types::{BlockChain, PoolEntry, PoolError};
+use crate::types::{BlockChain, PoolConfig, PoolEntry, PoolError, TxPackage};
 use mwc_core as core;
+use mwc_util::secp::pedersen::Commitment;
 use mwc_util::secp::Secp256k1;
 use std::cmp::Reverse;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::slice;
 use std::sync::Arc;
 
 pub struct Pool<B>
@@ -172,6 +174,23 @@ where
 		extra_tx: Option<Transaction>,
 		header: &BlockHeader,
 		secp: &Secp256k1,
+	) -> Result<(), PoolError> {
+		self.validate_entry(&entry, extra_tx, header, secp)?;
+		// If we get here successfully then we can safely add the entry to the pool.
+		self.log_pool_add(&entry, header);
+		self.entries.push(entry);
+
+		Ok(())
+	}
+
+	/// Check that the entry can be added to the pool: it is not a duplicate and
+	/// the pool with the entry is valid against the chain state at the header.
+	pub fn validate_entry(
+		&self,
+		entry: &PoolEntry,
+		extra_tx: Option<Transaction>,
+		header: &BlockHeader,
+		secp: &Secp256k1,
 	) -> Result<(), PoolError> {
 		// Combine all the txs from the pool with any extra txs provided.
 		let mut txs = self.all_transactions();
@@ -198,10 +217,6 @@ where
 		// Validate aggregated tx (existing pool + new tx), ignoring tx weight limits.
 		// Validate against known chain state at the provided header.
 		self.validate_raw_tx(&agg_tx, header, Weighting::NoLimit, secp)?;
-		// If we get here successfully then we can safely add the entry to the pool.
-		self.log_pool_add(&entry, header);
-		self.entries.push(entry);
-
 		Ok(())
 	}
 
@@ -298,7 +313,27 @@ where
 			transaction::cut_through(&mut inputs[..], &mut outputs[..])?;
 
 		// Lookup remaining outputs to be spent from the current utxo.
-		let spent_utxo = self.blockchain.validate_inputs(&spent_utxo.into())?;
+		let spent_utxo = match self.blockchain.validate_inputs(&spent_utxo.into()) {
+			Ok(spent_utxo) => spent_utxo,
+			Err(e) => {
+				// Outputs that are neither in the pool nor in the utxo, the parent
+				// transaction may be not seen yet
+				let missing: Vec<Commitment> = spent_utxo
+					.iter()
+					.filter(|input| {
+						self.blockchain
+							.validate_inputs(&slice::from_ref(*input).into())
+							.is_err()
+					})
+					.map(|input| input.commitment())
+					.collect();
+				return Err(if missing.is_empty() {
+					e
+				} else {
+					PoolError::MissingInputs(missing)
+				});
+			}
+		};
 
 		Ok((spent_pool.to_vec(), spent_utxo))
 	}
@@ -343,12 +378,193 @@ where
 	// Use our bucket logic to identify the best transaction for eviction and evict it.
 	// We want to avoid evicting a transaction where another transaction depends on it.
 	// We want to evict a transaction with low fee_rate.
-	pub fn evict_transaction(&mut self, secp: &Secp256k1) {
-		if let Some(evictable_transaction) =
-			self.bucket_transactions(Weighting::NoLimit, secp).last()
-		{
-			self.entries.retain(|x| x.tx != *evictable_transaction);
-		};
+	/// Evict the lowest fee rate transactions until the pool fits `max_size` and
+	/// `max_weight`. Returns the evicted entries.
+	pub fn evict_over_capacity(&mut self, max_size: usize, max_weight: u64) -> Vec<PoolEntry> {
+		let mut size = self.size();
+		let mut weight = self.weight();
+		if size <= max_size && weight <= max_weight {
+			return vec![];
+		}
+
+		let height = self.blockchain.chain_head().map(|x| x.height).unwrap_or(0);
+		let mut evict = HashSet::new();
+		for idx in self.eviction_order(height) {
+			if size <= max_size && weight <= max_weight {
+				break;
+			}
+			size -= 1;
+			weight = weight.saturating_sub(self.entries[idx].tx.weight_size());
+			evict.insert(idx);
+		}
+
+		let mut evicted = vec![];
+		let mut idx = 0;
+		self.entries.retain(|x| {
+			let keep = !evict.contains(&idx);
+			if !keep {
+				evicted.push(x.clone());
+			}
+			idx += 1;
+			keep
+		});
+		evicted
+	}
+
+	/// Fee rate of the transaction that is evicted first, None for the empty pool.
+	pub fn eviction_floor(&self) -> Option<u64> {
+		let height = self.blockchain.chain_head().map(|x| x.height).unwrap_or(0);
+		self.eviction_order(height)
+			.first()
+			.map(|idx| self.entries[*idx].tx.fee_rate(height))
+	}
+
+	/// Indexes of the entries in the eviction order, lowest fee rate first. A transaction
+	/// is evicted only after all pool transactions that spend its outputs, so the rest of
+	/// the pool stays valid. With the same fee rate the newest is evicted first.
+	fn eviction_order(&self, height: u64) -> Vec<usize> {
+		let (parents, children) = self.dependencies(&self.output_index());
+		let mut children: Vec<usize> = children.iter().map(|x| x.len()).collect();
+
+		let fee_rates: Vec<u64> = self.entries.iter().map(|x| x.tx.fee_rate(height)).collect();
+		let mut evictable: BinaryHeap<_> = (0..self.entries.len())
+			.filter(|idx| children[*idx] == 0)
+			.map(|idx| Reverse((fee_rates[idx], Reverse(idx))))
+			.collect();
+
+		let mut order = Vec::with_capacity(self.entries.len());
+		while let Some(Reverse((_, Reverse(idx)))) = evictable.pop() {
+			order.push(idx);
+			for parent in &parents[idx] {
+				children[*parent] -= 1;
+				if children[*parent] == 0 {
+					evictable.push(Reverse((fee_rates[*parent], Reverse(*parent))));
+				}
+			}
+		}
+		order
+	}
+
+	/// Pool ancestors and descendants of every entry, in the entries order.
+	pub fn packages(&self) -> Vec<TxPackage> {
+		let (parents, children) = self.dependencies(&self.output_index());
+		let weights: Vec<u64> = self.entries.iter().map(|x| x.tx.weight_size()).collect();
+		(0..self.entries.len())
+			.map(|idx| {
+				let ancestors = Self::linked(&parents[idx], &parents);
+				let descendants = Self::linked(&children[idx], &children);
+				TxPackage {
+					ancestors: ancestors.len(),
+					ancestors_weight: ancestors.iter().map(|x| weights[*x]).sum(),
+					descendants: descendants.len(),
+					descendants_weight: descendants.iter().map(|x| weights[*x]).sum(),
+				}
+			})
+			.collect()
+	}
+
+	/// Check that the new transaction doesn't make the chains of the dependent pool
+	/// transactions longer than the configured limits, both for the transaction
+	/// ancestors and for the descendants of every ancestor.
+	pub fn check_package_limits(
+		&self,
+		tx: &Transaction,
+		config: &PoolConfig,
+	) -> Result<(), PoolError> {
+		let index = self.output_index();
+		let tx_inputs: Vec<_> = tx.inputs().into();
+		let tx_parents: Vec<usize> = tx_inputs
+			.iter()
+			.filter_map(|input| index.get(&input.commitment()).cloned())
+			.collect();
+		if tx_parents.is_empty() {
+			return Ok(());
+		}
+
+		let (parents, children) = self.dependencies(&index);
+		let ancestors = Self::linked(&tx_parents, &parents);
+		if ancestors.len() > config.max_ancestors {
+			return Err(PoolError::PackageLimit(format!(
+				"{} pool ancestors, limit is {}",
+				ancestors.len(),
+				config.max_ancestors
+			)));
+		}
+
+		let tx_weight = tx.weight_size();
+		let weight = |idx: &usize| self.entries[*idx].tx.weight_size();
+		let ancestors_weight = tx_weight + ancestors.iter().map(weight).sum::<u64>();
+		if ancestors_weight > config.max_ancestors_weight {
+			return Err(PoolError::PackageLimit(format!(
+				"weight with the pool ancestors {}, limit is {}",
+				ancestors_weight, config.max_ancestors_weight
+			)));
+		}
+
+		for idx in &ancestors {
+			let descendants = Self::linked(&children[*idx], &children);
+			if descendants.len() + 1 > config.max_descendants {
+				return Err(PoolError::PackageLimit(format!(
+					"pool transaction {} would have {} descendants, limit is {}",
+					self.entries[*idx].tx.hash(),
+					descendants.len() + 1,
+					config.max_descendants
+				)));
+			}
+			let descendants_weight =
+				weight(idx) + tx_weight + descendants.iter().map(weight).sum::<u64>();
+			if descendants_weight > config.max_descendants_weight {
+				return Err(PoolError::PackageLimit(format!(
+					"pool transaction {} weight with the descendants would be {}, limit is {}",
+					self.entries[*idx].tx.hash(),
+					descendants_weight,
+					config.max_descendants_weight
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	// Index of the entry that created the output
+	fn output_index(&self) -> HashMap<Commitment, usize> {
+		let mut index = HashMap::new();
+		for (idx, entry) in self.entries.iter().enumerate() {
+			for out in entry.tx.outputs() {
+				index.insert(out.commitment(), idx);
+			}
+		}
+		index
+	}
+
+	// Pool parents and pool children of every entry
+	fn dependencies(
+		&self,
+		index: &HashMap<Commitment, usize>,
+	) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+		let mut parents: Vec<Vec<usize>> = vec![vec![]; self.entries.len()];
+		let mut children: Vec<Vec<usize>> = vec![vec![]; self.entries.len()];
+		for (idx, entry) in self.entries.iter().enumerate() {
+			let tx_inputs: Vec<_> = entry.tx.inputs().into();
+			for input in tx_inputs {
+				if let Some(parent) = index.get(&input.commitment()) {
+					parents[idx].push(*parent);
+					children[*parent].push(idx);
+				}
+			}
+		}
+		(parents, children)
+	}
+
+	// The start entries and all entries reachable from them by the links
+	fn linked(start: &[usize], links: &[Vec<usize>]) -> HashSet<usize> {
+		let mut found = HashSet::new();
+		let mut stack = start.to_vec();
+		while let Some(idx) = stack.pop() {
+			if found.insert(idx) {
+				stack.extend_from_slice(&links[idx]);
+			}
+		}
+		found
 	}
 
 	/// Buckets consist of a vec of txs and track the aggregate fee_rate.
@@ -497,6 +713,11 @@ where
 		self.entries.len()
 	}
 
+	/// Total weight of the transactions in the pool.
+	pub fn weight(&self) -> u64 {
+		self.entries.iter().map(|x| x.tx.weight_size()).sum()
+	}
+
 	/// Number of transaction kernels in the pool.
 	/// This may differ from the size (number of transactions) due to tx aggregation.
 	pub fn kernel_count(&self) -> usize {
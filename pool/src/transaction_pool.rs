@@ -25,6 +25,7 @@ use self::core::core::{
 };
 use self::core::global;
 use self::util::RwLock;
+use crate::orphans::OrphanTxPool;
 use crate::pool::Pool;
 use crate::types::{BlockChain, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource};
 use chrono::prelude::*;
@@ -33,10 +34,12 @@ use mwc_core as core;
 use mwc_core::ser;
 use mwc_keychain::base58;
 use mwc_util as util;
+use mwc_util::secp::pedersen::Commitment;
 use mwc_util::secp::Secp256k1;
 use std::collections::VecDeque;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// Transaction pool implementation.
 pub struct TransactionPool<B, P>
@@ -58,6 +61,8 @@ where
 	pub adapter: Arc<P>,
 	///the replay attack cache
 	pub replay_verifier_cache: Arc<RwLock<LruCache<[u8; 32], ()>>>,
+	/// Relayed transactions that wait for their parents
+	pub orphans: OrphanTxPool,
 }
 
 impl<B, P> TransactionPool<B, P>
@@ -68,7 +73,6 @@ where
 	/// Create a new transaction pool
 	pub fn new(config: PoolConfig, chain: Arc<B>, adapter: Arc<P>) -> Self {
 		TransactionPool {
-			config,
 			txpool: Pool::new(chain.clone(), "txpool".to_string()),
 			stempool: Pool::new(chain.clone(), "stempool".to_string()),
 			reorg_cache: Arc::new(RwLock::new(VecDeque::new())),
@@ -77,6 +81,8 @@ where
 			replay_verifier_cache: Arc::new(RwLock::new(LruCache::new(
 				NonZeroUsize::new(1000).unwrap(),
 			))),
+			orphans: OrphanTxPool::new(config.max_orphan_txs, config.max_orphan_bytes),
+			config,
 		}
 	}
 
@@ -157,6 +163,8 @@ where
 
 	/// Add the given tx to the pool, directing it to either the stempool or
 	/// txpool based on stem flag provided.
+	/// Fluffed transaction from the peer that spends the outputs that are not known yet
+	/// is kept as an orphan and is added when its parent arrives.
 	pub fn add_to_pool(
 		&mut self,
 		src: TxSource,
@@ -164,17 +172,114 @@ where
 		stem: bool,
 		header: &BlockHeader,
 		secp: &Secp256k1,
+	) -> Result<(), PoolError> {
+		let outputs: Vec<Commitment> = tx.outputs().iter().map(|x| x.commitment()).collect();
+		let orphan = if src == TxSource::Broadcast && !stem {
+			Some(tx.clone())
+		} else {
+			None
+		};
+
+		match self.add_tx(src, tx, stem, header, secp) {
+			Ok(_) => {
+				self.add_orphans(outputs, header, secp);
+				Ok(())
+			}
+			Err(PoolError::MissingInputs(missing)) => {
+				if let Some(tx) = orphan {
+					debug!("Transaction {} is orphan, missing {:?}", tx.hash(), missing);
+					self.orphans.add(PoolEntry::new(tx, src), missing.clone());
+				}
+				Err(PoolError::MissingInputs(missing))
+			}
+			Err(e) => Err(e),
+		}
+	}
+
+	// Add the orphans that wait for the outputs, and then their own orphans
+	fn add_orphans(&mut self, outputs: Vec<Commitment>, header: &BlockHeader, secp: &Secp256k1) {
+		let mut outputs = outputs;
+		while !outputs.is_empty() && !self.orphans.is_empty() {
+			let ready = self.orphans.remove_by_outputs(&outputs);
+			outputs = vec![];
+			for orphan in ready {
+				let tx = orphan.entry.tx;
+				let tx_hash = tx.hash();
+				let tx_outputs: Vec<Commitment> =
+					tx.outputs().iter().map(|x| x.commitment()).collect();
+				match self.add_tx(orphan.entry.src, tx.clone(), false, header, secp) {
+					Ok(_) => {
+						debug!("Orphan transaction {} is added to the pool", tx_hash);
+						outputs.extend(tx_outputs);
+					}
+					// Still waiting for the other parents
+					Err(PoolError::MissingInputs(missing)) => {
+						self.orphans
+							.add(PoolEntry::new(tx, orphan.entry.src), missing);
+					}
+					Err(e) => debug!("Orphan transaction {} is rejected, {}", tx_hash, e),
+				}
+			}
+		}
+	}
+
+	fn add_tx(
+		&mut self,
+		src: TxSource,
+		tx: Transaction,
+		stem: bool,
+		header: &BlockHeader,
+		secp: &Secp256k1,
 	) -> Result<(), PoolError> {
 		// Quick check for duplicate txs.
 		// Our stempool is private and we do not want to reveal anything about the txs contained.
 		// If this is a stem tx and is already present in stempool then fluff by adding to txpool.
 		// Otherwise if already present in txpool return a "duplicate tx" error.
 		if stem && self.stempool.contains_tx(&tx) {
-			return self.add_to_pool(src, tx, false, header, secp);
+			return self.add_tx(src, tx, false, header, secp);
 		} else if self.txpool.contains_tx(&tx) {
 			return Err(PoolError::DuplicateTx);
 		}
 
+		let (entry, extra_tx) = self.prepare_entry(src, tx, stem, header, secp)?;
+		let entry = &entry;
+
+		// If this is a stem tx then attempt to add it to stempool.
+		// If the adapter fails to accept the new stem tx then fallback to fluff via txpool.
+		if stem {
+			self.add_to_stempool(entry, header, extra_tx, secp)?;
+			if self.adapter.stem_tx_accepted(entry).is_ok() {
+				return Ok(());
+			}
+		}
+
+		// Add tx to txpool.
+		self.add_to_txpool(entry, header, secp)?;
+
+		// Transaction passed all the checks but we may have to make space for it.
+		// It is relayed only if it stays in the pool, the children are evicted before
+		// their parents, so the child of a low fee parent can be evicted right away.
+		let evicted = self.evict_from_txpool();
+		if evicted.iter().any(|x| x.tx.kernels() == entry.tx.kernels()) {
+			return Err(PoolError::OverCapacity);
+		}
+
+		self.add_to_reorg_cache(entry);
+		self.adapter.tx_accepted(entry, header.height);
+
+		Ok(())
+	}
+
+	// Run the checks of the new transaction that don't depend on the pool it goes to,
+	// returns the entry to add and the txpool aggregate that the stem tx is validated with.
+	fn prepare_entry(
+		&self,
+		src: TxSource,
+		tx: Transaction,
+		stem: bool,
+		header: &BlockHeader,
+		secp: &Secp256k1,
+	) -> Result<(PoolEntry, Option<Transaction>), PoolError> {
 		// Attempt to deaggregate the tx if not stem tx.
 		let entry = if stem {
 			PoolEntry::new(tx, src)
@@ -188,12 +293,13 @@ where
 		self.verify_kernel_variants(tx, header)?;
 
 		// Does this transaction pay the required fees and fit within the pool capacity?
-		let acceptability = self.is_acceptable(tx, stem);
-		let mut evict = false;
-		if !stem && acceptability.as_ref().err() == Some(&PoolError::OverCapacity) {
-			evict = true;
-		} else if acceptability.is_err() {
-			return acceptability;
+		self.is_acceptable(tx, stem)?;
+
+		// Does it keep the chains of the dependent pool transactions within the limits?
+		if stem {
+			self.stempool.check_package_limits(tx, &self.config)?;
+		} else {
+			self.txpool.check_package_limits(tx, &self.config)?;
 		}
 
 		// Make sure the transaction is valid before anything else.
@@ -247,28 +353,33 @@ where
 			.verify_coinbase_maturity(&coinbase_inputs.as_slice().into())?;
 
 		// Convert the tx to "v2" compatibility with "features and commit" inputs.
-		let ref entry = self.convert_tx_v2(entry, &spent_pool, &spent_utxo, secp)?;
+		let entry = self.convert_tx_v2(entry, &spent_pool, &spent_utxo, secp)?;
+		Ok((entry, extra_tx))
+	}
 
-		// If this is a stem tx then attempt to add it to stempool.
-		// If the adapter fails to accept the new stem tx then fallback to fluff via txpool.
-		if stem {
-			self.add_to_stempool(entry, header, extra_tx, secp)?;
-			if self.adapter.stem_tx_accepted(entry).is_ok() {
-				return Ok(());
-			}
+	/// Run all the checks of `add_to_pool` without adding the transaction to the pool
+	/// and without relaying it. Useful for the wallets to validate the transaction
+	/// before it is pushed.
+	pub fn check_transaction(
+		&self,
+		src: TxSource,
+		tx: Transaction,
+		stem: bool,
+		header: &BlockHeader,
+		secp: &Secp256k1,
+	) -> Result<(), PoolError> {
+		// Same as add_to_pool, stem tx that is already in the stempool would be fluffed
+		let stem = stem && !self.stempool.contains_tx(&tx);
+		if self.txpool.contains_tx(&tx) {
+			return Err(PoolError::DuplicateTx);
 		}
 
-		// Add tx to txpool.
-		self.add_to_txpool(entry, header, secp)?;
-		self.add_to_reorg_cache(entry);
-		self.adapter.tx_accepted(entry, header.height);
-
-		// Transaction passed all the checks but we have to make space for it
-		if evict {
-			self.evict_from_txpool(secp);
+		let (entry, extra_tx) = self.prepare_entry(src, tx, stem, header, secp)?;
+		if stem {
+			self.stempool.validate_entry(&entry, extra_tx, header, secp)
+		} else {
+			self.txpool.validate_entry(&entry, None, header, secp)
 		}
-
-		Ok(())
 	}
 
 	/// Convert a transaction for v2 compatibility.
@@ -305,11 +416,33 @@ where
 		Ok(PoolEntry::new(tx, entry.src))
 	}
 
-	// Evict a transaction from the txpool.
-	// Uses bucket logic to identify the "last" transaction.
-	// No other tx depends on it and it has low fee_rate
-	pub fn evict_from_txpool(&mut self, secp: &Secp256k1) {
-		self.txpool.evict_transaction(secp)
+	// Evict the lowest fee rate transactions from the txpool until it fits the configured
+	// size and weight. Transactions are evicted after their descendants.
+	pub fn evict_from_txpool(&mut self) -> Vec<PoolEntry> {
+		let evicted = self
+			.txpool
+			.evict_over_capacity(self.config.max_pool_size, self.config.max_pool_weight);
+		if !evicted.is_empty() {
+			debug!(
+				"evict_from_txpool: evicted {} transactions, pool size {}, weight {}",
+				evicted.len(),
+				self.txpool.size(),
+				self.txpool.weight()
+			);
+		}
+		evicted
+	}
+
+	/// Whether the txpool is at its size or weight capacity. New transactions
+	/// need a fee rate above the eviction floor then. The stempool has its own limit.
+	pub fn is_full(&self) -> bool {
+		self.txpool.size() >= self.config.max_pool_size
+			|| self.txpool.weight() >= self.config.max_pool_weight
+	}
+
+	/// Fee rate of the txpool transaction that is evicted first, None for the empty pool.
+	pub fn eviction_floor(&self) -> Option<u64> {
+		self.txpool.eviction_floor()
 	}
 
 	// Old txs will "age out" after 30 mins.
@@ -379,6 +512,11 @@ where
 			self.stempool.reconcile(txpool_tx, &block.header, secp)?;
 		}
 
+		// The block may have the parents of the orphans
+		self.orphans.evict_expired(Instant::now());
+		let outputs: Vec<Commitment> = block.outputs().iter().map(|x| x.commitment()).collect();
+		self.add_orphans(outputs, &block.header, secp);
+
 		if log_enabled!(log::Level::Debug) {
 			debug!("---------------- AFTER START --------------");
 			let reorg_cache = self.reorg_cache.read();
@@ -417,14 +555,8 @@ where
 	/// Whether the transaction is acceptable to the pool, given both how
 	/// full the pool is and the transaction weight.
 	fn is_acceptable(&self, tx: &Transaction, stem: bool) -> Result<(), PoolError> {
-		if self.total_size() > self.config.max_pool_size {
-			return Err(PoolError::OverCapacity);
-		}
-
 		// Check that the stempool can accept this transaction
-		if stem && self.stempool.size() > self.config.max_stempool_size
-			|| self.total_size() > self.config.max_pool_size
-		{
+		if stem && self.stempool.size() > self.config.max_stempool_size {
 			return Err(PoolError::OverCapacity);
 		}
 
@@ -435,6 +567,19 @@ where
 		if tx.shifted_fee(header.height) < tx.accept_fee(header.height) {
 			return Err(PoolError::LowFeeTransaction(tx.shifted_fee(header.height)));
 		}
+
+		// Full pool makes space by the eviction, the new transaction has to outbid
+		// the transaction that would be evicted
+		if self.is_full() {
+			if stem {
+				return Err(PoolError::OverCapacity);
+			}
+			if let Some(floor) = self.eviction_floor() {
+				if tx.fee_rate(header.height) <= floor {
+					return Err(PoolError::OverCapacity);
+				}
+			}
+		}
 		Ok(())
 	}
 
@@ -444,6 +589,11 @@ where
 		self.txpool.size()
 	}
 
+	/// Get the total weight of the txpool transactions.
+	pub fn total_weight(&self) -> u64 {
+		self.txpool.weight()
+	}
+
 	/// Returns a vector of transactions from the txpool so we can build a
 	/// block from them.
 	pub fn prepare_mineable_transactions(
@@ -25,6 +25,7 @@ use self::core::core::{BlockHeader, BlockSums, Inputs, OutputIdentifier};
 use chrono::prelude::*;
 use mwc_core as core;
 use mwc_keychain as keychain;
+use mwc_util::secp::pedersen::Commitment;
 
 /// Dandelion "epoch" length.
 const DANDELION_EPOCH_SECS: u16 = 600;
@@ -119,6 +120,37 @@ pub struct PoolConfig {
 	#[serde(default = "default_max_stempool_size")]
 	pub max_stempool_size: usize,
 
+	/// Maximum total weight of the transactions in the pool. The lowest fee rate
+	/// transactions are evicted above it.
+	#[serde(default = "default_max_pool_weight")]
+	pub max_pool_weight: u64,
+
+	/// Maximum number of the pool transactions that the transaction depends on,
+	/// directly or through the other pool transactions
+	#[serde(default = "default_max_ancestors")]
+	pub max_ancestors: usize,
+
+	/// Maximum weight of the transaction with its pool ancestors
+	#[serde(default = "default_max_package_weight")]
+	pub max_ancestors_weight: u64,
+
+	/// Maximum number of the pool transactions that depend on a pool transaction
+	#[serde(default = "default_max_descendants")]
+	pub max_descendants: usize,
+
+	/// Maximum weight of the pool transaction with its pool descendants
+	#[serde(default = "default_max_package_weight")]
+	pub max_descendants_weight: u64,
+
+	/// Maximum number of the orphan transactions, the ones that spend the outputs
+	/// of the transactions that were not seen yet
+	#[serde(default = "default_max_orphan_txs")]
+	pub max_orphan_txs: usize,
+
+	/// Maximum total serialized size of the orphan transactions in bytes
+	#[serde(default = "default_max_orphan_bytes")]
+	pub max_orphan_bytes: usize,
+
 	/// Maximum total weight of transactions that can get selected to build a
 	/// block from. Allows miners to restrict the maximum weight of their
 	/// blocks.
@@ -133,6 +165,13 @@ impl Default for PoolConfig {
 			max_pool_size: default_max_pool_size(),
 			reorg_cache_timeout: default_reorg_cache_timeout(),
 			max_stempool_size: default_max_stempool_size(),
+			max_pool_weight: default_max_pool_weight(),
+			max_ancestors: default_max_ancestors(),
+			max_ancestors_weight: default_max_package_weight(),
+			max_descendants: default_max_descendants(),
+			max_descendants_weight: default_max_package_weight(),
+			max_orphan_txs: default_max_orphan_txs(),
+			max_orphan_bytes: default_max_orphan_bytes(),
 			mineable_max_weight: default_mineable_max_weight(),
 		}
 	}
@@ -151,6 +190,25 @@ fn default_reorg_cache_timeout() -> i64 {
 fn default_max_stempool_size() -> usize {
 	50_000
 }
+fn default_max_pool_weight() -> u64 {
+	// A day of full blocks
+	1440 * consensus::MAX_BLOCK_WEIGHT
+}
+fn default_max_ancestors() -> usize {
+	25
+}
+fn default_max_descendants() -> usize {
+	25
+}
+fn default_max_package_weight() -> u64 {
+	consensus::MAX_BLOCK_WEIGHT / 4
+}
+fn default_max_orphan_txs() -> usize {
+	500
+}
+fn default_max_orphan_bytes() -> usize {
+	5_000_000
+}
 fn default_mineable_max_weight() -> u64 {
 	consensus::MAX_BLOCK_WEIGHT
 }
@@ -177,6 +235,16 @@ impl PoolEntry {
 	}
 }
 
+/// Pool transactions that the entry depends on (ancestors) and the ones that depend
+/// on it (descendants). The entry itself is not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TxPackage {
+	pub ancestors: usize,
+	pub ancestors_weight: u64,
+	pub descendants: usize,
+	pub descendants_weight: u64,
+}
+
 /// Used to make decisions based on transaction acceptance priority from
 /// various sources. For example, a node may want to bypass pool size
 /// restrictions when accepting a transaction from a local wallet.
@@ -190,6 +258,8 @@ pub enum TxSource {
 	Fluff,
 	EmbargoExpired,
 	Deaggregate,
+	/// Synthetic load test transaction of the node tx generator
+	Generator,
 }
 
 impl TxSource {
@@ -200,6 +270,14 @@ impl TxSource {
 			_ => false,
 		}
 	}
+
+	/// Transaction is created by this node, pushed via the api or by the tx generator
+	pub fn is_local(&self) -> bool {
+		match self {
+			TxSource::PushApi | TxSource::Generator => true,
+			_ => false,
+		}
+	}
 }
 
 /// Possible errors when interacting with the transaction pool.
@@ -251,6 +329,12 @@ pub enum PoolError {
 	/// NRD kernels are not valid if relative_height rule not met.
 	#[error("NRD kernel relative height")]
 	NRDKernelRelativeHeight,
+	/// Spent outputs are neither in the pool nor in the chain
+	#[error("Tx Pool Missing inputs {0:?}")]
+	MissingInputs(Vec<Commitment>),
+	/// Transaction makes too long chain of the dependent pool transactions
+	#[error("Tx Pool Package limit exceeded, {0}")]
+	PackageLimit(String),
 	/// Other kinds of error (not yet pulled out into meaningful errors).
 	#[error("Tx Pool General error {0}")]
 	Other(String),
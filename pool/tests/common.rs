@@ -195,6 +195,13 @@ where
 			reorg_cache_timeout: 1_440,
 			max_pool_size: 50,
 			max_stempool_size: 50,
+			max_pool_weight: 1_000_000,
+			max_ancestors: 25,
+			max_ancestors_weight: 100_000,
+			max_descendants: 25,
+			max_descendants_weight: 100_000,
+			max_orphan_txs: 50,
+			max_orphan_bytes: 1_000_000,
 			mineable_max_weight: 10_000,
 		},
 		chain.clone(),
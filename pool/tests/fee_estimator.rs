@@ -0,0 +1,64 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the fee recommendation.
+
+use mwc_pool::fee_estimator::{estimate_fee_base, BlockFeeSample, PoolTxFee};
+
+fn tx(fee_base: u64, weight: u64) -> PoolTxFee {
+	PoolTxFee { fee_base, weight }
+}
+
+fn block(utilization: f64, fee_base: Option<u64>) -> BlockFeeSample {
+	BlockFeeSample {
+		utilization,
+		fee_base,
+	}
+}
+
+#[test]
+fn test_fee_estimate() {
+	let min_fee_base = 1000;
+	let quiet = vec![block(0.1, Some(1000)), block(0.0, None)];
+
+	// Empty pool, the minimal fee is enough
+	assert_eq!(estimate_fee_base(&[], &quiet, 100, 1, min_fee_base), 1000);
+
+	// The pool doesn't fill the block
+	let pool = vec![tx(5000, 30), tx(3000, 30), tx(2000, 30)];
+	assert_eq!(estimate_fee_base(&pool, &quiet, 100, 1, min_fee_base), 1000);
+
+	// The pool fills the next block, need to outbid the transaction at the block limit
+	let pool = vec![tx(2000, 40), tx(5000, 40), tx(3000, 40), tx(1500, 40)];
+	assert_eq!(estimate_fee_base(&pool, &quiet, 100, 1, min_fee_base), 2001);
+	// Two blocks fit the whole pool
+	assert_eq!(estimate_fee_base(&pool, &quiet, 100, 2, min_fee_base), 1000);
+	// Two smaller blocks fit all but the cheapest one
+	assert_eq!(estimate_fee_base(&pool, &quiet, 80, 2, min_fee_base), 1501);
+
+	// Full recent blocks, the fee is not below their median fee base
+	let congested = vec![
+		block(0.95, Some(4000)),
+		block(0.98, Some(6000)),
+		block(0.9, Some(5000)),
+	];
+	assert_eq!(
+		estimate_fee_base(&[], &congested, 100, 1, min_fee_base),
+		5000
+	);
+	assert_eq!(
+		estimate_fee_base(&pool, &congested, 100, 1, min_fee_base),
+		5000
+	);
+}
@@ -0,0 +1,143 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::orphans::{OrphanTxPool, ORPHAN_TX_TTL};
+use self::pool::{PoolEntry, PoolError, TxSource};
+use crate::common::*;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_pool as pool;
+use mwc_util as util;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Relayed transactions that arrive before their parents are added with the parents.
+#[test]
+fn test_orphan_transactions() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	global::set_local_accept_fee_base(1);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.orphan_txs";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+
+	let mut pool = init_transaction_pool(Arc::new(ChainAdapter {
+		chain: chain.clone(),
+	}));
+
+	add_some_blocks(&chain, 4 * 3, &keychain);
+	let header = chain.head_header().unwrap();
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![500, 600, 1000]);
+	let tx_a = test_transaction(&keychain, vec![1000], vec![950]);
+	let tx_b = test_transaction(&keychain, vec![950], vec![900]);
+
+	// Grandchild and child arrive first
+	match pool.add_to_pool(
+		TxSource::Broadcast,
+		tx_b.clone(),
+		false,
+		&header,
+		chain.secp(),
+	) {
+		Err(PoolError::MissingInputs(missing)) => assert_eq!(missing.len(), 1),
+		res => panic!("Expected missing inputs, got {:?}", res),
+	}
+	assert!(pool
+		.add_to_pool(
+			TxSource::Broadcast,
+			tx_a.clone(),
+			false,
+			&header,
+			chain.secp()
+		)
+		.is_err());
+	assert_eq!(pool.orphans.len(), 2);
+	assert_eq!(pool.total_size(), 0);
+
+	// Pushed transactions are not kept
+	let tx_c = test_transaction(&keychain, vec![600], vec![550]);
+	assert!(pool
+		.add_to_pool(TxSource::PushApi, tx_c, false, &header, chain.secp())
+		.is_err());
+	assert_eq!(pool.orphans.len(), 2);
+
+	// The parent brings both orphans
+	pool.add_to_pool(
+		TxSource::Broadcast,
+		initial_tx.clone(),
+		false,
+		&header,
+		chain.secp(),
+	)
+	.unwrap();
+	assert_eq!(pool.total_size(), 3);
+	assert!(pool.txpool.contains_tx(&tx_a));
+	assert!(pool.txpool.contains_tx(&tx_b));
+	assert!(pool.orphans.is_empty());
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
+
+/// Orphans are limited by the number, the size and the time.
+#[test]
+fn test_orphan_pool_limits() {
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+	let txs: Vec<_> = (0..3)
+		.map(|i| test_transaction(&keychain, vec![1000 + i], vec![900 + i]))
+		.collect();
+	let missing = |i: usize| vec![txs[i].outputs()[0].commitment()];
+
+	let mut orphans = OrphanTxPool::new(2, 1_000_000);
+	let now = Instant::now();
+	for i in 0..3 {
+		assert!(orphans.add_at(
+			PoolEntry::new(txs[i].clone(), TxSource::Broadcast),
+			missing(i),
+			now + Duration::from_secs(i as u64),
+		));
+	}
+	// The oldest is evicted
+	assert_eq!(orphans.len(), 2);
+	assert_eq!(orphans.evicted(), 1);
+	assert!(!orphans.contains(&txs[0].hash()));
+
+	let ready = orphans.remove_by_outputs(&missing(1));
+	assert_eq!(ready.len(), 1);
+	assert_eq!(ready[0].entry.tx, txs[1]);
+	assert_eq!(orphans.len(), 1);
+
+	orphans.evict_expired(now + ORPHAN_TX_TTL + Duration::from_secs(2));
+	assert!(orphans.is_empty());
+	assert_eq!(orphans.bytes(), 0);
+
+	// Too large transactions are not kept
+	let mut orphans = OrphanTxPool::new(2, 10);
+	assert!(!orphans.add(
+		PoolEntry::new(txs[0].clone(), TxSource::Broadcast),
+		missing(0)
+	));
+}
@@ -0,0 +1,112 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolError;
+use crate::common::*;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_pool as pool;
+use mwc_util as util;
+use std::sync::Arc;
+
+/// Dry run reports the same errors as add_to_pool and doesn't change the pool.
+#[test]
+fn test_pool_check_transaction() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	global::set_local_accept_fee_base(1);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.pool_check";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+
+	let mut pool = init_transaction_pool(Arc::new(ChainAdapter {
+		chain: chain.clone(),
+	}));
+
+	add_some_blocks(&chain, 4 * 3, &keychain);
+	let header = chain.head_header().unwrap();
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![500, 600]);
+
+	// Valid transaction passes the check and is not added
+	pool.check_transaction(
+		test_source(),
+		initial_tx.clone(),
+		false,
+		&header,
+		chain.secp(),
+	)
+	.unwrap();
+	assert_eq!(pool.total_size(), 0);
+	assert_eq!(pool.stempool.size(), 0);
+
+	// Transaction spending the pool output is checked against the pool
+	let tx_a = test_transaction(&keychain, vec![600], vec![550]);
+	assert!(pool
+		.check_transaction(test_source(), tx_a.clone(), false, &header, chain.secp())
+		.is_err());
+
+	pool.add_to_pool(
+		test_source(),
+		initial_tx.clone(),
+		false,
+		&header,
+		chain.secp(),
+	)
+	.unwrap();
+	pool.check_transaction(test_source(), tx_a.clone(), false, &header, chain.secp())
+		.unwrap();
+	assert_eq!(pool.total_size(), 1);
+
+	// Already in the pool
+	assert_eq!(
+		pool.check_transaction(
+			test_source(),
+			initial_tx.clone(),
+			false,
+			&header,
+			chain.secp()
+		),
+		Err(PoolError::DuplicateTx)
+	);
+
+	// Double spend of the pool output
+	pool.add_to_pool(test_source(), tx_a.clone(), false, &header, chain.secp())
+		.unwrap();
+	let tx_b = test_transaction(&keychain, vec![600], vec![500]);
+	assert!(pool
+		.check_transaction(test_source(), tx_b, false, &header, chain.secp())
+		.is_err());
+
+	// Fee below the pool minimum
+	global::set_local_accept_fee_base(1_000_000);
+	let tx_low = test_transaction(&keychain, vec![500], vec![499]);
+	match pool.check_transaction(test_source(), tx_low, false, &header, chain.secp()) {
+		Err(PoolError::LowFeeTransaction(_)) => {}
+		res => panic!("Expected low fee error, got {:?}", res),
+	}
+	assert_eq!(pool.total_size(), 2);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
@@ -0,0 +1,118 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolError;
+use crate::common::*;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_pool as pool;
+use mwc_util as util;
+use std::sync::Arc;
+
+/// Lowest fee rate transactions are evicted first, children before their parents.
+#[test]
+fn test_pool_eviction() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	global::set_local_accept_fee_base(1);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.pool_eviction";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+
+	let mut pool = init_transaction_pool(Arc::new(ChainAdapter {
+		chain: chain.clone(),
+	}));
+
+	add_some_blocks(&chain, 4 * 3, &keychain);
+	let header = chain.head_header().unwrap();
+	let height = header.height;
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(
+		&keychain,
+		&header_1,
+		vec![500, 600, 700, 800, 900, 1000, 1100, 1200, 1300, 1400],
+	);
+	let tx_a = test_transaction(&keychain, vec![1400], vec![400]);
+	let tx_b = test_transaction(&keychain, vec![1300], vec![801]);
+	// tx_c spends the output of tx_b and pays the lowest fee
+	let tx_c = test_transaction(&keychain, vec![801], vec![701]);
+
+	for tx in vec![&initial_tx, &tx_a, &tx_b, &tx_c] {
+		pool.add_to_pool(test_source(), tx.clone(), false, &header, chain.secp())
+			.unwrap();
+	}
+	assert_eq!(pool.total_size(), 4);
+	assert!(!pool.is_full());
+
+	// tx_b pays less than tx_a, but tx_c depends on it and goes first
+	pool.config.max_pool_size = 4;
+	assert!(pool.is_full());
+	assert_eq!(pool.eviction_floor(), Some(tx_c.fee_rate(height)));
+
+	// Full pool doesn't take the transaction that doesn't outbid the floor
+	let tx_low = test_transaction(&keychain, vec![1100], vec![1050]);
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx_low, false, &header, chain.secp()),
+		Err(PoolError::OverCapacity)
+	);
+	assert_eq!(pool.total_size(), 4);
+
+	// Child of the floor transaction outbids it, but the children are evicted before
+	// their parents, so it would be evicted right away. It is rejected, the pool is unchanged
+	let tx_e = test_transaction(&keychain, vec![701], vec![450]);
+	assert!(tx_e.fee_rate(height) > tx_c.fee_rate(height));
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx_e.clone(), false, &header, chain.secp()),
+		Err(PoolError::OverCapacity)
+	);
+	assert!(!pool.txpool.contains_tx(&tx_e));
+	assert!(pool.txpool.contains_tx(&tx_c));
+	assert_eq!(pool.total_size(), 4);
+
+	// Higher fee rate transaction gets into the pool, tx_c is evicted
+	let tx_d = test_transaction(&keychain, vec![1200], vec![99]);
+	pool.add_to_pool(test_source(), tx_d.clone(), false, &header, chain.secp())
+		.unwrap();
+	assert_eq!(pool.total_size(), 4);
+	assert!(!pool.txpool.contains_tx(&tx_c));
+	assert!(pool.txpool.contains_tx(&tx_b));
+	assert_eq!(pool.eviction_floor(), Some(tx_b.fee_rate(height)));
+
+	// Shrinking the pool evicts tx_b then tx_a, the parent stays while it has children
+	pool.config.max_pool_size = 2;
+	pool.evict_from_txpool();
+	assert_eq!(pool.total_size(), 2);
+	assert!(pool.txpool.contains_tx(&initial_tx));
+	assert!(pool.txpool.contains_tx(&tx_d));
+
+	// Weight budget works the same way
+	pool.config.max_pool_size = 50;
+	pool.config.max_pool_weight = initial_tx.weight_size();
+	pool.evict_from_txpool();
+	assert_eq!(pool.total_size(), 1);
+	assert!(pool.txpool.contains_tx(&initial_tx));
+	assert_eq!(pool.total_weight(), initial_tx.weight_size());
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
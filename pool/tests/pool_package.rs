@@ -0,0 +1,112 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{PoolError, TxPackage};
+use crate::common::*;
+use mwc_core as core;
+use mwc_keychain as keychain;
+use mwc_pool as pool;
+use mwc_util as util;
+use std::sync::Arc;
+
+/// Chains of the dependent transactions are tracked and limited.
+#[test]
+fn test_pool_package_limits() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	global::set_local_accept_fee_base(1);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.pool_package";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+
+	let mut pool = init_transaction_pool(Arc::new(ChainAdapter {
+		chain: chain.clone(),
+	}));
+
+	add_some_blocks(&chain, 4 * 3, &keychain);
+	let header = chain.head_header().unwrap();
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![500, 600, 1000]);
+	let tx_a = test_transaction(&keychain, vec![1000], vec![950]);
+	let tx_b = test_transaction(&keychain, vec![950], vec![900]);
+	let tx_c = test_transaction(&keychain, vec![900], vec![850]);
+	let tx_d = test_transaction(&keychain, vec![600], vec![550]);
+
+	for tx in vec![&initial_tx, &tx_a, &tx_b, &tx_d] {
+		pool.add_to_pool(test_source(), tx.clone(), false, &header, chain.secp())
+			.unwrap();
+	}
+
+	let weight_a = tx_a.weight_size();
+	let weight_b = tx_b.weight_size();
+	let packages = pool.txpool.packages();
+	assert_eq!(
+		packages[0],
+		TxPackage {
+			ancestors: 0,
+			ancestors_weight: 0,
+			descendants: 3,
+			descendants_weight: weight_a + weight_b + tx_d.weight_size(),
+		}
+	);
+	assert_eq!(packages[2].ancestors, 2);
+	assert_eq!(
+		packages[2].ancestors_weight,
+		initial_tx.weight_size() + weight_a
+	);
+	assert_eq!(packages[2].descendants, 0);
+	assert_eq!(packages[3].ancestors, 1);
+
+	// tx_c would have 3 ancestors
+	pool.config.max_ancestors = 2;
+	match pool.add_to_pool(test_source(), tx_c.clone(), false, &header, chain.secp()) {
+		Err(PoolError::PackageLimit(_)) => {}
+		res => panic!("Expected package limit error, got {:?}", res),
+	}
+
+	// initial_tx would have 4 descendants
+	pool.config.max_ancestors = 25;
+	pool.config.max_descendants = 3;
+	match pool.add_to_pool(test_source(), tx_c.clone(), false, &header, chain.secp()) {
+		Err(PoolError::PackageLimit(_)) => {}
+		res => panic!("Expected package limit error, got {:?}", res),
+	}
+
+	// Weight of tx_c with its ancestors is above the limit
+	pool.config.max_descendants = 25;
+	pool.config.max_ancestors_weight = initial_tx.weight_size() + weight_a + weight_b;
+	match pool.add_to_pool(test_source(), tx_c.clone(), false, &header, chain.secp()) {
+		Err(PoolError::PackageLimit(_)) => {}
+		res => panic!("Expected package limit error, got {:?}", res),
+	}
+	assert_eq!(pool.total_size(), 4);
+
+	pool.config.max_ancestors_weight = 100_000;
+	pool.add_to_pool(test_source(), tx_c.clone(), false, &header, chain.secp())
+		.unwrap();
+	assert_eq!(pool.total_size(), 5);
+	assert_eq!(pool.txpool.packages()[4].ancestors, 3);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
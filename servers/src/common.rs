@@ -19,3 +19,5 @@ pub mod adapters;
 pub mod hooks;
 pub mod stats;
 pub mod types;
+#[cfg(feature = "zmq")]
+pub mod zmq;
@@ -19,12 +19,14 @@
 use crate::util::RwLock;
 use std::path::PathBuf;
 use std::sync::{Arc, Weak};
+use std::thread;
 use std::time::Instant;
 
+use crate::api;
 use crate::chain::txhashset::BitmapChunk;
-use crate::chain::{self, BlockStatus, ChainAdapter, Options, SyncState, SyncStatus};
+use crate::chain::{self, BlockStatus, ChainAdapter, ForkInfo, Options, SyncState, SyncStatus};
 
-use crate::common::hooks::{ChainEvents, NetEvents};
+use crate::common::hooks::{ChainEvents, NetEvents, PoolEvents};
 use crate::common::types::{ChainValidationMode, DandelionEpoch, ServerConfig};
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::transaction::Transaction;
@@ -35,13 +37,15 @@ use crate::core::core::{
 use crate::core::pow::Difficulty;
 use crate::core::ser::ProtocolVersion;
 use crate::core::{core, global};
+use crate::mwc::fork_monitor::ForkMonitor;
 use crate::mwc::sync::get_locator_heights;
 use crate::mwc::sync::sync_manager::SyncManager;
 use crate::p2p;
+use crate::p2p::msg::SendCompletion;
 use crate::p2p::types::PeerInfo;
 use crate::pool::{self, BlockChain, PoolAdapter};
 use crate::util::secp::pedersen::RangeProof;
-use crate::util::OneTime;
+use crate::util::{OneTime, ToHex};
 use chrono::prelude::*;
 use chrono::Duration;
 use mwc_chain::txhashset::Segmenter;
@@ -50,6 +54,9 @@ use mwc_util::secp::{ContextFlag, Secp256k1};
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering;
 
+/// Time to wait for the mined block to be written to the peers connections
+const MINED_BLOCK_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 // NetToChainAdapter need a memory cache to prevent data overloading for network core nodes (non leaf nodes)
 // This cache will drop sequence of the events during the second
 struct EventCache {
@@ -104,6 +111,7 @@ where
 	peers: OneTime<Weak<p2p::Peers>>,
 	config: ServerConfig,
 	hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
+	fork_monitor: ForkMonitor,
 
 	// local in mem cache
 	processed_headers: EventCache,
@@ -133,11 +141,15 @@ where
 		kernel_hash: Hash,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
-		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		// nothing much we can do with a new transaction while syncing or without the txhashset
+		if self.sync_state.is_syncing() || self.config.is_header_only() {
 			return Ok(true);
 		}
 
+		for hook in &self.hooks {
+			hook.on_transaction_kernel_received(&kernel_hash, &peer_info.addr);
+		}
+
 		let tx = self.tx_pool.read().retrieve_tx_by_kernel_hash(kernel_hash);
 
 		if tx.is_none() {
@@ -149,10 +161,11 @@ where
 	fn transaction_received(
 		&self,
 		tx: core::Transaction,
+		_peer_info: &PeerInfo,
 		stem: bool,
 	) -> Result<bool, chain::Error> {
-		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		// nothing much we can do with a new transaction while syncing or without the txhashset
+		if self.sync_state.is_syncing() || self.config.is_header_only() {
 			return Ok(true);
 		}
 
@@ -206,6 +219,10 @@ where
 			return Ok(true);
 		}
 
+		if self.config.is_header_only() {
+			return Ok(self.process_header_only(&b.header, &peer_info.addr));
+		}
+
 		let total_blocks = match self.chain().header_head() {
 			Ok(tip) => tip.height,
 			Err(_) => 0,
@@ -222,6 +239,9 @@ where
 			b.kernels().len(),
 			b.header.prev_hash,
 		);
+		if !opts.contains(chain::Options::SYNC) {
+			self.relay_header_first(&b.header);
+		}
 		self.process_block(b, peer_info, opts)
 	}
 
@@ -236,6 +256,10 @@ where
 			return Ok(true);
 		}
 
+		if self.config.is_header_only() {
+			return Ok(self.process_header_only(&cb.header, &peer_info.addr));
+		}
+
 		let bhash = cb.hash();
 		debug!(
 			"Received compact_block {} at {} from {} [out/kern/kern_ids: {}/{}/{}] going to process.",
@@ -249,6 +273,7 @@ where
 
 		let cb_hash = cb.hash();
 		if cb.kern_ids().is_empty() {
+			self.relay_header_first(&cb.header);
 			// push the freshly hydrated block through the chain pipeline
 			match core::Block::hydrate_from(cb, &[]) {
 				Ok(block) => {
@@ -276,6 +301,9 @@ where
 				debug!("Invalid compact block header {}: {:?}", cb_hash, e);
 				return Ok(!e.is_bad_data());
 			}
+			if self.is_header_first_relay() {
+				self.peers().relay_header_first(&cb.header);
+			}
 
 			let (txs, missing_short_ids) = {
 				self.tx_pool
@@ -394,7 +422,7 @@ where
 			if e.is_bad_data() {
 				return Ok(false);
 			} else {
-				if self.sync_state.are_headers_done() {
+				if self.sync_state.are_headers_done() && !self.config.is_header_only() {
 					// we got an error when trying to process the block header
 					// but nothing serious enough to need to ban the peer upstream
 					// Probably child block doesn't exist, let's request them
@@ -430,9 +458,15 @@ where
 			}
 		}
 
+		if !self.sync_state.is_syncing() {
+			self.check_fork(&bh, &peer_info.addr);
+		}
+
 		// we have successfully processed a block header
 		// so we can go request the block itself
-		self.request_compact_block(&bh, peer_info);
+		if !self.config.is_header_only() {
+			self.request_compact_block(&bh, peer_info);
+		}
 
 		// done receiving the header
 		Ok(true)
@@ -534,6 +568,37 @@ where
 		self.chain().get_tmpfile_pathname(tmpfile_name)
 	}
 
+	fn txhashset_receive_ready(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool {
+		self.sync_manager
+			.txhashset_receive_ready(peer, header_hash, header_height)
+	}
+
+	fn txhashset_download_update(&self, peer: &PeerAddr, downloaded_size: u64, total_size: u64) {
+		self.sync_manager
+			.txhashset_download_update(peer, downloaded_size, total_size);
+	}
+
+	fn txhashset_received(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) -> Result<(), chain::Error> {
+		info!(
+			"Received txhashset archive from {}. Header {} at {}",
+			peer, header_hash, header_height
+		);
+		self.sync_manager
+			.txhashset_received(peer, header_hash, header_height, path);
+		Ok(())
+	}
+
 	fn prepare_segmenter(&self) -> Result<Segmenter, chain::Error> {
 		if self.sync_state.is_syncing() {
 			return Err(chain::Error::ChainInSync);
@@ -645,6 +710,13 @@ where
 		);
 		self.sync_manager
 			.recieve_another_archive_header(peer, header_hash, header_height);
+		let fork = self.fork_monitor.check_archive_header(
+			&self.chain(),
+			&header_hash,
+			header_height,
+			peer,
+		);
+		self.report_fork(fork);
 		Ok(())
 	}
 
@@ -822,6 +894,10 @@ where
 		hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
 	) -> Self {
 		NetToChainAdapter {
+			fork_monitor: ForkMonitor::new(
+				config.fork_alert_depth.unwrap_or(3),
+				sync_state.clone(),
+			),
 			sync_state,
 			sync_manager,
 			chain: Arc::downgrade(&chain),
@@ -862,6 +938,14 @@ where
 		peer_info: &PeerInfo,
 		opts: chain::Options,
 	) -> Result<bool, chain::Error> {
+		// Historical blocks that we requested for the backfill are saved below the tail
+		if let Some(accepted) = self
+			.sync_manager
+			.recieve_backfill_block(&peer_info.addr, &b)
+		{
+			return Ok(accepted);
+		}
+
 		// We cannot process blocks earlier than the horizon so check for this here.
 		let chain = self.chain();
 		let head = {
@@ -880,6 +964,9 @@ where
 		match chain.process_block(b.clone(), opts) {
 			Ok(_) => {
 				self.validate_chain(&bhash);
+				if !self.sync_state.is_syncing() {
+					self.check_fork(&b.header, &peer_info.addr);
+				}
 				//self.check_compact();  Currently Sync process does that. No needs, also we don't want collosion to happens
 				self.sync_manager.recieve_block_reporting(
 					true,
@@ -949,6 +1036,41 @@ where
 		}
 	}
 
+	// Headers only node takes the header from the block, the body is dropped.
+	// Returns false if the header is bad and the peer needs to be banned.
+	fn process_header_only(&self, bh: &BlockHeader, peer: &PeerAddr) -> bool {
+		match self.chain().process_block_header(bh, chain::Options::NONE) {
+			Ok(_) => {
+				if !self.sync_state.is_syncing() {
+					self.check_fork(bh, peer);
+				}
+				true
+			}
+			Err(e) => {
+				debug!("Block header {} refused by chain: {:?}", bh.hash(), e);
+				!e.is_bad_data()
+			}
+		}
+	}
+
+	// Check if the peer is on a fork, the long forks are reported to the hooks
+	fn check_fork(&self, bh: &BlockHeader, peer: &PeerAddr) {
+		let fork = self.fork_monitor.check_header(&self.chain(), bh, peer);
+		self.report_fork(fork);
+	}
+
+	fn report_fork(&self, fork: Result<Option<ForkInfo>, chain::Error>) {
+		match fork {
+			Ok(Some(fork)) => {
+				for hook in &self.hooks {
+					hook.on_fork_detected(&fork);
+				}
+			}
+			Ok(None) => {}
+			Err(e) => debug!("Unable to check the peer fork, {}", e),
+		}
+	}
+
 	fn validate_chain(&self, bhash: &Hash) {
 		// If we are running in "validate the full chain every block" then
 		// panic here if validation fails for any reason.
@@ -998,6 +1120,28 @@ where
 		self.send_tx_request_to_peer(h, peer_info, |peer, h| peer.send_tx_request(h))
 	}
 
+	fn is_header_first_relay(&self) -> bool {
+		self.config.p2p_config.header_first_relay() && !self.sync_state.is_syncing()
+	}
+
+	// With header first relay we announce the new block header to our peers as soon as
+	// the header is valid. Full block validation (and compact block hydration) can take
+	// a while, peers can request the block from us or from anybody else meanwhile.
+	fn relay_header_first(&self, bh: &BlockHeader) {
+		if !self.is_header_first_relay() {
+			return;
+		}
+		match self.chain().process_block_header(bh, chain::Options::NONE) {
+			Ok(_) => self.peers().relay_header_first(bh),
+			Err(e) => debug!(
+				"relay_header_first: header {} at {} is not relayed, {:?}",
+				bh.hash(),
+				bh.height,
+				e
+			),
+		}
+	}
+
 	// After we have received a block header in "header first" propagation
 	// we need to go request the block (compact representation) from the
 	// same peer that gave us the header (unless we have already accepted the block)
@@ -1082,7 +1226,8 @@ where
 			if opts.contains(Options::MINE) {
 				// propagate compact block out if we mined the block
 				let cb: CompactBlock = b.clone().into();
-				self.peers().broadcast_compact_block(&cb);
+				let completions = self.peers().broadcast_mined_block(&cb);
+				self.report_mined_block_delivery(cb.hash(), completions);
 			} else {
 				// "header first" propagation if we are not the originator of this block
 				self.peers().broadcast_header(&b.header);
@@ -1142,6 +1287,31 @@ where
 			.upgrade()
 			.expect("Failed to upgrade weak ref to our peers.")
 	}
+
+	// Mined block must reach the network, otherwise it is lost. Waiting is done
+	// in a separate thread, the miner shouldn't wait for the slow peers.
+	fn report_mined_block_delivery(&self, hash: Hash, completions: Vec<SendCompletion>) {
+		let sent = completions.len();
+		let res = thread::Builder::new()
+			.name("mined_block_delivery".to_string())
+			.spawn(move || {
+				let delivered = SendCompletion::wait_all(&completions, MINED_BLOCK_SEND_TIMEOUT);
+				if delivered == 0 {
+					warn!(
+						"Mined block {} wasn't delivered to any peer, sent to {} peers",
+						hash, sent
+					);
+				} else {
+					info!(
+						"Mined block {} delivered to {} of {} peers",
+						hash, delivered, sent
+					);
+				}
+			});
+		if let Err(e) = res {
+			error!("Unable to start mined block delivery thread, {}", e);
+		}
+	}
 }
 
 /// Adapter between the transaction pool and the network, to relay
@@ -1149,6 +1319,8 @@ where
 pub struct PoolToNetAdapter {
 	peers: OneTime<Weak<p2p::Peers>>,
 	dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
+	ws_events: Arc<api::WsEventBus>,
+	hooks: Vec<Box<dyn PoolEvents + Send + Sync>>,
 }
 
 /// Adapter between the Dandelion monitor and the current Dandelion "epoch".
@@ -1161,6 +1333,9 @@ pub trait DandelionAdapter: Send + Sync {
 
 	/// Transition to the next Dandelion epoch (new stem/fluff state, select new relay peer).
 	fn next_epoch(&self);
+
+	/// Current Dandelion settings, they can be changed at runtime with the owner API
+	fn config(&self) -> pool::DandelionConfig;
 }
 
 impl DandelionAdapter for PoolToNetAdapter {
@@ -1175,11 +1350,43 @@ impl DandelionAdapter for PoolToNetAdapter {
 	fn next_epoch(&self) {
 		self.dandelion_epoch.write().next_epoch(&self.peers());
 	}
+
+	fn config(&self) -> pool::DandelionConfig {
+		self.dandelion_epoch.read().config()
+	}
+}
+
+impl api::DandelionControl for PoolToNetAdapter {
+	fn get_dandelion_config(&self) -> pool::DandelionConfig {
+		self.dandelion_epoch.read().config()
+	}
+
+	fn set_dandelion_config(&self, config: pool::DandelionConfig) {
+		self.dandelion_epoch.write().set_config(config);
+	}
+
+	fn get_dandelion_stats(&self) -> api::DandelionStats {
+		self.dandelion_epoch.read().stats()
+	}
 }
 
 impl pool::PoolAdapter for PoolToNetAdapter {
 	fn tx_accepted(&self, entry: &pool::PoolEntry, height: u64) {
 		self.peers().broadcast_transaction(&entry.tx, height);
+		// Stem transactions are not published, only the ones that are fluffed
+		self.ws_events.publish(api::WsEvent::MempoolTx {
+			hash: entry.tx.hash().to_hex(),
+			fee: entry.tx.fee(height),
+			kernels: entry
+				.tx
+				.kernels()
+				.iter()
+				.map(|k| k.excess.to_hex())
+				.collect(),
+		});
+		for hook in &self.hooks {
+			hook.on_transaction_accepted(&entry.tx);
+		}
 	}
 
 	fn stem_tx_accepted(&self, entry: &pool::PoolEntry) -> Result<(), pool::PoolError> {
@@ -1190,8 +1397,8 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 		// If "stem" epoch attempt to relay the tx to the next Dandelion relay.
 		// Fallback to immediately fluffing the tx if we cannot stem for any reason.
 		// If "fluff" epoch then nothing to do right now (fluff via Dandelion monitor).
-		// If node is configured to always stem our (pushed via api) txs then do so.
-		if epoch.is_stem() || (entry.src.is_pushed() && epoch.always_stem_our_txs()) {
+		// If node is configured to always stem our (pushed via api or generated) txs then do so.
+		if epoch.is_stem() || (entry.src.is_local() && epoch.always_stem_our_txs()) {
 			if let Some(peer) = epoch.relay_peer(&self.peers()) {
 				match peer.send_stem_transaction(&entry.tx) {
 					Ok(_) => {
@@ -1216,10 +1423,16 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 
 impl PoolToNetAdapter {
 	/// Create a new pool to net adapter
-	pub fn new(config: pool::DandelionConfig) -> PoolToNetAdapter {
+	pub fn new(
+		config: pool::DandelionConfig,
+		ws_events: Arc<api::WsEventBus>,
+		hooks: Vec<Box<dyn PoolEvents + Send + Sync>>,
+	) -> PoolToNetAdapter {
 		PoolToNetAdapter {
 			peers: OneTime::new(),
 			dandelion_epoch: Arc::new(RwLock::new(DandelionEpoch::new(config))),
+			ws_events,
+			hooks,
 		}
 	}
 
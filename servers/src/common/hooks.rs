@@ -20,10 +20,13 @@ extern crate hyper;
 extern crate hyper_rustls;
 extern crate tokio;
 
-use crate::chain::BlockStatus;
+use crate::api::{TipEvent, TipEventBus, WsEvent, WsEventBus};
+use crate::chain::{BlockStatus, ForkInfo};
 use crate::common::types::{ServerConfig, WebHooksConfig};
+#[cfg(feature = "zmq")]
+use crate::common::zmq::ZmqPublisher;
 use crate::core::core;
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hash, Hashed};
 use crate::p2p::types::PeerAddr;
 use futures::TryFutureExt;
 use hyper::client::HttpConnector;
@@ -34,6 +37,7 @@ use hyper_rustls::HttpsConnector;
 use mwc_util::ToHex;
 use serde::Serialize;
 use serde_json::{json, to_string};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::{Builder, Runtime};
 
@@ -44,6 +48,7 @@ pub fn init_net_hooks(config: &ServerConfig) -> Vec<Box<dyn NetEvents + Send + S
 	if config.webhook_config.block_received_url.is_some()
 		|| config.webhook_config.tx_received_url.is_some()
 		|| config.webhook_config.header_received_url.is_some()
+		|| config.webhook_config.fork_detected_url.is_some()
 	{
 		list.push(Box::new(WebHook::from_config(&config.webhook_config)));
 	}
@@ -60,17 +65,48 @@ pub fn init_chain_hooks(config: &ServerConfig) -> Vec<Box<dyn ChainEvents + Send
 	list
 }
 
+/// Chain and pool hooks of the ZeroMQ notifications
+pub type ZmqHooks = (
+	Vec<Box<dyn ChainEvents + Send + Sync>>,
+	Vec<Box<dyn PoolEvents + Send + Sync>>,
+);
+
+/// Returns the ZeroMQ notifications hooks, the chain and the pool share the publisher
+#[cfg(feature = "zmq")]
+pub fn init_zmq_hooks(config: &ServerConfig) -> Result<ZmqHooks, String> {
+	if !config.zmq_config.is_enabled() {
+		return Ok((vec![], vec![]));
+	}
+	let publisher = ZmqPublisher::from_config(&config.zmq_config)?;
+	Ok((vec![Box::new(publisher.clone())], vec![Box::new(publisher)]))
+}
+
+/// Returns the ZeroMQ notifications hooks, the node is built without them
+#[cfg(not(feature = "zmq"))]
+pub fn init_zmq_hooks(config: &ServerConfig) -> Result<ZmqHooks, String> {
+	if config.zmq_config.is_enabled() {
+		warn!("ZMQ notifications are configured, but the node is built without the zmq feature");
+	}
+	Ok((vec![], vec![]))
+}
+
 #[allow(unused_variables)]
 /// Trait to be implemented by Network Event Hooks
 pub trait NetEvents {
 	/// Triggers when a new transaction arrives
 	fn on_transaction_received(&self, tx: &core::Transaction) {}
 
+	/// Triggers when a peer announces a transaction kernel
+	fn on_transaction_kernel_received(&self, kernel_hash: &Hash, addr: &PeerAddr) {}
+
 	/// Triggers when a new block arrives
 	fn on_block_received(&self, block: &core::Block, addr: &PeerAddr) {}
 
 	/// Triggers when a new block header arrives
 	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {}
+
+	/// Triggers when a peer is on a fork longer than the fork alert depth
+	fn on_fork_detected(&self, fork: &ForkInfo) {}
 }
 
 #[allow(unused_variables)]
@@ -80,12 +116,20 @@ pub trait ChainEvents {
 	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {}
 }
 
+#[allow(unused_variables)]
+/// Trait to be implemented by Pool Event Hooks
+pub trait PoolEvents {
+	/// Triggers when a transaction is accepted by the pool and fluffed (not stem ones)
+	fn on_transaction_accepted(&self, tx: &core::Transaction) {}
+}
+
 /// Basic Logger
 struct EventLogger;
 
 impl NetEvents for EventLogger {
 	fn on_transaction_received(&self, tx: &core::Transaction) {
 		info!(
+			event = "tx_received";
 			"Received tx {}, [in/out/kern: {}/{}/{}] going to process.",
 			tx.hash(),
 			tx.inputs().len(),
@@ -96,6 +140,7 @@ impl NetEvents for EventLogger {
 
 	fn on_block_received(&self, block: &core::Block, addr: &PeerAddr) {
 		info!(
+			event = "block_received", height = block.header.height, peer:% = addr;
 			"Received block {} at {} from {} [in/out/kern: {}/{}/{}] going to process.",
 			block.hash(),
 			block.header.height,
@@ -108,12 +153,21 @@ impl NetEvents for EventLogger {
 
 	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {
 		info!(
+			event = "header_received", height = header.height, peer:% = addr;
 			"Received block header {} at {} from {}, going to process.",
 			header.hash(),
 			header.height,
 			addr
 		);
 	}
+
+	fn on_fork_detected(&self, fork: &ForkInfo) {
+		warn!(
+			event = "fork_detected", height = fork.tip_height, peer:% = fork.peer;
+			"Fork detected, peer {} is on a fork {} blocks long, tip {} at {}",
+			fork.peer, fork.length, fork.tip_hash, fork.tip_height
+		);
+	}
 }
 
 impl ChainEvents for EventLogger {
@@ -125,6 +179,7 @@ impl ChainEvents for EventLogger {
 				fork_point,
 			} => {
 				warn!(
+					event = "reorg", height = block.header.height;
 					"block_accepted (REORG!): {} at {}, (prev: {} at {}, prev_head: {} at {}, fork_point: {} at {}, depth: {})",
 					block.hash(),
 					block.header.height,
@@ -143,6 +198,7 @@ impl ChainEvents for EventLogger {
 				fork_point,
 			} => {
 				debug!(
+					event = "fork", height = block.header.height;
 					"block_accepted (fork?): {} at {}, (prev: {} at {}, head: {} at {}, fork_point: {} at {}, depth: {})",
 					block.hash(),
 					block.header.height,
@@ -157,6 +213,7 @@ impl ChainEvents for EventLogger {
 			}
 			BlockStatus::Next { prev } => {
 				debug!(
+					event = "block_accepted", height = block.header.height;
 					"block_accepted (head+): {} at {} (prev: {} at {})",
 					block.hash(),
 					block.header.height,
@@ -198,6 +255,8 @@ struct WebHook {
 	block_received_url: Option<hyper::Uri>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	block_accepted_url: Option<hyper::Uri>,
+	/// url to POST fork data when a peer is on a fork longer than the fork alert depth
+	fork_detected_url: Option<hyper::Uri>,
 	/// The hyper client to be used for all requests
 	client: Client<HttpsConnector<HttpConnector>>,
 	/// The tokio event loop
@@ -211,6 +270,7 @@ impl WebHook {
 		header_received_url: Option<hyper::Uri>,
 		block_received_url: Option<hyper::Uri>,
 		block_accepted_url: Option<hyper::Uri>,
+		fork_detected_url: Option<hyper::Uri>,
 		nthreads: u16,
 		timeout: u16,
 	) -> WebHook {
@@ -236,6 +296,7 @@ impl WebHook {
 			block_received_url,
 			header_received_url,
 			block_accepted_url,
+			fork_detected_url,
 			client,
 			runtime: Builder::new_multi_thread()
 				.enable_all()
@@ -252,6 +313,7 @@ impl WebHook {
 			parse_url(&config.header_received_url),
 			parse_url(&config.block_received_url),
 			parse_url(&config.block_accepted_url),
+			parse_url(&config.fork_detected_url),
 			config.nthreads,
 			config.timeout,
 		)
@@ -326,6 +388,68 @@ impl ChainEvents for WebHook {
 	}
 }
 
+/// Chain hook that publishes the accepted blocks to the WebSocket subscribers
+pub struct WsEventHook {
+	events: Arc<WsEventBus>,
+}
+
+impl WsEventHook {
+	/// Hook for the WebSocket events bus
+	pub fn new(events: Arc<WsEventBus>) -> WsEventHook {
+		WsEventHook { events }
+	}
+}
+
+impl ChainEvents for WsEventHook {
+	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+		let (status_str, depth) = match status {
+			BlockStatus::Reorg {
+				fork_point,
+				prev_head,
+				..
+			} => (
+				"reorg",
+				Some(prev_head.height.saturating_sub(fork_point.height)),
+			),
+			BlockStatus::Fork { .. } => ("fork", None),
+			BlockStatus::Next { .. } => ("head", None),
+		};
+		self.events.publish(WsEvent::Block {
+			hash: block.header.hash().to_hex(),
+			height: block.header.height,
+			prev_hash: block.header.prev_hash.to_hex(),
+			status: status_str.to_string(),
+			depth,
+		});
+	}
+}
+
+/// Chain hook that publishes the new head to the chain tip stream
+pub struct TipEventHook {
+	events: Arc<TipEventBus>,
+}
+
+impl TipEventHook {
+	/// Hook for the chain tip events bus
+	pub fn new(events: Arc<TipEventBus>) -> TipEventHook {
+		TipEventHook { events }
+	}
+}
+
+impl ChainEvents for TipEventHook {
+	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+		// Fork blocks don't change the tip
+		if let BlockStatus::Fork { .. } = status {
+			return;
+		}
+		self.events.publish(TipEvent {
+			height: block.header.height,
+			hash: block.header.hash().to_hex(),
+			total_difficulty: block.header.total_difficulty().to_num(),
+		});
+	}
+}
+
 impl NetEvents for WebHook {
 	/// Triggers when a new transaction arrives
 	fn on_transaction_received(&self, tx: &core::Transaction) {
@@ -369,4 +493,19 @@ impl NetEvents for WebHook {
 			);
 		}
 	}
+
+	/// Triggers when a peer is on a fork longer than the fork alert depth
+	fn on_fork_detected(&self, fork: &ForkInfo) {
+		let payload = json!({
+			"hash": fork.tip_hash,
+			"peer": fork.peer,
+			"data": fork
+		});
+		if !self.make_request(&payload, &self.fork_detected_url) {
+			error!(
+				"Failed to serialize fork {} at height {}",
+				fork.tip_hash, fork.tip_height
+			);
+		}
+	}
 }
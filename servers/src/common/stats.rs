@@ -18,6 +18,7 @@
 
 use crate::util::RwLock;
 use atomic_float::AtomicF64;
+use std::collections::HashMap;
 use std::sync::atomic::*;
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -27,7 +28,9 @@ use crate::core::ser::ProtocolVersion;
 
 use chrono::prelude::*;
 
+use crate::api::{self, MiningStatsSample};
 use crate::chain::SyncStatus;
+use crate::mining::stats_history::MiningStatsHistory;
 use crate::p2p;
 use crate::p2p::Capabilities;
 use mwc_core::pow::Difficulty;
@@ -69,6 +72,10 @@ pub struct ServerStats {
 	pub tx_stats: Option<TxStats>,
 	/// Disk usage in GB
 	pub disk_usage_gb: String,
+	/// Supervised components restarts
+	pub component_stats: Vec<ComponentStats>,
+	/// Blocks that are waiting for the headers
+	pub orphan_pool_stats: OrphanPoolStats,
 }
 
 /// Chain Statistics
@@ -110,6 +117,8 @@ pub struct WorkerStats {
 	pub pow_difficulty: u64,
 	/// number of valid shares submitted
 	pub num_accepted: u64,
+	/// sum of the share difficulties the valid shares were accepted at
+	pub accepted_difficulty: u64,
 	/// number of invalid shares submitted
 	pub num_rejected: u64,
 	/// number of shares submitted too late
@@ -118,6 +127,34 @@ pub struct WorkerStats {
 	pub num_blocks_found: u64,
 }
 
+/// Supervised component restarts
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentStats {
+	/// Component (thread) name
+	pub name: String,
+	/// Whether the component is running, false while waiting for the restart
+	pub is_running: bool,
+	/// Number of restarts after a panic
+	pub restarts: u32,
+	/// Message of the last panic
+	pub last_panic: Option<String>,
+	/// Time of the last panic
+	pub last_panic_time: Option<DateTime<Utc>>,
+}
+
+/// Pool of the blocks that can't be processed yet because the headers are not known
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrphanPoolStats {
+	/// Number of the blocks in the pool
+	pub blocks: usize,
+	/// Total size of the blocks in the pool, bytes
+	pub bytes: u64,
+	/// Number of the blocks evicted because of the pool limits
+	pub evicted: u64,
+	/// Number of the blocks rejected or replaced because of the peer limit
+	pub rejected: u64,
+}
+
 /// Struct to return relevant information about the stratum server
 #[derive(Debug)]
 pub struct StratumStats {
@@ -141,6 +178,11 @@ pub struct StratumStats {
 	pub minimum_share_difficulty: AtomicU64,
 	/// Individual worker status
 	worker_stats: RwLock<Vec<WorkerStats>>,
+	/// Stats by the worker login over all its connections, the id is the login.
+	/// Kept after the worker disconnects.
+	login_stats: RwLock<HashMap<String, WorkerStats>>,
+	/// Per minute samples of the hashrate and the shares
+	history: RwLock<MiningStatsHistory>,
 }
 
 /// Stats on the last WINDOW blocks and the difficulty calculation
@@ -263,6 +305,51 @@ impl StratumStats {
 	pub fn get_worker_stats(&self) -> Vec<WorkerStats> {
 		self.worker_stats.read().clone()
 	}
+
+	/// Update stats record of the worker login, the record is created for a new login
+	/// callback expected to be short and non locking
+	pub fn update_login_stats(&self, login: &str, f: impl FnOnce(&mut WorkerStats) -> ()) {
+		let mut login_stats = self.login_stats.write();
+		let stats = login_stats
+			.entry(login.to_string())
+			.or_insert_with(|| WorkerStats {
+				id: login.to_string(),
+				..WorkerStats::default()
+			});
+		f(stats);
+	}
+
+	/// Drop the stats record of the worker login
+	pub fn remove_login_stats(&self, login: &str) {
+		self.login_stats.write().remove(login);
+	}
+
+	/// Copy of the stats by the worker login, sorted by the login
+	pub fn get_login_stats(&self) -> Vec<WorkerStats> {
+		let mut stats: Vec<WorkerStats> = self.login_stats.read().values().cloned().collect();
+		stats.sort_by(|a, b| a.id.cmp(&b.id));
+		stats
+	}
+
+	/// Record the sample of the mining stats history, expected to be called every minute
+	pub fn record_history_sample(&self) {
+		let logins = self.get_login_stats();
+		self.history.write().add_sample(
+			Utc::now(),
+			self.network_hashrate.load(Ordering::Relaxed),
+			self.network_difficulty.load(Ordering::Relaxed),
+			&logins,
+		);
+	}
+}
+
+impl api::MiningStatsProvider for StratumStats {
+	fn mining_stats(&self, minutes: u32) -> Option<Vec<MiningStatsSample>> {
+		if !self.is_enabled.load(Ordering::Relaxed) {
+			return None;
+		}
+		Some(self.history.read().last_samples(minutes as usize))
+	}
 }
 
 impl PeerStats {
@@ -308,6 +395,7 @@ impl Default for WorkerStats {
 			initial_block_height: 0,
 			pow_difficulty: 0,
 			num_accepted: 0,
+			accepted_difficulty: 0,
 			num_rejected: 0,
 			num_stale: 0,
 			num_blocks_found: 0,
@@ -328,6 +416,8 @@ impl Default for StratumStats {
 			network_hashrate: AtomicF64::new(0.0),
 			minimum_share_difficulty: AtomicU64::new(1),
 			worker_stats: RwLock::new(Vec::new()),
+			login_stats: RwLock::new(HashMap::new()),
+			history: RwLock::new(MiningStatsHistory::new()),
 		}
 	}
 }
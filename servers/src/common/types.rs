@@ -30,7 +30,7 @@ use crate::pool;
 use crate::pool::types::DandelionConfig;
 use crate::store;
 use mwc_core::global;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
 /// Error type wrapping underlying module errors.
 #[derive(Debug, thiserror::Error)]
@@ -152,6 +152,24 @@ impl Default for ChainValidationMode {
 	}
 }
 
+/// What the node syncs and serves
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+	/// Regular node, headers, txhashset and blocks
+	Full,
+	/// Headers only node. Header sync only, the blocks and txhashset are not
+	/// downloaded, transactions are not accepted. For the monitoring devices
+	/// and the SPV-style services.
+	HeaderOnly,
+}
+
+impl Default for RunMode {
+	fn default() -> RunMode {
+		RunMode::Full
+	}
+}
+
 /// Type for Tor Configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TorConfig {
@@ -163,6 +181,13 @@ pub struct TorConfig {
 	pub tor_external: bool,
 	/// Onion address to use, only applicable with external tor
 	pub onion_address: Option<String>,
+	/// Control port of the external tor. If set, the node creates an ephemeral onion
+	/// service for its listeners at startup, onion_address is not used
+	#[serde(default)]
+	pub control_port: Option<u16>,
+	/// Password of the tor control port, the cookie authentication is used if not set
+	#[serde(default)]
+	pub control_password: Option<String>,
 }
 
 impl Default for TorConfig {
@@ -172,6 +197,8 @@ impl Default for TorConfig {
 			socks_port: 51234,
 			tor_external: false,
 			onion_address: Some("".to_string()),
+			control_port: None,
+			control_password: None,
 		}
 	}
 }
@@ -183,6 +210,11 @@ pub struct ServerConfig {
 	/// Directory under which the rocksdb stores will be created
 	pub db_root: String,
 
+	/// Key-value backend of the stores, "lmdb" or "rocksdb". RocksDB requires the node
+	/// built with the `rocksdb` feature.
+	#[serde(default)]
+	pub db_backend: store::StoreBackend,
+
 	/// Network address for the Rest API HTTP server.
 	pub api_http_addr: String,
 
@@ -196,6 +228,10 @@ pub struct ServerConfig {
 	pub tls_certificate_file: Option<String>,
 	/// TLS certificate private key file
 	pub tls_certificate_key: Option<String>,
+	/// CA certificates file for the TLS client certificates. If set, the owner scoped
+	/// routes are served only to the clients with the certificate signed by this CA
+	#[serde(default)]
+	pub tls_client_ca_file: Option<String>,
 
 	/// Setup the server for tests, testnet or mainnet
 	#[serde(default)]
@@ -205,9 +241,40 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub chain_validation_mode: ChainValidationMode,
 
+	/// Node run mode, "full" or "header_only"
+	/// (Default: full)
+	pub run_mode: Option<RunMode>,
+
 	/// Whether this node is a full archival node or a fast-sync, pruned node
 	pub archive_mode: Option<bool>,
 
+	/// Number of the recent full blocks to keep on a pruned node, if it is larger
+	/// than the cut-through horizon. The depth is advertised to the peers.
+	pub archive_depth: Option<u64>,
+
+	/// Backfill the full blocks history of a pruned node from the peers in the background.
+	/// The node switches to the archive mode once the history reaches genesis.
+	pub history_backfill: Option<bool>,
+
+	/// Maintain the kernel excess and output commitment to blocks index for the fast
+	/// lookups. The index covers the blocks accepted after it is enabled. Default: false
+	pub enable_index: Option<bool>,
+
+	/// Cut through horizon in blocks for a pruned node. The txhashset is compacted and
+	/// the full blocks are kept up to that depth. Can't be lower than the network horizon
+	/// (a week), max is 52 network horizons. Larger horizon needs more disk space but
+	/// can handle the deeper reorgs.
+	/// (Default: network horizon)
+	pub cut_through_horizon: Option<u32>,
+
+	/// Build the txhashset zip for the new archive header in advance, so syncing
+	/// peers don't wait for it. Default: false
+	pub txhashset_zip_prebuild: Option<bool>,
+
+	/// Compress the stored blocks below the cut-through horizon with zstd, it saves
+	/// the disk space of the archive nodes. Default: false
+	pub compress_old_blocks: Option<bool>,
+
 	/// Whether to skip the sync timeout on startup
 	/// (To assist testing on solo chains)
 	pub skip_sync_wait: Option<bool>,
@@ -220,10 +287,45 @@ pub struct ServerConfig {
 	/// (Default: 50 ms)
 	pub duration_sync_long: Option<i64>,
 
+	/// Forks that the peers are on and that are longer than that many blocks are reported
+	/// with the status API and the fork webhook.
+	/// (Default: 3)
+	pub fork_alert_depth: Option<u64>,
+
+	/// Reorgs deeper than that many blocks are not applied until approved with
+	/// the owner API `approve_reorg` call.
+	/// (Default: none, no limit)
+	pub max_reorg_depth: Option<u64>,
+
+	/// Number of threads verifying the rangeproofs and the kernel signatures of the
+	/// blocks. 1 verifies in the chain thread.
+	/// (Default: none, number of CPUs)
+	pub validation_threads: Option<usize>,
+
+	/// Number of the verified kernel signatures and of the rangeproofs kept in the cache,
+	/// the transactions verified by the pool are not verified again with the block.
+	/// 0 disables the cache. (Default: 50000)
+	pub verifier_cache_size: Option<usize>,
+
+	/// If the chain tip doesn't advance for that many minutes while the peers report
+	/// more work, the stuck peers are dropped and the headers sync is restarted.
+	/// 0 disables the recovery. (Default: 30)
+	pub stale_tip_timeout_minutes: Option<u64>,
+
 	/// Invalid Block hash list
 	/// (Default: none)
 	pub invalid_block_hashes: Option<Vec<String>>,
 
+	/// Use the header checkpoints, the checkpoint headers and their ancestors skip
+	/// the PoW verification during the initial sync.
+	/// (Default: true)
+	pub use_checkpoints: Option<bool>,
+
+	/// Additional header checkpoints in the format 'height:hash', they override the
+	/// hard-coded checkpoints at the same height.
+	/// (Default: none)
+	pub checkpoints: Option<Vec<String>>,
+
 	/// Whether to run the TUI
 	/// if enabled, this will disable logging to stdout
 	pub run_tui: Option<bool>,
@@ -234,6 +336,11 @@ pub struct ServerConfig {
 	/// Test miner wallet URL
 	pub test_miner_wallet_url: Option<String>,
 
+	/// Synthetic transactions per minute that the node generates and injects into its own
+	/// pool. Developer load testing on usernet and floonet, funded by the test miner
+	/// without wallet. Default: 0, disabled
+	pub tx_generator_rate: Option<u32>,
+
 	/// Enable libp2p server. It can run only with TOR. Needed for wallets to send messages to each other.
 	/// Default value: enabled
 	pub libp2p_enabled: Option<bool>,
@@ -264,39 +371,118 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub webhook_config: WebHooksConfig,
 
+	/// Configuration for the ZeroMQ notifications
+	#[serde(default)]
+	pub zmq_config: ZmqConfig,
+
+	/// Configuration for the webhook dispatcher with the delivery retries
+	#[serde(default)]
+	pub webhook_dispatcher_config: WebhookDispatcherConfig,
+
+	/// Disk usage monitor, chain data size and the free space protection
+	#[serde(default)]
+	pub disk_monitor_config: DiskMonitorConfig,
+
 	/// Tor Configuration
 	#[serde(default)]
 	pub tor_config: TorConfig,
+
+	/// Proxy for the local wallet listener, so the wallet foreign API can be reached
+	/// through the node API address (and its onion address)
+	#[serde(default)]
+	pub wallet_proxy_config: Option<api::WalletProxyConfig>,
+
+	/// Read-only API profile, only the allowlisted read endpoints are served, so the node API
+	/// can be exposed to the public internet (explorer backends)
+	#[serde(default)]
+	pub read_only_api_config: Option<api::ReadOnlyApiConfig>,
+
+	/// Additional API keys for the Rest API and v2 Owner API, each key is scoped to the
+	/// read-only, pool push or owner operations. The `api_secret_path` key has the owner scope.
+	#[serde(default)]
+	pub api_tokens: Option<Vec<api::ApiTokenConfig>>,
+
+	/// Cross-origin requests policy of the API, so the browser based explorers and web
+	/// wallets can call it directly. Cross-origin requests are not allowed if not set
+	#[serde(default)]
+	pub cors_config: Option<api::CorsConfig>,
+
+	/// Requests rate limiting for the public API routes (foreign API by default),
+	/// per source IP and per API key. Not limited if not set
+	#[serde(default)]
+	pub rate_limit_config: Option<api::RateLimitConfig>,
+
+	/// Thresholds of the /v2/ready probe, the defaults are used if not set
+	#[serde(default)]
+	pub health_config: Option<api::HealthConfig>,
+
+	/// Connection pool of the HTTP client that is used for the outgoing API calls
+	#[serde(default)]
+	pub http_client_config: Option<api::HttpClientConfig>,
+}
+
+impl ServerConfig {
+	/// Whether the node runs in the headers only mode
+	pub fn is_header_only(&self) -> bool {
+		self.run_mode == Some(RunMode::HeaderOnly)
+	}
 }
 
 impl Default for ServerConfig {
 	fn default() -> ServerConfig {
 		ServerConfig {
 			db_root: "mwc_chain".to_string(),
+			db_backend: store::StoreBackend::default(),
 			api_http_addr: "127.0.0.1:3413".to_string(),
 			api_secret_path: Some(".api_secret".to_string()),
 			foreign_api_secret_path: Some(".foreign_api_secret".to_string()),
 			tls_certificate_file: None,
 			tls_certificate_key: None,
+			tls_client_ca_file: None,
 			p2p_config: p2p::P2PConfig::default(),
 			dandelion_config: pool::DandelionConfig::default(),
 			stratum_mining_config: Some(StratumServerConfig::default()),
 			chain_type: ChainTypes::default(),
+			run_mode: Some(RunMode::Full),
 			archive_mode: Some(false),
+			archive_depth: Some(0),
+			history_backfill: Some(false),
+			enable_index: Some(false),
+			cut_through_horizon: None,
+			txhashset_zip_prebuild: Some(false),
+			compress_old_blocks: Some(false),
 			chain_validation_mode: ChainValidationMode::default(),
 			pool_config: pool::PoolConfig::default(),
 			skip_sync_wait: Some(false),
+			fork_alert_depth: Some(3),
+			max_reorg_depth: None,
+			validation_threads: None,
+			verifier_cache_size: Some(core::verifier_cache::DEFAULT_VERIFIER_CACHE_SIZE),
+			stale_tip_timeout_minutes: Some(30),
 			invalid_block_hashes: Some(vec![]),
+			use_checkpoints: Some(true),
+			checkpoints: None,
 			duration_sync_short: Some(30),
 			duration_sync_long: Some(50),
 			run_tui: Some(true),
 			run_test_miner: Some(false),
 			test_miner_wallet_url: None,
+			tx_generator_rate: Some(0),
 			libp2p_enabled: Some(true),
 			libp2p_port: Some(3417),
 			libp2p_topics: None,
 			webhook_config: WebHooksConfig::default(),
+			zmq_config: ZmqConfig::default(),
+			webhook_dispatcher_config: WebhookDispatcherConfig::default(),
+			disk_monitor_config: DiskMonitorConfig::default(),
 			tor_config: TorConfig::default(),
+			wallet_proxy_config: None,
+			read_only_api_config: None,
+			api_tokens: None,
+			cors_config: None,
+			rate_limit_config: None,
+			health_config: None,
+			http_client_config: None,
 		}
 	}
 }
@@ -311,6 +497,11 @@ pub struct StratumServerConfig {
 	/// If enabled, the address and port to listen on
 	pub stratum_server_addr: Option<String>,
 
+	/// Address and port of the WebSocket listener for the miners that can't use the raw
+	/// TCP. Disabled if not set.
+	#[serde(default)]
+	pub stratum_ws_server_addr: Option<String>,
+
 	/// How long to wait before stopping the miner, recollecting transactions
 	/// and starting again. Units: seconds
 	pub attempt_time_per_block: u32,
@@ -360,6 +551,55 @@ pub struct StratumServerConfig {
 	/// Black list of IPs
 	#[serde(default)]
 	pub ip_black_list: HashSet<String>,
+
+	/// Retarget every worker share difficulty toward `vardiff_shares_per_minute`
+	#[serde(default)]
+	pub vardiff_enabled: bool,
+
+	/// Goal of the worker shares rate for the variable difficulty. Units: shares per minute
+	#[serde(default = "StratumServerConfig::default_vardiff_shares_per_minute")]
+	pub vardiff_shares_per_minute: u32,
+
+	/// How often the worker difficulty is retargeted. Units: seconds
+	#[serde(default = "StratumServerConfig::default_vardiff_retarget_s")]
+	pub vardiff_retarget_s: i64,
+
+	/// Certificate of the stratum TLS listener. Plain TCP if not set
+	#[serde(default)]
+	pub tls_certificate_file: Option<String>,
+
+	/// Private key of the stratum TLS certificate
+	#[serde(default)]
+	pub tls_certificate_key: Option<String>,
+
+	/// Registered worker accounts. If set, only these workers can log in and get the jobs,
+	/// any login is accepted otherwise
+	#[serde(default)]
+	pub workers: Option<Vec<StratumWorkerConfig>>,
+
+	/// Split of the block reward between several wallets, replaces `wallet_listener_url`.
+	/// The whole reward goes to `wallet_listener_url` if not set.
+	#[serde(default)]
+	pub coinbase_payouts: Option<Vec<CoinbasePayout>>,
+}
+
+/// Share of the block reward (with the fees) that is paid to the wallet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoinbasePayout {
+	/// Wallet listener that builds the coinbase output of this share
+	pub wallet_listener_url: String,
+	/// Share of the reward, percent. The payouts are expected to sum up to 100.
+	pub percent: f64,
+}
+
+/// Stratum worker account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StratumWorkerConfig {
+	/// Worker login
+	pub login: String,
+	/// File with the worker password or token, the first line is used. Same format as
+	/// `api_secret_path`
+	pub password_path: String,
 }
 
 impl StratumServerConfig {
@@ -384,6 +624,12 @@ impl StratumServerConfig {
 	fn default_connection_pace_ms() -> i64 {
 		-1
 	}
+	fn default_vardiff_shares_per_minute() -> u32 {
+		6
+	}
+	fn default_vardiff_retarget_s() -> i64 {
+		120
+	}
 }
 
 impl Default for StratumServerConfig {
@@ -395,6 +641,7 @@ impl Default for StratumServerConfig {
 			minimum_share_difficulty: 1,
 			enable_stratum_server: Some(false),
 			stratum_server_addr: Some("127.0.0.1:3416".to_string()),
+			stratum_ws_server_addr: None,
 			ip_tracking: StratumServerConfig::default_ip_tracking(),
 			workers_connection_limit: StratumServerConfig::default_workers_connection_limit(),
 			ban_action_limit: StratumServerConfig::default_ban_action_limit(),
@@ -404,6 +651,13 @@ impl Default for StratumServerConfig {
 			connection_pace_ms: StratumServerConfig::default_connection_pace_ms(),
 			ip_white_list: HashSet::new(),
 			ip_black_list: HashSet::new(),
+			vardiff_enabled: false,
+			vardiff_shares_per_minute: StratumServerConfig::default_vardiff_shares_per_minute(),
+			vardiff_retarget_s: StratumServerConfig::default_vardiff_retarget_s(),
+			tls_certificate_file: None,
+			tls_certificate_key: None,
+			workers: None,
+			coinbase_payouts: None,
 		}
 	}
 }
@@ -419,6 +673,9 @@ pub struct WebHooksConfig {
 	pub block_received_url: Option<String>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	pub block_accepted_url: Option<String>,
+	/// url to POST fork data when a peer is on a fork longer than the fork alert depth
+	#[serde(default)]
+	pub fork_detected_url: Option<String>,
 	/// number of worker threads in the tokio runtime
 	#[serde(default = "default_nthreads")]
 	pub nthreads: u16,
@@ -442,12 +699,171 @@ impl Default for WebHooksConfig {
 			header_received_url: None,
 			block_received_url: None,
 			block_accepted_url: None,
+			fork_detected_url: None,
 			nthreads: default_nthreads(),
 			timeout: default_timeout(),
 		}
 	}
 }
 
+/// ZeroMQ notifications configuration, the same as bitcoind `zmqpub*` options. The node
+/// must be built with the `zmq` feature.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ZmqConfig {
+	/// endpoint to publish the hashes of the blocks accepted by our node, like "tcp://127.0.0.1:28332"
+	#[serde(default)]
+	pub pub_hash_block: Option<String>,
+	/// endpoint to publish the serialized blocks accepted by our node
+	#[serde(default)]
+	pub pub_raw_block: Option<String>,
+	/// endpoint to publish the serialized kernels of the transactions accepted by our pool
+	#[serde(default)]
+	pub pub_tx_kernel: Option<String>,
+	/// number of the messages queued per subscriber, slow subscribers lose the messages above it
+	#[serde(default = "default_zmq_high_water_mark")]
+	pub high_water_mark: i32,
+}
+
+fn default_zmq_high_water_mark() -> i32 {
+	1000
+}
+
+impl ZmqConfig {
+	/// Is any of the endpoints configured
+	pub fn is_enabled(&self) -> bool {
+		self.pub_hash_block.is_some()
+			|| self.pub_raw_block.is_some()
+			|| self.pub_tx_kernel.is_some()
+	}
+}
+
+impl Default for ZmqConfig {
+	fn default() -> ZmqConfig {
+		ZmqConfig {
+			pub_hash_block: None,
+			pub_raw_block: None,
+			pub_tx_kernel: None,
+			high_water_mark: default_zmq_high_water_mark(),
+		}
+	}
+}
+
+/// Events delivered by the webhook dispatcher
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+	/// New block extended our chain
+	Block,
+	/// Our chain was switched to the fork
+	Reorg,
+	/// Node finished the sync
+	SyncComplete,
+	/// Peer was banned
+	PeerBanned,
+	/// Transaction with the watched kernel was accepted by the pool
+	KernelAccepted,
+}
+
+/// Endpoint of the webhook dispatcher
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookEndpointConfig {
+	/// url to POST the events to
+	pub url: String,
+	/// secret to sign the payload, the `X-Mwc-Signature` header is `sha256=<hex HMAC>`
+	#[serde(default)]
+	pub secret: Option<String>,
+	/// events to deliver, all of them if empty
+	#[serde(default)]
+	pub events: Vec<WebhookEventKind>,
+}
+
+impl WebhookEndpointConfig {
+	/// Is the endpoint subscribed to the event
+	pub fn accepts(&self, kind: WebhookEventKind) -> bool {
+		self.events.is_empty() || self.events.contains(&kind)
+	}
+}
+
+/// Webhook dispatcher configuration. Unlike the `webhook_config` hooks, the failed
+/// deliveries are retried and the payloads can be signed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDispatcherConfig {
+	/// number of the delivery retries after the first attempt
+	#[serde(default = "default_webhook_max_retries")]
+	pub max_retries: u32,
+	/// delay before the first retry in seconds, doubled for every next one
+	#[serde(default = "default_webhook_retry_delay_secs")]
+	pub retry_delay_secs: u64,
+	/// timeout in seconds for the http request
+	#[serde(default = "default_timeout")]
+	pub request_timeout: u16,
+	/// number of the deliveries waiting in the queue, including retries. The oldest are dropped.
+	#[serde(default = "default_webhook_queue_size")]
+	pub queue_size: usize,
+	/// kernel excesses (hex) for the `kernel_accepted` event
+	#[serde(default)]
+	pub watched_kernels: Vec<String>,
+	/// endpoints to deliver the events to
+	#[serde(default)]
+	pub endpoints: Vec<WebhookEndpointConfig>,
+}
+
+fn default_webhook_max_retries() -> u32 {
+	5
+}
+
+fn default_webhook_retry_delay_secs() -> u64 {
+	5
+}
+
+fn default_webhook_queue_size() -> usize {
+	1000
+}
+
+impl Default for WebhookDispatcherConfig {
+	fn default() -> WebhookDispatcherConfig {
+		WebhookDispatcherConfig {
+			max_retries: default_webhook_max_retries(),
+			retry_delay_secs: default_webhook_retry_delay_secs(),
+			request_timeout: default_timeout(),
+			queue_size: default_webhook_queue_size(),
+			watched_kernels: vec![],
+			endpoints: vec![],
+		}
+	}
+}
+
+/// Disk usage monitor configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskMonitorConfig {
+	/// hard floor of the free disk space in MB, the new blocks are not accepted below it
+	#[serde(default = "default_disk_min_free_mb")]
+	pub min_free_mb: u64,
+	/// disk usage is checked that often, in seconds
+	#[serde(default = "default_disk_check_interval_secs")]
+	pub check_interval_secs: u64,
+}
+
+fn default_disk_min_free_mb() -> u64 {
+	1024
+}
+
+fn default_disk_check_interval_secs() -> u64 {
+	60
+}
+
+impl Default for DiskMonitorConfig {
+	fn default() -> DiskMonitorConfig {
+		DiskMonitorConfig {
+			min_free_mb: default_disk_min_free_mb(),
+			check_interval_secs: default_disk_check_interval_secs(),
+		}
+	}
+}
+
+/// Number of the recent epochs in the Dandelion stats
+const DANDELION_EPOCHS_HISTORY: usize = 24;
+
 /// A node is either "stem" of "fluff" for the duration of a single epoch.
 /// A node also maintains an outbound relay peer for the epoch.
 #[derive(Debug)]
@@ -459,6 +875,11 @@ pub struct DandelionEpoch {
 	is_stem: bool,
 	// Our current Dandelion relay peer (effective for this epoch).
 	relay_peer: Option<Arc<p2p::Peer>>,
+	// Rotation stats, the current epoch is the last one in the history
+	epochs: u64,
+	stem_epochs: u64,
+	relay_changes: u64,
+	history: VecDeque<api::DandelionEpochStats>,
 }
 
 impl DandelionEpoch {
@@ -469,9 +890,25 @@ impl DandelionEpoch {
 			start_time: None,
 			is_stem: true,
 			relay_peer: None,
+			epochs: 0,
+			stem_epochs: 0,
+			relay_changes: 0,
+			history: VecDeque::new(),
 		}
 	}
 
+	/// Current Dandelion settings
+	pub fn config(&self) -> DandelionConfig {
+		self.config.clone()
+	}
+
+	/// Replace the Dandelion settings. The current epoch is expired by the new epoch
+	/// length, the new stem probability is used from the next epoch.
+	pub fn set_config(&mut self, config: DandelionConfig) {
+		info!("DandelionEpoch: config updated: {:?}", config);
+		self.config = config;
+	}
+
 	/// Is the current Dandelion epoch expired?
 	/// It is expired if start_time is older than the configured epoch_secs.
 	pub fn is_expired(&self) -> bool {
@@ -488,7 +925,8 @@ impl DandelionEpoch {
 	/// Select stem/fluff based on configured stem_probability.
 	/// Choose a new outbound stem relay peer.
 	pub fn next_epoch(&mut self, peers: &Arc<p2p::Peers>) {
-		self.start_time = Some(Utc::now().timestamp());
+		let start_time = Utc::now().timestamp();
+		self.start_time = Some(start_time);
 		let my_fee_base = global::get_accept_fee_base();
 		self.relay_peer = peers
 			.iter()
@@ -507,6 +945,22 @@ impl DandelionEpoch {
 			"DandelionEpoch: next_epoch: is_stem: {} ({}%), relay: {:?}",
 			self.is_stem, stem_probability, addr
 		);
+
+		self.epochs += 1;
+		if self.is_stem {
+			self.stem_epochs += 1;
+		}
+		if self.history.len() >= DANDELION_EPOCHS_HISTORY {
+			self.history.pop_front();
+		}
+		self.history.push_back(api::DandelionEpochStats {
+			start_time,
+			is_stem: self.is_stem,
+			relay_peers: vec![],
+		});
+		if let Some(addr) = addr {
+			self.record_relay(&addr);
+		}
 	}
 
 	/// Are we stemming (or fluffing) transactions in this epoch?
@@ -543,12 +997,31 @@ impl DandelionEpoch {
 					p.is_connected() && p.info.is_outbound() && p.info.tx_base_fee <= my_fee_base
 				})
 				.choose_random();
-			info!(
-				"DandelionEpoch: relay_peer: new peer chosen: {:?}",
-				self.relay_peer.clone().map(|p| p.info.addr.clone())
-			);
+			let addr = self.relay_peer.clone().map(|p| p.info.addr.clone());
+			info!("DandelionEpoch: relay_peer: new peer chosen: {:?}", addr);
+			if let Some(addr) = addr {
+				self.record_relay(&addr);
+			}
 		}
 
 		self.relay_peer.clone()
 	}
+
+	/// Epochs and relay peers rotation stats
+	pub fn stats(&self) -> api::DandelionStats {
+		api::DandelionStats {
+			epochs: self.epochs,
+			stem_epochs: self.stem_epochs,
+			relay_changes: self.relay_changes,
+			relay_peer: self.relay_peer.as_ref().map(|p| p.info.addr.to_string()),
+			recent_epochs: self.history.iter().cloned().collect(),
+		}
+	}
+
+	fn record_relay(&mut self, addr: &p2p::PeerAddr) {
+		self.relay_changes += 1;
+		if let Some(epoch) = self.history.back_mut() {
+			epoch.relay_peers.push(addr.to_string());
+		}
+	}
 }
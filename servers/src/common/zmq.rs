@@ -0,0 +1,168 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! ZeroMQ notifications, the same way as the bitcoind `zmqpub*` options. Every message
+//! has 3 parts: the topic, the body and the 4 bytes little endian sequence number of
+//! the topic, so the subscriber can detect the lost messages. The sockets are owned by
+//! the publisher thread, the hooks never block the chain or the pool.
+
+use crate::chain::BlockStatus;
+use crate::common::hooks::{ChainEvents, PoolEvents};
+use crate::common::types::ZmqConfig;
+use crate::core::core;
+use crate::core::core::hash::Hashed;
+use crate::core::ser::{self, ProtocolVersion};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread;
+
+/// Hash of the accepted block
+pub const TOPIC_HASH_BLOCK: &str = "hashblock";
+/// Serialized accepted block
+pub const TOPIC_RAW_BLOCK: &str = "rawblock";
+/// Serialized kernel of the transaction accepted by the pool
+pub const TOPIC_TX_KERNEL: &str = "txkernel";
+
+/// Messages waiting for the publisher thread, the new ones are dropped above that
+const ZMQ_QUEUE_SIZE: usize = 1024;
+
+struct ZmqMessage {
+	topic: &'static str,
+	body: Vec<u8>,
+}
+
+/// Publisher of the chain and pool events
+#[derive(Clone)]
+pub struct ZmqPublisher {
+	sender: SyncSender<ZmqMessage>,
+	hash_block: bool,
+	raw_block: bool,
+	tx_kernel: bool,
+}
+
+impl ZmqPublisher {
+	/// Bind the configured endpoints and start the publisher thread. Topics can share
+	/// the endpoint, it is bound once.
+	pub fn from_config(config: &ZmqConfig) -> Result<ZmqPublisher, String> {
+		let context = zmq::Context::new();
+		let mut sockets: HashMap<String, zmq::Socket> = HashMap::new();
+		let mut topics: HashMap<&'static str, String> = HashMap::new();
+		for (topic, endpoint) in [
+			(TOPIC_HASH_BLOCK, &config.pub_hash_block),
+			(TOPIC_RAW_BLOCK, &config.pub_raw_block),
+			(TOPIC_TX_KERNEL, &config.pub_tx_kernel),
+		] {
+			let endpoint = match endpoint {
+				Some(endpoint) => endpoint,
+				None => continue,
+			};
+			if !sockets.contains_key(endpoint) {
+				let socket = context
+					.socket(zmq::PUB)
+					.and_then(|s| s.set_sndhwm(config.high_water_mark).map(|_| s))
+					.and_then(|s| s.bind(endpoint).map(|_| s))
+					.map_err(|e| format!("Unable to bind ZMQ endpoint {}, {}", endpoint, e))?;
+				info!("ZMQ publisher is listening on {}", endpoint);
+				sockets.insert(endpoint.clone(), socket);
+			}
+			topics.insert(topic, endpoint.clone());
+		}
+
+		let publisher = ZmqPublisher {
+			sender: Self::start(sockets, topics.clone())?,
+			hash_block: topics.contains_key(TOPIC_HASH_BLOCK),
+			raw_block: topics.contains_key(TOPIC_RAW_BLOCK),
+			tx_kernel: topics.contains_key(TOPIC_TX_KERNEL),
+		};
+		Ok(publisher)
+	}
+
+	// The thread exits when all the publisher clones are dropped
+	fn start(
+		sockets: HashMap<String, zmq::Socket>,
+		topics: HashMap<&'static str, String>,
+	) -> Result<SyncSender<ZmqMessage>, String> {
+		let (sender, receiver) = mpsc::sync_channel::<ZmqMessage>(ZMQ_QUEUE_SIZE);
+		thread::Builder::new()
+			.name("zmq_publisher".to_string())
+			.spawn(move || {
+				let mut sequences: HashMap<&'static str, u32> = HashMap::new();
+				for msg in receiver {
+					let socket = match topics.get(msg.topic).and_then(|e| sockets.get(e)) {
+						Some(socket) => socket,
+						None => continue,
+					};
+					let seq = sequences.entry(msg.topic).or_insert(0);
+					let parts = vec![
+						msg.topic.as_bytes().to_vec(),
+						msg.body,
+						seq.to_le_bytes().to_vec(),
+					];
+					*seq = seq.wrapping_add(1);
+					if let Err(e) = socket.send_multipart(parts, zmq::DONTWAIT) {
+						debug!("Unable to publish ZMQ {} message, {}", msg.topic, e);
+					}
+				}
+			})
+			.map_err(|e| format!("Unable to start ZMQ publisher thread, {}", e))?;
+		Ok(sender)
+	}
+
+	fn publish(&self, topic: &'static str, body: Vec<u8>) {
+		match self.sender.try_send(ZmqMessage { topic, body }) {
+			Ok(_) => {}
+			Err(TrySendError::Full(_)) => {
+				warn!("ZMQ publisher queue is full, {} message is dropped", topic)
+			}
+			Err(TrySendError::Disconnected(_)) => {
+				error!(
+					"ZMQ publisher thread is stopped, {} message is dropped",
+					topic
+				)
+			}
+		}
+	}
+}
+
+impl ChainEvents for ZmqPublisher {
+	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+		// Fork blocks don't change the head, the same as bitcoind they are not published
+		if let BlockStatus::Fork { .. } = status {
+			return;
+		}
+		if self.hash_block {
+			self.publish(TOPIC_HASH_BLOCK, block.hash().to_vec());
+		}
+		if self.raw_block {
+			match ser::ser_vec(block, ProtocolVersion::local()) {
+				Ok(body) => self.publish(TOPIC_RAW_BLOCK, body),
+				Err(e) => error!("Unable to serialize block {}, {}", block.hash(), e),
+			}
+		}
+	}
+}
+
+impl PoolEvents for ZmqPublisher {
+	fn on_transaction_accepted(&self, tx: &core::Transaction) {
+		if !self.tx_kernel {
+			return;
+		}
+		for kernel in tx.kernels() {
+			match ser::ser_vec(kernel, ProtocolVersion::local()) {
+				Ok(body) => self.publish(TOPIC_TX_KERNEL, body),
+				Err(e) => error!("Unable to serialize kernel {:?}, {}", kernel.excess, e),
+			}
+		}
+	}
+}
@@ -44,7 +44,13 @@ mod mining;
 mod mwc;
 mod tor;
 
-pub use crate::common::stats::{DiffBlock, PeerStats, ServerStats, StratumStats, WorkerStats};
-pub use crate::common::types::{ServerConfig, StratumServerConfig};
+pub use crate::common::stats::{
+	ComponentStats, DiffBlock, OrphanPoolStats, PeerStats, ServerStats, StratumStats, WorkerStats,
+};
+pub use crate::common::types::{
+	CoinbasePayout, RunMode, ServerConfig, StratumServerConfig, StratumWorkerConfig,
+};
 pub use crate::core::global::{FLOONET_DNS_SEEDS, MAINNET_DNS_SEEDS};
+pub use crate::mwc::config_watcher::ConfigLoader;
+pub use crate::mwc::node::{Node, NodeEvent, NodeEventHub};
 pub use crate::mwc::server::{Server, ServerTxPool};
@@ -15,7 +15,10 @@
 
 //! Mining + Mining server
 
+pub mod block_template;
 mod mine_block;
+pub mod stats_history;
 mod stratum_data;
 pub mod stratumserver;
 pub mod test_miner;
+mod vardiff;
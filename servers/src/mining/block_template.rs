@@ -0,0 +1,305 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Block templates for the external mining controllers (mining API). Works the same
+//! way as the stratum server jobs: the candidate block is rebuilt on the new head or
+//! when the TTL is expired, the solutions are accepted for any version of the current height.
+//! The long poll waits for the new head or for the noticeable growth of the pool fees,
+//! then the template is rebuilt.
+
+use crate::api;
+use crate::chain;
+use crate::common::types::{CoinbasePayout, Error, StratumServerConfig};
+use crate::core::consensus;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::Block;
+use crate::core::ser;
+use crate::keychain::Identifier;
+use crate::mining::mine_block;
+use crate::util::{self, Mutex, ToHex};
+use crate::ServerTxPool;
+use chrono::prelude::Utc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the long poll checks the chain head and the pool
+const LONGPOLL_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Growth of the pool fees, percent, that is worth the new template
+const LONGPOLL_POOL_FEES_GROWTH: u64 = 10;
+
+struct Candidate {
+	block: Block,
+	/// Scaled difficulty of the block
+	difficulty: u64,
+}
+
+struct State {
+	prev_hash: Hash,
+	/// Versions of the candidate block for the current height, the index is the template id
+	candidates: Vec<Candidate>,
+	/// Coinbase key derivation is reused while the candidate is rebuilt for the same height
+	key_id: Option<Identifier>,
+	/// Time to rebuild the candidate with the new transactions
+	deadline: i64,
+	/// Fees of the pool transactions when the last candidate was built
+	pool_fees: u64,
+}
+
+/// Candidate blocks for the mining API
+pub struct BlockTemplates {
+	chain: Arc<chain::Chain>,
+	tx_pool: ServerTxPool,
+	/// None to burn the reward
+	wallet_listener_url: Option<String>,
+	payouts: Vec<CoinbasePayout>,
+	ttl: u64,
+	state: Mutex<State>,
+}
+
+impl BlockTemplates {
+	/// Block templates with the coinbase and TTL settings of the stratum server
+	pub fn new(
+		chain: Arc<chain::Chain>,
+		tx_pool: ServerTxPool,
+		config: &StratumServerConfig,
+	) -> BlockTemplates {
+		let wallet_listener_url = if !config.burn_reward {
+			Some(config.wallet_listener_url.clone())
+		} else {
+			None
+		};
+		BlockTemplates {
+			chain,
+			tx_pool,
+			wallet_listener_url,
+			payouts: config.coinbase_payouts.clone().unwrap_or_default(),
+			ttl: config.attempt_time_per_block as u64,
+			state: Mutex::new(State {
+				prev_hash: Hash::default(),
+				candidates: vec![],
+				key_id: None,
+				deadline: 0,
+				pool_fees: 0,
+			}),
+		}
+	}
+
+	fn pool_fees(&self, height: u64) -> u64 {
+		self.tx_pool
+			.read()
+			.txpool
+			.entries
+			.iter()
+			.map(|e| e.tx.fee(height))
+			.sum()
+	}
+
+	fn longpoll_id(prev_hash: &Hash, template_id: usize) -> String {
+		format!("{}-{}", prev_hash.to_hex(), template_id)
+	}
+
+	// Current template, rebuilt on the new head, when the TTL is expired or if forced
+	fn current_template(&self, force_rebuild: bool) -> Result<api::BlockTemplate, api::Error> {
+		let head = self.chain.head()?;
+		let mut state = self.state.lock();
+		let now = Utc::now().timestamp();
+		let new_head = state.prev_hash != head.last_block_h;
+		if new_head || force_rebuild || now >= state.deadline || state.candidates.is_empty() {
+			let pool_fees = self.pool_fees(head.height + 1);
+			let (block, key_id) = self.build_candidate(state.key_id.clone()).map_err(|e| {
+				api::Error::Internal(format!("Unable to build the block template, {}", e))
+			})?;
+			if new_head {
+				state.candidates.clear();
+				state.prev_hash = head.last_block_h;
+			}
+			let difficulty = (block.header.total_difficulty() - head.total_difficulty).to_num();
+			state.key_id = key_id;
+			state.deadline = now + self.ttl as i64;
+			state.pool_fees = pool_fees;
+			state.candidates.push(Candidate { block, difficulty });
+		}
+		let template_id = state.candidates.len() - 1;
+		Ok(self.template(
+			template_id,
+			&state.candidates[template_id],
+			state.deadline,
+			Self::longpoll_id(&state.prev_hash, template_id),
+		))
+	}
+
+	// Whether the template of the long poll id is outdated: there is a new head, a newer
+	// version of the template or the pool fees are grown since the last build.
+	// Returns (outdated, pool fees are grown).
+	fn longpoll_changed(&self, longpoll_id: &str) -> Result<(bool, bool), api::Error> {
+		let head = self.chain.head()?;
+		let (current_id, built_pool_fees) = {
+			let state = self.state.lock();
+			if state.candidates.is_empty() || state.prev_hash != head.last_block_h {
+				return Ok((true, false));
+			}
+			(
+				Self::longpoll_id(&state.prev_hash, state.candidates.len() - 1),
+				state.pool_fees,
+			)
+		};
+		if current_id != longpoll_id {
+			return Ok((true, false));
+		}
+		let pool_fees = self.pool_fees(head.height + 1);
+		let grown = pool_fees > built_pool_fees
+			&& pool_fees - built_pool_fees >= built_pool_fees * LONGPOLL_POOL_FEES_GROWTH / 100;
+		Ok((grown, grown))
+	}
+
+	fn build_candidate(
+		&self,
+		key_id: Option<Identifier>,
+	) -> Result<(Block, Option<Identifier>), Error> {
+		let res = mine_block::build_block(
+			&self.chain,
+			&self.tx_pool,
+			key_id,
+			self.wallet_listener_url.clone(),
+			&self.payouts,
+			None,
+		);
+		let (block, block_fees) = match res {
+			// coinbase of the previous height was mined with this key, need the next derivation
+			Err(Error::Chain(chain::Error::DuplicateCommitment(_))) => mine_block::build_block(
+				&self.chain,
+				&self.tx_pool,
+				None,
+				self.wallet_listener_url.clone(),
+				&self.payouts,
+				None,
+			)?,
+			res => res?,
+		};
+		Ok((block, block_fees.key_id()))
+	}
+
+	fn template(
+		&self,
+		template_id: usize,
+		candidate: &Candidate,
+		deadline: i64,
+		longpoll_id: String,
+	) -> api::BlockTemplate {
+		let block = &candidate.block;
+		let mut header_buf = vec![];
+		{
+			let mut writer = ser::BinWriter::default(&mut header_buf);
+			block.header.write_pre_pow(&mut writer).unwrap();
+			block.header.pow.write_pre_pow(&mut writer).unwrap();
+		}
+		let fees = block.total_fees();
+		let commit = block
+			.outputs()
+			.iter()
+			.find(|o| o.is_coinbase())
+			.map(|o| o.commitment().to_hex())
+			.unwrap_or_default();
+		let excess = block
+			.kernels()
+			.iter()
+			.find(|k| k.is_coinbase())
+			.map(|k| k.excess.to_hex())
+			.unwrap_or_default();
+		api::BlockTemplate {
+			template_id: template_id as u64,
+			height: block.header.height,
+			prev_hash: block.header.prev_hash.to_hex(),
+			pre_pow: util::to_hex(&header_buf),
+			difficulty: candidate.difficulty,
+			ttl: deadline.saturating_sub(Utc::now().timestamp()).max(0) as u64,
+			longpoll_id,
+			coinbase: api::CoinbasePrintable {
+				reward: consensus::reward(fees, block.header.height),
+				fees,
+				commit,
+				excess,
+			},
+		}
+	}
+}
+
+impl api::BlockTemplateProvider for BlockTemplates {
+	fn get_block_template(&self) -> Result<api::BlockTemplate, api::Error> {
+		self.current_template(false)
+	}
+
+	fn wait_block_template(
+		&self,
+		longpoll_id: &str,
+		timeout: Duration,
+	) -> Result<api::BlockTemplate, api::Error> {
+		let deadline = Instant::now() + timeout;
+		loop {
+			let (changed, pool_fees_grown) = self.longpoll_changed(longpoll_id)?;
+			if changed {
+				return self.current_template(pool_fees_grown);
+			}
+			if Instant::now() >= deadline {
+				return self.current_template(false);
+			}
+			thread::sleep(LONGPOLL_CHECK_INTERVAL);
+		}
+	}
+
+	fn submit_block(&self, solution: api::BlockSolution) -> Result<String, api::Error> {
+		let (mut block, difficulty) = {
+			let state = self.state.lock();
+			match state.candidates.get(solution.template_id as usize) {
+				Some(c) if c.block.header.height == solution.height => {
+					(c.block.clone(), c.difficulty)
+				}
+				_ => {
+					return Err(api::Error::Argument(format!(
+						"Block template {} at height {} is not found, the solution is too late",
+						solution.template_id, solution.height
+					)))
+				}
+			}
+		};
+
+		block.header.pow.proof.edge_bits = solution.edge_bits;
+		block.header.pow.nonce = solution.nonce;
+		block.header.pow.proof.nonces = solution.pow;
+		if !block.header.pow.is_primary() && !block.header.pow.is_secondary() {
+			return Err(api::Error::Argument(format!(
+				"Cuckoo size {} is too small",
+				solution.edge_bits
+			)));
+		}
+		let solution_difficulty = block.header.pow.to_difficulty(block.header.height).to_num();
+		if solution_difficulty < difficulty {
+			return Err(api::Error::Argument(format!(
+				"Solution difficulty {} is lower than the block difficulty {}",
+				solution_difficulty, difficulty
+			)));
+		}
+
+		let hash = block.hash();
+		self.chain
+			.process_block(block, chain::Options::MINE)
+			.map_err(|e| api::Error::Argument(format!("Block {} is rejected, {}", hash, e)))?;
+		warn!(
+			"Block {} at height {} is mined by the external miner",
+			hash, solution.height
+		);
+		Ok(hash.to_hex())
+	}
+}
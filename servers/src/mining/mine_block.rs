@@ -18,12 +18,14 @@
 
 use crate::api;
 use crate::chain;
-use crate::common::types::Error;
-use crate::core::core::{Output, TxKernel};
+use crate::common::types::{CoinbasePayout, Error};
+use crate::core::core::{BlockHeader, Output, Transaction, TxKernel};
 use crate::core::libtx::secp_ser;
 use crate::core::libtx::ProofBuilder;
+use crate::core::pow::Difficulty;
 use crate::core::{consensus, core, global};
 use crate::keychain::{ExtKeychain, Identifier, Keychain};
+use crate::mwc::tx_generator::TxGenerator;
 use crate::ServerTxPool;
 use chrono::prelude::{DateTime, Utc};
 use mwc_util::secp::Secp256k1;
@@ -46,6 +48,14 @@ pub struct BlockFees {
 	pub height: u64,
 	/// key id
 	pub key_id: Option<Identifier>,
+	/// Amount of the coinbase output if the reward is split between several wallets,
+	/// the whole reward otherwise
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "secp_ser::opt_string_or_u64"
+	)]
+	pub amount: Option<u64>,
 }
 
 impl BlockFees {
@@ -67,17 +77,28 @@ pub struct CbData {
 }
 
 // Ensure a block suitable for mining is built and returned
-// If a wallet listener URL is not provided the reward will be "burnt"
+// If a wallet listener URL is not provided the reward will be "burnt", or paid to
+// the reward keychain (tx generator) if it is provided. If the payouts are provided,
+// the reward is split between the payout wallets instead of the wallet listener.
 // Warning: This call does not return until/unless a new block can be built
 pub fn get_block(
 	chain: &Arc<chain::Chain>,
 	tx_pool: &ServerTxPool,
 	key_id: Option<Identifier>,
 	wallet_listener_url: Option<String>,
+	payouts: &[CoinbasePayout],
+	reward_keychain: Option<&ExtKeychain>,
 ) -> (core::Block, BlockFees) {
 	let wallet_retry_interval = 5;
 	// get the latest chain state and build a block on top of it
-	let mut result = build_block(chain, tx_pool, key_id.clone(), wallet_listener_url.clone());
+	let mut result = build_block(
+		chain,
+		tx_pool,
+		key_id.clone(),
+		wallet_listener_url.clone(),
+		payouts,
+		reward_keychain,
+	);
 	while let Err(e) = result {
 		let mut new_key_id = key_id.to_owned();
 		match e {
@@ -111,18 +132,27 @@ pub fn get_block(
 			thread::sleep(Duration::from_millis(100));
 		}
 
-		result = build_block(chain, tx_pool, new_key_id, wallet_listener_url.clone());
+		result = build_block(
+			chain,
+			tx_pool,
+			new_key_id,
+			wallet_listener_url.clone(),
+			payouts,
+			reward_keychain,
+		);
 	}
 	return result.unwrap();
 }
 
 /// Builds a new block with the chain head as previous and eligible
 /// transactions from the pool.
-fn build_block(
+pub fn build_block(
 	chain: &Arc<chain::Chain>,
 	tx_pool: &ServerTxPool,
 	key_id: Option<Identifier>,
 	wallet_listener_url: Option<String>,
+	payouts: &[CoinbasePayout],
+	reward_keychain: Option<&ExtKeychain>,
 ) -> Result<(core::Block, BlockFees), Error> {
 	let head = chain.head_header()?;
 
@@ -162,20 +192,24 @@ fn build_block(
 		fees,
 		key_id,
 		height,
+		amount: None,
 	};
 
-	let (output, kernel, block_fees) = get_coinbase(wallet_listener_url, block_fees, chain.secp())?;
-	let mut b = core::Block::from_reward(
-		&head,
-		&txs,
-		output,
-		kernel,
-		difficulty.difficulty,
-		chain.secp(),
-	)?;
-
-	// making sure we're not spending time mining a useless block
-	b.validate(&head.total_kernel_offset, chain.secp())?;
+	let (coinbase, block_fees) = match wallet_listener_url {
+		Some(_) if !payouts.is_empty() => {
+			split_coinbase(payouts, block_fees, chain.secp(), create_coinbase)?
+		}
+		_ => {
+			let (output, kernel, block_fees) = get_coinbase(
+				wallet_listener_url,
+				block_fees,
+				reward_keychain,
+				chain.secp(),
+			)?;
+			(vec![(output, kernel)], block_fees)
+		}
+	};
+	let mut b = block_from_coinbase(&head, txs, coinbase, difficulty.difficulty, chain.secp())?;
 
 	b.header.pow.nonce = thread_rng().gen();
 	b.header.pow.secondary_scaling = difficulty.secondary_scaling;
@@ -218,6 +252,28 @@ fn build_block(
 	}
 }
 
+// Block takes a single reward, the other payouts go as the reward only transactions
+fn block_from_coinbase(
+	head: &BlockHeader,
+	mut txs: Vec<Transaction>,
+	mut coinbase: Vec<(core::Output, core::TxKernel)>,
+	difficulty: Difficulty,
+	secp: &Secp256k1,
+) -> Result<core::Block, Error> {
+	if coinbase.is_empty() {
+		return Err(Error::General("Block has no coinbase".into()));
+	}
+	let (output, kernel) = coinbase.remove(0);
+	for (output, kernel) in coinbase {
+		txs.push(Transaction::empty().with_output(output).with_kernel(kernel));
+	}
+	let b = core::Block::from_reward(head, &txs, output, kernel, difficulty, secp)?;
+
+	// making sure we're not spending time mining a useless block
+	b.validate(&head.total_kernel_offset, secp)?;
+	Ok(b)
+}
+
 ///
 /// Probably only want to do this when testing.
 ///
@@ -228,10 +284,30 @@ fn burn_reward(
 	warn!("Burning block fees: {:?}", block_fees);
 	let keychain = ExtKeychain::from_random_seed(global::is_floonet())?;
 	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	reward_output(&keychain, &key_id, block_fees, secp)
+}
+
+// Pay the reward to the tx generator keychain, it finds its coinbase outputs by height
+fn generator_reward(
+	keychain: &ExtKeychain,
+	block_fees: BlockFees,
+	secp: &Secp256k1,
+) -> Result<(core::Output, core::TxKernel, BlockFees), Error> {
+	debug!("Paying block reward to the tx generator: {:?}", block_fees);
+	let key_id = TxGenerator::reward_key_id(block_fees.height);
+	reward_output(keychain, &key_id, block_fees, secp)
+}
+
+fn reward_output(
+	keychain: &ExtKeychain,
+	key_id: &Identifier,
+	block_fees: BlockFees,
+	secp: &Secp256k1,
+) -> Result<(core::Output, core::TxKernel, BlockFees), Error> {
 	let (out, kernel) = crate::core::libtx::reward::output(
-		&keychain,
-		&ProofBuilder::new(&keychain),
-		&key_id,
+		keychain,
+		&ProofBuilder::new(keychain),
+		key_id,
 		block_fees.fees,
 		false,
 		block_fees.height,
@@ -241,14 +317,19 @@ fn burn_reward(
 }
 
 // Connect to the wallet listener and get coinbase.
-// Warning: If a wallet listener URL is not provided the reward will be "burnt"
+// Warning: If a wallet listener URL is not provided the reward will be "burnt",
+// unless the tx generator reward keychain is provided.
 fn get_coinbase(
 	wallet_listener_url: Option<String>,
 	block_fees: BlockFees,
+	reward_keychain: Option<&ExtKeychain>,
 	secp: &Secp256k1,
 ) -> Result<(core::Output, core::TxKernel, BlockFees), Error> {
 	match wallet_listener_url {
 		None => {
+			if let Some(keychain) = reward_keychain {
+				return generator_reward(keychain, block_fees, secp);
+			}
 			// Burn it
 			return burn_reward(block_fees, secp);
 		}
@@ -268,6 +349,75 @@ fn get_coinbase(
 	}
 }
 
+// Amounts of the payouts, proportional to their percents. The rounding remainder goes
+// to the last payout, so the amounts sum up to the reward exactly.
+fn split_amounts(reward: u64, payouts: &[CoinbasePayout]) -> Vec<u64> {
+	let total_percent: f64 = payouts.iter().map(|p| p.percent.max(0.0)).sum();
+	let mut amounts: Vec<u64> = payouts
+		.iter()
+		.map(|p| {
+			if total_percent > 0.0 {
+				(reward as f64 * p.percent.max(0.0) / total_percent).floor() as u64
+			} else {
+				0
+			}
+		})
+		.collect();
+	if let Some(last) = amounts.len().checked_sub(1) {
+		let others: u64 = amounts[..last].iter().sum();
+		amounts[last] = reward.saturating_sub(others);
+	}
+	amounts
+}
+
+// Every payout wallet builds the coinbase output for its share of the reward. The key
+// ids of the payout wallets are not tracked, every block asks for the new derivations.
+// `create_coinbase` calls the wallet by its listener url.
+fn split_coinbase(
+	payouts: &[CoinbasePayout],
+	block_fees: BlockFees,
+	secp: &Secp256k1,
+	create_coinbase: impl Fn(&str, &BlockFees) -> Result<CbData, Error>,
+) -> Result<(Vec<(core::Output, core::TxKernel)>, BlockFees), Error> {
+	let reward = consensus::reward(block_fees.fees, block_fees.height);
+	let mut coinbase = vec![];
+	for (payout, amount) in payouts.iter().zip(split_amounts(reward, payouts)) {
+		if amount == 0 {
+			continue;
+		}
+		let payout_fees = BlockFees {
+			key_id: None,
+			amount: Some(amount),
+			..block_fees.clone()
+		};
+		let res = create_coinbase(&payout.wallet_listener_url, &payout_fees)?;
+		// A wallet that doesn't support the split pays itself the whole reward
+		let excess = secp
+			.commit_value(amount)
+			.and_then(|value| secp.commit_sum(vec![res.output.commitment()], vec![value]))
+			.map_err(|e| Error::General(format!("Unable to check the coinbase, {:?}", e)))?;
+		if excess != res.kernel.excess {
+			return Err(Error::WalletComm(format!(
+				"Coinbase from {} doesn't have the amount {}, the wallet doesn't support the payout split",
+				payout.wallet_listener_url, amount
+			)));
+		}
+		coinbase.push((res.output, res.kernel));
+	}
+	debug!(
+		"split_coinbase: {:?} between {} payouts",
+		block_fees,
+		coinbase.len()
+	);
+	Ok((
+		coinbase,
+		BlockFees {
+			key_id: None,
+			..block_fees
+		},
+	))
+}
+
 /// Call the wallet API to create a coinbase output for the given block_fees.
 /// Will retry based on default "retry forever with backoff" behavior.
 fn create_coinbase(dest: &str, block_fees: &BlockFees) -> Result<CbData, Error> {
@@ -318,3 +468,120 @@ fn create_coinbase(dest: &str, block_fees: &BlockFees) -> Result<CbData, Error>
 
 	Ok(ret_val)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::core::{KernelFeatures, OutputFeatures};
+	use crate::core::libtx::{aggsig, proof};
+	use crate::keychain::SwitchCommitmentType;
+
+	// Coinbase of the payout wallet for its amount of the reward
+	fn wallet_coinbase(keychain: &ExtKeychain, block_fees: &BlockFees) -> CbData {
+		let secp = keychain.secp();
+		let amount = block_fees.amount.unwrap();
+		let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+		let switch = SwitchCommitmentType::Regular;
+		let commit = keychain.commit(amount, &key_id, switch).unwrap();
+		let builder = ProofBuilder::new(keychain);
+		let proof =
+			proof::create(keychain, &builder, amount, &key_id, switch, commit, None).unwrap();
+		let output = Output::new(OutputFeatures::Coinbase, commit, proof);
+		let over_commit = secp.commit_value(amount).unwrap();
+		let excess = secp.commit_sum(vec![commit], vec![over_commit]).unwrap();
+		let pubkey = excess.to_pubkey(secp).unwrap();
+		let msg = KernelFeatures::Coinbase.kernel_sig_msg().unwrap();
+		let excess_sig =
+			aggsig::sign_from_key_id(secp, keychain, &msg, amount, &key_id, None, Some(&pubkey))
+				.unwrap();
+		CbData {
+			output,
+			kernel: TxKernel {
+				features: KernelFeatures::Coinbase,
+				excess,
+				excess_sig,
+			},
+			key_id: Some(key_id),
+		}
+	}
+
+	#[test]
+	fn test_split_coinbase_block() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let pool = ExtKeychain::from_random_seed(false).unwrap();
+		let fee = ExtKeychain::from_random_seed(false).unwrap();
+		let secp = pool.secp();
+		let head = BlockHeader::default();
+		let block_fees = BlockFees {
+			fees: 0,
+			height: head.height + 1,
+			key_id: None,
+			amount: None,
+		};
+		let payouts = vec![
+			CoinbasePayout {
+				wallet_listener_url: "pool".to_string(),
+				percent: 97.5,
+			},
+			CoinbasePayout {
+				wallet_listener_url: "fee".to_string(),
+				percent: 2.5,
+			},
+		];
+
+		let (coinbase, _) = split_coinbase(&payouts, block_fees.clone(), secp, |url, fees| {
+			Ok(wallet_coinbase(
+				if url == "pool" { &pool } else { &fee },
+				fees,
+			))
+		})
+		.unwrap();
+		assert_eq!(coinbase.len(), 2);
+		let block = block_from_coinbase(&head, vec![], coinbase, Difficulty::min(), secp).unwrap();
+		assert_eq!(block.outputs().len(), 2);
+		assert_eq!(block.kernels().len(), 2);
+		block.validate(&head.total_kernel_offset, secp).unwrap();
+		block.verify_coinbase(secp).unwrap();
+
+		// The wallet that pays itself the whole reward is rejected
+		let res = split_coinbase(&payouts, block_fees, secp, |url, fees| {
+			let fees = BlockFees {
+				amount: Some(consensus::reward(fees.fees, fees.height)),
+				..fees.clone()
+			};
+			Ok(wallet_coinbase(
+				if url == "pool" { &pool } else { &fee },
+				&fees,
+			))
+		});
+		assert!(res.is_err());
+	}
+
+	fn payout(percent: f64) -> CoinbasePayout {
+		CoinbasePayout {
+			wallet_listener_url: "http://127.0.0.1:3415".to_string(),
+			percent,
+		}
+	}
+
+	#[test]
+	fn test_split_amounts() {
+		assert_eq!(
+			split_amounts(1000, &[payout(2.5), payout(97.5)]),
+			vec![25, 975]
+		);
+		// remainder goes to the last payout
+		assert_eq!(
+			split_amounts(100, &[payout(33.0), payout(33.0), payout(34.0)]),
+			vec![33, 33, 34]
+		);
+		assert_eq!(
+			split_amounts(10, &[payout(1.0), payout(1.0), payout(1.0)]),
+			vec![3, 3, 4]
+		);
+		// percents are relative to their sum
+		assert_eq!(split_amounts(90, &[payout(1.0), payout(2.0)]), vec![30, 60]);
+		assert_eq!(split_amounts(90, &[payout(0.0), payout(0.0)]), vec![0, 90]);
+		assert!(split_amounts(90, &[]).is_empty());
+	}
+}
@@ -0,0 +1,202 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per minute history of the stratum mining stats. The stratum server takes a sample
+//! every minute: the network hashrate and the shares of every worker login since the
+//! previous sample. The worker hashrate is estimated from the difficulty its shares were
+//! accepted at, in the same graphs per second as the network hashrate.
+
+use crate::api::{MiningStatsSample, WorkerHashrate};
+use crate::common::stats::WorkerStats;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+
+/// Samples are kept for a day
+pub const MINING_STATS_HISTORY_MINUTES: usize = 24 * 60;
+
+/// Share counters of the worker at the previous sample
+#[derive(Debug, Clone, Default)]
+struct ShareCounters {
+	accepted: u64,
+	accepted_difficulty: u64,
+	rejected: u64,
+	stale: u64,
+	blocks_found: u64,
+}
+
+impl ShareCounters {
+	fn from_stats(stats: &WorkerStats) -> ShareCounters {
+		ShareCounters {
+			accepted: stats.num_accepted,
+			accepted_difficulty: stats.accepted_difficulty,
+			rejected: stats.num_rejected,
+			stale: stats.num_stale,
+			blocks_found: stats.num_blocks_found,
+		}
+	}
+}
+
+/// Ring buffer of the mining stats samples
+#[derive(Debug)]
+pub struct MiningStatsHistory {
+	samples: VecDeque<MiningStatsSample>,
+	// counters of the previous sample by the worker login
+	counters: HashMap<String, ShareCounters>,
+	prev_time: Option<DateTime<Utc>>,
+}
+
+impl MiningStatsHistory {
+	pub fn new() -> MiningStatsHistory {
+		MiningStatsHistory {
+			samples: VecDeque::new(),
+			counters: HashMap::new(),
+			prev_time: None,
+		}
+	}
+
+	/// Add the sample for the interval since the previous one. `workers` are the stats by
+	/// the worker login, their counters are growing.
+	pub fn add_sample(
+		&mut self,
+		time: DateTime<Utc>,
+		network_hashrate: f64,
+		network_difficulty: u64,
+		workers: &[WorkerStats],
+	) {
+		let elapsed = match self.prev_time {
+			Some(prev) => (time - prev).num_milliseconds() as f64 / 1000.0,
+			None => 0.0,
+		};
+		self.prev_time = Some(time);
+
+		let mut sample = MiningStatsSample {
+			time,
+			network_hashrate,
+			network_difficulty,
+			workers: 0,
+			accepted: 0,
+			rejected: 0,
+			stale: 0,
+			blocks_found: 0,
+			worker_hashrates: vec![],
+		};
+		for ws in workers {
+			let counters = ShareCounters::from_stats(ws);
+			// First sample of the worker is the baseline, its shares can be from any time
+			let prev = match self.counters.insert(ws.id.clone(), counters.clone()) {
+				Some(prev) => prev,
+				None => continue,
+			};
+			let accepted = counters.accepted.saturating_sub(prev.accepted);
+			sample.accepted += accepted;
+			sample.rejected += counters.rejected.saturating_sub(prev.rejected);
+			sample.stale += counters.stale.saturating_sub(prev.stale);
+			sample.blocks_found += counters.blocks_found.saturating_sub(prev.blocks_found);
+			if !ws.is_connected && accepted == 0 {
+				continue;
+			}
+			sample.workers += 1;
+			// The share difficulty changes with vardiff, every share counts at its accept difficulty
+			let accepted_difficulty = counters
+				.accepted_difficulty
+				.saturating_sub(prev.accepted_difficulty);
+			let hashrate = if elapsed > 0.0 {
+				42.0 * accepted_difficulty as f64 / elapsed
+			} else {
+				0.0
+			};
+			sample.worker_hashrates.push(WorkerHashrate {
+				id: ws.id.clone(),
+				hashrate,
+				accepted,
+			});
+		}
+
+		if elapsed > 0.0 {
+			self.samples.push_back(sample);
+			while self.samples.len() > MINING_STATS_HISTORY_MINUTES {
+				self.samples.pop_front();
+			}
+		}
+	}
+
+	/// Samples of the last `minutes`, the oldest first
+	pub fn last_samples(&self, minutes: usize) -> Vec<MiningStatsSample> {
+		let skip = self.samples.len().saturating_sub(minutes);
+		self.samples.iter().skip(skip).cloned().collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::Duration;
+
+	fn worker(id: &str, accepted: u64, rejected: u64, difficulty: u64) -> WorkerStats {
+		WorkerStats {
+			id: id.to_string(),
+			is_connected: true,
+			pow_difficulty: difficulty,
+			num_accepted: accepted,
+			accepted_difficulty: accepted * difficulty,
+			num_rejected: rejected,
+			..WorkerStats::default()
+		}
+	}
+
+	#[test]
+	fn test_mining_stats_history() {
+		let mut history = MiningStatsHistory::new();
+		let start = Utc::now();
+		// baseline, no sample
+		history.add_sample(start, 10.0, 100, &[worker("rig1", 5, 1, 2)]);
+		assert!(history.last_samples(60).is_empty());
+
+		let time = start + Duration::seconds(60);
+		history.add_sample(
+			time,
+			11.0,
+			110,
+			&[worker("rig1", 11, 2, 2), worker("rig2", 3, 0, 4)],
+		);
+		let samples = history.last_samples(60);
+		assert_eq!(samples.len(), 1);
+		let sample = &samples[0];
+		assert_eq!(sample.time, time);
+		assert_eq!(sample.network_difficulty, 110);
+		// rig2 is new, its shares are the baseline
+		assert_eq!(sample.workers, 1);
+		assert_eq!(sample.accepted, 6);
+		assert_eq!(sample.rejected, 1);
+		assert_eq!(sample.worker_hashrates[0].accepted, 6);
+		assert_eq!(sample.worker_hashrates[0].hashrate, 42.0 * 12.0 / 60.0);
+
+		// The difficulty was changed, the shares are counted at their accept difficulty
+		let mut rig1 = worker("rig1", 14, 2, 8);
+		rig1.accepted_difficulty = 11 * 2 + 3 * 8;
+		history.add_sample(start + Duration::seconds(120), 11.0, 110, &[rig1]);
+		let sample = &history.last_samples(1)[0];
+		assert_eq!(sample.worker_hashrates[0].accepted, 3);
+		assert_eq!(sample.worker_hashrates[0].hashrate, 42.0 * 24.0 / 60.0);
+
+		for i in 3..(MINING_STATS_HISTORY_MINUTES as i64 + 10) {
+			history.add_sample(start + Duration::seconds(60 * i), 11.0, 110, &[]);
+		}
+		assert_eq!(
+			history.last_samples(usize::MAX).len(),
+			MINING_STATS_HISTORY_MINUTES
+		);
+		assert_eq!(history.last_samples(5).len(), 5);
+	}
+}
@@ -17,13 +17,17 @@
 // ----------------------------------------
 // Worker Object - a connected stratum client - a miner, pool, proxy, etc...
 
+use super::vardiff::VarDiff;
 use crate::common::stats::{StratumStats, WorkerStats};
+use crate::common::types::StratumWorkerConfig;
 use crate::core::consensus::graph_weight;
+use crate::util::file::get_first_line;
 use crate::util::RwLock;
 use chrono::prelude::Utc;
 use futures::channel::mpsc;
 use futures::channel::oneshot;
-use std::collections::HashMap;
+use ring::constant_time::verify_slices_are_equal;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -39,20 +43,29 @@ pub struct Worker {
 	pub agent: String,
 	pub login: Option<String>,
 	pub authenticated: bool,
+	pub vardiff: VarDiff,
 	tx: Arc<Tx>, // private, please use send_to method
 	kill_switch: Arc<RwLock<Option<oneshot::Sender<()>>>>,
 }
 
 impl Worker {
 	/// Creates a new Stratum Worker.
-	pub fn new(id: usize, ip: String, tx: Tx, kill_switch: oneshot::Sender<()>) -> Worker {
+	pub fn new(
+		id: usize,
+		ip: String,
+		tx: Tx,
+		kill_switch: oneshot::Sender<()>,
+		difficulty: u64,
+	) -> Worker {
+		let create_time = Utc::now().timestamp_millis();
 		Worker {
 			id: id,
 			ip,
-			create_time: Utc::now().timestamp_millis(),
+			create_time,
 			agent: String::from(""),
 			login: None,
 			authenticated: false,
+			vardiff: VarDiff::new(difficulty, create_time),
 			tx: Arc::new(tx),
 			kill_switch: Arc::new(RwLock::new(Some(kill_switch))),
 		}
@@ -75,6 +88,43 @@ impl Worker {
 	}
 } // impl Worker
 
+/// Registered worker accounts: the login and its password or token
+pub struct WorkerRegistry {
+	accounts: HashMap<String, String>,
+}
+
+impl WorkerRegistry {
+	/// Read the passwords of the configured workers. The worker which password file
+	/// can't be read is not able to log in.
+	pub fn new(workers: &[StratumWorkerConfig]) -> WorkerRegistry {
+		let mut accounts = HashMap::new();
+		for worker in workers {
+			match get_first_line(Some(worker.password_path.clone())) {
+				Some(password) => {
+					accounts.insert(worker.login.clone(), password);
+				}
+				None => error!(
+					"Stratum: unable to read the password of worker {} from {}",
+					worker.login, worker.password_path
+				),
+			}
+		}
+		WorkerRegistry { accounts }
+	}
+
+	pub fn logins(&self) -> Vec<String> {
+		self.accounts.keys().cloned().collect()
+	}
+
+	/// Check the worker login and password
+	pub fn check(&self, login: &str, password: &str) -> bool {
+		match self.accounts.get(login) {
+			Some(p) => verify_slices_are_equal(p.as_bytes(), password.as_bytes()).is_ok(),
+			None => false,
+		}
+	}
+}
+
 /// Collection of the active workers
 struct WorkersMap {
 	workers: RwLock<HashMap<usize, Worker>>,
@@ -133,6 +183,23 @@ impl WorkersMap {
 		workers.len()
 	}
 
+	/// Update worker variable difficulty
+	fn update_vardiff<R>(&self, worker_id: &usize, f: impl FnOnce(&mut VarDiff) -> R) -> Option<R> {
+		self.workers
+			.write()
+			.get_mut(worker_id)
+			.map(|w| f(&mut w.vardiff))
+	}
+
+	/// Update variable difficulty of all workers, returns the results that are not None
+	fn update_all_vardiff<R>(&self, f: impl Fn(&mut VarDiff) -> Option<R>) -> Vec<(usize, R)> {
+		self.workers
+			.write()
+			.iter_mut()
+			.filter_map(|(id, w)| f(&mut w.vardiff).map(|r| (*id, r)))
+			.collect()
+	}
+
 	fn get_workers_list(&self) -> Vec<Worker> {
 		self.workers.read().values().map(|w| w.clone()).collect()
 	}
@@ -146,13 +213,16 @@ pub struct WorkersList {
 	// Please never use workers_list directly, allways use getter/setter for that
 	workers_map: Arc<WorkersMap>,
 	stratum_stats: Arc<StratumStats>,
+	// Logins of the registered workers, their stats are kept while they are disconnected
+	registered: HashSet<String>,
 }
 
 impl WorkersList {
-	pub fn new(stratum_stats: Arc<StratumStats>) -> Self {
+	pub fn new(stratum_stats: Arc<StratumStats>, registered: HashSet<String>) -> Self {
 		WorkersList {
 			workers_map: Arc::new(WorkersMap::new()),
 			stratum_stats: stratum_stats,
+			registered,
 		}
 	}
 
@@ -161,12 +231,12 @@ impl WorkersList {
 		// Or just somebody want to attack the mining pool.
 		// let worker_id = stratum_stats.worker_stats.len();
 
-		let worker_id = self.stratum_stats.allocate_new_worker(
-			self.stratum_stats
-				.minimum_share_difficulty
-				.load(Ordering::Relaxed),
-		);
-		let worker = Worker::new(worker_id, ip, tx, kill_switch);
+		let difficulty = self
+			.stratum_stats
+			.minimum_share_difficulty
+			.load(Ordering::Relaxed);
+		let worker_id = self.stratum_stats.allocate_new_worker(difficulty);
+		let worker = Worker::new(worker_id, ip, tx, kill_switch, difficulty);
 
 		let num_workers = self.workers_map.add(&worker_id, worker);
 		self.stratum_stats
@@ -189,18 +259,43 @@ impl WorkersList {
 	}
 
 	pub fn remove_worker(&self, worker_id: usize) {
+		let login = self.get_worker(&worker_id).and_then(|w| w.login);
 		let num_workers = self.workers_map.remove(&worker_id);
 		self.stratum_stats
 			.num_workers
 			.store(num_workers, Ordering::Relaxed);
 		self.update_stats(worker_id, |ws| ws.is_connected = false);
+		if let Some(login) = login {
+			self.refresh_login_stats(&login);
+		}
+	}
+
+	// The same login can be used by several connections. The stats of the login that is
+	// not registered are dropped with its last connection, so the random logins don't pile up.
+	fn refresh_login_stats(&self, login: &str) {
+		let connected = self
+			.get_workers_list()
+			.iter()
+			.any(|w| w.login.as_deref() == Some(login));
+		if connected || self.registered.contains(login) {
+			self.stratum_stats
+				.update_login_stats(login, |ls| ls.is_connected = connected);
+		} else {
+			self.stratum_stats.remove_login_stats(login);
+		}
 	}
 
+	// The password is checked by the caller
 	pub fn login(&self, worker_id: &usize, login: String, agent: String) -> bool {
 		if let Some(mut worker) = self.get_worker(worker_id) {
-			worker.login = Some(login);
+			let difficulty = worker.vardiff.difficulty();
+			self.stratum_stats.update_login_stats(&login, |ls| {
+				ls.is_connected = true;
+				ls.last_seen = SystemTime::now();
+				ls.pow_difficulty = difficulty;
+			});
+			let previous = worker.login.replace(login);
 
-			// XXX TODO Future - Validate password?
 			// Here you can add you code and work with worker as long as you need. Here nothing is blocked
 
 			worker.agent = agent;
@@ -208,19 +303,41 @@ impl WorkersList {
 
 			// Apply what you changed to the workrer
 			self.update_worker(&worker);
+			// The connection is moved from the previous login
+			if let Some(previous) = previous.filter(|p| Some(p) != worker.login.as_ref()) {
+				self.refresh_login_stats(&previous);
+			}
 			return true;
 		}
 
 		false
 	}
 
+	/// Reset the login of the worker, it is not authenticated anymore
+	pub fn logout(&self, worker_id: &usize) {
+		if let Some(mut worker) = self.get_worker(worker_id) {
+			let previous = worker.login.take();
+			worker.authenticated = false;
+			self.update_worker(&worker);
+			if let Some(previous) = previous {
+				self.refresh_login_stats(&previous);
+			}
+		}
+	}
+
+	pub fn is_authenticated(&self, worker_id: usize) -> bool {
+		self.get_worker(&worker_id)
+			.map(|w| w.authenticated)
+			.unwrap_or(false)
+	}
+
 	pub fn get_stats(&self, worker_id: usize) -> Option<WorkerStats> {
 		self.stratum_stats.get_stats(worker_id)
 	}
 
 	pub fn last_seen(&self, worker_id: usize) {
 		//self.stratum_stats.write().worker_stats[worker_id].last_seen = SystemTime::now();
-		self.update_stats(worker_id, |ws| ws.last_seen = SystemTime::now());
+		self.update_share_stats(worker_id, |ws| ws.last_seen = SystemTime::now());
 	}
 
 	// f - must be very functional, no blocking allowed
@@ -228,6 +345,60 @@ impl WorkersList {
 		self.stratum_stats.update_stats(worker_id, f);
 	}
 
+	/// Update the stats of the worker connection and of its login
+	// f - must be very functional, no blocking allowed
+	pub fn update_share_stats(&self, worker_id: usize, f: impl Fn(&mut WorkerStats) -> ()) {
+		self.update_stats(worker_id, &f);
+		if let Some(login) = self.get_worker(&worker_id).and_then(|w| w.login) {
+			self.stratum_stats.update_login_stats(&login, &f);
+		}
+	}
+
+	/// Count the accepted share for the worker variable difficulty
+	pub fn add_share(&self, worker_id: usize) {
+		self.workers_map
+			.update_vardiff(&worker_id, |vardiff| vardiff.add_share());
+	}
+
+	/// Current share difficulty of the worker
+	pub fn worker_difficulty(&self, worker_id: usize) -> Option<u64> {
+		self.workers_map
+			.get(&worker_id)
+			.map(|w| w.vardiff.difficulty())
+	}
+
+	/// Minimal share difficulty that is accepted from the worker
+	pub fn accept_difficulty(&self, worker_id: usize) -> Option<u64> {
+		self.workers_map
+			.get(&worker_id)
+			.map(|w| w.vardiff.accept_difficulty())
+	}
+
+	/// Retarget the difficulty of the workers which window is over.
+	/// Returns the workers with the changed difficulty.
+	pub fn retarget_workers(
+		&self,
+		now: i64,
+		shares_per_minute: u32,
+		retarget_ms: i64,
+		min_difficulty: u64,
+		max_difficulty: u64,
+	) -> Vec<(usize, u64)> {
+		let changed = self.workers_map.update_all_vardiff(|vardiff| {
+			vardiff.retarget(
+				now,
+				shares_per_minute,
+				retarget_ms,
+				min_difficulty,
+				max_difficulty,
+			)
+		});
+		for (worker_id, difficulty) in &changed {
+			self.update_share_stats(*worker_id, |ws| ws.pow_difficulty = *difficulty);
+		}
+		changed
+	}
+
 	pub fn send_to(&self, worker_id: &usize, msg: String) {
 		if let Some(tx) = self.workers_map.get_tx(worker_id) {
 			if tx.unbounded_send(msg).is_err() {
@@ -236,6 +407,17 @@ impl WorkersList {
 		}
 	}
 
+	/// Send the job to every worker, or only to the authenticated ones. The job message is
+	/// built for every worker.
+	pub fn send_jobs(&self, authenticated_only: bool, mut job: impl FnMut(&Worker) -> String) {
+		for worker in self.get_workers_list() {
+			if authenticated_only && !worker.authenticated {
+				continue;
+			}
+			self.send_to(&worker.id, job(&worker));
+		}
+	}
+
 	pub fn broadcast(&self, msg: String) {
 		let keys: Vec<usize> = self.workers_map.get_woker_id_list();
 
@@ -277,6 +459,10 @@ impl WorkersList {
 			.store(network_hashrate, Ordering::Relaxed);
 	}
 
+	pub fn record_history_sample(&self) {
+		self.stratum_stats.record_history_sample();
+	}
+
 	pub fn update_edge_bits(&self, edge_bits: u16) {
 		self.stratum_stats
 			.edge_bits
@@ -289,3 +475,95 @@ impl WorkersList {
 			.fetch_add(1, Ordering::Relaxed);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	fn registry(dir: &std::path::Path) -> WorkerRegistry {
+		let alice = dir.join("alice");
+		fs::write(&alice, "secret\n").unwrap();
+		WorkerRegistry::new(&[
+			StratumWorkerConfig {
+				login: "alice".to_string(),
+				password_path: alice.to_str().unwrap().to_string(),
+			},
+			StratumWorkerConfig {
+				login: "bob".to_string(),
+				password_path: dir.join("missing").to_str().unwrap().to_string(),
+			},
+		])
+	}
+
+	fn add_worker(workers: &WorkersList) -> (usize, mpsc::UnboundedReceiver<String>) {
+		let (tx, rx) = mpsc::unbounded();
+		let (kill_switch, _) = oneshot::channel();
+		(
+			workers.add_worker("127.0.0.1".to_string(), tx, kill_switch),
+			rx,
+		)
+	}
+
+	fn login_stats(stats: &StratumStats, login: &str) -> Option<WorkerStats> {
+		stats.get_login_stats().into_iter().find(|s| s.id == login)
+	}
+
+	#[test]
+	fn worker_registry_check() {
+		let dir = tempfile::tempdir().unwrap();
+		let registry = registry(dir.path());
+
+		assert!(registry.check("alice", "secret"));
+		assert!(!registry.check("alice", "secret2"));
+		assert!(!registry.check("alice", ""));
+		assert!(!registry.check("carol", "secret"));
+		// The password file can't be read, the worker can't log in
+		assert!(!registry.check("bob", ""));
+		assert_eq!(registry.logins(), vec!["alice".to_string()]);
+	}
+
+	#[test]
+	fn login_stats_of_unregistered_logins() {
+		let stats = Arc::new(StratumStats::default());
+		let registered = vec!["alice".to_string()].into_iter().collect();
+		let workers = WorkersList::new(stats.clone(), registered);
+
+		let (w1, _rx1) = add_worker(&workers);
+		let (w2, _rx2) = add_worker(&workers);
+		assert!(workers.login(&w1, "alice".to_string(), "test".to_string()));
+		assert!(workers.login(&w2, "carol".to_string(), "test".to_string()));
+		assert!(login_stats(&stats, "carol").unwrap().is_connected);
+
+		// The unregistered login is dropped with its last connection
+		workers.remove_worker(w2);
+		assert!(login_stats(&stats, "carol").is_none());
+
+		// Re-login moves the connection from the previous login
+		assert!(workers.login(&w1, "dave".to_string(), "test".to_string()));
+		assert!(!login_stats(&stats, "alice").unwrap().is_connected);
+		assert!(login_stats(&stats, "dave").unwrap().is_connected);
+
+		workers.logout(&w1);
+		assert!(!workers.is_authenticated(w1));
+		assert!(workers.get_worker(&w1).unwrap().login.is_none());
+		assert!(login_stats(&stats, "dave").is_none());
+		assert!(login_stats(&stats, "alice").is_some());
+	}
+
+	#[test]
+	fn send_jobs_to_authenticated() {
+		let workers = WorkersList::new(Arc::new(StratumStats::default()), HashSet::new());
+		let (w1, mut rx1) = add_worker(&workers);
+		let (_w2, mut rx2) = add_worker(&workers);
+		workers.login(&w1, "alice".to_string(), "test".to_string());
+
+		workers.send_jobs(true, |w| format!("job {}", w.id));
+		assert_eq!(rx1.try_next().unwrap(), Some(format!("job {}", w1)));
+		assert!(rx2.try_next().is_err());
+
+		workers.send_jobs(false, |_| "job".to_string());
+		assert_eq!(rx1.try_next().unwrap(), Some("job".to_string()));
+		assert_eq!(rx2.try_next().unwrap(), Some("job".to_string()));
+	}
+}
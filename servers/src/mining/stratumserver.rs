@@ -18,8 +18,12 @@
 use futures::channel::{mpsc, oneshot};
 use futures::pin_mut;
 use futures::{SinkExt, StreamExt, TryStreamExt};
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::runtime::Runtime;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
 use tokio_util::codec::{Framed, LinesCodec};
 
 use crate::util::RwLock;
@@ -31,7 +35,8 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::{cmp, thread};
 
-use super::stratum_data::WorkersList;
+use super::stratum_data::{WorkerRegistry, WorkersList};
+use crate::api::TLSConfig;
 use crate::chain::{self, SyncState};
 use crate::common::stats::StratumStats;
 use crate::common::types::StratumServerConfig;
@@ -47,6 +52,13 @@ use crate::util::ToHex;
 use crate::ServerTxPool;
 use std::cmp::min;
 
+/// Period of the mining stats history samples
+const HISTORY_SAMPLE_PERIOD_MS: i64 = 60_000;
+// The worker is registered before the TLS handshake, the stalled handshake is dropped
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// The stratum requests are small, the submit with the proof is about 1KB
+const WS_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
 // ----------------------------------------
 // http://www.jsonrpc.org/specification
 // RPC Methods
@@ -126,6 +138,12 @@ impl RpcError {
 			message: "Invalid Request".to_string(),
 		}
 	}
+	pub fn unauthorized() -> Self {
+		RpcError {
+			code: -32500,
+			message: "Unauthorized worker".to_string(),
+		}
+	}
 }
 
 impl From<RpcError> for Value {
@@ -210,6 +228,8 @@ struct Handler {
 	ip_pool: Arc<connections::StratumIpPool>,
 	worker_connections: Arc<AtomicI32>,
 	config: StratumServerConfig,
+	// None if any login is accepted
+	registry: Option<WorkerRegistry>,
 }
 
 impl Handler {
@@ -219,9 +239,23 @@ impl Handler {
 			"Stratum ip_pool_ban_history_s value must has reasonable value"
 		);
 
+		let registry = stratum.config.workers.as_ref().map(|workers| {
+			let registry = WorkerRegistry::new(workers);
+			// Registered workers are reported before they connect
+			for login in registry.logins() {
+				stratum.stratum_stats.update_login_stats(&login, |_| ());
+			}
+			registry
+		});
+
+		let registered = registry
+			.as_ref()
+			.map(|r| r.logins().into_iter().collect())
+			.unwrap_or_default();
+
 		Handler {
 			id: stratum.id.clone(),
-			workers: Arc::new(WorkersList::new(stratum.stratum_stats.clone())),
+			workers: Arc::new(WorkersList::new(stratum.stratum_stats.clone(), registered)),
 			sync_state: stratum.sync_state.clone(),
 			chain: stratum.chain.clone(),
 			current_state: Arc::new(RwLock::new(State::new(
@@ -230,15 +264,105 @@ impl Handler {
 			ip_pool: stratum.ip_pool.clone(),
 			worker_connections: stratum.worker_connections.clone(),
 			config: stratum.config.clone(),
+			registry,
+		}
+	}
+
+	// Check the connection against the IP lists and the bans
+	fn is_accepted_ip(&self, ip: &String) -> bool {
+		let config = &self.config;
+		if config.ip_white_list.contains(ip) {
+			info!(
+				"Stratum accepting new connection for {}, it is in white list",
+				ip
+			);
+			true
+		} else if config.ip_black_list.contains(ip) {
+			warn!(
+				"Stratum rejecting new connection for {}, it is in black list",
+				ip
+			);
+			false
+		} else if config.ip_tracking && self.ip_pool.is_banned(ip, true) {
+			warn!("Rejecting connection from ip {} because ip_tracking is active and that ip is banned.", ip);
+			false
+		} else {
+			info!("Stratum accepting new connection for {}", ip);
+			true
 		}
 	}
 
+	// Register the worker of the new connection. Returns the worker id, the receiver of
+	// the messages to the worker and the worker killer switch.
+	fn add_worker(
+		&self,
+		ip: &String,
+	) -> (
+		usize,
+		mpsc::UnboundedReceiver<String>,
+		oneshot::Receiver<()>,
+	) {
+		self.worker_connections.fetch_add(1, Ordering::Relaxed);
+
+		// Worker IO channels
+		let (tx, rx) = mpsc::unbounded();
+
+		// Worker killer switch
+		let (kill_switch, kill_switch_receiver) = oneshot::channel::<()>();
+
+		let worker_id = self.workers.add_worker(ip.clone(), tx, kill_switch);
+		info!("Worker {} connected", worker_id);
+		self.ip_pool.add_worker(ip);
+		(worker_id, rx, kill_switch_receiver)
+	}
+
+	// With the registered workers only login and keepalive are allowed before the login
+	fn is_allowed(&self, method: &str, worker_id: usize) -> bool {
+		self.registry.is_none()
+			|| method == "login"
+			|| method == "keepalive"
+			|| self.workers.is_authenticated(worker_id)
+	}
+
 	fn handle_rpc_requests(&self, request: RpcRequest, worker_id: usize, ip: &String) -> String {
 		self.workers.last_seen(worker_id);
 
 		// Call the handler function for requested method
-		let response = match request.method.as_str() {
-			"login" => match self.handle_login(request.params, &worker_id) {
+		let response = if !self.is_allowed(&request.method, worker_id) {
+			self.ip_pool.report_fail_noise(ip);
+			Err(RpcError::unauthorized())
+		} else {
+			self.call_method(&request, worker_id, ip)
+		};
+
+		// Package the reply as RpcResponse json
+		let resp = match response {
+			Err(rpc_error) => RpcResponse {
+				id: request.id,
+				jsonrpc: String::from("2.0"),
+				method: request.method,
+				result: None,
+				error: Some(rpc_error.into()),
+			},
+			Ok(response) => RpcResponse {
+				id: request.id,
+				jsonrpc: String::from("2.0"),
+				method: request.method,
+				result: Some(response),
+				error: None,
+			},
+		};
+		serde_json::to_string(&resp).unwrap_or("{}".to_string())
+	}
+
+	fn call_method(
+		&self,
+		request: &RpcRequest,
+		worker_id: usize,
+		ip: &String,
+	) -> Result<Value, RpcError> {
+		match request.method.as_str() {
+			"login" => match self.handle_login(request.params.clone(), &worker_id) {
 				Ok(r) => {
 					self.ip_pool.report_ok_login(ip);
 					Ok(r)
@@ -249,7 +373,7 @@ impl Handler {
 				}
 			},
 			"submit" => {
-				let res = self.handle_submit(request.params, worker_id);
+				let res = self.handle_submit(request.params.clone(), worker_id);
 				// this key_id has been used now, reset
 				let res = match res {
 					Ok(ok) => {
@@ -273,7 +397,7 @@ impl Handler {
 				if self.sync_state.is_syncing() {
 					Err(RpcError::node_is_syncing())
 				} else {
-					self.handle_getjobtemplate()
+					self.handle_getjobtemplate(worker_id)
 				}
 			}
 			"status" => self.handle_status(worker_id),
@@ -282,31 +406,23 @@ impl Handler {
 				// Called undefined method
 				Err(RpcError::method_not_found())
 			}
-		};
-
-		// Package the reply as RpcResponse json
-		let resp = match response {
-			Err(rpc_error) => RpcResponse {
-				id: request.id,
-				jsonrpc: String::from("2.0"),
-				method: request.method,
-				result: None,
-				error: Some(rpc_error.into()),
-			},
-			Ok(response) => RpcResponse {
-				id: request.id,
-				jsonrpc: String::from("2.0"),
-				method: request.method,
-				result: Some(response),
-				error: None,
-			},
-		};
-		serde_json::to_string(&resp).unwrap_or("{}".to_string())
+		}
 	}
 
 	fn handle_login(&self, params: Option<Value>, worker_id: &usize) -> Result<Value, RpcError> {
 		// Note !!!! self.workers.login HAS to be there.
 		let params: LoginParams = parse_params(params)?;
+		if let Some(registry) = &self.registry {
+			if !registry.check(&params.login, &params.pass) {
+				warn!(
+					"(Server ID: {}) Worker {} failed to login as {}",
+					self.id, worker_id, params.login
+				);
+				// The failed login drops the previous one
+				self.workers.logout(worker_id);
+				return Err(RpcError::unauthorized());
+			}
+		}
 		if !self.workers.login(worker_id, params.login, params.agent) {
 			return Ok("false".into()); // you migth change that response, Possible solution Error 'Unauthorized worker'
 		}
@@ -343,9 +459,14 @@ impl Handler {
 		return Ok(response);
 	}
 	// Handle GETJOBTEMPLATE message
-	fn handle_getjobtemplate(&self) -> Result<Value, RpcError> {
+	fn handle_getjobtemplate(&self, worker_id: usize) -> Result<Value, RpcError> {
 		// Build a JobTemplate from a BlockHeader and return JSON
-		let job_template = self.build_block_template();
+		let mut job_template = self.build_block_template();
+		if self.config.vardiff_enabled {
+			if let Some(difficulty) = self.workers.worker_difficulty(worker_id) {
+				job_template.difficulty = difficulty;
+			}
+		}
 		let response = serde_json::to_value(&job_template).unwrap_or(Value::Null);
 		debug!(
 			"(Server ID: {}) sending block {} with id {} to single worker",
@@ -395,7 +516,7 @@ impl Handler {
 		// Validate parameters
 		let params: SubmitParams = parse_params(params)?;
 
-		let (b, header_height, minimum_share_difficulty, current_difficulty) = {
+		let (b, header_height, mut minimum_share_difficulty, current_difficulty) = {
 			let state = self.current_state.read();
 
 			(
@@ -408,6 +529,11 @@ impl Handler {
 				state.current_difficulty,
 			)
 		};
+		if self.config.vardiff_enabled {
+			if let Some(difficulty) = self.workers.accept_difficulty(worker_id) {
+				minimum_share_difficulty = cmp::min(difficulty, current_difficulty);
+			}
+		}
 
 		// Find the correct version of the block to match this header
 		if params.height != header_height || b.is_none() {
@@ -416,7 +542,8 @@ impl Handler {
 				"(Server ID: {}) Share at height {}, edge_bits {}, nonce {}, job_id {} submitted too late",
 				self.id, params.height, params.edge_bits, params.nonce, params.job_id,
 			);
-			self.workers.update_stats(worker_id, |ws| ws.num_stale += 1);
+			self.workers
+				.update_share_stats(worker_id, |ws| ws.num_stale += 1);
 			return Err(RpcError::too_late());
 		}
 
@@ -437,7 +564,7 @@ impl Handler {
 				self.id, params.height, b.hash(), params.edge_bits, params.nonce, params.job_id,
 			);
 			self.workers
-				.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+				.update_share_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
 			return Err(RpcError::cannot_validate());
 		}
 
@@ -454,7 +581,7 @@ impl Handler {
 				self.id, params.height, b.hash(), params.edge_bits, params.nonce, params.job_id, unscaled_share_difficulty, minimum_share_difficulty,
 			);
 			self.workers
-				.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+				.update_share_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
 			return Err(RpcError::too_low_difficulty());
 		}
 
@@ -475,12 +602,12 @@ impl Handler {
 					e,
 				);
 				self.workers
-					.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+					.update_share_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
 				return Err(RpcError::cannot_validate());
 			}
 			share_is_block = true;
 			self.workers
-				.update_stats(worker_id, |worker_stats| worker_stats.num_blocks_found += 1);
+				.update_share_stats(worker_id, |worker_stats| worker_stats.num_blocks_found += 1);
 			self.workers.increment_block_found();
 			// Log message to make it obvious we found a block
 			let stats = self
@@ -511,7 +638,7 @@ impl Handler {
 					res,
 				);
 				self.workers
-					.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+					.update_share_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
 				return Err(RpcError::cannot_validate());
 			}
 		}
@@ -537,8 +664,11 @@ impl Handler {
 			);
 		}
 
-		self.workers
-			.update_stats(worker_id, |worker_stats| worker_stats.num_accepted += 1);
+		self.workers.update_share_stats(worker_id, |worker_stats| {
+			worker_stats.num_accepted += 1;
+			worker_stats.accepted_difficulty += minimum_share_difficulty;
+		});
+		self.workers.add_share(worker_id);
 		let submit_response = if share_is_block {
 			format!("blockfound - {}", b.hash().to_hex())
 		} else {
@@ -550,11 +680,9 @@ impl Handler {
 		));
 	} // handle submit a solution
 
-	fn broadcast_job(&self) {
-		debug!("broadcast job");
-		// Package new block into RpcRequest
-		let job_template = self.build_block_template();
-		let job_template_json = serde_json::to_string(&job_template).unwrap_or("{}".to_string());
+	// Package the job template into the 'job' RpcRequest
+	fn job_request_json(job_template: &JobTemplate) -> String {
+		let job_template_json = serde_json::to_string(job_template).unwrap_or("{}".to_string());
 		// Issue #1159 - use a serde_json Value type to avoid extra quoting
 		let job_template_value: Value =
 			serde_json::from_str(&job_template_json).unwrap_or(Value::Null);
@@ -564,12 +692,59 @@ impl Handler {
 			method: String::from("job"),
 			params: Some(job_template_value),
 		};
-		let job_request_json = serde_json::to_string(&job_request).unwrap_or("{}".to_string());
+		serde_json::to_string(&job_request).unwrap_or("{}".to_string())
+	}
+
+	fn broadcast_job(&self) {
+		debug!("broadcast job");
+		let mut job_template = self.build_block_template();
 		debug!(
 			"(Server ID: {}) sending block {} with id {} to stratum clients",
 			self.id, job_template.height, job_template.job_id,
 		);
-		self.workers.broadcast(job_request_json);
+		if self.config.vardiff_enabled || self.registry.is_some() {
+			// Every worker mines with its own difficulty, the workers that are not logged
+			// in don't get the jobs if the logins are checked
+			let vardiff_enabled = self.config.vardiff_enabled;
+			self.workers.send_jobs(self.registry.is_some(), |worker| {
+				if vardiff_enabled {
+					job_template.difficulty = worker.vardiff.difficulty();
+				}
+				Self::job_request_json(&job_template)
+			});
+		} else {
+			self.workers
+				.broadcast(Self::job_request_json(&job_template));
+		}
+	}
+
+	// Retarget the workers difficulty toward the configured shares rate. The new
+	// difficulty is announced to the worker with the current job.
+	fn retarget_workers(&self, now: i64) {
+		let (min_difficulty, max_difficulty) = {
+			let state = self.current_state.read();
+			(state.minimum_share_difficulty, state.current_difficulty)
+		};
+		let changed = self.workers.retarget_workers(
+			now,
+			self.config.vardiff_shares_per_minute,
+			self.config.vardiff_retarget_s * 1000,
+			min_difficulty,
+			max_difficulty,
+		);
+		if changed.is_empty() {
+			return;
+		}
+		let mut job_template = self.build_block_template();
+		for (worker_id, difficulty) in changed {
+			debug!(
+				"(Server ID: {}) worker {} difficulty is retargeted to {}",
+				self.id, worker_id, difficulty
+			);
+			job_template.difficulty = difficulty;
+			self.workers
+				.send_to(&worker_id, Self::job_request_json(&job_template));
+		}
 	}
 
 	pub fn run(&self, config: &StratumServerConfig, tx_pool: &ServerTxPool) {
@@ -587,6 +762,9 @@ impl Handler {
 		let mut next_worker_checking = Utc::now().timestamp_millis() + worker_checking_period;
 		let mut next_ip_pool_checking =
 			Utc::now().timestamp_millis() + self.config.ip_pool_ban_history_s * 1000 / 10;
+		// The first sample is the baseline of the shares counters
+		self.workers.record_history_sample();
+		let mut next_history_sample = Utc::now().timestamp_millis() + HISTORY_SAMPLE_PERIOD_MS;
 
 		loop {
 			// get the latest chain state
@@ -613,6 +791,8 @@ impl Handler {
 						tx_pool,
 						self.current_state.read().current_key_id.clone(),
 						wallet_listener_url,
+						config.coinbase_payouts.as_deref().unwrap_or(&[]),
+						None,
 					);
 
 					{
@@ -660,6 +840,10 @@ impl Handler {
 			if cur_time > next_worker_checking {
 				next_worker_checking = cur_time + worker_checking_period;
 
+				if config.vardiff_enabled {
+					self.retarget_workers(cur_time);
+				}
+
 				if config.ip_tracking {
 					let mut banned_ips = self.ip_pool.get_banned_ips();
 
@@ -722,16 +906,30 @@ impl Handler {
 					.retire_old_events(cur_time - self.config.ip_pool_ban_history_s * 1000);
 			}
 
+			if cur_time > next_history_sample {
+				next_history_sample = cur_time + HISTORY_SAMPLE_PERIOD_MS;
+				self.workers.record_history_sample();
+			}
+
 			// sleep before restarting loop
 			thread::sleep(Duration::from_millis(5));
 		} // Main Loop
 	}
 }
 
+// Plain or TLS stream of the worker connection
+trait AsyncReadWrite: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncReadWrite for T {}
+
 // ----------------------------------------
 // Worker Factory Thread Function
 // Returned runtime must be kept for a server lifetime
-fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
+fn accept_connections(
+	listen_addr: SocketAddr,
+	ws_listen_addr: Option<SocketAddr>,
+	handler: Arc<Handler>,
+	tls_acceptor: Option<TlsAcceptor>,
+) {
 	info!("Start tokio stratum server");
 
 	if !handler.config.ip_white_list.is_empty() {
@@ -761,6 +959,14 @@ fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
 	}
 
 	let task = async move {
+		if let Some(ws_listen_addr) = ws_listen_addr {
+			tokio::spawn(accept_ws_connections(
+				ws_listen_addr,
+				handler.clone(),
+				tls_acceptor.clone(),
+			));
+		}
+
 		let listener = match TcpListener::bind(&listen_addr).await {
 			Ok(listener) => listener,
 			Err(e) => {
@@ -773,114 +979,108 @@ fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
 		};
 
 		let server = async_stream::stream! {
-            loop {
-                match listener.accept().await {
-                    Ok((socket, _)) => yield socket,
-                    Err(e) => {
-                        error!("accept error = {:?}", e);
-                        continue;
-                    }
-                }
-            }
-        }
-            .for_each(move |socket| {
-                let peer_addr = socket
-                    .peer_addr()
-                    .unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 1234));
-                let ip = peer_addr.ip().to_string();
-
-                let handler = handler.clone();
-
-                async move {
-                    let config = &handler.config;
-
-                    let accepting_connection = if config.ip_white_list.contains(&ip) {
-                        info!(
-                            "Stratum accepting new connection for {}, it is in white list",
-                            ip
-                        );
-                        true
-                    } else if config.ip_black_list.contains(&ip) {
-                        warn!(
-                            "Stratum rejecting new connection for {}, it is in black list",
-                            ip
-                        );
-                        false
-                    } else if config.ip_tracking && handler.ip_pool.is_banned(&ip, true) {
-                        warn!("Rejecting connection from ip {} because ip_tracking is active and that ip is banned.", ip);
-                        false
-                    } else {
-                        info!("Stratum accepting new connection for {}", ip);
-                        true
-                    };
-
-                    handler.worker_connections.fetch_add(1, Ordering::Relaxed);
-                    let ip_pool = handler.ip_pool.clone();
-
-                    // Worker IO channels
-                    let (tx, mut rx) = mpsc::unbounded();
-
-                    // Worker killer switch
-                    let (kill_switch, kill_switch_receiver) = oneshot::channel::<()>();
-
-                    let worker_id = handler.workers.add_worker(ip.clone(), tx, kill_switch);
-                    info!("Worker {} connected", worker_id);
-                    ip_pool.add_worker(&ip);
-
-                    let framed = Framed::new(socket, LinesCodec::new());
-                    let (mut writer, mut reader) = framed.split();
-
-                    let h = handler.clone();
-                    let workers = h.workers.clone();
-                    let ip_clone = ip.clone();
-                    let ip_clone2 = ip.clone();
-                    let ip_pool_clone2 = ip_pool.clone();
-                    let ip_pool_clone3 = ip_pool.clone();
-
-                    let read = async move {
-                        if accepting_connection {
-                            while let Some(line) = reader.try_next().await.map_err(|e| {
-                                ip_pool_clone2.report_fail_noise(&ip_clone2);
-                                error!("error processing request to stratum, {}", e)
-                            })? {
-                                if !line.is_empty() {
-                                    debug!("get request: {}", line);
-                                    let request = serde_json::from_str(&line).map_err(|e| {
-                                        ip_pool_clone3.report_fail_noise(&ip_clone2);
-                                        error!("error serializing line: {}", e)
-                                    })?;
-                                    let resp = h.handle_rpc_requests(request, worker_id, &ip_clone);
-                                    workers.send_to(&worker_id, resp);
-                                }
-                            }
-                        }
-
-                        Result::<_, ()>::Ok(())
-                    };
-
-                    let write = async move {
-                        if accepting_connection {
-                            while let Some(line) = rx.next().await {
-                                // No need to add line separator for the client, because
-                                // Frames with LinesCodec does that.
-                                writer.send(line).await.map_err(|e| {
-                                    error!("stratum cannot send data to worker, {}", e)
-                                })?;
-                            }
-                        }
-                        Result::<_, ()>::Ok(())
-                    };
-
-                    let task = async move {
-                        pin_mut!(read, write);
-                        let rw = futures::future::select(read, write);
-                        futures::future::select(rw, kill_switch_receiver).await;
-                        handler.workers.remove_worker(worker_id);
-                        info!("Worker {} disconnected", worker_id);
-                    };
-                    tokio::spawn(task);
-                }
-            });
+			loop {
+				match listener.accept().await {
+					Ok((socket, _)) => yield socket,
+					Err(e) => {
+						error!("accept error = {:?}", e);
+						continue;
+					}
+				}
+			}
+		}
+		.for_each(move |socket| {
+			let peer_addr = socket
+				.peer_addr()
+				.unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 1234));
+			let ip = peer_addr.ip().to_string();
+
+			let handler = handler.clone();
+			let tls_acceptor = tls_acceptor.clone();
+
+			async move {
+				let accepting_connection = handler.is_accepted_ip(&ip);
+				let ip_pool = handler.ip_pool.clone();
+				let (worker_id, mut rx, kill_switch_receiver) = handler.add_worker(&ip);
+
+				let h = handler.clone();
+				let workers = h.workers.clone();
+				let ip_clone = ip.clone();
+				let ip_clone2 = ip.clone();
+				let ip_pool_clone2 = ip_pool.clone();
+				let ip_pool_clone3 = ip_pool.clone();
+
+				let task = async move {
+					// TLS handshake is done in the worker task, a slow client doesn't block the listener
+					let socket: Box<dyn AsyncReadWrite> = match tls_acceptor {
+						Some(acceptor) => match tokio::time::timeout(
+							TLS_HANDSHAKE_TIMEOUT,
+							acceptor.accept(socket),
+						)
+						.await
+						{
+							Ok(Ok(tls_socket)) => Box::new(tls_socket),
+							Ok(Err(e)) => {
+								warn!("Stratum TLS handshake with {} failed, {}", ip_clone, e);
+								ip_pool_clone3.report_fail_noise(&ip_clone);
+								handler.workers.remove_worker(worker_id);
+								return;
+							}
+							Err(_) => {
+								warn!("Stratum TLS handshake with {} timed out", ip_clone);
+								ip_pool_clone3.report_fail_noise(&ip_clone);
+								handler.workers.remove_worker(worker_id);
+								return;
+							}
+						},
+						None => Box::new(socket),
+					};
+					let framed = Framed::new(socket, LinesCodec::new());
+					let (mut writer, mut reader) = framed.split();
+
+					let read = async move {
+						if accepting_connection {
+							while let Some(line) = reader.try_next().await.map_err(|e| {
+								ip_pool_clone2.report_fail_noise(&ip_clone2);
+								error!("error processing request to stratum, {}", e)
+							})? {
+								if !line.is_empty() {
+									debug!("get request: {}", line);
+									let request = serde_json::from_str(&line).map_err(|e| {
+										ip_pool_clone3.report_fail_noise(&ip_clone2);
+										error!("error serializing line: {}", e)
+									})?;
+									let resp = h.handle_rpc_requests(request, worker_id, &ip_clone);
+									workers.send_to(&worker_id, resp);
+								}
+							}
+						}
+
+						Result::<_, ()>::Ok(())
+					};
+
+					let write = async move {
+						if accepting_connection {
+							while let Some(line) = rx.next().await {
+								// No need to add line separator for the client, because
+								// Frames with LinesCodec does that.
+								writer.send(line).await.map_err(|e| {
+									error!("stratum cannot send data to worker, {}", e)
+								})?;
+							}
+						}
+						Result::<_, ()>::Ok(())
+					};
+
+					pin_mut!(read, write);
+					let rw = futures::future::select(read, write);
+					futures::future::select(rw, kill_switch_receiver).await;
+					handler.workers.remove_worker(worker_id);
+					info!("Worker {} disconnected", worker_id);
+				};
+				tokio::spawn(task);
+			}
+		});
 		server.await
 	};
 
@@ -888,6 +1088,140 @@ fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
 	rt.block_on(task);
 }
 
+// ----------------------------------------
+// WebSocket listener. Every text message is a single JSON-RPC request, the responses
+// and the jobs are sent back as the text messages, exactly the same as the TCP lines.
+async fn accept_ws_connections(
+	listen_addr: SocketAddr,
+	handler: Arc<Handler>,
+	tls_acceptor: Option<TlsAcceptor>,
+) {
+	let listener = match TcpListener::bind(&listen_addr).await {
+		Ok(listener) => listener,
+		Err(e) => {
+			error!(
+				"Stratum: Failed to bind to WebSocket listen address {}, {}",
+				listen_addr, e
+			);
+			return;
+		}
+	};
+	warn!("Stratum WebSocket server started on {}", listen_addr);
+
+	loop {
+		let (socket, peer_addr) = match listener.accept().await {
+			Ok(conn) => conn,
+			Err(e) => {
+				error!("accept error = {:?}", e);
+				continue;
+			}
+		};
+		let ip = peer_addr.ip().to_string();
+		if !handler.is_accepted_ip(&ip) {
+			continue;
+		}
+		tokio::spawn(handle_ws_connection(
+			socket,
+			ip,
+			handler.clone(),
+			tls_acceptor.clone(),
+		));
+	}
+}
+
+async fn handle_ws_connection(
+	socket: TcpStream,
+	ip: String,
+	handler: Arc<Handler>,
+	tls_acceptor: Option<TlsAcceptor>,
+) {
+	let handshake = async {
+		let socket: Box<dyn AsyncReadWrite> = match tls_acceptor {
+			Some(acceptor) => Box::new(
+				acceptor
+					.accept(socket)
+					.await
+					.map_err(|e| format!("TLS handshake failed, {}", e))?,
+			),
+			None => Box::new(socket),
+		};
+		let config = WebSocketConfig {
+			max_message_size: Some(WS_MAX_MESSAGE_SIZE),
+			max_frame_size: Some(WS_MAX_MESSAGE_SIZE),
+			..WebSocketConfig::default()
+		};
+		tokio_tungstenite::accept_async_with_config(socket, Some(config))
+			.await
+			.map_err(|e| format!("WebSocket handshake failed, {}", e))
+	};
+	// Nothing is registered yet, the timeout just drops the connection
+	let ws = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake).await {
+		Ok(Ok(ws)) => ws,
+		Ok(Err(e)) => {
+			warn!("Stratum WebSocket connection from {}, {}", ip, e);
+			handler.ip_pool.report_fail_noise(&ip);
+			return;
+		}
+		Err(_) => {
+			warn!("Stratum WebSocket handshake with {} timed out", ip);
+			handler.ip_pool.report_fail_noise(&ip);
+			return;
+		}
+	};
+
+	let (worker_id, mut rx, kill_switch_receiver) = handler.add_worker(&ip);
+	let (mut writer, mut reader) = ws.split();
+
+	let h = handler.clone();
+	let read_ip = ip.clone();
+	let read = async move {
+		while let Some(msg) = reader.next().await {
+			let msg = msg.map_err(|e| {
+				h.ip_pool.report_fail_noise(&read_ip);
+				error!("error processing WebSocket request to stratum, {}", e)
+			})?;
+			match msg {
+				Message::Text(line) => {
+					if line.is_empty() {
+						continue;
+					}
+					debug!("get request: {}", line);
+					let request = serde_json::from_str(&line).map_err(|e| {
+						h.ip_pool.report_fail_noise(&read_ip);
+						error!("error serializing line: {}", e)
+					})?;
+					let resp = h.handle_rpc_requests(request, worker_id, &read_ip);
+					h.workers.send_to(&worker_id, resp);
+				}
+				Message::Close(_) => break,
+				// pings are answered by the websocket itself
+				Message::Ping(_) | Message::Pong(_) => {}
+				_ => {
+					h.ip_pool.report_fail_noise(&read_ip);
+					return Err(error!("stratum expects the text WebSocket messages"));
+				}
+			}
+		}
+		Result::<_, ()>::Ok(())
+	};
+
+	let write = async move {
+		while let Some(line) = rx.next().await {
+			writer
+				.send(Message::Text(line))
+				.await
+				.map_err(|e| error!("stratum cannot send data to worker, {}", e))?;
+		}
+		Result::<_, ()>::Ok(())
+	};
+
+	pin_mut!(read, write);
+	let rw = futures::future::select(read, write);
+	futures::future::select(rw, kill_switch_receiver).await;
+	handler.workers.remove_worker(worker_id);
+	info!("WebSocket worker {} disconnected", worker_id);
+}
+
 // ----------------------------------------
 // Mwc Stratum Server
 
@@ -944,11 +1278,30 @@ impl StratumServer {
 			.parse()
 			.expect("Stratum: Incorrect address ");
 
+		let tls_acceptor = match self.tls_acceptor() {
+			Ok(acceptor) => acceptor,
+			Err(e) => {
+				error!("Stratum: unable to start the TLS listener, {}", e);
+				return;
+			}
+		};
+
+		let ws_listen_addr = match &self.config.stratum_ws_server_addr {
+			Some(addr) => match addr.parse() {
+				Ok(addr) => Some(addr),
+				Err(e) => {
+					error!("Stratum: Incorrect WebSocket address {}, {}", addr, e);
+					return;
+				}
+			},
+			None => None,
+		};
+
 		let handler = Arc::new(Handler::from_stratum(&self));
 		let h = handler.clone();
 
 		let _listener_th = thread::spawn(move || {
-			accept_connections(listen_addr, h);
+			accept_connections(listen_addr, ws_listen_addr, h, tls_acceptor);
 		});
 
 		// We have started
@@ -972,6 +1325,23 @@ impl StratumServer {
 
 		handler.run(&self.config, &self.tx_pool);
 	} // fn run_loop()
+
+	// TLS acceptor if the certificate is configured
+	fn tls_acceptor(&self) -> Result<Option<TlsAcceptor>, String> {
+		let certificate = match &self.config.tls_certificate_file {
+			Some(certificate) => certificate.clone(),
+			None => return Ok(None),
+		};
+		let key = self
+			.config
+			.tls_certificate_key
+			.clone()
+			.ok_or_else(|| "tls_certificate_key is not defined".to_string())?;
+		let config = TLSConfig::new(certificate, key)
+			.build_server_config()
+			.map_err(|e| format!("{}", e))?;
+		Ok(Some(TlsAcceptor::from(config)))
+	}
 } // StratumServer
 
 // Utility function to parse a JSON RPC parameter object, returning a proper
@@ -988,6 +1358,147 @@ where
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::common::types::StratumWorkerConfig;
+	use std::path::Path;
+
+	// Handler on a fresh chain, with the worker "alice" registered
+	fn test_handler(dir: &Path, registered: bool) -> Handler {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let chain = chain::Chain::init(
+			dir.join("chain").to_str().unwrap().to_string(),
+			Arc::new(chain::types::NoopAdapter {}),
+			pow::mine_genesis_block().unwrap(),
+			pow::verify_size,
+			false,
+		)
+		.unwrap();
+		let registry = if registered {
+			let password_path = dir.join("alice");
+			std::fs::write(&password_path, "secret").unwrap();
+			Some(WorkerRegistry::new(&[StratumWorkerConfig {
+				login: "alice".to_string(),
+				password_path: password_path.to_str().unwrap().to_string(),
+			}]))
+		} else {
+			None
+		};
+		let registered = registry
+			.as_ref()
+			.map(|r| r.logins().into_iter().collect())
+			.unwrap_or_default();
+		Handler {
+			id: "test".to_string(),
+			workers: Arc::new(WorkersList::new(
+				Arc::new(StratumStats::default()),
+				registered,
+			)),
+			sync_state: Arc::new(SyncState::new()),
+			chain: Arc::new(chain),
+			current_state: Arc::new(RwLock::new(State::new(1))),
+			ip_pool: Arc::new(connections::StratumIpPool::new(10, 1, -1)),
+			worker_connections: Arc::new(AtomicI32::new(0)),
+			config: StratumServerConfig::default(),
+			registry,
+		}
+	}
+
+	fn login(handler: &Handler, worker_id: usize, pass: &str) -> bool {
+		let params = serde_json::json!({"login": "alice", "pass": pass, "agent": "test"});
+		handler.handle_login(Some(params), &worker_id).is_ok()
+	}
+
+	#[test]
+	fn test_methods_need_login() {
+		let dir = tempfile::tempdir().unwrap();
+		let handler = test_handler(dir.path(), true);
+		let (worker_id, _rx, _kill_switch) = handler.add_worker(&"127.0.0.1".to_string());
+
+		assert!(handler.is_allowed("login", worker_id));
+		assert!(handler.is_allowed("keepalive", worker_id));
+		for method in &["getjobtemplate", "submit", "status"] {
+			assert!(!handler.is_allowed(method, worker_id));
+		}
+
+		assert!(!login(&handler, worker_id, "wrong"));
+		assert!(!handler.is_allowed("submit", worker_id));
+		assert!(login(&handler, worker_id, "secret"));
+		assert!(handler.is_allowed("submit", worker_id));
+		assert!(handler.is_allowed("getjobtemplate", worker_id));
+
+		// The failed login drops the previous one
+		assert!(!login(&handler, worker_id, "wrong"));
+		assert!(!handler.is_allowed("submit", worker_id));
+		assert!(!handler.is_allowed("getjobtemplate", worker_id));
+
+		// Without the registry any worker is allowed
+		let dir = tempfile::tempdir().unwrap();
+		let handler = test_handler(dir.path(), false);
+		let (worker_id, _rx, _kill_switch) = handler.add_worker(&"127.0.0.1".to_string());
+		assert!(handler.is_allowed("submit", worker_id));
+	}
+
+	#[test]
+	fn test_websocket_login() {
+		let dir = tempfile::tempdir().unwrap();
+		let handler = Arc::new(test_handler(dir.path(), true));
+		let rt = Runtime::new().unwrap();
+		rt.block_on(async {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let addr = listener.local_addr().unwrap();
+			let server_handler = handler.clone();
+			let server = tokio::spawn(async move {
+				let (socket, peer_addr) = listener.accept().await.unwrap();
+				let ip = peer_addr.ip().to_string();
+				handle_ws_connection(socket, ip, server_handler, None).await;
+			});
+
+			let stream = TcpStream::connect(addr).await.unwrap();
+			let (mut ws, _) = tokio_tungstenite::client_async(format!("ws://{}/", addr), stream)
+				.await
+				.unwrap();
+			let login = serde_json::json!({
+				"id": "1",
+				"jsonrpc": "2.0",
+				"method": "login",
+				"params": {"login": "alice", "pass": "secret", "agent": "test"},
+			});
+			ws.send(Message::Text(login.to_string())).await.unwrap();
+			let resp = match ws.next().await.unwrap().unwrap() {
+				Message::Text(resp) => resp,
+				msg => panic!("unexpected message {:?}", msg),
+			};
+			let resp: Value = serde_json::from_str(&resp).unwrap();
+			assert_eq!(resp["id"], "1");
+			assert_eq!(resp["result"], "ok");
+			let workers = handler.workers.get_workers_list();
+			assert_eq!(workers.len(), 1);
+			assert!(workers[0].authenticated);
+
+			// The oversized message drops the connection
+			let _ = ws
+				.send(Message::Text("x".repeat(WS_MAX_MESSAGE_SIZE + 1)))
+				.await;
+			tokio::time::timeout(Duration::from_secs(5), server)
+				.await
+				.unwrap()
+				.unwrap();
+			assert!(handler.workers.get_workers_list().is_empty());
+		});
+	}
+
+	#[test]
+	fn test_broadcast_job_skips_unauthenticated() {
+		let dir = tempfile::tempdir().unwrap();
+		let handler = test_handler(dir.path(), true);
+		let (alice, mut alice_rx, _alice_kill) = handler.add_worker(&"127.0.0.1".to_string());
+		let (_anon, mut anon_rx, _anon_kill) = handler.add_worker(&"127.0.0.1".to_string());
+		assert!(login(&handler, alice, "secret"));
+
+		handler.broadcast_job();
+		let job: Value = serde_json::from_str(&alice_rx.try_next().unwrap().unwrap()).unwrap();
+		assert_eq!(job["method"], "job");
+		assert!(anon_rx.try_next().is_err());
+	}
 
 	/// Tests deserializing an `RpcRequest` given a String as the id.
 	#[test]
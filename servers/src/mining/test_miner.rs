@@ -26,6 +26,7 @@ use crate::common::types::StratumServerConfig;
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::{Block, BlockHeader};
 use crate::core::global;
+use crate::keychain::ExtKeychain;
 use crate::mining::mine_block;
 use crate::util::StopState;
 use crate::ServerTxPool;
@@ -39,6 +40,8 @@ pub struct Miner {
 	tx_pool: ServerTxPool,
 	stop_state: Arc<StopState>,
 	sync_state: Arc<SyncState>,
+	// Block rewards go to this keychain instead of being burnt (tx generator funding)
+	reward_keychain: Option<ExtKeychain>,
 	// Just to hold the port we're on, so this miner can be identified
 	// while watching debug output
 	debug_output_id: String,
@@ -61,6 +64,7 @@ impl Miner {
 			debug_output_id: String::from("none"),
 			stop_state,
 			sync_state,
+			reward_keychain: None,
 		}
 	}
 
@@ -70,6 +74,12 @@ impl Miner {
 		self.debug_output_id = debug_output_id;
 	}
 
+	/// Pay the block rewards to the keychain instead of burning them, used
+	/// when there is no wallet listener.
+	pub fn set_reward_keychain(&mut self, reward_keychain: ExtKeychain) {
+		self.reward_keychain = Some(reward_keychain);
+	}
+
 	/// The inner part of mining loop for the internal miner
 	/// kept around mostly for automated testing purposes
 	fn inner_mining_loop(
@@ -156,6 +166,8 @@ impl Miner {
 				&self.tx_pool,
 				key_id.clone(),
 				wallet_listener_url.clone(),
+				&[],
+				self.reward_keychain.as_ref(),
 			);
 
 			let sol = self.inner_mining_loop(
@@ -0,0 +1,150 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Variable share difficulty of the stratum worker. The accepted shares are counted
+//! over the retarget window, at the end of the window the difficulty is scaled by the
+//! ratio of the observed and the goal share rate, so every worker of a mixed fleet
+//! submits about the same number of shares.
+
+use std::cmp;
+
+/// The observed rate within this ratio of the goal doesn't change the difficulty,
+/// the share arrivals are random and a small window is noisy
+const VARDIFF_TOLERANCE_LOW: f64 = 0.7;
+const VARDIFF_TOLERANCE_HIGH: f64 = 1.4;
+/// Maximum change of the difficulty in a single retarget
+const VARDIFF_MAX_STEP: f64 = 4.0;
+
+/// Share rate tracking of the single worker
+#[derive(Debug, Clone)]
+pub struct VarDiff {
+	/// Current share difficulty of the worker, unscaled
+	difficulty: u64,
+	/// Difficulty before the last retarget. The shares of the jobs that were sent before
+	/// the change are accepted with it until the next retarget.
+	prev_difficulty: Option<u64>,
+	/// Start of the retarget window, ms
+	window_start: i64,
+	/// Shares accepted in the window
+	shares: u64,
+}
+
+impl VarDiff {
+	pub fn new(difficulty: u64, now: i64) -> VarDiff {
+		VarDiff {
+			difficulty,
+			prev_difficulty: None,
+			window_start: now,
+			shares: 0,
+		}
+	}
+
+	/// Current share difficulty of the worker
+	pub fn difficulty(&self) -> u64 {
+		self.difficulty
+	}
+
+	/// Minimal difficulty of the share that is accepted from the worker
+	pub fn accept_difficulty(&self) -> u64 {
+		match self.prev_difficulty {
+			Some(prev) => cmp::min(prev, self.difficulty),
+			None => self.difficulty,
+		}
+	}
+
+	pub fn add_share(&mut self) {
+		self.shares += 1;
+	}
+
+	/// Retarget the difficulty if the window is over. The difficulty stays in
+	/// `min_difficulty..=max_difficulty`. Returns the new difficulty if it was changed.
+	pub fn retarget(
+		&mut self,
+		now: i64,
+		shares_per_minute: u32,
+		retarget_ms: i64,
+		min_difficulty: u64,
+		max_difficulty: u64,
+	) -> Option<u64> {
+		let elapsed = now - self.window_start;
+		if elapsed < retarget_ms || elapsed <= 0 || shares_per_minute == 0 {
+			return None;
+		}
+		let rate = self.shares as f64 * 60_000.0 / elapsed as f64;
+		let ratio = rate / shares_per_minute as f64;
+		self.window_start = now;
+		self.shares = 0;
+		self.prev_difficulty = None;
+
+		let max_difficulty = cmp::max(min_difficulty, max_difficulty);
+		let difficulty = if ratio > VARDIFF_TOLERANCE_LOW && ratio < VARDIFF_TOLERANCE_HIGH {
+			self.difficulty
+		} else {
+			let ratio = ratio.max(1.0 / VARDIFF_MAX_STEP).min(VARDIFF_MAX_STEP);
+			(self.difficulty as f64 * ratio) as u64
+		};
+		let difficulty = cmp::min(cmp::max(difficulty, min_difficulty), max_difficulty);
+		if difficulty == self.difficulty {
+			return None;
+		}
+		self.prev_difficulty = Some(self.difficulty);
+		self.difficulty = difficulty;
+		Some(difficulty)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_vardiff_retarget() {
+		let mut vardiff = VarDiff::new(10, 0);
+		// window is not over
+		vardiff.add_share();
+		assert_eq!(vardiff.retarget(30_000, 6, 60_000, 1, 1000), None);
+
+		// 60 shares per minute, 10 times above the goal, limited by the max step
+		for _ in 0..59 {
+			vardiff.add_share();
+		}
+		assert_eq!(vardiff.retarget(60_000, 6, 60_000, 1, 1000), Some(40));
+		assert_eq!(vardiff.difficulty(), 40);
+		// jobs of the old difficulty are still accepted
+		assert_eq!(vardiff.accept_difficulty(), 10);
+
+		// close to the goal, no change
+		for _ in 0..7 {
+			vardiff.add_share();
+		}
+		assert_eq!(vardiff.retarget(120_000, 6, 60_000, 1, 1000), None);
+		assert_eq!(vardiff.accept_difficulty(), 40);
+
+		// 3 shares per minute, half of the goal
+		for _ in 0..3 {
+			vardiff.add_share();
+		}
+		assert_eq!(vardiff.retarget(180_000, 6, 60_000, 1, 1000), Some(20));
+
+		// no shares, limited by the minimum
+		assert_eq!(vardiff.retarget(240_000, 6, 60_000, 8, 1000), Some(8));
+		assert_eq!(vardiff.retarget(300_000, 6, 60_000, 8, 1000), None);
+
+		// limited by the maximum
+		for _ in 0..100 {
+			vardiff.add_share();
+		}
+		assert_eq!(vardiff.retarget(360_000, 6, 60_000, 8, 16), Some(16));
+	}
+}
@@ -15,7 +15,18 @@
 
 //! Mwc P2P / API server
 
+pub mod block_compressor;
+pub mod config_watcher;
 pub mod dandelion_monitor;
+pub mod disk_monitor;
+pub mod fork_monitor;
+pub mod node;
+pub mod node_control;
 pub mod seed;
+pub mod self_test;
 pub mod server;
+pub mod supervisor;
 pub mod sync;
+pub mod tx_generator;
+pub mod txhashset_monitor;
+pub mod webhooks;
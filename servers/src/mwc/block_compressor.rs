@@ -0,0 +1,62 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::chain::{self, SyncState};
+use crate::mwc::supervisor::Supervisor;
+use crate::util::StopState;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Blocks compressed in one run, the next run continues right away if there are more
+const BLOCKS_PER_RUN: usize = 1000;
+
+/// A process to compress the stored blocks once they are below the cut-through horizon.
+/// It matters for the archive nodes, the rest of the nodes remove these blocks. The
+/// existing blocks are compressed gradually, a few runs after the start.
+pub fn monitor_block_compression(
+	chain: Arc<chain::Chain>,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+	supervisor: &Arc<Supervisor>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!("Started block compression monitor.");
+
+	supervisor.spawn("block_compression", move || {
+		let run_interval = Duration::from_secs(60);
+		let mut last_run = Instant::now()
+			.checked_sub(run_interval)
+			.unwrap_or_else(Instant::now);
+		loop {
+			if stop_state.is_stopped() {
+				break;
+			}
+
+			// Not competing with the sync for the db
+			if last_run.elapsed() > run_interval && !sync_state.is_syncing() {
+				match chain.compress_old_blocks(BLOCKS_PER_RUN) {
+					Ok(n) if n == BLOCKS_PER_RUN => (),
+					Ok(_) => last_run = Instant::now(),
+					Err(e) => {
+						error!("block_compression: Unable to compress blocks, {}", e);
+						last_run = Instant::now();
+					}
+				}
+			}
+
+			// Monitor loops every minute, but check stop flag every second.
+			thread::sleep(Duration::from_secs(1));
+		}
+	})
+}
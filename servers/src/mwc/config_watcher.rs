@@ -0,0 +1,243 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Config reload on SIGHUP or by the owner API. The config file is read again and
+//! compared with the settings the node runs with. The peer counts, the log levels, the
+//! Dandelion settings, the API rate limits and the pool `tx_fee_base` are applied right
+//! away, the other changes are reported as requiring the restart.
+
+use crate::api::{self, ApiRateLimiter, ConfigReload, DandelionControl};
+use crate::common::types::ServerConfig;
+use crate::core::global;
+use crate::mwc::server::ServerTxPool;
+use crate::p2p;
+use crate::util::logger::{self, LoggingConfig};
+use crate::util::{Mutex, RwLock, StopState};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Reads the server and the logging config from the config file
+pub type ConfigLoader =
+	Box<dyn Fn() -> Result<(ServerConfig, Option<LoggingConfig>), String> + Send + Sync>;
+
+// Settings that are applied at runtime, the rest require the restart
+const PEER_COUNTS: [&str; 4] = [
+	"server.p2p_config.peer_max_inbound_count",
+	"server.p2p_config.peer_max_outbound_count",
+	"server.p2p_config.peer_min_preferred_outbound_count",
+	"server.p2p_config.peer_listener_buffer_count",
+];
+const TX_FEE_BASE: &str = "server.pool_config.tx_fee_base";
+const DANDELION_PREFIX: &str = "server.dandelion_config.";
+const RATE_LIMIT_PREFIX: &str = "server.rate_limit_config.";
+const LOG_LEVELS: [&str; 2] = ["logging.stdout_log_level", "logging.file_log_level"];
+const MODULE_LOG_LEVELS: &str = "logging.module_log_levels";
+
+// SIGHUP flag is checked that often
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Applies the changed settings from the config file to the running node
+pub struct ConfigWatcher {
+	loader: RwLock<Option<ConfigLoader>>,
+	// Startup config with the applied changes
+	running: Mutex<ServerConfig>,
+	peers: Arc<p2p::Peers>,
+	dandelion: Arc<dyn DandelionControl>,
+	tx_pool: ServerTxPool,
+	rate_limiter: Option<Arc<Mutex<ApiRateLimiter>>>,
+}
+
+impl ConfigWatcher {
+	pub fn new(
+		config: ServerConfig,
+		peers: Arc<p2p::Peers>,
+		dandelion: Arc<dyn DandelionControl>,
+		tx_pool: ServerTxPool,
+		rate_limiter: Option<Arc<Mutex<ApiRateLimiter>>>,
+	) -> ConfigWatcher {
+		ConfigWatcher {
+			loader: RwLock::new(None),
+			running: Mutex::new(config),
+			peers,
+			dandelion,
+			tx_pool,
+			rate_limiter,
+		}
+	}
+
+	/// Set the config file reader. Without it the reload is not available, for example
+	/// when the node runs with the default config.
+	pub fn set_loader(&self, loader: ConfigLoader) {
+		*self.loader.write() = Some(loader);
+	}
+
+	/// Read the config file and apply the settings that can be changed at runtime
+	pub fn reload(&self) -> Result<ConfigReload, String> {
+		let (config, logging) = match self.loader.read().as_ref() {
+			Some(loader) => loader()?,
+			None => return Err("the node runs without the config file".to_string()),
+		};
+
+		let mut running = self.running.lock();
+		let rate_limit_hot = self.rate_limiter.is_some() && config.rate_limit_config.is_some();
+		let is_hot = |path: &str| {
+			PEER_COUNTS.contains(&path)
+				|| path == TX_FEE_BASE
+				|| path.starts_with(DANDELION_PREFIX)
+				|| (rate_limit_hot && path.starts_with(RATE_LIMIT_PREFIX))
+				|| LOG_LEVELS.contains(&path)
+				|| path == MODULE_LOG_LEVELS
+				|| path.starts_with(&format!("{}.", MODULE_LOG_LEVELS))
+		};
+
+		let mut changes = config_changes("server", &*running, &config)?;
+		let running_logging = logger::logging_config();
+		let logging = logging.map(|mut logging| {
+			// TUI mode is selected by the server config at startup
+			logging.tui_running = running_logging.tui_running;
+			logging
+		});
+		if let Some(logging) = &logging {
+			changes.extend(config_changes("logging", &running_logging, logging)?);
+		}
+		let (applied, restart_required): (Vec<String>, Vec<String>) =
+			changes.into_iter().partition(|path| is_hot(path.as_str()));
+
+		// Validate everything before applying anything
+		if applied.iter().any(|p| p.starts_with(DANDELION_PREFIX)) {
+			api::validate_dandelion_config(&config.dandelion_config).map_err(|e| e.to_string())?;
+		}
+
+		if applied.iter().any(|p| PEER_COUNTS.contains(&p.as_str())) {
+			self.peers.set_peer_counts(&config.p2p_config);
+			running.p2p_config.peer_max_inbound_count = config.p2p_config.peer_max_inbound_count;
+			running.p2p_config.peer_max_outbound_count = config.p2p_config.peer_max_outbound_count;
+			running.p2p_config.peer_min_preferred_outbound_count =
+				config.p2p_config.peer_min_preferred_outbound_count;
+			running.p2p_config.peer_listener_buffer_count =
+				config.p2p_config.peer_listener_buffer_count;
+		}
+		if applied.iter().any(|p| p == TX_FEE_BASE) {
+			global::set_global_accept_fee_base(
+				config
+					.pool_config
+					.tx_fee_base
+					.unwrap_or(global::DEFAULT_ACCEPT_FEE_BASE),
+			);
+			self.tx_pool.write().config.tx_fee_base = config.pool_config.tx_fee_base;
+			running.pool_config.tx_fee_base = config.pool_config.tx_fee_base;
+		}
+		if applied.iter().any(|p| p.starts_with(DANDELION_PREFIX)) {
+			self.dandelion
+				.set_dandelion_config(config.dandelion_config.clone());
+			running.dandelion_config = config.dandelion_config.clone();
+		}
+		if let (Some(limiter), Some(rate_limit_config)) = (
+			self.rate_limiter.as_ref(),
+			config.rate_limit_config.as_ref(),
+		) {
+			if applied.iter().any(|p| p.starts_with(RATE_LIMIT_PREFIX)) {
+				limiter.lock().set_config(rate_limit_config.clone());
+				running.rate_limit_config = Some(rate_limit_config.clone());
+			}
+		}
+		if let Some(logging) = logging {
+			if applied.iter().any(|p| p.starts_with("logging.")) {
+				logger::update_log_levels(
+					logging.stdout_log_level,
+					logging.file_log_level,
+					logging.module_log_levels.unwrap_or_default(),
+				);
+			}
+		}
+
+		if !applied.is_empty() {
+			info!("Config is reloaded, applied changes: {:?}", applied);
+		}
+		if !restart_required.is_empty() {
+			warn!(
+				"Config is reloaded, these changes require the node restart: {:?}",
+				restart_required
+			);
+		}
+		Ok(ConfigReload {
+			applied,
+			restart_required,
+		})
+	}
+}
+
+// Changed leaf settings of the two configs as `prefix.section.field` paths
+fn config_changes<T: Serialize>(prefix: &str, old: &T, new: &T) -> Result<Vec<String>, String> {
+	let old = serde_json::to_value(old).map_err(|e| e.to_string())?;
+	let new = serde_json::to_value(new).map_err(|e| e.to_string())?;
+	let mut changes = vec![];
+	diff_values(prefix, &old, &new, &mut changes);
+	Ok(changes)
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+	match (old, new) {
+		(Value::Object(old), Value::Object(new)) => {
+			let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+			for key in keys {
+				diff_values(
+					&format!("{}.{}", path, key),
+					old.get(key).unwrap_or(&Value::Null),
+					new.get(key).unwrap_or(&Value::Null),
+					changes,
+				);
+			}
+		}
+		_ => {
+			if old != new {
+				changes.push(path.to_string());
+			}
+		}
+	}
+}
+
+/// Reload the config on SIGHUP until the node stops. Returns None on the platforms
+/// without the signal, the owner API can be used there.
+pub fn run_config_watcher(
+	watcher: Arc<ConfigWatcher>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<Option<thread::JoinHandle<()>>> {
+	let reload_requested = Arc::new(AtomicBool::new(false));
+	#[cfg(unix)]
+	signal_hook::flag::register(signal_hook::consts::SIGHUP, reload_requested.clone())?;
+	if cfg!(not(unix)) {
+		return Ok(None);
+	}
+
+	let handle = thread::Builder::new()
+		.name("config_watcher".to_string())
+		.spawn(move || {
+			while !stop_state.is_stopped() {
+				if reload_requested.swap(false, Ordering::Relaxed) {
+					info!("SIGHUP is received, reloading the config");
+					if let Err(e) = watcher.reload() {
+						error!("Unable to reload config, {}", e);
+					}
+				}
+				thread::sleep(SIGNAL_CHECK_INTERVAL);
+			}
+		})?;
+	Ok(Some(handle))
+}
@@ -16,6 +16,7 @@
 use crate::common::adapters::DandelionAdapter;
 use crate::core::core::hash::Hashed;
 use crate::core::core::transaction;
+use crate::mwc::supervisor::Supervisor;
 use crate::pool::{BlockChain, DandelionConfig, Pool, PoolEntry, PoolError, TxSource};
 use crate::util::StopState;
 use crate::ServerTxPool;
@@ -34,55 +35,54 @@ use std::time::{Duration, Instant};
 /// stempool and test if the timer is expired for each transaction. In that case
 /// the transaction will be sent in fluff phase (to multiple peers) instead of
 /// sending only to the peer relay.
+/// The timers are taken from the adapter on every run, so they can be changed at runtime.
 pub fn monitor_transactions(
-	dandelion_config: DandelionConfig,
 	tx_pool: ServerTxPool,
 	adapter: Arc<dyn DandelionAdapter>,
 	stop_state: Arc<StopState>,
+	supervisor: &Arc<Supervisor>,
 ) -> std::io::Result<thread::JoinHandle<()>> {
 	debug!("Started Dandelion transaction monitor.");
 
-	thread::Builder::new()
-		.name("dandelion".to_string())
-		.spawn(move || {
-			let run_interval = Duration::from_secs(10);
-			let secp = Secp256k1::with_caps(ContextFlag::Commit);
-			let mut last_run = Instant::now()
-				.checked_sub(Duration::from_secs(20))
-				.unwrap_or_else(Instant::now);
-			loop {
-				// Halt Dandelion monitor if we have been notified that we are stopping.
-				if stop_state.is_stopped() {
-					break;
-				}
+	supervisor.spawn("dandelion", move || {
+		let run_interval = Duration::from_secs(10);
+		let secp = Secp256k1::with_caps(ContextFlag::Commit);
+		let mut last_run = Instant::now()
+			.checked_sub(Duration::from_secs(20))
+			.unwrap_or_else(Instant::now);
+		loop {
+			// Halt Dandelion monitor if we have been notified that we are stopping.
+			if stop_state.is_stopped() {
+				break;
+			}
 
-				if last_run.elapsed() > run_interval {
-					if !adapter.is_stem() {
-						let _ = process_fluff_phase(&dandelion_config, &tx_pool, &adapter, &secp)
-							.map_err(|e| {
-								error!("dand_mon: Problem processing fluff phase. {}", e);
-							});
-					}
-
-					// Now find all expired entries based on embargo timer.
-					let _ =
-						process_expired_entries(&dandelion_config, &tx_pool, &secp).map_err(|e| {
-							error!("dand_mon: Problem processing expired entries. {}", e);
+			if last_run.elapsed() > run_interval {
+				let dandelion_config = adapter.config();
+				if !adapter.is_stem() {
+					let _ = process_fluff_phase(&dandelion_config, &tx_pool, &adapter, &secp)
+						.map_err(|e| {
+							error!("dand_mon: Problem processing fluff phase. {}", e);
 						});
-
-					// Handle the tx above *before* we transition to next epoch.
-					// This gives us an opportunity to do the final "fluff" before we start
-					// stemming on the subsequent epoch.
-					if adapter.is_expired() {
-						adapter.next_epoch();
-					}
-					last_run = Instant::now();
 				}
 
-				// Monitor loops every 10s, but check stop flag every second.
-				thread::sleep(Duration::from_secs(1));
+				// Now find all expired entries based on embargo timer.
+				let _ = process_expired_entries(&dandelion_config, &tx_pool, &secp).map_err(|e| {
+					error!("dand_mon: Problem processing expired entries. {}", e);
+				});
+
+				// Handle the tx above *before* we transition to next epoch.
+				// This gives us an opportunity to do the final "fluff" before we start
+				// stemming on the subsequent epoch.
+				if adapter.is_expired() {
+					adapter.next_epoch();
+				}
+				last_run = Instant::now();
 			}
-		})
+
+			// Monitor loops every 10s, but check stop flag every second.
+			thread::sleep(Duration::from_secs(1));
+		}
+	})
 }
 
 // Query the pool for transactions older than the cutoff.
@@ -0,0 +1,201 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Disk usage monitor. The chain data size and the free space of its disk are checked
+//! periodically. Below the hard floor the chain stops accepting the blocks until the
+//! space is freed, and the state download (PIBD or the txhashset archive) doesn't start
+//! if its projected size doesn't fit.
+
+use crate::api::DiskSubsystemStatus;
+use crate::chain::Chain;
+use crate::common::types::DiskMonitorConfig;
+use crate::core::core::pmmr;
+use crate::core::core::BlockHeader;
+use crate::util::{RwLock, StopState};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MB: u64 = 1024 * 1024;
+
+// Serialized sizes of the txhashset data, the rangeproof is stored with its length
+const OUTPUT_BYTES: u64 = 34;
+const RANGEPROOF_BYTES: u64 = 8 + 675;
+const KERNEL_BYTES: u64 = 114;
+const HASH_BYTES: u64 = 32;
+
+// Stop state is checked that often while the monitor waits for the next check
+const STOP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound of the txhashset state size at the header. The spent outputs are pruned
+/// while the state is downloaded, so the real size is smaller.
+pub fn projected_state_size(header: &BlockHeader) -> u64 {
+	let outputs = pmmr::n_leaves(header.output_mmr_size);
+	let kernels = pmmr::n_leaves(header.kernel_mmr_size);
+	outputs * (OUTPUT_BYTES + RANGEPROOF_BYTES)
+		+ header.output_mmr_size * HASH_BYTES * 2
+		+ kernels * KERNEL_BYTES
+		+ header.kernel_mmr_size * HASH_BYTES
+}
+
+#[derive(Clone, Copy)]
+struct DiskUsage {
+	chain_data_bytes: u64,
+	free_bytes: u64,
+}
+
+/// Tracks the chain data size and the free disk space
+pub struct DiskMonitor {
+	config: DiskMonitorConfig,
+	chain: Arc<Chain>,
+	data_dir: PathBuf,
+	// None until the first check
+	usage: RwLock<Option<DiskUsage>>,
+	state_download_refused: AtomicBool,
+}
+
+impl DiskMonitor {
+	pub fn new(config: DiskMonitorConfig, chain: Arc<Chain>, data_dir: PathBuf) -> DiskMonitor {
+		DiskMonitor {
+			config,
+			chain,
+			data_dir,
+			usage: RwLock::new(None),
+			state_download_refused: AtomicBool::new(false),
+		}
+	}
+
+	/// Measure the disk usage. The blocks acceptance is paused below the hard floor and
+	/// resumed when the space is freed.
+	pub fn refresh(&self) -> io::Result<()> {
+		let free_bytes = fs2::available_space(&self.data_dir)?;
+		let chain_data_bytes = dir_size(&self.data_dir)?;
+
+		let paused = free_bytes < self.min_free_bytes();
+		if paused != self.chain.is_block_acceptance_paused() {
+			if paused {
+				error!(
+					"Free disk space {} MB is below the {} MB floor, new blocks are not accepted until the space is freed",
+					free_bytes / MB,
+					self.config.min_free_mb
+				);
+			} else {
+				info!(
+					"Free disk space is {} MB, accepting the blocks again",
+					free_bytes / MB
+				);
+			}
+			self.chain.pause_block_acceptance(paused);
+		}
+
+		*self.usage.write() = Some(DiskUsage {
+			chain_data_bytes,
+			free_bytes,
+		});
+		Ok(())
+	}
+
+	fn min_free_bytes(&self) -> u64 {
+		self.config.min_free_mb.saturating_mul(MB)
+	}
+
+	/// Last measured disk usage, zero sizes until the first check
+	pub fn status(&self) -> DiskSubsystemStatus {
+		let usage = *self.usage.read();
+		DiskSubsystemStatus {
+			chain_data_bytes: usage.map(|u| u.chain_data_bytes).unwrap_or(0),
+			free_bytes: usage.map(|u| u.free_bytes).unwrap_or(0),
+			min_free_bytes: self.min_free_bytes(),
+			blocks_paused: self.chain.is_block_acceptance_paused(),
+			state_download_refused: self.state_download_refused.load(Ordering::Relaxed),
+		}
+	}
+
+	/// Check that the state at the archive header fits the disk above the hard floor.
+	/// It passes until the disk usage is measured.
+	pub fn check_state_download(&self, archive_header: &BlockHeader) -> Result<(), String> {
+		let free_bytes = match *self.usage.read() {
+			Some(usage) => usage.free_bytes,
+			None => return Ok(()),
+		};
+		let needed = projected_state_size(archive_header);
+		if free_bytes < needed.saturating_add(self.min_free_bytes()) {
+			let msg = format!(
+				"Not enough disk space for the state at height {}, {} MB is free, {} MB is needed above the {} MB floor",
+				archive_header.height,
+				free_bytes / MB,
+				needed / MB,
+				self.config.min_free_mb
+			);
+			if !self.state_download_refused.swap(true, Ordering::Relaxed) {
+				error!("{}", msg);
+			}
+			return Err(msg);
+		}
+		if self.state_download_refused.swap(false, Ordering::Relaxed) {
+			info!("Disk space is enough for the state download now");
+		}
+		Ok(())
+	}
+}
+
+// Size of the files under the directory, the symlinks are not followed. Files that are
+// deleted during the walk (compaction, temp files) are skipped.
+fn dir_size(dir: &Path) -> io::Result<u64> {
+	let mut size = 0;
+	for entry in fs::read_dir(dir)? {
+		let res = entry.and_then(|entry| {
+			let file_type = entry.file_type()?;
+			if file_type.is_dir() {
+				dir_size(&entry.path())
+			} else if file_type.is_file() {
+				Ok(entry.metadata()?.len())
+			} else {
+				Ok(0)
+			}
+		});
+		match res {
+			Ok(s) => size += s,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(size)
+}
+
+/// Check the disk usage until the node stops
+pub fn run_disk_monitor(
+	monitor: Arc<DiskMonitor>,
+	stop_state: Arc<StopState>,
+) -> io::Result<thread::JoinHandle<()>> {
+	let interval = Duration::from_secs(monitor.config.check_interval_secs.max(1));
+	thread::Builder::new()
+		.name("disk_monitor".to_string())
+		.spawn(move || {
+			let mut last_check: Option<Instant> = None;
+			while !stop_state.is_stopped() {
+				if last_check.map(|t| t.elapsed() >= interval).unwrap_or(true) {
+					if let Err(e) = monitor.refresh() {
+						warn!("Unable to check the disk usage, {}", e);
+					}
+					last_check = Some(Instant::now());
+				}
+				thread::sleep(STOP_CHECK_INTERVAL);
+			}
+		})
+}
@@ -0,0 +1,173 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fork monitoring. Tracks the competing chain tips that the peers are on, from the
+//! relayed headers and the archive headers. Forks longer than the alert depth are
+//! reported with the status API and the fork webhook.
+
+use crate::chain::{self, Chain, ForkInfo, SyncState};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::BlockHeader;
+use crate::p2p::PeerAddr;
+use crate::util::{RwLock, ToHex};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Forks that are not reported for that time are dropped
+const FORK_EXPIRATION_SECS: i64 = 3600;
+/// Max number of the headers to walk back looking for the fork point
+const MAX_FORK_WALK: u64 = 1000;
+
+pub struct ForkMonitor {
+	alert_depth: u64,
+	forks: RwLock<HashMap<PeerAddr, ForkInfo>>,
+	sync_state: Arc<SyncState>,
+}
+
+impl ForkMonitor {
+	pub fn new(alert_depth: u64, sync_state: Arc<SyncState>) -> ForkMonitor {
+		ForkMonitor {
+			alert_depth,
+			forks: RwLock::new(HashMap::new()),
+			sync_state,
+		}
+	}
+
+	/// Check the processed header that the peer is on.
+	/// Returns the fork if the peer is on a new fork longer than the alert depth.
+	pub fn check_header(
+		&self,
+		chain: &Chain,
+		header: &BlockHeader,
+		peer: &PeerAddr,
+	) -> Result<Option<ForkInfo>, chain::Error> {
+		if is_on_header_chain(chain, &header.hash(), header.height)? {
+			self.remove_fork(peer);
+			return Ok(None);
+		}
+
+		let head = chain.header_head()?;
+		let mut fork_point = chain.get_previous_header(header)?;
+		let mut steps = 1;
+		// If the fork is deeper than we walk, the depth is a low estimation
+		while steps < MAX_FORK_WALK
+			&& !is_on_header_chain(chain, &fork_point.hash(), fork_point.height)?
+		{
+			fork_point = chain.get_previous_header(&fork_point)?;
+			steps += 1;
+		}
+
+		Ok(self.add_fork(
+			peer,
+			ForkInfo {
+				peer: peer.to_string(),
+				tip_hash: header.hash().to_hex(),
+				tip_height: header.height,
+				fork_height: fork_point.height,
+				depth: head.height.saturating_sub(fork_point.height),
+				length: header.height.saturating_sub(fork_point.height),
+				tip_difficulty: Some(header.total_difficulty().to_num()),
+				head_difficulty: head.total_difficulty.to_num(),
+				last_seen: Utc::now(),
+			},
+		))
+	}
+
+	/// Check the archive header that the peer responded with.
+	/// Returns the fork if the peer is on a new fork longer than the alert depth.
+	pub fn check_archive_header(
+		&self,
+		chain: &Chain,
+		hash: &Hash,
+		height: u64,
+		peer: &PeerAddr,
+	) -> Result<Option<ForkInfo>, chain::Error> {
+		if let Ok(header) = chain.get_block_header(hash) {
+			return self.check_header(chain, &header, peer);
+		}
+
+		let head = chain.header_head()?;
+		if height > head.height || is_on_header_chain(chain, hash, height)? {
+			return Ok(None);
+		}
+
+		// The peer's archive header is unknown, so the fork point is somewhere below it.
+		// The archive height is derived from the peer's head, so its fork is about as long as ours.
+		let fork_height = height.saturating_sub(1);
+		let depth = head.height.saturating_sub(fork_height);
+		Ok(self.add_fork(
+			peer,
+			ForkInfo {
+				peer: peer.to_string(),
+				tip_hash: hash.to_hex(),
+				tip_height: height,
+				fork_height,
+				depth,
+				length: depth,
+				tip_difficulty: None,
+				head_difficulty: head.total_difficulty.to_num(),
+				last_seen: Utc::now(),
+			},
+		))
+	}
+
+	fn add_fork(&self, peer: &PeerAddr, fork: ForkInfo) -> Option<ForkInfo> {
+		let mut forks = self.forks.write();
+		let now = Utc::now();
+		forks.retain(|_, f| (now - f.last_seen).num_seconds() < FORK_EXPIRATION_SECS);
+
+		// Alert once per fork, not on every header that extends it
+		let alerted = match forks.get(peer) {
+			Some(f) => f.fork_height == fork.fork_height && f.length >= self.alert_depth,
+			None => false,
+		};
+		let alert = if fork.length >= self.alert_depth && !alerted {
+			warn!(
+				"Peer {} is on a fork {} blocks long at height {}, our chain has {} blocks above it. Fork tip {} at {}",
+				peer, fork.length, fork.fork_height, fork.depth, fork.tip_hash, fork.tip_height
+			);
+			Some(fork.clone())
+		} else {
+			None
+		};
+		forks.insert(peer.clone(), fork);
+
+		self.update_sync_state(&forks);
+		alert
+	}
+
+	fn remove_fork(&self, peer: &PeerAddr) {
+		let mut forks = self.forks.write();
+		if forks.remove(peer).is_some() {
+			self.update_sync_state(&forks);
+		}
+	}
+
+	fn update_sync_state(&self, forks: &HashMap<PeerAddr, ForkInfo>) {
+		let warnings = forks
+			.values()
+			.filter(|f| f.length >= self.alert_depth)
+			.cloned()
+			.collect();
+		self.sync_state.update_forks(warnings);
+	}
+}
+
+fn is_on_header_chain(chain: &Chain, hash: &Hash, height: u64) -> Result<bool, chain::Error> {
+	if height > chain.header_head()?.height {
+		return Ok(false);
+	}
+	Ok(chain.get_header_by_height(height)?.hash() == *hash)
+}
@@ -0,0 +1,398 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Embeddable node. Applications like GUI wallets or explorers can run a full node
+//! in-process with `Node` instead of running the `mwc` binary and polling its API.
+
+use std::sync::mpsc;
+use std::sync::Arc;
+
+use futures::channel::oneshot;
+
+use crate::chain::{self, BlockStatus, SyncState};
+use crate::common::hooks::{ChainEvents, NetEvents};
+use crate::common::stats::ServerStats;
+use crate::common::types::{Error, ServerConfig};
+use crate::core::core;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::mwc::server::{Server, ServerTxPool};
+use crate::p2p;
+use crate::p2p::types::PeerAddr;
+use crate::util::{RwLock, StopState};
+
+/// Events that are delivered to the node subscribers
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+	/// Block was accepted by the chain
+	BlockAccepted {
+		/// Block hash
+		hash: Hash,
+		/// Block height
+		height: u64,
+		/// How the block changed the chain (next, fork or reorg)
+		status: BlockStatus,
+	},
+	/// Block was received from the peer
+	BlockReceived {
+		/// Block hash
+		hash: Hash,
+		/// Block height
+		height: u64,
+		/// Peer that sent the block
+		peer: PeerAddr,
+	},
+	/// Header was received from the peer
+	HeaderReceived {
+		/// Header hash
+		hash: Hash,
+		/// Header height
+		height: u64,
+		/// Peer that sent the header
+		peer: PeerAddr,
+	},
+	/// Transaction was received from the network or the API
+	TransactionReceived {
+		/// Transaction hash
+		hash: Hash,
+	},
+}
+
+/// Delivers node events to all subscribers. Subscribers that dropped their receiver
+/// are removed on the next event.
+#[derive(Default)]
+pub struct NodeEventHub {
+	subscribers: RwLock<Vec<mpsc::Sender<NodeEvent>>>,
+}
+
+impl NodeEventHub {
+	/// New hub without subscribers
+	pub fn new() -> NodeEventHub {
+		NodeEventHub {
+			subscribers: RwLock::new(vec![]),
+		}
+	}
+
+	/// New subscription, events are delivered from the moment of the call
+	pub fn subscribe(&self) -> mpsc::Receiver<NodeEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.write().push(tx);
+		rx
+	}
+
+	fn broadcast(&self, event: NodeEvent) {
+		let mut subscribers = self.subscribers.write();
+		subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+	}
+}
+
+/// Chain and network hook that forwards events to the hub
+pub struct NodeEventHook {
+	hub: Arc<NodeEventHub>,
+}
+
+impl NodeEventHook {
+	/// Hook for the hub
+	pub fn new(hub: Arc<NodeEventHub>) -> NodeEventHook {
+		NodeEventHook { hub }
+	}
+}
+
+impl ChainEvents for NodeEventHook {
+	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+		self.hub.broadcast(NodeEvent::BlockAccepted {
+			hash: block.hash(),
+			height: block.header.height,
+			status,
+		});
+	}
+}
+
+impl NetEvents for NodeEventHook {
+	fn on_transaction_received(&self, tx: &core::Transaction) {
+		self.hub
+			.broadcast(NodeEvent::TransactionReceived { hash: tx.hash() });
+	}
+
+	fn on_block_received(&self, block: &core::Block, addr: &PeerAddr) {
+		self.hub.broadcast(NodeEvent::BlockReceived {
+			hash: block.hash(),
+			height: block.header.height,
+			peer: addr.clone(),
+		});
+	}
+
+	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {
+		self.hub.broadcast(NodeEvent::HeaderReceived {
+			hash: header.hash(),
+			height: header.height,
+			peer: addr.clone(),
+		});
+	}
+}
+
+/// Full node running in the current process. Starts the same services as the
+/// `mwc server run` command (p2p, sync, API, stratum and test miner if configured),
+/// but without the TUI and the logger setup, those are up to the application.
+pub struct Node {
+	server: Server,
+	events: Arc<NodeEventHub>,
+}
+
+impl Node {
+	/// Start the node with the provided config. Global chain type must be
+	/// initialized before the call.
+	pub fn start(config: ServerConfig) -> Result<Node, Error> {
+		Node::start_with_stop_state(config, None)
+	}
+
+	/// Start the node that can be stopped from outside with provided stop state
+	pub fn start_with_stop_state(
+		config: ServerConfig,
+		stop_state: Option<Arc<StopState>>,
+	) -> Result<Node, Error> {
+		// API server needs its own shutdown channel for every started node
+		let api_chan = oneshot::channel::<()>();
+		let events = Arc::new(NodeEventHub::new());
+		let server = Server::init(config, false, stop_state, api_chan, Some(events.clone()))?;
+		Ok(Node { server, events })
+	}
+
+	/// Subscribe to the chain and network events
+	pub fn subscribe(&self) -> mpsc::Receiver<NodeEvent> {
+		self.events.subscribe()
+	}
+
+	/// Handle to the chain
+	pub fn chain(&self) -> Arc<chain::Chain> {
+		self.server.chain.clone()
+	}
+
+	/// Handle to the transaction pool
+	pub fn tx_pool(&self) -> ServerTxPool {
+		self.server.tx_pool.clone()
+	}
+
+	/// Handle to the connected and known peers
+	pub fn peers(&self) -> Arc<p2p::Peers> {
+		self.server.p2p.peers.clone()
+	}
+
+	/// Sync state of the node
+	pub fn sync_state(&self) -> Arc<SyncState> {
+		self.server.sync_state.clone()
+	}
+
+	/// Node config
+	pub fn config(&self) -> &ServerConfig {
+		&self.server.config
+	}
+
+	/// Current chain head
+	pub fn head(&self) -> Result<chain::Tip, Error> {
+		self.server.head()
+	}
+
+	/// Current node stats, the same data that TUI is showing
+	pub fn stats(&self) -> Result<ServerStats, Error> {
+		self.server.get_server_stats()
+	}
+
+	/// Underlying server, for the functionality that is not covered by the node API
+	pub fn server(&self) -> &Server {
+		&self.server
+	}
+
+	/// Stop the node, blocks until all the threads are stopped
+	pub fn stop(self) {
+		self.server.stop();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::core::consensus;
+	use crate::core::global;
+	use crate::core::libtx::{reward, ProofBuilder};
+	use crate::core::pow;
+	use crate::keychain::{ExtKeychain, Keychain};
+	use std::collections::VecDeque;
+	use std::net::TcpListener;
+	use std::path::Path;
+	use std::time::Duration;
+
+	fn free_port() -> u16 {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		listener.local_addr().unwrap().port()
+	}
+
+	fn test_config(dir: &Path) -> ServerConfig {
+		ServerConfig {
+			db_root: dir.join("chain").to_str().unwrap().to_string(),
+			api_http_addr: format!("127.0.0.1:{}", free_port()),
+			api_secret_path: None,
+			foreign_api_secret_path: None,
+			p2p_config: p2p::P2PConfig {
+				host: "127.0.0.1".parse().unwrap(),
+				port: free_port(),
+				seeding_type: p2p::Seeding::None,
+				..p2p::P2PConfig::default()
+			},
+			stratum_mining_config: None,
+			chain_type: global::ChainTypes::AutomatedTesting,
+			skip_sync_wait: Some(true),
+			run_tui: Some(false),
+			libp2p_enabled: Some(false),
+			..ServerConfig::default()
+		}
+	}
+
+	// Mine the next block with the reward paid to a random key
+	fn mine_block(chain: &chain::Chain) -> core::Block {
+		let keychain = ExtKeychain::from_random_seed(false).unwrap();
+		let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+		let prev = chain.head_header().unwrap();
+		let next_header_info = consensus::next_difficulty(
+			prev.height + 1,
+			chain.difficulty_iter().unwrap(),
+			&mut VecDeque::new(),
+		);
+		let reward = reward::output(
+			&keychain,
+			&ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			prev.height + 1,
+			keychain.secp(),
+		)
+		.unwrap();
+		let mut b = core::Block::new(
+			&prev,
+			&[],
+			next_header_info.difficulty,
+			reward,
+			keychain.secp(),
+		)
+		.unwrap();
+		b.header.timestamp = prev.timestamp + chrono::Duration::seconds(60);
+		b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+		chain.set_txhashset_roots(&mut b).unwrap();
+		let edge_bits = global::min_edge_bits();
+		b.header.pow.proof.edge_bits = edge_bits;
+		pow::pow_size(
+			&mut b.header,
+			next_header_info.difficulty,
+			global::proofsize(),
+			edge_bits,
+		)
+		.unwrap();
+		b
+	}
+
+	#[test]
+	fn node_event_hub_delivery() {
+		let hub = Arc::new(NodeEventHub::new());
+		let hook = NodeEventHook::new(hub.clone());
+		let first = hub.subscribe();
+		let second = hub.subscribe();
+		let peer = PeerAddr::Ip("10.0.0.1:3414".parse().unwrap());
+
+		let block = core::Block::default();
+		hook.on_block_received(&block, &peer);
+		hook.on_header_received(&block.header, &peer);
+		for rx in &[&first, &second] {
+			match rx.try_recv().unwrap() {
+				NodeEvent::BlockReceived {
+					hash,
+					height,
+					peer: p,
+				} => {
+					assert_eq!(hash, block.hash());
+					assert_eq!(height, 0);
+					assert_eq!(p, peer);
+				}
+				e => panic!("unexpected event {:?}", e),
+			}
+			match rx.try_recv().unwrap() {
+				NodeEvent::HeaderReceived { hash, .. } => assert_eq!(hash, block.header.hash()),
+				e => panic!("unexpected event {:?}", e),
+			}
+		}
+
+		// Dropped subscriber is removed, the others still get the events
+		drop(second);
+		let tx = core::Transaction::empty();
+		hook.on_transaction_received(&tx);
+		assert_eq!(hub.subscribers.read().len(), 1);
+		match first.try_recv().unwrap() {
+			NodeEvent::TransactionReceived { hash } => assert_eq!(hash, tx.hash()),
+			e => panic!("unexpected event {:?}", e),
+		}
+		let status = BlockStatus::Next {
+			prev: chain::Tip::from_header(&block.header),
+		};
+		hook.on_block_accepted(&block, status);
+		assert!(matches!(
+			first.try_recv().unwrap(),
+			NodeEvent::BlockAccepted { height: 0, .. }
+		));
+		assert!(first.try_recv().is_err());
+	}
+
+	#[test]
+	fn node_start_stop() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		// Node threads read the global chain type
+		global::set_global_chain_type(global::ChainTypes::AutomatedTesting);
+
+		// Every node has its own API shutdown channel, nodes can be started one after another
+		for _ in 0..2 {
+			let dir = tempfile::tempdir().unwrap();
+			let node = Node::start(test_config(dir.path())).unwrap();
+			assert_eq!(node.head().unwrap().height, 0);
+			assert_eq!(node.peers().iter().connected().count(), 0);
+			node.stop();
+		}
+	}
+
+	#[test]
+	fn node_block_events() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		global::set_global_chain_type(global::ChainTypes::AutomatedTesting);
+
+		let dir = tempfile::tempdir().unwrap();
+		let node = Node::start(test_config(dir.path())).unwrap();
+		let events = node.subscribe();
+
+		let block = mine_block(&node.chain());
+		node.chain()
+			.process_block(block.clone(), chain::Options::MINE)
+			.unwrap();
+		match events.recv_timeout(Duration::from_secs(10)).unwrap() {
+			NodeEvent::BlockAccepted {
+				hash,
+				height,
+				status,
+			} => {
+				assert_eq!(hash, block.hash());
+				assert_eq!(height, 1);
+				assert!(matches!(status, BlockStatus::Next { .. }));
+			}
+			e => panic!("unexpected event {:?}", e),
+		}
+		assert_eq!(node.head().unwrap().height, 1);
+		node.stop();
+	}
+}
@@ -0,0 +1,143 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Owner API control of the node process, see `api::NodeControl`
+
+use crate::api::{
+	ConfigReload, DiskSubsystemStatus, NodeControl, PoolSubsystemStatus, StratumSubsystemStatus,
+	StratumWorkerStatus, TorSubsystemStatus,
+};
+use crate::common::stats::StratumStats;
+use crate::core::global;
+use crate::mwc::config_watcher::ConfigWatcher;
+use crate::mwc::disk_monitor::DiskMonitor;
+use crate::mwc::server::ServerTxPool;
+use crate::mwc::sync::sync_manager::SyncManager;
+use crate::util::StopState;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Shutdown, sync restart, config reload and the subsystems status for the owner API
+pub struct ServerNodeControl {
+	sync_manager: Arc<SyncManager>,
+	config_watcher: Arc<ConfigWatcher>,
+	tx_pool: ServerTxPool,
+	stratum_stats: Arc<StratumStats>,
+	// Tor is started before the server, its state doesn't change after that
+	tor: Option<TorSubsystemStatus>,
+	disk_monitor: Arc<DiskMonitor>,
+	stop_state: Arc<StopState>,
+}
+
+impl ServerNodeControl {
+	pub fn new(
+		sync_manager: Arc<SyncManager>,
+		config_watcher: Arc<ConfigWatcher>,
+		tx_pool: ServerTxPool,
+		stratum_stats: Arc<StratumStats>,
+		tor: Option<TorSubsystemStatus>,
+		disk_monitor: Arc<DiskMonitor>,
+		stop_state: Arc<StopState>,
+	) -> ServerNodeControl {
+		ServerNodeControl {
+			sync_manager,
+			config_watcher,
+			tx_pool,
+			stratum_stats,
+			tor,
+			disk_monitor,
+			stop_state,
+		}
+	}
+}
+
+impl NodeControl for ServerNodeControl {
+	// The process that runs the server is waiting for that flag, the same as for Ctrl+C.
+	// It calls Server::stop that stops the threads with the stop state and joins them.
+	fn shutdown(&self) {
+		global::request_server_stop();
+	}
+
+	// The sync thread restarts the sync on its next iteration
+	fn restart_sync(&self) -> Result<(), String> {
+		if self.stop_state.is_stopped() {
+			return Err("node is stopping".to_string());
+		}
+		self.sync_manager.request_restart();
+		Ok(())
+	}
+
+	fn reload_config(&self) -> Result<ConfigReload, String> {
+		self.config_watcher.reload()
+	}
+
+	fn pool_status(&self) -> PoolSubsystemStatus {
+		let tx_pool = self.tx_pool.read();
+		let eviction_fee_rate = if tx_pool.is_full() {
+			tx_pool.eviction_floor()
+		} else {
+			None
+		};
+		PoolSubsystemStatus {
+			size: tx_pool.txpool.size(),
+			stem_size: tx_pool.stempool.size(),
+			min_fee_base: global::get_accept_fee_base(),
+			eviction_fee_rate,
+		}
+	}
+
+	fn stratum_status(&self) -> Option<StratumSubsystemStatus> {
+		let stats = &self.stratum_stats;
+		if !stats.is_enabled.load(Ordering::Relaxed) {
+			return None;
+		}
+		Some(StratumSubsystemStatus {
+			running: stats.is_running.load(Ordering::Relaxed),
+			workers: stats.num_workers.load(Ordering::Relaxed),
+			network_hashrate: stats.network_hashrate.load(Ordering::Relaxed),
+			blocks_found: stats.blocks_found.load(Ordering::Relaxed),
+		})
+	}
+
+	fn stratum_workers(&self) -> Option<Vec<StratumWorkerStatus>> {
+		if !self.stratum_stats.is_enabled.load(Ordering::Relaxed) {
+			return None;
+		}
+		let workers = self
+			.stratum_stats
+			.get_login_stats()
+			.into_iter()
+			.map(|ws| StratumWorkerStatus {
+				login: ws.id,
+				connected: ws.is_connected,
+				last_seen: DateTime::<Utc>::from(ws.last_seen),
+				difficulty: ws.pow_difficulty,
+				accepted: ws.num_accepted,
+				rejected: ws.num_rejected,
+				stale: ws.num_stale,
+				blocks_found: ws.num_blocks_found,
+			})
+			.collect();
+		Some(workers)
+	}
+
+	fn tor_status(&self) -> Option<TorSubsystemStatus> {
+		self.tor.clone()
+	}
+
+	fn disk_status(&self) -> DiskSubsystemStatus {
+		self.disk_monitor.status()
+	}
+}
@@ -21,6 +21,7 @@
 use crate::core::global;
 use crate::core::global::{FLOONET_DNS_SEEDS, MAINNET_DNS_SEEDS};
 use crate::core::pow::Difficulty;
+use crate::mwc::supervisor::Supervisor;
 use crate::p2p;
 #[cfg(feature = "libp2p")]
 use crate::p2p::libp2p_connection;
@@ -49,143 +50,180 @@ const PEER_MAX_INITIATE_CONNECTIONS: usize = 50;
 
 const PEER_PING_INTERVAL: i64 = 10;
 
+// Interval in seconds between the tx announcements reconciliation rounds
+const TX_RECONCILIATION_INTERVAL: i64 = 2;
+
 pub fn connect_and_monitor(
 	p2p_server: Arc<p2p::Server>,
 	seed_list: Box<dyn Fn() -> Vec<PeerAddr> + Send>,
 	config: P2PConfig,
 	stop_state: Arc<StopState>,
 	use_tor_connection: bool,
+	supervisor: &Arc<Supervisor>,
 ) -> std::io::Result<thread::JoinHandle<()>> {
-	thread::Builder::new()
-		.name("seed".to_string())
-		.spawn(move || {
-			let peers = p2p_server.peers.clone();
+	supervisor.spawn("seed", move || {
+		let peers = p2p_server.peers.clone();
+
+		// open a channel with a listener that connects every peer address sent below
+		// max peer count
+		let (tx, rx) = mpsc::channel();
+		let seed_list = seed_list();
+
+		// check seeds first
+		let now = Utc::now();
+		let mut seed_connect_time;
+		let mut peers_connect_time = now + Duration::seconds(PEERS_CHECK_TIME_BOOST);
+		let mut expire_check_time = now + Duration::seconds(EXPIRE_INTERVAL);
+		let mut peer_monitor_time = now.clone();
+		let mut listen_time = now.clone();
+
+		let mut connecting_history: HashMap<PeerAddr, DateTime<Utc>> = HashMap::new();
+		let mut connection_threads: Vec<thread::JoinHandle<()>> = Vec::new();
+
+		connect_initial(&p2p_server, &config, &seed_list, &tx, |addr| {
+			connecting_history.insert(addr.clone(), Utc::now());
+			connection_threads.push(spawn_peer_connection(
+				addr,
+				p2p_server.peers.clone(),
+				p2p_server.clone(),
+				use_tor_connection,
+			));
+		});
+		seed_connect_time = Utc::now() + Duration::seconds(CONNECT_TO_SEED_INTERVAL);
+
+		#[cfg(feature = "libp2p")]
+		libp2p_connection::set_seed_list(&seed_list, true);
+
+		let mut prev_ping = Utc::now();
+		let mut prev_reconciliation = Utc::now();
+
+		let mut listen_q_addrs: Vec<PeerAddr> = Vec::new();
+		let mut addr_verify_thread: Option<thread::JoinHandle<()>> = None;
+
+		loop {
+			if stop_state.is_stopped() {
+				break;
+			}
+			// Pause egress peer connection request. Only for tests.
+			if stop_state.is_paused() {
+				thread::sleep(time::Duration::from_secs(1));
+				continue;
+			}
 
-			// open a channel with a listener that connects every peer address sent below
-			// max peer count
-			let (tx, rx) = mpsc::channel();
-			let seed_list = seed_list();
+			let connected_peers = peers.iter().connected().count();
 
-			// check seeds first
 			let now = Utc::now();
-			let mut seed_connect_time;
-			let mut peers_connect_time = now + Duration::seconds(PEERS_CHECK_TIME_BOOST);
-			let mut expire_check_time = now + Duration::seconds(EXPIRE_INTERVAL);
-			let mut peer_monitor_time = now.clone();
-			let mut listen_time = now.clone();
 
-			let mut connecting_history: HashMap<PeerAddr, DateTime<Utc>> = HashMap::new();
-
-			connect_to_seeds_and_peers(peers.clone(), tx.clone(), &seed_list, config.clone());
-			seed_connect_time = Utc::now() + Duration::seconds(CONNECT_TO_SEED_INTERVAL);
-
-			#[cfg(feature = "libp2p")]
-			libp2p_connection::set_seed_list(&seed_list, true);
-
-			let mut prev_ping = Utc::now();
-
-			let mut listen_q_addrs: Vec<PeerAddr> = Vec::new();
-			let mut connection_threads: Vec<thread::JoinHandle<()>> = Vec::new();
-
-			loop {
-				if stop_state.is_stopped() {
-					break;
-				}
-				// Pause egress peer connection request. Only for tests.
-				if stop_state.is_paused() {
-					thread::sleep(time::Duration::from_secs(1));
-					continue;
+			if connected_peers == 0 {
+				if now > seed_connect_time {
+					info!("No peers connected, trying to reconnect to seeds!");
+					connect_to_seeds_and_peers(
+						peers.clone(),
+						tx.clone(),
+						&seed_list,
+						config.clone(),
+					);
+					seed_connect_time = now + Duration::seconds(CONNECT_TO_SEED_INTERVAL);
 				}
+			}
 
-				let connected_peers = peers.iter().connected().count();
-
-				let now = Utc::now();
-
-				if connected_peers == 0 {
-					if now > seed_connect_time {
-						info!("No peers connected, trying to reconnect to seeds!");
-						connect_to_seeds_and_peers(
-							peers.clone(),
-							tx.clone(),
-							&seed_list,
-							config.clone(),
-						);
-						seed_connect_time = now + Duration::seconds(CONNECT_TO_SEED_INTERVAL);
-					}
-				}
+			// Check for and remove expired peers from the storage
+			if now > expire_check_time {
+				peers.remove_expired();
+				expire_check_time = now + Duration::seconds(EXPIRE_INTERVAL);
+			}
 
-				// Check for and remove expired peers from the storage
-				if now > expire_check_time {
-					peers.remove_expired();
-					expire_check_time = now + Duration::seconds(EXPIRE_INTERVAL);
+			let request_more_connections = now > listen_time;
+
+			// monitor peers first, then process sent requests with 'listen_for_addrs'
+			if now > peer_monitor_time || (request_more_connections && listen_q_addrs.is_empty()) {
+				// monitor additional peers if we need to add more
+				monitor_peers(
+					peers.clone(),
+					p2p_server.config.clone(),
+					use_tor_connection,
+					tx.clone(),
+					listen_q_addrs.is_empty(),
+					request_more_connections,
+				);
+
+				if peers.is_sync_mode() {
+					peer_monitor_time = now + Duration::seconds(PEERS_MONITOR_INTERVAL / 5); // every 12 seconds let's do the check
+				} else {
+					peer_monitor_time = now + Duration::seconds(PEERS_MONITOR_INTERVAL); // once a minute checking
 				}
+			}
 
-				let request_more_connections = now > listen_time;
-
-				// monitor peers first, then process sent requests with 'listen_for_addrs'
-				if now > peer_monitor_time
-					|| (request_more_connections && listen_q_addrs.is_empty())
-				{
-					// monitor additional peers if we need to add more
-					monitor_peers(
+			// make several attempts to get peers as quick as possible
+			// with exponential backoff
+			if now > peers_connect_time || request_more_connections {
+				let is_boost = peers.is_boosting_mode();
+				if peers.enough_outbound_peers() && !request_more_connections {
+					peers_connect_time = now + Duration::seconds(PEERS_CHECK_TIME_FULL);
+				} else {
+					// try to connect to any address sent to the channel
+					listen_for_addrs(
 						peers.clone(),
-						p2p_server.config.clone(),
+						p2p_server.clone(),
+						&rx,
+						&mut connecting_history,
 						use_tor_connection,
-						tx.clone(),
-						listen_q_addrs.is_empty(),
-						request_more_connections,
+						&mut connection_threads,
+						&mut listen_q_addrs,
+						&seed_list,
 					);
-
-					if peers.is_sync_mode() {
-						peer_monitor_time = now + Duration::seconds(PEERS_MONITOR_INTERVAL / 5); // every 12 seconds let's do the check
+					let duration = if is_boost || !listen_q_addrs.is_empty() {
+						PEERS_CHECK_TIME_BOOST
 					} else {
-						peer_monitor_time = now + Duration::seconds(PEERS_MONITOR_INTERVAL); // once a minute checking
-					}
+						PEERS_CHECK_TIME_FULL
+					};
+					peers_connect_time = now + Duration::seconds(duration);
+					listen_time = now + Duration::seconds(PEERS_LISTEN_MIN_INTERVAL);
 				}
+			}
 
-				// make several attempts to get peers as quick as possible
-				// with exponential backoff
-				if now > peers_connect_time || request_more_connections {
-					let is_boost = peers.is_boosting_mode();
-					if peers.enough_outbound_peers() && !request_more_connections {
-						peers_connect_time = now + Duration::seconds(PEERS_CHECK_TIME_FULL);
-					} else {
-						// try to connect to any address sent to the channel
-						listen_for_addrs(
-							peers.clone(),
-							p2p_server.clone(),
-							&rx,
-							&mut connecting_history,
-							use_tor_connection,
-							&mut connection_threads,
-							&mut listen_q_addrs,
-							&seed_list,
-						);
-						let duration = if is_boost || !listen_q_addrs.is_empty() {
-							PEERS_CHECK_TIME_BOOST
-						} else {
-							PEERS_CHECK_TIME_FULL
-						};
-						peers_connect_time = now + Duration::seconds(duration);
-						listen_time = now + Duration::seconds(PEERS_LISTEN_MIN_INTERVAL);
-					}
-				}
+			// Reconcile the tx announcements with the outbound peers.
+			if Utc::now() - prev_reconciliation > Duration::seconds(TX_RECONCILIATION_INTERVAL) {
+				peers.reconcile_transactions();
+				prev_reconciliation = Utc::now();
+			}
 
-				// Ping connected peers on every 10s to monitor peers.
-				if Utc::now() - prev_ping > Duration::seconds(PEER_PING_INTERVAL) {
-					let total_diff = peers.total_difficulty();
-					let total_height = peers.total_height();
-					if let (Ok(total_diff), Ok(total_height)) = (total_diff, total_height) {
-						peers.check_all(total_diff, total_height);
-						prev_ping = Utc::now();
-					} else {
-						error!("failed to get peers difficulty and/or height");
-					}
-				}
+			// Dial back the gossiped addresses. Not possible with Tor, direct
+			// connections would leak our IP.
+			if !use_tor_connection
+				&& peers.addr_verify_pending() > 0
+				&& addr_verify_thread
+					.as_ref()
+					.map(|h| h.is_finished())
+					.unwrap_or(true)
+			{
+				let p2p_server = p2p_server.clone();
+				addr_verify_thread = thread::Builder::new()
+					.name("addr_verify".to_string())
+					.spawn(move || p2p_server.verify_addrs())
+					.map_err(|e| error!("Unable to start addr_verify thread, {}", e))
+					.ok();
+			}
 
-				thread::sleep(time::Duration::from_secs(1));
+			// Ping connected peers on every 10s to monitor peers.
+			if Utc::now() - prev_ping > Duration::seconds(PEER_PING_INTERVAL) {
+				let total_diff = peers.total_difficulty();
+				let total_height = peers.total_height();
+				if let (Ok(total_diff), Ok(total_height)) = (total_diff, total_height) {
+					peers.check_all(total_diff, total_height);
+					prev_ping = Utc::now();
+				} else {
+					error!("failed to get peers difficulty and/or height");
+				}
 			}
-		})
+
+			thread::sleep(time::Duration::from_secs(1));
+		}
+
+		if let Some(h) = addr_verify_thread {
+			let _ = h.join();
+		}
+	})
 }
 
 fn monitor_peers(
@@ -247,10 +285,12 @@ fn monitor_peers(
 	let boost_peers_capabilities = peers.get_boost_peers_capabilities();
 	let in_sync_mode = peers.is_sync_mode();
 
-	// maintenance step first, clean up p2p server peers
+	// maintenance step first, clean up p2p server peers. The peer counts come from the
+	// peers, they can be changed by the config reload.
+	let peer_counts = peers.config();
 	peers.clean_peers(
-		config.peer_max_inbound_count() as usize,
-		config.peer_max_outbound_count(in_sync_mode) as usize,
+		peer_counts.peer_max_inbound_count() as usize,
+		peer_counts.peer_max_outbound_count(in_sync_mode) as usize,
 		boost_peers_capabilities,
 		config.clone(),
 	);
@@ -440,6 +480,13 @@ fn listen_for_addrs(
 			continue;
 		}
 
+		// Tor only mode, clearnet peers are not dialed
+		if let PeerAddr::Ip(_) = addr {
+			if peers.is_tor_only() {
+				continue;
+			}
+		}
+
 		connecting_history.insert(addr.clone(), now);
 
 		if p2p.socks_port == 0 {
@@ -451,67 +498,113 @@ fn listen_for_addrs(
 			}
 		}
 
-		let addr_c = addr.clone();
-		let peers_c = peers.clone();
-		let p2p_c = p2p.clone();
-		let thr = thread::Builder::new()
-			.name("peer_connect".to_string())
-			.spawn(move || {
-				// if we don't have a socks port, and it's onion, don't set as defunct because
-				// we don't know.
-				match p2p_c.connect(&addr_c) {
-					Ok(p) => {
-						debug!(
-							"New peer {} is connected as outbound! Capability: {:b}",
-							p.info.addr, p.info.capabilities
-						);
-						// If peer advertizes PEER_LIST then ask it for more peers that support PEER_LIST.
-						// We want to build a local db of possible peers to connect to.
-						// We do not necessarily care (at this point in time) what other capabilities these peers support.
-						if p.info.capabilities.contains(Capabilities::PEER_LIST) {
-							debug!("Sending peer request to {}", addr_c);
-							match p.send_peer_request(
-								Capabilities::PEER_LIST | peers_c.get_boost_peers_capabilities(),
-								use_tor_connection,
-							) {
-								Ok(_) => {
-									match addr_c {
-										PeerAddr::Onion(_) => {
-											#[cfg(feature = "libp2p")]
-											if let Err(_) = libp2p_connection::add_new_peer(&addr_c)
-											{
-												error!("Unable to add libp2p peer {}", addr_c);
-											}
+		let thr = spawn_peer_connection(addr, peers.clone(), p2p.clone(), use_tor_connection);
+		connection_threads.push(thr);
+	}
+}
+
+/// Initiate an outbound connection to the peer in a separate thread. On success the
+/// peer is asked for more peers and pinged, on failure it is marked as defunct.
+fn spawn_peer_connection(
+	addr: PeerAddr,
+	peers: Arc<p2p::Peers>,
+	p2p: Arc<p2p::Server>,
+	use_tor_connection: bool,
+) -> thread::JoinHandle<()> {
+	thread::Builder::new()
+		.name("peer_connect".to_string())
+		.spawn(move || {
+			// if we don't have a socks port, and it's onion, don't set as defunct because
+			// we don't know.
+			match p2p.connect(&addr) {
+				Ok(p) => {
+					debug!(
+						"New peer {} is connected as outbound! Capability: {:b}",
+						p.info.addr, p.info.capabilities
+					);
+					// If peer advertizes PEER_LIST then ask it for more peers that support PEER_LIST.
+					// We want to build a local db of possible peers to connect to.
+					// We do not necessarily care (at this point in time) what other capabilities these peers support.
+					if p.info.capabilities.contains(Capabilities::PEER_LIST) {
+						debug!("Sending peer request to {}", addr);
+						match p.send_peer_request(
+							Capabilities::PEER_LIST | peers.get_boost_peers_capabilities(),
+							use_tor_connection,
+						) {
+							Ok(_) => {
+								match addr {
+									PeerAddr::Onion(_) => {
+										#[cfg(feature = "libp2p")]
+										if let Err(_) = libp2p_connection::add_new_peer(&addr) {
+											error!("Unable to add libp2p peer {}", addr);
 										}
-										_ => (),
-									};
-								}
-								Err(e) => {
-									error!(
-										"Failed send_peer_request to {}, Error: {}",
-										p.info.addr, e
-									);
-								}
+									}
+									_ => (),
+								};
+							}
+							Err(e) => {
+								error!("Failed send_peer_request to {}, Error: {}", p.info.addr, e);
 							}
 						}
-						// Requesting ping as well, need to know the height asap
-						let total_diff = peers_c.total_difficulty().unwrap_or(Difficulty::zero());
-						let total_height = peers_c.total_height().unwrap_or(0);
-						if let Err(e) = p.send_ping(total_diff, total_height) {
-							error!("Failed send_ping to {}, Error: {}", p.info.addr, e);
-						}
-
-						let _ = peers_c.update_state(&addr_c, p2p::State::Healthy);
 					}
-					Err(e) => {
-						debug!("Connection to the peer {} was rejected, {}", addr_c, e);
-						let _ = peers_c.update_state(&addr_c, p2p::State::Defunct);
+					// Requesting ping as well, need to know the height asap
+					let total_diff = peers.total_difficulty().unwrap_or(Difficulty::zero());
+					let total_height = peers.total_height().unwrap_or(0);
+					if let Err(e) = p.send_ping(total_diff, total_height) {
+						error!("Failed send_ping to {}, Error: {}", p.info.addr, e);
 					}
+
+					let _ = peers.update_state(&addr, p2p::State::Healthy);
 				}
-			})
-			.expect("failed to launch peer_connect thread");
-		connection_threads.push(thr);
+				Err(e) => {
+					debug!("Connection to the peer {} was rejected, {}", addr, e);
+					let _ = peers.update_state(&addr, p2p::State::Defunct);
+				}
+			}
+		})
+		.expect("failed to launch peer_connect thread")
+}
+
+/// Initial connections on startup. The anchor peers saved on the last clean shutdown
+/// are dialed right away with `dial`, before the seeds and the peers from the db are
+/// queued, so restarting the node doesn't give an attacker a chance to surround it
+/// with its own peers.
+fn connect_initial<F>(
+	p2p_server: &Arc<p2p::Server>,
+	config: &P2PConfig,
+	seed_list: &Vec<PeerAddr>,
+	tx: &mpsc::Sender<PeerAddr>,
+	mut dial: F,
+) where
+	F: FnMut(PeerAddr),
+{
+	let peers_deny = config.peers_deny.clone().unwrap_or(PeerAddrs::default());
+
+	for addr in p2p_server.peers.get_anchors() {
+		if peers_deny.contains(&addr) {
+			continue;
+		}
+		if let Some(peers_allow) = &config.peers_allow {
+			if !peers_allow.contains(&addr) {
+				continue;
+			}
+		}
+		if p2p_server.socks_port == 0 {
+			if let Onion(_) = &addr {
+				continue;
+			}
+		}
+
+		info!("Connecting to anchor peer {}", addr);
+		dial(addr);
 	}
+
+	connect_to_seeds_and_peers(
+		p2p_server.peers.clone(),
+		tx.clone(),
+		seed_list,
+		config.clone(),
+	);
 }
 
 pub fn default_dns_seeds() -> Box<dyn Fn() -> Vec<PeerAddr> + Send> {
@@ -569,3 +662,63 @@ pub fn resolve_dns_to_addrs(dns_records: &Vec<String>) -> Vec<PeerAddr> {
 pub fn predefined_seeds(addrs: Vec<PeerAddr>) -> Box<dyn Fn() -> Vec<PeerAddr> + Send> {
 	Box::new(move || addrs.clone())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain::SyncState;
+	use crate::core::core::hash::Hash;
+	use crate::p2p::store::PeerStore;
+
+	fn addr(s: &str) -> PeerAddr {
+		PeerAddr::Ip(s.parse().unwrap())
+	}
+
+	#[test]
+	fn test_anchors_dialed_before_seeds() {
+		global::init_global_chain_type(global::ChainTypes::AutomatedTesting);
+		let db_root = tempfile::tempdir().unwrap();
+		let db_root = db_root.path().to_str().unwrap();
+		let anchors = vec![addr("10.0.0.1:3414"), addr("10.0.0.2:3414")];
+		{
+			// anchors of the previous run
+			let store = PeerStore::new(db_root).unwrap();
+			store.save_anchors(&anchors).unwrap();
+		}
+
+		let config = P2PConfig {
+			peers_allow: None,
+			peers_deny: None,
+			peers_preferred: None,
+			..P2PConfig::default()
+		};
+		let p2p_server = Arc::new(
+			p2p::Server::new(
+				db_root,
+				Capabilities::UNKNOWN,
+				config.clone(),
+				Arc::new(p2p::DummyAdapter {}),
+				Hash::from_vec(&[]),
+				Arc::new(SyncState::new()),
+				Arc::new(StopState::new()),
+				0,
+				None,
+			)
+			.unwrap(),
+		);
+
+		let seeds = vec![addr("10.0.1.1:3414"), addr("10.0.1.2:3414")];
+		let (tx, rx) = mpsc::channel();
+		let mut dialed = vec![];
+		connect_initial(&p2p_server, &config, &seeds, &tx, |addr| {
+			// no seeds are queued yet when the anchor is dialed
+			assert!(rx.try_recv().is_err());
+			dialed.push(addr);
+		});
+
+		assert_eq!(dialed.len(), anchors.len());
+		assert!(anchors.iter().all(|a| dialed.contains(a)));
+		let queued: Vec<PeerAddr> = rx.try_iter().collect();
+		assert_eq!(queued, seeds);
+	}
+}
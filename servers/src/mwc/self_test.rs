@@ -0,0 +1,80 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Startup self-test. The genesis block is known to be valid, so its PoW, range
+//! proof, kernel signature and serialization are checked before the node starts.
+//! A miscompiled or corrupted binary fails here instead of rejecting valid blocks
+//! from the network and banning honest peers.
+
+use crate::common::types::Error;
+use crate::core::core::hash::Hashed;
+use crate::core::core::Block;
+use crate::core::pow;
+use crate::core::ser::{self, DeserializationMode, ProtocolVersion};
+use mwc_util::secp::{ContextFlag, Secp256k1};
+use std::time::Instant;
+
+/// Verify the genesis block with all the backends. Returns the error with all
+/// failed checks.
+pub fn run_self_test(genesis: &Block) -> Result<(), Error> {
+	let start = Instant::now();
+	let mut failures: Vec<String> = vec![];
+
+	if let Err(e) = pow::verify_size(&genesis.header) {
+		failures.push(format!("PoW verification failed, {}", e));
+	}
+
+	let secp = Secp256k1::with_caps(ContextFlag::Commit);
+	for output in genesis.outputs() {
+		if let Err(e) = output.verify_proof(&secp) {
+			failures.push(format!("range proof verification failed, {}", e));
+		}
+	}
+	for kernel in genesis.kernels() {
+		if let Err(e) = kernel.verify(&secp) {
+			failures.push(format!("kernel signature verification failed, {}", e));
+		}
+	}
+
+	let version = ProtocolVersion::local();
+	match ser::ser_vec(genesis, version).and_then(|data| {
+		let block: Block =
+			ser::deserialize(&mut &data[..], version, DeserializationMode::default())?;
+		let data2 = ser::ser_vec(&block, version)?;
+		Ok((block, data == data2))
+	}) {
+		Ok((block, same_data)) => {
+			if block.hash() != genesis.hash() || !same_data {
+				failures.push("block serialization round trip doesn't match".to_string());
+			}
+		}
+		Err(e) => failures.push(format!("block serialization round trip failed, {}", e)),
+	}
+
+	if failures.is_empty() {
+		debug!(
+			"Startup self-test passed in {} ms",
+			start.elapsed().as_millis()
+		);
+		Ok(())
+	} else {
+		for f in &failures {
+			error!("Startup self-test: {}", f);
+		}
+		Err(Error::General(format!(
+			"Startup self-test failed, the binary might be miscompiled or corrupted: {}",
+			failures.join("; ")
+		)))
+	}
+}
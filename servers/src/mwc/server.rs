@@ -18,6 +18,7 @@
 //! as a facade.
 
 use crate::tor::config as tor_config;
+use crate::tor::control::TorControl;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
@@ -42,24 +43,37 @@ use crate::chain::{self, SyncState, SyncStatus};
 use crate::common::adapters::{
 	ChainToPoolAndNetAdapter, NetToChainAdapter, PoolToChainAdapter, PoolToNetAdapter,
 };
-use crate::common::hooks::{init_chain_hooks, init_net_hooks};
+use crate::common::hooks::{
+	init_chain_hooks, init_net_hooks, init_zmq_hooks, TipEventHook, WsEventHook,
+};
 use crate::common::stats::{
 	ChainStats, DiffBlock, DiffStats, PeerStats, ServerStateInfo, ServerStats, TxStats,
 };
 use crate::common::types::{Error, ServerConfig, StratumServerConfig};
-use crate::core::core::hash::{Hashed, ZERO_HASH};
+use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
+use crate::core::core::{verifier_cache, Block};
 use crate::core::ser::ProtocolVersion;
 use crate::core::stratum::connections;
 use crate::core::{consensus, genesis, global, pow};
+use crate::mining::block_template::BlockTemplates;
 use crate::mining::stratumserver;
 use crate::mining::test_miner::Miner;
-use crate::mwc::{dandelion_monitor, seed, sync};
+use crate::mwc::config_watcher::{self, ConfigLoader, ConfigWatcher};
+use crate::mwc::disk_monitor::{self, DiskMonitor};
+use crate::mwc::node::{NodeEventHook, NodeEventHub};
+use crate::mwc::node_control::ServerNodeControl;
+use crate::mwc::supervisor::Supervisor;
+use crate::mwc::tx_generator::{self, TxGenerator, TxGeneratorHook};
+use crate::mwc::webhooks::{self, WebhookDispatcher, WebhookHook};
+use crate::mwc::{block_compressor, dandelion_monitor, seed, self_test, sync, txhashset_monitor};
 use crate::p2p;
 use crate::p2p::types::PeerAddr;
 use crate::pool;
+use crate::store;
+use crate::store::migration::{MigrationOptions, MigrationReport};
 use crate::tor::process as tor_process;
 use crate::util::file::get_first_line;
-use crate::util::{RwLock, StopState};
+use crate::util::{Mutex, RwLock, StopState};
 use futures::channel::oneshot;
 use mwc_util::logger::LogEntry;
 use mwc_util::secp::{Secp256k1, SecretKey};
@@ -108,6 +122,22 @@ pub struct Server {
 	connect_thread: Option<JoinHandle<()>>,
 	sync_thread: JoinHandle<()>,
 	dandelion_thread: JoinHandle<()>,
+	txhashset_zip_thread: Option<JoinHandle<()>>,
+	block_compression_thread: Option<JoinHandle<()>>,
+	/// Restarts the non-critical components after a panic
+	supervisor: Arc<Supervisor>,
+	/// Sync process, for the stats
+	sync_manager: Arc<SyncManager>,
+	/// Synthetic transactions generator, developer mode
+	tx_generator: Option<Arc<TxGenerator>>,
+	tx_generator_thread: Option<JoinHandle<()>>,
+	webhook_thread: Option<JoinHandle<()>>,
+	/// Applies the config changes on SIGHUP or by the owner API
+	config_watcher: Arc<ConfigWatcher>,
+	config_watcher_thread: Option<JoinHandle<()>>,
+	disk_monitor_thread: JoinHandle<()>,
+	/// Control connection of the ephemeral onion service
+	tor_control: Option<TorControl>,
 }
 
 impl Server {
@@ -120,11 +150,26 @@ impl Server {
 		mut info_callback: F,
 		allow_to_stop: bool,
 		stop_state: Option<Arc<StopState>>,
-		api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 	) -> Result<(), Error>
 	where
 		F: FnMut(Server, Option<mpsc::Receiver<LogEntry>>),
 	{
+		let serv = Server::init(config, allow_to_stop, stop_state, api_chan, None)?;
+		info_callback(serv, logs_rx);
+		Ok(())
+	}
+
+	/// Instantiates and starts a new server together with the stratum server and the
+	/// test miner if they are enabled. Optional event hub receives the chain and network
+	/// events for the embedding application.
+	pub(crate) fn init(
+		config: ServerConfig,
+		allow_to_stop: bool,
+		stop_state: Option<Arc<StopState>>,
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
+		event_hub: Option<Arc<NodeEventHub>>,
+	) -> Result<Server, Error> {
 		if let Some(hashes) = config.invalid_block_hashes.as_ref() {
 			if hashes.len() > 0 {
 				info!("config.invalid_block_hashes = {:?}", hashes);
@@ -132,6 +177,19 @@ impl Server {
 		}
 
 		mwc_chain::pipe::init_invalid_lock_hashes(&config.invalid_block_hashes)?;
+		mwc_chain::checkpoints::init_checkpoints(
+			&config.chain_type,
+			config.use_checkpoints.unwrap_or(true),
+			&config.checkpoints,
+		)?;
+		mwc_chain::reorg_guard::init_max_reorg_depth(config.max_reorg_depth);
+		mwc_chain::validation_pool::init_validation_threads(config.validation_threads)?;
+		verifier_cache::init_verifier_cache(
+			config
+				.verifier_cache_size
+				.unwrap_or(verifier_cache::DEFAULT_VERIFIER_CACHE_SIZE),
+		);
+		store::init_backend(config.db_backend)?;
 
 		let mining_config = config.stratum_mining_config.clone();
 		let enable_test_miner = config.run_test_miner;
@@ -156,6 +214,7 @@ impl Server {
 			stratum_ip_pool.clone(),
 			stop_state,
 			api_chan,
+			event_hub,
 		)?;
 
 		if let Some(c) = mining_config {
@@ -179,8 +238,7 @@ impl Server {
 			}
 		}
 
-		info_callback(serv, logs_rx);
-		Ok(())
+		Ok(serv)
 	}
 
 	// Exclusive (advisory) lock_file to ensure we do not run multiple
@@ -208,6 +266,116 @@ impl Server {
 		Ok(Arc::new(lock_file))
 	}
 
+	fn genesis_block(chain_type: &global::ChainTypes) -> Block {
+		match chain_type {
+			global::ChainTypes::AutomatedTesting => pow::mine_genesis_block().unwrap(),
+			global::ChainTypes::UserTesting => pow::mine_genesis_block().unwrap(),
+			global::ChainTypes::Floonet => genesis::genesis_floo(),
+			global::ChainTypes::Mainnet => genesis::genesis_main(),
+		}
+	}
+
+	/// Offline chain data integrity check, the node must not be running. If `truncate` is set
+	/// and an inconsistent height is found, the chain is reset to the block below it.
+	pub fn check_chain_integrity(
+		config: &ServerConfig,
+		fast_validation: bool,
+		truncate: bool,
+	) -> Result<chain::IntegrityReport, Error> {
+		let _lock_file = Server::one_mwc_at_a_time(config)?;
+		store::init_backend(config.db_backend)?;
+
+		let chain = chain::Chain::init(
+			config.db_root.clone(),
+			Arc::new(chain::types::NoopAdapter {}),
+			Server::genesis_block(&config.chain_type),
+			pow::verify_size,
+			config.archive_mode.unwrap_or(false),
+		)?;
+		let report = chain.check_integrity(fast_validation)?;
+
+		if let Some(bad_height) = report.first_bad_height {
+			if truncate && bad_height > 0 {
+				let header = chain.get_header_by_height(bad_height - 1)?;
+				warn!(
+					"Truncating the chain to {} at {}",
+					header.hash(),
+					header.height
+				);
+				chain.reset_chain_head(&header, true)?;
+			}
+		}
+		Ok(report)
+	}
+
+	/// Offline schema migration of the chain and the peer stores, the node must not be
+	/// running. The dry run only reports the pending migrations.
+	pub fn migrate_stores(
+		config: &ServerConfig,
+		options: &MigrationOptions,
+	) -> Result<Vec<MigrationReport>, Error> {
+		let _lock_file = Server::one_mwc_at_a_time(config)?;
+		store::init_backend(config.db_backend)?;
+
+		let chain_report = chain::ChainStore::run_migrations(&config.db_root, options)?;
+		let peer_report = p2p::store::PeerStore::run_migrations(&config.db_root, options)?;
+		Ok(vec![chain_report, peer_report])
+	}
+
+	/// Offline restore of the chain data snapshot, the node must not be running. The restored
+	/// chain is opened once to check that it is usable.
+	pub fn restore_snapshot(
+		config: &ServerConfig,
+		snapshot_dir: &Path,
+	) -> Result<chain::snapshot::SnapshotRestore, Error> {
+		let _lock_file = Server::one_mwc_at_a_time(config)?;
+		store::init_backend(config.db_backend)?;
+
+		let restore = chain::snapshot::restore_snapshot(snapshot_dir, Path::new(&config.db_root))?;
+		if let Err(e) = chain::Chain::init(
+			config.db_root.clone(),
+			Arc::new(chain::types::NoopAdapter {}),
+			Server::genesis_block(&config.chain_type),
+			pow::verify_size,
+			config.archive_mode.unwrap_or(false),
+		) {
+			if let Some(backup_dir) = &restore.backup_dir {
+				error!(
+					"Restored chain data can't be opened, the previous data is kept at {}",
+					backup_dir.display()
+				);
+			}
+			return Err(e.into());
+		}
+		Ok(restore)
+	}
+
+	/// Offline bootstrap of the empty chain from the UTXO archive, the node must not be
+	/// running. The archive header must have `trusted_hash`.
+	pub fn import_utxo_archive(
+		config: &ServerConfig,
+		path: &Path,
+		trusted_hash: Hash,
+	) -> Result<chain::utxo_archive::UtxoArchiveInfo, Error> {
+		let _lock_file = Server::one_mwc_at_a_time(config)?;
+		store::init_backend(config.db_backend)?;
+
+		let chain = chain::Chain::init(
+			config.db_root.clone(),
+			Arc::new(chain::types::NoopAdapter {}),
+			Server::genesis_block(&config.chain_type),
+			pow::verify_size,
+			config.archive_mode.unwrap_or(false),
+		)?;
+		let info = chain.import_utxo_archive(
+			path,
+			trusted_hash,
+			Arc::new(SyncState::new()),
+			Arc::new(StopState::new()),
+		)?;
+		Ok(info)
+	}
+
 	// We don't want allow_to_stop in config because it is too dangerous flag. We don't
 	// want to forget about that, make default e.t.c. That is why it is separated
 
@@ -217,7 +385,8 @@ impl Server {
 		allow_to_stop: bool,
 		stratum_ip_pool: Arc<connections::StratumIpPool>,
 		stop_state: Option<Arc<StopState>>,
-		api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+		api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
+		event_hub: Option<Arc<NodeEventHub>>,
 	) -> Result<Server, Error> {
 		//let duration_sync_long = config.duration_sync_long.unwrap_or(150);
 		//let duration_sync_short = config.duration_sync_short.unwrap_or(100);
@@ -231,6 +400,10 @@ impl Server {
 			e
 		})?;
 
+		if let Some(http_client_config) = config.http_client_config.clone() {
+			api::client::init_http_client(http_client_config)?;
+		}
+
 		// Defaults to None (optional) in config file.
 		// This translates to false here.
 		let archive_mode = match config.archive_mode {
@@ -243,9 +416,26 @@ impl Server {
 		} else {
 			Arc::new(StopState::new())
 		};
+		let supervisor = Arc::new(Supervisor::new(stop_state.clone()));
 
 		let pool_adapter = Arc::new(PoolToChainAdapter::new());
-		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(config.dandelion_config.clone()));
+		let ws_events = Arc::new(api::WsEventBus::new());
+		let (zmq_chain_hooks, mut pool_hooks) =
+			init_zmq_hooks(&config).map_err(Error::Configuration)?;
+		let webhook_dispatcher = if config.webhook_dispatcher_config.endpoints.is_empty() {
+			None
+		} else {
+			let dispatcher = Arc::new(WebhookDispatcher::new(
+				config.webhook_dispatcher_config.clone(),
+			)?);
+			pool_hooks.push(Box::new(WebhookHook::new(dispatcher.clone())));
+			Some(dispatcher)
+		};
+		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(
+			config.dandelion_config.clone(),
+			ws_events.clone(),
+			pool_hooks,
+		));
 		let tx_pool = Arc::new(RwLock::new(pool::TransactionPool::new(
 			config.pool_config.clone(),
 			pool_adapter.clone(),
@@ -254,34 +444,81 @@ impl Server {
 
 		let sync_state = Arc::new(SyncState::new());
 
-		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(
-			tx_pool.clone(),
-			init_chain_hooks(&config),
-		));
+		let mut chain_hooks = init_chain_hooks(&config);
+		let mut net_hooks = init_net_hooks(&config);
+		chain_hooks.push(Box::new(WsEventHook::new(ws_events.clone())));
+		chain_hooks.extend(zmq_chain_hooks);
+		if let Some(dispatcher) = &webhook_dispatcher {
+			chain_hooks.push(Box::new(WebhookHook::new(dispatcher.clone())));
+		}
+		let tip_events = Arc::new(api::TipEventBus::new());
+		chain_hooks.push(Box::new(TipEventHook::new(tip_events.clone())));
+		if let Some(hub) = &event_hub {
+			chain_hooks.push(Box::new(NodeEventHook::new(hub.clone())));
+			net_hooks.push(Box::new(NodeEventHook::new(hub.clone())));
+		}
 
-		let genesis = match config.chain_type {
-			global::ChainTypes::AutomatedTesting => pow::mine_genesis_block().unwrap(),
-			global::ChainTypes::UserTesting => pow::mine_genesis_block().unwrap(),
-			global::ChainTypes::Floonet => genesis::genesis_floo(),
-			global::ChainTypes::Mainnet => genesis::genesis_main(),
+		let tx_generator_rate = config.tx_generator_rate.unwrap_or(0);
+		let tx_generator = if tx_generator_rate == 0 {
+			None
+		} else {
+			match config.chain_type {
+				global::ChainTypes::UserTesting | global::ChainTypes::Floonet => {
+					let generator = Arc::new(TxGenerator::new()?);
+					net_hooks.push(Box::new(TxGeneratorHook::new(generator.clone())));
+					Some(generator)
+				}
+				_ => {
+					error!("tx_generator_rate is ignored, tx generator is allowed only for usertesting and floonet");
+					None
+				}
+			}
 		};
 
+		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(tx_pool.clone(), chain_hooks));
+
+		let genesis = Server::genesis_block(&config.chain_type);
+
 		info!("Starting server, genesis block: {}", genesis.hash());
 
-		let shared_chain = Arc::new(chain::Chain::init(
-			config.db_root.clone(),
-			chain_adapter.clone(),
-			genesis.clone(),
-			pow::verify_size,
-			archive_mode,
-		)?);
+		// Refuse to start if the binary can't validate the known good data
+		self_test::run_self_test(&genesis)?;
+
+		let shared_chain = Arc::new(
+			chain::Chain::init(
+				config.db_root.clone(),
+				chain_adapter.clone(),
+				genesis.clone(),
+				pow::verify_size,
+				archive_mode,
+			)?
+			.with_archive_depth(config.archive_depth.unwrap_or(0))
+			.with_history_backfill(config.history_backfill.unwrap_or(false))
+			.with_header_only(config.is_header_only())
+			.with_index(config.enable_index.unwrap_or(false))?,
+		);
+		if config.is_header_only() {
+			warn!("Running headers only node, blocks and txhashset are not synced");
+		}
 
 		pool_adapter.set_chain(shared_chain.clone());
 
+		// Measured before the sync starts, the state download is checked against it
+		let disk_monitor = Arc::new(DiskMonitor::new(
+			config.disk_monitor_config.clone(),
+			shared_chain.clone(),
+			PathBuf::from(&config.db_root),
+		));
+		if let Err(e) = disk_monitor.refresh() {
+			warn!("Unable to check the disk usage, {}", e);
+		}
+
 		let sync_manager: Arc<SyncManager> = Arc::new(SyncManager::new(
 			shared_chain.clone(),
 			sync_state.clone(),
 			stop_state.clone(),
+			disk_monitor.clone(),
+			config.is_header_only(),
 		));
 
 		let net_adapter = Arc::new(NetToChainAdapter::new(
@@ -290,11 +527,14 @@ impl Server {
 			sync_manager.clone(),
 			tx_pool.clone(),
 			config.clone(),
-			init_net_hooks(&config),
+			net_hooks,
 		));
 
 		api::reset_server_onion_address();
 
+		// Ephemeral onion service of the external tor, removed on shutdown
+		let mut tor_control = None;
+
 		#[allow(unused_variables)]
 		let (onion_address, tor_secret) = if config.tor_config.tor_enabled {
 			if !config.p2p_config.host.is_loopback() {
@@ -370,6 +610,38 @@ impl Server {
 				})?;
 				debug_assert!(secret.is_some());
 				(onion_address, secret)
+			} else if let Some(control_port) = config.tor_config.control_port {
+				let secret = SecretKey::new(shared_chain.secp(), &mut rand::thread_rng());
+				let ports = vec![
+					(
+						80,
+						format!("{}:{}", config.p2p_config.host, config.p2p_config.port),
+					),
+					(8080, config.api_http_addr.clone()),
+					(
+						global::get_tor_libp2p_port(),
+						format!("127.0.0.1:{}", config.libp2p_port.unwrap_or(3417)),
+					),
+				];
+				let mut control = TorControl::connect(
+					control_port,
+					config.tor_config.control_password.as_deref(),
+				)
+				.map_err(|e| {
+					Error::Configuration(format!(
+						"Unable to connect to the tor control port {}, {}",
+						control_port, e
+					))
+				})?;
+				let address = control.add_onion(&secret.0, &ports).map_err(|e| {
+					Error::Configuration(format!("Unable to create the onion service, {}", e))
+				})?;
+				info!(
+					"Tor configured to run externally! Ephemeral onion address = {}.onion",
+					address
+				);
+				tor_control = Some(control);
+				(Some(format!("{}.onion", address)), Some(to_hex(&secret.0)))
 			} else {
 				let onion_address = config.tor_config.onion_address.clone();
 
@@ -402,6 +674,21 @@ impl Server {
 			socks_port, config.tor_config.tor_enabled
 		);
 
+		// Tor is started at that point, the node exits if it failed
+		let tor_status = if config.tor_config.tor_enabled {
+			Some(api::TorSubsystemStatus {
+				external: config.tor_config.tor_external,
+				bootstrapped: if config.tor_config.tor_external {
+					None
+				} else {
+					Some(onion_address.is_some())
+				},
+				onion_address: onion_address.clone(),
+			})
+		} else {
+			None
+		};
+
 		// Initialize libp2p server
 		#[cfg(feature = "libp2p")]
 		if config.libp2p_enabled.unwrap_or(true) && onion_address.is_some() && tor_secret.is_some()
@@ -515,10 +802,14 @@ impl Server {
 
 		// Initialize our capabilities.
 		// Currently either "default" or with optional "archive_mode" (block history) support enabled.
-		let capabilities = Capabilities::new(
-			onion_address.is_some(),
-			config.archive_mode.unwrap_or(false),
-		);
+		let capabilities = if config.is_header_only() {
+			Capabilities::header_only(onion_address.is_some())
+		} else {
+			Capabilities::new(
+				onion_address.is_some(),
+				config.archive_mode.unwrap_or(false),
+			)
+		};
 		debug!("Capabilities: {:?}", capabilities);
 		let use_tor = onion_address.is_some();
 
@@ -533,6 +824,9 @@ impl Server {
 			socks_port,
 			onion_address,
 		)?);
+		p2p_server
+			.peers
+			.set_history_depth(shared_chain.history_depth()?);
 
 		// Initialize various adapters with our dynamic set of connected peers.
 		chain_adapter.init(p2p_server.peers.clone());
@@ -565,6 +859,7 @@ impl Server {
 				config.p2p_config.clone(),
 				stop_state.clone(),
 				use_tor,
+				&supervisor,
 			)?);
 		}
 
@@ -578,6 +873,7 @@ impl Server {
 			shared_chain.clone(),
 			stop_state.clone(),
 			sync_manager.clone(),
+			config.stale_tip_timeout_minutes.unwrap_or(0),
 		)?;
 
 		let p2p_inner = p2p_server.clone();
@@ -591,10 +887,28 @@ impl Server {
 			})?;
 
 		info!("Starting rest apis at: {}", &config.api_http_addr);
-		let api_secret = get_first_line(config.api_secret_path.clone());
+		let mut api_tokens = api::ApiTokenStore::new();
+		if let Some(api_secret) = get_first_line(config.api_secret_path.clone()) {
+			api_tokens.add("api_secret".to_string(), api_secret, api::ApiScope::Owner);
+		}
+		for token in config.api_tokens.clone().unwrap_or_default() {
+			let secret = get_first_line(Some(token.token_path.clone())).ok_or_else(|| {
+				Error::ArgumentError(format!(
+					"Unable to read API key {} from {}",
+					token.name, token.token_path
+				))
+			})?;
+			info!("API key {} with scope {:?}", token.name, token.scope);
+			api_tokens.add(token.name, secret, token.scope);
+		}
 		let foreign_api_secret = get_first_line(config.foreign_api_secret_path.clone());
 		let tls_conf = match config.tls_certificate_file.clone() {
-			None => None,
+			None => {
+				if config.tls_client_ca_file.is_some() {
+					warn!("tls_client_ca_file is ignored, TLS certificate is not set");
+				}
+				None
+			}
 			Some(file) => {
 				let key = match config.tls_certificate_key.clone() {
 					Some(k) => k,
@@ -603,10 +917,39 @@ impl Server {
 						return Err(Error::ArgumentError(msg));
 					}
 				};
-				Some(TLSConfig::new(file, key))
+				Some(TLSConfig::new(file, key).with_client_ca(config.tls_client_ca_file.clone()))
 			}
 		};
 
+		// Mining API uses the stratum coinbase settings, not available without them
+		let block_templates: Option<Arc<dyn api::BlockTemplateProvider>> =
+			match &config.stratum_mining_config {
+				Some(c) if !config.is_header_only() => Some(Arc::new(BlockTemplates::new(
+					shared_chain.clone(),
+					tx_pool.clone(),
+					c,
+				))),
+				_ => None,
+			};
+
+		// Shared with the API middleware, so the config reload can change the limits
+		let rate_limiter = config
+			.rate_limit_config
+			.clone()
+			.map(|c| Arc::new(Mutex::new(api::ApiRateLimiter::new(c))));
+
+		let config_watcher = Arc::new(ConfigWatcher::new(
+			config.clone(),
+			p2p_server.peers.clone(),
+			pool_net_adapter.clone(),
+			tx_pool.clone(),
+			rate_limiter.clone(),
+		));
+
+		let state_info = ServerStateInfo {
+			..Default::default()
+		};
+
 		// TODO fix API shutdown and join this thread
 		api::node_apis(
 			&config.api_http_addr,
@@ -614,23 +957,104 @@ impl Server {
 			tx_pool.clone(),
 			p2p_server.peers.clone(),
 			sync_state.clone(),
-			api_secret,
+			api_tokens,
 			foreign_api_secret,
 			tls_conf,
+			config.wallet_proxy_config.clone(),
+			config.read_only_api_config.clone(),
+			config.cors_config.clone(),
+			rate_limiter,
 			allow_to_stop,
 			stratum_ip_pool,
+			ws_events,
+			tip_events,
+			block_templates,
+			Some(state_info.stratum_stats.clone() as Arc<dyn api::MiningStatsProvider>),
+			Some(pool_net_adapter.clone() as Arc<dyn api::DandelionControl>),
+			Some(Arc::new(ServerNodeControl::new(
+				sync_manager.clone(),
+				config_watcher.clone(),
+				tx_pool.clone(),
+				state_info.stratum_stats.clone(),
+				tor_status,
+				disk_monitor.clone(),
+				stop_state.clone(),
+			)) as Arc<dyn api::NodeControl>),
+			config.health_config.clone().unwrap_or_default(),
+			Some(supervisor.clone() as Arc<dyn api::ThreadSupervisor>),
 			api_chan,
 			stop_state.clone(),
 		)?;
 
 		info!("Starting dandelion monitor: {}", &config.api_http_addr);
 		let dandelion_thread = dandelion_monitor::monitor_transactions(
-			config.dandelion_config.clone(),
 			tx_pool.clone(),
 			pool_net_adapter,
 			stop_state.clone(),
+			&supervisor,
 		)?;
 
+		let txhashset_zip_thread = if config.txhashset_zip_prebuild.unwrap_or(false) {
+			info!("Starting txhashset zip monitor");
+			Some(txhashset_monitor::monitor_txhashset_zip(
+				shared_chain.clone(),
+				sync_state.clone(),
+				stop_state.clone(),
+				&supervisor,
+			)?)
+		} else {
+			None
+		};
+
+		let block_compression_thread = if config.compress_old_blocks.unwrap_or(false) {
+			info!("Starting block compression monitor");
+			Some(block_compressor::monitor_block_compression(
+				shared_chain.clone(),
+				sync_state.clone(),
+				stop_state.clone(),
+				&supervisor,
+			)?)
+		} else {
+			None
+		};
+
+		let webhook_thread = match webhook_dispatcher {
+			Some(dispatcher) => {
+				info!("Starting webhook dispatcher");
+				Some(webhooks::run_webhook_dispatcher(
+					dispatcher,
+					shared_chain.clone(),
+					p2p_server.peers.clone(),
+					sync_state.clone(),
+					stop_state.clone(),
+				)?)
+			}
+			None => None,
+		};
+
+		let tx_generator_thread = match &tx_generator {
+			Some(generator) => {
+				warn!(
+					"Starting synthetic tx generator, {} transactions per minute",
+					tx_generator_rate
+				);
+				Some(tx_generator::run_tx_generator(
+					generator.clone(),
+					tx_generator_rate,
+					shared_chain.clone(),
+					tx_pool.clone(),
+					sync_state.clone(),
+					stop_state.clone(),
+				)?)
+			}
+			None => None,
+		};
+
+		let config_watcher_thread =
+			config_watcher::run_config_watcher(config_watcher.clone(), stop_state.clone())?;
+
+		let disk_monitor_thread = disk_monitor::run_disk_monitor(disk_monitor, stop_state.clone())?;
+
 		warn!("MWC server started.");
 		Ok(Server {
 			config,
@@ -638,17 +1062,37 @@ impl Server {
 			chain: shared_chain,
 			tx_pool,
 			sync_state,
-			state_info: ServerStateInfo {
-				..Default::default()
-			},
+			state_info,
 			stop_state,
 			lock_file,
 			connect_thread,
 			sync_thread,
 			dandelion_thread,
+			txhashset_zip_thread,
+			block_compression_thread,
+			supervisor,
+			sync_manager,
+			tx_generator,
+			tx_generator_thread,
+			webhook_thread,
+			config_watcher,
+			config_watcher_thread,
+			disk_monitor_thread,
+			tor_control,
 		})
 	}
 
+	/// Run the component in the current thread, it is restarted after a panic.
+	/// Returns when the component returns or the node is stopping.
+	pub fn run_supervised<F: FnMut()>(&self, name: &str, f: F) {
+		self.supervisor.run(name, f);
+	}
+
+	/// Set the config file reader for the config reload on SIGHUP and by the owner API
+	pub fn set_config_loader(&self, loader: ConfigLoader) {
+		self.config_watcher.set_loader(loader);
+	}
+
 	#[cfg(not(target_os = "windows"))]
 	fn adjust_canonicalization<P: AsRef<Path>>(p: P) -> String {
 		p.as_ref().display().to_string()
@@ -827,21 +1271,26 @@ impl Server {
 		config: StratumServerConfig,
 		ip_pool: Arc<connections::StratumIpPool>,
 	) {
+		if self.config.is_header_only() {
+			warn!("Stratum server is not available for headers only node");
+			return;
+		}
 		let proof_size = global::proofsize();
 		let sync_state = self.sync_state.clone();
 
-		let mut stratum_server = stratumserver::StratumServer::new(
-			config,
-			self.chain.clone(),
-			self.tx_pool.clone(),
-			self.state_info.stratum_stats.clone(),
-			ip_pool,
-		);
-		let _ = thread::Builder::new()
-			.name("stratum_server".to_string())
-			.spawn(move || {
-				stratum_server.run_loop(proof_size, sync_state);
-			});
+		let chain = self.chain.clone();
+		let tx_pool = self.tx_pool.clone();
+		let stratum_stats = self.state_info.stratum_stats.clone();
+		let _ = self.supervisor.spawn("stratum_server", move || {
+			let mut stratum_server = stratumserver::StratumServer::new(
+				config.clone(),
+				chain.clone(),
+				tx_pool.clone(),
+				stratum_stats.clone(),
+				ip_pool.clone(),
+			);
+			stratum_server.run_loop(proof_size, sync_state.clone());
+		});
 	}
 
 	/// Start mining for blocks internally on a separate thread. Relies on
@@ -852,6 +1301,10 @@ impl Server {
 		wallet_listener_url: Option<String>,
 		stop_state: Arc<StopState>,
 	) {
+		if self.config.is_header_only() {
+			warn!("Test miner is not available for headers only node");
+			return;
+		}
 		info!("start_test_miner - start",);
 		let sync_state = self.sync_state.clone();
 		let config_wallet_url = match wallet_listener_url.clone() {
@@ -864,6 +1317,7 @@ impl Server {
 			burn_reward: false,
 			enable_stratum_server: None,
 			stratum_server_addr: None,
+			stratum_ws_server_addr: None,
 			wallet_listener_url: config_wallet_url,
 			minimum_share_difficulty: 1,
 			ip_tracking: false,
@@ -875,6 +1329,13 @@ impl Server {
 			connection_pace_ms: -1,
 			ip_white_list: HashSet::new(),
 			ip_black_list: HashSet::new(),
+			vardiff_enabled: false,
+			vardiff_shares_per_minute: 6,
+			vardiff_retarget_s: 120,
+			tls_certificate_file: None,
+			tls_certificate_key: None,
+			workers: None,
+			coinbase_payouts: None,
 		};
 
 		let mut miner = Miner::new(
@@ -885,6 +1346,11 @@ impl Server {
 			sync_state,
 		);
 		miner.set_debug_output_id(format!("Port {}", self.config.p2p_config.port));
+		if wallet_listener_url.is_none() {
+			if let Some(generator) = &self.tx_generator {
+				miner.set_reward_keychain(generator.reward_keychain());
+			}
+		}
 		let _ = thread::Builder::new()
 			.name("test_miner".to_string())
 			.spawn(move || miner.run_loop(wallet_listener_url));
@@ -1012,6 +1478,8 @@ impl Server {
 			header_stats: header_stats,
 			sync_status: self.sync_state.status(),
 			disk_usage_gb: disk_usage_gb,
+			component_stats: self.supervisor.stats(),
+			orphan_pool_stats: self.sync_manager.orphan_pool_stats(),
 			stratum_stats: self.state_info.stratum_stats.clone(),
 			peer_stats: peer_stats,
 			diff_stats: diff_stats,
@@ -1043,10 +1511,53 @@ impl Server {
 				Err(e) => error!("failed to join to dandelion_monitor thread: {:?}", e),
 				Ok(_) => info!("dandelion_monitor thread stopped"),
 			}
+
+			if let Some(txhashset_zip_thread) = self.txhashset_zip_thread {
+				match txhashset_zip_thread.join() {
+					Err(e) => error!("failed to join to txhashset_zip thread: {:?}", e),
+					Ok(_) => info!("txhashset_zip thread stopped"),
+				}
+			}
+			if let Some(block_compression_thread) = self.block_compression_thread {
+				match block_compression_thread.join() {
+					Err(e) => error!("failed to join to block_compression thread: {:?}", e),
+					Ok(_) => info!("block_compression thread stopped"),
+				}
+			}
+			if let Some(tx_generator_thread) = self.tx_generator_thread {
+				match tx_generator_thread.join() {
+					Err(e) => error!("failed to join to tx_generator thread: {:?}", e),
+					Ok(_) => info!("tx_generator thread stopped"),
+				}
+			}
+			if let Some(webhook_thread) = self.webhook_thread {
+				match webhook_thread.join() {
+					Err(e) => error!("failed to join to webhooks thread: {:?}", e),
+					Ok(_) => info!("webhooks thread stopped"),
+				}
+			}
+
+			if let Some(config_watcher_thread) = self.config_watcher_thread {
+				match config_watcher_thread.join() {
+					Err(e) => error!("failed to join to config_watcher thread: {:?}", e),
+					Ok(_) => info!("config_watcher thread stopped"),
+				}
+			}
+
+			match self.disk_monitor_thread.join() {
+				Err(e) => error!("failed to join to disk_monitor thread: {:?}", e),
+				Ok(_) => info!("disk_monitor thread stopped"),
+			}
 		}
 		// this call is blocking and makes sure all peers stop, however
 		// we can't be sure that we stopped a listener blocked on accept, so we don't join the p2p thread
 		self.p2p.stop();
+		if let Some(mut tor_control) = self.tor_control {
+			match tor_control.del_onion() {
+				Err(e) => warn!("Unable to remove the onion service, {}", e),
+				Ok(_) => info!("onion service removed"),
+			}
+		}
 		let _ = FileExt::unlock(&*self.lock_file);
 		warn!("Shutdown complete");
 	}
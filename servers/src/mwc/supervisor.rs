@@ -0,0 +1,209 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Supervision of the non-critical subsystem threads (seeder, stratum, monitors, API, TUI).
+//! A panic in the supervised component is caught and the component is restarted
+//! in the same thread with a backoff, the chain and p2p core keep running.
+//! Restart counts are reported with the server stats.
+
+use crate::api::ThreadSupervisor;
+use crate::common::stats::ComponentStats;
+use chrono::Utc;
+use mwc_util::{RwLock, StopState};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// First restart delay, doubled on every next panic
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(1);
+/// Max restart delay
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(300);
+/// Component that is running that long without panic starts again from the min backoff
+const RESTART_BACKOFF_RESET: Duration = Duration::from_secs(600);
+
+#[derive(Clone)]
+pub struct Supervisor {
+	components: Arc<RwLock<Vec<ComponentStats>>>,
+	stop_state: Arc<StopState>,
+}
+
+impl Supervisor {
+	pub fn new(stop_state: Arc<StopState>) -> Supervisor {
+		Supervisor {
+			components: Arc::new(RwLock::new(vec![])),
+			stop_state,
+		}
+	}
+
+	/// Run the component `f` in the named thread. If `f` panics, it is called again after
+	/// the backoff. Supervision ends when `f` returns or the node is stopping.
+	pub fn spawn<F>(&self, name: &str, f: F) -> std::io::Result<thread::JoinHandle<()>>
+	where
+		F: FnMut() + Send + 'static,
+	{
+		let supervisor = self.clone();
+		let thread_name = name.to_string();
+		self.register(name);
+		thread::Builder::new()
+			.name(thread_name.clone())
+			.spawn(move || supervisor.supervise(&thread_name, f))
+	}
+
+	/// Run the component `f` in the current thread with the same supervision as `spawn`.
+	/// Returns when `f` returns or the node is stopping.
+	pub fn run<F: FnMut()>(&self, name: &str, f: F) {
+		self.register(name);
+		self.supervise(name, f);
+	}
+
+	fn register(&self, name: &str) {
+		self.components.write().push(ComponentStats {
+			name: name.to_string(),
+			is_running: false,
+			restarts: 0,
+			last_panic: None,
+			last_panic_time: None,
+		});
+	}
+
+	fn supervise<F: FnMut()>(&self, name: &str, mut f: F) {
+		let mut backoff = RESTART_BACKOFF_MIN;
+		loop {
+			self.update(name, |c| c.is_running = true);
+			let start = Instant::now();
+			let res = panic::catch_unwind(AssertUnwindSafe(|| f()));
+			self.update(name, |c| c.is_running = false);
+
+			let payload = match res {
+				Ok(_) => break,
+				Err(payload) => payload,
+			};
+			if self.stop_state.is_stopped() {
+				break;
+			}
+
+			let msg = match payload.downcast_ref::<&'static str>() {
+				Some(s) => s.to_string(),
+				None => match payload.downcast_ref::<String>() {
+					Some(s) => s.clone(),
+					None => "unknown panic".to_string(),
+				},
+			};
+			if start.elapsed() > RESTART_BACKOFF_RESET {
+				backoff = RESTART_BACKOFF_MIN;
+			}
+			error!(
+				"Component {} panicked with '{}', restarting in {} seconds",
+				name,
+				msg,
+				backoff.as_secs()
+			);
+			self.update(name, |c| {
+				c.restarts += 1;
+				c.last_panic = Some(msg.clone());
+				c.last_panic_time = Some(Utc::now());
+			});
+
+			if !self.wait(backoff) {
+				break;
+			}
+			backoff = (backoff * 2).min(RESTART_BACKOFF_MAX);
+		}
+	}
+
+	/// Supervised components
+	pub fn stats(&self) -> Vec<ComponentStats> {
+		self.components.read().clone()
+	}
+
+	fn update<U: FnOnce(&mut ComponentStats)>(&self, name: &str, update: U) {
+		if let Some(c) = self.components.write().iter_mut().find(|c| c.name == name) {
+			update(c);
+		}
+	}
+
+	// Returns false if node is stopping
+	fn wait(&self, delay: Duration) -> bool {
+		let until = Instant::now() + delay;
+		while Instant::now() < until {
+			if self.stop_state.is_stopped() {
+				return false;
+			}
+			thread::sleep(Duration::from_millis(100));
+		}
+		!self.stop_state.is_stopped()
+	}
+}
+
+impl ThreadSupervisor for Supervisor {
+	fn spawn(
+		&self,
+		name: &str,
+		f: Box<dyn FnMut() + Send>,
+	) -> std::io::Result<thread::JoinHandle<()>> {
+		Supervisor::spawn(self, name, f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	#[test]
+	fn test_panicking_component_is_restarted() {
+		let supervisor = Supervisor::new(Arc::new(StopState::new()));
+
+		// Panics on the first call, returns on the second one
+		let calls = Arc::new(AtomicUsize::new(0));
+		let calls2 = calls.clone();
+		let handle = supervisor
+			.spawn("test_component", move || {
+				if calls2.fetch_add(1, Ordering::Relaxed) == 0 {
+					panic!("component failure");
+				}
+			})
+			.unwrap();
+		handle.join().unwrap();
+
+		assert_eq!(calls.load(Ordering::Relaxed), 2);
+		let stats = supervisor.stats();
+		assert_eq!(stats.len(), 1);
+		assert_eq!(stats[0].name, "test_component");
+		assert!(!stats[0].is_running);
+		assert_eq!(stats[0].restarts, 1);
+		assert_eq!(stats[0].last_panic, Some("component failure".to_string()));
+		assert!(stats[0].last_panic_time.is_some());
+
+		// Component that runs in the current thread, not restarted if the node is stopping
+		let mut calls = 0;
+		supervisor.run("test_current_thread", || {
+			calls += 1;
+			if calls == 1 {
+				panic!("{} failure", "formatted");
+			}
+		});
+		assert_eq!(calls, 2);
+		supervisor.stop_state.stop();
+		supervisor.run("test_stopped", || panic!("failure on stop"));
+
+		let stats = supervisor.stats();
+		assert_eq!(stats.len(), 3);
+		assert_eq!(stats[1].restarts, 1);
+		assert_eq!(stats[1].last_panic, Some("formatted failure".to_string()));
+		assert_eq!(stats[2].restarts, 0);
+		assert!(!stats[2].is_running);
+	}
+}
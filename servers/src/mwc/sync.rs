@@ -15,16 +15,23 @@
 
 //! Syncing of the chain with the rest of the network
 
+mod bandwidth;
 mod block_headers_request_cache;
 mod body_sync;
 mod header_hashes_sync;
+mod header_ranges;
 mod header_sync;
+mod history_backfill;
 mod orphans_sync;
+mod stale_tip;
 mod state_sync;
 pub mod sync_manager;
 mod sync_peers;
+mod sync_progress;
+mod sync_stage;
 mod sync_utils;
 mod syncer;
+mod txhashset_sync;
 
 pub use header_sync::get_locator_heights;
 
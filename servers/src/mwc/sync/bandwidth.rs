@@ -0,0 +1,141 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sync data throughput estimation for the peers. The sync data bytes counted by
+//! the peer connection tracker are sampled while the peer has requests in flight,
+//! idle peers keep their last estimation. The requests are scheduled to the peers
+//! proportionally to their throughput.
+
+use crate::p2p::{self, Capabilities, Peer, PeerAddr};
+use chrono::{DateTime, Utc};
+use mwc_util::RwLock;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Throughput is sampled not more often than that
+const SAMPLE_INTERVAL_MS: i64 = 1000;
+/// Weight of the new sample for the moving average
+const SAMPLE_WEIGHT: f64 = 0.3;
+
+struct PeerBandwidth {
+	bytes: u64,
+	time: DateTime<Utc>,
+	// bytes per second
+	rate: Option<f64>,
+}
+
+pub struct BandwidthEstimator {
+	peers: RwLock<HashMap<PeerAddr, PeerBandwidth>>,
+}
+
+impl BandwidthEstimator {
+	pub fn new() -> BandwidthEstimator {
+		BandwidthEstimator {
+			peers: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Sample the received bytes of the connected peers with `capabilities`. `is_busy`
+	/// reports if the peer has requests in flight.
+	pub fn update<F>(&self, peers: &Arc<p2p::Peers>, capabilities: Capabilities, is_busy: F)
+	where
+		F: Fn(&PeerAddr) -> bool,
+	{
+		let now = Utc::now();
+		let connected: Vec<Arc<Peer>> = peers
+			.iter()
+			.with_capabilities(capabilities)
+			.connected()
+			.into_iter()
+			.collect();
+
+		let mut estimations = self.peers.write();
+		estimations.retain(|addr, _| connected.iter().any(|p| p.info.addr == *addr));
+		for peer in connected {
+			let bytes = peer.bulk_received_bytes();
+			let estimation = match estimations.get_mut(&peer.info.addr) {
+				Some(estimation) => estimation,
+				None => {
+					estimations.insert(
+						peer.info.addr.clone(),
+						PeerBandwidth {
+							bytes,
+							time: now,
+							rate: None,
+						},
+					);
+					continue;
+				}
+			};
+			let elapsed_ms = (now - estimation.time).num_milliseconds();
+			if elapsed_ms < SAMPLE_INTERVAL_MS {
+				continue;
+			}
+			let received = bytes.saturating_sub(estimation.bytes);
+			if received > 0 || is_busy(&peer.info.addr) {
+				let sample = received as f64 * 1000.0 / elapsed_ms as f64;
+				estimation.rate = Some(match estimation.rate {
+					Some(rate) => rate * (1.0 - SAMPLE_WEIGHT) + sample * SAMPLE_WEIGHT,
+					None => sample,
+				});
+			}
+			estimation.bytes = bytes;
+			estimation.time = now;
+		}
+	}
+
+	// Peers without the estimation get the average, so they have a chance to show their speed
+	fn weights(&self, peers: &[Arc<Peer>]) -> Vec<f64> {
+		let estimations = self.peers.read();
+		let rates: Vec<Option<f64>> = peers
+			.iter()
+			.map(|p| estimations.get(&p.info.addr).and_then(|e| e.rate))
+			.collect();
+		let known: Vec<f64> = rates.iter().filter_map(|r| *r).collect();
+		let average = if known.is_empty() {
+			1.0
+		} else {
+			known.iter().sum::<f64>() / known.len() as f64
+		};
+		// Stalled peers still get some requests, otherwise they never recover
+		let min_weight = (average / 100.0).max(1.0);
+		rates
+			.iter()
+			.map(|r| r.unwrap_or(average).max(min_weight))
+			.collect()
+	}
+
+	/// Choose the peer randomly, proportionally to the throughput
+	pub fn choose<'a, R: Rng>(&self, peers: &'a [Arc<Peer>], rng: &mut R) -> Option<&'a Arc<Peer>> {
+		let weights = self.weights(peers);
+		let idx: Vec<usize> = (0..peers.len()).collect();
+		idx.choose_weighted(rng, |i| weights[*i])
+			.ok()
+			.map(|i| &peers[*i])
+	}
+
+	/// Up to `n` fastest peers
+	pub fn fastest(&self, peers: &[Arc<Peer>], n: usize) -> Vec<Arc<Peer>> {
+		let weights = self.weights(peers);
+		let mut idx: Vec<usize> = (0..peers.len()).collect();
+		idx.sort_by(|a, b| {
+			weights[*b]
+				.partial_cmp(&weights[*a])
+				.unwrap_or(std::cmp::Ordering::Equal)
+		});
+		idx.into_iter().take(n).map(|i| peers[i].clone()).collect()
+	}
+}
@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::chain::{self, SyncState, SyncStatus};
+use crate::chain::{self, SyncEventKind, SyncState, SyncStatus};
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::BlockHeader;
 use crate::mwc::sync::sync_peers::SyncPeers;
 use crate::mwc::sync::sync_utils;
 use crate::mwc::sync::sync_utils::{RequestTracker, SyncRequestResponses, SyncResponse};
@@ -29,6 +30,11 @@ use std::cmp;
 use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
 
+/// Block bodies sync. Blocks of the lookahead window above the fork point are requested
+/// from multiple peers in parallel. Blocks that are received out of order are buffered as
+/// orphans and applied to the chain in order once the gap is filled. The window slides
+/// as soon as the lowest block of it is applied, so the pipeline doesn't wait for the
+/// next sync loop.
 pub struct BodySync {
 	chain: Arc<Chain>,
 	required_capabilities: RwLock<Capabilities>,
@@ -38,6 +44,7 @@ pub struct BodySync {
 	last_retry_height: RwLock<u64>,
 	retry_expiration_times: RwLock<VecDeque<DateTime<Utc>>>,
 	excluded_peers: RwLock<HashSet<PeerAddr>>,
+	max_avail_height: RwLock<u64>,
 }
 
 impl BodySync {
@@ -51,6 +58,7 @@ impl BodySync {
 			last_retry_height: RwLock::new(0),
 			retry_expiration_times: RwLock::new(VecDeque::new()),
 			excluded_peers: RwLock::new(HashSet::new()),
+			max_avail_height: RwLock::new(0),
 		}
 	}
 
@@ -71,6 +79,7 @@ impl BodySync {
 		let header_head = self.chain.header_head()?;
 
 		let max_avail_height = cmp::min(best_height, header_head.height);
+		*self.max_avail_height.write() = max_avail_height;
 
 		// Last few blocks no need to sync, new mined blocks will be synced regular way
 		if head.height > max_avail_height.saturating_sub(7) {
@@ -114,9 +123,12 @@ impl BodySync {
 
 		let (peer_capabilities, required_capabilities) =
 			if self.chain.archive_mode() && head.height <= archive_height {
+				// Peers with the partial history are fine if they have deep enough blocks
+				let history_capability =
+					Capabilities::for_history_depth(best_height.saturating_sub(fork_point.height));
 				(
-					Capabilities::BLOCK_HIST,
-					Capabilities::BLOCK_HIST | Capabilities::HEADER_HIST,
+					history_capability,
+					history_capability | Capabilities::HEADER_HIST,
 				)
 			} else {
 				(Capabilities::UNKNOWN, Capabilities::HEADER_HIST) // needed for headers sync, that can go in parallel
@@ -127,6 +139,13 @@ impl BodySync {
 		let excluded_peers = self
 			.request_tracker
 			.retain_expired(pibd_params::PIBD_REQUESTS_TIMEOUT_SECS, sync_peers);
+		if !excluded_peers.is_empty() {
+			sync_state.add_event(
+				SyncEventKind::RequestsTimedOut,
+				excluded_peers.iter().map(|p| p.to_string()).collect(),
+				"Blocks requests are timed out, retrying with other peers".into(),
+			);
+		}
 		*self.excluded_peers.write() = excluded_peers;
 
 		let (peers, excluded_requests, excluded_peers) = sync_utils::get_sync_peers(
@@ -222,30 +241,7 @@ impl BodySync {
 				}
 
 				if need_refresh_request_series {
-					let mut new_request_series: Vec<(Hash, u64)> = Vec::new();
-
-					// Don't collect more than 500 blocks in the cache. The block size limit is 1.5MB, so total cache mem can be up to 750 Mb which is ok
-					let max_height = cmp::min(
-						fork_point.height + (self.pibd_params.get_orphans_num_limit() / 2) as u64,
-						max_avail_height,
-					);
-					let mut current = self.chain.get_header_by_height(max_height)?;
-
-					while current.height > fork_point.height {
-						let hash = current.hash();
-						if !self.chain.is_orphan(&hash) {
-							new_request_series.push((hash, current.height));
-						}
-						current = self.chain.get_previous_header(&current)?;
-					}
-
-					if let Some((hash, height)) = new_request_series.last() {
-						debug!(
-							"New body request series starting from {} / {}",
-							hash, height
-						);
-					}
-					*self.request_series.write() = new_request_series;
+					self.update_request_series(&fork_point, max_avail_height)?;
 				}
 
 				// Now we can try to submit more requests...
@@ -289,7 +285,9 @@ impl BodySync {
 				}
 
 				// let's request next package since we get this one...
-				if self.request_tracker.get_update_requests_to_next_ask() == 0 {
+				if self.request_tracker.get_update_requests_to_next_ask() == 0
+					|| (valid_block && self.slide_request_series())
+				{
 					if let Ok(head) = self.chain.head() {
 						let (peers, excluded_requests, excluded_peers) = sync_utils::get_sync_peers(
 							peers,
@@ -326,6 +324,64 @@ impl BodySync {
 		}
 	}
 
+	// Rebuild the lookahead window from the fork point
+	fn update_request_series(
+		&self,
+		fork_point: &BlockHeader,
+		max_avail_height: u64,
+	) -> Result<(), chain::Error> {
+		let mut new_request_series: Vec<(Hash, u64)> = Vec::new();
+
+		// Don't collect more than 500 blocks in the cache. The block size limit is 1.5MB, so total cache mem can be up to 750 Mb which is ok
+		let max_height = cmp::min(
+			fork_point.height + (self.pibd_params.get_orphans_num_limit() / 2) as u64,
+			max_avail_height,
+		);
+		let mut current = self.chain.get_header_by_height(max_height)?;
+
+		while current.height > fork_point.height {
+			let hash = current.hash();
+			if !self.chain.is_orphan(&hash) {
+				new_request_series.push((hash, current.height));
+			}
+			current = self.chain.get_previous_header(&current)?;
+		}
+
+		if let Some((hash, height)) = new_request_series.last() {
+			debug!(
+				"New body request series starting from {} / {}",
+				hash, height
+			);
+		}
+		*self.request_series.write() = new_request_series;
+		Ok(())
+	}
+
+	// If the lowest block of the window is applied, the window is moved up to the new fork point.
+	// Returns true if the window was moved.
+	fn slide_request_series(&self) -> bool {
+		let lowest = match self.request_series.read().last().cloned() {
+			Some((hash, _)) => hash,
+			None => return false,
+		};
+		match self.chain.block_exists(&lowest) {
+			Ok(true) => {}
+			_ => return false,
+		}
+		let max_avail_height = *self.max_avail_height.read();
+		let res = self
+			.chain
+			.fork_point()
+			.and_then(|fork_point| self.update_request_series(&fork_point, max_avail_height));
+		match res {
+			Ok(_) => true,
+			Err(e) => {
+				warn!("Unable to move body request series, {}", e);
+				false
+			}
+		}
+	}
+
 	fn is_block_recieved(&self, hash: &Hash) -> Result<bool, chain::Error> {
 		Ok(self.chain.is_orphan(&hash) || self.chain.block_exists(&hash)?)
 	}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::chain::{self, pibd_params, SyncState, SyncStatus};
+use crate::chain::{self, pibd_params, SyncEventKind, SyncState, SyncStatus};
 use crate::core::core::hash::Hashed;
 use crate::mwc::sync::sync_peers::SyncPeers;
 use crate::mwc::sync::sync_utils;
@@ -32,6 +32,12 @@ use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Number of the peers that must agree on the headers hash root before we trust it.
+/// If there are fewer qualified peers, all of them must respond.
+const HEADERS_HASH_ROOT_QUORUM: usize = 3;
+/// Share of the responses, percent, that must agree on the elected root
+const HEADERS_HASH_ROOT_QUORUM_PERCENT: usize = 67;
+
 /// Headers Hash Sync is needed for Fast Headers synchronization
 pub struct HeadersHashSync {
 	chain: Arc<chain::Chain>,
@@ -209,6 +215,12 @@ impl HeadersHashSync {
 				}
 			}
 
+			let headers_hash_peers = sync_utils::get_qualify_peers(
+				peers,
+				self.target_archive_height,
+				Capabilities::HEADERS_HASH,
+			);
+
 			if !self.responded_headers_hash_from.is_empty()
 				&& ((self.responded_headers_hash_from.len()
 					>= self.requested_headers_hash_from.len()
@@ -216,39 +228,30 @@ impl HeadersHashSync {
 					|| (now - first_request).num_seconds()
 						> pibd_params::PIBD_REQUESTS_TIMEOUT_SECS / 2)
 			{
-				// We can elect the group with a most representative hash
-				let mut hash_counts: HashMap<Hash, i32> = HashMap::new();
-				for (_, (hash, _)) in &self.responded_headers_hash_from {
-					hash_counts.insert(hash.clone(), hash_counts.get(hash).unwrap_or(&0) + 1);
+				match self.elect_root_hash(headers_hash_peers.len()) {
+					Some(best_root_hash) => {
+						self.report_divergent_peers(&best_root_hash, sync_state, sync_peers);
+						let desegmenter = HeaderHashesDesegmenter::new(
+							self.chain.genesis().hash(),
+							target_archive_height,
+							best_root_hash,
+							self.pibd_params.clone(),
+						);
+						let segment_num = desegmenter.get_segments_total();
+						self.headers_hash_desegmenter = Some(desegmenter);
+						sync_state.update(SyncStatus::HeaderHashSync {
+							completed_blocks: 0,
+							total_blocks: segment_num,
+						});
+						// Headers desegmenter is ready - let's retry and request some headers
+						return self.request_impl(peers, sync_state, sync_peers, best_height);
+					}
+					None => {
+						// Not enough peers agree, more peers are needed to outvote the divergent ones
+						peers.set_boost_peers_capabilities(Self::get_peer_capabilities());
+					}
 				}
-				// selecting hash with max value
-				debug_assert!(!hash_counts.is_empty());
-				let (best_root_hash, _) = hash_counts
-					.iter()
-					.max_by_key(|&(_, count)| count)
-					.expect("hash_counts is empty?");
-
-				let desegmenter = HeaderHashesDesegmenter::new(
-					self.chain.genesis().hash(),
-					target_archive_height,
-					best_root_hash.clone(),
-					self.pibd_params.clone(),
-				);
-				let segment_num = desegmenter.get_segments_total();
-				self.headers_hash_desegmenter = Some(desegmenter);
-				sync_state.update(SyncStatus::HeaderHashSync {
-					completed_blocks: 0,
-					total_blocks: segment_num,
-				});
-				// Headers desegmenter is ready - let's retry and request some headers
-				return self.request_impl(peers, sync_state, sync_peers, best_height);
 			}
-
-			let headers_hash_peers = sync_utils::get_qualify_peers(
-				peers,
-				self.target_archive_height,
-				Capabilities::HEADERS_HASH,
-			);
 			if headers_hash_peers.is_empty() {
 				return SyncResponse::new(
 					SyncRequestResponses::WaitingForPeers,
@@ -387,12 +390,14 @@ impl HeadersHashSync {
 			let mut peers2send: Vec<Arc<Peer>> = Vec::new();
 			for peer in headers_hash_peers.iter().filter(|&p| {
 				let peer_adr = &p.info.addr;
-				if self.responded_headers_hash_from.contains_key(peer_adr)
-					&& !self.responded_with_another_height.contains(peer_adr)
-				{
-					return true;
+				// Only the peers that agree on the elected root can serve its segments
+				match self.responded_headers_hash_from.get(peer_adr) {
+					Some((hash, _)) => {
+						*hash == headers_root_hash
+							&& !self.responded_with_another_height.contains(peer_adr)
+					}
+					None => false,
 				}
-				false
 			}) {
 				peers2send.push(peer.clone());
 			}
@@ -443,6 +448,63 @@ impl HeadersHashSync {
 		);
 	}
 
+	// Root hash that the quorum of the responded peers agree on. Quorum is HEADERS_HASH_ROOT_QUORUM
+	// peers, or all qualified peers if there are fewer of them, and HEADERS_HASH_ROOT_QUORUM_PERCENT
+	// of all responses.
+	fn elect_root_hash(&self, qualified_peers: usize) -> Option<Hash> {
+		let mut hash_counts: HashMap<Hash, usize> = HashMap::new();
+		for (_, (hash, _)) in &self.responded_headers_hash_from {
+			*hash_counts.entry(hash.clone()).or_insert(0) += 1;
+		}
+		let (best_root_hash, votes) = hash_counts.into_iter().max_by_key(|&(_, count)| count)?;
+
+		let responses = self.responded_headers_hash_from.len();
+		let quorum = cmp::max(1, cmp::min(HEADERS_HASH_ROOT_QUORUM, qualified_peers));
+		if votes >= quorum && votes * 100 >= responses * HEADERS_HASH_ROOT_QUORUM_PERCENT {
+			Some(best_root_hash)
+		} else {
+			debug!(
+				"No quorum for headers hash root at {}. Best root {} has {} of {} responses, need {}",
+				self.target_archive_height, best_root_hash, votes, responses, quorum
+			);
+			None
+		}
+	}
+
+	// Peers that responded with another root might feed us a bogus hash tree, they are flagged.
+	// The peers that agree on the root are the ones that we request the segments from.
+	fn report_divergent_peers(
+		&self,
+		root_hash: &Hash,
+		sync_state: &SyncState,
+		sync_peers: &SyncPeers,
+	) {
+		let mut agreed: Vec<String> = Vec::new();
+		let mut divergent = 0;
+		for (peer, (hash, _)) in &self.responded_headers_hash_from {
+			if hash == root_hash {
+				agreed.push(peer.to_string());
+			} else {
+				divergent += 1;
+				sync_peers.report_error_response(
+					peer,
+					format!(
+						"Peer {} headers hash root at {} doesn't match the quorum root {}",
+						peer, self.target_archive_height, root_hash
+					),
+				);
+			}
+		}
+		sync_state.add_event(
+			SyncEventKind::PeerSelected,
+			agreed,
+			format!(
+				"Headers hash root {} at {} is elected, {} peers responded with another root",
+				root_hash, self.target_archive_height, divergent
+			),
+		);
+	}
+
 	pub fn receive_headers_hash_response(
 		&mut self,
 		peer: &PeerAddr,
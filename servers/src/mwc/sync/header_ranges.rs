@@ -0,0 +1,322 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scheduler for the header ranges above the archive horizon. Every range is anchored
+//! by the header before it and is requested with a single hash locator, so the ranges
+//! are disjoint. The ranges are striped across the peers, the least loaded peer gets
+//! the next range. A range that is not received in time is reassigned to another peer,
+//! the late response is still accepted. Received ranges wait in the buffer until all
+//! the ranges below them are received, so the headers are applied in order.
+
+use crate::core::core::hash::Hash;
+use chrono::{DateTime, Duration, Utc};
+use mwc_core::core::BlockHeader;
+use mwc_p2p::PeerAddr;
+use std::collections::{BTreeMap, HashMap};
+
+struct HeaderRange {
+	// hash of the anchor header, the range starts after it
+	hash: Hash,
+	peer: Option<PeerAddr>,
+	request_time: DateTime<Utc>,
+	// peers that didn't respond in time
+	failed_peers: Vec<PeerAddr>,
+}
+
+pub struct HeaderRanges {
+	// requested ranges by the anchor height
+	ranges: BTreeMap<u64, HeaderRange>,
+	// received ranges by the anchor height, waiting for the ranges below
+	received: BTreeMap<u64, (PeerAddr, Vec<BlockHeader>)>,
+	max_per_peer: usize,
+}
+
+impl HeaderRanges {
+	pub fn new(max_per_peer: usize) -> HeaderRanges {
+		HeaderRanges {
+			ranges: BTreeMap::new(),
+			received: BTreeMap::new(),
+			max_per_peer,
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.ranges.clear();
+		self.received.clear();
+	}
+
+	/// No ranges are requested or waiting to be applied
+	pub fn is_empty(&self) -> bool {
+		self.ranges.is_empty() && self.received.is_empty()
+	}
+
+	/// Add the range that follows the header `hash` at `height`. False if it is known
+	pub fn add(&mut self, hash: Hash, height: u64) -> bool {
+		if self.ranges.contains_key(&height) || self.received.contains_key(&height) {
+			return false;
+		}
+		self.ranges.insert(
+			height,
+			HeaderRange {
+				hash,
+				peer: None,
+				request_time: Utc::now(),
+				failed_peers: vec![],
+			},
+		);
+		true
+	}
+
+	/// Is the range that follows `hash` requested from the peer, now or before the reassignment
+	pub fn is_range_peer(&self, hash: &Hash, peer: &PeerAddr) -> bool {
+		self.ranges.values().any(|r| {
+			r.hash == *hash && (r.peer.as_ref() == Some(peer) || r.failed_peers.contains(peer))
+		})
+	}
+
+	/// Is the range that follows `hash` received and waiting for the ranges below
+	pub fn is_received(&self, hash: &Hash) -> bool {
+		self.received
+			.values()
+			.any(|(_, headers)| headers.first().map(|h| h.prev_hash) == Some(*hash))
+	}
+
+	/// Assign the waiting ranges, lowest first. Every range goes to the least loaded of
+	/// `peers` that didn't fail it before, a peer has up to max_per_peer ranges.
+	/// Returns the anchor hash, the anchor height and the peer for the requests.
+	pub fn schedule(
+		&mut self,
+		peers: &[PeerAddr],
+		now: DateTime<Utc>,
+	) -> Vec<(Hash, u64, PeerAddr)> {
+		let mut load: HashMap<PeerAddr, usize> = peers.iter().map(|p| (p.clone(), 0)).collect();
+		for peer in self.ranges.values().filter_map(|r| r.peer.as_ref()) {
+			if let Some(n) = load.get_mut(peer) {
+				*n += 1;
+			}
+		}
+
+		let max_per_peer = self.max_per_peer;
+		let mut res = vec![];
+		for (height, range) in self.ranges.iter_mut() {
+			if range.peer.is_some() {
+				continue;
+			}
+			let available: Vec<&PeerAddr> =
+				peers.iter().filter(|p| load[*p] < max_per_peer).collect();
+			// If every peer failed this range, let's try them again
+			let peer = available
+				.iter()
+				.filter(|p| !range.failed_peers.contains(**p))
+				.min_by_key(|p| load[**p])
+				.or_else(|| available.iter().min_by_key(|p| load[**p]))
+				.map(|p| (*p).clone());
+			let peer = match peer {
+				Some(peer) => peer,
+				None => break,
+			};
+			*load.get_mut(&peer).expect("peer load is known") += 1;
+			range.peer = Some(peer.clone());
+			range.request_time = now;
+			res.push((range.hash, *height, peer));
+		}
+		res
+	}
+
+	/// Release the ranges that are not received during `timeout`, so they can be
+	/// reassigned. Returns the anchor heights and the peers that failed them.
+	pub fn expire(&mut self, timeout: Duration, now: DateTime<Utc>) -> Vec<(u64, PeerAddr)> {
+		let mut res = vec![];
+		for (height, range) in self.ranges.iter_mut() {
+			if range.peer.is_some() && now - range.request_time > timeout {
+				let peer = range.peer.take().expect("range peer is checked");
+				range.failed_peers.push(peer.clone());
+				res.push((*height, peer));
+			}
+		}
+		res
+	}
+
+	/// Release the range if the request to the peer can't be sent
+	pub fn release(&mut self, height: u64, peer: &PeerAddr) {
+		if let Some(range) = self.ranges.get_mut(&height) {
+			if range.peer.as_ref() == Some(peer) {
+				range.peer = None;
+				range.failed_peers.push(peer.clone());
+			}
+		}
+	}
+
+	/// Headers are received from the peer. The headers are returned back if it is not
+	/// a range that the peer was asked for.
+	pub fn receive(
+		&mut self,
+		peer: &PeerAddr,
+		headers: Vec<BlockHeader>,
+	) -> Result<(), Vec<BlockHeader>> {
+		let (height, prev_hash) = match headers.first() {
+			Some(h) => (h.height.saturating_sub(1), h.prev_hash),
+			None => return Err(headers),
+		};
+		match self.ranges.get(&height) {
+			Some(range)
+				if range.hash == prev_hash
+					&& (range.peer.as_ref() == Some(peer) || range.failed_peers.contains(peer)) => {}
+			_ => return Err(headers),
+		}
+		self.ranges.remove(&height);
+		self.received.insert(height, (peer.clone(), headers));
+		Ok(())
+	}
+
+	/// Received ranges that have all the ranges below them received, in order
+	pub fn take_ready(&mut self) -> Vec<(PeerAddr, Vec<BlockHeader>)> {
+		let first_waiting = self.ranges.keys().next().cloned();
+		let mut res = vec![];
+		while let Some(height) = self.received.keys().next().cloned() {
+			if first_waiting.map(|h| height > h).unwrap_or(false) {
+				break;
+			}
+			res.push(
+				self.received
+					.remove(&height)
+					.expect("received range exists"),
+			);
+		}
+		res
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use mwc_core::core::hash::Hashed;
+
+	fn headers(num: u64) -> Vec<BlockHeader> {
+		let mut res: Vec<BlockHeader> = vec![BlockHeader::default()];
+		for height in 1..=num {
+			let prev_hash = res.last().unwrap().hash();
+			res.push(BlockHeader {
+				height,
+				prev_hash,
+				..BlockHeader::default()
+			});
+		}
+		res
+	}
+
+	fn peer(n: u8) -> PeerAddr {
+		PeerAddr::Ip(format!("10.0.0.{}:3414", n).parse().unwrap())
+	}
+
+	#[test]
+	fn header_ranges_striping() {
+		let chain = headers(30);
+		let mut ranges = HeaderRanges::new(1);
+		for height in &[0, 10, 20] {
+			assert!(ranges.add(chain[*height].hash(), *height as u64));
+		}
+		assert!(!ranges.add(chain[10].hash(), 10));
+
+		let now = Utc::now();
+		let requests = ranges.schedule(&[peer(1), peer(2)], now);
+		assert_eq!(
+			requests,
+			vec![
+				(chain[0].hash(), 0, peer(1)),
+				(chain[10].hash(), 10, peer(2))
+			]
+		);
+		// Peers are busy, the last range waits
+		assert!(ranges.schedule(&[peer(1), peer(2)], now).is_empty());
+		let requests = ranges.schedule(&[peer(1), peer(2), peer(3)], now);
+		assert_eq!(requests, vec![(chain[20].hash(), 20, peer(3))]);
+		assert!(ranges.is_range_peer(&chain[10].hash(), &peer(2)));
+		assert!(!ranges.is_range_peer(&chain[10].hash(), &peer(1)));
+	}
+
+	#[test]
+	fn header_ranges_in_order() {
+		let chain = headers(30);
+		let mut ranges = HeaderRanges::new(2);
+		for height in &[0, 10, 20] {
+			ranges.add(chain[*height].hash(), *height as u64);
+		}
+		ranges.schedule(&[peer(1), peer(2)], Utc::now());
+
+		// Not requested range and a stranger response are refused
+		assert!(ranges.receive(&peer(2), chain[1..11].to_vec()).is_err());
+		assert!(ranges.receive(&peer(3), chain[11..21].to_vec()).is_err());
+
+		assert!(ranges.receive(&peer(2), chain[11..21].to_vec()).is_ok());
+		assert!(ranges.receive(&peer(1), chain[21..31].to_vec()).is_ok());
+		assert!(ranges.take_ready().is_empty());
+		assert!(ranges.is_received(&chain[10].hash()));
+		assert!(!ranges.is_received(&chain[0].hash()));
+
+		assert!(ranges.receive(&peer(1), chain[1..11].to_vec()).is_ok());
+		let ready = ranges.take_ready();
+		assert_eq!(ready.len(), 3);
+		assert_eq!(ready[0], (peer(1), chain[1..11].to_vec()));
+		assert_eq!(ready[1], (peer(2), chain[11..21].to_vec()));
+		assert_eq!(ready[2], (peer(1), chain[21..31].to_vec()));
+		assert!(ranges.is_empty());
+	}
+
+	#[test]
+	fn header_ranges_reassignment() {
+		let chain = headers(20);
+		let mut ranges = HeaderRanges::new(1);
+		ranges.add(chain[0].hash(), 0);
+		ranges.add(chain[10].hash(), 10);
+		let now = Utc::now();
+		ranges.schedule(&[peer(1), peer(2)], now);
+
+		let timeout = Duration::seconds(5);
+		assert!(ranges
+			.expire(timeout, now + Duration::seconds(1))
+			.is_empty());
+		assert!(ranges.receive(&peer(2), chain[11..21].to_vec()).is_ok());
+		assert_eq!(
+			ranges.expire(timeout, now + Duration::seconds(6)),
+			vec![(0, peer(1))]
+		);
+
+		// The range goes to another peer, the one that failed it is the last resort
+		let later = now + Duration::seconds(6);
+		assert_eq!(
+			ranges.schedule(&[peer(1), peer(2)], later),
+			vec![(chain[0].hash(), 0, peer(2))]
+		);
+		assert!(ranges.take_ready().is_empty());
+
+		// Late response from the first peer is still good
+		assert!(ranges.receive(&peer(1), chain[1..11].to_vec()).is_ok());
+		assert_eq!(ranges.take_ready().len(), 2);
+
+		// Failed by every peer, retried anyway
+		ranges.add(chain[20].hash(), 20);
+		ranges.schedule(&[peer(1)], later);
+		ranges.expire(timeout, later + Duration::seconds(6));
+		assert_eq!(
+			ranges.schedule(&[peer(1)], later),
+			vec![(chain[20].hash(), 20, peer(1))]
+		);
+		ranges.release(20, &peer(1));
+		assert_eq!(
+			ranges.schedule(&[peer(1)], later),
+			vec![(chain[20].hash(), 20, peer(1))]
+		);
+	}
+}
@@ -12,11 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::chain::{self, SyncState, SyncStatus};
+use crate::chain::{self, SyncEventKind, SyncState, SyncStatus};
 use crate::common::types::Error;
 use crate::core::core::hash::Hash;
 use crate::core::pow::Difficulty;
 use crate::mwc::sync::header_hashes_sync::HeadersHashSync;
+use crate::mwc::sync::header_ranges::HeaderRanges;
 use crate::mwc::sync::sync_peers::SyncPeers;
 use crate::mwc::sync::sync_utils;
 use crate::mwc::sync::sync_utils::{
@@ -34,9 +35,17 @@ use mwc_p2p::PeerAddr;
 use mwc_util::RwLock;
 use rand::seq::IteratorRandom;
 use rand::seq::SliceRandom;
+use std::cmp;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
+/// Number of the header ranges above the horizon that a peer is asked for at the same time
+const HEADERS_RANGES_PER_PEER: usize = 1;
+/// The range above the horizon is reassigned to another peer if there is no response
+/// during the double average latency, limited by these values.
+const HEADERS_RANGE_MIN_TIMEOUT_SECS: i64 = 2;
+const HEADERS_RANGE_MAX_TIMEOUT_SECS: i64 = 10;
+
 pub struct HeaderSync {
 	chain: Arc<chain::Chain>,
 	received_cache: RwLock<Option<HeadersRecieveCache>>,
@@ -49,6 +58,7 @@ pub struct HeaderSync {
 	retry_expiration_times: RwLock<VecDeque<DateTime<Utc>>>,
 	send_requests_lock: RwLock<u8>,
 	excluded_peers: RwLock<HashSet<PeerAddr>>,
+	tail_ranges: RwLock<HeaderRanges>,
 }
 
 impl HeaderSync {
@@ -64,6 +74,7 @@ impl HeaderSync {
 			retry_expiration_times: RwLock::new(VecDeque::new()),
 			send_requests_lock: RwLock::new(0),
 			excluded_peers: RwLock::new(HashSet::new()),
+			tail_ranges: RwLock::new(HeaderRanges::new(HEADERS_RANGES_PER_PEER)),
 		}
 	}
 
@@ -106,6 +117,13 @@ impl HeaderSync {
 		let excluded_peers = self
 			.request_tracker
 			.retain_expired(pibd_params::PIBD_REQUESTS_TIMEOUT_SECS, sync_peers);
+		if !excluded_peers.is_empty() {
+			sync_state.add_event(
+				SyncEventKind::RequestsTimedOut,
+				excluded_peers.iter().map(|p| p.to_string()).collect(),
+				"Headers requests are timed out, retrying with other peers".into(),
+			);
+		}
 		*self.excluded_peers.write() = excluded_peers;
 
 		// it is initial statis flag
@@ -219,8 +237,8 @@ impl HeaderSync {
 			}
 		}
 
-		// At this point we are above the archive height, so we can request headers sequentually the normal way...
-		// sync_state is no needs to update
+		// At this point we are above the archive height, the headers are requested by the
+		// ranges starting from the header head. sync_state is no needs to update
 		let sync_peer = Self::choose_sync_peer(peers);
 
 		if sync_peer.is_none() {
@@ -236,23 +254,11 @@ impl HeaderSync {
 
 		let sync_peer = sync_peer.unwrap();
 		let header_head = self.chain.header_head().expect("header_head is broken");
-		let header_head_hash = header_head.hash();
-
-		if self.request_tracker.has_request(&header_head_hash) {
-			return SyncResponse::new(
-				SyncRequestResponses::HeadersPibdReady,
-				Self::get_peer_capabilities(),
-				"Loading headers above horizon".into(),
-			);
-		}
-
-		let (_, peer_diff) = {
-			let info = sync_peer.info.live_info.read();
-			(info.height, info.total_difficulty)
-		};
+		let peer_diff = sync_peer.info.live_info.read().total_difficulty;
 
 		// Quick check - nothing to sync if we are caught up with the peer.
 		if peer_diff <= header_head.total_difficulty {
+			self.tail_ranges.write().clear();
 			// we can relax for a pretty long time
 			let resp = SyncResponse::new(
 				SyncRequestResponses::HeadersReady,
@@ -264,28 +270,28 @@ impl HeaderSync {
 			return resp;
 		}
 
-		match self.request_headers(header_head, sync_peer.clone()) {
-			Ok(_) => {
-				self.request_tracker.register_request(
-					header_head_hash,
-					sync_peer.info.addr.clone(),
-					format!("Tail header for {}", header_head.height),
-				);
-			}
-			Err(e) => {
-				let msg = format!(
-					"Failed to send headers request to {} for height {}, Error: {}",
-					sync_peer.info.addr, header_head.height, e
-				);
-				error!("{}", msg);
-				sync_peers.report_no_response(&sync_peer.info.addr, msg);
+		{
+			let mut tail_ranges = self.tail_ranges.write();
+			if tail_ranges.is_empty() {
+				tail_ranges.add(header_head.last_block_h, header_head.height);
 			}
 		}
+		let requested_peers = self.send_tail_requests(peers, sync_peers);
+		if !requested_peers.is_empty() {
+			sync_state.add_event(
+				SyncEventKind::PeerSelected,
+				requested_peers.iter().map(|p| p.to_string()).collect(),
+				format!(
+					"Headers above horizon are requested from height {}",
+					header_head.height
+				),
+			);
+		}
 
 		return SyncResponse::new(
 			SyncRequestResponses::HeadersPibdReady,
 			Self::get_peer_capabilities(),
-			"Loading headers above horizon, just requested one.".into(),
+			"Loading headers above horizon".into(),
 		);
 	}
 
@@ -352,6 +358,17 @@ impl HeaderSync {
 		let peer_adr = self.request_tracker.remove_request(&bhs[0].prev_hash, peer);
 		if let Some(peer_addr) = peer_adr {
 			expected_peer = peer_addr == *peer;
+			if !expected_peer
+				&& self
+					.tail_ranges
+					.read()
+					.is_range_peer(&bhs[0].prev_hash, peer)
+			{
+				// The range was reassigned, the response from any of the peers is good
+				expected_peer = true;
+				self.request_tracker
+					.remove_request(&bhs[0].prev_hash, &peer_addr);
+			}
 
 			// let's request next package since we get this one...
 			if self.request_tracker.get_update_requests_to_next_ask() == 0 {
@@ -437,50 +454,152 @@ impl HeaderSync {
 			}
 		}
 
-		// At this point we are processing the headers the regular way, it is expecte that it is a sequentual reponse
-		let sync_head = self
-			.chain
-			.header_head()
-			.expect("Header head must be always defined");
+		// At this point we are processing the headers above the horizon. The requested ranges
+		// are applied in order, the next range is requested before these headers are validated.
+		let next_range = match bhs.last() {
+			Some(h) if bhs.len() >= p2p::MAX_BLOCK_HEADERS as usize => Some((h.hash(), h.height)),
+			_ => None,
+		};
+		let (ready, requested) = {
+			let mut tail_ranges = self.tail_ranges.write();
+			match tail_ranges.receive(peer, bhs) {
+				Ok(()) => {
+					if let Some((hash, height)) = next_range {
+						tail_ranges.add(hash, height);
+					}
+					(tail_ranges.take_ready(), true)
+				}
+				// Late response for the range that is waiting for the ranges below
+				Err(bhs) if tail_ranges.is_received(&bhs[0].prev_hash) => (vec![], false),
+				Err(bhs) => (vec![(peer.clone(), bhs)], false),
+			}
+		};
+		if requested {
+			self.send_tail_requests(peers, sync_peers);
+		}
 
-		match self
-			.chain
-			.sync_block_headers(&bhs, sync_head, chain::Options::SYNC)
-		{
-			Ok(sync_head) => {
-				if let Some(sync_head) = sync_head {
-					// If we have an updated sync_head after processing this batch of headers,
-					// then we can request relevant headers in the next batch.
-					if !self.request_tracker.has_request(&sync_head.last_block_h) {
-						if let Some(sync_peer) = Self::choose_sync_peer(peers) {
-							match self.request_headers(sync_head, sync_peer.clone()) {
-								Ok(_) => {
-									self.request_tracker.register_request(
-										sync_head.last_block_h,
-										sync_peer.info.addr.clone(),
-										format!("Tail headers for {}", sync_head.height),
-									);
-									sync_peers.report_ok_response(peer);
-								}
-								Err(e) => {
-									let msg = format!("Failed to send headers request to {} for height {}, Error: {}", sync_peer.info.addr, sync_head.height, e);
-									error!("{}", msg);
-									sync_peers.report_no_response(&sync_peer.info.addr, msg);
-								}
-							}
-						}
+		for (peer, bhs) in ready {
+			let sync_head = self
+				.chain
+				.header_head()
+				.expect("Header head must be always defined");
+			match self.chain.sync_block_headers(
+				&bhs,
+				sync_head,
+				chain::Options::SYNC | chain::Options::CHECKPOINTS,
+			) {
+				Ok(sync_head) => {
+					if requested {
+						sync_peers.report_ok_response(&peer);
+					} else if let Some(sync_head) = sync_head {
+						// Not requested headers (fork or restart) moved the header head, the ranges
+						// are anchored by the old one, so let's start them from the new head.
+						let mut tail_ranges = self.tail_ranges.write();
+						tail_ranges.clear();
+						tail_ranges.add(sync_head.last_block_h, sync_head.height);
+						drop(tail_ranges);
+						self.send_tail_requests(peers, sync_peers);
 					}
 				}
+				Err(e) => {
+					debug!("Headers refused by chain: {:?}", e);
+					sync_peers.report_error_response(
+						&peer,
+						format!("sync_block_headers failed with error {}", e),
+					);
+					// The ranges above are anchored by the refused headers
+					self.tail_ranges.write().clear();
+					break;
+				}
 			}
+		}
+		Ok(())
+	}
+
+	/// Reassign the timed out header ranges above the horizon and request the waiting ones
+	/// from the most work peers. Returns the peers that got the requests.
+	fn send_tail_requests(&self, peers: &Arc<p2p::Peers>, sync_peers: &SyncPeers) -> Vec<PeerAddr> {
+		let timeout = cmp::max(
+			cmp::min(
+				self.request_tracker.get_average_latency() * 2,
+				Duration::seconds(HEADERS_RANGE_MAX_TIMEOUT_SECS),
+			),
+			Duration::seconds(HEADERS_RANGE_MIN_TIMEOUT_SECS),
+		);
+		let header_head = match self.chain.header_head() {
+			Ok(head) => head,
 			Err(e) => {
-				debug!("Headers refused by chain: {:?}", e);
-				sync_peers.report_error_response(
-					&peer,
-					format!("sync_block_headers failed with error {}", e),
-				);
+				error!("sync: unable to get header head, {}", e);
+				return vec![];
 			}
+		};
+
+		let mut tail_ranges = self.tail_ranges.write();
+		for (height, peer) in tail_ranges.expire(timeout, Utc::now()) {
+			debug!(
+				"sync: no headers after {} from {} yet, reassigning the range",
+				height, peer
+			);
 		}
-		Ok(())
+
+		let mut sync_peers_list = Self::most_work_peers(peers);
+		sync_peers_list.shuffle(&mut rand::thread_rng());
+		let addrs: Vec<PeerAddr> = sync_peers_list
+			.iter()
+			.map(|p| p.info.addr.clone())
+			.collect();
+
+		let mut res = vec![];
+		for (hash, height, addr) in tail_ranges.schedule(&addrs, Utc::now()) {
+			let peer = sync_peers_list
+				.iter()
+				.find(|p| p.info.addr == addr)
+				.expect("scheduled peer is in the list");
+			// The header head might be on a fork, the locator finds the fork point
+			let res_send = if hash == header_head.last_block_h {
+				self.request_headers(header_head, peer.clone())
+			} else {
+				self.request_headers_for_hash(hash, height, peer.clone())
+			};
+			match res_send {
+				Ok(_) => {
+					// Latency is tracked from the first request of the range
+					if !self.request_tracker.has_request(&hash) {
+						self.request_tracker.register_request(
+							hash,
+							addr.clone(),
+							format!("Tail headers for {}", height),
+						);
+					}
+					res.push(addr);
+				}
+				Err(e) => {
+					tail_ranges.release(height, &addr);
+					let msg = format!(
+						"Failed to send headers request to {} for height {}, Error: {}",
+						addr, height, e
+					);
+					error!("{}", msg);
+					sync_peers.report_no_response(&addr, msg);
+				}
+			}
+		}
+		res
+	}
+
+	/// Connected HEADER_HIST peers with the most work
+	fn most_work_peers(peers: &Arc<p2p::Peers>) -> Vec<Arc<Peer>> {
+		let peers_iter = || {
+			peers
+				.iter()
+				.with_capabilities(Capabilities::HEADER_HIST)
+				.connected()
+		};
+		let max_diff = peers_iter().max_difficulty().unwrap_or(Difficulty::zero());
+		peers_iter()
+			.with_difficulty(|x| x >= max_diff)
+			.into_iter()
+			.collect()
 	}
 
 	fn choose_sync_peer(peers: &Arc<p2p::Peers>) -> Option<Arc<Peer>> {
@@ -502,6 +621,37 @@ impl HeaderSync {
 		})
 	}
 
+	/// Drop the cached state and ask up to `peers_num` most work peers for the headers
+	/// from our header head. Used to recover the stale tip.
+	pub fn restart(&self, peers: &Arc<p2p::Peers>, peers_num: usize) -> Result<(), chain::Error> {
+		*self.cached_response.write() = None;
+		self.tail_ranges.write().clear();
+
+		let header_head = self.chain.header_head()?;
+		let max_diff = peers
+			.iter()
+			.with_capabilities(Self::get_peer_capabilities())
+			.connected()
+			.max_difficulty()
+			.unwrap_or(Difficulty::zero());
+		let sync_peers = peers
+			.iter()
+			.with_capabilities(Self::get_peer_capabilities())
+			.connected()
+			.with_difficulty(|x| x >= max_diff)
+			.into_iter()
+			.choose_multiple(&mut rand::thread_rng(), peers_num);
+		for peer in sync_peers {
+			if let Err(e) = self.request_headers(header_head, peer.clone()) {
+				warn!(
+					"Unable to request headers from peer {}, {}",
+					peer.info.addr, e
+				);
+			}
+		}
+		Ok(())
+	}
+
 	/// Request some block headers from a peer to advance us.
 	fn request_headers(&self, sync_head: chain::Tip, peer: Arc<Peer>) -> Result<(), chain::Error> {
 		let locator = self
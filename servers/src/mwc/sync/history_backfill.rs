@@ -0,0 +1,169 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocks history backfill. A synced pruned node requests the full blocks below its
+//! history tail from the peers that have deep enough history, verifies them against our
+//! headers and kernel MMR and saves them. When the history reaches genesis, the chain
+//! switches to the archive mode and the node advertises the full history.
+
+use crate::chain::{self, Chain};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::Block;
+use crate::mwc::sync::sync_peers::SyncPeers;
+use crate::mwc::sync::sync_utils::{self, RequestTracker};
+use crate::p2p::{self, Capabilities, PeerAddr};
+use crate::util::RwLock;
+use mwc_chain::pibd_params;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Number of the blocks below the history tail that can be requested at once
+const BACKFILL_WINDOW: usize = 64;
+/// Backfill is a background job, every peer gets a few requests only
+const BACKFILL_REQUESTS_PER_PEER: usize = 4;
+
+pub struct HistoryBackfill {
+	chain: Arc<Chain>,
+	request_tracker: RequestTracker<Hash>,
+	excluded_peers: RwLock<HashSet<PeerAddr>>,
+}
+
+impl HistoryBackfill {
+	pub fn new(chain: Arc<Chain>) -> HistoryBackfill {
+		HistoryBackfill {
+			chain,
+			request_tracker: RequestTracker::new(),
+			excluded_peers: RwLock::new(HashSet::new()),
+		}
+	}
+
+	/// Request the next blocks below the history tail. Expected to be called when the node is synced.
+	pub fn request(
+		&self,
+		in_peers: &Arc<p2p::Peers>,
+		sync_peers: &SyncPeers,
+	) -> Result<(), chain::Error> {
+		if !self.chain.history_backfill() || self.chain.archive_mode() || self.chain.header_only() {
+			return Ok(());
+		}
+		let history_tail = self.chain.history_tail()?;
+		if history_tail.height == 0 {
+			return Ok(());
+		}
+		let head = self.chain.head()?;
+
+		*self.excluded_peers.write() = self
+			.request_tracker
+			.retain_expired(pibd_params::PIBD_REQUESTS_TIMEOUT_SECS, sync_peers);
+
+		// Peers must have the history down to the lowest block of the window
+		let lowest_height = history_tail
+			.height
+			.saturating_sub(BACKFILL_WINDOW as u64)
+			.max(1);
+		let capabilities =
+			Capabilities::for_history_depth(head.height.saturating_sub(lowest_height));
+		let (peers, excluded_requests, excluded_peers) = sync_utils::get_sync_peers(
+			in_peers,
+			BACKFILL_REQUESTS_PER_PEER,
+			capabilities,
+			head.height,
+			&self.request_tracker,
+			&*self.excluded_peers.read(),
+		);
+		if peers.is_empty() {
+			return Ok(());
+		}
+
+		let mut need_request = self.request_tracker.calculate_needed_requests(
+			peers.len(),
+			excluded_requests as usize,
+			excluded_peers as usize,
+			BACKFILL_REQUESTS_PER_PEER,
+			BACKFILL_WINDOW,
+		);
+
+		let mut header = self.chain.get_block_header(&history_tail.last_block_h)?;
+		let mut peer_idx = 0;
+		while need_request > 0 && header.height > lowest_height {
+			header = self.chain.get_previous_header(&header)?;
+			let hash = header.hash();
+			if self.request_tracker.has_request(&hash) || self.chain.block_exists(&hash)? {
+				continue;
+			}
+			let peer = &peers[peer_idx % peers.len()];
+			peer_idx += 1;
+			match peer.send_block_request(hash, chain::Options::SYNC) {
+				Ok(_) => {
+					self.request_tracker.register_request(
+						hash,
+						peer.info.addr.clone(),
+						format!("Backfill block {} at {}", hash, header.height),
+					);
+					need_request -= 1;
+				}
+				Err(e) => {
+					let msg = format!(
+						"Failed to send backfill block request to peer {}, {}",
+						peer.info.addr, e
+					);
+					warn!("{}", msg);
+					sync_peers.report_no_response(&peer.info.addr, msg);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Process the block if it is the backfill response. Returns None if we didn't request it,
+	/// otherwise if the block is accepted.
+	pub fn recieve_block(
+		&self,
+		peer: &PeerAddr,
+		b: &Block,
+		sync_peers: &SyncPeers,
+	) -> Option<bool> {
+		let hash = b.hash();
+		if self.request_tracker.remove_request(&hash, peer).is_none() {
+			return None;
+		}
+
+		match self.chain.backfill_block(b) {
+			Ok(history_tail) => {
+				sync_peers.report_ok_response(peer);
+				if history_tail.height == 0 {
+					info!("Blocks history backfill is complete");
+				} else {
+					debug!(
+						"Backfill block {} at {}, history tail is at {}",
+						hash, b.header.height, history_tail.height
+					);
+				}
+				Some(true)
+			}
+			Err(e) => {
+				if e.is_bad_data() {
+					sync_peers.report_error_response(
+						peer,
+						format!("Get bad backfill block {} for peer {}, {}", hash, peer, e),
+					);
+					Some(false)
+				} else {
+					warn!("Unable to save backfill block {}, {}", hash, e);
+					Some(true)
+				}
+			}
+		}
+	}
+}
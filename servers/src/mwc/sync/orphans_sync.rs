@@ -12,18 +12,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::common::stats::OrphanPoolStats;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::ser::{self, ProtocolVersion};
 use chrono::{DateTime, Utc};
 use mwc_chain::pibd_params::PibdParams;
 use mwc_chain::Chain;
 use mwc_core::core::Block;
-use mwc_p2p::{Peer, Peers};
+use mwc_p2p::{Peer, PeerAddr, Peers};
 use mwc_util::RwLock;
 use rand::prelude::*;
 use rand::thread_rng;
+use std::cmp;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+/// A single peer can't hold more than that part of the unknown blocks pool
+const PEER_POOL_SHARE: usize = 4;
+
+// Block that we can't process yet
+struct UnknownBlock {
+	block: Block,
+	peer: PeerAddr,
+	size: usize,
+	received: DateTime<Utc>,
+	// Last time the block was received, for the LRU eviction
+	last_access: DateTime<Utc>,
+}
+
 // We might have orphans that we can't process because there are no prev headers exist. That is why we are putting them aside
 // Until header data will arrive
 pub struct OrphansSync {
@@ -32,7 +49,11 @@ pub struct OrphansSync {
 	pibd_params: Arc<PibdParams>,
 	// Some blocks that we can't process yet. Likely there are no headers. We don't want to trigger whole sync,
 	// instead let's request child blocks routinely. That should handle bad network problem with a brute force
-	unknown_blocks: RwLock<HashMap<Hash, (Block, DateTime<Utc>)>>, // Lock 1
+	unknown_blocks: RwLock<HashMap<Hash, UnknownBlock>>, // Lock 1
+	// Number of the blocks evicted because of the pool limits
+	evicted: AtomicU64,
+	// Number of the blocks rejected because of the peer limit
+	rejected: AtomicU64,
 }
 
 impl OrphansSync {
@@ -42,14 +63,28 @@ impl OrphansSync {
 			chain,
 			orphans_requests: RwLock::new(HashMap::new()),
 			unknown_blocks: RwLock::new(HashMap::new()),
+			evicted: AtomicU64::new(0),
+			rejected: AtomicU64::new(0),
+		}
+	}
+
+	/// Unknown blocks pool metrics
+	pub fn get_stats(&self) -> OrphanPoolStats {
+		let unknown_blocks = self.unknown_blocks.read();
+		OrphanPoolStats {
+			blocks: unknown_blocks.len(),
+			bytes: unknown_blocks.values().map(|b| b.size as u64).sum(),
+			evicted: self.evicted.load(Ordering::Relaxed),
+			rejected: self.rejected.load(Ordering::Relaxed),
 		}
 	}
 
 	/// Process and keep a new block if it was rejected by the chain. Return true if prev block is needed
-	pub fn recieve_block_reporting(&self, block: Block) -> bool {
+	pub fn recieve_block_reporting(&self, block: Block, peer: &PeerAddr) -> bool {
 		let bhash = block.hash();
 		let need_prev_block = self.need_prev_block(&block.header.prev_hash, block.header.height);
-		if self.unknown_blocks.read().contains_key(&bhash) {
+		if let Some(b) = self.unknown_blocks.write().get_mut(&bhash) {
+			b.last_access = Utc::now();
 			return need_prev_block;
 		}
 
@@ -61,12 +96,83 @@ impl OrphansSync {
 			return false;
 		}
 
-		self.unknown_blocks
-			.write()
-			.insert(bhash, (block, Utc::now()));
+		let size = ser::ser_vec(&block, ProtocolVersion::local())
+			.map(|v| v.len())
+			.unwrap_or(0);
+		let max_blocks = self.max_blocks();
+		let max_bytes = self.pibd_params.get_orphans_bytes_limit();
+		if size > max_bytes {
+			self.rejected.fetch_add(1, Ordering::Relaxed);
+			return false;
+		}
+
+		let mut unknown_blocks = self.unknown_blocks.write();
+
+		// Peer that already holds its share replaces its own LRU block
+		let peer_limit = cmp::max(1, max_blocks / PEER_POOL_SHARE);
+		let peer_blocks = unknown_blocks.values().filter(|b| b.peer == *peer).count();
+		if peer_blocks >= peer_limit {
+			debug!(
+				"Peer {} has {} unknown blocks, replacing its oldest one with {} at {}",
+				peer, peer_blocks, bhash, block.header.height
+			);
+			self.rejected.fetch_add(1, Ordering::Relaxed);
+			Self::evict_lru(&mut unknown_blocks, Some(peer));
+		}
+
+		let now = Utc::now();
+		unknown_blocks.insert(
+			bhash,
+			UnknownBlock {
+				block,
+				peer: peer.clone(),
+				size,
+				received: now,
+				last_access: now,
+			},
+		);
+
+		// Global limits, evicting the least recently used blocks
+		let mut evicted = 0;
+		while unknown_blocks.len() > max_blocks
+			|| unknown_blocks.values().map(|b| b.size).sum::<usize>() > max_bytes
+		{
+			if !Self::evict_lru(&mut unknown_blocks, None) {
+				break;
+			}
+			evicted += 1;
+		}
+		if evicted > 0 {
+			debug!(
+				"Evicted {} unknown blocks because of the pool limits",
+				evicted
+			);
+			self.evicted.fetch_add(evicted, Ordering::Relaxed);
+		}
 		need_prev_block
 	}
 
+	// Max number of the unknown blocks to keep
+	fn max_blocks(&self) -> usize {
+		cmp::max(1, self.pibd_params.get_orphans_num_limit() / 2)
+	}
+
+	// Remove the least recently used block, of the peer if it is specified. Return false if nothing was removed
+	fn evict_lru(
+		unknown_blocks: &mut HashMap<Hash, UnknownBlock>,
+		peer: Option<&PeerAddr>,
+	) -> bool {
+		let lru = unknown_blocks
+			.iter()
+			.filter(|(_, b)| peer.map(|p| b.peer == *p).unwrap_or(true))
+			.min_by_key(|(_, b)| b.last_access)
+			.map(|(hash, _)| hash.clone());
+		match lru {
+			Some(hash) => unknown_blocks.remove(&hash).is_some(),
+			None => false,
+		}
+	}
+
 	// Expected that it is called ONLY when state_sync is done
 	pub fn sync_orphans(&self, peers: &Arc<Peers>) -> Result<(), mwc_chain::Error> {
 		// check if we need something to request from the peers.
@@ -79,10 +185,7 @@ impl OrphansSync {
 			let mut unknown_blocks = self.unknown_blocks.write();
 
 			// let's try apply blocks int the chain, we migth already has some data for that, let's apply from low height to the higher
-			let mut blocks: Vec<&Block> = Vec::new();
-			for (b, _) in unknown_blocks.values() {
-				blocks.push(b);
-			}
+			let mut blocks: Vec<&Block> = unknown_blocks.values().map(|b| &b.block).collect();
 			blocks.sort_by_key(|b| b.header.height);
 			for b in blocks {
 				let _ = self
@@ -90,20 +193,16 @@ impl OrphansSync {
 					.process_block(b.clone(), mwc_chain::Options::NONE);
 			}
 
-			unknown_blocks.retain(|hash, (_block, time)| {
+			unknown_blocks.retain(|hash, b| {
 				if self.chain.is_orphan(hash) || self.chain.block_exists(hash).unwrap_or(false) {
 					return false;
 				}
 				// 10 minutes should be enough to do something with the unknown blocks. It is not expecte dthat the lock chains will be formed
-				(now - *time).num_seconds() < 600
+				(now - b.received).num_seconds() < 600
 			});
 
-			if unknown_blocks.len() > self.pibd_params.get_orphans_num_limit() / 2 {
-				unknown_blocks.clear();
-			}
-
-			for (b, _) in unknown_blocks.values() {
-				block_to_validate.insert(b.hash());
+			for b in unknown_blocks.values() {
+				block_to_validate.insert(b.block.hash());
 			}
 		}
 
@@ -136,7 +235,10 @@ impl OrphansSync {
 					Some((prev_block_hash.clone(), bl_height))
 				}
 				None => match self.unknown_blocks.read().get(orph_hash) {
-					Some((b, _time)) => Some((b.header.prev_hash.clone(), b.header.height.clone())),
+					Some(b) => Some((
+						b.block.header.prev_hash.clone(),
+						b.block.header.height.clone(),
+					)),
 					None => None,
 				},
 			};
@@ -0,0 +1,128 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stale tip detection. If the chain tip doesn't advance for the timeout while the
+//! peers report more work, the node is likely stuck with the peers that don't deliver.
+//! The stuck peers are dropped, the new outbound connections are boosted and the headers
+//! are requested again with the locator from our header head.
+
+use crate::chain::{self, Chain, SyncState, SyncStatus};
+use crate::mwc::sync::sync_manager::SyncManager;
+use crate::p2p::{self, Capabilities};
+use chrono::{DateTime, Duration, Utc};
+use mwc_core::pow::Difficulty;
+use std::sync::Arc;
+
+/// Number of the best peers that we request the headers from on recovery
+const RECOVERY_HEADER_PEERS: usize = 3;
+
+pub struct StaleTipMonitor {
+	timeout: Duration,
+	last_difficulty: Difficulty,
+	last_header_difficulty: Difficulty,
+	last_update: DateTime<Utc>,
+}
+
+impl StaleTipMonitor {
+	/// New monitor, `timeout_minutes` 0 disables the recovery
+	pub fn new(timeout_minutes: u64) -> StaleTipMonitor {
+		StaleTipMonitor {
+			timeout: Duration::minutes(timeout_minutes as i64),
+			last_difficulty: Difficulty::zero(),
+			last_header_difficulty: Difficulty::zero(),
+			last_update: Utc::now(),
+		}
+	}
+
+	/// Check the tip and run the recovery if it is stale. Returns true if the recovery was done.
+	pub fn check(
+		&mut self,
+		chain: &Chain,
+		sync_state: &SyncState,
+		peers: &Arc<p2p::Peers>,
+		sync_manager: &SyncManager,
+	) -> Result<bool, chain::Error> {
+		if self.timeout.is_zero() {
+			return Ok(false);
+		}
+
+		let head = chain.head()?;
+		let header_head = chain.header_head()?;
+		let now = Utc::now();
+		// The headers sync is a progress as well, the tip doesn't move during the state sync
+		if head.total_difficulty != self.last_difficulty
+			|| header_head.total_difficulty != self.last_header_difficulty
+			|| Self::is_state_sync(sync_state.status())
+		{
+			self.last_difficulty = head.total_difficulty;
+			self.last_header_difficulty = header_head.total_difficulty;
+			self.last_update = now;
+			return Ok(false);
+		}
+		if now - self.last_update < self.timeout {
+			return Ok(false);
+		}
+
+		let better_peers: Vec<Arc<p2p::Peer>> = peers
+			.iter()
+			.connected()
+			.with_difficulty(|d| d > head.total_difficulty)
+			.into_iter()
+			.collect();
+		if better_peers.is_empty() {
+			// Nobody has more work, the network is just slow
+			return Ok(false);
+		}
+
+		warn!(
+			"Chain tip {} at {} didn't advance for {} minutes while {} peers report more work, recovering",
+			head.last_block_h,
+			head.height,
+			(now - self.last_update).num_minutes(),
+			better_peers.len()
+		);
+		// Next recovery is not earlier than the timeout
+		self.last_update = now;
+
+		// Peers that report more work, but their work didn't change for the timeout are stuck
+		// on their own tip or lying about it. They are dropped, new ones will be connected.
+		for p in &better_peers {
+			let stuck_since = p.info.live_info.read().stuck_detector;
+			if now - stuck_since >= self.timeout {
+				debug!(
+					"Dropping peer {} that is stuck at height {}",
+					p.info.addr,
+					p.info.height()
+				);
+				p.stop();
+			}
+		}
+		peers.set_boost_peers_capabilities(Capabilities::HEADER_HIST);
+		sync_manager.restart_headers_sync(peers, RECOVERY_HEADER_PEERS);
+		Ok(true)
+	}
+
+	fn is_state_sync(status: SyncStatus) -> bool {
+		match status {
+			SyncStatus::TxHashsetPibd { .. }
+			| SyncStatus::TxHashsetDownload(_)
+			| SyncStatus::ValidatingKernelsHistory
+			| SyncStatus::TxHashsetHeadersValidation { .. }
+			| SyncStatus::TxHashsetKernelsPosValidation { .. }
+			| SyncStatus::TxHashsetRangeProofsValidation { .. }
+			| SyncStatus::TxHashsetKernelsValidation { .. } => true,
+			_ => false,
+		}
+	}
+}
@@ -13,8 +13,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::chain::{self, pibd_params, SyncState};
+use crate::chain::{self, pibd_params, SyncEventKind, SyncState};
 use crate::core::core::{hash::Hashed, pmmr::segment::SegmentType};
+use crate::mwc::sync::bandwidth::BandwidthEstimator;
 use crate::mwc::sync::sync_peers::SyncPeers;
 use crate::mwc::sync::sync_utils;
 use crate::mwc::sync::sync_utils::{RequestTracker, SyncRequestResponses, SyncResponse};
@@ -23,18 +24,20 @@ use crate::util::StopState;
 use chrono::prelude::{DateTime, Utc};
 use mwc_chain::pibd_params::PibdParams;
 use mwc_chain::txhashset::{BitmapChunk, Desegmenter};
-use mwc_chain::{Chain, SyncStatus};
+use mwc_chain::{Chain, SegmentsProgress, SyncStatus};
 use mwc_core::core::hash::Hash;
 use mwc_core::core::{OutputIdentifier, Segment, SegmentTypeIdentifier, TxKernel};
 use mwc_p2p::{Error, PeerAddr};
 use mwc_util::secp::pedersen::RangeProof;
 use mwc_util::RwLock;
-use rand::prelude::IteratorRandom;
 use rand::seq::SliceRandom;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
+/// PIBD download progress is saved that often, so it can be resumed after restart
+const PIBD_PROGRESS_SAVE_INTERVAL_SECS: i64 = 30;
+
 /// Fast sync has 3 "states":
 /// * syncing headers
 /// * once all headers are sync'd, requesting the txhashset state
@@ -62,6 +65,8 @@ pub struct StateSync {
 
 	excluded_peers: RwLock<HashSet<PeerAddr>>,
 	send_requests_lock: RwLock<u8>,
+	bandwidth: BandwidthEstimator,
+	last_progress_save: RwLock<DateTime<Utc>>,
 }
 
 impl StateSync {
@@ -82,6 +87,8 @@ impl StateSync {
 			retry_expiration_times: RwLock::new(VecDeque::new()),
 			excluded_peers: RwLock::new(HashSet::new()),
 			send_requests_lock: RwLock::new(0),
+			bandwidth: BandwidthEstimator::new(),
+			last_progress_save: RwLock::new(Utc::now()),
 		}
 	}
 
@@ -161,6 +168,13 @@ impl StateSync {
 		let excluded_peers = self
 			.request_tracker
 			.retain_expired(pibd_params::PIBD_REQUESTS_TIMEOUT_SECS, sync_peers);
+		if !excluded_peers.is_empty() {
+			sync_state.add_event(
+				SyncEventKind::RequestsTimedOut,
+				excluded_peers.iter().map(|p| p.to_string()).collect(),
+				"Segments requests are timed out, retrying with other peers".into(),
+			);
+		}
 		*self.excluded_peers.write() = excluded_peers;
 
 		// Requesting root_hash...
@@ -271,39 +285,67 @@ impl StateSync {
 					.expect("hash_counts is empty?");
 
 				info!("Creating desegmenter for root hash {}", best_root_hash);
+				sync_state.add_event(
+					SyncEventKind::PeerSelected,
+					responded_root_hash
+						.iter()
+						.filter(|(_, (hash, _))| hash == best_root_hash)
+						.map(|(peer, _)| peer.to_string())
+						.collect(),
+					format!(
+						"PIBD root hash {} for archive height {} is selected",
+						best_root_hash, archive_header.height
+					),
+				);
 
-				if let Err(e) = self.chain.reset_pibd_chain() {
-					let msg = format!(
-						"Failed to reset chain before start BIPD state sync. Error: {}",
-						e
-					);
-					error!("{}", msg);
-					return SyncResponse::new(
-						SyncRequestResponses::Syncing,
-						Self::get_peer_capabilities(),
-						msg,
-					);
-				}
-				match self
+				// Segments downloaded before restart are kept if the peers agree on the same state
+				let resumed = match self
 					.chain
-					.init_desegmenter(archive_header.height, best_root_hash.clone())
+					.resume_desegmenter(archive_header.height, best_root_hash.clone())
 				{
-					Ok(desegmenter) => {
-						*self.target_archive_hash.write() = archive_header.hash();
-						*self.desegmenter.write() = Some(desegmenter);
-					}
+					Ok(resumed) => resumed,
 					Err(e) => {
-						error!("Failed to create PIBD desgmenter, {}", e);
-						// let's try to reset everything...
-						if let Err(e) = self.chain.reset_pibd_chain() {
-							error!("reset_pibd_chain failed with error: {}", e);
-						}
+						warn!("Unable to resume PIBD download, {}", e);
+						None
+					}
+				};
+				if let Some(desegmenter) = resumed {
+					*self.target_archive_hash.write() = archive_header.hash();
+					*self.desegmenter.write() = Some(desegmenter);
+				} else {
+					if let Err(e) = self.chain.reset_pibd_chain() {
+						let msg = format!(
+							"Failed to reset chain before start BIPD state sync. Error: {}",
+							e
+						);
+						error!("{}", msg);
 						return SyncResponse::new(
 							SyncRequestResponses::Syncing,
 							Self::get_peer_capabilities(),
-							format!("Failed to create PIBD desgmenter, {}", e),
+							msg,
 						);
 					}
+					match self
+						.chain
+						.init_desegmenter(archive_header.height, best_root_hash.clone())
+					{
+						Ok(desegmenter) => {
+							*self.target_archive_hash.write() = archive_header.hash();
+							*self.desegmenter.write() = Some(desegmenter);
+						}
+						Err(e) => {
+							error!("Failed to create PIBD desgmenter, {}", e);
+							// let's try to reset everything...
+							if let Err(e) = self.chain.reset_pibd_chain() {
+								error!("reset_pibd_chain failed with error: {}", e);
+							}
+							return SyncResponse::new(
+								SyncRequestResponses::Syncing,
+								Self::get_peer_capabilities(),
+								format!("Failed to create PIBD desgmenter, {}", e),
+							);
+						}
+					}
 				}
 
 				self.request_tracker.clear();
@@ -380,6 +422,14 @@ impl StateSync {
 
 		sync_state.update(desegmenter.get_pibd_progress());
 
+		if (now - *self.last_progress_save.read()).num_seconds() >= PIBD_PROGRESS_SAVE_INTERVAL_SECS
+		{
+			if let Err(e) = desegmenter.save_progress() {
+				warn!("Unable to save PIBD progress, {}", e);
+			}
+			*self.last_progress_save.write() = now;
+		}
+
 		// let's check what peers with root hash are exist
 		let root_hash = desegmenter.get_bitmap_root_hash();
 		let mut root_hash_peers: Vec<Arc<Peer>> = Vec::new();
@@ -429,6 +479,7 @@ impl StateSync {
 			}
 		}
 
+		self.update_bandwidth(in_peers);
 		self.send_requests(
 			&root_hash_peers,
 			&root_hash_peers,
@@ -439,6 +490,16 @@ impl StateSync {
 		)
 	}
 
+	fn update_bandwidth(&self, peers: &Arc<p2p::Peers>) {
+		self.bandwidth
+			.update(peers, Capabilities::PIBD_HIST, |addr| {
+				self.request_tracker
+					.get_peer_track_data(addr)
+					.map(|d| d.requests() > 0)
+					.unwrap_or(false)
+			});
+	}
+
 	fn ban_this_session(&self, root_hash: &Hash, sync_peers: &SyncPeers) {
 		error!("Banning all peers joind for root hash {}", root_hash);
 		// Banning all peers that was agree with that hash...
@@ -450,9 +511,27 @@ impl StateSync {
 				}
 			}
 		}
+		// Bad state must not be resumed after restart
+		if let Err(e) = self.chain.delete_pibd_progress() {
+			error!("Unable to delete PIBD progress, {}", e);
+		}
 		self.reset_desegmenter.store(true, Ordering::Relaxed);
 	}
 
+	/// Outputs, rangeproofs and kernels segments progress of the current desegmenter
+	pub fn get_segments_progress(
+		&self,
+	) -> (
+		Option<SegmentsProgress>,
+		Option<SegmentsProgress>,
+		Option<SegmentsProgress>,
+	) {
+		match self.desegmenter.read().as_ref() {
+			Some(desegmenter) => desegmenter.get_segments_progress(),
+			None => (None, None, None),
+		}
+	}
+
 	pub fn reset_desegmenter_data(&self) {
 		*self.desegmenter.write() = None;
 		self.requested_root_hash.write().clear();
@@ -533,6 +612,7 @@ impl StateSync {
 		let _ = self.request_tracker.remove_request(key, peer);
 
 		if self.request_tracker.get_update_requests_to_next_ask() == 0 {
+			self.update_bandwidth(peers);
 			let (peers, excluded_requests, excluded_peers) = sync_utils::get_sync_peers(
 				peers,
 				self.pibd_params.get_segments_request_per_peer(),
@@ -821,11 +901,13 @@ impl StateSync {
 											segm.segment_type.clone(),
 											segm.identifier.leaf_offset(),
 										)) {
-										let dup_peers: Vec<Arc<Peer>> = peers
+										// Segments that block the progress go to the fastest peers
+										let other_peers: Vec<Arc<Peer>> = peers
 											.iter()
 											.filter(|p| p.info.addr != requested_peer)
 											.cloned()
-											.choose_multiple(&mut rng, 2);
+											.collect();
+										let dup_peers = self.bandwidth.fastest(&other_peers, 2);
 
 										if dup_peers.len() == 0 {
 											break;
@@ -880,8 +962,9 @@ impl StateSync {
 							let key = (seg.segment_type.clone(), seg.identifier.leaf_offset());
 							debug_assert!(!self.request_tracker.has_request(&key));
 							debug_assert!(!root_hash_peers.is_empty());
-							let peer = root_hash_peers
-								.choose(&mut rng)
+							let peer = self
+								.bandwidth
+								.choose(root_hash_peers, &mut rng)
 								.expect("peers is not empty");
 
 							let send_res = Self::send_request(peer, &seg, &target_archive_hash);
@@ -919,10 +1002,12 @@ impl StateSync {
 										segm.segment_type.clone(),
 										segm.identifier.leaf_offset(),
 									)) {
-									let dup_peer = peers
+									let other_peers: Vec<Arc<Peer>> = peers
 										.iter()
 										.filter(|p| p.info.addr != requested_peer)
-										.choose(&mut rng);
+										.cloned()
+										.collect();
+									let dup_peer = self.bandwidth.choose(&other_peers, &mut rng);
 
 									if dup_peer.is_none() {
 										break;
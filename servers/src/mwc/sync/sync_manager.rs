@@ -15,14 +15,23 @@
 // sync_utils contain banch of shared between mutiple sync modules routines
 // Normally we would put that into the base class, but rust doesn't support that.
 
+use crate::common::stats::OrphanPoolStats;
+use crate::mwc::disk_monitor::DiskMonitor;
 use crate::mwc::sync::block_headers_request_cache::HeadersBlocksRequests;
 use crate::mwc::sync::body_sync::BodySync;
 use crate::mwc::sync::header_hashes_sync::HeadersHashSync;
 use crate::mwc::sync::header_sync::HeaderSync;
+use crate::mwc::sync::history_backfill::HistoryBackfill;
 use crate::mwc::sync::orphans_sync::OrphansSync;
 use crate::mwc::sync::state_sync::StateSync;
 use crate::mwc::sync::sync_peers::SyncPeers;
+use crate::mwc::sync::sync_progress::SyncProgressTracker;
+use crate::mwc::sync::sync_stage::{
+	run_stages, BodyStage, DiskSpaceStage, HeadersHashStage, HeadersStage, StateStage, SyncContext,
+	SyncStage,
+};
 use crate::mwc::sync::sync_utils::{CachedResponse, SyncRequestResponses, SyncResponse};
+use crate::mwc::sync::txhashset_sync::TxHashsetSync;
 use chrono::Duration;
 use mwc_chain::txhashset::BitmapChunk;
 use mwc_chain::{Chain, SyncState};
@@ -32,43 +41,170 @@ use mwc_p2p::{Capabilities, PeerAddr, Peers};
 use mwc_util::secp::pedersen::RangeProof;
 use mwc_util::secp::rand::Rng;
 use mwc_util::{RwLock, StopState};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 
+/// Number of the state sync requests in a row that the current method is waiting for the
+/// capable peers. Then the node falls back to another method.
+const STATE_METHOD_RETRY_BUDGET: u32 = 120;
+
+/// Method to download the txhashset state at the archive height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateSyncMethod {
+	/// Segments from many peers in parallel
+	Pibd,
+	/// Full txhashset zip archive from a single peer
+	TxHashsetArchive,
+}
+
+/// Selects the state sync method. PIBD is preferred, the txhashset archive is a fallback
+/// for the network segments without PIBD peers, and vice versa.
+pub struct StateMethodSelector {
+	method: RwLock<StateSyncMethod>,
+	failures: AtomicU32,
+}
+
+impl StateMethodSelector {
+	pub(crate) fn new() -> Self {
+		StateMethodSelector {
+			method: RwLock::new(StateSyncMethod::Pibd),
+			failures: AtomicU32::new(0),
+		}
+	}
+
+	pub fn method(&self) -> StateSyncMethod {
+		*self.method.read()
+	}
+
+	/// Account the state sync response of the current method. Returns true if the
+	/// retry budget is spent and the method is switched.
+	pub fn report(&self, resp: &SyncResponse) -> bool {
+		match resp.response {
+			SyncRequestResponses::WaitingForPeers => {
+				let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+				if failures < STATE_METHOD_RETRY_BUDGET {
+					return false;
+				}
+				self.failures.store(0, Ordering::Relaxed);
+				let mut method = self.method.write();
+				let prev_method = *method;
+				*method = match prev_method {
+					StateSyncMethod::Pibd => StateSyncMethod::TxHashsetArchive,
+					StateSyncMethod::TxHashsetArchive => StateSyncMethod::Pibd,
+				};
+				warn!(
+					"State sync with {:?} has no capable peers for {} requests, switching to {:?}",
+					prev_method, failures, *method
+				);
+				true
+			}
+			// Headers are not related to the state method
+			SyncRequestResponses::WaitingForHeaders => false,
+			_ => {
+				self.failures.store(0, Ordering::Relaxed);
+				false
+			}
+		}
+	}
+}
+
 /// Sync Manager is reponsible for coordination of all syncing process
 pub struct SyncManager {
-	headers_hashes: RwLock<HeadersHashSync>,
-	headers: HeaderSync,
-	state: StateSync,
-	body: BodySync,
+	headers_hashes: Arc<RwLock<HeadersHashSync>>,
+	headers: Arc<HeaderSync>,
+	state: Arc<StateSync>,
+	txhashset: Arc<TxHashsetSync>,
+	body: Arc<BodySync>,
 	orphans: OrphansSync,
+	history_backfill: HistoryBackfill,
 	headers_block_requests: HeadersBlocksRequests,
 
 	// Headers has complications with banning. In case of bad hashes, we will found that much later
 	// when we ban many peers. That is why we need to track that separately and unban it in such case.
-	headers_sync_peers: SyncPeers,
+	headers_sync_peers: Arc<SyncPeers>,
 	// state & body sync
-	state_sync_peers: SyncPeers,
+	state_sync_peers: Arc<SyncPeers>,
+	// Sync pipeline, the stages share the sync modules with the responses handlers
+	stages: Vec<Box<dyn SyncStage>>,
 	sync_state: Arc<SyncState>,
 	stop_state: Arc<StopState>,
+	// Sync progress with ETA for the API
+	progress: SyncProgressTracker,
+	// Pipeline stage that is in progress
+	current_stage: RwLock<Option<&'static str>>,
 
 	cached_response: RwLock<Option<CachedResponse<SyncResponse>>>,
+	// Restart requested by the API, the sync thread does it between the sync requests
+	restart_requested: AtomicBool,
 }
 
 impl SyncManager {
-	pub fn new(chain: Arc<Chain>, sync_state: Arc<SyncState>, stop_state: Arc<StopState>) -> Self {
+	pub fn new(
+		chain: Arc<Chain>,
+		sync_state: Arc<SyncState>,
+		stop_state: Arc<StopState>,
+		disk_monitor: Arc<DiskMonitor>,
+		header_only: bool,
+	) -> Self {
+		let headers_hashes = Arc::new(RwLock::new(HeadersHashSync::new(chain.clone())));
+		let headers = Arc::new(HeaderSync::new(chain.clone()));
+		let state = Arc::new(StateSync::new(chain.clone()));
+		let txhashset = Arc::new(TxHashsetSync::new(chain.clone()));
+		let body = Arc::new(BodySync::new(chain.clone()));
+		let headers_sync_peers = Arc::new(SyncPeers::new());
+		let state_sync_peers = Arc::new(SyncPeers::new());
+
+		// Headers, then the txhashset state, then the blocks. Headers only node stops after headers.
+		let mut stages: Vec<Box<dyn SyncStage>> = vec![
+			Box::new(HeadersHashStage::<Arc<Peers>> {
+				headers_hashes: headers_hashes.clone(),
+				sync_peers: headers_sync_peers.clone(),
+			}),
+			Box::new(HeadersStage {
+				headers: headers.clone(),
+				headers_hashes: headers_hashes.clone(),
+				sync_peers: headers_sync_peers.clone(),
+			}),
+		];
+		if !header_only {
+			stages.push(Box::new(DiskSpaceStage {
+				chain: chain.clone(),
+				disk_monitor,
+				checked_height: AtomicU64::new(0),
+			}));
+			stages.push(Box::new(StateStage::<Arc<Peers>> {
+				state: state.clone(),
+				txhashset: txhashset.clone(),
+				method: Arc::new(StateMethodSelector::new()),
+				sync_peers: state_sync_peers.clone(),
+			}));
+			stages.push(Box::new(BodyStage::<Arc<Peers>> {
+				body: body.clone(),
+				state: state.clone(),
+				sync_peers: state_sync_peers.clone(),
+			}));
+		}
+
 		SyncManager {
-			headers_hashes: RwLock::new(HeadersHashSync::new(chain.clone())),
-			headers: HeaderSync::new(chain.clone()),
-			state: StateSync::new(chain.clone()),
-			body: BodySync::new(chain.clone()),
+			headers_hashes,
+			headers,
+			state,
+			txhashset,
+			body,
 			orphans: OrphansSync::new(chain.clone()),
+			history_backfill: HistoryBackfill::new(chain.clone()),
 			headers_block_requests: HeadersBlocksRequests::new(chain),
 
-			headers_sync_peers: SyncPeers::new(),
-			state_sync_peers: SyncPeers::new(),
+			headers_sync_peers,
+			state_sync_peers,
+			stages,
 			sync_state,
 			stop_state,
+			progress: SyncProgressTracker::new(),
+			current_stage: RwLock::new(None),
 			cached_response: RwLock::new(None),
+			restart_requested: AtomicBool::new(false),
 		}
 	}
 
@@ -103,6 +239,14 @@ impl SyncManager {
 	}
 
 	pub fn sync_request(&self, peers: &Arc<Peers>) -> SyncResponse {
+		let resp = self.sync_request_impl(peers);
+		let stage = *self.current_stage.read();
+		self.progress
+			.update(&self.sync_state, peers, &self.state, stage);
+		resp
+	}
+
+	fn sync_request_impl(&self, peers: &Arc<Peers>) -> SyncResponse {
 		let cached_response = self.cached_response.read().clone();
 		if let Some(cached_response) = cached_response {
 			if !cached_response.is_expired() {
@@ -119,6 +263,10 @@ impl SyncManager {
 		offline1.append(&mut offline2);
 		let mut rng = rand::thread_rng();
 		offline1.retain(|_| rng.gen_range(0, 10) != 7); // We want to exclude some, because peer might become online
+
+		// Peers on probation are excluded until probation expires
+		offline1.append(&mut self.headers_sync_peers.get_probation_peers());
+		offline1.append(&mut self.state_sync_peers.get_probation_peers());
 		peers.set_excluded_peers(&offline1);
 
 		let mut best_height = peers
@@ -161,125 +309,41 @@ impl SyncManager {
 			);
 		}
 
-		let r = self.headers_hashes.read().request_pre(best_height);
-		let headers_hash_resp = match r {
-			Some(resp) => resp,
-			None => self.headers_hashes.write().request_impl(
-				peers,
-				&self.sync_state,
-				&self.headers_sync_peers,
-				best_height,
-			),
+		let mut ctx = SyncContext {
+			peers,
+			sync_state: &self.sync_state,
+			stop_state: &self.stop_state,
+			best_height,
+			headers_ready: false,
 		};
-
-		debug!("headers_hash_resp: {:?}", headers_hash_resp);
-		match headers_hash_resp.response {
-			SyncRequestResponses::WaitingForPeers => return headers_hash_resp,
-			SyncRequestResponses::Syncing => return headers_hash_resp,
-			SyncRequestResponses::HeadersPibdReady | SyncRequestResponses::HeadersHashReady => {}
-			_ => {
-				debug_assert!(false);
-			}
+		let in_progress = run_stages(&self.stages, &mut ctx);
+		*self.current_stage.write() = in_progress.as_ref().map(|(stage, _)| *stage);
+		if let Some((_, resp)) = in_progress {
+			return resp;
 		}
 
-		let mut headers_ready = false;
-
-		let headers_resp = self.headers.request(
-			peers,
-			&self.sync_state,
-			&self.headers_sync_peers,
-			&self.headers_hashes.read(),
-			best_height,
-		);
-		debug!("headers_resp: {:?}", headers_resp);
-		match headers_resp.response {
-			SyncRequestResponses::WaitingForPeers => {
-				self.headers_hashes
-					.write()
-					.reset_ban_commited_to_hash(peers, &self.headers_sync_peers);
-				self.headers_sync_peers.reset();
-				return headers_resp;
-			}
-			SyncRequestResponses::Syncing => return headers_resp,
-			SyncRequestResponses::HasMoreHeadersToApply => return headers_resp,
-			SyncRequestResponses::WaitingForHeadersHash => {
-				debug_assert!(false); // should never happen, headers_hashes above must be in sync or wait for peers
-				return headers_resp;
-			}
-			SyncRequestResponses::HeadersPibdReady => self.headers_hashes.write().reset_hash_data(),
-			SyncRequestResponses::HeadersReady => headers_ready = true,
-			_ => {
-				debug_assert!(false);
-			}
+		if !ctx.headers_ready {
+			return SyncResponse::new(
+				SyncRequestResponses::Syncing,
+				self.body.get_peer_capabilities(),
+				"Waiting for headers, even body is done, more is expected".into(),
+			);
 		}
 
-		let state_resp = self.state.request(
-			peers,
-			self.sync_state.clone(),
-			&self.state_sync_peers,
-			self.stop_state.clone(),
-			best_height,
+		let resp = SyncResponse::new(
+			SyncRequestResponses::SyncDone,
+			Capabilities::UNKNOWN,
+			"DONE!".into(),
 		);
-		debug!("state_resp: {:?}", state_resp);
-		match state_resp.response {
-			SyncRequestResponses::Syncing => return state_resp,
-			SyncRequestResponses::WaitingForPeers => return state_resp,
-			SyncRequestResponses::WaitingForHeaders => return state_resp,
-			SyncRequestResponses::StatePibdReady => {}
-			_ => {
-				debug_assert!(false);
-			}
-		}
+		peers.set_excluded_peers(&vec![]);
+		*self.cached_response.write() =
+			Some(CachedResponse::new(resp.clone(), Duration::seconds(35)));
 
-		match self
-			.body
-			.request(peers, &self.sync_state, &self.state_sync_peers, best_height)
-		{
-			Ok(body_resp) => {
-				debug!("body_resp: {:?}", body_resp);
-				match body_resp.response {
-					SyncRequestResponses::Syncing => return body_resp,
-					SyncRequestResponses::BodyReady => {
-						if headers_ready {
-							let resp = SyncResponse::new(
-								SyncRequestResponses::SyncDone,
-								Capabilities::UNKNOWN,
-								"DONE!".into(),
-							);
-							peers.set_excluded_peers(&vec![]);
-							*self.cached_response.write() =
-								Some(CachedResponse::new(resp.clone(), Duration::seconds(35)));
-
-							if let Err(e) = self.orphans.sync_orphans(peers) {
-								error!("Failed to sync_orphans. Error: {}", e);
-							}
-
-							return resp;
-						} else {
-							return SyncResponse::new(
-								SyncRequestResponses::Syncing,
-								self.body.get_peer_capabilities(),
-								"Waiting for headers, even body is done, more is expected".into(),
-							);
-						}
-					}
-					SyncRequestResponses::WaitingForPeers => return body_resp,
-					SyncRequestResponses::BadState => {
-						self.state.reset_desegmenter_data();
-						return body_resp;
-					}
-					_ => debug_assert!(false),
-				}
-			}
-			Err(e) => error!("Body request is failed, {}", e),
+		if let Err(e) = self.orphans.sync_orphans(peers) {
+			error!("Failed to sync_orphans. Error: {}", e);
 		}
 
-		debug_assert!(false);
-		SyncResponse::new(
-			SyncRequestResponses::Syncing,
-			Capabilities::UNKNOWN,
-			"Invalid state, internal error".into(),
-		)
+		resp
 	}
 
 	pub fn receive_headers_hash_response(
@@ -358,6 +422,37 @@ impl SyncManager {
 			.recieve_another_archive_header(peer, &header_hash, header_height);
 	}
 
+	pub fn txhashset_receive_ready(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+	) -> bool {
+		self.txhashset
+			.receive_ready(peer, header_hash, header_height)
+	}
+
+	pub fn txhashset_download_update(
+		&self,
+		peer: &PeerAddr,
+		downloaded_size: u64,
+		total_size: u64,
+	) {
+		self.txhashset
+			.download_update(peer, downloaded_size, total_size, &self.sync_state);
+	}
+
+	pub fn txhashset_received(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) {
+		self.txhashset
+			.receive_archive(peer, header_hash, header_height, path);
+	}
+
 	pub fn receive_bitmap_segment(
 		&self,
 		peer: &PeerAddr,
@@ -422,6 +517,71 @@ impl SyncManager {
 		);
 	}
 
+	/// Restart the headers sync from our header head with up to `peers_num` most work peers
+	pub fn restart_headers_sync(&self, peers: &Arc<Peers>, peers_num: usize) {
+		*self.cached_response.write() = None;
+		if let Err(e) = self.headers.restart(peers, peers_num) {
+			error!("Unable to restart headers sync, {}", e);
+		}
+	}
+
+	/// Ask the sync thread to restart the sync. The restart resets the state that the sync
+	/// stages are working with, so it is not done from the other threads.
+	pub fn request_restart(&self) {
+		self.restart_requested.store(true, Ordering::Relaxed);
+	}
+
+	/// Whether the restart is requested and not done yet
+	pub fn is_restart_requested(&self) -> bool {
+		self.restart_requested.load(Ordering::Relaxed)
+	}
+
+	/// Restart the sync if it was requested. Called by the sync thread.
+	pub fn process_restart_request(&self, chain: &Chain, peers: &Arc<Peers>) {
+		if self.restart_requested.swap(false, Ordering::Relaxed) {
+			info!("Restarting the sync");
+			if let Err(e) = self.restart_sync(chain, peers) {
+				error!("Unable to restart the sync, {}", e);
+			}
+		}
+	}
+
+	/// Drop the sync progress and the chain state, then restart the sync from the scratch.
+	/// The headers chain is kept, the body head is reset to genesis, so the state is
+	/// downloaded again with PIBD.
+	fn restart_sync(&self, chain: &Chain, peers: &Arc<Peers>) -> Result<(), mwc_chain::Error> {
+		*self.cached_response.write() = None;
+		*self.current_stage.write() = None;
+		self.headers_hashes.write().reset();
+		self.state.reset_desegmenter_data();
+		self.txhashset.reset();
+		self.headers_sync_peers.reset();
+		self.state_sync_peers.reset();
+		chain.reset_pibd_chain()?;
+		self.sync_state.reset();
+		self.restart_headers_sync(peers, peers.iter().connected().count());
+		Ok(())
+	}
+
+	/// Request the blocks history below the tail, if the backfill is enabled
+	pub fn backfill_history(&self, peers: &Arc<Peers>) {
+		if let Err(e) = self.history_backfill.request(peers, &self.state_sync_peers) {
+			error!("Failed to request the backfill blocks, {}", e);
+		}
+	}
+
+	/// Process the block if it was requested by the history backfill. Returns None if it wasn't,
+	/// otherwise if the block is accepted.
+	pub fn recieve_backfill_block(&self, peer: &PeerAddr, b: &Block) -> Option<bool> {
+		self.history_backfill
+			.recieve_block(peer, b, &self.state_sync_peers)
+	}
+
+	/// Metrics of the blocks that are waiting for the headers
+	pub fn orphan_pool_stats(&self) -> OrphanPoolStats {
+		self.orphans.get_stats()
+	}
+
 	// return true if need to request prev block
 	pub fn recieve_block_reporting(
 		&self,
@@ -440,9 +600,71 @@ impl SyncManager {
 		);
 
 		if valid_block && opts == mwc_chain::Options::NONE {
-			self.orphans.recieve_block_reporting(b)
+			self.orphans.recieve_block_reporting(b, peer)
 		} else {
 			false
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::common::types::DiskMonitorConfig;
+	use mwc_chain::types::NoopAdapter;
+	use mwc_chain::SyncStatus;
+	use mwc_core::{global, pow};
+	use mwc_p2p::store::PeerStore;
+	use mwc_p2p::{DummyAdapter, P2PConfig};
+
+	#[test]
+	fn test_restart_is_done_by_sync_thread() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let dir = tempfile::tempdir().unwrap();
+		let chain = Arc::new(
+			Chain::init(
+				dir.path().join("chain").to_str().unwrap().to_string(),
+				Arc::new(NoopAdapter {}),
+				pow::mine_genesis_block().unwrap(),
+				pow::verify_size,
+				false,
+			)
+			.unwrap(),
+		);
+		let sync_state = Arc::new(SyncState::new());
+		let stop_state = Arc::new(StopState::new());
+		let peers = Arc::new(Peers::new(
+			PeerStore::new(dir.path().join("peers").to_str().unwrap()).unwrap(),
+			Arc::new(DummyAdapter {}),
+			P2PConfig::default(),
+			stop_state.clone(),
+		));
+		let disk_monitor = Arc::new(DiskMonitor::new(
+			DiskMonitorConfig::default(),
+			chain.clone(),
+			dir.path().to_path_buf(),
+		));
+		let sync_manager = SyncManager::new(
+			chain.clone(),
+			sync_state.clone(),
+			stop_state,
+			disk_monitor,
+			false,
+		);
+
+		// The request doesn't touch the sync state, the sync thread may be using it
+		sync_state.update(SyncStatus::AwaitingPeers);
+		sync_manager.request_restart();
+		assert!(sync_manager.is_restart_requested());
+		assert_eq!(sync_state.status(), SyncStatus::AwaitingPeers);
+
+		sync_manager.process_restart_request(&chain, &peers);
+		assert!(!sync_manager.is_restart_requested());
+		assert_eq!(sync_state.status(), SyncStatus::NoSync);
+
+		// The request is processed once
+		sync_state.update(SyncStatus::AwaitingPeers);
+		sync_manager.process_restart_request(&chain, &peers);
+		assert_eq!(sync_state.status(), SyncStatus::AwaitingPeers);
+	}
+}
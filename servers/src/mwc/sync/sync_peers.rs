@@ -15,6 +15,7 @@
 // sync_utils contain banch of shared between mutiple sync modules routines
 // Normally we would put that into the base class, but rust doesn't support that.
 
+use chrono::{DateTime, Duration, Utc};
 use mwc_p2p::{PeerAddr, Peers, ReasonForBan};
 use mwc_util::RwLock;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -30,14 +31,21 @@ enum PeerStatusEvent {
 
 const MIN_RESPONSE_NUM: usize = 13; // 6*2+1  8 requests per peer is expected, see get_segments_request_per_peer()
 
+/// Peers that served invalid data or stalled repeatedly are skipped by sync for that time
+const PROBATION_MINUTES: i64 = 10;
+/// Number of the offline checks in a row that put the peer on probation
+const OFFLINE_PROBATION_NUM: usize = 3;
+
 pub struct PeerPibdStatus {
 	responses: VecDeque<PeerStatusEvent>,
+	offline_checks: usize,
 }
 
 impl PeerPibdStatus {
 	fn default() -> PeerPibdStatus {
 		PeerPibdStatus {
 			responses: VecDeque::new(),
+			offline_checks: 0,
 		}
 	}
 
@@ -48,8 +56,10 @@ impl PeerPibdStatus {
 	/// Checking events log to decide if peer wasn't active enough
 	/// Note, this method is expecting to truncate responses, so data will be managable
 	/// during long run
-	/// Return: (ban, offline, comment)
-	fn check_for_ban(&mut self, peer: &String) -> (bool, bool, String) {
+	/// Explicit ban events are banning the peer, invalid responses or repeated stalls
+	/// are putting it on probation.
+	/// Return: (ban, probation, offline, comment)
+	fn check_for_ban(&mut self, peer: &String) -> (bool, bool, bool, String) {
 		let mut bans = 0;
 		let mut errors = 0;
 		let mut no_response = 0;
@@ -85,31 +95,49 @@ impl PeerPibdStatus {
 			}
 		}
 
-		let res_ban = bans > 0 || errors > 1;
+		let res_ban = bans > 0;
 
 		let res_network_issue =
 			self.responses.len() >= MIN_RESPONSE_NUM && success <= self.responses.len() / 2;
+		if res_network_issue {
+			self.offline_checks += 1;
+		} else {
+			self.offline_checks = 0;
+		}
+
+		let res_probation =
+			!res_ban && (errors > 1 || self.offline_checks >= OFFLINE_PROBATION_NUM);
 
 		debug!(
-			"Checking for Ban. Peer: {}, bans={} errors={} no_resp={} ok={}  RES={},{}",
-			peer, bans, errors, no_response, success, res_ban, res_network_issue
+			"Checking for Ban. Peer: {}, bans={} errors={} no_resp={} ok={} offline_checks={}  RES={},{},{}",
+			peer,
+			bans,
+			errors,
+			no_response,
+			success,
+			self.offline_checks,
+			res_ban,
+			res_probation,
+			res_network_issue
 		);
 
 		while self.responses.len() > MIN_RESPONSE_NUM {
 			self.responses.pop_front();
 		}
 
-		(res_ban, res_network_issue, comment)
+		(res_ban, res_probation, res_network_issue, comment)
 	}
 
 	pub fn reset(&mut self) {
 		self.responses.clear();
+		self.offline_checks = 0;
 	}
 }
 
 pub struct SyncPeers {
 	peers_status: RwLock<HashMap<String, PeerPibdStatus>>,
 	banned_peers: RwLock<HashSet<PeerAddr>>, // collecting banned peers because we might need to unban them.
+	probation_peers: RwLock<HashMap<PeerAddr, DateTime<Utc>>>, // peer -> probation expiration time
 	new_events_peers: RwLock<HashSet<String>>,
 }
 
@@ -118,13 +146,17 @@ impl SyncPeers {
 		SyncPeers {
 			peers_status: RwLock::new(HashMap::new()),
 			banned_peers: RwLock::new(HashSet::new()),
+			probation_peers: RwLock::new(HashMap::new()),
 			new_events_peers: RwLock::new(HashSet::new()),
 		}
 	}
 
+	/// Reset is called when sync is waiting for peers, so the probation is
+	/// cleared as well. Better to retry those peers than to have no peers at all.
 	pub fn reset(&self) {
 		self.peers_status.write().clear();
 		self.banned_peers.write().clear();
+		self.probation_peers.write().clear();
 		self.new_events_peers.write().clear();
 	}
 
@@ -132,6 +164,21 @@ impl SyncPeers {
 		self.banned_peers.read().clone()
 	}
 
+	/// Peers with not expired probation. Expired probations are removed, those peers are retried.
+	pub fn get_probation_peers(&self) -> Vec<PeerAddr> {
+		let now = Utc::now();
+		let mut probation_peers = self.probation_peers.write();
+		probation_peers.retain(|peer, until| {
+			if *until > now {
+				true
+			} else {
+				info!("Sync probation is expired for peer {}", peer);
+				false
+			}
+		});
+		probation_peers.keys().cloned().collect()
+	}
+
 	pub fn report_no_response(&self, peer: &PeerAddr, message: String) {
 		self.add_event(peer.as_key(), PeerStatusEvent::NoResponse(message));
 	}
@@ -162,8 +209,19 @@ impl SyncPeers {
 		let mut offline_peers: Vec<PeerAddr> = Vec::new();
 		for cp in check_peers.iter() {
 			if let Some(status) = peers_status.get_mut(cp) {
-				let (ban, offline, comment) = status.check_for_ban(cp);
+				let (ban, probation, offline, comment) = status.check_for_ban(cp);
 				let peer_addr = PeerAddr::from_str(cp);
+				if probation {
+					info!(
+						"Peer {} is on sync probation for {} minutes, {}",
+						peer_addr, PROBATION_MINUTES, comment
+					);
+					status.reset();
+					self.probation_peers.write().insert(
+						peer_addr.clone(),
+						Utc::now() + Duration::minutes(PROBATION_MINUTES),
+					);
+				}
 				if ban {
 					if let Err(e) = peers.ban_peer(&peer_addr, ReasonForBan::PibdFailure, &comment)
 					{
@@ -172,7 +230,7 @@ impl SyncPeers {
 					status.reset();
 					self.banned_peers.write().insert(peer_addr.clone());
 				}
-				if offline {
+				if offline && !probation {
 					offline_peers.push(peer_addr);
 				}
 			}
@@ -0,0 +1,107 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sync progress for the API. The progress of the current phase is measured from
+//! the moment the phase started, so the ETA is the remaining items at the average
+//! rate of the phase.
+
+use crate::mwc::sync::state_sync::StateSync;
+use crate::p2p::Peers;
+use chrono::{DateTime, Utc};
+use mwc_chain::{SyncPhase, SyncProgress, SyncState};
+use mwc_util::RwLock;
+use std::sync::Arc;
+
+/// ETA is not estimated until the phase is running for that long
+const ETA_MIN_ELAPSED_SECS: i64 = 10;
+
+struct PhaseStart {
+	phase: SyncPhase,
+	time: DateTime<Utc>,
+	done: u64,
+}
+
+pub struct SyncProgressTracker {
+	phase_start: RwLock<Option<PhaseStart>>,
+}
+
+impl SyncProgressTracker {
+	pub fn new() -> SyncProgressTracker {
+		SyncProgressTracker {
+			phase_start: RwLock::new(None),
+		}
+	}
+
+	/// Build the progress of the current sync status and publish it to the sync state.
+	/// `stage` is the sync pipeline stage that is in progress.
+	pub fn update(
+		&self,
+		sync_state: &SyncState,
+		peers: &Arc<Peers>,
+		state: &StateSync,
+		stage: Option<&str>,
+	) {
+		let now = Utc::now();
+		let (phase, done, total) = SyncProgress::phase_items(&sync_state.status());
+
+		let eta_secs = {
+			let mut phase_start = self.phase_start.write();
+			// Phase is measured again if it is changed or restarted
+			let restart = match phase_start.as_ref() {
+				Some(start) => start.phase != phase || done < start.done,
+				None => true,
+			};
+			if restart {
+				*phase_start = Some(PhaseStart {
+					phase,
+					time: now,
+					done,
+				});
+			}
+			let start = phase_start.as_ref().unwrap();
+			let elapsed_secs = (now - start.time).num_seconds();
+			let progressed = done - start.done;
+			if elapsed_secs < ETA_MIN_ELAPSED_SECS || progressed == 0 || total < done {
+				None
+			} else {
+				Some(((total - done) as f64 * elapsed_secs as f64 / progressed as f64) as u64)
+			}
+		};
+
+		let bytes_per_min: u64 = peers
+			.iter()
+			.connected()
+			.into_iter()
+			.map(|p| p.tracker().received_bytes.read().bytes_per_min())
+			.sum();
+
+		let (pibd_outputs, pibd_rangeproofs, pibd_kernels) = if phase == SyncPhase::PibdState {
+			state.get_segments_progress()
+		} else {
+			(None, None, None)
+		};
+
+		sync_state.update_progress(SyncProgress {
+			phase,
+			done,
+			total,
+			bytes_per_sec: bytes_per_min / 60,
+			eta_secs,
+			pibd_outputs,
+			pibd_rangeproofs,
+			pibd_kernels,
+			stage: stage.map(|s| s.to_string()),
+		});
+	}
+}
@@ -0,0 +1,692 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sync stages. The sync manager runs the stages in order on every sync request,
+//! the first stage that is not ready stops the pipeline and its response is
+//! reported. Every stage maps the responses of its sync module into the outcome,
+//! so a different strategy is a different list of stages.
+
+use crate::chain;
+use crate::mwc::disk_monitor::DiskMonitor;
+use crate::mwc::sync::body_sync::BodySync;
+use crate::mwc::sync::header_hashes_sync::HeadersHashSync;
+use crate::mwc::sync::header_sync::HeaderSync;
+use crate::mwc::sync::state_sync::StateSync;
+use crate::mwc::sync::sync_manager::{StateMethodSelector, StateSyncMethod};
+use crate::mwc::sync::sync_peers::SyncPeers;
+use crate::mwc::sync::sync_utils::{SyncRequestResponses, SyncResponse};
+use crate::mwc::sync::txhashset_sync::TxHashsetSync;
+use mwc_chain::{Chain, SyncState};
+use mwc_core::core::BlockHeader;
+use mwc_p2p::{Capabilities, Peers};
+use mwc_util::{RwLock, StopState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Data that the stages get on every sync request. The peers are passed to the sync
+/// modules as they are, so the stages can run with any peers of their modules.
+pub struct SyncContext<'a, P = Arc<Peers>> {
+	pub peers: &'a P,
+	pub sync_state: &'a Arc<SyncState>,
+	pub stop_state: &'a Arc<StopState>,
+	/// Height of the best connected peer
+	pub best_height: u64,
+	/// Set by the headers stage when headers are synced up to the best peer
+	pub headers_ready: bool,
+}
+
+/// Result of the stage request
+#[derive(Debug)]
+pub enum StageOutcome {
+	/// Stage is not done, the next stages wait. The response is reported to the caller.
+	InProgress(SyncResponse),
+	/// Stage is done, the next stage can run
+	Ready,
+}
+
+/// Single step of the sync pipeline
+pub trait SyncStage<P = Arc<Peers>>: Send + Sync {
+	/// Stage name for the logs
+	fn name(&self) -> &'static str;
+
+	/// Make the requests that are needed to advance this stage
+	fn request(&self, ctx: &mut SyncContext<P>) -> StageOutcome;
+}
+
+/// Header hashes requests of the HeadersHashStage
+pub trait HeadersHashRequests<P>: Send + Sync {
+	fn request(&self, ctx: &SyncContext<P>, sync_peers: &SyncPeers) -> SyncResponse;
+}
+
+/// State requests of the StateStage, one per state sync method
+pub trait StateRequests<P>: Send + Sync {
+	fn request(&self, ctx: &SyncContext<P>, sync_peers: &SyncPeers) -> SyncResponse;
+
+	/// Drop the downloaded data, so the next download starts clean
+	fn reset(&self);
+}
+
+/// Blocks requests of the BodyStage
+pub trait BodyRequests<P>: Send + Sync {
+	fn request(
+		&self,
+		ctx: &SyncContext<P>,
+		sync_peers: &SyncPeers,
+	) -> Result<SyncResponse, chain::Error>;
+}
+
+/// Chain state that the DiskSpaceStage checks
+pub trait StageChain: Send + Sync {
+	fn is_block_acceptance_paused(&self) -> bool;
+	fn archive_mode(&self) -> bool;
+	fn head_height(&self) -> Result<u64, chain::Error>;
+	fn get_header_by_height(&self, height: u64) -> Result<BlockHeader, chain::Error>;
+}
+
+/// Run the stages in order until the first one that is not ready. Returns the name of
+/// that stage and its response, None if all stages are ready.
+pub fn run_stages<P>(
+	stages: &[Box<dyn SyncStage<P>>],
+	ctx: &mut SyncContext<P>,
+) -> Option<(&'static str, SyncResponse)> {
+	for stage in stages {
+		match stage.request(ctx) {
+			StageOutcome::InProgress(resp) => return Some((stage.name(), resp)),
+			StageOutcome::Ready => {}
+		}
+	}
+	None
+}
+
+// Responses that the stage doesn't expect are a bug, the pipeline goes on as before
+fn unexpected(stage: &str, resp: &SyncResponse) -> StageOutcome {
+	error!("Sync stage {} unexpected response {:?}", stage, resp);
+	debug_assert!(false);
+	StageOutcome::Ready
+}
+
+/// Header hashes download up to the archive height (PIBD headers)
+pub struct HeadersHashStage<P = Arc<Peers>> {
+	pub headers_hashes: Arc<dyn HeadersHashRequests<P>>,
+	pub sync_peers: Arc<SyncPeers>,
+}
+
+impl<P> SyncStage<P> for HeadersHashStage<P> {
+	fn name(&self) -> &'static str {
+		"headers_hash"
+	}
+
+	fn request(&self, ctx: &mut SyncContext<P>) -> StageOutcome {
+		let resp = self.headers_hashes.request(ctx, &self.sync_peers);
+		debug!("headers_hash_resp: {:?}", resp);
+		match resp.response {
+			SyncRequestResponses::WaitingForPeers | SyncRequestResponses::Syncing => {
+				StageOutcome::InProgress(resp)
+			}
+			SyncRequestResponses::HeadersPibdReady | SyncRequestResponses::HeadersHashReady => {
+				StageOutcome::Ready
+			}
+			_ => unexpected(self.name(), &resp),
+		}
+	}
+}
+
+/// Headers download, by the header hashes below the archive height, then the regular way
+pub struct HeadersStage {
+	pub headers: Arc<HeaderSync>,
+	pub headers_hashes: Arc<RwLock<HeadersHashSync>>,
+	pub sync_peers: Arc<SyncPeers>,
+}
+
+impl SyncStage for HeadersStage {
+	fn name(&self) -> &'static str {
+		"headers"
+	}
+
+	fn request(&self, ctx: &mut SyncContext) -> StageOutcome {
+		let resp = self.headers.request(
+			ctx.peers,
+			ctx.sync_state,
+			&self.sync_peers,
+			&self.headers_hashes.read(),
+			ctx.best_height,
+		);
+		debug!("headers_resp: {:?}", resp);
+		match resp.response {
+			SyncRequestResponses::WaitingForPeers => {
+				self.headers_hashes
+					.write()
+					.reset_ban_commited_to_hash(ctx.peers, &self.sync_peers);
+				self.sync_peers.reset();
+				StageOutcome::InProgress(resp)
+			}
+			SyncRequestResponses::Syncing | SyncRequestResponses::HasMoreHeadersToApply => {
+				StageOutcome::InProgress(resp)
+			}
+			SyncRequestResponses::WaitingForHeadersHash => {
+				// should never happen, headers_hashes stage must be in sync or wait for peers
+				debug_assert!(false);
+				StageOutcome::InProgress(resp)
+			}
+			SyncRequestResponses::HeadersPibdReady => {
+				self.headers_hashes.write().reset_hash_data();
+				StageOutcome::Ready
+			}
+			SyncRequestResponses::HeadersReady => {
+				ctx.headers_ready = true;
+				StageOutcome::Ready
+			}
+			_ => unexpected(self.name(), &resp),
+		}
+	}
+}
+
+/// Disk space check before the state and the blocks. The state download doesn't start if
+/// its projected size doesn't fit the disk, the blocks wait while the chain refuses them.
+pub struct DiskSpaceStage {
+	pub chain: Arc<dyn StageChain>,
+	pub disk_monitor: Arc<DiskMonitor>,
+	// Archive height that passed the check, the started download is not checked again
+	pub checked_height: AtomicU64,
+}
+
+impl<P> SyncStage<P> for DiskSpaceStage {
+	fn name(&self) -> &'static str {
+		"disk_space"
+	}
+
+	fn request(&self, ctx: &mut SyncContext<P>) -> StageOutcome {
+		if self.chain.is_block_acceptance_paused() {
+			return StageOutcome::InProgress(SyncResponse::new(
+				SyncRequestResponses::WaitingForDiskSpace,
+				Capabilities::UNKNOWN,
+				"Blocks are not accepted, free disk space is below the floor".into(),
+			));
+		}
+
+		let archive_height = Chain::height_2_archive_height(ctx.best_height);
+		if self.chain.archive_mode()
+			|| archive_height == 0
+			|| self.checked_height.load(Ordering::Relaxed) == archive_height
+		{
+			return StageOutcome::Ready;
+		}
+		match self.chain.head_height() {
+			Ok(height) if height < archive_height => {}
+			// state is not needed, or the state stage reports the chain error
+			_ => return StageOutcome::Ready,
+		}
+		// State stage waits for the archive header
+		let archive_header = match self.chain.get_header_by_height(archive_height) {
+			Ok(header) => header,
+			Err(_) => return StageOutcome::Ready,
+		};
+		match self.disk_monitor.check_state_download(&archive_header) {
+			Ok(_) => {
+				self.checked_height.store(archive_height, Ordering::Relaxed);
+				StageOutcome::Ready
+			}
+			Err(e) => StageOutcome::InProgress(SyncResponse::new(
+				SyncRequestResponses::WaitingForDiskSpace,
+				Capabilities::UNKNOWN,
+				e,
+			)),
+		}
+	}
+}
+
+/// Download of the txhashset state at the archive height, with PIBD or the txhashset archive
+pub struct StateStage<P = Arc<Peers>> {
+	pub state: Arc<dyn StateRequests<P>>,
+	pub txhashset: Arc<dyn StateRequests<P>>,
+	pub method: Arc<StateMethodSelector>,
+	pub sync_peers: Arc<SyncPeers>,
+}
+
+impl<P> SyncStage<P> for StateStage<P> {
+	fn name(&self) -> &'static str {
+		"state"
+	}
+
+	fn request(&self, ctx: &mut SyncContext<P>) -> StageOutcome {
+		let method = self.method.method();
+		let requests = match method {
+			StateSyncMethod::Pibd => &self.state,
+			StateSyncMethod::TxHashsetArchive => &self.txhashset,
+		};
+		let resp = requests.request(ctx, &self.sync_peers);
+		debug!("state_resp ({:?}): {:?}", method, resp);
+		if self.method.report(&resp) {
+			// Data of the previous method is dropped, so it starts clean if we switch back
+			requests.reset();
+		}
+		match resp.response {
+			SyncRequestResponses::Syncing
+			| SyncRequestResponses::WaitingForPeers
+			| SyncRequestResponses::WaitingForHeaders => StageOutcome::InProgress(resp),
+			SyncRequestResponses::StatePibdReady => StageOutcome::Ready,
+			_ => unexpected(self.name(), &resp),
+		}
+	}
+}
+
+/// Full blocks download above the state
+pub struct BodyStage<P = Arc<Peers>> {
+	pub body: Arc<dyn BodyRequests<P>>,
+	pub state: Arc<dyn StateRequests<P>>,
+	pub sync_peers: Arc<SyncPeers>,
+}
+
+impl<P> SyncStage<P> for BodyStage<P> {
+	fn name(&self) -> &'static str {
+		"body"
+	}
+
+	fn request(&self, ctx: &mut SyncContext<P>) -> StageOutcome {
+		let resp = match self.body.request(ctx, &self.sync_peers) {
+			Ok(resp) => resp,
+			Err(e) => {
+				error!("Body request is failed, {}", e);
+				return StageOutcome::InProgress(SyncResponse::new(
+					SyncRequestResponses::Syncing,
+					Capabilities::UNKNOWN,
+					format!("Body request is failed, {}", e),
+				));
+			}
+		};
+		debug!("body_resp: {:?}", resp);
+		match resp.response {
+			SyncRequestResponses::Syncing | SyncRequestResponses::WaitingForPeers => {
+				StageOutcome::InProgress(resp)
+			}
+			SyncRequestResponses::BodyReady => StageOutcome::Ready,
+			SyncRequestResponses::BadState => {
+				self.state.reset();
+				StageOutcome::InProgress(resp)
+			}
+			_ => unexpected(self.name(), &resp),
+		}
+	}
+}
+
+impl HeadersHashRequests<Arc<Peers>> for RwLock<HeadersHashSync> {
+	fn request(&self, ctx: &SyncContext, sync_peers: &SyncPeers) -> SyncResponse {
+		let r = self.read().request_pre(ctx.best_height);
+		match r {
+			Some(resp) => resp,
+			None => {
+				self.write()
+					.request_impl(ctx.peers, ctx.sync_state, sync_peers, ctx.best_height)
+			}
+		}
+	}
+}
+
+impl StateRequests<Arc<Peers>> for StateSync {
+	fn request(&self, ctx: &SyncContext, sync_peers: &SyncPeers) -> SyncResponse {
+		StateSync::request(
+			self,
+			ctx.peers,
+			ctx.sync_state.clone(),
+			sync_peers,
+			ctx.stop_state.clone(),
+			ctx.best_height,
+		)
+	}
+
+	fn reset(&self) {
+		self.reset_desegmenter_data();
+	}
+}
+
+impl StateRequests<Arc<Peers>> for TxHashsetSync {
+	fn request(&self, ctx: &SyncContext, sync_peers: &SyncPeers) -> SyncResponse {
+		TxHashsetSync::request(
+			self,
+			ctx.peers,
+			ctx.sync_state.clone(),
+			sync_peers,
+			ctx.stop_state.clone(),
+			ctx.best_height,
+		)
+	}
+
+	fn reset(&self) {
+		TxHashsetSync::reset(self);
+	}
+}
+
+impl BodyRequests<Arc<Peers>> for BodySync {
+	fn request(
+		&self,
+		ctx: &SyncContext,
+		sync_peers: &SyncPeers,
+	) -> Result<SyncResponse, chain::Error> {
+		BodySync::request(self, ctx.peers, ctx.sync_state, sync_peers, ctx.best_height)
+	}
+}
+
+impl StageChain for Chain {
+	fn is_block_acceptance_paused(&self) -> bool {
+		Chain::is_block_acceptance_paused(self)
+	}
+
+	fn archive_mode(&self) -> bool {
+		Chain::archive_mode(self)
+	}
+
+	fn head_height(&self) -> Result<u64, chain::Error> {
+		Ok(self.head()?.height)
+	}
+
+	fn get_header_by_height(&self, height: u64) -> Result<BlockHeader, chain::Error> {
+		Chain::get_header_by_height(self, height)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use mwc_p2p::store::PeerStore;
+	use mwc_p2p::{DummyAdapter, P2PConfig};
+	use std::collections::VecDeque;
+	use std::sync::Mutex;
+
+	// Stage that returns the scripted outcomes, Ready when the script is over
+	struct ScriptedStage {
+		name: &'static str,
+		outcomes: Mutex<VecDeque<StageOutcome>>,
+		calls: AtomicU64,
+		headers_ready: bool,
+	}
+
+	impl ScriptedStage {
+		// `headers_ready` stage reports the headers state to the context like the headers stage
+		fn new(
+			name: &'static str,
+			headers_ready: bool,
+			outcomes: Vec<StageOutcome>,
+		) -> Arc<ScriptedStage> {
+			Arc::new(ScriptedStage {
+				name,
+				outcomes: Mutex::new(outcomes.into()),
+				calls: AtomicU64::new(0),
+				headers_ready,
+			})
+		}
+
+		fn calls(&self) -> u64 {
+			self.calls.load(Ordering::Relaxed)
+		}
+	}
+
+	impl SyncStage for Arc<ScriptedStage> {
+		fn name(&self) -> &'static str {
+			self.name
+		}
+
+		fn request(&self, ctx: &mut SyncContext) -> StageOutcome {
+			self.calls.fetch_add(1, Ordering::Relaxed);
+			let outcome = self
+				.outcomes
+				.lock()
+				.unwrap()
+				.pop_front()
+				.unwrap_or(StageOutcome::Ready);
+			if self.headers_ready {
+				ctx.headers_ready = matches!(outcome, StageOutcome::Ready);
+			}
+			outcome
+		}
+	}
+
+	fn syncing() -> StageOutcome {
+		StageOutcome::InProgress(SyncResponse::new(
+			SyncRequestResponses::Syncing,
+			Capabilities::UNKNOWN,
+			"syncing".into(),
+		))
+	}
+
+	#[test]
+	fn test_stage_transitions() {
+		let dir = tempfile::tempdir().unwrap();
+		let stop_state = Arc::new(StopState::new());
+		let sync_state = Arc::new(SyncState::new());
+		let peers = Arc::new(Peers::new(
+			PeerStore::new(dir.path().to_str().unwrap()).unwrap(),
+			Arc::new(DummyAdapter {}),
+			P2PConfig::default(),
+			stop_state.clone(),
+		));
+
+		let headers = ScriptedStage::new("headers", true, vec![syncing()]);
+		let state = ScriptedStage::new("state", false, vec![syncing(), syncing()]);
+		let body = ScriptedStage::new("body", false, vec![]);
+		let stages: Vec<Box<dyn SyncStage>> = vec![
+			Box::new(headers.clone()),
+			Box::new(state.clone()),
+			Box::new(body.clone()),
+		];
+
+		let mut run = || {
+			let mut ctx = SyncContext {
+				peers: &peers,
+				sync_state: &sync_state,
+				stop_state: &stop_state,
+				best_height: 100,
+				headers_ready: false,
+			};
+			let res = run_stages(&stages, &mut ctx).map(|(stage, resp)| {
+				assert_eq!(resp.response, SyncRequestResponses::Syncing);
+				stage
+			});
+			(res, ctx.headers_ready)
+		};
+
+		// Stage in progress stops the pipeline, the next stages are not requested
+		assert_eq!(run(), (Some("headers"), false));
+		assert_eq!((headers.calls(), state.calls(), body.calls()), (1, 0, 0));
+
+		// Ready stage passes to the next one, every request starts from the first stage
+		assert_eq!(run(), (Some("state"), true));
+		assert_eq!(run(), (Some("state"), true));
+		assert_eq!((headers.calls(), state.calls(), body.calls()), (3, 2, 0));
+
+		// All stages are ready
+		assert_eq!(run(), (None, true));
+		assert_eq!((headers.calls(), state.calls(), body.calls()), (4, 3, 1));
+	}
+
+	// Sync module that returns the scripted responses, the last one is repeated
+	struct ScriptedRequests {
+		responses: Mutex<VecDeque<SyncRequestResponses>>,
+		calls: AtomicU64,
+		resets: AtomicU64,
+	}
+
+	impl ScriptedRequests {
+		fn new(responses: Vec<SyncRequestResponses>) -> Arc<ScriptedRequests> {
+			Arc::new(ScriptedRequests {
+				responses: Mutex::new(responses.into()),
+				calls: AtomicU64::new(0),
+				resets: AtomicU64::new(0),
+			})
+		}
+
+		fn next(&self) -> SyncResponse {
+			self.calls.fetch_add(1, Ordering::Relaxed);
+			let mut responses = self.responses.lock().unwrap();
+			let response = if responses.len() > 1 {
+				responses.pop_front().unwrap()
+			} else {
+				responses.front().cloned().unwrap()
+			};
+			SyncResponse::new(response, Capabilities::UNKNOWN, "scripted".into())
+		}
+
+		fn calls(&self) -> u64 {
+			self.calls.load(Ordering::Relaxed)
+		}
+
+		fn resets(&self) -> u64 {
+			self.resets.load(Ordering::Relaxed)
+		}
+	}
+
+	impl HeadersHashRequests<()> for ScriptedRequests {
+		fn request(&self, _ctx: &SyncContext<()>, _sync_peers: &SyncPeers) -> SyncResponse {
+			self.next()
+		}
+	}
+
+	impl StateRequests<()> for ScriptedRequests {
+		fn request(&self, _ctx: &SyncContext<()>, _sync_peers: &SyncPeers) -> SyncResponse {
+			self.next()
+		}
+
+		fn reset(&self) {
+			self.resets.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	impl BodyRequests<()> for ScriptedRequests {
+		fn request(
+			&self,
+			_ctx: &SyncContext<()>,
+			_sync_peers: &SyncPeers,
+		) -> Result<SyncResponse, chain::Error> {
+			let resp = self.next();
+			match resp.response {
+				// Stands for the chain failure
+				SyncRequestResponses::WaitingForHeaders => {
+					Err(chain::Error::Other("scripted".to_string()))
+				}
+				_ => Ok(resp),
+			}
+		}
+	}
+
+	// Run a single stage without peers, returns the response if it is in progress
+	fn request_stage(stage: &dyn SyncStage<()>) -> Option<SyncRequestResponses> {
+		let sync_state = Arc::new(SyncState::new());
+		let stop_state = Arc::new(StopState::new());
+		let mut ctx = SyncContext {
+			peers: &(),
+			sync_state: &sync_state,
+			stop_state: &stop_state,
+			best_height: 100,
+			headers_ready: false,
+		};
+		match stage.request(&mut ctx) {
+			StageOutcome::InProgress(resp) => Some(resp.response),
+			StageOutcome::Ready => None,
+		}
+	}
+
+	#[test]
+	fn test_headers_hash_stage() {
+		let hashes = ScriptedRequests::new(vec![
+			SyncRequestResponses::WaitingForPeers,
+			SyncRequestResponses::Syncing,
+			SyncRequestResponses::HeadersHashReady,
+			SyncRequestResponses::HeadersPibdReady,
+		]);
+		let stage = HeadersHashStage::<()> {
+			headers_hashes: hashes.clone(),
+			sync_peers: Arc::new(SyncPeers::new()),
+		};
+		assert_eq!(
+			request_stage(&stage),
+			Some(SyncRequestResponses::WaitingForPeers)
+		);
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::Syncing));
+		assert_eq!(request_stage(&stage), None);
+		assert_eq!(request_stage(&stage), None);
+		assert_eq!(hashes.calls(), 4);
+	}
+
+	#[test]
+	fn test_state_stage() {
+		let pibd = ScriptedRequests::new(vec![
+			SyncRequestResponses::WaitingForHeaders,
+			SyncRequestResponses::Syncing,
+			SyncRequestResponses::StatePibdReady,
+		]);
+		let archive = ScriptedRequests::new(vec![SyncRequestResponses::Syncing]);
+		let stage = StateStage::<()> {
+			state: pibd.clone(),
+			txhashset: archive.clone(),
+			method: Arc::new(StateMethodSelector::new()),
+			sync_peers: Arc::new(SyncPeers::new()),
+		};
+		assert_eq!(
+			request_stage(&stage),
+			Some(SyncRequestResponses::WaitingForHeaders)
+		);
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::Syncing));
+		assert_eq!(request_stage(&stage), None);
+		assert_eq!((pibd.calls(), archive.calls()), (3, 0));
+		assert_eq!(pibd.resets(), 0);
+
+		// No PIBD peers for too long, the node switches to the txhashset archive and
+		// the PIBD data is dropped
+		let pibd = ScriptedRequests::new(vec![SyncRequestResponses::WaitingForPeers]);
+		let stage = StateStage::<()> {
+			state: pibd.clone(),
+			txhashset: archive.clone(),
+			method: Arc::new(StateMethodSelector::new()),
+			sync_peers: Arc::new(SyncPeers::new()),
+		};
+		let mut requests = 0;
+		while pibd.resets() == 0 {
+			assert_eq!(
+				request_stage(&stage),
+				Some(SyncRequestResponses::WaitingForPeers)
+			);
+			requests += 1;
+			assert!(requests < 1000);
+		}
+		assert_eq!(stage.method.method(), StateSyncMethod::TxHashsetArchive);
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::Syncing));
+		assert_eq!((pibd.calls(), archive.calls()), (requests, 1));
+		assert_eq!(pibd.resets(), 1);
+		assert_eq!(archive.resets(), 0);
+	}
+
+	#[test]
+	fn test_body_stage() {
+		let body = ScriptedRequests::new(vec![
+			SyncRequestResponses::WaitingForHeaders,
+			SyncRequestResponses::Syncing,
+			SyncRequestResponses::BadState,
+			SyncRequestResponses::BodyReady,
+		]);
+		let state = ScriptedRequests::new(vec![SyncRequestResponses::Syncing]);
+		let stage = BodyStage::<()> {
+			body: body.clone(),
+			state: state.clone(),
+			sync_peers: Arc::new(SyncPeers::new()),
+		};
+		// Failed request keeps the pipeline syncing
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::Syncing));
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::Syncing));
+		assert_eq!(state.resets(), 0);
+		// Bad state is dropped, so the state stage downloads it again
+		assert_eq!(request_stage(&stage), Some(SyncRequestResponses::BadState));
+		assert_eq!(state.resets(), 1);
+		assert_eq!(request_stage(&stage), None);
+		assert_eq!((body.calls(), state.calls()), (4, 0));
+	}
+}
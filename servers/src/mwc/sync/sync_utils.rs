@@ -19,6 +19,7 @@ use crate::mwc::sync::sync_peers::SyncPeers;
 use chrono::{DateTime, Duration, Utc};
 use mwc_chain::txhashset::request_lookup::RequestLookup;
 use mwc_chain::{pibd_params, Chain};
+use mwc_p2p::bandwidth::TrafficClass;
 use mwc_p2p::{Capabilities, Peer, PeerAddr, Peers};
 use mwc_util::RwLock;
 use std::cmp;
@@ -39,6 +40,7 @@ pub enum SyncRequestResponses {
 	StatePibdReady,
 	BadState, // need update state, probably horizon was changed, need to retry
 	BodyReady,
+	WaitingForDiskSpace,
 	SyncDone,
 }
 
@@ -95,6 +97,11 @@ impl PeerTrackData {
 	fn new(requests: u32) -> Self {
 		PeerTrackData { requests }
 	}
+
+	/// Number of the requests that are waiting for the response
+	pub fn requests(&self) -> u32 {
+		self.requests
+	}
 }
 
 pub struct RequestData {
@@ -399,5 +406,11 @@ pub fn get_sync_peers<T: std::cmp::Eq + std::hash::Hash>(
 			}
 		}
 	}
+	// Sync budget is exhausted, new requests would eat into the relay reserve. Peers are
+	// reported as excluded, so the callers keep syncing instead of waiting for the new peers.
+	if !res.is_empty() && !peers.bandwidth().allowed(TrafficClass::Sync) {
+		excluded_peers += res.len() as u32;
+		res.clear();
+	}
 	(res, excluded_requests as u32, excluded_peers)
 }
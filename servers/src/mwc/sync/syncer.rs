@@ -14,10 +14,11 @@
 // limitations under the License.
 
 use crate::chain::{self, SyncState, SyncStatus};
+use crate::mwc::sync::stale_tip::StaleTipMonitor;
 use crate::mwc::sync::sync_manager::SyncManager;
 use crate::mwc::sync::sync_utils::SyncRequestResponses;
 use crate::p2p;
-use crate::util::StopState;
+use crate::util::{RwLock, StopState};
 use chrono::Utc;
 use mwc_p2p::{Capabilities, Peer};
 use std::sync::Arc;
@@ -30,11 +31,19 @@ pub fn run_sync(
 	chain: Arc<chain::Chain>,
 	stop_state: Arc<StopState>,
 	sync_manager: Arc<SyncManager>,
+	stale_tip_timeout_minutes: u64,
 ) -> std::io::Result<std::thread::JoinHandle<()>> {
 	thread::Builder::new()
 		.name("sync".to_string())
 		.spawn(move || {
-			let runner = SyncRunner::new(sync_state, peers, chain, stop_state, sync_manager);
+			let runner = SyncRunner::new(
+				sync_state,
+				peers,
+				chain,
+				stop_state,
+				sync_manager,
+				stale_tip_timeout_minutes,
+			);
 			runner.sync_loop();
 		})
 }
@@ -45,6 +54,7 @@ pub struct SyncRunner {
 	chain: Arc<chain::Chain>,
 	stop_state: Arc<StopState>,
 	sync_manager: Arc<SyncManager>,
+	stale_tip: RwLock<StaleTipMonitor>,
 }
 
 impl SyncRunner {
@@ -54,6 +64,7 @@ impl SyncRunner {
 		chain: Arc<chain::Chain>,
 		stop_state: Arc<StopState>,
 		sync_manager: Arc<SyncManager>,
+		stale_tip_timeout_minutes: u64,
 	) -> SyncRunner {
 		SyncRunner {
 			sync_state,
@@ -61,6 +72,7 @@ impl SyncRunner {
 			chain,
 			stop_state,
 			sync_manager,
+			stale_tip: RwLock::new(StaleTipMonitor::new(stale_tip_timeout_minutes)),
 		}
 	}
 
@@ -126,6 +138,8 @@ impl SyncRunner {
 			thread::sleep(time::Duration::from_millis(sleep_time));
 
 			self.sync_manager.headers_blocks_request(&self.peers);
+			self.sync_manager
+				.process_restart_request(&self.chain, &self.peers);
 
 			// Onle in a while let's dump the peers. Needed to understand how network is doing
 			let now = Utc::now();
@@ -146,6 +160,16 @@ impl SyncRunner {
 				}
 			}
 
+			// Recover if the tip is stuck while peers have more work
+			if let Err(e) = self.stale_tip.write().check(
+				&self.chain,
+				&self.sync_state,
+				&self.peers,
+				&self.sync_manager,
+			) {
+				error!("Stale tip check is failed. Error: {}", e);
+			}
+
 			// run each sync stage, each of them deciding whether they're needed
 			// except for state sync that only runs if body sync return true (means txhashset is needed)
 			let sync_reponse = self.sync_manager.sync_request(&self.peers);
@@ -165,6 +189,11 @@ impl SyncRunner {
 					self.peers
 						.set_boost_peers_capabilities(sync_reponse.peers_capabilities);
 				}
+				SyncRequestResponses::WaitingForDiskSpace => {
+					debug!("Waiting for the disk space, {}", sync_reponse.message);
+					self.peers
+						.set_boost_peers_capabilities(Capabilities::UNKNOWN);
+				}
 				SyncRequestResponses::HasMoreHeadersToApply => {
 					debug!("Has more headers to apply, will continue soon");
 					sleep_time = 100;
@@ -175,17 +204,27 @@ impl SyncRunner {
 					self.peers
 						.set_boost_peers_capabilities(Capabilities::UNKNOWN);
 
-					if let Err(e) = self.chain.compact() {
-						error!("Compact chain is failed. Error: {}", e);
+					if !self.chain.header_only() {
+						if let Err(e) = self.chain.compact() {
+							error!("Compact chain is failed. Error: {}", e);
+						}
+					}
+					match self.chain.history_depth() {
+						Ok(depth) => self.peers.set_history_depth(depth),
+						Err(e) => error!("Unable to get blocks history depth. Error: {}", e),
 					}
 
 					for _ in 0..20 {
-						if !self.stop_state.is_stopped() {
+						// The requested restart doesn't wait for the synced node pause
+						if !self.stop_state.is_stopped()
+							&& !self.sync_manager.is_restart_requested()
+						{
 							thread::sleep(time::Duration::from_secs(1));
 							// Processing regular headers/blocks requests.
 							// Every second we will fire the requests to headers/blocks from the queue
 							// Purpose of that to prevent data requests flooding.
 							self.sync_manager.headers_blocks_request(&self.peers);
+							self.sync_manager.backfill_history(&self.peers);
 						}
 					}
 				}
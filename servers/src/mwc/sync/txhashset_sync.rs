@@ -0,0 +1,463 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Legacy txhashset sync. The full txhashset zip archive at the archive height is
+//! downloaded from a single peer and validated as a whole. It is a fallback for the
+//! PIBD, the old network segments might not have enough PIBD peers.
+
+use crate::chain::{self, Chain, SyncEventKind, SyncState, SyncStatus, TxHashsetDownloadStats};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::mwc::sync::sync_peers::SyncPeers;
+use crate::mwc::sync::sync_utils::{SyncRequestResponses, SyncResponse};
+use crate::p2p::{self, Capabilities, Peer, PeerAddr};
+use crate::util::{RwLock, StopState};
+use chrono::prelude::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The archive download is dropped if there is no data from the peer for that long.
+/// Peer might need some time to build the archive, so it is longer than the PIBD timeout.
+const TXHASHSET_DOWNLOAD_TIMEOUT_SECS: i64 = 180;
+/// Download progress is reported into the sync status not more often than that
+const TXHASHSET_STATUS_UPDATE_SECS: i64 = 1;
+/// Number of times the interrupted download is resumed from the same peer
+const TXHASHSET_MAX_RESUMES: u32 = 3;
+
+struct ArchiveRequest {
+	peer: PeerAddr,
+	hash: Hash,
+	height: u64,
+	// last time we get something from the peer
+	last_update: DateTime<Utc>,
+	stats: TxHashsetDownloadStats,
+	// the connection to the peer was broken during the download
+	interrupted: bool,
+	resumes: u32,
+}
+
+pub struct TxHashsetSync {
+	chain: Arc<Chain>,
+	request: RwLock<Option<ArchiveRequest>>,
+	// Downloaded archive that is waiting for the validation
+	received: RwLock<Option<(PeerAddr, Hash, PathBuf)>>,
+	// Peers that failed to provide the archive, they are not requested again until the reset
+	excluded_peers: RwLock<HashSet<PeerAddr>>,
+}
+
+impl TxHashsetSync {
+	pub fn new(chain: Arc<Chain>) -> TxHashsetSync {
+		TxHashsetSync {
+			chain,
+			request: RwLock::new(None),
+			received: RwLock::new(None),
+			excluded_peers: RwLock::new(HashSet::new()),
+		}
+	}
+
+	fn get_peer_capabilities() -> Capabilities {
+		Capabilities::TXHASHSET_HIST
+	}
+
+	pub fn request(
+		&self,
+		in_peers: &Arc<p2p::Peers>,
+		sync_state: Arc<SyncState>,
+		sync_peers: &SyncPeers,
+		stop_state: Arc<StopState>,
+		best_height: u64,
+	) -> SyncResponse {
+		// In case of archive mode, this step is must be skipped. Body sync will catch up.
+		if self.chain.archive_mode() {
+			return SyncResponse::new(
+				SyncRequestResponses::StatePibdReady,
+				Capabilities::UNKNOWN,
+				"".into(),
+			);
+		}
+
+		let target_archive_height = Chain::height_2_archive_height(best_height);
+		if target_archive_height == 0 {
+			return SyncResponse::new(
+				SyncRequestResponses::WaitingForPeers,
+				Self::get_peer_capabilities(),
+				format!(
+					"best_height={}  target_archive_height={}",
+					best_height, target_archive_height
+				),
+			);
+		}
+
+		if let Ok(head) = self.chain.head() {
+			if head.height >= target_archive_height {
+				self.reset();
+				return SyncResponse::new(
+					SyncRequestResponses::StatePibdReady,
+					Capabilities::UNKNOWN,
+					format!(
+						"head.height={}  target_archive_height={}",
+						head.height, target_archive_height
+					),
+				);
+			}
+		}
+
+		let archive_header = match self.chain.get_header_by_height(target_archive_height) {
+			Ok(archive_header) => archive_header,
+			Err(_) => {
+				return SyncResponse::new(
+					SyncRequestResponses::WaitingForHeaders,
+					Self::get_peer_capabilities(),
+					format!("Header at height {} doesn't exist", target_archive_height),
+				);
+			}
+		};
+
+		// Downloaded archive, validating it. It is a long operation, the sync is blocked until it is done.
+		let received = self.received.write().take();
+		if let Some((peer, hash, path)) = received {
+			*self.request.write() = None;
+			let res = if hash == archive_header.hash() {
+				File::open(&path)
+					.map_err(chain::Error::from)
+					.and_then(|file| {
+						self.chain.txhashset_write(
+							hash,
+							file,
+							sync_state.clone(),
+							stop_state.clone(),
+						)
+					})
+			} else {
+				Err(chain::Error::Unfit(format!(
+					"archive header is changed to {} at {}",
+					archive_header.hash(),
+					archive_header.height
+				)))
+			};
+			if let Err(e) = fs::remove_file(&path) {
+				warn!(
+					"Unable to remove txhashset archive {}, {}",
+					path.display(),
+					e
+				);
+			}
+			return match res {
+				Ok(_) => {
+					info!(
+						"Txhashset archive from {} is downloaded and validated with success!",
+						peer
+					);
+					sync_peers.report_ok_response(&peer);
+					self.excluded_peers.write().clear();
+					SyncResponse::new(
+						SyncRequestResponses::StatePibdReady,
+						Capabilities::UNKNOWN,
+						"Txhashset archive download and validation is done with success!".into(),
+					)
+				}
+				Err(e) => {
+					let msg = format!("Txhashset archive from {} is rejected, {}", peer, e);
+					error!("{}", msg);
+					if e.is_bad_data() && !stop_state.is_stopped() {
+						sync_peers.report_error_response(&peer, msg.clone());
+					}
+					self.excluded_peers.write().insert(peer);
+					SyncResponse::new(
+						SyncRequestResponses::Syncing,
+						Self::get_peer_capabilities(),
+						msg,
+					)
+				}
+			};
+		}
+
+		let now = Utc::now();
+
+		// Download in progress
+		if let Some(request) = self.request.write().as_mut() {
+			if request.hash == archive_header.hash() {
+				if let Some(resp) = self.check_interrupted(request, in_peers, sync_state.clone()) {
+					return resp;
+				}
+				if (now - request.last_update).num_seconds() < TXHASHSET_DOWNLOAD_TIMEOUT_SECS {
+					return SyncResponse::new(
+						SyncRequestResponses::Syncing,
+						Self::get_peer_capabilities(),
+						format!(
+							"Downloading txhashset archive from {}, {} of {} bytes",
+							request.peer, request.stats.downloaded_size, request.stats.total_size
+						),
+					);
+				}
+			}
+		}
+
+		// Timed out or outdated request
+		if let Some(request) = self.request.write().take() {
+			self.remove_partial_archive(&request.hash);
+			if request.hash == archive_header.hash() {
+				let msg = format!("txhashset archive download from {}", request.peer);
+				sync_state.add_event(
+					SyncEventKind::RequestsTimedOut,
+					vec![request.peer.to_string()],
+					"Txhashset archive download is timed out, retrying with another peer".into(),
+				);
+				sync_peers.report_no_response(&request.peer, msg);
+				self.excluded_peers.write().insert(request.peer);
+			}
+		}
+
+		// Peers that serve the same archive. Archive height depends on the peer height.
+		let peers: Vec<Arc<Peer>> = {
+			let excluded_peers = self.excluded_peers.read();
+			in_peers
+				.iter()
+				.with_capabilities(Self::get_peer_capabilities())
+				.connected()
+				.into_iter()
+				.filter(|p| {
+					Chain::height_2_archive_height(p.info.height()) == target_archive_height
+						&& !excluded_peers.contains(&p.info.addr)
+				})
+				.collect()
+		};
+
+		let peer = match peers.choose(&mut rand::thread_rng()) {
+			Some(peer) => peer,
+			None => {
+				// Excluded peers get another try after the reset
+				return SyncResponse::new(
+					SyncRequestResponses::WaitingForPeers,
+					Self::get_peer_capabilities(),
+					"No peers that can provide txhashset archive".into(),
+				);
+			}
+		};
+
+		match peer.send_txhashset_request(archive_header.height, archive_header.hash()) {
+			Ok(_) => {
+				sync_state.add_event(
+					SyncEventKind::PeerSelected,
+					vec![peer.info.addr.to_string()],
+					format!(
+						"Txhashset archive for {} at {} is requested",
+						archive_header.hash(),
+						archive_header.height
+					),
+				);
+				let stats = TxHashsetDownloadStats::default();
+				sync_state.update(SyncStatus::TxHashsetDownload(stats));
+				*self.request.write() = Some(ArchiveRequest {
+					peer: peer.info.addr.clone(),
+					hash: archive_header.hash(),
+					height: archive_header.height,
+					last_update: now,
+					stats,
+					interrupted: false,
+					resumes: 0,
+				});
+				SyncResponse::new(
+					SyncRequestResponses::Syncing,
+					Self::get_peer_capabilities(),
+					format!("Txhashset archive is requested from {}", peer.info.addr),
+				)
+			}
+			Err(e) => {
+				let msg = format!(
+					"Failed to send txhashset request to peer {}, {}",
+					peer.info.addr, e
+				);
+				warn!("{}", msg);
+				sync_peers.report_no_response(&peer.info.addr, msg.clone());
+				self.excluded_peers.write().insert(peer.info.addr.clone());
+				SyncResponse::new(
+					SyncRequestResponses::Syncing,
+					Self::get_peer_capabilities(),
+					msg,
+				)
+			}
+		}
+	}
+
+	// The download is resumed with a range request when the peer is connected again after
+	// the connection was broken. The archive zip is built by the peer, so the rest must be
+	// downloaded from the same peer. Returns the response if the request is resumed.
+	fn check_interrupted(
+		&self,
+		request: &mut ArchiveRequest,
+		in_peers: &Arc<p2p::Peers>,
+		sync_state: Arc<SyncState>,
+	) -> Option<SyncResponse> {
+		let peer = match in_peers.get_connected_peer(&request.peer) {
+			Some(peer) => peer,
+			None => {
+				if !request.interrupted {
+					info!(
+						"Txhashset archive download from {} is interrupted at {} of {} bytes",
+						request.peer, request.stats.downloaded_size, request.stats.total_size
+					);
+					request.interrupted = true;
+				}
+				return None;
+			}
+		};
+		if !request.interrupted || request.resumes >= TXHASHSET_MAX_RESUMES {
+			return None;
+		}
+		let offset = self.partial_archive_size(&request.hash);
+		if offset == 0 {
+			return None;
+		}
+		match peer.send_txhashset_range_request(request.height, request.hash, offset) {
+			Ok(_) => {
+				sync_state.add_event(
+					SyncEventKind::PeerSelected,
+					vec![request.peer.to_string()],
+					format!(
+						"Txhashset archive download for {} is resumed from {} bytes",
+						request.hash, offset
+					),
+				);
+				request.interrupted = false;
+				request.resumes += 1;
+				request.last_update = Utc::now();
+				Some(SyncResponse::new(
+					SyncRequestResponses::Syncing,
+					Self::get_peer_capabilities(),
+					format!(
+						"Txhashset archive download from {} is resumed from {} bytes",
+						request.peer, offset
+					),
+				))
+			}
+			Err(e) => {
+				warn!(
+					"Failed to send txhashset range request to peer {}, {}",
+					request.peer, e
+				);
+				None
+			}
+		}
+	}
+
+	fn partial_archive_path(&self, hash: &Hash) -> PathBuf {
+		self.chain
+			.get_tmp_dir()
+			.join(format!("txhashset-{}.zip", hash))
+	}
+
+	/// Size of the partially downloaded archive, 0 if there is nothing to resume
+	fn partial_archive_size(&self, hash: &Hash) -> u64 {
+		fs::metadata(self.partial_archive_path(hash))
+			.map(|m| m.len())
+			.unwrap_or(0)
+	}
+
+	fn remove_partial_archive(&self, hash: &Hash) {
+		let path = self.partial_archive_path(hash);
+		if path.exists() {
+			if let Err(e) = fs::remove_file(&path) {
+				warn!(
+					"Unable to remove txhashset archive {}, {}",
+					path.display(),
+					e
+				);
+			}
+		}
+	}
+
+	/// Check if the archive from the peer is the one that we requested
+	pub fn receive_ready(&self, peer: &PeerAddr, header_hash: Hash, header_height: u64) -> bool {
+		match &*self.request.read() {
+			Some(request) => {
+				request.peer == *peer
+					&& request.hash == header_hash
+					&& request.height == header_height
+			}
+			None => false,
+		}
+	}
+
+	pub fn download_update(
+		&self,
+		peer: &PeerAddr,
+		downloaded_size: u64,
+		total_size: u64,
+		sync_state: &SyncState,
+	) {
+		let mut request = self.request.write();
+		if let Some(request) = request.as_mut() {
+			if request.peer != *peer {
+				return;
+			}
+			let now = Utc::now();
+			request.last_update = now;
+			let stats = &mut request.stats;
+			if (now - stats.update_time).num_seconds() >= TXHASHSET_STATUS_UPDATE_SECS
+				|| downloaded_size == total_size
+			{
+				stats.prev_update_time = stats.update_time;
+				stats.prev_downloaded_size = stats.downloaded_size;
+				stats.update_time = now;
+				stats.downloaded_size = downloaded_size;
+				stats.total_size = total_size;
+				sync_state.update(SyncStatus::TxHashsetDownload(*stats));
+			}
+		}
+	}
+
+	pub fn receive_archive(
+		&self,
+		peer: &PeerAddr,
+		header_hash: Hash,
+		header_height: u64,
+		path: PathBuf,
+	) {
+		if self.receive_ready(peer, header_hash, header_height) {
+			*self.received.write() = Some((peer.clone(), header_hash, path));
+		} else {
+			warn!(
+				"Txhashset archive from {} is not expected any more, dropping it",
+				peer
+			);
+			if let Err(e) = fs::remove_file(&path) {
+				warn!(
+					"Unable to remove txhashset archive {}, {}",
+					path.display(),
+					e
+				);
+			}
+		}
+	}
+
+	/// Drop the download, the archive that is in progress will not be accepted
+	pub fn reset(&self) {
+		if let Some(request) = self.request.write().take() {
+			self.remove_partial_archive(&request.hash);
+		}
+		if let Some((_, _, path)) = self.received.write().take() {
+			if let Err(e) = fs::remove_file(&path) {
+				warn!(
+					"Unable to remove txhashset archive {}, {}",
+					path.display(),
+					e
+				);
+			}
+		}
+		self.excluded_peers.write().clear();
+	}
+}
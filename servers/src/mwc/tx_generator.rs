@@ -0,0 +1,677 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synthetic transactions generator for the load testing of the Dandelion, pool
+//! eviction and broadcast paths. Developer mode, usernet and floonet only.
+//!
+//! The generator owns a random keychain. The local test miner pays the block rewards
+//! to it (instead of burning them), mature coinbase outputs are found by scanning
+//! the new blocks. Every generated transaction spends one of our outputs (confirmed
+//! or still in the pool) and splits it into two, so the number of spendable outputs
+//! grows with the load. Relay latency is measured from the moment of the injection
+//! until the connected peers announce our transaction back.
+
+use crate::chain::{self, SyncState};
+use crate::common::hooks::NetEvents;
+use crate::common::types::Error;
+use crate::core::consensus;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::{KernelFeatures, Transaction};
+use crate::core::global;
+use crate::core::libtx::{build, ProofBuilder};
+use crate::keychain::{ExtKeychain, Identifier, Keychain, SwitchCommitmentType};
+use crate::p2p::types::PeerAddr;
+use crate::pool;
+use crate::util::{Mutex, StopState};
+use crate::ServerTxPool;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Transactions that were not announced back during that time are not tracked any more
+const RELAY_TRACKING_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Interval for the generator stats report
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Output is not split if its halves would be smaller than that many fees
+const MIN_SPLIT_FEES: u64 = 10;
+
+// Output that the generator can spend
+struct SpendableOutput {
+	key_id: Identifier,
+	value: u64,
+	coinbase: bool,
+}
+
+// Relay latency of our transactions
+#[derive(Default)]
+struct LatencyStats {
+	count: u64,
+	total_ms: u64,
+	min_ms: u64,
+	max_ms: u64,
+}
+
+impl LatencyStats {
+	fn add(&mut self, latency: Duration) {
+		let ms = latency.as_millis() as u64;
+		if self.count == 0 || ms < self.min_ms {
+			self.min_ms = ms;
+		}
+		self.max_ms = self.max_ms.max(ms);
+		self.total_ms += ms;
+		self.count += 1;
+	}
+
+	fn avg_ms(&self) -> u64 {
+		if self.count == 0 {
+			0
+		} else {
+			self.total_ms / self.count
+		}
+	}
+}
+
+// Injected transaction that is waiting for the announcements from the peers
+struct RelayTracking {
+	injected: Instant,
+	announced_by: HashSet<PeerAddr>,
+}
+
+struct GeneratorState {
+	// last height that was checked for our coinbase outputs, the chain head at the
+	// first scan. Keychain is new, there is nothing for us in the older blocks.
+	scanned_height: Option<u64>,
+	outputs: VecDeque<SpendableOutput>,
+	next_key: u32,
+	// kernel hash -> tracking data
+	in_flight: HashMap<Hash, RelayTracking>,
+	generated: u64,
+	rejected: u64,
+	// latency of the first announcement from any peer
+	first_relay: LatencyStats,
+	// latency per peer
+	peer_relay: HashMap<PeerAddr, LatencyStats>,
+}
+
+/// Generates the synthetic transactions and measures their relay latency
+pub struct TxGenerator {
+	keychain: ExtKeychain,
+	state: Mutex<GeneratorState>,
+}
+
+impl TxGenerator {
+	/// New generator with a random keychain. Only our own future coinbase outputs
+	/// can fund it.
+	pub fn new() -> Result<TxGenerator, Error> {
+		let keychain = ExtKeychain::from_random_seed(global::is_floonet())?;
+		Ok(TxGenerator {
+			keychain,
+			state: Mutex::new(GeneratorState {
+				scanned_height: None,
+				outputs: VecDeque::new(),
+				next_key: 0,
+				in_flight: HashMap::new(),
+				generated: 0,
+				rejected: 0,
+				first_relay: LatencyStats::default(),
+				peer_relay: HashMap::new(),
+			}),
+		})
+	}
+
+	/// Keychain for the block rewards that fund the generator
+	pub fn reward_keychain(&self) -> ExtKeychain {
+		self.keychain.clone()
+	}
+
+	/// Key id of the block reward at the height, test miner should use it with
+	/// the reward keychain. The height takes two path levels, so every height has
+	/// its own key. Outputs of the generated transactions are at the depth 2, so the
+	/// keys never collide.
+	pub fn reward_key_id(height: u64) -> Identifier {
+		ExtKeychain::derive_key_id(
+			3,
+			1,
+			(height >> 32) as u32,
+			(height & 0xffff_ffff) as u32,
+			0,
+		)
+	}
+
+	/// Values of the outputs that the input is split into. The output is not split if
+	/// its halves would be smaller than MIN_SPLIT_FEES fees. Empty if the input can't
+	/// pay the fee.
+	fn split_values(value: u64, fee: u64) -> Vec<u64> {
+		if value <= fee {
+			return vec![];
+		}
+		let amount = value - fee;
+		let outputs_num = if amount >= 2 * MIN_SPLIT_FEES * fee {
+			2
+		} else {
+			1
+		};
+		let mut values = vec![amount / outputs_num; outputs_num as usize];
+		values[0] += amount % outputs_num;
+		values
+	}
+
+	/// Pool rejected the transaction because its input is spent or unknown, so the
+	/// input can't be used again.
+	fn is_input_gone(e: &pool::PoolError) -> bool {
+		match e {
+			pool::PoolError::MissingInputs(_)
+			| pool::PoolError::DuplicateKernelOrDuplicateSpent(_) => true,
+			_ => false,
+		}
+	}
+
+	/// Check the new blocks for our mature coinbase outputs
+	fn scan_coinbase(&self, chain: &chain::Chain) -> Result<(), Error> {
+		let head = chain.head()?;
+		let mature_height = head.height.saturating_sub(global::coinbase_maturity());
+		let scanned_height = self.state.lock().scanned_height;
+		let from_height = match scanned_height {
+			Some(h) => h + 1,
+			None => {
+				self.state.lock().scanned_height = Some(head.height);
+				return Ok(());
+			}
+		};
+		let mut found = vec![];
+		for height in from_height..=mature_height {
+			let header = chain.get_header_by_height(height)?;
+			let block = chain.get_block(&header.hash())?;
+			let value = consensus::reward(block.total_fees(), height);
+			let key_id = Self::reward_key_id(height);
+			let commit = self
+				.keychain
+				.commit(value, &key_id, SwitchCommitmentType::Regular)?;
+			let ours = block
+				.outputs()
+				.iter()
+				.any(|o| o.is_coinbase() && o.commitment() == commit);
+			if ours && chain.get_unspent(commit)?.is_some() {
+				found.push(SpendableOutput {
+					key_id,
+					value,
+					coinbase: true,
+				});
+			}
+		}
+
+		let mut state = self.state.lock();
+		if from_height <= mature_height {
+			state.scanned_height = Some(mature_height);
+		}
+		if !found.is_empty() {
+			info!(
+				"tx_generator: found {} mature coinbase outputs up to height {}",
+				found.len(),
+				mature_height
+			);
+			state.outputs.extend(found);
+		}
+		Ok(())
+	}
+
+	/// Build the transaction that spends one of our outputs and add it to the pool.
+	/// Returns None if there is nothing to spend. The input is returned back if the
+	/// transaction is not accepted, unless the pool says that the input is gone.
+	fn generate(
+		&self,
+		chain: &chain::Chain,
+		tx_pool: &ServerTxPool,
+		stem: bool,
+	) -> Result<Option<Hash>, Error> {
+		let fee = Transaction::weight_for_fee(1, 2, 1) * global::get_accept_fee_base();
+		let (input, out_values, out_keys) = {
+			let mut state = self.state.lock();
+			let (input, out_values) = loop {
+				match state.outputs.pop_front() {
+					Some(o) => {
+						let out_values = Self::split_values(o.value, fee);
+						if !out_values.is_empty() {
+							break (o, out_values);
+						}
+						// dust, can't pay the fee
+					}
+					None => return Ok(None),
+				}
+			};
+			let mut out_keys = vec![];
+			for _ in 0..out_values.len() {
+				out_keys.push(ExtKeychain::derive_key_id(2, state.next_key, 0, 0, 0));
+				state.next_key += 1;
+			}
+			(input, out_values, out_keys)
+		};
+
+		let tx = match self.build_tx(&input, &out_keys, &out_values, fee) {
+			Ok(tx) => tx,
+			Err(e) => {
+				self.state.lock().outputs.push_back(input);
+				return Err(e);
+			}
+		};
+		let kernel_hash = tx.kernels()[0].hash();
+
+		let header = match chain.head_header() {
+			Ok(header) => header,
+			Err(e) => {
+				self.state.lock().outputs.push_back(input);
+				return Err(e.into());
+			}
+		};
+		let res =
+			tx_pool
+				.write()
+				.add_to_pool(pool::TxSource::Generator, tx, stem, &header, chain.secp());
+
+		let mut state = self.state.lock();
+		match res {
+			Ok(_) => {
+				state.generated += 1;
+				state.in_flight.insert(
+					kernel_hash,
+					RelayTracking {
+						injected: Instant::now(),
+						announced_by: HashSet::new(),
+					},
+				);
+				for (key_id, value) in out_keys.into_iter().zip(out_values) {
+					state.outputs.push_back(SpendableOutput {
+						key_id,
+						value,
+						coinbase: false,
+					});
+				}
+				Ok(Some(kernel_hash))
+			}
+			Err(e) => {
+				state.rejected += 1;
+				debug!(
+					"tx_generator: generated tx {} is rejected by the pool, {}",
+					kernel_hash, e
+				);
+				// Evicted parent tx or reorg, the input is gone
+				if !Self::is_input_gone(&e) {
+					state.outputs.push_back(input);
+				}
+				Ok(None)
+			}
+		}
+	}
+
+	fn build_tx(
+		&self,
+		input: &SpendableOutput,
+		out_keys: &[Identifier],
+		out_values: &[u64],
+		fee: u64,
+	) -> Result<Transaction, Error> {
+		let mut elems = vec![if input.coinbase {
+			build::coinbase_input(input.value, input.key_id.clone())
+		} else {
+			build::input(input.value, input.key_id.clone())
+		}];
+		for (key_id, value) in out_keys.iter().zip(out_values.iter()) {
+			elems.push(build::output(*value, key_id.clone()));
+		}
+		let tx = build::transaction(
+			KernelFeatures::Plain {
+				fee: fee.try_into().map_err(|e| {
+					Error::General(format!("Unable to build tx fee {}, {}", fee, e))
+				})?,
+			},
+			&elems,
+			&self.keychain,
+			&ProofBuilder::new(&self.keychain),
+		)?;
+		Ok(tx)
+	}
+
+	/// Peer announced the transaction kernel to us
+	fn kernel_announced(&self, kernel_hash: &Hash, addr: &PeerAddr) {
+		let mut state = self.state.lock();
+		let state = &mut *state;
+		if let Some(tracking) = state.in_flight.get_mut(kernel_hash) {
+			if tracking.announced_by.insert(addr.clone()) {
+				let latency = tracking.injected.elapsed();
+				if tracking.announced_by.len() == 1 {
+					state.first_relay.add(latency);
+				}
+				state
+					.peer_relay
+					.entry(addr.clone())
+					.or_default()
+					.add(latency);
+			}
+		}
+	}
+
+	/// Log the stats and forget the old in flight transactions
+	fn report(&self) {
+		let mut state = self.state.lock();
+		state
+			.in_flight
+			.retain(|_, t| t.injected.elapsed() < RELAY_TRACKING_TIMEOUT);
+		let not_relayed = state
+			.in_flight
+			.values()
+			.filter(|t| t.announced_by.is_empty())
+			.count();
+		info!(
+			"tx_generator: generated {}, rejected {}, spendable outputs {}, not relayed yet {}, relay latency avg/min/max {}/{}/{} ms ({} txs)",
+			state.generated,
+			state.rejected,
+			state.outputs.len(),
+			not_relayed,
+			state.first_relay.avg_ms(),
+			state.first_relay.min_ms,
+			state.first_relay.max_ms,
+			state.first_relay.count,
+		);
+		for (addr, stats) in &state.peer_relay {
+			debug!(
+				"tx_generator: peer {} relay latency avg/min/max {}/{}/{} ms ({} txs)",
+				addr,
+				stats.avg_ms(),
+				stats.min_ms,
+				stats.max_ms,
+				stats.count,
+			);
+		}
+	}
+}
+
+/// Network hook that passes the transaction announcements to the generator
+pub struct TxGeneratorHook {
+	generator: Arc<TxGenerator>,
+}
+
+impl TxGeneratorHook {
+	/// Hook for the generator
+	pub fn new(generator: Arc<TxGenerator>) -> TxGeneratorHook {
+		TxGeneratorHook { generator }
+	}
+}
+
+impl NetEvents for TxGeneratorHook {
+	fn on_transaction_kernel_received(&self, kernel_hash: &Hash, addr: &PeerAddr) {
+		self.generator.kernel_announced(kernel_hash, addr);
+	}
+}
+
+/// Run the generator, `rate` is the number of transactions per minute.
+pub fn run_tx_generator(
+	generator: Arc<TxGenerator>,
+	rate: u32,
+	chain: Arc<chain::Chain>,
+	tx_pool: ServerTxPool,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!("Started tx generator, {} transactions per minute.", rate);
+
+	thread::Builder::new()
+		.name("tx_generator".to_string())
+		.spawn(move || {
+			let tx_interval = Duration::from_secs(60) / rate.max(1);
+			let mut next_tx = Instant::now();
+			let mut last_scan: Option<Instant> = None;
+			let mut last_report = Instant::now();
+			loop {
+				if stop_state.is_stopped() {
+					break;
+				}
+
+				if sync_state.is_syncing() {
+					thread::sleep(Duration::from_secs(1));
+					continue;
+				}
+
+				if last_scan.map_or(true, |t| t.elapsed() > Duration::from_secs(1)) {
+					if let Err(e) = generator.scan_coinbase(&chain) {
+						error!("tx_generator: Unable to scan for coinbase outputs, {}", e);
+					}
+					last_scan = Some(Instant::now());
+				}
+
+				if last_report.elapsed() > STATS_INTERVAL {
+					generator.report();
+					last_report = Instant::now();
+				}
+
+				let now = Instant::now();
+				if now >= next_tx {
+					match generator.generate(&chain, &tx_pool, true) {
+						Ok(Some(h)) => trace!("tx_generator: injected tx {}", h),
+						Ok(None) => (),
+						Err(e) => error!("tx_generator: Unable to generate tx, {}", e),
+					}
+					next_tx += tx_interval;
+					// Don't try to catch up if we are too far behind
+					if next_tx + Duration::from_secs(1) < now {
+						next_tx = now;
+					}
+				} else {
+					thread::sleep((next_tx - now).min(Duration::from_millis(100)));
+				}
+			}
+		})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::chain::types::NoopAdapter;
+	use crate::core::core::Block;
+	use crate::core::libtx::reward;
+	use crate::core::pow;
+	use std::path::Path;
+
+	fn init_chain(dir: &Path) -> chain::Chain {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		chain::Chain::init(
+			dir.to_str().unwrap().to_string(),
+			Arc::new(NoopAdapter {}),
+			pow::mine_genesis_block().unwrap(),
+			pow::verify_size,
+			false,
+		)
+		.unwrap()
+	}
+
+	// Mine the next block with the reward paid to the key
+	fn mine_block(chain: &chain::Chain, keychain: &ExtKeychain, key_id: &Identifier) {
+		let prev = chain.head_header().unwrap();
+		let next_header_info = consensus::next_difficulty(
+			prev.height + 1,
+			chain.difficulty_iter().unwrap(),
+			&mut VecDeque::new(),
+		);
+		let reward = reward::output(
+			keychain,
+			&ProofBuilder::new(keychain),
+			key_id,
+			0,
+			false,
+			prev.height + 1,
+			keychain.secp(),
+		)
+		.unwrap();
+		let mut b = Block::new(
+			&prev,
+			&[],
+			next_header_info.difficulty,
+			reward,
+			keychain.secp(),
+		)
+		.unwrap();
+		b.header.timestamp = prev.timestamp + chrono::Duration::seconds(60);
+		b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+		chain.set_txhashset_roots(&mut b).unwrap();
+		let edge_bits = global::min_edge_bits();
+		b.header.pow.proof.edge_bits = edge_bits;
+		pow::pow_size(
+			&mut b.header,
+			next_header_info.difficulty,
+			global::proofsize(),
+			edge_bits,
+		)
+		.unwrap();
+		chain.process_block(b, chain::Options::MINE).unwrap();
+	}
+
+	fn scanned_outputs(generator: &TxGenerator) -> Vec<(Identifier, u64)> {
+		let state = generator.state.lock();
+		assert!(state.outputs.iter().all(|o| o.coinbase));
+		state
+			.outputs
+			.iter()
+			.map(|o| (o.key_id.clone(), o.value))
+			.collect()
+	}
+
+	#[test]
+	fn tx_generator_coinbase_scan() {
+		let dir = tempfile::tempdir().unwrap();
+		let chain = init_chain(dir.path());
+		let generator = TxGenerator::new().unwrap();
+		let keychain = generator.reward_keychain();
+		let other = ExtKeychain::from_random_seed(false).unwrap();
+
+		// First scan only marks the head, the older blocks are not ours
+		generator.scan_coinbase(&chain).unwrap();
+		assert_eq!(generator.state.lock().scanned_height, Some(0));
+
+		// Block at height 2 is mined by someone else
+		for height in 1..=6 {
+			let keychain = if height == 2 { &other } else { &keychain };
+			mine_block(&chain, keychain, &TxGenerator::reward_key_id(height));
+		}
+		let ours = |height: u64| {
+			(
+				TxGenerator::reward_key_id(height),
+				consensus::reward(0, height),
+			)
+		};
+
+		// Only the mature outputs are found
+		let mature_height = 6 - global::coinbase_maturity();
+		generator.scan_coinbase(&chain).unwrap();
+		assert_eq!(generator.state.lock().scanned_height, Some(mature_height));
+		let expected: Vec<_> = (1..=mature_height).filter(|h| *h != 2).map(ours).collect();
+		assert_eq!(scanned_outputs(&generator), expected);
+
+		// Scanned blocks are not scanned again
+		generator.scan_coinbase(&chain).unwrap();
+		assert_eq!(scanned_outputs(&generator), expected);
+		mine_block(&chain, &keychain, &TxGenerator::reward_key_id(7));
+		generator.scan_coinbase(&chain).unwrap();
+		let mut expected = expected;
+		expected.push(ours(mature_height + 1));
+		assert_eq!(scanned_outputs(&generator), expected);
+
+		// Every height has its own key
+		assert_ne!(
+			TxGenerator::reward_key_id(5),
+			TxGenerator::reward_key_id((1 << 32) + 5)
+		);
+	}
+
+	#[test]
+	fn tx_generator_split() {
+		let fee = 100;
+		assert!(TxGenerator::split_values(50, fee).is_empty());
+		assert!(TxGenerator::split_values(fee, fee).is_empty());
+		assert_eq!(TxGenerator::split_values(fee + 1, fee), vec![1]);
+
+		// Halves are too small
+		let min_split = fee + 2 * MIN_SPLIT_FEES * fee;
+		assert_eq!(
+			TxGenerator::split_values(min_split - 1, fee),
+			vec![min_split - 1 - fee]
+		);
+		assert_eq!(
+			TxGenerator::split_values(min_split, fee),
+			vec![MIN_SPLIT_FEES * fee, MIN_SPLIT_FEES * fee]
+		);
+		// Odd amount, the first output takes the remainder
+		assert_eq!(
+			TxGenerator::split_values(min_split + 1, fee),
+			vec![MIN_SPLIT_FEES * fee + 1, MIN_SPLIT_FEES * fee]
+		);
+
+		// Rejected transaction returns its input unless the input is spent or unknown
+		assert!(TxGenerator::is_input_gone(&pool::PoolError::MissingInputs(
+			vec![]
+		)));
+		assert!(TxGenerator::is_input_gone(
+			&pool::PoolError::DuplicateKernelOrDuplicateSpent("spent".into())
+		));
+		assert!(!TxGenerator::is_input_gone(&pool::PoolError::OverCapacity));
+		assert!(!TxGenerator::is_input_gone(
+			&pool::PoolError::ImmatureCoinbase
+		));
+	}
+
+	#[test]
+	fn tx_generator_relay_latency() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let generator = TxGenerator::new().unwrap();
+		let kernel = Hash::from_vec(&[1]);
+		let peer1 = PeerAddr::Ip("10.0.0.1:3414".parse().unwrap());
+		let peer2 = PeerAddr::Ip("10.0.0.2:3414".parse().unwrap());
+		generator.state.lock().in_flight.insert(
+			kernel,
+			RelayTracking {
+				injected: Instant::now() - Duration::from_millis(100),
+				announced_by: HashSet::new(),
+			},
+		);
+
+		generator.kernel_announced(&kernel, &peer1);
+		// Repeated announcement and unknown kernels are not counted
+		generator.kernel_announced(&kernel, &peer1);
+		generator.kernel_announced(&Hash::from_vec(&[2]), &peer2);
+		generator.kernel_announced(&kernel, &peer2);
+
+		{
+			let state = generator.state.lock();
+			assert_eq!(state.in_flight[&kernel].announced_by.len(), 2);
+			assert_eq!(state.first_relay.count, 1);
+			assert!(state.first_relay.min_ms >= 100);
+			assert_eq!(state.peer_relay.len(), 2);
+			assert_eq!(state.peer_relay[&peer1].count, 1);
+			assert_eq!(state.peer_relay[&peer2].count, 1);
+			assert!(state.peer_relay[&peer2].min_ms >= state.peer_relay[&peer1].min_ms);
+		}
+
+		let mut stats = LatencyStats::default();
+		assert_eq!(stats.avg_ms(), 0);
+		for ms in &[20, 10, 30] {
+			stats.add(Duration::from_millis(*ms));
+		}
+		assert_eq!(
+			(stats.count, stats.min_ms, stats.max_ms, stats.avg_ms()),
+			(3, 10, 30, 20)
+		);
+	}
+}
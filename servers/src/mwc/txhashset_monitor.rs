@@ -0,0 +1,70 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::chain::{self, SyncState};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::mwc::supervisor::Supervisor;
+use crate::util::StopState;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A process to pre-build the txhashset zip. The zip is built for the archive header,
+/// that changes every archive interval (12 hours). Building it takes minutes, so
+/// without the pre-build the first syncing peer after the archive header change
+/// has to wait for it.
+pub fn monitor_txhashset_zip(
+	chain: Arc<chain::Chain>,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+	supervisor: &Arc<Supervisor>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!("Started txhashset zip monitor.");
+
+	supervisor.spawn("txhashset_zip", move || {
+		let run_interval = Duration::from_secs(60);
+		let mut last_run = Instant::now()
+			.checked_sub(run_interval)
+			.unwrap_or_else(Instant::now);
+		let mut last_archive_header = Hash::default();
+		loop {
+			if stop_state.is_stopped() {
+				break;
+			}
+
+			// Archive header is not stable until we are synced
+			if last_run.elapsed() > run_interval && !sync_state.is_syncing() {
+				match chain.txhashset_archive_header() {
+					Ok(header) if header.hash() != last_archive_header => {
+						match chain.prebuild_txhashset_zip() {
+							Ok(_) => last_archive_header = header.hash(),
+							Err(e) => error!(
+								"txhashset_zip: Unable to build zip for {} at {}, {}",
+								header.hash(),
+								header.height,
+								e
+							),
+						}
+					}
+					Ok(_) => (),
+					Err(e) => error!("txhashset_zip: Unable to get archive header, {}", e),
+				}
+				last_run = Instant::now();
+			}
+
+			// Monitor loops every minute, but check stop flag every second.
+			thread::sleep(Duration::from_secs(1));
+		}
+	})
+}
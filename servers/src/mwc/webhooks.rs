@@ -0,0 +1,551 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Webhook dispatcher. The events are POSTed as JSON `{"event", "timestamp", "data"}`
+//! to every endpoint that subscribed to them. Failed deliveries are retried with the
+//! doubling delay, and the payload is signed with HMAC-SHA256 if the endpoint has a
+//! secret, so the receiver can check that the node sent it. The deliveries run
+//! concurrently with the request timeout, a slow endpoint doesn't hold the others.
+//!
+//! Blocks and pool transactions come from the hooks. Sync completion and peer bans
+//! are polled by the dispatcher thread from the sync state and the peers change log.
+
+use crate::chain::{self, BlockStatus, SyncState};
+use crate::common::hooks::{ChainEvents, PoolEvents};
+use crate::common::types::{
+	Error, WebhookDispatcherConfig, WebhookEndpointConfig, WebhookEventKind,
+};
+use crate::core::core;
+use crate::core::core::hash::Hashed;
+use crate::p2p;
+use crate::p2p::PeerChangeKind;
+use crate::util::{Mutex, StopState, ToHex};
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use hyper::header::HeaderValue;
+use hyper::{Body, Client, Method, Request};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Header with the `sha256=<hex HMAC>` signature of the payload
+pub const SIGNATURE_HEADER: &str = "X-Mwc-Signature";
+/// Header with the event name
+pub const EVENT_HEADER: &str = "X-Mwc-Event";
+
+// Retry delay doubling stops at that shift
+const MAX_BACKOFF_SHIFT: u32 = 10;
+
+// Dispatcher thread wakes up that often to deliver and to poll the events
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+// Deliveries in flight, the others wait in the queue
+const MAX_IN_FLIGHT: usize = 16;
+
+type HttpClient = Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, Body>;
+
+struct Delivery {
+	endpoint: usize,
+	kind: WebhookEventKind,
+	body: String,
+	attempts: u32,
+	due: Instant,
+}
+
+/// Queue of the webhook deliveries, filled by the hooks and served by the dispatcher thread
+pub struct WebhookDispatcher {
+	config: WebhookDispatcherConfig,
+	urls: Vec<hyper::Uri>,
+	watched_kernels: HashSet<String>,
+	queue: Mutex<VecDeque<Delivery>>,
+}
+
+impl WebhookDispatcher {
+	/// Dispatcher for the configured endpoints, the urls must be http or https
+	pub fn new(config: WebhookDispatcherConfig) -> Result<WebhookDispatcher, Error> {
+		let mut urls = vec![];
+		for endpoint in &config.endpoints {
+			let uri: hyper::Uri = endpoint.url.parse().map_err(|e| {
+				Error::Configuration(format!("Invalid webhook url {}, {}", endpoint.url, e))
+			})?;
+			match uri.scheme_str() {
+				Some("http") | Some("https") => urls.push(uri),
+				_ => {
+					return Err(Error::Configuration(format!(
+						"Invalid webhook url scheme {}, expected http or https",
+						endpoint.url
+					)))
+				}
+			}
+		}
+		let watched_kernels = config
+			.watched_kernels
+			.iter()
+			.map(|k| k.to_lowercase())
+			.collect();
+		Ok(WebhookDispatcher {
+			config,
+			urls,
+			watched_kernels,
+			queue: Mutex::new(VecDeque::new()),
+		})
+	}
+
+	/// Queue the event for every endpoint that subscribed to it. The oldest deliveries
+	/// are dropped if the queue is full.
+	pub fn notify(&self, kind: WebhookEventKind, data: Value) {
+		let endpoints: Vec<usize> = self
+			.config
+			.endpoints
+			.iter()
+			.enumerate()
+			.filter(|(_, e)| e.accepts(kind))
+			.map(|(i, _)| i)
+			.collect();
+		if endpoints.is_empty() {
+			return;
+		}
+		let body = json!({
+			"event": kind,
+			"timestamp": Utc::now().timestamp(),
+			"data": data,
+		})
+		.to_string();
+
+		let now = Instant::now();
+		let mut queue = self.queue.lock();
+		for endpoint in endpoints {
+			queue.push_back(Delivery {
+				endpoint,
+				kind,
+				body: body.clone(),
+				attempts: 0,
+				due: now,
+			});
+		}
+		self.trim(&mut queue);
+	}
+
+	// Drop the oldest deliveries over the queue size
+	fn trim(&self, queue: &mut VecDeque<Delivery>) {
+		while queue.len() > self.config.queue_size.max(1) {
+			if let Some(dropped) = queue.pop_front() {
+				warn!(
+					"Webhook queue is full, dropping {:?} event for {}",
+					dropped.kind, self.config.endpoints[dropped.endpoint].url
+				);
+			}
+		}
+	}
+
+	/// Number of the deliveries waiting in the queue, including the retries
+	pub fn queued(&self) -> usize {
+		self.queue.lock().len()
+	}
+
+	// Take up to `max` deliveries that are due, the oldest first
+	fn take_due(&self, now: Instant, max: usize) -> Vec<Delivery> {
+		let mut queue = self.queue.lock();
+		let mut due = vec![];
+		let mut waiting = VecDeque::with_capacity(queue.len());
+		for delivery in queue.drain(..) {
+			if due.len() < max && delivery.due <= now {
+				due.push(delivery);
+			} else {
+				waiting.push_back(delivery);
+			}
+		}
+		*queue = waiting;
+		due
+	}
+
+	// Schedule the failed delivery again, false if it ran out of the retries
+	fn retry(&self, mut delivery: Delivery) -> bool {
+		delivery.attempts += 1;
+		if delivery.attempts > self.config.max_retries {
+			return false;
+		}
+		let shift = (delivery.attempts - 1).min(MAX_BACKOFF_SHIFT);
+		let delay = Duration::from_secs(self.config.retry_delay_secs.saturating_mul(1 << shift));
+		delivery.due = Instant::now() + delay;
+		let mut queue = self.queue.lock();
+		queue.push_back(delivery);
+		self.trim(&mut queue);
+		true
+	}
+
+	fn request(&self, delivery: &Delivery) -> Request<Body> {
+		let endpoint: &WebhookEndpointConfig = &self.config.endpoints[delivery.endpoint];
+		let mut req = Request::new(Body::from(delivery.body.clone()));
+		*req.method_mut() = Method::POST;
+		*req.uri_mut() = self.urls[delivery.endpoint].clone();
+		let headers = req.headers_mut();
+		headers.insert(
+			hyper::header::CONTENT_TYPE,
+			HeaderValue::from_static("application/json"),
+		);
+		if let Ok(value) = HeaderValue::from_str(&event_name(delivery.kind)) {
+			headers.insert(EVENT_HEADER, value);
+		}
+		if let Some(secret) = &endpoint.secret {
+			let signature = sign_payload(secret, &delivery.body);
+			if let Ok(value) = HeaderValue::from_str(&format!("sha256={}", signature)) {
+				headers.insert(SIGNATURE_HEADER, value);
+			}
+		}
+		req
+	}
+}
+
+fn http_client() -> HttpClient {
+	let https = hyper_rustls::HttpsConnectorBuilder::new()
+		.with_native_roots()
+		.https_or_http()
+		.enable_http1()
+		.build();
+	Client::builder().build::<_, Body>(https)
+}
+
+// Send the delivery, the failed one is scheduled for the retry
+async fn deliver(
+	dispatcher: Arc<WebhookDispatcher>,
+	client: HttpClient,
+	delivery: Delivery,
+	timeout: Duration,
+) {
+	let res = tokio::time::timeout(timeout, client.request(dispatcher.request(&delivery))).await;
+	let failure = match res {
+		Ok(Ok(resp)) if resp.status().is_success() => None,
+		Ok(Ok(resp)) => Some(format!("status {}", resp.status())),
+		Ok(Err(e)) => Some(e.to_string()),
+		Err(_) => Some("timeout".to_string()),
+	};
+	if let Some(e) = failure {
+		let (kind, attempts) = (delivery.kind, delivery.attempts + 1);
+		let url = dispatcher.config.endpoints[delivery.endpoint].url.clone();
+		if dispatcher.retry(delivery) {
+			debug!(
+				"webhooks: {:?} delivery to {} failed, {}, attempt {}",
+				kind, url, e, attempts
+			);
+		} else {
+			warn!(
+				"webhooks: {:?} delivery to {} failed after {} attempts, {}",
+				kind, url, attempts, e
+			);
+		}
+	}
+}
+
+// Start the due deliveries in the free in-flight slots. Must be called on the runtime.
+fn dispatch_due(
+	dispatcher: &Arc<WebhookDispatcher>,
+	client: &HttpClient,
+	in_flight: &Arc<Semaphore>,
+	timeout: Duration,
+) {
+	let due = dispatcher.take_due(Instant::now(), in_flight.available_permits());
+	for delivery in due {
+		let permit = match in_flight.clone().try_acquire_owned() {
+			Ok(permit) => permit,
+			Err(_) => {
+				dispatcher.queue.lock().push_front(delivery);
+				continue;
+			}
+		};
+		let dispatcher = dispatcher.clone();
+		let client = client.clone();
+		tokio::spawn(async move {
+			deliver(dispatcher, client, delivery, timeout).await;
+			drop(permit);
+		});
+	}
+}
+
+/// Hex HMAC-SHA256 of the payload with the endpoint secret
+pub fn sign_payload(secret: &str, body: &str) -> String {
+	let mut mac =
+		Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+	mac.update(body.as_bytes());
+	mac.finalize().into_bytes().to_hex()
+}
+
+fn event_name(kind: WebhookEventKind) -> String {
+	serde_json::to_value(kind)
+		.ok()
+		.and_then(|v| v.as_str().map(|s| s.to_string()))
+		.unwrap_or_default()
+}
+
+/// Chain and pool hook that queues the events for the dispatcher
+pub struct WebhookHook {
+	dispatcher: Arc<WebhookDispatcher>,
+}
+
+impl WebhookHook {
+	/// Hook for the dispatcher
+	pub fn new(dispatcher: Arc<WebhookDispatcher>) -> WebhookHook {
+		WebhookHook { dispatcher }
+	}
+}
+
+impl ChainEvents for WebhookHook {
+	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+		match status {
+			BlockStatus::Next { .. } => self.dispatcher.notify(
+				WebhookEventKind::Block,
+				json!({
+					"hash": block.hash().to_hex(),
+					"height": block.header.height,
+					"prev_hash": block.header.prev_hash.to_hex(),
+				}),
+			),
+			BlockStatus::Reorg {
+				prev_head,
+				fork_point,
+				..
+			} => self.dispatcher.notify(
+				WebhookEventKind::Reorg,
+				json!({
+					"hash": block.hash().to_hex(),
+					"height": block.header.height,
+					"depth": prev_head.height.saturating_sub(fork_point.height),
+					"prev_head": {
+						"hash": prev_head.hash().to_hex(),
+						"height": prev_head.height,
+					},
+					"fork_point": {
+						"hash": fork_point.hash().to_hex(),
+						"height": fork_point.height,
+					},
+				}),
+			),
+			BlockStatus::Fork { .. } => {}
+		}
+	}
+}
+
+impl PoolEvents for WebhookHook {
+	fn on_transaction_accepted(&self, tx: &core::Transaction) {
+		if self.dispatcher.watched_kernels.is_empty() {
+			return;
+		}
+		for kernel in tx.kernels() {
+			let excess = kernel.excess.to_hex();
+			if self.dispatcher.watched_kernels.contains(&excess) {
+				self.dispatcher.notify(
+					WebhookEventKind::KernelAccepted,
+					json!({
+						"excess": excess,
+						"tx_hash": tx.hash().to_hex(),
+					}),
+				);
+			}
+		}
+	}
+}
+
+/// Deliver the queued events until the node stops. Sync completion and the peer bans
+/// are detected here as well.
+pub fn run_webhook_dispatcher(
+	dispatcher: Arc<WebhookDispatcher>,
+	chain: Arc<chain::Chain>,
+	peers: Arc<p2p::Peers>,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!(
+		"Started webhook dispatcher for {} endpoints.",
+		dispatcher.urls.len()
+	);
+
+	thread::Builder::new()
+		.name("webhooks".to_string())
+		.spawn(move || {
+			let runtime = match tokio::runtime::Builder::new_current_thread()
+				.enable_all()
+				.build()
+			{
+				Ok(runtime) => runtime,
+				Err(e) => {
+					error!("webhooks: Unable to start the runtime, {}", e);
+					return;
+				}
+			};
+			let client = http_client();
+			let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+			let timeout = Duration::from_secs(dispatcher.config.request_timeout.max(1) as u64);
+
+			let mut was_syncing = true;
+			let mut peer_changes_seq = peers.peer_changes_since(0).seq;
+			runtime.block_on(async {
+				loop {
+					if stop_state.is_stopped() {
+						break;
+					}
+
+					let syncing = sync_state.is_syncing();
+					if was_syncing && !syncing {
+						if let Ok(head) = chain.head() {
+							dispatcher.notify(
+								WebhookEventKind::SyncComplete,
+								json!({
+									"hash": head.last_block_h.to_hex(),
+									"height": head.height,
+								}),
+							);
+						}
+					}
+					was_syncing = syncing;
+
+					let changes = peers.peer_changes_since(peer_changes_seq);
+					peer_changes_seq = changes.seq;
+					for change in changes.changes {
+						if change.kind == PeerChangeKind::Banned {
+							dispatcher.notify(
+								WebhookEventKind::PeerBanned,
+								json!({ "peer": change.addr.to_string() }),
+							);
+						}
+					}
+
+					dispatch_due(&dispatcher, &client, &in_flight, timeout);
+
+					tokio::time::sleep(DISPATCH_INTERVAL).await;
+				}
+			});
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hyper::service::{make_service_fn, service_fn};
+	use hyper::{Response, Server, StatusCode};
+	use std::convert::Infallible;
+	use std::net::SocketAddr;
+
+	// Received body and signature header
+	type Received = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+	// Local endpoint that fails the first request and accepts the others
+	fn start_endpoint(received: Received) -> SocketAddr {
+		let make_svc = make_service_fn(move |_| {
+			let received = received.clone();
+			async move {
+				Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+					let received = received.clone();
+					async move {
+						let signature = req
+							.headers()
+							.get(SIGNATURE_HEADER)
+							.and_then(|v| v.to_str().ok())
+							.map(|v| v.to_string());
+						let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+						let body = String::from_utf8(body.to_vec()).unwrap();
+						let mut received = received.lock();
+						received.push((body, signature));
+						let status = if received.len() == 1 {
+							StatusCode::INTERNAL_SERVER_ERROR
+						} else {
+							StatusCode::OK
+						};
+						let mut resp = Response::new(Body::empty());
+						*resp.status_mut() = status;
+						Ok::<_, Infallible>(resp)
+					}
+				}))
+			}
+		});
+		let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+		let addr = server.local_addr();
+		tokio::spawn(server);
+		addr
+	}
+
+	fn endpoint(url: String, secret: Option<&str>) -> WebhookEndpointConfig {
+		WebhookEndpointConfig {
+			url,
+			secret: secret.map(|s| s.to_string()),
+			events: vec![],
+		}
+	}
+
+	#[test]
+	fn test_delivery_and_retry() {
+		let runtime = tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.unwrap();
+		runtime.block_on(async {
+			let received: Received = Arc::new(Mutex::new(vec![]));
+			let addr = start_endpoint(received.clone());
+			// Accepts the connections, never responds
+			let stalled = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+			let dispatcher = Arc::new(
+				WebhookDispatcher::new(WebhookDispatcherConfig {
+					max_retries: 2,
+					retry_delay_secs: 0,
+					request_timeout: 30,
+					queue_size: 10,
+					watched_kernels: vec![],
+					endpoints: vec![
+						endpoint(
+							format!("http://{}/stalled", stalled.local_addr().unwrap()),
+							None,
+						),
+						endpoint(format!("http://{}/hook", addr), Some("secret")),
+					],
+				})
+				.unwrap(),
+			);
+			dispatcher.notify(WebhookEventKind::Block, json!({ "height": 1 }));
+			assert_eq!(dispatcher.queued(), 2);
+
+			// The first attempt fails, the retry is delivered while the stalled endpoint
+			// is still waiting for its response
+			let client = http_client();
+			let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+			let timeout = Duration::from_secs(30);
+			for _ in 0..100 {
+				dispatch_due(&dispatcher, &client, &in_flight, timeout);
+				if received.lock().len() == 2
+					&& dispatcher.queued() == 0
+					&& in_flight.available_permits() == MAX_IN_FLIGHT - 1
+				{
+					break;
+				}
+				tokio::time::sleep(Duration::from_millis(50)).await;
+			}
+			let received = received.lock().clone();
+			assert_eq!(received.len(), 2);
+			assert_eq!(received[0], received[1]);
+			let (body, signature) = &received[0];
+			let event: Value = serde_json::from_str(body).unwrap();
+			assert_eq!(event["event"], "block");
+			assert_eq!(event["data"]["height"], 1);
+			assert_eq!(
+				signature.as_deref(),
+				Some(format!("sha256={}", sign_payload("secret", body)).as_str())
+			);
+			assert_eq!(dispatcher.queued(), 0);
+			assert_eq!(in_flight.available_permits(), MAX_IN_FLIGHT - 1);
+		});
+	}
+}
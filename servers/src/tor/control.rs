@@ -0,0 +1,254 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tor control port client. With the external tor the node creates an ephemeral v3
+//! onion service for its listeners (ADD_ONION), so the operator doesn't need to configure
+//! the hidden service. The service lives while the control connection is open, it is
+//! removed (DEL_ONION) on shutdown.
+
+use crate::util::{self, OnionV3Address};
+use crate::Error;
+use ed25519_dalek::{ExpandedSecretKey, SecretKey as DalekSecretKey};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Connect and the reply timeout. Tor answers the control commands immediately, ADD_ONION
+/// doesn't wait for the service descriptor to be published.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection to the tor control port
+pub struct TorControl {
+	reader: BufReader<TcpStream>,
+	writer: TcpStream,
+	/// Onion service that was created by this connection
+	service_id: Option<String>,
+}
+
+impl TorControl {
+	/// Connect to the control port of the local tor and authenticate. Without the password
+	/// the cookie authentication is used, or none if tor doesn't require it.
+	pub fn connect(port: u16, password: Option<&str>) -> Result<TorControl, Error> {
+		let addr = SocketAddr::from(([127, 0, 0, 1], port));
+		let stream = TcpStream::connect_timeout(&addr, CONTROL_TIMEOUT)
+			.map_err(|e| Error::IO(format!("Unable to connect to {}, {}", addr, e)))?;
+		stream
+			.set_read_timeout(Some(CONTROL_TIMEOUT))
+			.map_err(|e| Error::IO(format!("Unable to set the read timeout, {}", e)))?;
+		let writer = stream
+			.try_clone()
+			.map_err(|e| Error::IO(format!("Unable to clone the control connection, {}", e)))?;
+		let mut control = TorControl {
+			reader: BufReader::new(stream),
+			writer,
+			service_id: None,
+		};
+		control.authenticate(password)?;
+		Ok(control)
+	}
+
+	fn authenticate(&mut self, password: Option<&str>) -> Result<(), Error> {
+		if let Some(password) = password {
+			self.command(&format!("AUTHENTICATE {}", quote(password)))?;
+			return Ok(());
+		}
+		let info = self.command("PROTOCOLINFO 1")?;
+		let (methods, cookie_file) = info
+			.iter()
+			.find_map(|l| l.strip_prefix("AUTH "))
+			.map(parse_auth_line)
+			.ok_or_else(|| Error::TorConfig("PROTOCOLINFO reply has no AUTH line".to_string()))?;
+		if methods.iter().any(|m| m == "NULL") {
+			self.command("AUTHENTICATE")?;
+		} else if methods.iter().any(|m| m == "COOKIE") {
+			let cookie_file = cookie_file.ok_or_else(|| {
+				Error::TorConfig("PROTOCOLINFO reply has no COOKIEFILE".to_string())
+			})?;
+			let cookie = fs::read(&cookie_file).map_err(|e| {
+				Error::IO(format!("Unable to read tor cookie {}, {}", cookie_file, e))
+			})?;
+			self.command(&format!("AUTHENTICATE {}", util::to_hex(&cookie)))?;
+		} else {
+			return Err(Error::TorConfig(format!(
+				"Unsupported tor control authentication {}, set control_password",
+				methods.join(",")
+			)));
+		}
+		Ok(())
+	}
+
+	/// Create the onion service of the ed25519 secret, `ports` are the virtual ports of
+	/// the service and their targets. Returns the onion address of the service.
+	pub fn add_onion(
+		&mut self,
+		secret: &[u8; 32],
+		ports: &[(u16, String)],
+	) -> Result<OnionV3Address, Error> {
+		let address = OnionV3Address::from_private(secret)?;
+		let d_sec_key = DalekSecretKey::from_bytes(secret)
+			.map_err(|e| Error::ED25519Key(format!("Unable to parse private key, {}", e)))?;
+		// tor expects the expanded key, the same one as in hs_ed25519_secret_key
+		let expanded = ExpandedSecretKey::from(&d_sec_key);
+		let mut cmd = format!(
+			"ADD_ONION ED25519-V3:{}",
+			base64::encode(&expanded.to_bytes()[..])
+		);
+		for (port, target) in ports {
+			cmd.push_str(&format!(" Port={},{}", port, target));
+		}
+		let reply = self.command(&cmd)?;
+		let service_id = reply
+			.iter()
+			.find_map(|l| l.strip_prefix("ServiceID="))
+			.ok_or_else(|| Error::TorConfig("ADD_ONION reply has no ServiceID".to_string()))?;
+		if service_id != address.to_string() {
+			return Err(Error::TorConfig(format!(
+				"Tor created the onion service {}, expected {}",
+				service_id, address
+			)));
+		}
+		self.service_id = Some(service_id.to_string());
+		Ok(address)
+	}
+
+	/// Remove the onion service that was created by this connection
+	pub fn del_onion(&mut self) -> Result<(), Error> {
+		if let Some(service_id) = self.service_id.take() {
+			self.command(&format!("DEL_ONION {}", service_id))?;
+		}
+		Ok(())
+	}
+
+	// Send the command, returns the reply lines without the status code
+	fn command(&mut self, cmd: &str) -> Result<Vec<String>, Error> {
+		// The arguments can be the secrets, only the keyword goes to the errors
+		let keyword = cmd.split(' ').next().unwrap_or_default();
+		self.writer
+			.write_all(format!("{}\r\n", cmd).as_bytes())
+			.map_err(|e| Error::IO(format!("Unable to send {} to tor, {}", keyword, e)))?;
+		let (code, lines) = self.read_reply()?;
+		if code != 250 {
+			return Err(Error::TorConfig(format!(
+				"Tor rejected {}, {} {}",
+				keyword,
+				code,
+				lines.join(" ")
+			)));
+		}
+		Ok(lines)
+	}
+
+	fn read_line(&mut self) -> Result<String, Error> {
+		let mut line = String::new();
+		let n = self
+			.reader
+			.read_line(&mut line)
+			.map_err(|e| Error::IO(format!("Unable to read the tor reply, {}", e)))?;
+		if n == 0 {
+			return Err(Error::IO("Tor closed the control connection".to_string()));
+		}
+		Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+	}
+
+	// Reply lines are "<code>-<text>", "<code>+<text>" followed by the data lines up to
+	// ".", the last line is "<code> <text>"
+	fn read_reply(&mut self) -> Result<(u16, Vec<String>), Error> {
+		let mut lines = vec![];
+		loop {
+			let line = self.read_line()?;
+			let code = line
+				.get(0..3)
+				.and_then(|c| c.parse::<u16>().ok())
+				.ok_or_else(|| Error::TorConfig(format!("Invalid tor reply '{}'", line)))?;
+			lines.push(line.get(4..).unwrap_or_default().to_string());
+			match line.get(3..4) {
+				Some(" ") | None => return Ok((code, lines)),
+				Some("-") => (),
+				Some("+") => loop {
+					let data = self.read_line()?;
+					if data == "." {
+						break;
+					}
+					lines.push(data);
+				},
+				Some(_) => {
+					return Err(Error::TorConfig(format!("Invalid tor reply '{}'", line)));
+				}
+			}
+		}
+	}
+}
+
+impl Drop for TorControl {
+	// tor removes the service when the connection is closed anyway, it is just faster
+	fn drop(&mut self) {
+		self.del_onion().unwrap_or(());
+	}
+}
+
+/// Quoted string of the control protocol
+fn quote(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Auth methods and the cookie file from the PROTOCOLINFO
+/// `METHODS=COOKIE,SAFECOOKIE COOKIEFILE="/run/tor/control.authcookie"`
+fn parse_auth_line(line: &str) -> (Vec<String>, Option<String>) {
+	let methods = line
+		.split(' ')
+		.find_map(|p| p.strip_prefix("METHODS="))
+		.map(|m| m.split(',').map(|m| m.to_string()).collect())
+		.unwrap_or_default();
+	let cookie_file = line.find("COOKIEFILE=\"").map(|start| {
+		let mut file = String::new();
+		let mut chars = line[start + "COOKIEFILE=\"".len()..].chars();
+		while let Some(c) = chars.next() {
+			match c {
+				'"' => break,
+				'\\' => {
+					if let Some(c) = chars.next() {
+						file.push(c);
+					}
+				}
+				c => file.push(c),
+			}
+		}
+		file
+	});
+	(methods, cookie_file)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_auth_line() {
+		let (methods, cookie_file) = parse_auth_line(
+			"METHODS=COOKIE,SAFECOOKIE COOKIEFILE=\"C:\\\\tor\\\\control \\\"auth\\\"cookie\"",
+		);
+		assert_eq!(methods, vec!["COOKIE", "SAFECOOKIE"]);
+		assert_eq!(
+			cookie_file,
+			Some("C:\\tor\\control \"auth\"cookie".to_string())
+		);
+
+		let (methods, cookie_file) = parse_auth_line("METHODS=NULL");
+		assert_eq!(methods, vec!["NULL"]);
+		assert_eq!(cookie_file, None);
+
+		assert_eq!(quote("pa\"ss\\"), "\"pa\\\"ss\\\\\"");
+	}
+}
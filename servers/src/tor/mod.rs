@@ -14,4 +14,5 @@
 // limitations under the License.
 
 pub mod config;
+pub mod control;
 pub mod process;
@@ -19,11 +19,14 @@ use clap::ArgMatches;
 
 use crate::api::client;
 use crate::api::json_rpc::*;
-use crate::api::types::Status;
+use crate::api::types::{SnapshotInfo, Status, UtxoArchiveInfo, UtxoDumpInfo};
+use crate::chain::utxo_dump::{UtxoDump, UtxoEntry};
 use crate::config::GlobalConfig;
 use crate::p2p::types::PeerInfoDisplay;
 use crate::util::file::get_first_line;
 use serde_json::json;
+use std::fs::File;
+use std::io::BufReader;
 
 const ENDPOINT: &str = "/v2/owner";
 
@@ -48,6 +51,10 @@ impl HTTPNodeClient {
 		let timeout = match method {
 			// 6 hours read timeout
 			"validate_chain" => client::TimeOut::new(20, 21600, 20),
+			// 1 hour read timeout
+			"export_utxo_set" => client::TimeOut::new(20, 3600, 20),
+			"create_snapshot" => client::TimeOut::new(20, 3600, 20),
+			"write_utxo_archive" => client::TimeOut::new(20, 3600, 20),
 			_ => client::TimeOut::default(),
 		};
 		let url = format!("http://{}{}", self.node_url, ENDPOINT);
@@ -57,6 +64,7 @@ impl HTTPNodeClient {
 			self.node_api_secret.clone(),
 			&req,
 			timeout,
+			client::RequestPolicy::default(),
 		);
 
 		match res {
@@ -179,6 +187,73 @@ impl HTTPNodeClient {
 		e.reset().unwrap();
 	}
 
+	pub fn export_utxo_set(&self, path: String) {
+		let mut e = term::stdout().unwrap();
+		let params = json!([path]);
+		writeln!(e, "Exporting the UTXO set. This might take time...").unwrap();
+		match self.send_json_request::<UtxoDumpInfo>("export_utxo_set", &params) {
+			Ok(info) => {
+				writeln!(e, "UTXO set is written to {}", info.path).unwrap();
+				writeln!(e, "Height: {}", info.height).unwrap();
+				writeln!(e, "Block hash: {}", info.hash).unwrap();
+				writeln!(e, "Output root: {}", info.output_root).unwrap();
+				writeln!(e, "Rangeproof root: {}", info.rangeproof_root).unwrap();
+				writeln!(e, "Kernel root: {}", info.kernel_root).unwrap();
+				writeln!(e, "Unspent outputs: {}", info.utxos).unwrap();
+			}
+			Err(err) => writeln!(e, "Failed to export UTXO set: {:?}", err).unwrap(),
+		}
+		e.reset().unwrap();
+	}
+
+	pub fn create_snapshot(&self, path: String) -> i32 {
+		let mut e = term::stdout().unwrap();
+		let params = json!([path]);
+		writeln!(
+			e,
+			"Writing the chain data snapshot. This might take time..."
+		)
+		.unwrap();
+		let res = match self.send_json_request::<SnapshotInfo>("create_snapshot", &params) {
+			Ok(info) => {
+				writeln!(e, "Chain data snapshot is written to {}", info.path).unwrap();
+				writeln!(e, "Height: {}", info.height).unwrap();
+				writeln!(e, "Block hash: {}", info.hash).unwrap();
+				writeln!(e, "Linked files: {}", info.linked_files).unwrap();
+				writeln!(e, "Copied bytes: {}", info.copied_bytes).unwrap();
+				0
+			}
+			Err(err) => {
+				writeln!(e, "Failed to write the chain data snapshot: {:?}", err).unwrap();
+				1
+			}
+		};
+		e.reset().unwrap();
+		res
+	}
+
+	pub fn write_utxo_archive(&self, path: String) -> i32 {
+		let mut e = term::stdout().unwrap();
+		let params = json!([path]);
+		writeln!(e, "Writing the UTXO archive. This might take time...").unwrap();
+		let res = match self.send_json_request::<UtxoArchiveInfo>("write_utxo_archive", &params) {
+			Ok(info) => {
+				writeln!(e, "UTXO archive is written to {}", info.path).unwrap();
+				writeln!(e, "Height: {}", info.height).unwrap();
+				writeln!(e, "Block hash: {}", info.hash).unwrap();
+				writeln!(e, "Headers: {}", info.headers).unwrap();
+				writeln!(e, "Segments: {}", info.segments).unwrap();
+				0
+			}
+			Err(err) => {
+				writeln!(e, "Failed to write the UTXO archive: {:?}", err).unwrap();
+				1
+			}
+		};
+		e.reset().unwrap();
+		res
+	}
+
 	pub fn ban_peer(&self, peer_addr: &SocketAddr) {
 		let mut e = term::stdout().unwrap();
 		let params = json!([peer_addr]);
@@ -198,6 +273,107 @@ impl HTTPNodeClient {
 		};
 		e.reset().unwrap();
 	}
+
+	pub fn set_tor_only(&self, tor_only: bool) {
+		let mut e = term::stdout().unwrap();
+		let params = json!([tor_only]);
+		match self.send_json_request::<usize>("set_tor_only", &params) {
+			Ok(closed) => {
+				if tor_only {
+					writeln!(
+						e,
+						"Tor only mode is on, {} clearnet peers are closed",
+						closed
+					)
+					.unwrap()
+				} else {
+					writeln!(e, "Tor only mode is off").unwrap()
+				}
+			}
+			Err(err) => writeln!(e, "Failed to switch Tor only mode: {:?}", err).unwrap(),
+		};
+		e.reset().unwrap();
+	}
+}
+
+fn read_utxo_dump(path: &str) -> Result<UtxoDump, String> {
+	let file = File::open(path).map_err(|e| format!("Unable to open {}, {}", path, e))?;
+	UtxoDump::read(BufReader::new(file)).map_err(|e| format!("Unable to read {}, {}", path, e))
+}
+
+fn print_utxos(e: &mut Box<term::StdoutTerminal>, title: &str, utxos: &[UtxoEntry]) {
+	if utxos.is_empty() {
+		return;
+	}
+	writeln!(e, "{}: {}", title, utxos.len()).unwrap();
+	for utxo in utxos.iter().take(20) {
+		writeln!(
+			e,
+			"  {:?} {:?} at {}",
+			utxo.commit, utxo.features, utxo.height
+		)
+		.unwrap();
+	}
+	if utxos.len() > 20 {
+		writeln!(e, "  ...").unwrap();
+	}
+}
+
+/// Compare two UTXO set dumps, returns 0 if they match
+pub fn verify_utxo_dumps(first: &str, second: &str) -> i32 {
+	let mut e = term::stdout().unwrap();
+	let (a, b) = match (read_utxo_dump(first), read_utxo_dump(second)) {
+		(Ok(a), Ok(b)) => (a, b),
+		(Err(err), _) | (_, Err(err)) => {
+			writeln!(e, "{}", err).unwrap();
+			return 1;
+		}
+	};
+	let diff = a.compare(&b);
+	if diff.is_match() {
+		writeln!(
+			e,
+			"UTXO dumps match at height {}, block {}, {} unspent outputs",
+			a.height,
+			a.hash,
+			a.utxos.len()
+		)
+		.unwrap();
+		e.reset().unwrap();
+		return 0;
+	}
+
+	if diff.block_mismatch {
+		writeln!(
+			e,
+			"Dumps are made for different blocks: {} at {} and {} at {}. Roots are not compared",
+			a.hash, a.height, b.hash, b.height
+		)
+		.unwrap();
+	}
+	for root in &diff.roots_mismatch {
+		writeln!(e, "Roots don't match: {}", root).unwrap();
+	}
+	print_utxos(&mut e, "Outputs only in the first dump", &diff.only_first);
+	print_utxos(&mut e, "Outputs only in the second dump", &diff.only_second);
+	if !diff.mismatch.is_empty() {
+		writeln!(
+			e,
+			"Outputs with different features or height: {}",
+			diff.mismatch.len()
+		)
+		.unwrap();
+		for (x, y) in diff.mismatch.iter().take(20) {
+			writeln!(
+				e,
+				"  {:?}: {:?} at {} vs {:?} at {}",
+				x.commit, x.features, x.height, y.features, y.height
+			)
+			.unwrap();
+		}
+	}
+	e.reset().unwrap();
+	1
 }
 
 pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig) -> i32 {
@@ -221,6 +397,15 @@ pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig)
 			let hash = args.value_of("hash").unwrap();
 			node_client.invalidate_header(hash.to_string());
 		}
+		("export-utxo", Some(args)) => {
+			let path = args.value_of("file").unwrap();
+			node_client.export_utxo_set(path.to_string());
+		}
+		("verify-utxo-dump", Some(args)) => {
+			let first = args.value_of("first").unwrap();
+			let second = args.value_of("second").unwrap();
+			return verify_utxo_dumps(first, second);
+		}
 		("verify-chain", Some(args)) => {
 			let assume_valid_rangeproofs_kernels = args.is_present("fast");
 			node_client.verify_chain(assume_valid_rangeproofs_kernels);
@@ -243,6 +428,10 @@ pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig)
 				panic!("Invalid peer address format");
 			}
 		}
+		("tor-only", Some(args)) => {
+			let tor_only = args.value_of("mode").unwrap() == "on";
+			node_client.set_tor_only(tor_only);
+		}
 		_ => panic!("Unknown client command, use 'mwc help client' for details"),
 	}
 	0
@@ -14,9 +14,13 @@
 // limitations under the License.
 
 /// Mwc configuration file output command
-use crate::config::GlobalConfig;
+use crate::config::setup::write_setup_config;
+use crate::config::{
+	check_config, ConfigError, GlobalConfig, IssueLevel, ResourceProfile, SetupOptions, TorMode,
+};
 use crate::core::global;
 use std::env;
+use std::io::{self, BufRead, Write};
 
 /// Create a config file in the current directory
 pub fn config_command_server(chain_type: &global::ChainTypes, file_name: &str) {
@@ -44,3 +48,179 @@ pub fn config_command_server(chain_type: &global::ChainTypes, file_name: &str) {
 		file_name
 	);
 }
+
+/// Validate the configuration and print found problems. Returns exit code,
+/// non zero if configuration can't be loaded or has errors.
+pub fn check_config_command(config: Result<GlobalConfig, ConfigError>) -> i32 {
+	let config = match config {
+		Ok(config) => config,
+		Err(e) => {
+			println!("ERROR   [config] {}", e);
+			return 1;
+		}
+	};
+
+	if let Some(file_path) = &config.config_file_path {
+		println!("Checking configuration file {}", file_path.display());
+	}
+
+	let issues = check_config(&config);
+	for issue in &issues {
+		println!("{}", issue);
+	}
+
+	let errors = issues
+		.iter()
+		.filter(|i| i.level == IssueLevel::Error)
+		.count();
+	println!(
+		"Configuration check finished: {} error(s), {} warning(s)",
+		errors,
+		issues.len() - errors
+	);
+
+	if errors > 0 {
+		1
+	} else {
+		0
+	}
+}
+
+/// Read an answer from stdin. Empty answer selects the default.
+fn ask(question: &str, default: &str) -> String {
+	print!("{} [{}]: ", question, default);
+	let _ = io::stdout().flush();
+	let mut answer = String::new();
+	if io::stdin().lock().read_line(&mut answer).is_err() {
+		return default.to_string();
+	}
+	let answer = answer.trim();
+	if answer.is_empty() {
+		default.to_string()
+	} else {
+		answer.to_string()
+	}
+}
+
+/// Ask until one of the options is selected, returns the index of the option.
+fn ask_choice(question: &str, options: &[&str], default: usize) -> usize {
+	loop {
+		let answer = ask(
+			&format!("{} ({})", question, options.join("/")),
+			options[default],
+		)
+		.to_lowercase();
+		if let Some(idx) = options.iter().position(|o| *o == answer) {
+			return idx;
+		}
+		println!("Please answer one of: {}", options.join(", "));
+	}
+}
+
+fn ask_yes_no(question: &str, default: bool) -> bool {
+	ask_choice(question, &["yes", "no"], if default { 0 } else { 1 }) == 0
+}
+
+/// Interactive first run setup. Asks the operator about the node usage and
+/// generates a tuned config and api secret files.
+pub fn init_command_server(chain_type: &global::ChainTypes, file_name: &str) -> i32 {
+	println!("MWC node setup. Press Enter to accept the default value in brackets.");
+	println!();
+
+	let chain_type = match ask_choice(
+		"Network",
+		&["mainnet", "floonet"],
+		if *chain_type == global::ChainTypes::Floonet {
+			1
+		} else {
+			0
+		},
+	) {
+		1 => global::ChainTypes::Floonet,
+		_ => global::ChainTypes::Mainnet,
+	};
+
+	let tor = match ask_choice(
+		"Use Tor: none, node managed Tor or already running external Tor",
+		&["none", "embedded", "external"],
+		0,
+	) {
+		1 => TorMode::Embedded,
+		2 => {
+			let socks_port = loop {
+				match ask("External Tor socks port", "9050").parse::<u16>() {
+					Ok(port) if port > 0 => break port,
+					_ => println!("Please enter a valid port number"),
+				}
+			};
+			let onion_address = loop {
+				let address = ask("Onion address published by external Tor for this node", "");
+				if !address.is_empty() {
+					break address;
+				}
+				println!("Onion address is required with external Tor");
+			};
+			TorMode::External {
+				socks_port,
+				onion_address,
+			}
+		}
+		_ => TorMode::Disabled,
+	};
+
+	let archive_mode = ask_yes_no(
+		"Run archive node (keep full history, needs much more disk space)",
+		false,
+	);
+
+	let public_api = ask_yes_no(
+		"Expose node API to other hosts (API will be protected with a secret)",
+		false,
+	);
+
+	let profile = match ask_choice(
+		"Resource profile: low for small devices, high for well connected servers",
+		&["low", "standard", "high"],
+		1,
+	) {
+		0 => ResourceProfile::Low,
+		2 => ResourceProfile::High,
+		_ => ResourceProfile::Standard,
+	};
+
+	let options = SetupOptions {
+		chain_type,
+		tor,
+		archive_mode,
+		public_api,
+		profile,
+	};
+
+	let mut result = write_setup_config(&options, file_name, false);
+	if let Err(ConfigError::FileIOError(path, _)) = &result {
+		if ask_yes_no(&format!("{} already exists. Overwrite it", path), false) {
+			result = write_setup_config(&options, file_name, true);
+		} else {
+			println!("Setup cancelled, existing configuration is kept");
+			return 1;
+		}
+	}
+
+	match result {
+		Ok(config_path) => {
+			println!();
+			println!("Configuration is written to {}", config_path.display());
+			if public_api {
+				println!("API secrets are stored next to the config file. Please consider setting up TLS certificates, see tls_certificate_file and tls_certificate_key.");
+			}
+			println!(
+				"Run 'mwc server check-config' to validate it, 'mwc server run' to start the node."
+			);
+			0
+		}
+		Err(e) => {
+			println!("Unable to write configuration, {}", e);
+			1
+		}
+	}
+}
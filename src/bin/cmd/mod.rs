@@ -18,5 +18,5 @@ mod config;
 mod server;
 
 pub use self::client::client_command;
-pub use self::config::config_command_server;
+pub use self::config::{check_config_command, config_command_server, init_command_server};
 pub use self::server::server_command;
@@ -14,39 +14,50 @@
 // limitations under the License.
 
 /// Mwc server commands processing
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use clap::ArgMatches;
 use futures::channel::oneshot;
 
+use super::client::HTTPNodeClient;
 use crate::config::GlobalConfig;
+use crate::core::core::hash::Hash;
 use crate::core::global;
 use crate::p2p::Seeding;
 use crate::servers;
 use crate::tui::ui;
 use mwc_p2p::msg::PeerAddrs;
 use mwc_p2p::PeerAddr;
+use mwc_store::migration::MigrationOptions;
+use mwc_util::file::get_first_line;
 use mwc_util::logger::LogEntry;
+use mwc_util::Mutex;
+use mwc_util::ToHex;
 use std::sync::mpsc;
 
 /// wrap below to allow UI to clean up on stop
 pub fn start_server(
 	config: servers::ServerConfig,
+	config_loader: Option<servers::ConfigLoader>,
 	logs_rx: Option<mpsc::Receiver<LogEntry>>,
 	allow_to_stop: bool,
-	api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+	api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 ) {
-	start_server_tui(config, logs_rx, allow_to_stop, api_chan);
+	start_server_tui(config, config_loader, logs_rx, allow_to_stop, api_chan);
 	exit(0);
 }
 
 fn start_server_tui(
 	config: servers::ServerConfig,
+	mut config_loader: Option<servers::ConfigLoader>,
 	logs_rx: Option<mpsc::Receiver<LogEntry>>,
 	allow_to_stop: bool,
-	api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+	api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 ) {
 	// Run the UI controller.. here for now for simplicity to access
 	// everything it might need
@@ -56,10 +67,18 @@ fn start_server_tui(
 			config,
 			logs_rx,
 			|serv: servers::Server, logs_rx: Option<mpsc::Receiver<LogEntry>>| {
-				let mut controller = ui::Controller::new(logs_rx.unwrap()).unwrap_or_else(|e| {
-					panic!("Error loading UI controller: {}", e);
+				if let Some(loader) = config_loader.take() {
+					serv.set_config_loader(loader);
+				}
+				// The UI is created again after a panic, it reads the same logs
+				let logs_rx = Arc::new(Mutex::new(logs_rx.unwrap()));
+				serv.run_supervised("tui", || {
+					let mut controller = ui::Controller::new(logs_rx.clone()).unwrap_or_else(|e| {
+						panic!("Error loading UI controller: {}", e);
+					});
+					controller.run(&serv);
 				});
-				controller.run(serv);
+				serv.stop();
 			},
 			allow_to_stop,
 			None,
@@ -73,14 +92,19 @@ fn start_server_tui(
 			config,
 			logs_rx,
 			|serv: servers::Server, _: Option<mpsc::Receiver<LogEntry>>| {
-				ctrlc::set_handler(move || {
-					global::request_server_stop();
-				})
-				.expect("Error setting handler for both SIGINT (Ctrl+C) and SIGTERM (kill)");
-				while global::is_server_running() {
+				if let Some(loader) = config_loader.take() {
+					serv.set_config_loader(loader);
+				}
+				let terminate = Arc::new(AtomicBool::new(false));
+				set_termination_handler(terminate.clone());
+				while global::is_server_running() && !terminate.load(Ordering::Relaxed) {
 					thread::sleep(Duration::from_millis(300));
 				}
-				warn!("Received SIGINT (Ctrl+C) or SIGTERM (kill).");
+				if terminate.load(Ordering::Relaxed) {
+					warn!("Received SIGINT (Ctrl+C) or SIGTERM (kill).");
+				} else {
+					warn!("Shutdown is requested.");
+				}
 				serv.stop();
 			},
 			allow_to_stop,
@@ -92,6 +116,216 @@ fn start_server_tui(
 	}
 }
 
+// SIGHUP reloads the config, so on unix only SIGINT and SIGTERM stop the node
+#[cfg(unix)]
+fn set_termination_handler(terminate: Arc<AtomicBool>) {
+	use signal_hook::consts::{SIGINT, SIGTERM};
+	for signal in &[SIGINT, SIGTERM] {
+		signal_hook::flag::register(*signal, terminate.clone())
+			.expect("Error setting handler for both SIGINT (Ctrl+C) and SIGTERM (kill)");
+	}
+}
+
+#[cfg(not(unix))]
+fn set_termination_handler(terminate: Arc<AtomicBool>) {
+	ctrlc::set_handler(move || terminate.store(true, Ordering::Relaxed))
+		.expect("Error setting handler for both SIGINT (Ctrl+C) and SIGTERM (kill)");
+}
+
+// Config reload reads the same file the node was started with
+fn file_config_loader(config_file: PathBuf) -> servers::ConfigLoader {
+	Box::new(move || {
+		let config =
+			GlobalConfig::new(&config_file.to_string_lossy()).map_err(|e| e.to_string())?;
+		let members = config
+			.members
+			.ok_or_else(|| "config file is empty".to_string())?;
+		Ok((members.server, members.logging))
+	})
+}
+
+/// Offline chain data integrity check, prints the first inconsistent height if any
+fn validate_chain_command(config: &servers::ServerConfig, fast: bool, truncate: bool) -> i32 {
+	println!(
+		"Validating the chain data at {}, it might take a while...",
+		config.db_root
+	);
+	let report = match servers::Server::check_chain_integrity(config, fast, truncate) {
+		Ok(report) => report,
+		Err(e) => {
+			println!("Unable to validate the chain data, {}", e);
+			return 1;
+		}
+	};
+
+	let bad_height = match report.first_bad_height {
+		Some(height) => height,
+		None => {
+			println!("Chain data is valid, head at {}", report.head_height);
+			return 0;
+		}
+	};
+	println!(
+		"Chain data is invalid at the head {}, {}",
+		report.head_height,
+		report.error.unwrap_or_default()
+	);
+	if bad_height == report.lowest_height {
+		println!(
+			"First inconsistent height is {} or below, it is the lowest height that can be checked",
+			bad_height
+		);
+	} else {
+		println!("First inconsistent height is {}", bad_height);
+	}
+	if bad_height > 0 {
+		if truncate {
+			println!("Chain is truncated to {}", bad_height - 1);
+		} else {
+			println!(
+				"Run with --truncate to reset the chain to {}",
+				bad_height - 1
+			);
+		}
+	}
+	1
+}
+
+fn migrate_command(config: &servers::ServerConfig, args: &ArgMatches<'_>) -> i32 {
+	let options = MigrationOptions {
+		dry_run: args.is_present("dry-run"),
+		backup_dir: args.value_of("backup").map(PathBuf::from),
+	};
+	let reports = match servers::Server::migrate_stores(config, &options) {
+		Ok(reports) => reports,
+		Err(e) => {
+			println!("Unable to migrate the stores, {}", e);
+			return 1;
+		}
+	};
+	for report in reports {
+		if report.pending.is_empty() {
+			println!(
+				"Store {} is up to date, schema version {}",
+				report.store, report.from_version
+			);
+			continue;
+		}
+		for (version, description) in &report.pending {
+			println!(
+				"Store {} migration {}: {}",
+				report.store, version, description
+			);
+		}
+		if options.dry_run {
+			println!(
+				"Store {} has {} pending migrations, schema version {}",
+				report.store,
+				report.pending.len(),
+				report.from_version
+			);
+		} else {
+			println!(
+				"Store {} is migrated from schema version {} to {}",
+				report.store, report.from_version, report.to_version
+			);
+		}
+		if let Some(backup) = report.backup {
+			println!("Store {} backup is at {}", report.store, backup.display());
+		}
+	}
+	0
+}
+
+// Snapshot is written by the running node through the owner API, the chain data is locked
+// by the node. Restore works with the stopped node only.
+fn snapshot_command(config: &servers::ServerConfig, args: &ArgMatches<'_>) -> i32 {
+	if let Some(out) = args.value_of("out") {
+		let api_secret = get_first_line(config.api_secret_path.clone());
+		let node_client = HTTPNodeClient::new(&config.api_http_addr, api_secret);
+		return node_client.create_snapshot(out.to_string());
+	}
+
+	let snapshot_dir =
+		match args.value_of("restore") {
+			Some(dir) => dir,
+			None => {
+				println!("One of --out or --restore is required, use 'mwc help server snapshot' for details");
+				return 1;
+			}
+		};
+	println!(
+		"Restoring the chain data snapshot {} into {}",
+		snapshot_dir, config.db_root
+	);
+	match servers::Server::restore_snapshot(config, Path::new(snapshot_dir)) {
+		Ok(restore) => {
+			println!(
+				"Chain data is restored, head at {} {}",
+				restore.height,
+				restore.hash.to_hex()
+			);
+			if let Some(backup_dir) = restore.backup_dir {
+				println!("Previous chain data is moved to {}", backup_dir.display());
+			}
+			0
+		}
+		Err(e) => {
+			println!("Unable to restore the chain data snapshot, {}", e);
+			1
+		}
+	}
+}
+
+// Archive is written by the running node through the owner API. Import works with the
+// stopped node and the empty chain only.
+fn utxo_archive_command(config: &servers::ServerConfig, args: &ArgMatches<'_>) -> i32 {
+	if let Some(out) = args.value_of("out") {
+		let api_secret = get_first_line(config.api_secret_path.clone());
+		let node_client = HTTPNodeClient::new(&config.api_http_addr, api_secret);
+		return node_client.write_utxo_archive(out.to_string());
+	}
+
+	let archive = match args.value_of("import") {
+		Some(archive) => archive,
+		None => {
+			println!("One of --out or --import is required, use 'mwc help server utxo-archive' for details");
+			return 1;
+		}
+	};
+	let trusted_hash = match args.value_of("trusted-hash") {
+		Some(hash) => match Hash::from_hex(hash) {
+			Ok(hash) => hash,
+			Err(_) => {
+				println!("Invalid trusted hash {}", hash);
+				return 1;
+			}
+		},
+		None => {
+			println!("--trusted-hash is required for the import, it is the hash of the archive header from a source you trust");
+			return 1;
+		}
+	};
+	println!(
+		"Importing the UTXO archive {} into {}. This might take time...",
+		archive, config.db_root
+	);
+	match servers::Server::import_utxo_archive(config, Path::new(archive), trusted_hash) {
+		Ok(info) => {
+			println!(
+				"UTXO archive is imported, head at {} {}",
+				info.height,
+				info.hash.to_hex()
+			);
+			0
+		}
+		Err(e) => {
+			println!("Unable to import the UTXO archive, {}", e);
+			1
+		}
+	}
+}
+
 /// Handles the server part of the command line, mostly running, starting and
 /// stopping the Mwc blockchain server. Processes all the command line
 /// arguments to build a proper configuration and runs Mwc with that
@@ -100,10 +334,14 @@ pub fn server_command(
 	server_args: Option<&ArgMatches<'_>>,
 	global_config: GlobalConfig,
 	logs_rx: Option<mpsc::Receiver<LogEntry>>,
-	api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>),
+	api_chan: (oneshot::Sender<()>, oneshot::Receiver<()>),
 ) -> i32 {
 	// just get defaults from the global config
 	let mut server_config = global_config.members.as_ref().unwrap().server.clone();
+	let config_loader = global_config
+		.config_file_path
+		.clone()
+		.map(file_config_loader);
 	let mut allow_to_stop = false;
 
 	if let Some(a) = server_args {
@@ -133,6 +371,10 @@ pub fn server_command(
 			server_config.p2p_config.seeds = Some(PeerAddrs { peers });
 		}
 
+		if a.is_present("no-checkpoints") {
+			server_config.use_checkpoints = Some(false);
+		}
+
 		allow_to_stop = a.is_present("allow_to_stop");
 	}
 
@@ -143,7 +385,29 @@ pub fn server_command(
 	if let Some(a) = server_args {
 		match a.subcommand() {
 			("run", _) => {
-				start_server(server_config, logs_rx, allow_to_stop, api_chan);
+				start_server(
+					server_config,
+					config_loader,
+					logs_rx,
+					allow_to_stop,
+					api_chan,
+				);
+			}
+			("validate-chain", Some(validate_args)) => {
+				return validate_chain_command(
+					&server_config,
+					validate_args.is_present("fast"),
+					validate_args.is_present("truncate"),
+				);
+			}
+			("migrate", Some(migrate_args)) => {
+				return migrate_command(&server_config, migrate_args);
+			}
+			("snapshot", Some(snapshot_args)) => {
+				return snapshot_command(&server_config, snapshot_args);
+			}
+			("utxo-archive", Some(archive_args)) => {
+				return utxo_archive_command(&server_config, archive_args);
 			}
 			("", _) => {
 				println!("Subcommand required, use 'mwc help server' for details");
@@ -157,7 +421,13 @@ pub fn server_command(
 			}
 		}
 	} else {
-		start_server(server_config, logs_rx, allow_to_stop, api_chan);
+		start_server(
+			server_config,
+			config_loader,
+			logs_rx,
+			allow_to_stop,
+			api_chan,
+		);
 	}
 	0
 }
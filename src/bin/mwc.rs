@@ -99,6 +99,17 @@ fn real_main() -> i32 {
 			cmd::config_command_server(&chain_type, SERVER_CONFIG_FILE_NAME);
 			return 0;
 		}
+		if let ("init", Some(_)) = server_args.subcommand() {
+			return cmd::init_command_server(&chain_type, SERVER_CONFIG_FILE_NAME);
+		}
+		// Config validation must report problems instead of panicking on them
+		if let ("check-config", Some(_)) = server_args.subcommand() {
+			let config = match server_args.value_of("config_file") {
+				Some(path) => config::GlobalConfig::new(path),
+				None => config::initial_setup_server(&chain_type),
+			};
+			return cmd::check_config_command(config);
+		}
 	}
 
 	// Load relevant config
@@ -130,9 +141,14 @@ fn real_main() -> i32 {
 	let config = node_config.clone().unwrap();
 	let mut logging_config = config.members.as_ref().unwrap().logging.clone().unwrap();
 	logging_config.tui_running = config.members.as_ref().unwrap().server.run_tui;
+	// Offline chain validation prints to the console
+	if let ("server", Some(server_args)) = args.subcommand() {
+		if let ("validate-chain", Some(_)) = server_args.subcommand() {
+			logging_config.tui_running = Some(false);
+		}
+	}
 
-	let api_chan: &'static mut (oneshot::Sender<()>, oneshot::Receiver<()>) =
-		Box::leak(Box::new(oneshot::channel::<()>()));
+	let api_chan = oneshot::channel::<()>();
 
 	let (logs_tx, logs_rx) = if logging_config.tui_running.unwrap() {
 		let (logs_tx, logs_rx) = mpsc::sync_channel::<LogEntry>(200);
@@ -178,6 +194,18 @@ fn real_main() -> i32 {
 		global::init_global_accept_fee_base(tx_fee_base);
 	}
 	info!("Accept Fee Base: {:?}", global::get_accept_fee_base());
+	if let Some(horizon) = config.members.as_ref().unwrap().server.cut_through_horizon {
+		let (min, max) = global::cut_through_horizon_bounds(global::get_chain_type());
+		if horizon < min || horizon > max {
+			error!(
+				"cut_through_horizon {} is out of the valid range {}..{}",
+				horizon, min, max
+			);
+			return 1;
+		}
+		global::init_global_cut_through_horizon(horizon);
+	}
+	info!("Cut Through Horizon: {}", global::cut_through_horizon());
 	log_feature_flags();
 
 	// Execute subcommand
@@ -75,6 +75,19 @@ impl TUIStatusView {
 					))
 				}
 			}
+			SyncStatus::TxHashsetDownload(stats) => {
+				let percent = if stats.total_size == 0 {
+					0
+				} else {
+					stats.downloaded_size * 100 / stats.total_size
+				};
+				Cow::Owned(format!(
+					"Sync step 2/7: Downloading Tx state (zip archive) - {} / {} MB - {}%",
+					stats.downloaded_size / 1_000_000,
+					stats.total_size / 1_000_000,
+					percent
+				))
+			}
 			SyncStatus::ValidatingKernelsHistory => {
 				Cow::Owned("Sync step 3/7: Validating kernels history".to_string())
 			}
@@ -170,6 +183,16 @@ impl TUIStatusView {
 						.child(TextView::new("Disk Usage (GB):              "))
 						.child(TextView::new("0").with_name("disk_usage")),
 				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Component Restarts:           "))
+						.child(TextView::new("0").with_name("component_restarts")),
+				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Orphan Blocks:                "))
+						.child(TextView::new("0").with_name("orphan_blocks")),
+				)
 				.child(
 					LinearLayout::new(Orientation::Horizontal).child(TextView::new(
 						"--------------------------------------------------------",
@@ -276,6 +299,29 @@ impl TUIStatusListener for TUIStatusView {
 		c.call_on_name("disk_usage", |t: &mut TextView| {
 			t.set_content(stats.disk_usage_gb.clone());
 		});
+		c.call_on_name("component_restarts", |t: &mut TextView| {
+			let restarted: Vec<String> = stats
+				.component_stats
+				.iter()
+				.filter(|c| c.restarts > 0)
+				.map(|c| format!("{}: {}", c.name, c.restarts))
+				.collect();
+			if restarted.is_empty() {
+				t.set_content("0");
+			} else {
+				t.set_content(restarted.join(", "));
+			}
+		});
+		c.call_on_name("orphan_blocks", |t: &mut TextView| {
+			let orphans = &stats.orphan_pool_stats;
+			t.set_content(format!(
+				"{} ({} KB), evicted {}, rejected {}",
+				orphans.blocks,
+				orphans.bytes / 1024,
+				orphans.evicted,
+				orphans.rejected
+			));
+		});
 		c.call_on_name("tip_hash", |t: &mut TextView| {
 			t.set_content(stats.chain_stats.last_block_h.to_string() + "...");
 		});
@@ -30,7 +30,7 @@ use cursive::views::{
 	CircularFocus, Dialog, LinearLayout, Panel, SelectView, StackView, TextView, ViewRef,
 };
 use cursive::{CursiveRunnable, CursiveRunner};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 use std::{thread, time};
 
 use super::constants::MAIN_MENU;
@@ -41,13 +41,15 @@ use crate::tui::types::{TUIStatusListener, UIMessage};
 use crate::tui::{logs, menu, mining, peers, status, version};
 use mwc_core::global;
 use mwc_util::logger::LogEntry;
+use mwc_util::Mutex;
 
 pub struct UI {
 	cursive: CursiveRunner<CursiveRunnable>,
 	ui_rx: mpsc::Receiver<UIMessage>,
 	ui_tx: mpsc::Sender<UIMessage>,
 	controller_tx: mpsc::Sender<ControllerMessage>,
-	logs_rx: mpsc::Receiver<LogEntry>,
+	// Shared with the UI that is created after a panic
+	logs_rx: Arc<Mutex<mpsc::Receiver<LogEntry>>>,
 }
 
 fn modify_theme(theme: &mut Theme) {
@@ -66,7 +68,7 @@ impl UI {
 	/// Create a new UI
 	pub fn new(
 		controller_tx: mpsc::Sender<ControllerMessage>,
-		logs_rx: mpsc::Receiver<LogEntry>,
+		logs_rx: Arc<Mutex<mpsc::Receiver<LogEntry>>>,
 	) -> UI {
 		let (ui_tx, ui_rx) = mpsc::channel::<UIMessage>();
 
@@ -140,9 +142,11 @@ impl UI {
 			return false;
 		}
 
-		while let Some(message) = self.logs_rx.try_iter().next() {
+		let logs_rx = self.logs_rx.lock();
+		while let Some(message) = logs_rx.try_iter().next() {
 			logs::TUILogsView::update(&mut self.cursive, message);
 		}
+		drop(logs_rx);
 
 		// Process any pending UI messages
 		while let Some(message) = self.ui_rx.try_iter().next() {
@@ -183,7 +187,7 @@ pub enum ControllerMessage {
 
 impl Controller {
 	/// Create a new controller
-	pub fn new(logs_rx: mpsc::Receiver<LogEntry>) -> Result<Controller, String> {
+	pub fn new(logs_rx: Arc<Mutex<mpsc::Receiver<LogEntry>>>) -> Result<Controller, String> {
 		let (tx, rx) = mpsc::channel::<ControllerMessage>();
 		Ok(Controller {
 			rx,
@@ -191,8 +195,9 @@ impl Controller {
 		})
 	}
 
-	/// Run the controller
-	pub fn run(&mut self, server: Server) {
+	/// Run the controller until the UI is closed or the node is stopping,
+	/// the caller stops the server
+	pub fn run(&mut self, server: &Server) {
 		let stat_update_interval = 1;
 		let mut next_stat_update = Utc::now().timestamp() + stat_update_interval;
 		let delay = time::Duration::from_millis(250);
@@ -202,12 +207,18 @@ impl Controller {
 					ControllerMessage::Shutdown => {
 						warn!("Shutdown in progress, please wait");
 						self.ui.stop();
-						server.stop();
 						return;
 					}
 				}
 			}
 
+			// Stop requested by the owner API
+			if !global::is_server_running() {
+				warn!("Shutdown is requested, please wait");
+				self.ui.stop();
+				return;
+			}
+
 			if Utc::now().timestamp() > next_stat_update {
 				next_stat_update = Utc::now().timestamp() + stat_update_interval;
 				if let Ok(stats) = server.get_server_stats() {
@@ -216,6 +227,5 @@ impl Controller {
 			}
 			thread::sleep(delay);
 		}
-		server.stop();
 	}
 }
@@ -0,0 +1,109 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Key-value backends of the stores. LMDB is the default one. RocksDB is available with
+//! the `rocksdb` feature for the setups where the LMDB map size or the memory mapped
+//! files are a problem (NFS for example). The backend is selected once per process,
+//! before the stores are opened.
+
+use crate::lmdb::Error;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Key-value backend of the stores
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+	/// LMDB, memory mapped B-tree
+	Lmdb,
+	/// RocksDB, LSM tree. Requires the `rocksdb` feature.
+	RocksDb,
+}
+
+impl Default for StoreBackend {
+	fn default() -> StoreBackend {
+		StoreBackend::Lmdb
+	}
+}
+
+// Files that tell the backend of the existing data in the store directory
+#[cfg(feature = "rocksdb")]
+pub(crate) const LMDB_DATA_FILE: &str = "data.mdb";
+pub(crate) const ROCKSDB_MARKER_FILE: &str = "CURRENT";
+
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Select the backend of the stores that are opened later
+pub fn init_backend(backend: StoreBackend) -> Result<(), Error> {
+	if backend == StoreBackend::RocksDb && cfg!(not(feature = "rocksdb")) {
+		return Err(Error::OtherErr(
+			"RocksDB store backend is configured, but the node is built without the rocksdb feature"
+				.to_string(),
+		));
+	}
+	let value = match backend {
+		StoreBackend::Lmdb => 0,
+		StoreBackend::RocksDb => 1,
+	};
+	BACKEND.store(value, Ordering::Relaxed);
+	Ok(())
+}
+
+/// Backend of the stores
+pub fn backend() -> StoreBackend {
+	match BACKEND.load(Ordering::Relaxed) {
+		1 => StoreBackend::RocksDb,
+		_ => StoreBackend::Lmdb,
+	}
+}
+
+/// Key and value pairs in the key order. The iterator sees the data of the moment it was
+/// created, a read failure is the last item.
+pub type KvIter = Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>), Error>>>;
+
+/// Raw key-value database. The serialization and the batches are built on top of it
+/// by the [`Store`](../lmdb/struct.Store.html).
+pub trait KvStore: Send + Sync {
+	/// Committed value of the key
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+	/// Committed entries with the key prefix, in the key order
+	fn iter(&self, prefix: &[u8]) -> Result<KvIter, Error>;
+	/// Read only transaction with a consistent view of the data
+	fn read_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error>;
+	/// Write transaction, the writers wait for each other
+	fn write_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error>;
+	/// Consistent copy of the data into the empty directory
+	fn copy_to(&self, path: &str) -> Result<(), Error>;
+}
+
+/// Transaction of the key-value database. The write transaction sees its own changes.
+pub trait KvTxn {
+	/// Whether the transaction can write
+	fn is_write(&self) -> bool;
+	/// Value of the key
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+	/// Write the value of the key
+	fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error>;
+	/// Delete the key
+	fn delete(&self, key: &[u8]) -> Result<(), Error>;
+	/// Nested write transaction, its changes are merged into this one on commit and
+	/// abandoned otherwise
+	fn child(&mut self) -> Result<Box<dyn KvTxn + '_>, Error>;
+	/// Apply the changes
+	fn commit(self: Box<Self>) -> Result<(), Error>;
+}
+
+pub(crate) fn read_batch_err() -> Error {
+	Error::BatchTypeError("expected write batch, got read".to_string())
+}
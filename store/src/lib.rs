@@ -21,6 +21,9 @@
 #![deny(unused_mut)]
 #![warn(missing_docs)]
 
+#[cfg(feature = "rocksdb")]
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
 #[macro_use]
@@ -29,16 +32,21 @@ extern crate mwc_util as util;
 
 //use mwc_core as core;
 
+pub mod kv;
 pub mod leaf_set;
 pub mod lmdb;
+pub mod migration;
 pub mod pmmr;
 pub mod prune_list;
+#[cfg(feature = "rocksdb")]
+mod rocks;
 pub mod types;
 
 const SEP: u8 = b':';
 
 use byteorder::{BigEndian, WriteBytesExt};
 
+pub use crate::kv::{init_backend, StoreBackend};
 pub use crate::lmdb::*;
 
 /// Build a db key from a prefix and a byte vector identifier.
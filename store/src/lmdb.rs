@@ -13,15 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Storage of core types using LMDB.
+//! Storage of core types using LMDB or another key-value backend.
 
 use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
 use lmdb_zero as lmdb;
 use lmdb_zero::traits::CreateCursor;
 use lmdb_zero::LmdbResultExt;
 
+use crate::kv::{self, KvIter, KvStore, KvTxn, StoreBackend};
 use crate::mwc_core::global;
 use crate::mwc_core::ser::{self, DeserializationMode, ProtocolVersion};
 use crate::util::RwLock;
@@ -84,18 +86,17 @@ where
 
 const DEFAULT_DB_VERSION: ProtocolVersion = ProtocolVersion(3);
 
-/// LMDB-backed store facilitating data access and serialization. All writes
-/// are done through a Batch abstraction providing atomicity.
+/// Store facilitating data access and serialization on top of the key-value backend
+/// (LMDB by default, see `kv`). All writes are done through a Batch abstraction
+/// providing atomicity.
 pub struct Store {
-	env: Arc<lmdb::Environment>,
-	db: Arc<RwLock<Option<Arc<lmdb::Database<'static>>>>>,
+	db: Arc<dyn KvStore>,
 	name: String,
 	version: ProtocolVersion,
-	alloc_chunk_size: usize,
 }
 
 impl Store {
-	/// Create a new LMDB env under the provided directory.
+	/// Create a new store env under the provided directory.
 	/// By default creates an environment named "lmdb".
 	/// Be aware of transactional semantics in lmdb
 	/// (transactions are per environment, not per database).
@@ -121,53 +122,31 @@ impl Store {
 			))
 		})?;
 
-		let mut env_builder = lmdb::EnvBuilder::new()?;
-		env_builder.set_maxdbs(8)?;
-
-		if let Some(max_readers) = max_readers {
-			env_builder.set_maxreaders(max_readers)?;
-		}
-
-		let alloc_chunk_size = match global::is_production_mode() {
-			true => ALLOC_CHUNK_SIZE_DEFAULT,
-			false => ALLOC_CHUNK_SIZE_DEFAULT_TEST,
+		let db: Arc<dyn KvStore> = match kv::backend() {
+			StoreBackend::Lmdb => Arc::new(LmdbStore::open(&full_path, &db_name, max_readers)?),
+			#[cfg(feature = "rocksdb")]
+			StoreBackend::RocksDb => Arc::new(crate::rocks::RocksStore::open(&full_path, &db_name)?),
+			#[cfg(not(feature = "rocksdb"))]
+			StoreBackend::RocksDb => {
+				return Err(Error::OtherErr(
+					"the node is built without the rocksdb feature".to_string(),
+				))
+			}
 		};
-
-		let env = unsafe { env_builder.open(&full_path, lmdb::open::NOTLS, 0o600)? };
-
-		debug!("DB Mapsize for {} is {}", full_path, env.info()?.mapsize);
-		let res = Store {
-			env: Arc::new(env),
-			db: Arc::new(RwLock::new(None)),
+		Ok(Store {
+			db,
 			name: db_name,
 			version: DEFAULT_DB_VERSION,
-			alloc_chunk_size,
-		};
-
-		{
-			let mut w = res.db.write();
-			*w = Some(Arc::new(lmdb::Database::open(
-				res.env.clone(),
-				Some(&res.name),
-				&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
-			)?));
-		}
-		Ok(res)
+		})
 	}
 
 	/// Construct a new store using a specific protocol version.
 	/// Permits access to the db with legacy protocol versions for db migrations.
 	pub fn with_version(&self, version: ProtocolVersion) -> Store {
-		let alloc_chunk_size = match global::is_production_mode() {
-			true => ALLOC_CHUNK_SIZE_DEFAULT,
-			false => ALLOC_CHUNK_SIZE_DEFAULT_TEST,
-		};
 		Store {
-			env: self.env.clone(),
 			db: self.db.clone(),
 			name: self.name.clone(),
 			version,
-			alloc_chunk_size,
 		}
 	}
 
@@ -176,101 +155,15 @@ impl Store {
 		self.version
 	}
 
-	/// Opens the database environment
-	pub fn open(&self) -> Result<(), Error> {
-		let mut w = self.db.write();
-		*w = Some(Arc::new(lmdb::Database::open(
-			self.env.clone(),
-			Some(&self.name),
-			&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
-		)?));
-		Ok(())
-	}
-
-	/// Determines whether the environment needs a resize based on a simple percentage threshold
-	pub fn needs_resize(&self) -> Result<bool, Error> {
-		let env_info = self.env.info()?;
-		let stat = self.env.stat()?;
-
-		let size_used = stat.psize as usize * env_info.last_pgno;
-		trace!("DB map size: {}", env_info.mapsize);
-		trace!("Space used: {}", size_used);
-		trace!("Space remaining: {}", env_info.mapsize - size_used);
-		let resize_percent = RESIZE_PERCENT;
-		trace!(
-			"Percent used: {:.*}  Percent threshold: {:.*}",
-			4,
-			size_used as f64 / env_info.mapsize as f64,
-			4,
-			resize_percent
-		);
-
-		if size_used as f32 / env_info.mapsize as f32 > resize_percent
-			|| env_info.mapsize < self.alloc_chunk_size
-		{
-			trace!("Resize threshold met (percent-based)");
-			Ok(true)
-		} else {
-			trace!("Resize threshold not met (percent-based)");
-			Ok(false)
-		}
-	}
-
-	/// Increments the database size by as many ALLOC_CHUNK_SIZES
-	/// to give a minimum threshold of free space
-	pub fn do_resize(&self) -> Result<(), Error> {
-		let env_info = self.env.info()?;
-		let stat = self.env.stat()?;
-		let size_used = stat.psize as usize * env_info.last_pgno;
-
-		let new_mapsize = if env_info.mapsize < self.alloc_chunk_size {
-			self.alloc_chunk_size
-		} else {
-			let mut tot = env_info.mapsize;
-			while size_used as f32 / tot as f32 > RESIZE_MIN_TARGET_PERCENT {
-				tot += self.alloc_chunk_size;
-			}
-			tot
-		};
-
-		// close
-		let mut w = self.db.write();
-		*w = None;
-
-		unsafe {
-			self.env.set_mapsize(new_mapsize)?;
-		}
-
-		*w = Some(Arc::new(lmdb::Database::open(
-			self.env.clone(),
-			Some(&self.name),
-			&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
-		)?));
-
-		info!(
-			"Resized database from {} to {}",
-			env_info.mapsize, new_mapsize
-		);
-		Ok(())
+	/// Name of the database in the environment
+	pub fn name(&self) -> &str {
+		&self.name
 	}
 
-	/// Gets a value from the db, provided its key.
-	/// Deserializes the retrieved data using the provided function.
-	pub fn get_with<F, T>(
-		&self,
-		key: &[u8],
-		access: &lmdb::ConstAccessor<'_>,
-		db: &lmdb::Database<'_>,
-		deserialize: F,
-	) -> Result<Option<T>, Error>
-	where
-		F: Fn(&[u8], &[u8]) -> Result<T, Error>,
-	{
-		let res: Option<&[u8]> = access.get(db, key).to_opt()?;
-		match res {
-			None => Ok(None),
-			Some(res) => deserialize(key, res).map(Some),
-		}
+	/// Consistent compacted copy of the environment into the existing empty directory.
+	/// The copy is made within a read transaction, the writers are not blocked.
+	pub fn copy_to(&self, path: &str) -> Result<(), Error> {
+		self.db.copy_to(path)
 	}
 
 	/// Gets a `Readable` value from the db, provided its key.
@@ -280,32 +173,21 @@ impl Store {
 		key: &[u8],
 		deser_mode: Option<DeserializationMode>,
 	) -> Result<Option<T>, Error> {
-		let lock = self.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-		let txn = lmdb::ReadTransaction::new(self.env.clone())?;
-		let access = txn.access();
 		let d = match deser_mode {
 			Some(d) => d,
 			_ => DeserializationMode::default(),
 		};
-		self.get_with(key, &access, &db, |_, mut data| {
-			ser::deserialize(&mut data, self.protocol_version(), d).map_err(From::from)
-		})
+		match self.db.get(key)? {
+			None => Ok(None),
+			Some(data) => ser::deserialize(&mut &data[..], self.protocol_version(), d)
+				.map(Some)
+				.map_err(From::from),
+		}
 	}
 
 	/// Whether the provided key exists
 	pub fn exists(&self, key: &[u8]) -> Result<bool, Error> {
-		let lock = self.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-		let txn = lmdb::ReadTransaction::new(self.env.clone())?;
-		let access = txn.access();
-
-		let res: Option<&lmdb::Ignore> = access.get(db, key).to_opt()?;
-		Ok(res.is_some())
+		Ok(self.db.get(key)?.is_some())
 	}
 
 	/// Produces an iterator from the provided key prefix.
@@ -313,40 +195,22 @@ impl Store {
 	where
 		F: Fn(&[u8], &[u8]) -> Result<T, Error>,
 	{
-		let lock = self.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-		let tx = Arc::new(lmdb::ReadTransaction::new(self.env.clone())?);
-		let cursor = Arc::new(tx.cursor(db.clone())?);
-		Ok(PrefixIterator::new(tx, cursor, prefix, deserialize))
+		Ok(PrefixIterator::new(self.db.iter(prefix)?, deserialize))
 	}
 
 	/// Builds a new read only batch to be used with this store.
 	pub fn batch_read(&self) -> Result<Batch<'_>, Error> {
-		// check if the db needs resizing before returning the batch
-		if self.needs_resize()? {
-			self.do_resize()?;
-		}
-		let tx = lmdb::ReadTransaction::new(self.env.clone())?;
 		Ok(Batch {
 			store: self,
-			tx_w: None,
-			tx_r: Some(tx),
+			tx: self.db.read_txn()?,
 		})
 	}
 
 	/// Builds a new batch with write access to be used with this store.
 	pub fn batch_write(&self) -> Result<Batch<'_>, Error> {
-		// check if the db needs resizing before returning the batch
-		if self.needs_resize()? {
-			self.do_resize()?;
-		}
-		let tx = lmdb::WriteTransaction::new(self.env.clone())?;
 		Ok(Batch {
 			store: self,
-			tx_w: Some(tx),
-			tx_r: None,
+			tx: self.db.write_txn()?,
 		})
 	}
 }
@@ -354,25 +218,13 @@ impl Store {
 /// Batch to write multiple Writeables to db in an atomic manner.
 pub struct Batch<'a> {
 	store: &'a Store,
-	tx_w: Option<lmdb::WriteTransaction<'a>>,
-	tx_r: Option<lmdb::ReadTransaction<'a>>,
+	tx: Box<dyn KvTxn + 'a>,
 }
 
 impl<'a> Batch<'a> {
 	/// Writes a single key/value pair to the db
 	pub fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
-		let lock = self.store.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-		if let Some(tx) = &self.tx_w {
-			tx.access().put(db, key, value, lmdb::put::Flags::empty())?;
-			Ok(())
-		} else {
-			return Err(Error::BatchTypeError(
-				"expected write batch, got read".to_string(),
-			));
-		}
+		self.tx.put(key, value)
 	}
 
 	/// Writes a single key and its `Writeable` value to the db.
@@ -407,39 +259,16 @@ impl<'a> Batch<'a> {
 	where
 		F: Fn(&[u8], &[u8]) -> Result<T, Error>,
 	{
-		let lock = self.store.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-
-		if let Some(tx) = &self.tx_r {
-			self.store.get_with(key, &tx.access(), &db, deserialize)
-		} else if let Some(tx) = &self.tx_w {
-			self.store.get_with(key, &tx.access(), &db, deserialize)
-		} else {
-			Err(Error::BatchTypeError(
-				"No Read/Write transaction is found".to_string(),
-			))
+		match self.tx.get(key)? {
+			None => Ok(None),
+			Some(data) => deserialize(key, &data).map(Some),
 		}
 	}
 
 	/// Whether the provided key exists.
 	/// This is in the context of the current write transaction.
 	pub fn exists(&self, key: &[u8]) -> Result<bool, Error> {
-		let lock = self.store.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-
-		if let Some(tx) = &self.tx_r {
-			Ok(tx.access().get::<[u8], [u8]>(db, key).to_opt()?.is_some())
-		} else if let Some(tx) = &self.tx_w {
-			Ok(tx.access().get::<[u8], [u8]>(db, key).to_opt()?.is_some())
-		} else {
-			Err(Error::BatchTypeError(
-				"No Read/Write transaction is found".to_string(),
-			))
-		}
+		Ok(self.tx.get(key)?.is_some())
 	}
 
 	/// Produces an iterator from the provided key prefix.
@@ -470,63 +299,43 @@ impl<'a> Batch<'a> {
 
 	/// Deletes a key/value pair from the db
 	pub fn delete(&self, key: &[u8]) -> Result<(), Error> {
-		let lock = self.store.db.read();
-		let db = lock
-			.as_ref()
-			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
-		if let Some(tx) = &self.tx_w {
-			tx.access().del_key(db, key)?;
-			Ok(())
-		} else {
-			Err(Error::BatchTypeError(
-				"expected write batch, got read".to_string(),
-			))
-		}
+		self.tx.delete(key)
 	}
 
 	/// Writes the batch to db
 	pub fn commit(self) -> Result<(), Error> {
-		if let Some(tx) = self.tx_w {
-			tx.commit()?;
-			Ok(())
-		} else {
-			Err(Error::BatchTypeError(
-				"expected write batch, got read".to_string(),
-			))
+		if !self.tx.is_write() {
+			return Err(kv::read_batch_err());
 		}
+		self.tx.commit()
 	}
 
 	/// Creates a child of this batch. It will be merged with its parent on
 	/// commit, abandoned otherwise.
 	pub fn child(&mut self) -> Result<Batch<'_>, Error> {
-		if self.tx_r.is_some() {
+		if !self.tx.is_write() {
 			return Err(Error::BatchTypeError(
 				"Method 'child' called for read batch".to_string(),
 			));
 		}
-
 		Ok(Batch {
 			store: self.store,
-			tx_r: None,
-			tx_w: match self.tx_w.as_mut() {
-				Some(tx) => Some(tx.child_tx()?),
-				None => None,
-			},
+			tx: self.tx.child()?,
 		})
 	}
 }
 
 /// An iterator based on key prefix.
 /// Caller is responsible for deserialization of the data.
+/// The iteration stops on the read failure of the backend, the failure is available
+/// from `error()` then.
 pub struct PrefixIterator<F, T>
 where
 	F: Fn(&[u8], &[u8]) -> Result<T, Error>,
 {
-	tx: Arc<lmdb::ReadTransaction<'static>>,
-	cursor: Arc<lmdb::Cursor<'static, 'static>>,
-	seek: bool,
-	prefix: Vec<u8>,
+	iter: KvIter,
 	deserialize: F,
+	error: Option<Error>,
 }
 
 impl<F, T> Iterator for PrefixIterator<F, T>
@@ -536,21 +345,17 @@ where
 	type Item = T;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		let access = self.tx.access();
-		let cursor = Arc::get_mut(&mut self.cursor).expect("failed to get cursor");
-		let kv: Result<(&[u8], &[u8]), _> = if self.seek {
-			cursor.next(&access)
-		} else {
-			self.seek = true;
-			cursor.seek_range_k(&access, &self.prefix[..])
-		};
-		kv.ok()
-			.filter(|(k, _)| k.starts_with(self.prefix.as_slice()))
-			.map(|(k, v)| match (self.deserialize)(k, v) {
-				Ok(v) => Some(v),
-				Err(_) => None,
-			})
-			.flatten()
+		if self.error.is_some() {
+			return None;
+		}
+		match self.iter.next()? {
+			Ok((k, v)) => (self.deserialize)(&k, &v).ok(),
+			Err(e) => {
+				error!("Store iterator failure, {}", e);
+				self.error = Some(e);
+				None
+			}
+		}
 	}
 }
 
@@ -559,18 +364,299 @@ where
 	F: Fn(&[u8], &[u8]) -> Result<T, Error>,
 {
 	/// Initialize a new prefix iterator.
-	pub fn new(
-		tx: Arc<lmdb::ReadTransaction<'static>>,
-		cursor: Arc<lmdb::Cursor<'static, 'static>>,
-		prefix: &[u8],
-		deserialize: F,
-	) -> PrefixIterator<F, T> {
+	pub fn new(iter: KvIter, deserialize: F) -> PrefixIterator<F, T> {
 		PrefixIterator {
+			iter,
+			deserialize,
+			error: None,
+		}
+	}
+
+	/// Read failure that stopped the iteration
+	pub fn error(&self) -> Option<&Error> {
+		self.error.as_ref()
+	}
+}
+
+/// LMDB backend
+struct LmdbStore {
+	env: Arc<lmdb::Environment>,
+	db: RwLock<Option<Arc<lmdb::Database<'static>>>>,
+	name: String,
+	alloc_chunk_size: usize,
+}
+
+impl LmdbStore {
+	fn open(full_path: &str, db_name: &str, max_readers: Option<u32>) -> Result<LmdbStore, Error> {
+		if Path::new(full_path).join(kv::ROCKSDB_MARKER_FILE).exists() {
+			return Err(Error::FileErr(format!(
+				"{} has RocksDB data, set db_backend = \"rocksdb\" or move the data away",
+				full_path
+			)));
+		}
+
+		let mut env_builder = lmdb::EnvBuilder::new()?;
+		env_builder.set_maxdbs(8)?;
+
+		if let Some(max_readers) = max_readers {
+			env_builder.set_maxreaders(max_readers)?;
+		}
+
+		let alloc_chunk_size = match global::is_production_mode() {
+			true => ALLOC_CHUNK_SIZE_DEFAULT,
+			false => ALLOC_CHUNK_SIZE_DEFAULT_TEST,
+		};
+
+		let env = unsafe { env_builder.open(full_path, lmdb::open::NOTLS, 0o600)? };
+
+		debug!("DB Mapsize for {} is {}", full_path, env.info()?.mapsize);
+		let env = Arc::new(env);
+		let db = lmdb::Database::open(
+			env.clone(),
+			Some(db_name),
+			&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
+		)?;
+		Ok(LmdbStore {
+			env,
+			db: RwLock::new(Some(Arc::new(db))),
+			name: db_name.to_owned(),
+			alloc_chunk_size,
+		})
+	}
+
+	/// Determines whether the environment needs a resize based on a simple percentage threshold
+	fn needs_resize(&self) -> Result<bool, Error> {
+		let env_info = self.env.info()?;
+		let stat = self.env.stat()?;
+
+		let size_used = stat.psize as usize * env_info.last_pgno;
+		trace!("DB map size: {}", env_info.mapsize);
+		trace!("Space used: {}", size_used);
+		trace!("Space remaining: {}", env_info.mapsize - size_used);
+		let resize_percent = RESIZE_PERCENT;
+		trace!(
+			"Percent used: {:.*}  Percent threshold: {:.*}",
+			4,
+			size_used as f64 / env_info.mapsize as f64,
+			4,
+			resize_percent
+		);
+
+		if size_used as f32 / env_info.mapsize as f32 > resize_percent
+			|| env_info.mapsize < self.alloc_chunk_size
+		{
+			trace!("Resize threshold met (percent-based)");
+			Ok(true)
+		} else {
+			trace!("Resize threshold not met (percent-based)");
+			Ok(false)
+		}
+	}
+
+	/// Increments the database size by as many ALLOC_CHUNK_SIZES
+	/// to give a minimum threshold of free space
+	fn do_resize(&self) -> Result<(), Error> {
+		let env_info = self.env.info()?;
+		let stat = self.env.stat()?;
+		let size_used = stat.psize as usize * env_info.last_pgno;
+
+		let new_mapsize = if env_info.mapsize < self.alloc_chunk_size {
+			self.alloc_chunk_size
+		} else {
+			let mut tot = env_info.mapsize;
+			while size_used as f32 / tot as f32 > RESIZE_MIN_TARGET_PERCENT {
+				tot += self.alloc_chunk_size;
+			}
+			tot
+		};
+
+		// close
+		let mut w = self.db.write();
+		*w = None;
+
+		unsafe {
+			self.env.set_mapsize(new_mapsize)?;
+		}
+
+		*w = Some(Arc::new(lmdb::Database::open(
+			self.env.clone(),
+			Some(&self.name),
+			&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
+		)?));
+
+		info!(
+			"Resized database from {} to {}",
+			env_info.mapsize, new_mapsize
+		);
+		Ok(())
+	}
+
+	fn resize_if_needed(&self) -> Result<(), Error> {
+		// check if the db needs resizing before starting the transaction
+		if self.needs_resize()? {
+			self.do_resize()?;
+		}
+		Ok(())
+	}
+}
+
+fn db_is_none() -> Error {
+	Error::NotFoundErr("chain db is None".to_string())
+}
+
+fn lmdb_get(
+	access: &lmdb::ConstAccessor<'_>,
+	db: &lmdb::Database<'_>,
+	key: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+	let res: Option<&[u8]> = access.get(db, key).to_opt()?;
+	Ok(res.map(|v| v.to_vec()))
+}
+
+impl KvStore for LmdbStore {
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		let lock = self.db.read();
+		let db = lock.as_ref().ok_or_else(db_is_none)?;
+		let txn = lmdb::ReadTransaction::new(self.env.clone())?;
+		let access = txn.access();
+		lmdb_get(&access, &db, key)
+	}
+
+	fn iter(&self, prefix: &[u8]) -> Result<KvIter, Error> {
+		let lock = self.db.read();
+		let db = lock.as_ref().ok_or_else(db_is_none)?;
+		let tx = Arc::new(lmdb::ReadTransaction::new(self.env.clone())?);
+		let cursor = Arc::new(tx.cursor(db.clone())?);
+		Ok(Box::new(LmdbIter {
 			tx,
 			cursor,
 			seek: false,
 			prefix: prefix.to_vec(),
-			deserialize,
+		}))
+	}
+
+	fn read_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		self.resize_if_needed()?;
+		let tx = lmdb::ReadTransaction::new(self.env.clone())?;
+		Ok(Box::new(LmdbTxn {
+			store: self,
+			tx: LmdbTx::Read(tx),
+		}))
+	}
+
+	fn write_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		self.resize_if_needed()?;
+		let tx = lmdb::WriteTransaction::new(self.env.clone())?;
+		Ok(Box::new(LmdbTxn {
+			store: self,
+			tx: LmdbTx::Write(tx),
+		}))
+	}
+
+	fn copy_to(&self, path: &str) -> Result<(), Error> {
+		self.env.copy(path, lmdb::copy::COMPACT)?;
+		Ok(())
+	}
+}
+
+enum LmdbTx<'a> {
+	Read(lmdb::ReadTransaction<'a>),
+	Write(lmdb::WriteTransaction<'a>),
+}
+
+struct LmdbTxn<'a> {
+	store: &'a LmdbStore,
+	tx: LmdbTx<'a>,
+}
+
+impl<'a> KvTxn for LmdbTxn<'a> {
+	fn is_write(&self) -> bool {
+		match self.tx {
+			LmdbTx::Read(_) => false,
+			LmdbTx::Write(_) => true,
+		}
+	}
+
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		let lock = self.store.db.read();
+		let db = lock.as_ref().ok_or_else(db_is_none)?;
+		match &self.tx {
+			LmdbTx::Read(tx) => lmdb_get(&tx.access(), &db, key),
+			LmdbTx::Write(tx) => lmdb_get(&tx.access(), &db, key),
+		}
+	}
+
+	fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+		let lock = self.store.db.read();
+		let db = lock.as_ref().ok_or_else(db_is_none)?;
+		match &self.tx {
+			LmdbTx::Write(tx) => {
+				tx.access().put(db, key, value, lmdb::put::Flags::empty())?;
+				Ok(())
+			}
+			LmdbTx::Read(_) => Err(kv::read_batch_err()),
+		}
+	}
+
+	fn delete(&self, key: &[u8]) -> Result<(), Error> {
+		let lock = self.store.db.read();
+		let db = lock.as_ref().ok_or_else(db_is_none)?;
+		match &self.tx {
+			LmdbTx::Write(tx) => {
+				tx.access().del_key(db, key)?;
+				Ok(())
+			}
+			LmdbTx::Read(_) => Err(kv::read_batch_err()),
+		}
+	}
+
+	fn child(&mut self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		match &mut self.tx {
+			LmdbTx::Write(tx) => Ok(Box::new(LmdbTxn {
+				store: self.store,
+				tx: LmdbTx::Write(tx.child_tx()?),
+			})),
+			LmdbTx::Read(_) => Err(kv::read_batch_err()),
+		}
+	}
+
+	fn commit(self: Box<Self>) -> Result<(), Error> {
+		match self.tx {
+			LmdbTx::Write(tx) => {
+				tx.commit()?;
+				Ok(())
+			}
+			LmdbTx::Read(_) => Err(kv::read_batch_err()),
+		}
+	}
+}
+
+/// LMDB cursor over the key prefix
+struct LmdbIter {
+	tx: Arc<lmdb::ReadTransaction<'static>>,
+	cursor: Arc<lmdb::Cursor<'static, 'static>>,
+	seek: bool,
+	prefix: Vec<u8>,
+}
+
+impl Iterator for LmdbIter {
+	type Item = Result<(Vec<u8>, Vec<u8>), Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let access = self.tx.access();
+		let cursor = Arc::get_mut(&mut self.cursor).expect("failed to get cursor");
+		let kv: Result<(&[u8], &[u8]), _> = if self.seek {
+			cursor.next(&access)
+		} else {
+			self.seek = true;
+			cursor.seek_range_k(&access, &self.prefix[..])
+		};
+		// not found is the end of the data
+		match kv.to_opt() {
+			Ok(kv) => kv
+				.filter(|(k, _)| k.starts_with(self.prefix.as_slice()))
+				.map(|(k, v)| Ok((k.to_vec(), v.to_vec()))),
+			Err(e) => Some(Err(e.into())),
 		}
 	}
 }
@@ -0,0 +1,151 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versioned schema migrations of the stores. Every store keeps the version of the last
+//! applied migration, the pending migrations run in order when the store is opened.
+//! The version is recorded after each migration, so an interrupted run continues
+//! from the failed one.
+
+use crate::lmdb::{Error, Store};
+use std::fs;
+use std::path::PathBuf;
+
+// Prefix is not used by any store
+const SCHEMA_VERSION_KEY: &[u8] = b"~:schema_version";
+
+/// Single schema change of the store `T`
+pub struct Migration<T> {
+	/// Schema version after the migration, versions start from 1 and grow by 1
+	pub version: u32,
+	/// What the migration changes, for the logs and the dry run report
+	pub description: &'static str,
+	/// Migration itself
+	pub run: fn(&T) -> Result<(), Error>,
+}
+
+/// How to run the pending migrations
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+	/// Report the pending migrations without running them
+	pub dry_run: bool,
+	/// Directory for the copy of the store made before the first pending migration
+	pub backup_dir: Option<PathBuf>,
+}
+
+/// Result of the migrations run
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+	/// Database name
+	pub store: String,
+	/// Schema version before the run
+	pub from_version: u32,
+	/// Schema version after the run, it is unchanged by the dry run
+	pub to_version: u32,
+	/// Pending migrations, version and description
+	pub pending: Vec<(u32, String)>,
+	/// Backup copy of the store, if it was made
+	pub backup: Option<PathBuf>,
+}
+
+/// Current schema version of the store, 0 if no migration was ever applied
+pub fn schema_version(db: &Store) -> Result<u32, Error> {
+	Ok(db.get_ser::<u32>(SCHEMA_VERSION_KEY, None)?.unwrap_or(0))
+}
+
+/// Run the pending migrations of the store. `db` is the store `target` works with.
+/// Store with the schema newer than the known migrations is refused, it was written by
+/// a newer node.
+pub fn run_migrations<T>(
+	db: &Store,
+	target: &T,
+	migrations: &[Migration<T>],
+	options: &MigrationOptions,
+) -> Result<MigrationReport, Error> {
+	for (i, m) in migrations.iter().enumerate() {
+		if m.version != i as u32 + 1 {
+			return Err(Error::OtherErr(format!(
+				"Migration '{}' of {} has version {}, expected {}",
+				m.description,
+				db.name(),
+				m.version,
+				i + 1
+			)));
+		}
+	}
+
+	let from_version = schema_version(db)?;
+	let latest = migrations.len() as u32;
+	if from_version > latest {
+		return Err(Error::OtherErr(format!(
+			"Store {} has schema version {}, this node supports up to {}. Please upgrade the node",
+			db.name(),
+			from_version,
+			latest
+		)));
+	}
+
+	let pending: Vec<&Migration<T>> = migrations
+		.iter()
+		.filter(|m| m.version > from_version)
+		.collect();
+	let mut report = MigrationReport {
+		store: db.name().to_string(),
+		from_version,
+		to_version: from_version,
+		pending: pending
+			.iter()
+			.map(|m| (m.version, m.description.to_string()))
+			.collect(),
+		backup: None,
+	};
+	if pending.is_empty() || options.dry_run {
+		return Ok(report);
+	}
+
+	if let Some(backup_dir) = &options.backup_dir {
+		let backup = backup_dir.join(format!("{}_v{}", db.name(), from_version));
+		fs::create_dir_all(&backup).map_err(|e| {
+			Error::FileErr(format!(
+				"Unable to create backup directory {}, {}",
+				backup.display(),
+				e
+			))
+		})?;
+		let path = backup
+			.to_str()
+			.ok_or_else(|| Error::FileErr(format!("Invalid backup path {}", backup.display())))?;
+		db.copy_to(path)?;
+		info!(
+			"Store {} is copied to {} before the migration",
+			db.name(),
+			path
+		);
+		report.backup = Some(backup);
+	}
+
+	for m in pending {
+		info!(
+			"Migrating store {} to schema version {}, {}",
+			db.name(),
+			m.version,
+			m.description
+		);
+		(m.run)(target)?;
+		let batch = db.batch_write()?;
+		batch.put_ser(SCHEMA_VERSION_KEY, &m.version)?;
+		batch.commit()?;
+		report.to_version = m.version;
+	}
+	Ok(report)
+}
@@ -0,0 +1,330 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RocksDB backend of the stores. The database name of the store is a column family,
+//! so the stores of the same environment share one RocksDB instance like they share
+//! the LMDB environment.
+//! RocksDB doesn't have the nested transactions, the write transaction collects the
+//! changes in memory and applies them as a single write batch on commit. The writers
+//! are serialized, the readers see a snapshot.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{
+	BoundColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, MultiThreaded, Options,
+	ReadOptions, SnapshotWithThreadMode, WriteBatch,
+};
+
+use crate::kv::{self, KvIter, KvStore, KvTxn};
+use crate::lmdb::Error;
+use crate::util::{Mutex, MutexGuard};
+
+type DB = rocksdb::DBWithThreadMode<MultiThreaded>;
+type Snapshot<'a> = SnapshotWithThreadMode<'a, DB>;
+
+// number of entries the prefix iterator reads by one RocksDB iterator
+const ITER_CHUNK_SIZE: usize = 1000;
+
+lazy_static! {
+	// Opened RocksDB instances by the path, one instance serves all the column families
+	static ref INSTANCES: Mutex<HashMap<String, Weak<RocksEnv>>> = Mutex::new(HashMap::new());
+}
+
+// RocksDB instance, the writers of all its column families are serialized
+// the same way as they are in the LMDB environment
+struct RocksEnv {
+	db: DB,
+	write_lock: Mutex<()>,
+}
+
+fn rocks_err(e: rocksdb::Error) -> Error {
+	Error::OtherErr(format!("RocksDB error, {}", e))
+}
+
+/// RocksDB column family as a key-value store
+pub struct RocksStore {
+	env: Arc<RocksEnv>,
+	cf_name: String,
+}
+
+impl RocksStore {
+	/// Open (or create) the column family `db_name` of the database at `full_path`
+	pub fn open(full_path: &str, db_name: &str) -> Result<RocksStore, Error> {
+		if Path::new(full_path).join(kv::LMDB_DATA_FILE).exists() {
+			return Err(Error::FileErr(format!(
+				"{} has LMDB data, set db_backend = \"lmdb\" or move the data away",
+				full_path
+			)));
+		}
+
+		let mut instances = INSTANCES.lock();
+		let env = match instances.get(full_path).and_then(|w| w.upgrade()) {
+			Some(env) => env,
+			None => {
+				let mut opts = Options::default();
+				opts.create_if_missing(true);
+				opts.create_missing_column_families(true);
+				let cfs = match DB::list_cf(&opts, full_path) {
+					Ok(cfs) => cfs,
+					Err(_) => vec![],
+				};
+				let cfs = cfs
+					.into_iter()
+					.map(|name| ColumnFamilyDescriptor::new(name, Options::default()));
+				let env = Arc::new(RocksEnv {
+					db: DB::open_cf_descriptors(&opts, full_path, cfs).map_err(rocks_err)?,
+					write_lock: Mutex::new(()),
+				});
+				debug!("Opened RocksDB at {}", full_path);
+				instances.insert(full_path.to_owned(), Arc::downgrade(&env));
+				env
+			}
+		};
+		if env.db.cf_handle(db_name).is_none() {
+			env.db
+				.create_cf(db_name, &Options::default())
+				.map_err(rocks_err)?;
+		}
+		Ok(RocksStore {
+			env,
+			cf_name: db_name.to_owned(),
+		})
+	}
+
+	fn cf(&self) -> Result<Arc<BoundColumnFamily<'_>>, Error> {
+		self.env
+			.db
+			.cf_handle(&self.cf_name)
+			.ok_or_else(|| Error::NotFoundErr(format!("column family {}", self.cf_name)))
+	}
+}
+
+impl KvStore for RocksStore {
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		self.env.db.get_cf(&self.cf()?, key).map_err(rocks_err)
+	}
+
+	fn iter(&self, prefix: &[u8]) -> Result<KvIter, Error> {
+		let snapshot = self.env.db.snapshot();
+		// SAFETY: the snapshot borrows the database that is owned by the env, the iterator
+		// keeps the env and drops the snapshot first
+		let snapshot = unsafe { std::mem::transmute::<Snapshot<'_>, Snapshot<'static>>(snapshot) };
+		let mut iter = RocksIter {
+			snapshot,
+			env: self.env.clone(),
+			cf_name: self.cf_name.clone(),
+			prefix: prefix.to_vec(),
+			next_key: Some(prefix.to_vec()),
+			chunk: Vec::new().into_iter(),
+		};
+		// the failure of the first read goes to the caller
+		iter.chunk = iter.read_chunk()?.into_iter();
+		Ok(Box::new(iter))
+	}
+
+	fn read_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		Ok(Box::new(RocksTxn {
+			store: self,
+			kind: TxnKind::Read(self.env.db.snapshot()),
+			changes: RefCell::new(BTreeMap::new()),
+		}))
+	}
+
+	fn write_txn(&self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		Ok(Box::new(RocksTxn {
+			store: self,
+			kind: TxnKind::Write(self.env.write_lock.lock()),
+			changes: RefCell::new(BTreeMap::new()),
+		}))
+	}
+
+	fn copy_to(&self, path: &str) -> Result<(), Error> {
+		// checkpoint creates the directory itself
+		if Path::new(path).exists() {
+			fs::remove_dir(path).map_err(|e| {
+				Error::FileErr(format!("Unable to use {} for the copy, {}", path, e))
+			})?;
+		}
+		Checkpoint::new(&self.env.db)
+			.and_then(|c| c.create_checkpoint(path))
+			.map_err(rocks_err)
+	}
+}
+
+enum TxnKind<'a> {
+	Read(Snapshot<'a>),
+	Write(MutexGuard<'a, ()>),
+	Child(&'a RocksTxn<'a>),
+}
+
+// Pending changes, None is a deleted key
+type Changes = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+struct RocksTxn<'a> {
+	store: &'a RocksStore,
+	kind: TxnKind<'a>,
+	changes: RefCell<Changes>,
+}
+
+impl<'a> RocksTxn<'a> {
+	fn pending(&self, key: &[u8]) -> Option<Option<Vec<u8>>> {
+		if let Some(v) = self.changes.borrow().get(key) {
+			return Some(v.clone());
+		}
+		match &self.kind {
+			TxnKind::Child(parent) => parent.pending(key),
+			_ => None,
+		}
+	}
+}
+
+impl<'a> KvTxn for RocksTxn<'a> {
+	fn is_write(&self) -> bool {
+		match self.kind {
+			TxnKind::Read(_) => false,
+			_ => true,
+		}
+	}
+
+	fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		match &self.kind {
+			TxnKind::Read(snapshot) => snapshot.get_cf(&self.store.cf()?, key).map_err(rocks_err),
+			_ => match self.pending(key) {
+				Some(v) => Ok(v),
+				None => self.store.get(key),
+			},
+		}
+	}
+
+	fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+		if !self.is_write() {
+			return Err(kv::read_batch_err());
+		}
+		self.changes
+			.borrow_mut()
+			.insert(key.to_vec(), Some(value.to_vec()));
+		Ok(())
+	}
+
+	fn delete(&self, key: &[u8]) -> Result<(), Error> {
+		if !self.is_write() {
+			return Err(kv::read_batch_err());
+		}
+		self.changes.borrow_mut().insert(key.to_vec(), None);
+		Ok(())
+	}
+
+	fn child(&mut self) -> Result<Box<dyn KvTxn + '_>, Error> {
+		if !self.is_write() {
+			return Err(kv::read_batch_err());
+		}
+		Ok(Box::new(RocksTxn {
+			store: self.store,
+			kind: TxnKind::Child(self),
+			changes: RefCell::new(BTreeMap::new()),
+		}))
+	}
+
+	fn commit(self: Box<Self>) -> Result<(), Error> {
+		let changes = self.changes.into_inner();
+		match self.kind {
+			TxnKind::Read(_) => Err(kv::read_batch_err()),
+			TxnKind::Child(parent) => {
+				parent.changes.borrow_mut().extend(changes);
+				Ok(())
+			}
+			TxnKind::Write(_guard) => {
+				let cf = self.store.cf()?;
+				let mut batch = WriteBatch::default();
+				for (k, v) in changes {
+					match v {
+						Some(v) => batch.put_cf(&cf, k, v),
+						None => batch.delete_cf(&cf, k),
+					}
+				}
+				self.store.env.db.write(batch).map_err(rocks_err)
+			}
+		}
+	}
+}
+
+/// Prefix iterator. RocksDB iterators borrow the database, so the entries are read
+/// by chunks, every chunk from a fresh iterator positioned after the last key. All the
+/// chunks are read from the snapshot that was taken when the iterator was created.
+struct RocksIter {
+	// must be dropped before the env
+	snapshot: Snapshot<'static>,
+	env: Arc<RocksEnv>,
+	cf_name: String,
+	prefix: Vec<u8>,
+	next_key: Option<Vec<u8>>,
+	chunk: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+}
+
+impl RocksIter {
+	fn read_chunk(&mut self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Error> {
+		let start = match self.next_key.take() {
+			Some(k) => k,
+			None => return Ok(vec![]),
+		};
+		let cf = self
+			.env
+			.db
+			.cf_handle(&self.cf_name)
+			.ok_or_else(|| Error::NotFoundErr(format!("column family {}", self.cf_name)))?;
+		let mut res = Vec::with_capacity(ITER_CHUNK_SIZE);
+		let iter = self.snapshot.iterator_cf_opt(
+			&cf,
+			ReadOptions::default(),
+			IteratorMode::From(&start, Direction::Forward),
+		);
+		for item in iter {
+			let (k, v) = item.map_err(rocks_err)?;
+			if !k.starts_with(&self.prefix) {
+				return Ok(res);
+			}
+			res.push((k.to_vec(), v.to_vec()));
+			if res.len() == ITER_CHUNK_SIZE {
+				// the smallest key after the last one
+				let mut next = k.to_vec();
+				next.push(0);
+				self.next_key = Some(next);
+				return Ok(res);
+			}
+		}
+		Ok(res)
+	}
+}
+
+impl Iterator for RocksIter {
+	type Item = Result<(Vec<u8>, Vec<u8>), Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(kv) = self.chunk.next() {
+			return Some(Ok(kv));
+		}
+		match self.read_chunk() {
+			Ok(chunk) => {
+				self.chunk = chunk.into_iter();
+				self.chunk.next().map(Ok)
+			}
+			Err(e) => Some(Err(e)),
+		}
+	}
+}
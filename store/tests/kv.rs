@@ -0,0 +1,129 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core as core;
+use mwc_store as store;
+use mwc_util as util;
+
+use crate::core::global;
+use crate::store::StoreBackend;
+use std::fs;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	global::set_local_chain_type(global::ChainTypes::Mainnet);
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+}
+
+// Same expectations for every backend
+fn check_backend(test_dir: &str) -> Result<(), store::Error> {
+	let store = store::Store::new(test_dir, Some("kv"), Some("first"), None)?;
+	let other = store::Store::new(test_dir, Some("kv"), Some("second"), None)?;
+
+	let mut batch = store.batch_write()?;
+	batch.put(&[1, 1], &[1])?;
+	batch.put(&[1, 2], &[2])?;
+	batch.put(&[2, 1], &[3])?;
+	{
+		// abandoned child
+		let child = batch.child()?;
+		child.put(&[1, 3], &[4])?;
+		child.delete(&[1, 1])?;
+		assert!(child.exists(&[1, 3])?);
+		assert!(!child.exists(&[1, 1])?);
+	}
+	assert!(!batch.exists(&[1, 3])?);
+	assert!(batch.exists(&[1, 1])?);
+	{
+		let child = batch.child()?;
+		child.delete(&[1, 2])?;
+		child.commit()?;
+	}
+	assert!(!batch.exists(&[1, 2])?);
+	assert!(!store.exists(&[1, 1])?);
+	batch.commit()?;
+
+	assert!(store.exists(&[1, 1])?);
+	assert!(!store.exists(&[1, 2])?);
+	// databases of the same environment are separated
+	assert!(!other.exists(&[1, 1])?);
+
+	// read batch can't write
+	let batch = store.batch_read()?;
+	assert!(batch.exists(&[1, 1])?);
+	assert!(batch.put(&[1, 4], &[5]).is_err());
+	assert!(batch.commit().is_err());
+
+	// prefix iteration in the key order
+	let batch = store.batch_write()?;
+	for i in 0..2500u32 {
+		batch.put(&[&[3u8][..], &i.to_be_bytes()[..]].concat(), &[0])?;
+	}
+	batch.commit()?;
+	let keys: Vec<Vec<u8>> = store.iter(&[1], |k, _| Ok(k.to_vec()))?.collect();
+	assert_eq!(keys, vec![vec![1, 1]]);
+	assert_eq!(store.iter(&[3], |k, _| Ok(k.to_vec()))?.count(), 2500);
+	let last = store.iter(&[3], |k, _| Ok(k.to_vec()))?.last();
+	assert_eq!(
+		last,
+		Some([&[3u8][..], &2499u32.to_be_bytes()[..]].concat())
+	);
+
+	// the iterator doesn't see the changes that are committed while it is read
+	let mut iter = store.iter(&[3], |k, _| Ok(k.to_vec()))?;
+	assert_eq!(iter.next(), Some(vec![3, 0, 0, 0, 0]));
+	let batch = store.batch_write()?;
+	batch.delete(&[&[3u8][..], &2000u32.to_be_bytes()[..]].concat())?;
+	batch.put(&[&[3u8][..], &5000u32.to_be_bytes()[..]].concat(), &[0])?;
+	batch.commit()?;
+	let rest: Vec<Vec<u8>> = iter.by_ref().collect();
+	assert_eq!(rest.len(), 2499);
+	assert!(rest.contains(&[&[3u8][..], &2000u32.to_be_bytes()[..]].concat()));
+	assert_eq!(
+		rest.last(),
+		Some(&[&[3u8][..], &2499u32.to_be_bytes()[..]].concat())
+	);
+	assert!(iter.error().is_none());
+	Ok(())
+}
+
+#[test]
+fn test_kv_backends() -> Result<(), store::Error> {
+	let test_dir = "target/test_kv_backends";
+	setup(test_dir);
+
+	store::init_backend(StoreBackend::Lmdb)?;
+	check_backend(&format!("{}/lmdb", test_dir))?;
+
+	if cfg!(feature = "rocksdb") {
+		store::init_backend(StoreBackend::RocksDb)?;
+		check_backend(&format!("{}/rocksdb", test_dir))?;
+
+		// the data of the other backend is refused
+		assert!(store::Store::new(&format!("{}/lmdb", test_dir), Some("kv"), None, None).is_err());
+		store::init_backend(StoreBackend::Lmdb)?;
+		assert!(
+			store::Store::new(&format!("{}/rocksdb", test_dir), Some("kv"), None, None).is_err()
+		);
+	} else {
+		assert!(store::init_backend(StoreBackend::RocksDb).is_err());
+	}
+
+	clean_output_dir(test_dir);
+	Ok(())
+}
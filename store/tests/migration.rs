@@ -0,0 +1,142 @@
+// Copyright 2024 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mwc_core as core;
+use mwc_store as store;
+use mwc_util as util;
+
+use crate::core::global;
+use crate::store::migration::{self, Migration, MigrationOptions};
+use std::fs;
+use std::path::PathBuf;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	global::set_local_chain_type(global::ChainTypes::Mainnet);
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+}
+
+fn put(store: &store::Store, key: &[u8]) -> Result<(), store::Error> {
+	let batch = store.batch_write()?;
+	batch.put(key, &[1])?;
+	batch.commit()
+}
+
+fn add_first(store: &store::Store) -> Result<(), store::Error> {
+	put(store, b"first")
+}
+
+fn add_second(store: &store::Store) -> Result<(), store::Error> {
+	put(store, b"second")
+}
+
+fn fail(_store: &store::Store) -> Result<(), store::Error> {
+	Err(store::Error::OtherErr("broken migration".to_string()))
+}
+
+const MIGRATIONS: [Migration<store::Store>; 2] = [
+	Migration {
+		version: 1,
+		description: "first",
+		run: add_first,
+	},
+	Migration {
+		version: 2,
+		description: "second",
+		run: add_second,
+	},
+];
+
+#[test]
+fn test_migrations() -> Result<(), store::Error> {
+	let test_dir = "target/test_migrations";
+	setup(test_dir);
+
+	let store = store::Store::new(test_dir, Some("migrations"), None, None)?;
+	assert_eq!(migration::schema_version(&store)?, 0);
+
+	// dry run changes nothing
+	let options = MigrationOptions {
+		dry_run: true,
+		backup_dir: None,
+	};
+	let report = migration::run_migrations(&store, &store, &MIGRATIONS[..1], &options)?;
+	assert_eq!(report.pending.len(), 1);
+	assert_eq!(report.to_version, 0);
+	assert!(!store.exists(b"first")?);
+	assert_eq!(migration::schema_version(&store)?, 0);
+
+	// first migration is applied with the backup of the untouched store
+	let backup_dir = PathBuf::from(test_dir).join("backup");
+	let options = MigrationOptions {
+		dry_run: false,
+		backup_dir: Some(backup_dir.clone()),
+	};
+	let report = migration::run_migrations(&store, &store, &MIGRATIONS[..1], &options)?;
+	assert_eq!((report.from_version, report.to_version), (0, 1));
+	assert_eq!(report.backup, Some(backup_dir.join("lmdb_v0")));
+	assert!(backup_dir.join("lmdb_v0").join("data.mdb").is_file());
+	assert!(store.exists(b"first")?);
+
+	// only the new migration runs later
+	let report = migration::run_migrations(&store, &store, &MIGRATIONS, &Default::default())?;
+	assert_eq!(report.pending, vec![(2, "second".to_string())]);
+	assert_eq!(report.to_version, 2);
+	assert!(store.exists(b"second")?);
+
+	// nothing is pending
+	let report = migration::run_migrations(&store, &store, &MIGRATIONS, &Default::default())?;
+	assert!(report.pending.is_empty());
+
+	// store migrated by a newer node is refused
+	assert!(
+		migration::run_migrations(&store, &store, &MIGRATIONS[..1], &Default::default()).is_err()
+	);
+
+	// failed migration doesn't move the version
+	let broken = [
+		Migration {
+			version: 1,
+			description: "first",
+			run: add_first,
+		},
+		Migration {
+			version: 2,
+			description: "second",
+			run: add_second,
+		},
+		Migration {
+			version: 3,
+			description: "broken",
+			run: fail,
+		},
+	];
+	assert!(migration::run_migrations(&store, &store, &broken, &Default::default()).is_err());
+	assert_eq!(migration::schema_version(&store)?, 2);
+
+	// versions must be in order
+	let unordered = [Migration {
+		version: 2,
+		description: "second",
+		run: add_second,
+	}];
+	assert!(migration::run_migrations(&store, &store, &unordered, &Default::default()).is_err());
+
+	clean_output_dir(test_dir);
+	Ok(())
+}
@@ -13,13 +13,16 @@
 // limitations under the License.
 
 //! Logging wrapper to be used throughout all crates in the workspace
-use crate::Mutex;
+use crate::{Mutex, RwLock};
+use std::collections::BTreeMap;
 use std::ops::Deref;
 
 use backtrace::Backtrace;
 use std::{panic, thread};
 
-use log::{Level, Record};
+use chrono::SecondsFormat;
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Record};
 use log4rs::append::console::ConsoleAppender;
 use log4rs::append::file::FileAppender;
 use log4rs::append::rolling_file::{
@@ -31,7 +34,7 @@ use log4rs::append::Append;
 use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::encode::writer::simple::SimpleWriter;
-use log4rs::encode::Encode;
+use log4rs::encode::{self, Encode};
 use log4rs::filter::{threshold::ThresholdFilter, Filter, Response};
 use std::sync::mpsc;
 use std::sync::mpsc::SyncSender;
@@ -44,6 +47,13 @@ lazy_static! {
 	static ref TUI_RUNNING: Mutex<bool> = Mutex::new(false);
 	/// Static Logging configuration, should only be set once, before first logging call
 	static ref LOGGING_CONFIG: Mutex<LoggingConfig> = Mutex::new(LoggingConfig::default());
+	/// Log levels of the modules, they can be changed at runtime
+	static ref MODULE_LOG_LEVELS: RwLock<BTreeMap<String, LevelFilter>> = RwLock::new(BTreeMap::new());
+	/// Most verbose level of the stdout and file appenders
+	static ref BASE_LOG_LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+	/// Levels of the stdout (or TUI) and file appenders, they can be changed at runtime
+	static ref STDOUT_LOG_LEVEL: RwLock<LevelFilter> = RwLock::new(LevelFilter::Warn);
+	static ref FILE_LOG_LEVEL: RwLock<LevelFilter> = RwLock::new(LevelFilter::Info);
 }
 
 const LOGGING_PATTERN: &str = "{d(%Y%m%d %H:%M:%S%.3f)} {h({l})} {M} - {m}{n}";
@@ -81,6 +91,12 @@ pub struct LoggingConfig {
 	pub log_max_files: Option<u32>,
 	/// Whether the tui is running (optional)
 	pub tui_running: Option<bool>,
+	/// Whether to write the log records as JSON lines (optional)
+	#[serde(default)]
+	pub log_json: Option<bool>,
+	/// Log levels of the modules, they are used instead of the stdout and file levels (optional)
+	#[serde(default)]
+	pub module_log_levels: Option<BTreeMap<String, LevelFilter>>,
 }
 
 impl Default for LoggingConfig {
@@ -95,6 +111,8 @@ impl Default for LoggingConfig {
 			log_max_size: Some(1024 * 1024 * 16), // 16 megabytes default
 			log_max_files: Some(DEFAULT_ROTATE_LOG_FILES),
 			tui_running: None,
+			log_json: Some(false),
+			module_log_levels: None,
 		}
 	}
 }
@@ -117,6 +135,165 @@ impl Filter for MwcFilter {
 	}
 }
 
+/// Level threshold of the appender. Records of the modules with the configured level
+/// are checked against that level instead.
+#[derive(Debug)]
+struct ModuleLevelFilter {
+	level: &'static RwLock<LevelFilter>,
+}
+
+impl ModuleLevelFilter {
+	fn new(level: &'static RwLock<LevelFilter>) -> ModuleLevelFilter {
+		ModuleLevelFilter { level }
+	}
+}
+
+impl Filter for ModuleLevelFilter {
+	fn filter(&self, record: &Record<'_>) -> Response {
+		let level = record
+			.module_path()
+			.and_then(module_log_level)
+			.unwrap_or_else(|| *self.level.read());
+		if record.level() <= level {
+			Response::Neutral
+		} else {
+			Response::Reject
+		}
+	}
+}
+
+// The longest configured module prefix wins, "mwc_p2p" covers "mwc_p2p::peer"
+fn module_log_level(module_path: &str) -> Option<LevelFilter> {
+	let levels = MODULE_LOG_LEVELS.read();
+	levels
+		.iter()
+		.filter(|(module, _)| {
+			module_path.starts_with(module.as_str())
+				&& (module_path.len() == module.len()
+					|| module_path[module.len()..].starts_with("::"))
+		})
+		.max_by_key(|(module, _)| module.len())
+		.map(|(_, level)| *level)
+}
+
+/// Current log levels of the modules
+pub fn module_log_levels() -> BTreeMap<String, LevelFilter> {
+	MODULE_LOG_LEVELS.read().clone()
+}
+
+/// Set the log level of the module at runtime, None restores the stdout and file levels
+/// for it. Submodules are covered as well.
+pub fn set_module_log_level(module: &str, level: Option<LevelFilter>) {
+	{
+		let mut levels = MODULE_LOG_LEVELS.write();
+		match level {
+			Some(level) => levels.insert(module.to_string(), level),
+			None => levels.remove(module),
+		};
+	}
+	update_max_level();
+}
+
+/// Replace the stdout, file and module log levels at runtime, the appenders are not
+/// rebuilt. Used by the config reload.
+pub fn update_log_levels(
+	stdout_level: Level,
+	file_level: Level,
+	modules: BTreeMap<String, LevelFilter>,
+) {
+	{
+		let mut config = LOGGING_CONFIG.lock();
+		config.stdout_log_level = stdout_level;
+		config.file_log_level = file_level;
+		config.module_log_levels = Some(modules.clone());
+	}
+	*STDOUT_LOG_LEVEL.write() = stdout_level.to_level_filter();
+	*FILE_LOG_LEVEL.write() = file_level.to_level_filter();
+	*BASE_LOG_LEVEL.lock() = stdout_level
+		.to_level_filter()
+		.max(file_level.to_level_filter());
+	*MODULE_LOG_LEVELS.write() = modules;
+	update_max_level();
+}
+
+/// Current logging configuration
+pub fn logging_config() -> LoggingConfig {
+	LOGGING_CONFIG.lock().clone()
+}
+
+// Records above the max level are skipped by the log macros, it must cover the modules
+fn update_max_level() {
+	let base = *BASE_LOG_LEVEL.lock();
+	let modules = MODULE_LOG_LEVELS
+		.read()
+		.values()
+		.max()
+		.cloned()
+		.unwrap_or(LevelFilter::Off);
+	log::set_max_level(base.max(modules));
+}
+
+/// Writes the record as a single line JSON object for the log pipelines. The key-values
+/// of the record (peer, height, event) are added as the fields.
+#[derive(Debug)]
+struct JsonEncoder;
+
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs, 'a> VisitSource<'kvs> for JsonFields<'a> {
+	fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+		let value = if let Some(v) = value.to_u64() {
+			serde_json::Value::from(v)
+		} else if let Some(v) = value.to_i64() {
+			serde_json::Value::from(v)
+		} else if let Some(v) = value.to_bool() {
+			serde_json::Value::from(v)
+		} else {
+			serde_json::Value::from(value.to_string())
+		};
+		self.0.insert(key.as_str().to_string(), value);
+		Ok(())
+	}
+}
+
+impl Encode for JsonEncoder {
+	fn encode(&self, w: &mut dyn encode::Write, record: &Record<'_>) -> anyhow::Result<()> {
+		let mut fields = serde_json::Map::new();
+		fields.insert(
+			"time".to_string(),
+			chrono::Utc::now()
+				.to_rfc3339_opts(SecondsFormat::Millis, true)
+				.into(),
+		);
+		fields.insert("level".to_string(), record.level().as_str().into());
+		fields.insert(
+			"module".to_string(),
+			record.module_path().unwrap_or("").into(),
+		);
+		fields.insert(
+			"thread".to_string(),
+			thread::current().name().unwrap_or("unnamed").into(),
+		);
+		fields.insert("message".to_string(), record.args().to_string().into());
+		record
+			.key_values()
+			.visit(&mut JsonFields(&mut fields))
+			.map_err(|e| anyhow::anyhow!("Unable to encode log record fields, {}", e))?;
+		let mut line = serde_json::to_string(&fields)?;
+		line.push('\n');
+		w.write_all(line.as_bytes())?;
+		Ok(())
+	}
+}
+
+fn log_encoder(json: bool) -> Box<dyn Encode> {
+	if json {
+		Box::new(JsonEncoder)
+	} else {
+		Box::new(PatternEncoder::new(&LOGGING_PATTERN))
+	}
+}
+
 #[derive(Debug)]
 struct ChannelAppender {
 	output: Mutex<SyncSender<LogEntry>>,
@@ -166,9 +343,15 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 			level_file
 		};
 
+		let json = c.log_json.unwrap_or(false);
+		*MODULE_LOG_LEVELS.write() = c.module_log_levels.clone().unwrap_or_default();
+		*BASE_LOG_LEVEL.lock() = level_minimum;
+		*STDOUT_LOG_LEVEL.write() = level_stdout;
+		*FILE_LOG_LEVEL.write() = level_file;
+
 		// Start logger
 		let stdout = ConsoleAppender::builder()
-			.encoder(Box::new(PatternEncoder::new(&LOGGING_PATTERN)))
+			.encoder(log_encoder(json))
 			.build();
 
 		let mut root = Root::builder();
@@ -176,6 +359,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 		let mut appenders = vec![];
 
 		if tui_running {
+			// TUI shows the plain text records
 			let channel_appender = ChannelAppender {
 				encoder: Box::new(PatternEncoder::new(&LOGGING_PATTERN)),
 				output: Mutex::new(logs_tx.unwrap()),
@@ -183,7 +367,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 
 			appenders.push(
 				Appender::builder()
-					.filter(Box::new(ThresholdFilter::new(level_stdout)))
+					.filter(Box::new(ModuleLevelFilter::new(&STDOUT_LOG_LEVEL)))
 					.filter(Box::new(MwcFilter))
 					.build("tui", Box::new(channel_appender)),
 			);
@@ -191,7 +375,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 		} else if c.log_to_stdout {
 			appenders.push(
 				Appender::builder()
-					.filter(Box::new(ThresholdFilter::new(level_stdout)))
+					.filter(Box::new(ModuleLevelFilter::new(&STDOUT_LOG_LEVEL)))
 					.filter(Box::new(MwcFilter))
 					.build("stdout", Box::new(stdout)),
 			);
@@ -201,7 +385,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 		if c.log_to_file {
 			// If maximum log size is specified, use rolling file appender
 			// or use basic one otherwise
-			let filter = Box::new(ThresholdFilter::new(level_file));
+			let filter = Box::new(ModuleLevelFilter::new(&FILE_LOG_LEVEL));
 			let file: Box<dyn Append> = {
 				if let Some(size) = c.log_max_size {
 					let count = c.log_max_files.unwrap_or_else(|| DEFAULT_ROTATE_LOG_FILES);
@@ -215,7 +399,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 					Box::new(
 						RollingFileAppender::builder()
 							.append(c.log_file_append)
-							.encoder(Box::new(PatternEncoder::new(&LOGGING_PATTERN)))
+							.encoder(log_encoder(json))
 							.build(c.log_file_path, Box::new(policy))
 							.expect("Failed to create logfile"),
 					)
@@ -223,7 +407,7 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 					Box::new(
 						FileAppender::builder()
 							.append(c.log_file_append)
-							.encoder(Box::new(PatternEncoder::new(&LOGGING_PATTERN)))
+							.encoder(log_encoder(json))
 							.build(c.log_file_path)
 							.expect("Failed to create logfile"),
 					)
@@ -239,12 +423,14 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 			root = root.appender("file");
 		}
 
+		// Root passes everything, the appenders filter by the stdout, file and module levels
 		let config = Config::builder()
 			.appenders(appenders)
-			.build(root.build(level_minimum))
+			.build(root.build(LevelFilter::Trace))
 			.unwrap();
 
 		let _ = log4rs::init_config(config).unwrap();
+		update_max_level();
 
 		info!(
 			"log4rs is initialized, file level: {:?}, stdout level: {:?}, min. level: {:?}",